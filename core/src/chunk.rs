@@ -37,6 +37,25 @@ impl Chunk {
         a.meta.id == b.meta.id && a.chunk[1] == b.chunk[1]
     }
 
+    /// split bytes into chunks, each chunk contains at most `chunk_size` bytes
+    pub fn split(bytes: &Bytes, chunk_size: usize) -> Vec<Chunk> {
+        let chunks: Vec<Bytes> = bytes
+            .chunks(chunk_size)
+            .map(|c| c.to_vec().into())
+            .collect();
+        let chunks_len: usize = chunks.len();
+        let meta = ChunkMeta::default();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, data)| Chunk {
+                meta,
+                chunk: [i, chunks_len],
+                data,
+            })
+            .collect()
+    }
+
     /// serelize chunk to bytes
     pub fn to_bincode(&self) -> Result<Bytes> {
         bincode::serialize(self)
@@ -180,20 +199,7 @@ impl<const MTU: usize> IntoIterator for ChunkList<MTU> {
 
 impl<const MTU: usize> From<&Bytes> for ChunkList<MTU> {
     fn from(bytes: &Bytes) -> Self {
-        let chunks: Vec<Bytes> = bytes.chunks(MTU).map(|c| c.to_vec().into()).collect();
-        let chunks_len: usize = chunks.len();
-        let meta = ChunkMeta::default();
-        Self(
-            chunks
-                .into_iter()
-                .enumerate()
-                .map(|(i, data)| Chunk {
-                    meta,
-                    chunk: [i, chunks_len],
-                    data,
-                })
-                .collect::<Vec<Chunk>>(),
-        )
+        Self(Chunk::split(bytes, MTU))
     }
 }
 
@@ -290,6 +296,17 @@ mod test {
         assert_eq!(ret[ret.len() - 1].chunk, [319, 320]);
     }
 
+    #[test]
+    fn test_split_with_chunk_size() {
+        let data: Bytes = "helloworld".repeat(1024).into();
+        let ret = Chunk::split(&data, 100);
+        assert_eq!(ret.len(), 103);
+        assert_eq!(ret[102].chunk, [102, 103]);
+        assert_eq!(ret[102].data.len(), 40);
+        assert!(ret.iter().all(|c| c.meta.id == ret[0].meta.id));
+        assert_eq!(ChunkList::<100>::from(ret).try_withdraw().unwrap(), data);
+    }
+
     #[test]
     fn test_withdraw() {
         let data = "helloworld".repeat(1024).into();
@@ -3,8 +3,30 @@
 /// default ttl in ms
 pub const DEFAULT_TTL_MS: usize = 300 * 1000;
 pub const MAX_TTL_MS: usize = DEFAULT_TTL_MS * 10;
+/// default count of hops a message can be forwarded, which is 2 * log2 of a ring of 65536 nodes
+pub const DEFAULT_HOP_LIMIT: u8 = 32;
+/// default count of transports in handshake a swarm holds, the oldest one is evicted when exceeded
+pub const DEFAULT_MAX_PENDING_TRANSPORTS: usize = 128;
+/// default count of offers a swarm generates concurrently, excess ones wait for a slot
+pub const DEFAULT_MAX_CONCURRENT_OFFERS: usize = 16;
 pub const TS_OFFSET_TOLERANCE_MS: u128 = 3000;
+/// default tolerance in ms of clock skew between the authorizer of a session and local node
+pub const DEFAULT_SESSION_CLOCK_SKEW_MS: u128 = TS_OFFSET_TOLERANCE_MS;
 pub const DEFAULT_SESSION_TTL_MS: usize = 30 * 24 * 3600 * 1000;
+pub const DEFAULT_SESSION_MAX_TTL_MS: usize = 30 * 24 * 3600 * 1000;
 pub const TRANSPORT_MTU: usize = 60000;
 pub const TRANSPORT_MAX_SIZE: usize = TRANSPORT_MTU * 16;
+/// default bytes buffered in data channel above which sending is held back
+pub const DEFAULT_SEND_HIGH_WATERMARK: usize = TRANSPORT_MAX_SIZE * 4;
+/// default bytes buffered in data channel below which held back sending is resumed
+pub const DEFAULT_SEND_LOW_WATERMARK: usize = TRANSPORT_MAX_SIZE;
+/// interval of checking buffered amount of data channel when sending is held back
+pub const SEND_QUEUE_POLL_INTERVAL_MS: u64 = 10;
+/// bytes buffered in data channel below which chunks queued in priority lanes are sent
+pub const SEND_LANES_THRESHOLD: usize = TRANSPORT_MTU * 4;
 pub const VNODE_DATA_MAX_LEN: usize = 1024;
+pub const DEFAULT_REPLAY_CACHE_CAPACITY: usize = 10000;
+/// default count of fetched virtual nodes cached, the least recently used one is evicted when exceeded
+pub const DEFAULT_VNODE_CACHE_CAPACITY: usize = 10000;
+/// default time in ms a fetched virtual node is cached, it should be fetched again after that
+pub const DEFAULT_VNODE_CACHE_TTL_MS: u64 = 60 * 1000;
@@ -0,0 +1,213 @@
+#![warn(missing_docs)]
+//! A bounded cache of virtual nodes fetched from DHT.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::consts::DEFAULT_VNODE_CACHE_CAPACITY;
+use crate::consts::DEFAULT_VNODE_CACHE_TTL_MS;
+use crate::dht::vnode::VirtualNode;
+use crate::dht::Did;
+use crate::utils::get_epoch_ms;
+
+struct CacheEntry {
+    vnode: VirtualNode,
+    cached_at: u128,
+    /// tick of the last access, the least recently used entry has the min one
+    used: u64,
+}
+
+#[derive(Default)]
+struct VNodeCacheInner {
+    entries: HashMap<Did, CacheEntry>,
+    tick: u64,
+}
+
+/// VNodeCache keeps fetched virtual nodes until they are expired or evicted.
+///
+/// An entry cached longer than `ttl_ms` is expired, it's not returned and should be fetched again.
+/// When the cache is larger than `capacity`, expired entries are dropped first,
+/// and then the least recently used ones.
+pub struct VNodeCache {
+    capacity: usize,
+    ttl_ms: Option<u128>,
+    inner: Mutex<VNodeCacheInner>,
+}
+
+impl Default for VNodeCache {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_VNODE_CACHE_CAPACITY,
+            Some(DEFAULT_VNODE_CACHE_TTL_MS),
+        )
+    }
+}
+
+impl VNodeCache {
+    /// Create a VNodeCache with capacity and ttl in milliseconds, entries never expire if ttl is `None`.
+    pub fn new(capacity: usize, ttl_ms: Option<u64>) -> Self {
+        Self {
+            capacity,
+            ttl_ms: ttl_ms.map(|ttl| ttl as u128),
+            inner: Mutex::new(VNodeCacheInner::default()),
+        }
+    }
+
+    /// Cache a vnode, replacing the one with the same did.
+    pub fn set(&self, vnode: VirtualNode) {
+        self.set_at(vnode, get_epoch_ms())
+    }
+
+    /// Get an unexpired vnode.
+    pub fn get(&self, vid: Did) -> Option<VirtualNode> {
+        self.get_at(vid, get_epoch_ms())
+    }
+
+    /// Drop the cached vnode of `vid`, return it if it's not expired.
+    pub fn remove(&self, vid: Did) -> Option<VirtualNode> {
+        let now = get_epoch_ms();
+        let entry = self.inner.lock().ok()?.entries.remove(&vid)?;
+        (!self.is_expired(&entry, now)).then_some(entry.vnode)
+    }
+
+    /// Number of entries in cache, including expired ones not dropped yet.
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|i| i.entries.len()).unwrap_or(0)
+    }
+
+    /// Check cache is empty or not.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Unexpired vnodes with their dids.
+    pub fn items(&self) -> Vec<(Did, VirtualNode)> {
+        let now = get_epoch_ms();
+        let Ok(inner) = self.inner.lock() else {
+            return vec![];
+        };
+        inner
+            .entries
+            .iter()
+            .filter(|(_, e)| !self.is_expired(e, now))
+            .map(|(did, e)| (*did, e.vnode.clone()))
+            .collect()
+    }
+
+    fn is_expired(&self, entry: &CacheEntry, now: u128) -> bool {
+        self.ttl_ms
+            .map_or(false, |ttl| now >= entry.cached_at.saturating_add(ttl))
+    }
+
+    fn set_at(&self, vnode: VirtualNode, now: u128) {
+        let Ok(mut guard) = self.inner.lock() else {
+            return;
+        };
+        let inner = &mut *guard;
+        inner.tick += 1;
+        inner.entries.insert(vnode.did, CacheEntry {
+            vnode,
+            cached_at: now,
+            used: inner.tick,
+        });
+        if inner.entries.len() <= self.capacity {
+            return;
+        }
+        inner.entries.retain(|_, e| !self.is_expired(e, now));
+        while inner.entries.len() > self.capacity {
+            let Some(lru) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(did, _)| *did)
+            else {
+                break;
+            };
+            inner.entries.remove(&lru);
+        }
+    }
+
+    fn get_at(&self, vid: Did, now: u128) -> Option<VirtualNode> {
+        let mut guard = self.inner.lock().ok()?;
+        let inner = &mut *guard;
+        let expired = self.is_expired(inner.entries.get(&vid)?, now);
+        if expired {
+            inner.entries.remove(&vid);
+            return None;
+        }
+        inner.tick += 1;
+        let entry = inner.entries.get_mut(&vid)?;
+        entry.used = inner.tick;
+        Some(entry.vnode.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vnode(topic: &str) -> VirtualNode {
+        topic.to_string().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_vnode_cache_expire() {
+        let cache = VNodeCache::new(10, Some(1000));
+        let v = vnode("test_vnode_cache_expire");
+        cache.set_at(v.clone(), 0);
+        assert_eq!(cache.get_at(v.did, 999), Some(v.clone()));
+        assert_eq!(cache.get_at(v.did, 1000), None);
+        assert!(cache.is_empty());
+
+        // A refetched vnode is cached again.
+        cache.set_at(v.clone(), 1000);
+        assert_eq!(cache.get_at(v.did, 1500), Some(v));
+    }
+
+    #[test]
+    fn test_vnode_cache_evict_lru() {
+        let cache = VNodeCache::new(2, None);
+        let vs = (0..3)
+            .map(|i| vnode(&format!("test_vnode_cache_evict_lru{}", i)))
+            .collect::<Vec<_>>();
+
+        cache.set_at(vs[0].clone(), 0);
+        cache.set_at(vs[1].clone(), 0);
+        // vs[0] is used more recently than vs[1].
+        assert!(cache.get_at(vs[0].did, 0).is_some());
+        cache.set_at(vs[2].clone(), 0);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get_at(vs[0].did, 0).is_some());
+        assert!(cache.get_at(vs[1].did, 0).is_none());
+        assert!(cache.get_at(vs[2].did, 0).is_some());
+    }
+
+    #[test]
+    fn test_vnode_cache_evict_expired_first() {
+        let cache = VNodeCache::new(2, Some(1000));
+        let vs = (0..3)
+            .map(|i| vnode(&format!("test_vnode_cache_evict_expired_first{}", i)))
+            .collect::<Vec<_>>();
+
+        cache.set_at(vs[0].clone(), 1000);
+        cache.set_at(vs[1].clone(), 0);
+        assert!(cache.get_at(vs[1].did, 500).is_some());
+        cache.set_at(vs[2].clone(), 1500);
+
+        // vs[1] is expired, though it's used more recently than vs[0].
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get_at(vs[0].did, 1500).is_some());
+        assert!(cache.get_at(vs[2].did, 1500).is_some());
+    }
+
+    #[test]
+    fn test_vnode_cache_remove() {
+        let cache = VNodeCache::new(10, None);
+        let v = vnode("test_vnode_cache_remove");
+        cache.set(v.clone());
+        assert_eq!(cache.remove(v.did), Some(v.clone()));
+        assert!(cache.get(v.did).is_none());
+        assert!(cache.remove(v.did).is_none());
+    }
+}
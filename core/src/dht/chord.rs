@@ -9,6 +9,7 @@ use num_bigint::BigUint;
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::cache::VNodeCache;
 use super::did::BiasId;
 use super::successor::SuccessorSeq;
 use super::types::Chord;
@@ -18,6 +19,7 @@ use super::types::ChordStorageSync;
 use super::types::CorrectChord;
 use super::vnode::VNodeOperation;
 use super::vnode::VirtualNode;
+use super::vnode::VirtualNodeRange;
 use super::FingerTable;
 use crate::dht::Did;
 use crate::dht::LiveDid;
@@ -50,7 +52,9 @@ pub struct PeerRing {
     /// Local storage for [ChordStorage].
     pub storage: Arc<PersistenceStorage>,
     /// Local cache for [ChordStorage].
-    pub cache: Arc<MemStorage<Did, VirtualNode>>,
+    pub cache: Arc<VNodeCache>,
+    /// Local cache of ranges fetched from [ChordStorage].
+    pub range_cache: Arc<MemStorage<Did, VirtualNodeRange>>,
 }
 
 /// Type alias is just for making the code easy to read.
@@ -191,7 +195,8 @@ impl PeerRing {
             // for Eth address, it's 160
             finger: Arc::new(Mutex::new(FingerTable::new(did, 160))),
             storage: Arc::new(storage),
-            cache: Arc::new(MemStorage::<Did, VirtualNode>::new()),
+            cache: Arc::new(VNodeCache::default()),
+            range_cache: Arc::new(MemStorage::<Did, VirtualNodeRange>::new()),
             did,
         }
     }
@@ -243,6 +248,12 @@ impl PeerRing {
     pub fn bias(&self, did: Did) -> BiasId {
         BiasId::new(self.did, did)
     }
+
+    /// The closest node preceding `target` in finger table, which a lookup of `target` is forwarded to.
+    /// Return did of current node if no finger precedes `target`.
+    pub fn closest_preceding_node(&self, target: Did) -> Result<Did> {
+        Ok(self.lock_finger()?.closest_predecessor(target))
+    }
 }
 
 impl Chord<PeerRingAction> for PeerRing {
@@ -498,12 +509,27 @@ impl ChordStorageSync<PeerRingAction> for PeerRing {
 impl ChordStorageCache<PeerRingAction> for PeerRing {
     /// Cache fetched `vnode` locally.
     fn local_cache_set(&self, vnode: VirtualNode) {
-        self.cache.set(&vnode.did.clone(), vnode);
+        self.cache.set(vnode);
     }
 
     /// Get vnode from local cache.
     fn local_cache_get(&self, vid: Did) -> Option<VirtualNode> {
-        self.cache.get(&vid)
+        self.cache.get(vid)
+    }
+
+    /// Drop vnode from local cache.
+    fn local_cache_remove(&self, vid: Did) -> Option<VirtualNode> {
+        self.cache.remove(vid)
+    }
+
+    /// Cache fetched `range` of vnode locally.
+    fn local_range_cache_set(&self, range: VirtualNodeRange) {
+        self.range_cache.set(&range.vnode.did.clone(), range);
+    }
+
+    /// Get range of vnode from local cache.
+    fn local_range_cache_get(&self, vid: Did) -> Option<VirtualNodeRange> {
+        self.range_cache.get(&vid)
     }
 }
 
@@ -814,6 +840,35 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_closest_preceding_node_wraparound() -> Result<()> {
+        let ring = BigUint::from(2u16).pow(160);
+        let top = Did::from(&ring - 100u32);
+        let low = Did::from(50u32);
+        let half = Did::from(BigUint::from(2u16).pow(159));
+
+        let db_path = PersistenceStorage::random_path("./tmp");
+        let db = PersistenceStorage::new_with_path(db_path.as_str())
+            .await
+            .unwrap();
+        let node = PeerRing::new_with_storage(top, 3, db);
+        assert_eq!(node.closest_preceding_node(low)?, top);
+
+        node.join(low)?;
+        node.join(half)?;
+        assert_eq!(top.distance(&low), BigUint::from(150u32));
+
+        // low precedes a target across the ring boundary.
+        assert_eq!(node.closest_preceding_node(Did::from(60u32))?, low);
+        // Nothing in finger table is between top and the target.
+        assert_eq!(node.closest_preceding_node(Did::from(40u32))?, top);
+        assert_eq!(node.closest_preceding_node(half + Did::from(1u32))?, half);
+        assert_eq!(node.closest_preceding_node(half)?, low);
+        tokio::fs::remove_dir_all("./tmp").await.ok();
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_two_node_finger() -> Result<()> {
         let mut key1 = SecretKey::random();
@@ -45,6 +45,8 @@ use serde::Deserialize;
 use serde::Serialize;
 use web3::contract::tokens::Tokenizable;
 use web3::types::H160;
+#[cfg(any(test, feature = "testing"))]
+use web3::types::U256;
 
 use crate::ecc::HashStr;
 use crate::error::Error;
@@ -178,6 +180,12 @@ impl Did {
         BiasId::new(did, *self)
     }
 
+    /// Clockwise distance from self to `other` on the ring, which is `other - self` mod 2^160.
+    /// The distance is not symmetric, `a.distance(&b)` is `2^160 - b.distance(&a)` unless `a == b`.
+    pub fn distance(&self, other: &Did) -> BigUint {
+        (*other - *self).into()
+    }
+
     /// Rotate Transport did to a list of affined did
     /// affine x, n = [x + rotate(360/n)]
     pub fn rotate_affine(&self, scalar: u16) -> Vec<Did> {
@@ -186,6 +194,27 @@ impl Did {
     }
 }
 
+/// Helpers to place dids precisely on the ring, for tests which need a controlled topology.
+#[cfg(any(test, feature = "testing"))]
+impl Did {
+    /// Parse a did from hex, with or without `0x` prefix.
+    /// Hex shorter than 40 digits is left padded by zeros, so `0x1` is the did next to zero.
+    pub fn from_hex(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.is_empty() || hex.len() > 40 {
+            return Err(Error::BadCHexInCache);
+        }
+        Self::from_str(&format!("0x{:0>40}", hex))
+    }
+
+    /// The did at `position` of the ring, which wraps around at 2^160.
+    pub fn at_position(position: U256) -> Self {
+        let mut bytes = [0u8; 32];
+        position.to_big_endian(&mut bytes);
+        Self::from(BigUint::from_bytes_be(&bytes))
+    }
+}
+
 /// Ordering with a did reference
 /// This trait defines necessary method for sorting based on did.
 pub trait SortRing {
@@ -290,6 +319,26 @@ mod tests {
         assert!(c > b && b > a);
     }
 
+    #[test]
+    fn test_did_at_position() {
+        assert_eq!(Did::from_hex("0x1").unwrap(), Did::from(1u32));
+        assert_eq!(
+            Did::from_hex("ff").unwrap(),
+            Did::at_position(U256::from(255))
+        );
+        assert_eq!(
+            Did::from_hex("0xc0ffee254729296a45a3885639AC7E10F9d54979").unwrap(),
+            Did::from_str("0xc0ffee254729296a45a3885639AC7E10F9d54979").unwrap()
+        );
+        assert!(Did::from_hex("0x").is_err());
+        assert!(Did::from_hex(&"f".repeat(41)).is_err());
+        assert!(Did::from_hex("0xzz").is_err());
+
+        let ring = U256::from(2).pow(U256::from(160));
+        assert_eq!(Did::at_position(ring + U256::one()), Did::from(1u32));
+        assert!(Did::at_position(U256::from(2)) > Did::at_position(U256::one()));
+    }
+
     #[test]
     fn test_finate_ring_neg() {
         let zero = Did::from_str("0x0000000000000000000000000000000000000000").unwrap();
@@ -315,6 +364,26 @@ mod tests {
         assert_eq!(v, vec![d, a, b, c]);
     }
 
+    #[test]
+    fn test_did_distance() {
+        let ring = BigUint::from(2u16).pow(160);
+        let a = Did::from(10u32);
+        let b = Did::from(25u32);
+        assert_eq!(a.distance(&b), BigUint::from(15u32));
+        assert_eq!(b.distance(&a), &ring - 15u32);
+        assert_eq!(a.distance(&a), BigUint::from(0u32));
+
+        // Wraparound at the ring boundary.
+        let top = Did::from(&ring - 10u32);
+        let bottom = Did::from(5u32);
+        assert_eq!(top.distance(&bottom), BigUint::from(15u32));
+        assert_eq!(bottom.distance(&top), &ring - 15u32);
+        assert_eq!(
+            Did::from(&ring - 1u32).distance(&Did::from(0u32)),
+            BigUint::from(1u32)
+        );
+    }
+
     #[test]
     fn rotate_transformation() {
         assert_eq!(Did::from(0u32), Did::from(BigUint::from(2u16).pow(160)));
@@ -5,6 +5,8 @@
 //! With high probability, the number of nodes that must be contacted to find a successor in an N-node network is O(log N).
 pub mod did;
 pub use did::Did;
+pub mod cache;
+pub use cache::VNodeCache;
 mod chord;
 pub use chord::TopoInfo;
 /// Finger table for Rings
@@ -24,7 +26,10 @@ pub use types::ChordStorageSync;
 pub use types::CorrectChord;
 pub use types::LiveDid;
 mod stabilization;
+pub use stabilization::NeighborObserver;
+pub use stabilization::NeighborObserverImpl;
 pub use stabilization::Stabilization;
+pub use stabilization::StabilizationConfig;
 pub use stabilization::TStabilize;
 /// Implement Subring with VNode
 pub mod subring;
@@ -1,4 +1,7 @@
 //! Stabilization wait to notify predecessors and update fingersTable.
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -6,14 +9,17 @@ use async_trait::async_trait;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::types::CorrectChord;
 use crate::dht::Chord;
+use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::dht::PeerRingRemoteAction;
+use crate::error::Error;
 use crate::error::Result;
 use crate::message::handlers::MessageHandlerEvent;
 use crate::message::FindSuccessorReportHandler;
 use crate::message::FindSuccessorSend;
 use crate::message::FindSuccessorThen;
+use crate::message::JoinDHT;
 use crate::message::Message;
 use crate::message::MessagePayload;
 use crate::message::NotifyPredecessorSend;
@@ -30,7 +36,24 @@ use crate::types::ice_transport::IceTransportInterface;
 pub struct Stabilization {
     chord: Arc<PeerRing>,
     swarm: Arc<Swarm>,
-    timeout: usize,
+    config: StabilizationConfig,
+    rounds: Arc<AtomicU64>,
+    stopped: Arc<AtomicBool>,
+    observer: Option<NeighborObserverImpl>,
+}
+
+/// Config of [Stabilization].
+/// - succ_len: max length of successor list. A longer list keeps the ring connected when
+///   more successors fail at the same time, and gives more replicas for vnodes, but each round
+///   of stabilization notifies every successor, and more transports should be kept alive.
+/// - interval_secs: delay between two rounds of stabilization. It's independent of succ_len,
+///   a fast-churning network may keep a short successor list but stabilize frequently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StabilizationConfig {
+    /// Max length of successor list.
+    pub succ_len: usize,
+    /// Delay between two rounds of stabilization, in seconds.
+    pub interval_secs: u64,
 }
 
 /// A trait with `wait` method.
@@ -41,6 +64,23 @@ pub trait TStabilize {
     async fn wait(self: Arc<Self>);
 }
 
+/// Observer of neighbors of node, notified when a round of [Stabilization] changes them,
+/// such as a successor is removed with its unavailable transport.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait NeighborObserver {
+    /// Called with the new successor list and predecessor, after either of them changed.
+    async fn neighbors_changed(&self, successors: Vec<Did>, predecessor: Option<Did>);
+}
+
+/// Type of NeighborObserver, see [NeighborObserver].
+#[cfg(not(feature = "wasm"))]
+pub type NeighborObserverImpl = Arc<dyn NeighborObserver + Send + Sync>;
+
+/// Type of NeighborObserver, see [NeighborObserver].
+#[cfg(feature = "wasm")]
+pub type NeighborObserverImpl = Arc<dyn NeighborObserver>;
+
 impl Stabilization {
     /// Clean unavailable transports from swarm.
     pub async fn clean_unavailable_transports(&self) -> Result<()> {
@@ -58,18 +98,79 @@ impl Stabilization {
 }
 
 impl Stabilization {
-    /// Create a new instance of Stabilization
+    /// Create a new instance of Stabilization, keep successor list length of dht.
+    /// The `timeout` is the interval between two rounds of stabilization, in seconds.
     pub fn new(swarm: Arc<Swarm>, timeout: usize) -> Self {
+        let chord = swarm.dht();
+        let config = StabilizationConfig {
+            succ_len: chord.successors().max_len().into(),
+            interval_secs: timeout as u64,
+        };
         Self {
-            chord: swarm.dht(),
+            chord,
             swarm,
-            timeout,
+            config,
+            rounds: Arc::new(AtomicU64::new(0)),
+            stopped: Arc::new(AtomicBool::new(false)),
+            observer: None,
         }
     }
 
-    /// Get timeout of waiting delays.
+    /// Create a new instance of Stabilization with config,
+    /// the successor list of dht will be resized to `config.succ_len`.
+    /// Return error if `config.succ_len` is 0 or larger than 255.
+    pub fn new_with_config(swarm: Arc<Swarm>, config: StabilizationConfig) -> Result<Self> {
+        let succ_len = u8::try_from(config.succ_len)
+            .ok()
+            .filter(|len| *len > 0)
+            .ok_or(Error::InvalidSuccessorLength(config.succ_len))?;
+        let chord = swarm.dht();
+        chord.successors().set_max_len(succ_len)?;
+        Ok(Self {
+            chord,
+            swarm,
+            config,
+            rounds: Arc::new(AtomicU64::new(0)),
+            stopped: Arc::new(AtomicBool::new(false)),
+            observer: None,
+        })
+    }
+
+    /// Notify observer when a round of stabilization changes successors or predecessor.
+    pub fn with_observer(mut self, observer: NeighborObserverImpl) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Get timeout of waiting delays, which is the interval between two rounds in seconds.
     pub fn get_timeout(&self) -> usize {
-        self.timeout
+        self.config.interval_secs as usize
+    }
+
+    /// Get the interval between two rounds in milliseconds, saturated to the max timeout of js timer.
+    #[cfg(feature = "wasm")]
+    fn interval_ms(&self) -> i32 {
+        i32::try_from(self.config.interval_secs.saturating_mul(1000)).unwrap_or(i32::MAX)
+    }
+
+    /// Get config of stabilization.
+    pub fn config(&self) -> StabilizationConfig {
+        self.config
+    }
+
+    /// Get the number of finished rounds of stabilization.
+    pub fn rounds(&self) -> u64 {
+        self.rounds.load(Ordering::SeqCst)
+    }
+
+    /// Stop stabilization, no more rounds will be started and `wait` will return.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if stabilization is stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
     }
 }
 
@@ -89,7 +190,7 @@ impl Stabilization {
                 tracing::info!("STABILIZATION notify_predecessor: {:?}", s);
                 let payload = MessagePayload::new_send(
                     msg.clone(),
-                    self.swarm.session_manager(),
+                    &self.swarm.session_manager(),
                     s,
                     self.swarm.did(),
                 )?;
@@ -101,6 +202,59 @@ impl Stabilization {
         }
     }
 
+    /// Leave the ring gracefully, this is a DHT operation.
+    /// The predecessor is asked to join our successor, and the successor is notified
+    /// that our predecessor is its new predecessor, both of them will drop this node.
+    /// So the ring can re-link without waiting for next round of stabilization.
+    /// Should be called before transports close.
+    pub async fn leave(&self) -> Result<()> {
+        let predecessor = { *self.chord.lock_predecessor()? };
+        let successor = {
+            let successor = self.chord.successors();
+            if successor.is_empty()? {
+                None
+            } else {
+                Some(successor.min()?)
+            }
+        };
+        let did = self.chord.did;
+
+        if let Some(succ) = successor.filter(|s| *s != did) {
+            // In a ring of two nodes, the only neighbor is both successor and predecessor.
+            // Ask it to join itself, so it just drops this node.
+            if predecessor == Some(succ) {
+                tracing::info!("STABILIZATION leave: ask {:?} to drop this node", succ);
+                let payload = MessagePayload::new_send(
+                    Message::JoinDHT(JoinDHT { did: succ }),
+                    &self.swarm.session_manager(),
+                    succ,
+                    succ,
+                )?;
+                self.swarm.send_payload(payload).await?;
+            } else if let Some(pred) = predecessor.filter(|p| *p != did) {
+                tracing::info!("STABILIZATION leave: ask {:?} to join {:?}", pred, succ);
+                let payload = MessagePayload::new_send(
+                    Message::JoinDHT(JoinDHT { did: succ }),
+                    &self.swarm.session_manager(),
+                    pred,
+                    pred,
+                )?;
+                self.swarm.send_payload(payload).await?;
+
+                tracing::info!("STABILIZATION leave: notify {:?} with {:?}", succ, pred);
+                let payload = MessagePayload::new_send(
+                    Message::NotifyPredecessorSend(NotifyPredecessorSend { did: pred }),
+                    &self.swarm.session_manager(),
+                    succ,
+                    succ,
+                )?;
+                self.swarm.send_payload(payload).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Fix fingers from finger table, this is a DHT operation.
     async fn fix_fingers(&self) -> Result<()> {
         match self.chord.fix_fingers() {
@@ -118,7 +272,7 @@ impl Stabilization {
                     });
                     let payload = MessagePayload::new_send(
                         msg.clone(),
-                        self.swarm.session_manager(),
+                        &self.swarm.session_manager(),
                         closest_predecessor,
                         closest_predecessor,
                     )?;
@@ -157,8 +311,17 @@ impl Stabilization {
 }
 
 impl Stabilization {
+    /// Successor list and predecessor of node.
+    fn neighbors(&self) -> Result<(Vec<Did>, Option<Did>)> {
+        let successors = self.chord.successors().list()?;
+        let predecessor = *self.chord.lock_predecessor()?;
+        Ok((successors, predecessor))
+    }
+
     /// Call stabilize periodly.
+    /// The observer is notified if successors or predecessor changed during the round.
     pub async fn stabilize(&self) -> Result<()> {
+        let neighbors = self.neighbors()?;
         tracing::debug!("STABILIZATION notify_predecessor start");
         if let Err(e) = self.notify_predecessor().await {
             tracing::error!("[stabilize] Failed on notify predecessor {:?}", e);
@@ -182,6 +345,14 @@ impl Stabilization {
             }
             tracing::debug!("STABILIZATION correct_stabilize end");
         }
+        if let Some(observer) = &self.observer {
+            let current = self.neighbors()?;
+            if current != neighbors {
+                let (successors, predecessor) = current;
+                observer.neighbors_changed(successors, predecessor).await;
+            }
+        }
+        self.rounds.fetch_add(1, Ordering::SeqCst);
         Ok(())
     }
 }
@@ -203,14 +374,19 @@ mod stabilizer {
     #[async_trait]
     impl TStabilize for Stabilization {
         async fn wait(self: Arc<Self>) {
-            loop {
-                let timeout = Delay::new(Duration::from_secs(self.timeout as u64)).fuse();
+            while !self.is_stopped() {
+                let timeout = Delay::new(Duration::from_secs(self.config.interval_secs)).fuse();
                 pin_mut!(timeout);
                 select! {
-                    _ = timeout => self
-                        .stabilize()
-                        .await
-                        .unwrap_or_else(|e| tracing::error!("failed to stabilize {:?}", e)),
+                    _ = timeout => {
+                        if self.is_stopped() {
+                            break;
+                        }
+                        self
+                            .stabilize()
+                            .await
+                            .unwrap_or_else(|e| tracing::error!("failed to stabilize {:?}", e))
+                    },
                 }
             }
         }
@@ -231,8 +407,12 @@ mod stabilizer {
     #[async_trait(?Send)]
     impl TStabilize for Stabilization {
         async fn wait(self: Arc<Self>) {
+            let interval_ms = self.interval_ms();
             let caller = Arc::clone(&self);
             let func = move || {
+                if caller.is_stopped() {
+                    return;
+                }
                 let caller = caller.clone();
                 spawn_local(Box::pin(async move {
                     caller
@@ -241,7 +421,7 @@ mod stabilizer {
                         .unwrap_or_else(|e| tracing::error!("failed to stabilize {:?}", e));
                 }))
             };
-            poll!(func, 25000);
+            poll!(func, interval_ms);
         }
     }
 }
@@ -322,4 +502,42 @@ pub mod tests {
         assert!(node1.get_transport(node2.did()).is_none());
         assert!(node1.get_transport(node3.did()).is_none());
     }
+
+    /// Record neighbors reported to observer.
+    #[derive(Default)]
+    struct NeighborRecorder {
+        changes: std::sync::Mutex<Vec<(Vec<Did>, Option<Did>)>>,
+    }
+
+    #[async_trait]
+    impl NeighborObserver for NeighborRecorder {
+        async fn neighbors_changed(&self, successors: Vec<Did>, predecessor: Option<Did>) {
+            self.changes.lock().unwrap().push((successors, predecessor));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stabilize_notify_neighbor_observer() {
+        let key1 = SecretKey::random();
+        let key2 = SecretKey::random();
+        let (node1, _) = prepare_node(key1).await;
+        let (node2, _) = prepare_node(key2).await;
+
+        manually_establish_connection(&node1, &node2).await.unwrap();
+        node1.dht().join(node2.did()).unwrap();
+        *node1.dht().lock_predecessor().unwrap() = Some(node2.did());
+
+        let recorder = Arc::new(NeighborRecorder::default());
+        let stb = Stabilization::new(node1.clone(), 3).with_observer(recorder.clone());
+
+        stb.stabilize().await.unwrap();
+        assert!(recorder.changes.lock().unwrap().is_empty());
+
+        node2.disconnect(node1.did()).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(10)).await;
+
+        // The unavailable transport is cleaned, so node2 is removed from neighbors.
+        stb.stabilize().await.unwrap();
+        assert_eq!(*recorder.changes.lock().unwrap(), vec![(vec![], None)]);
+    }
 }
@@ -6,6 +6,7 @@ use super::chord::TopoInfo;
 use super::did::Did;
 use super::vnode::VNodeOperation;
 use super::vnode::VirtualNode;
+use super::vnode::VirtualNodeRange;
 use crate::error::Result;
 
 /// Chord is a distributed hash table (DHT) algorithm that is designed to efficiently
@@ -89,6 +90,12 @@ pub trait ChordStorageCache<Action>: Chord<Action> {
     fn local_cache_set(&self, vnode: VirtualNode);
     /// Get local cache.
     fn local_cache_get(&self, vid: Did) -> Option<VirtualNode>;
+    /// Drop local cache, so that the next read waits for a fetched one.
+    fn local_cache_remove(&self, vid: Did) -> Option<VirtualNode>;
+    /// Cache fetched range of resource locally, the last fetched range of a resource is kept.
+    fn local_range_cache_set(&self, range: VirtualNodeRange);
+    /// Get local cache of range.
+    fn local_range_cache_get(&self, vid: Did) -> Option<VirtualNodeRange>;
 }
 
 /// Chord online correction that inspired by Pamela Zave's work.
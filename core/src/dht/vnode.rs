@@ -64,6 +64,17 @@ pub struct VirtualNode {
     pub kind: VNodeType,
 }
 
+/// A slice of data of a [VirtualNode], which is fetched by range instead of the whole node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualNodeRange {
+    /// The virtual node with data in range only.
+    pub vnode: VirtualNode,
+    /// Index of the first entry of data in the whole virtual node.
+    pub start: u64,
+    /// Count of entries of data in the whole virtual node.
+    pub total: u64,
+}
+
 impl VirtualNode {
     /// Generate did from topic.
     pub fn gen_did(topic: &str) -> Result<Did> {
@@ -166,6 +177,26 @@ impl VirtualNode {
         vnode
     }
 
+    /// Slice at most `count` entries of data from `start`.
+    pub fn range(&self, start: u64, count: u64) -> VirtualNodeRange {
+        let data = self
+            .data
+            .iter()
+            .skip(start as usize)
+            .take(count as usize)
+            .cloned()
+            .collect();
+        VirtualNodeRange {
+            vnode: VirtualNode {
+                did: self.did,
+                data,
+                kind: self.kind,
+            },
+            start,
+            total: self.data.len() as u64,
+        }
+    }
+
     /// The entry point of [VNodeOperation].
     /// Will dispatch to different operation handlers according to the variant.
     pub fn operate(&self, op: VNodeOperation) -> Result<Self> {
@@ -1,29 +1,47 @@
 //! BIP137 Signer
 
-use arrayref::array_mut_ref;
 use sha2::Digest;
 use sha2::Sha256;
 
 use crate::ecc::Address;
 use crate::ecc::PublicKey;
+use crate::ecc::SigBytes;
+use crate::error::Error;
 use crate::error::Result;
 
 /// recover pubkey according to signature.
+/// The signature is `header || r || s`, where header is `27 + recovery_id`.
+/// High-S signatures are rejected, since they are malleated form of low-S ones.
 pub fn recover(msg: &str, sig: impl AsRef<[u8]>) -> Result<PublicKey> {
-    let mut sig = sig.as_ref().to_vec();
-    sig.rotate_left(1);
-    let sig = sig.as_mut_slice();
-    let sig_byte = array_mut_ref![sig, 0, 65];
+    let sig = sig.as_ref();
+    if sig.len() != 65 {
+        return Err(Error::InvalidSignatureLength(sig.len(), 65));
+    }
+
+    let recovery_id = sig[0]
+        .checked_sub(27)
+        .filter(|id| *id <= 3)
+        .ok_or(Error::InvalidRecoveryId(sig[0]))?;
+
+    let rs: [u8; 64] = sig[1..].try_into()?;
+    let signature = libsecp256k1::Signature::parse_standard(&rs)
+        .map_err(|e| Error::Libsecp256k1SignatureParseStandard(e.to_string()))?;
+    if signature.s.is_high() {
+        return Err(Error::HighSSignature);
+    }
+
+    let mut sig_byte: SigBytes = [0u8; 65];
+    sig_byte[..64].copy_from_slice(&rs);
+    sig_byte[64] = recovery_id;
     let hash = self::magic_hash(msg);
-    sig_byte[64] -= 27;
-    crate::ecc::recover_hash(&hash, sig_byte)
+    crate::ecc::recover_hash(&hash, &sig_byte)
 }
 
 /// verify message signed by Ethereum address.
 pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>) -> bool {
     match recover(msg, sig.as_ref()) {
         Ok(recover_pk) => {
-            if recover_pk.address() == *address {
+            if super::constant_time_eq(recover_pk.address(), address) {
                 return true;
             }
             tracing::debug!(
@@ -45,38 +63,54 @@ pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>) -> bool {
     }
 }
 
-fn varint_buf_num(n: u64) -> Vec<u8> {
-    if n < 253 {
-        vec![n as u8]
-    } else if n < 0x10000 {
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&[253u8]);
-        buf.extend_from_slice(&(n as u16).to_le_bytes());
-        buf
-    } else if n < 0x100000000 {
-        let mut buf = Vec::new();
-        buf.extend_from_slice(&[254u8]);
-        buf.extend_from_slice(&(n as u32).to_le_bytes());
-        buf
-    } else {
-        let mut buf = vec![255u8, 0, 0, 0, 0, 0, 0, 0, 0];
-        buf[1..5].copy_from_slice(&n.to_le_bytes()[..4]);
-        buf[5..9].copy_from_slice(&((n >> 32) as u32).to_le_bytes()[..4]);
-        buf.truncate(1 + 8);
-        buf
+/// Bitcoin CompactSize encoding of an integer, which is at most 9 bytes.
+struct VarInt {
+    buf: [u8; 9],
+    len: usize,
+}
+
+impl AsRef<[u8]> for VarInt {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.len]
     }
 }
 
+/// Encode `n` as Bitcoin CompactSize:
+/// a single byte below 253, otherwise a 253/254/255 prefix with 2/4/8 little-endian bytes.
+fn varint_buf_num(n: u64) -> VarInt {
+    let mut buf = [0u8; 9];
+    let len = if n < 253 {
+        buf[0] = n as u8;
+        1
+    } else if n <= 0xFFFF {
+        buf[0] = 253;
+        buf[1..3].copy_from_slice(&(n as u16).to_le_bytes());
+        3
+    } else if n <= 0xFFFF_FFFF {
+        buf[0] = 254;
+        buf[1..5].copy_from_slice(&(n as u32).to_le_bytes());
+        5
+    } else {
+        buf[0] = 255;
+        buf[1..9].copy_from_slice(&n.to_le_bytes());
+        9
+    };
+    VarInt { buf, len }
+}
+
+/// Hash of message to be signed by BIP137, which is
+/// `sha256(sha256(varint(len(magic)) || magic || varint(len(msg)) || msg))`,
+/// where magic is `"Bitcoin Signed Message:\n"`.
 pub fn magic_hash(msg: &str) -> [u8; 32] {
     let magic_bytes = "Bitcoin Signed Message:\n".as_bytes();
     let msg_bytes = msg.as_bytes();
-    let mut buf = Vec::new();
-    buf.extend_from_slice(varint_buf_num(magic_bytes.len() as u64).as_slice());
-    buf.extend_from_slice(magic_bytes);
-    buf.extend_from_slice(varint_buf_num(msg_bytes.len() as u64).as_slice());
-    buf.extend_from_slice(msg_bytes);
-    let hash = Sha256::digest(Sha256::digest(&buf));
-    hash.into()
+    let hash = Sha256::new()
+        .chain_update(varint_buf_num(magic_bytes.len() as u64))
+        .chain_update(magic_bytes)
+        .chain_update(varint_buf_num(msg_bytes.len() as u64))
+        .chain_update(msg_bytes)
+        .finalize();
+    Sha256::digest(hash).into()
 }
 
 #[cfg(test)]
@@ -104,4 +138,98 @@ mod test {
         assert_eq!(pk, pubkey);
         assert_eq!(pk.address(), pubkey.address());
     }
+
+    fn sig_fixture() -> Vec<u8> {
+        vec![
+            27, 204, 122, 109, 87, 84, 60, 195, 135, 84, 231, 22, 77, 88, 215, 161, 77, 74, 181,
+            192, 19, 219, 188, 251, 142, 104, 2, 233, 132, 82, 171, 102, 125, 114, 45, 23, 202, 59,
+            86, 236, 76, 169, 164, 164, 179, 221, 206, 54, 32, 106, 81, 115, 217, 42, 93, 114, 131,
+            115, 128, 227, 45, 231, 30, 111, 34,
+        ]
+    }
+
+    #[test]
+    fn test_recover_truncated_signature() {
+        let sig = sig_fixture();
+        assert!(matches!(
+            self::recover("Hello World 42", &sig[..64]),
+            Err(Error::InvalidSignatureLength(64, 65))
+        ));
+        assert!(matches!(
+            self::recover("Hello World 42", []),
+            Err(Error::InvalidSignatureLength(0, 65))
+        ));
+    }
+
+    #[test]
+    fn test_recover_invalid_recovery_id() {
+        let mut sig = sig_fixture();
+        sig[0] = 26;
+        assert!(matches!(
+            self::recover("Hello World 42", &sig),
+            Err(Error::InvalidRecoveryId(26))
+        ));
+        sig[0] = 31;
+        assert!(matches!(
+            self::recover("Hello World 42", &sig),
+            Err(Error::InvalidRecoveryId(31))
+        ));
+    }
+
+    #[test]
+    fn test_varint_buf_num() {
+        let cases: &[(u64, &[u8])] = &[
+            (0, &[0x00]),
+            (252, &[0xfc]),
+            (253, &[0xfd, 0xfd, 0x00]),
+            (0xFFFF, &[0xfd, 0xff, 0xff]),
+            (0x10000, &[0xfe, 0x00, 0x00, 0x01, 0x00]),
+            (0xFFFFFFFF, &[0xfe, 0xff, 0xff, 0xff, 0xff]),
+            (0x100000000, &[
+                0xff, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            ]),
+            (0x0123456789abcdef, &[
+                0xff, 0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01,
+            ]),
+            (u64::MAX, &[0xff; 9]),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(varint_buf_num(*n).as_ref(), *expected, "varint of {:#x}", n);
+        }
+    }
+
+    #[test]
+    fn test_magic_hash_long_message() {
+        // The length of message over 252 bytes is prefixed by 0xfd and 2 bytes.
+        let msg = "a".repeat(300);
+        let mut buf = vec![24u8];
+        buf.extend_from_slice(b"Bitcoin Signed Message:\n");
+        buf.extend_from_slice(&[0xfd, 0x2c, 0x01]);
+        buf.extend_from_slice(msg.as_bytes());
+        let expected: [u8; 32] = Sha256::digest(Sha256::digest(&buf)).into();
+        assert_eq!(magic_hash(&msg), expected);
+    }
+
+    #[test]
+    fn test_recover_high_s_signature() {
+        let sig = sig_fixture();
+        // malleate signature: (r, s, v) -> (r, n - s, v ^ 1)
+        let rs: [u8; 64] = sig[1..].try_into().unwrap();
+        let signature = libsecp256k1::Signature::parse_standard(&rs).unwrap();
+        let high_s = -signature.s;
+        assert!(high_s.is_high());
+
+        let mut malleated = sig.clone();
+        malleated[0] = 27 + ((sig[0] - 27) ^ 1);
+        malleated[33..].copy_from_slice(&high_s.b32());
+        assert!(matches!(
+            self::recover("Hello World 42", &malleated),
+            Err(Error::HighSSignature)
+        ));
+        assert!(!self::verify(
+            "Hello World 42",
+            &self::recover("Hello World 42", &sig).unwrap().address(),
+            &malleated
+        ));
+    }
 }
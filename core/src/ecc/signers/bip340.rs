@@ -0,0 +1,154 @@
+//! BIP340 Signer, schnorr signature used by taproot wallets.
+//! ref <https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki>
+//!
+//! The public key of BIP340 is x-only, it's stored as a compressed [PublicKey] with even y.
+//! Schnorr signature is not recoverable, so the public key should always be provided.
+
+use libsecp256k1::curve::Affine;
+use libsecp256k1::curve::Field;
+use libsecp256k1::curve::Jacobian;
+use libsecp256k1::curve::Scalar;
+use libsecp256k1::ECMULT_CONTEXT;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::ecc::PublicKey;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Tagged hash defined in BIP340, `sha256(sha256(tag) || sha256(tag) || data)`.
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Lift an x-only public key to the point with even y.
+pub fn lift_x(xonly_pubkey: &[u8; 32]) -> Result<Affine> {
+    let mut x = Field::default();
+    if !x.set_b32(xonly_pubkey) {
+        return Err(Error::InvalidPublicKey);
+    }
+    let mut p = Affine::default();
+    if !p.set_xo_var(&x, false) {
+        return Err(Error::InvalidPublicKey);
+    }
+    Ok(p)
+}
+
+/// Convert an x-only public key to compressed [PublicKey] with even y.
+pub fn pubkey_from_xonly(xonly_pubkey: &[u8; 32]) -> Result<PublicKey> {
+    lift_x(xonly_pubkey)?;
+    let mut pk = [0u8; 33];
+    pk[0] = 0x02;
+    pk[1..].copy_from_slice(xonly_pubkey);
+    Ok(PublicKey(pk))
+}
+
+/// Verify the signature and return the public key, as recover does for other signers.
+pub fn recover(
+    msg: impl AsRef<[u8]>,
+    xonly_pubkey: &[u8; 32],
+    sig: impl AsRef<[u8]>,
+) -> Result<PublicKey> {
+    let sig: &[u8; 64] = sig
+        .as_ref()
+        .try_into()
+        .map_err(|_| Error::VerifySignatureFailed)?;
+    let p = lift_x(xonly_pubkey)?;
+
+    let mut r = Field::default();
+    if !r.set_b32(sig[..32].try_into()?) {
+        return Err(Error::VerifySignatureFailed);
+    }
+    let mut s = Scalar::default();
+    if bool::from(s.set_b32(sig[32..].try_into()?)) {
+        return Err(Error::VerifySignatureFailed);
+    }
+
+    let challenge = tagged_hash(
+        "BIP0340/challenge",
+        &[&sig[..32], &xonly_pubkey[..], msg.as_ref()].concat(),
+    );
+    let mut e = Scalar::default();
+    let _ = e.set_b32(&challenge);
+
+    // R = s⋅G - e⋅P
+    let mut rj = Jacobian::default();
+    ECMULT_CONTEXT.ecmult(&mut rj, &Jacobian::from_ge(&p), &-e, &s);
+    if rj.is_infinity() {
+        return Err(Error::VerifySignatureFailed);
+    }
+    let mut ra = Affine::from_gej(&rj);
+    ra.x.normalize_var();
+    ra.y.normalize_var();
+    if ra.y.is_odd() || !ra.x.eq_var(&r) {
+        return Err(Error::VerifySignatureFailed);
+    }
+
+    pubkey_from_xonly(xonly_pubkey)
+}
+
+/// verify message signed by schnorr signature with x-only public key.
+pub fn verify(msg: impl AsRef<[u8]>, xonly_pubkey: &[u8; 32], sig: impl AsRef<[u8]>) -> bool {
+    match recover(msg, xonly_pubkey, sig) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!("failed to verify bip340 signature: {:?}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Test vectors from <https://github.com/bitcoin/bips/blob/master/bip-0340/test-vectors.csv>
+    #[test]
+    fn test_verify() {
+        let pk: [u8; 32] =
+            hex::decode("F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let msg = [0u8; 32];
+        let sig = hex::decode("E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA821525F66A4A85EA8B71E482A74F382D2CE5EBEEE8FDB2172F477DF4900D310536C0").unwrap();
+        assert!(self::verify(msg, &pk, sig));
+
+        let pk: [u8; 32] =
+            hex::decode("DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let msg = hex::decode("243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89")
+            .unwrap();
+        let sig = hex::decode("6896BD60EEAE296DB48A229FF71DFE071BDE413E6D43F917DC8DCF8C78DE33418906D11AC976ABCCB20B091292BFF4EA897EFCB639EA871CFA95F6DE339E4B0A").unwrap();
+        assert!(self::verify(&msg, &pk, &sig));
+        assert_eq!(
+            self::recover(&msg, &pk, &sig).unwrap(),
+            pubkey_from_xonly(&pk).unwrap()
+        );
+
+        // tampered message
+        let mut tampered = msg.clone();
+        tampered[0] ^= 1;
+        assert!(!self::verify(&tampered, &pk, &sig));
+        // truncated signature
+        assert!(!self::verify(&msg, &pk, &sig[..63]));
+    }
+
+    #[test]
+    fn test_invalid_pubkey() {
+        // public key not on the curve
+        let pk: [u8; 32] =
+            hex::decode("EEFDEA4CDB677750A420FEE807EACF21EB9898AE79B9768766E4FAA04A2D4A34")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        assert!(pubkey_from_xonly(&pk).is_err());
+    }
+}
@@ -3,10 +3,32 @@ use ed25519_dalek::Verifier;
 
 use crate::ecc::Address;
 use crate::ecc::PublicKey;
+use crate::ecc::SecretKey;
+
+/// Derive an ed25519 secret key from the bytes of a [SecretKey].
+fn derive_secret(key: SecretKey) -> ed25519_dalek::SecretKey {
+    // 32 bytes is always a valid ed25519 secret key.
+    ed25519_dalek::SecretKey::from_bytes(&key.ser()).unwrap()
+}
+
+/// Get the ed25519 public key derived from the bytes of a [SecretKey].
+pub fn pubkey(key: SecretKey) -> PublicKey {
+    let secret = derive_secret(key);
+    ed25519_dalek::PublicKey::from(&secret).into()
+}
+
+/// Sign message with the ed25519 key derived from the bytes of a [SecretKey].
+pub fn sign(key: SecretKey, msg: &str) -> [u8; 64] {
+    let secret = derive_secret(key);
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    ed25519_dalek::ExpandedSecretKey::from(&secret)
+        .sign(msg.as_bytes(), &public)
+        .to_bytes()
+}
 
 /// ref <https://www.rfc-editor.org/rfc/rfc8709>
 pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>, pubkey: PublicKey) -> bool {
-    if pubkey.address() != *address {
+    if !super::constant_time_eq(pubkey.address(), address) {
         return false;
     }
     if sig.as_ref().len() != 64 {
@@ -51,4 +73,14 @@ mod test {
         let sig: Vec<u8> = base58::FromBase58::from_base58(sig_b58).unwrap();
         assert!(self::verify(msg, &signer.address(), sig.as_slice(), signer))
     }
+
+    #[test]
+    fn test_sign_ed25519() {
+        let key = SecretKey::random();
+        let pk = pubkey(key);
+        let sig = sign(key, "helloworld");
+        assert!(self::verify("helloworld", &pk.address(), sig, pk));
+        assert!(!self::verify("hello", &pk.address(), sig, pk));
+        assert_ne!(pk, pubkey(SecretKey::random()));
+    }
 }
@@ -0,0 +1,79 @@
+//! eip1271, signature validation for smart contract wallets.
+//! ref <https://eips.ethereum.org/EIPS/eip-1271>
+//!
+//! The signature of a contract wallet can not be recovered to an address,
+//! it can only be validated by calling `isValidSignature(bytes32,bytes)` of the contract.
+//! Since core is not bound to any JSON-RPC client, an [Eip1271Provider] should be injected.
+
+use async_trait::async_trait;
+use web3::ethabi;
+
+use crate::ecc::signers::eip191;
+use crate::ecc::Address;
+use crate::error::Error;
+use crate::error::Result;
+
+/// The return value of `isValidSignature` when signature is valid.
+/// It's also the function selector of `isValidSignature(bytes32,bytes)`.
+pub const MAGIC_VALUE: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+
+/// Provider to perform `eth_call` on a specific chain.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait Eip1271Provider {
+    /// Call contract with abi encoded data, and return the abi encoded result.
+    async fn call(&self, chain_id: u64, contract: Address, data: Vec<u8>) -> Result<Vec<u8>>;
+}
+
+/// Encode call data of `isValidSignature(bytes32,bytes)`.
+pub fn encode_call(hash: &[u8; 32], sig: &[u8]) -> Vec<u8> {
+    let mut data = MAGIC_VALUE.to_vec();
+    data.extend(ethabi::encode(&[
+        ethabi::Token::FixedBytes(hash.to_vec()),
+        ethabi::Token::Bytes(sig.to_vec()),
+    ]));
+    data
+}
+
+/// Check the result of `isValidSignature`, which returns a bytes4 left aligned in 32 bytes.
+pub fn is_magic_value(ret: &[u8]) -> bool {
+    ret.len() >= 4 && ret[..4] == MAGIC_VALUE
+}
+
+/// verify message that signed by contract wallet, the message is hashed with eip191.
+pub async fn verify(
+    provider: &dyn Eip1271Provider,
+    chain_id: u64,
+    msg: &str,
+    contract: &Address,
+    sig: impl AsRef<[u8]>,
+) -> Result<bool> {
+    let hash = eip191::hash(msg);
+    let data = encode_call(&hash, sig.as_ref());
+    let ret = provider
+        .call(chain_id, *contract, data)
+        .await
+        .map_err(|e| {
+            tracing::debug!("failed to call isValidSignature: {:?}", e);
+            Error::VerifySignatureFailed
+        })?;
+    Ok(is_magic_value(&ret))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_call() {
+        let hash = [1u8; 32];
+        let sig = vec![2u8; 65];
+        let data = encode_call(&hash, &sig);
+        assert_eq!(data[..4], MAGIC_VALUE);
+        // selector + bytes32 + offset + length + 65 bytes padded to 96
+        assert_eq!(data.len(), 4 + 32 + 32 + 32 + 96);
+        assert_eq!(data[4..36], hash);
+        assert!(is_magic_value(&[&MAGIC_VALUE[..], &[0u8; 28][..]].concat()));
+        assert!(!is_magic_value(&[0u8; 32]));
+    }
+}
@@ -39,7 +39,7 @@ pub fn recover(msg: &str, sig: impl AsRef<[u8]>) -> Result<PublicKey> {
 /// verify message signed by Ethereum address.
 pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>) -> bool {
     if let Ok(p) = recover(msg, sig) {
-        p.address() == *address
+        super::constant_time_eq(p.address(), address)
     } else {
         false
     }
@@ -0,0 +1,193 @@
+//! EIP712 Signer, which signs typed structured data instead of a raw string.
+//! ref <https://eips.ethereum.org/EIPS/eip-712>
+//!
+//! A packed session is signed as a `Session` struct in the domain of [DOMAIN_NAME] and [DOMAIN_VERSION].
+//! The domain and types are fixed, so wallets and verifiers always agree on the hash,
+//! and wallets like MetaMask show the fields of session to user by `eth_signTypedData_v4`.
+
+use std::str::FromStr;
+
+use web3::signing::keccak256;
+
+use crate::dht::Did;
+use crate::ecc::Address;
+use crate::ecc::PublicKey;
+use crate::ecc::SecretKey;
+use crate::error::Error;
+use crate::error::Result;
+use crate::session::SessionPayload;
+
+/// Name of the EIP712 domain.
+pub const DOMAIN_NAME: &str = "Rings Network";
+/// Version of the EIP712 domain.
+pub const DOMAIN_VERSION: &str = "1";
+const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version)";
+const SESSION_TYPE: &str = "Session(address sessionId,uint256 timestamp,uint256 ttl,string scopes)";
+
+/// Fields of a packed session, which is the message of typed data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedSession {
+    /// did of session key
+    pub session_id: Did,
+    /// timestamp when session created
+    pub ts_ms: u128,
+    /// lifetime of session
+    pub ttl_ms: u128,
+    /// comma separated scopes, empty if the session is granted all scopes
+    pub scopes: String,
+}
+
+impl FromStr for TypedSession {
+    type Err = Error;
+
+    /// Parse a packed session, the typed data is defined for v1 payload only.
+    fn from_str(packed: &str) -> Result<Self> {
+        let payload = SessionPayload::from_str(packed)?;
+        Ok(Self {
+            session_id: payload.session_id,
+            ts_ms: payload.ts_ms,
+            ttl_ms: payload.ttl_ms as u128,
+            scopes: payload.scopes.unwrap_or_default().join(","),
+        })
+    }
+}
+
+impl TypedSession {
+    /// `hashStruct` of session.
+    pub fn struct_hash(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(32 * 5);
+        data.extend_from_slice(&keccak256(SESSION_TYPE.as_bytes()));
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(self.session_id.as_bytes());
+        data.extend_from_slice(&encode_uint(self.ts_ms));
+        data.extend_from_slice(&encode_uint(self.ttl_ms));
+        data.extend_from_slice(&keccak256(self.scopes.as_bytes()));
+        keccak256(&data)
+    }
+
+    /// Typed data of session in JSON, which is the param of `eth_signTypedData_v4`.
+    pub fn typed_data(&self) -> serde_json::Value {
+        serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                ],
+                "Session": [
+                    { "name": "sessionId", "type": "address" },
+                    { "name": "timestamp", "type": "uint256" },
+                    { "name": "ttl", "type": "uint256" },
+                    { "name": "scopes", "type": "string" },
+                ],
+            },
+            "primaryType": "Session",
+            "domain": {
+                "name": DOMAIN_NAME,
+                "version": DOMAIN_VERSION,
+            },
+            "message": {
+                "sessionId": format!("0x{}", self.session_id),
+                "timestamp": self.ts_ms.to_string(),
+                "ttl": self.ttl_ms.to_string(),
+                "scopes": self.scopes,
+            },
+        })
+    }
+}
+
+fn encode_uint(v: u128) -> [u8; 32] {
+    let mut data = [0u8; 32];
+    data[16..].copy_from_slice(&v.to_be_bytes());
+    data
+}
+
+/// `hashStruct` of the fixed domain.
+pub fn domain_separator() -> [u8; 32] {
+    let mut data = Vec::with_capacity(32 * 3);
+    data.extend_from_slice(&keccak256(DOMAIN_TYPE.as_bytes()));
+    data.extend_from_slice(&keccak256(DOMAIN_NAME.as_bytes()));
+    data.extend_from_slice(&keccak256(DOMAIN_VERSION.as_bytes()));
+    keccak256(&data)
+}
+
+/// Hash of typed data reconstructed from a packed session,
+/// `keccak256("\x19\x01" || domainSeparator || hashStruct(session))`.
+pub fn hash(msg: &str) -> Result<[u8; 32]> {
+    let session = TypedSession::from_str(msg)?;
+    let mut data = Vec::with_capacity(2 + 32 * 2);
+    data.extend_from_slice(b"\x19\x01");
+    data.extend_from_slice(&domain_separator());
+    data.extend_from_slice(&session.struct_hash());
+    Ok(keccak256(&data))
+}
+
+/// sign function passing a packed session.
+pub fn sign_raw(sec: SecretKey, msg: &str) -> Result<[u8; 65]> {
+    Ok(super::eip191::sign(sec, &hash(msg)?))
+}
+
+/// recover pubkey according to signature, `v` of signature can be 0/1 or 27/28.
+pub fn recover(msg: &str, sig: impl AsRef<[u8]>) -> Result<PublicKey> {
+    let mut sig: [u8; 65] = sig.as_ref().try_into()?;
+    if sig[64] >= 27 {
+        sig[64] -= 27;
+    }
+    crate::ecc::recover_hash(&hash(msg)?, &sig)
+}
+
+/// verify packed session signed as typed data by Ethereum address.
+pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>) -> bool {
+    if let Ok(p) = recover(msg, sig) {
+        super::constant_time_eq(p.address(), address)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hex::FromHex;
+
+    use super::*;
+
+    #[test]
+    fn test_eip712() {
+        let key =
+            SecretKey::try_from("65860affb4b570dba06db294aa7c676f68e04a5bf2721243ad3cbc05a79c68c0")
+                .unwrap();
+        let address = Address::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let msg =
+            "c5d2460186f7233c927e7db2dcc703c0e500b653\n1700000000000\n2592000000\nconnect,send";
+
+        let session = TypedSession::from_str(msg).unwrap();
+        assert_eq!(session.scopes, "connect,send");
+        assert_eq!(
+            session.typed_data()["message"]["sessionId"],
+            "0xc5d2460186f7233c927e7db2dcc703c0e500b653"
+        );
+
+        assert_eq!(
+            hex::encode(hash(msg).unwrap()),
+            "fa09c7df353f383af05309eb0774cf1fea7c68e65e154b55e2cbcdda4835e013"
+        );
+
+        // Wallets sign deterministically by RFC6979, so the signature of `session.typed_data()` is the same as
+        // window.ethereum.request({method: "eth_signTypedData_v4", params: ["0x11E807fcc88dD319270493fB2e822e388Fe36ab0", JSON.stringify(typed_data)]})
+        let wallet_sig = Vec::from_hex("6b95fd5551f09a9f89971a10c296e97c3c001dede184ec68550d7f3bd519e9d44dd0a3e8843dd11b788ce83e73347016ac79d292269269d0cfbbf832d322902a1b").unwrap();
+        let sig = sign_raw(key, msg).unwrap();
+        assert_eq!(wallet_sig.as_slice(), sig);
+        assert_eq!(recover(msg, sig).unwrap().address(), address);
+        assert!(verify(msg, &address, sig));
+
+        // v of 0/1 is accepted as well.
+        let mut sig01 = sig;
+        sig01[64] -= 27;
+        assert!(verify(msg, &address, sig01));
+
+        // Any change of fields breaks the signature.
+        let tampered =
+            "c5d2460186f7233c927e7db2dcc703c0e500b653\n1700000000000\n2592000000\nconnect";
+        assert!(!verify(tampered, &address, sig));
+        assert!(hash("not a session").is_err());
+    }
+}
@@ -1,4 +1,33 @@
+//! Signers of authorizers, which sign and verify messages in their own schemes.
+use subtle::ConstantTimeEq;
+
 pub mod bip137;
+pub mod bip340;
 pub mod ed25519;
+pub mod eip1271;
 pub mod eip191;
+pub mod eip712;
 pub mod secp256k1;
+
+/// Compare bytes, such as addresses or signatures, in constant time to not leak timing information.
+/// Only the length may be leaked, inputs of different lengths are unequal.
+pub fn constant_time_eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    a.as_ref().ct_eq(b.as_ref()).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    #[test]
+    fn test_constant_time_eq() {
+        let address = SecretKey::random().address();
+        assert!(constant_time_eq(address, address));
+        assert!(!constant_time_eq(address, SecretKey::random().address()));
+        assert!(constant_time_eq([1u8, 2, 3], vec![1u8, 2, 3]));
+        assert!(!constant_time_eq([1u8, 2, 3], [1u8, 2, 4]));
+        assert!(!constant_time_eq([1u8, 2, 3], [1u8, 2]));
+        assert!(constant_time_eq([], []));
+    }
+}
@@ -30,7 +30,7 @@ pub fn recover(msg: &str, sig: impl AsRef<[u8]>) -> Result<PublicKey> {
 /// verify signature with message and address.
 pub fn verify(msg: &str, address: &Address, sig: impl AsRef<[u8]>) -> bool {
     if let Ok(p) = recover(msg, sig) {
-        p.address() == *address
+        super::constant_time_eq(p.address(), address)
     } else {
         false
     }
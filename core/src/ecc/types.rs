@@ -29,6 +29,19 @@ impl PublicKey {
         Self::from_u8(value.as_slice())
     }
 
+    /// solana style b58, which is plain base58 of the 32 bytes ed25519 public key.
+    /// Unlike `try_from_b58t`, other lengths and invalid ed25519 points are rejected.
+    pub fn try_from_solana(value: &str) -> Result<PublicKey> {
+        let value: Vec<u8> =
+            base58::FromBase58::from_base58(value).map_err(|_| Error::PublicKeyBadFormat)?;
+        if value.len() != 32 {
+            return Err(Error::PublicKeyBadFormat);
+        }
+        let pubkey = ed25519_dalek::PublicKey::from_bytes(&value)
+            .map_err(|_| Error::EdDSAPublicKeyBadFormat)?;
+        Ok(pubkey.into())
+    }
+
     /// monero and bitcoin style b58
     pub fn try_from_b58m(value: &str) -> Result<PublicKey> {
         let value: &[u8] =
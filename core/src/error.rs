@@ -82,9 +82,57 @@ pub enum Error {
     #[error("Unknown authorizer")]
     UnknownAuthorizer,
 
+    #[error("Authorizer is not supported by this verification method")]
+    UnsupportedAuthorizer,
+
+    #[error("Unknown session key algorithm: {0}")]
+    UnknownSessionKeyAlgorithm(String),
+
+    #[error("Session pubkey can not be recovered from signature of {0}")]
+    SessionPubkeyUnrecoverable(String),
+
+    #[error("Session {0} is dumped without the public key of session key")]
+    SessionKeyPubkeyNotFound(crate::dht::Did),
+
     #[error("Failed on verify message signature")]
     VerifySignatureFailed,
 
+    #[error("Invalid threshold {0} of multisig session with {1} authorizers")]
+    InvalidMultisigThreshold(usize, usize),
+
+    #[error("Multisig session is signed by {0} authorizers, less than threshold {1}")]
+    MultisigThresholdNotMet(usize, usize),
+
+    #[error("Session lifetime {0}ms exceeds the max lifetime {1}ms")]
+    SessionTtlExceeded(usize, usize),
+
+    #[error("Session {0} is revoked")]
+    SessionRevoked(crate::dht::Did),
+
+    #[error("Session is authorized by {1}, which mismatches the existing authorizer {0}")]
+    AuthorizerMismatch(crate::dht::Did, crate::dht::Did),
+
+    #[error("Session created at {1} is older than the existing one created at {0}")]
+    StaleSession(u128, u128),
+
+    #[error("Invalid session scope {0:?}, which should be nonempty without comma or newline")]
+    InvalidSessionScope(String),
+
+    #[error("Invalid signature length {0}, expect {1}")]
+    InvalidSignatureLength(usize, usize),
+
+    #[error("Invalid recovery id of signature, header byte {0}")]
+    InvalidRecoveryId(u8),
+
+    #[error("Signature is not low-S normalized")]
+    HighSSignature,
+
+    #[error("Invalid session payload {0:?}")]
+    InvalidSessionPayload(String),
+
+    #[error("Unsupported version {0} of session payload")]
+    UnsupportedSessionPayloadVersion(u8),
+
     #[error("Gzip encode error.")]
     GzipEncode,
 
@@ -103,6 +151,15 @@ pub enum Error {
     #[error("Ice server get url without host")]
     IceServerURLMissHost,
 
+    #[error("Ice server {0} requires username and credential")]
+    IceServerMissCredential(String),
+
+    #[error("Transport is not restartable in ice connection state {0}")]
+    TransportNotRestartable(String),
+
+    #[error("No other connected peer to relay ice restart offer")]
+    IceRestartNoRelay,
+
     #[error("SecretKey parse error, {0}")]
     Libsecp256k1SecretKeyParse(String),
 
@@ -282,6 +339,9 @@ pub enum Error {
     #[error("Suspected infinite looping in path")]
     InfiniteRelayPath,
 
+    #[error("Hop limit of message is exceeded")]
+    HopLimitExceeded,
+
     #[error("The destination of report message should always be the first element of path")]
     InvalidRelayDestination,
 
@@ -323,6 +383,12 @@ pub enum Error {
     #[error("Message invalid: {0}")]
     InvalidMessage(String),
 
+    #[error("Message is replayed")]
+    MessageReplayed,
+
+    #[error("call lock() of replay cache failed")]
+    ReplayCacheLockFailed,
+
     #[error("Message encryption failed")]
     MessageEncryptionFailed(ecies::SecpError),
 
@@ -332,6 +398,12 @@ pub enum Error {
     #[error("message too large, consider use ChunkList")]
     MessageTooLarge,
 
+    #[error("Send queue of transport is full, {0} bytes buffered")]
+    SendQueueFull(usize),
+
+    #[error("Invalid watermarks of send queue, low {0} should not be greater than high {1}")]
+    InvalidWatermarks(usize, usize),
+
     #[cfg(feature = "wasm")]
     #[error("Cannot get property {0} from JsValue")]
     FailedOnGetProperty(String),
@@ -350,6 +422,12 @@ pub enum Error {
 
     #[error("Session is expired")]
     SessionExpired,
+
+    #[error("Session created at {0} is ahead of local clock {1}, the clock of either side may be skewed")]
+    SessionNotYetValid(u128, u128),
+
+    #[error("Invalid length of successor list {0}, it should be in 1..=255")]
+    InvalidSuccessorLength(usize),
 }
 
 #[cfg(feature = "wasm")]
@@ -5,6 +5,7 @@ use crate::dht::vnode::VirtualNode;
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::SuccessorReader;
+use crate::dht::VNodeCache;
 use crate::storage::MemStorage;
 use crate::storage::PersistenceStorage;
 use crate::storage::PersistenceStorageReadAndWrite;
@@ -28,12 +29,19 @@ pub struct TransportInspect {
     pub state: Option<String>,
 }
 
+/// Snapshot of the position of a node in the chord ring.
+/// Fields are ordered deterministically, so that views of different nodes can be diffed.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DHTInspect {
+    /// did of the node
     pub did: String,
+    /// successor list, ordered by distance from the node
     pub successors: Vec<String>,
+    /// predecessor of the node
     #[serde(default)]
     pub predecessor: Option<String>,
+    /// finger table compressed as `(did, start, end)`,
+    /// which means fingers from index `start` to `end` are all `did`
     pub finger_table: Vec<(Option<String>, usize, usize)>,
 }
 
@@ -63,7 +71,7 @@ impl SwarmInspect {
         };
         let persistence_storage =
             StorageInspect::inspect_persistence_storage(&swarm.dht().storage).await;
-        let cache_storage = StorageInspect::inspect_mem_storage(&swarm.dht().cache);
+        let cache_storage = StorageInspect::inspect_vnode_cache(&swarm.dht().cache);
 
         Self {
             transports,
@@ -123,6 +131,16 @@ impl StorageInspect {
                 .collect(),
         }
     }
+    pub fn inspect_vnode_cache(cache: &VNodeCache) -> Self {
+        Self {
+            items: cache
+                .items()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        }
+    }
+
     pub fn inspect_mem_storage(storage: &MemStorage<Did, VirtualNode>) -> Self {
         Self {
             items: storage
@@ -8,12 +8,36 @@ use serde::Serialize;
 use crate::error::Error;
 use crate::error::Result;
 
+/// Leading byte of binary encoded data, which never leads the bytes of base58 encoded text.
+pub const BINARY_ENCODED_TAG: u8 = 0;
+
+/// Leading byte of deflated data in [Encoded], it should never lead the raw data of the same type.
+pub const COMPRESSED_ENCODED_TAG: u8 = 0xff;
+
 pub trait Encoder {
     fn encode(&self) -> Result<Encoded>;
+
+    /// Encode with data deflated and prefixed by [COMPRESSED_ENCODED_TAG], which is shorter
+    /// for large data. The default is [Encoder::encode], for types without a compressed form.
+    /// Decoders of types with a compressed form detect it, and still decode the raw one.
+    fn encode_compressed(&self) -> Result<Encoded> {
+        self.encode()
+    }
+
+    /// Encode to bytes. The default is the bytes of textual [Encoded],
+    /// types having a binary form should prefix it with [BINARY_ENCODED_TAG].
+    fn encode_bytes(&self) -> Result<Bytes> {
+        Ok(Bytes::from(Vec::from(self.encode()?)))
+    }
 }
 
 pub trait Decoder: Sized {
     fn from_encoded(encoded: &Encoded) -> Result<Self>;
+
+    /// Decode from bytes of [Encoder::encode_bytes], which can also be the bytes of textual [Encoded].
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_encoded(&Encoded::try_from(data.to_vec())?)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -6,6 +6,7 @@ use super::dht;
 use crate::dht::types::CorrectChord;
 use crate::dht::Chord;
 use crate::dht::PeerRingAction;
+use crate::dht::SuccessorReader;
 use crate::dht::TopoInfo;
 use crate::error::Error;
 use crate::error::Result;
@@ -13,6 +14,8 @@ use crate::message::types::ConnectNodeReport;
 use crate::message::types::ConnectNodeSend;
 use crate::message::types::FindSuccessorReport;
 use crate::message::types::FindSuccessorSend;
+use crate::message::types::IceRestartReport;
+use crate::message::types::IceRestartSend;
 use crate::message::types::JoinDHT;
 use crate::message::types::Message;
 use crate::message::types::QueryForTopoInfoReport;
@@ -76,9 +79,15 @@ impl HandleMsg<QueryForTopoInfoReport> for MessageHandler {
 impl HandleMsg<LeaveDHT> for MessageHandler {
     async fn handle(
         &self,
-        _ctx: &MessagePayload<Message>,
+        ctx: &MessagePayload<Message>,
         msg: &LeaveDHT,
     ) -> Result<Vec<MessageHandlerEvent>> {
+        let sender = ctx.relay.origin_sender();
+        // A remote node can only leave for itself.
+        if sender != self.dht.did && (msg.did != sender || ctx.origin_authorizer_did()? != sender) {
+            tracing::warn!("Ignore LeaveDHT of {} from {}", msg.did, sender);
+            return Ok(vec![]);
+        }
         Ok(vec![MessageHandlerEvent::Disconnect(msg.did)])
     }
 }
@@ -96,6 +105,26 @@ impl HandleMsg<JoinDHT> for MessageHandler {
         // otherwise, it will be a `send` op
         // let act = self.dht.join(msg.did)?;
         // handle_join_dht(&self, act, ctx).await
+        let sender = ctx.relay.origin_sender();
+        if sender != self.dht.did {
+            // Sent by a leaving successor, which hands over its own successor.
+            // Only the signer of origin verification can leave, and only if it's our successor.
+            if ctx.origin_authorizer_did()? != sender || !self.dht.successors().contains(&sender)? {
+                tracing::warn!("Ignore JoinDHT of {} from {}", msg.did, sender);
+                return Ok(vec![]);
+            }
+            self.dht.remove(sender)?;
+            // In a ring of two nodes, the handed over successor is this node.
+            if msg.did == self.dht.did {
+                return Ok(vec![]);
+            }
+            // Never connect to a handed over did, it's joined only if it's already in finger table.
+            // Otherwise stabilization will find the new successor.
+            if !self.dht.lock_finger()?.contains(Some(msg.did)) {
+                return Ok(vec![]);
+            }
+            return Ok(vec![MessageHandlerEvent::JoinDHT(ctx.clone(), msg.did)]);
+        }
         Ok(vec![MessageHandlerEvent::JoinDHT(ctx.clone(), msg.did)])
     }
 }
@@ -138,6 +167,44 @@ impl HandleMsg<ConnectNodeReport> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<IceRestartSend> for MessageHandler {
+    async fn handle(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &IceRestartSend,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        if self.dht.did != ctx.relay.destination {
+            Ok(vec![MessageHandlerEvent::ForwardPayload(ctx.clone(), None)])
+        } else {
+            Ok(vec![MessageHandlerEvent::AnswerIceRestart(
+                ctx.clone(),
+                msg.clone(),
+            )])
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<IceRestartReport> for MessageHandler {
+    async fn handle(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &IceRestartReport,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        if self.dht.did != ctx.relay.destination {
+            Ok(vec![MessageHandlerEvent::ForwardPayload(ctx.clone(), None)])
+        } else {
+            Ok(vec![MessageHandlerEvent::AcceptIceRestart(
+                ctx.relay.origin_sender(),
+                msg.clone(),
+            )])
+        }
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<FindSuccessorSend> for MessageHandler {
@@ -215,11 +282,14 @@ pub mod tests {
     use crate::ecc::tests::gen_ordered_keys;
     use crate::ecc::SecretKey;
     use crate::message::handlers::tests::assert_no_more_msg;
+    use crate::message::handlers::tests::wait_for_msgs;
     use crate::swarm::Swarm;
     use crate::tests::default::prepare_node;
     use crate::tests::manually_establish_connection;
+    use crate::transports::manager::TransportHandshake;
     use crate::transports::manager::TransportManager;
     use crate::types::ice_transport::IceTransportInterface;
+    use crate::types::ice_transport::IceTrickleScheme;
 
     // node1.key < node2.key < node3.key
     //
@@ -990,4 +1060,78 @@ pub mod tests {
         assert!(t3_1.is_connected().await);
         Ok(())
     }
+
+    // node1, node2 and node3 are connected to each other.
+    // After node1 restart ice of transport to node3, the offer is relayed by node2:
+    //
+    // 1. Node1 send IceRestartSend(node3) to node2, node2 relay it to node3.
+    //
+    // 2. Node3 respond by sending IceRestartReport(node1) to node2, node2 relay it to node1.
+    //
+    // Then transports between node1 and node3 will be reconnected with new candidates.
+    #[tokio::test]
+    async fn test_restart_ice() -> Result<()> {
+        let keys = gen_ordered_keys(3);
+        let (key1, key2, key3) = (keys[0], keys[1], keys[2]);
+        let (node1, _path1) = prepare_node(key1).await;
+        let (node2, _path2) = prepare_node(key2).await;
+        let (node3, _path3) = prepare_node(key3).await;
+
+        // node1 has only node3 to restart, no relay.
+        manually_establish_connection(&node1, &node3).await?;
+        wait_for_msgs(&node1, &node2, &node3).await;
+        assert!(matches!(
+            node1.restart_ice(node3.did()).await,
+            Err(Error::IceRestartNoRelay)
+        ));
+
+        manually_establish_connection(&node1, &node2).await?;
+        manually_establish_connection(&node2, &node3).await?;
+        wait_for_msgs(&node1, &node2, &node3).await;
+
+        let t1_3 = node1.get_transport(node3.did()).unwrap();
+        let t3_1 = node3.get_transport(node1.did()).unwrap();
+        let restarted = node1.restart_ice(node3.did()).await?;
+        assert_eq!(restarted.id, t1_3.id);
+
+        // 1->2 IceRestartSend
+        let ev_2 = node2.listen_once().await.unwrap().0;
+        assert!(matches!(ev_2.data, Message::IceRestartSend(_)));
+        // 2->3 IceRestartSend
+        let ev_3 = node3.listen_once().await.unwrap().0;
+        assert!(matches!(ev_3.data, Message::IceRestartSend(_)));
+        assert_eq!(ev_3.relay.path, vec![node1.did(), node2.did()]);
+        // 3->2 IceRestartReport
+        let ev_2 = node2.listen_once().await.unwrap().0;
+        assert!(matches!(ev_2.data, Message::IceRestartReport(_)));
+        // 2->1 IceRestartReport
+        let ev_1 = node1.listen_once().await.unwrap().0;
+        assert!(matches!(
+            ev_1.data,
+            Message::IceRestartReport(ref msg) if msg.transport_uuid == t1_3.id.to_string()
+        ));
+
+        // drain the messages after ice reconnected.
+        wait_for_msgs(&node1, &node2, &node3).await;
+
+        // transports and dht are kept.
+        assert_eq!(node1.get_transport(node3.did()).unwrap().id, t1_3.id);
+        assert_eq!(node3.get_transport(node1.did()).unwrap().id, t3_1.id);
+        assert!(t1_3.is_connected().await);
+        assert!(t3_1.is_connected().await);
+        assert!(node1.dht().successors().list()?.contains(&node3.did()));
+
+        // transport not connected yet is not restartable.
+        let (pending, _) = node1.create_offer().await?;
+        assert!(matches!(
+            pending.get_ice_restart_info().await,
+            Err(Error::TransportNotRestartable(_))
+        ));
+        let did4 = SecretKey::random().address().into();
+        assert!(matches!(
+            node1.restart_ice(did4).await,
+            Err(Error::TransportNotFound)
+        ));
+        Ok(())
+    }
 }
@@ -9,6 +9,7 @@
 ///     | Builtin Message Callback |  |  Custom Message Callback |
 ///     +--------------------------+  +--------------------------+
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use async_recursion::async_recursion;
 use async_trait::async_trait;
@@ -16,6 +17,7 @@ use async_trait::async_trait;
 use super::CustomMessage;
 use super::Message;
 use super::MessagePayload;
+use super::ReplayCache;
 use crate::dht::vnode::VirtualNode;
 use crate::dht::Did;
 use crate::dht::PeerRing;
@@ -23,6 +25,8 @@ use crate::error::Error;
 use crate::error::Result;
 use crate::message::ConnectNodeReport;
 use crate::message::ConnectNodeSend;
+use crate::message::IceRestartReport;
+use crate::message::IceRestartSend;
 
 /// Operator and Handler for Connection
 pub mod connection;
@@ -98,6 +102,14 @@ pub enum MessageHandlerEvent {
     /// sender's Did and Message.
     AcceptAnswer(NextHop, ConnectNodeReport),
 
+    /// Instructs the swarm to answer an ice restart offer inside payload
+    /// on the transport of sender.
+    AnswerIceRestart(Payload, IceRestartSend),
+
+    /// Instructs the swarm to accept an ice restart answer by given
+    /// sender's Did and Message.
+    AcceptIceRestart(Did, IceRestartReport),
+
     /// Tell swarm to forward the payload to destination by given
     /// Payload and optional next hop.
     ForwardPayload(Payload, Option<Did>),
@@ -131,6 +143,8 @@ pub struct MessageHandler {
     callback: Arc<Option<CallbackFn>>,
     /// A specific validator implement ValidatorFn.
     validator: Arc<Option<ValidatorFn>>,
+    /// Recently seen verifications, used to reject replayed messages.
+    replay_cache: Arc<Mutex<ReplayCache>>,
 }
 
 /// Generic trait for handle message ,inspired by Actor-Model.
@@ -156,15 +170,41 @@ impl MessageHandler {
             dht,
             callback: Arc::new(callback),
             validator: Arc::new(validator),
+            replay_cache: Arc::new(Mutex::new(ReplayCache::default())),
         }
     }
 
+    /// Replace the replay cache, to configure its capacity and window.
+    pub fn with_replay_cache(mut self, replay_cache: ReplayCache) -> Self {
+        self.replay_cache = Arc::new(Mutex::new(replay_cache));
+        self
+    }
+
+    /// Reject message if its origin verification is already seen.
+    /// The verification of each hop is resigned when forwarding, so it's not checked,
+    /// otherwise a message can be replayed by any node with a fresh hop verification.
+    fn check_replay(&self, payload: &MessagePayload<Message>) -> Result<()> {
+        let mut replay_cache = self
+            .replay_cache
+            .lock()
+            .map_err(|_| Error::ReplayCacheLockFailed)?;
+        if !replay_cache.check(&payload.origin_verification) {
+            tracing::warn!("Drop replayed message {}", &payload.tx_id);
+            return Err(Error::MessageReplayed);
+        }
+        Ok(())
+    }
+
     /// Invoke callback, which will be call after builtin handler.
     async fn invoke_callback(&self, payload: &MessagePayload<Message>) -> Vec<MessageHandlerEvent> {
         if let Some(ref cb) = *self.callback {
             match payload.data {
                 Message::CustomMessage(ref msg) => {
                     if self.dht.did == payload.relay.destination {
+                        if payload.origin_verification.is_expired() {
+                            tracing::warn!("Drop expired custom message {}", &payload.tx_id);
+                            return vec![];
+                        }
                         tracing::debug!("INVOKE CUSTOM MESSAGE CALLBACK {}", &payload.tx_id);
                         return cb.custom_message(payload, msg).await;
                     }
@@ -203,6 +243,7 @@ impl MessageHandler {
         }
         tracing::debug!("START HANDLE MESSAGE: {} {}", &payload.tx_id, &payload.data);
 
+        self.check_replay(payload)?;
         self.validate(payload).await?;
 
         let mut events = match &payload.data {
@@ -221,6 +262,10 @@ impl MessageHandler {
             Message::CustomMessage(ref msg) => self.handle(payload, msg).await,
             Message::QueryForTopoInfoSend(ref msg) => self.handle(payload, msg).await,
             Message::QueryForTopoInfoReport(ref msg) => self.handle(payload, msg).await,
+            Message::IceRestartSend(ref msg) => self.handle(payload, msg).await,
+            Message::IceRestartReport(ref msg) => self.handle(payload, msg).await,
+            Message::SearchVNodeRange(ref msg) => self.handle(payload, msg).await,
+            Message::FoundVNodeRange(ref msg) => self.handle(payload, msg).await,
         }?;
 
         tracing::debug!("INVOKE CALLBACK {}", &payload.tx_id);
@@ -242,6 +287,7 @@ pub mod tests {
     use super::*;
     use crate::dht::Did;
     use crate::ecc::SecretKey;
+    use crate::message::OriginVerificationGen;
     use crate::message::PayloadSender;
     use crate::swarm::Swarm;
     use crate::tests::default::prepare_node_with_callback;
@@ -357,6 +403,96 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_drop_expired_custom_message() -> Result<()> {
+        let key1 = SecretKey::random();
+        let key2 = SecretKey::random();
+
+        let msg_callback = MessageCallbackInstance {
+            handler_messages: Arc::new(Mutex::new(vec![])),
+        };
+        let cb: CallbackFn = Box::new(msg_callback.clone());
+
+        let (node1, _path1) = prepare_node_with_callback(key1, None).await;
+        let (node2, _path2) = prepare_node_with_callback(key2, None).await;
+        let handler = MessageHandler::new(node2.dht(), Some(cb), None);
+
+        let expired = MessagePayload::new_send_with_ttl(
+            Message::custom("expired".as_bytes())?,
+            &node1.session_manager(),
+            node2.did(),
+            node2.did(),
+            1,
+        )?;
+        let payload = MessagePayload::new_send(
+            Message::custom("alive".as_bytes())?,
+            &node1.session_manager(),
+            node2.did(),
+            node2.did(),
+        )?;
+        sleep(Duration::from_millis(10)).await;
+
+        assert!(expired.origin_verification.is_expired());
+        handler.handle_message(&expired).await?;
+        handler.handle_message(&payload).await?;
+
+        assert_eq!(msg_callback.handler_messages.lock().await.as_slice(), &[(
+            node1.did(),
+            "alive".as_bytes().to_vec()
+        )]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drop_replayed_message() -> Result<()> {
+        let key1 = SecretKey::random();
+        let key2 = SecretKey::random();
+
+        let msg_callback = MessageCallbackInstance {
+            handler_messages: Arc::new(Mutex::new(vec![])),
+        };
+        let cb: CallbackFn = Box::new(msg_callback.clone());
+
+        let (node1, _path1) = prepare_node_with_callback(key1, None).await;
+        let (node2, _path2) = prepare_node_with_callback(key2, None).await;
+        let handler = MessageHandler::new(node2.dht(), Some(cb), None);
+
+        let payload = MessagePayload::new_send(
+            Message::custom("hello".as_bytes())?,
+            &node1.session_manager(),
+            node2.did(),
+            node2.did(),
+        )?;
+
+        handler.handle_message(&payload).await?;
+        assert!(matches!(
+            handler.handle_message(&payload).await,
+            Err(Error::MessageReplayed)
+        ));
+
+        // Replay the origin message with a fresh hop verification.
+        sleep(Duration::from_millis(2)).await;
+        let resigned = MessagePayload::new(
+            payload.data.clone(),
+            &node1.session_manager(),
+            OriginVerificationGen::Stick(Box::new(payload.origin_verification.clone())),
+            payload.relay.clone(),
+        )?;
+        assert_ne!(resigned.verification, payload.verification);
+        assert!(matches!(
+            handler.handle_message(&resigned).await,
+            Err(Error::MessageReplayed)
+        ));
+
+        assert_eq!(msg_callback.handler_messages.lock().await.as_slice(), &[(
+            node1.did(),
+            "hello".as_bytes().to_vec()
+        )]);
+
+        Ok(())
+    }
+
     pub async fn assert_no_more_msg(node1: &Swarm, node2: &Swarm, node3: &Swarm) {
         tokio::select! {
             _ = node1.listen_once() => unreachable!("node1 should not receive any message"),
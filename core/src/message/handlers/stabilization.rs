@@ -23,10 +23,18 @@ impl HandleMsg<NotifyPredecessorSend> for MessageHandler {
         msg: &NotifyPredecessorSend,
     ) -> Result<Vec<MessageHandlerEvent>> {
         let predecessor = { *self.dht.lock_predecessor()? };
+        let sender = ctx.relay.origin_sender();
+        // The predecessor is leaving and hands over its own predecessor.
+        if predecessor == Some(sender)
+            && msg.did != sender
+            && ctx.origin_authorizer_did()? == sender
+        {
+            self.dht.remove(sender)?;
+        }
         self.dht.notify(msg.did)?;
 
         if let Some(did) = predecessor {
-            if did != ctx.relay.origin_sender() {
+            if did != sender {
                 return Ok(vec![MessageHandlerEvent::SendReportMessage(
                     ctx.clone(),
                     Message::NotifyPredecessorReport(NotifyPredecessorReport { did }),
@@ -3,6 +3,7 @@ use async_recursion::async_recursion;
 use async_trait::async_trait;
 
 use crate::dht::vnode::VirtualNode;
+use crate::dht::vnode::VirtualNodeRange;
 use crate::dht::ChordStorage;
 use crate::dht::ChordStorageCache;
 use crate::dht::Did;
@@ -12,8 +13,10 @@ use crate::dht::PeerRingRemoteAction;
 use crate::error::Error;
 use crate::error::Result;
 use crate::message::types::FoundVNode;
+use crate::message::types::FoundVNodeRange;
 use crate::message::types::Message;
 use crate::message::types::SearchVNode;
+use crate::message::types::SearchVNodeRange;
 use crate::message::types::SyncVNodeWithSuccessor;
 use crate::message::Encoded;
 use crate::message::HandleMsg;
@@ -30,6 +33,9 @@ use crate::swarm::Swarm;
 pub trait ChordStorageInterface<const REDUNDANT: u16> {
     /// fetch virtual node from DHT
     async fn storage_fetch(&self, vid: Did) -> Result<()>;
+    /// fetch at most `count` entries of data of virtual node from `start` on DHT,
+    /// instead of the whole virtual node
+    async fn storage_fetch_range(&self, vid: Did, start: u64, count: u64) -> Result<()>;
     /// store virtual node on DHT
     async fn storage_store(&self, vnode: VirtualNode) -> Result<()>;
     /// append data to Data type virtual node
@@ -46,6 +52,9 @@ pub trait ChordStorageInterfaceCacheChecker {
     ///
     /// Returns an optional `VirtualNode` representing the cached data, or `None` if it is not found.
     async fn storage_check_cache(&self, vid: Did) -> Option<VirtualNode>;
+
+    /// Check the local cache of the DHT for the last fetched range of a virtual node.
+    async fn storage_check_range_cache(&self, vid: Did) -> Option<VirtualNodeRange>;
 }
 
 /// Handle the storage fetch action of the peer ring.
@@ -79,6 +88,45 @@ async fn handle_storage_fetch_act(swarm: &Swarm, act: PeerRingAction) -> Result<
     Ok(())
 }
 
+/// Handle the storage fetch range action of the peer ring.
+#[cfg_attr(feature = "wasm", async_recursion(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_recursion)]
+async fn handle_storage_fetch_range_act(
+    swarm: &Swarm,
+    act: PeerRingAction,
+    start: u64,
+    count: u64,
+) -> Result<()> {
+    match act {
+        PeerRingAction::None => (),
+        PeerRingAction::SomeVNode(v) => {
+            swarm.dht.local_range_cache_set(v.range(start, count));
+        }
+        PeerRingAction::RemoteAction(next, dht_act) => {
+            if let PeerRingRemoteAction::FindVNode(vid) = dht_act {
+                tracing::debug!(
+                    "storage_fetch_range send_message: SearchVNodeRange({:?}) to {:?}",
+                    vid,
+                    next
+                );
+                swarm
+                    .send_message(
+                        Message::SearchVNodeRange(SearchVNodeRange { vid, start, count }),
+                        next,
+                    )
+                    .await?;
+            }
+        }
+        PeerRingAction::MultiActions(acts) => {
+            for act in acts {
+                handle_storage_fetch_range_act(swarm, act, start, count).await?;
+            }
+        }
+        act => return Err(Error::PeerRingUnexpectedAction(act)),
+    }
+    Ok(())
+}
+
 /// Handle the storage store operations of the peer ring.
 #[cfg_attr(feature = "wasm", async_recursion(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_recursion)]
@@ -107,6 +155,11 @@ impl ChordStorageInterfaceCacheChecker for Swarm {
     async fn storage_check_cache(&self, vid: Did) -> Option<VirtualNode> {
         self.dht.local_cache_get(vid)
     }
+
+    /// Check local cache of range
+    async fn storage_check_range_cache(&self, vid: Did) -> Option<VirtualNodeRange> {
+        self.dht.local_range_cache_get(vid)
+    }
 }
 
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
@@ -121,6 +174,14 @@ impl<const REDUNDANT: u16> ChordStorageInterface<REDUNDANT> for Swarm {
         Ok(())
     }
 
+    /// Fetch range of virtual node, if exist in localstoreage, copy the range to the cache,
+    /// else Query Remote Node for the range only
+    async fn storage_fetch_range(&self, vid: Did, start: u64, count: u64) -> Result<()> {
+        let act = <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_lookup(&self.dht, vid).await?;
+        handle_storage_fetch_range_act(self, act, start, count).await?;
+        Ok(())
+    }
+
     /// Store VirtualNode, `TryInto<VirtualNode>` is implemented for alot of types
     async fn storage_store(&self, vnode: VirtualNode) -> Result<()> {
         let op = VNodeOperation::Overwrite(vnode);
@@ -195,6 +256,54 @@ impl HandleMsg<FoundVNode> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<SearchVNodeRange> for MessageHandler {
+    /// Search range of VNode via successor, only data in range is responded.
+    async fn handle(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &SearchVNodeRange,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        // For relay message, set redundant to 1
+        match <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&self.dht, msg.vid).await {
+            Ok(action) => match action {
+                PeerRingAction::None => Ok(vec![]),
+                PeerRingAction::SomeVNode(v) => Ok(vec![MessageHandlerEvent::SendReportMessage(
+                    ctx.clone(),
+                    Message::FoundVNodeRange(FoundVNodeRange {
+                        data: v.range(msg.start, msg.count),
+                    }),
+                )]),
+                PeerRingAction::RemoteAction(next, _) => {
+                    Ok(vec![MessageHandlerEvent::ResetDestination(
+                        ctx.clone(),
+                        next,
+                    )])
+                }
+                act => Err(Error::PeerRingUnexpectedAction(act)),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<FoundVNodeRange> for MessageHandler {
+    async fn handle(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &FoundVNodeRange,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        if self.dht.did != ctx.relay.destination {
+            return Ok(vec![MessageHandlerEvent::ForwardPayload(ctx.clone(), None)]);
+        }
+        self.dht.local_range_cache_set(msg.data.clone());
+        Ok(vec![])
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<VNodeOperation> for MessageHandler {
@@ -463,4 +572,75 @@ mod test {
         tokio::fs::remove_dir_all("./tmp").await.ok();
         Ok(())
     }
+
+    #[cfg(not(feature = "redundant"))]
+    #[tokio::test]
+    async fn test_fetch_range_of_large_vnode() -> Result<()> {
+        let keys = gen_ordered_keys(2);
+        let (key1, key2) = (keys[0], keys[1]);
+        let (node1, _path1) = prepare_node(key1).await;
+        let (node2, _path2) = prepare_node(key2).await;
+        test_only_two_nodes_establish_connection(&node1, &node2).await?;
+
+        let topic = "test_fetch_range_of_large_vnode".to_string();
+        let vid = VirtualNode::gen_did(&topic)?;
+        let data = (0..500)
+            .map(|i| format!("msg{}", i).encode())
+            .collect::<Result<Vec<_>>>()?;
+        let vnode = VirtualNode {
+            did: vid,
+            data: data.clone(),
+            kind: VNodeType::Data,
+        };
+
+        // Make sure the data is stored on node2.
+        let (node1, node2) = if vid.in_range(node2.did(), node2.did(), node1.did()) {
+            (node1, node2)
+        } else {
+            (node2, node1)
+        };
+
+        <Swarm as ChordStorageInterface<1>>::storage_store(&node1, vnode.clone())
+            .await
+            .unwrap();
+        let ev = node2.listen_once().await.unwrap().0;
+        assert!(matches!(
+            ev.data,
+            Message::OperateVNode(VNodeOperation::Overwrite(x)) if x.did == vid
+        ));
+
+        // fetch the whole vnode
+        <Swarm as ChordStorageInterface<1>>::storage_fetch(&node1, vid)
+            .await
+            .unwrap();
+        let ev = node2.listen_once().await.unwrap().0;
+        assert!(matches!(ev.data, Message::SearchVNode(x) if x.vid == vid));
+        let full = node1.listen_once().await.unwrap().0;
+        assert!(matches!(full.data, Message::FoundVNode(ref x) if x.data[0].did == vid));
+        assert_eq!(node1.storage_check_cache(vid).await, Some(vnode));
+
+        // fetch the tail only
+        <Swarm as ChordStorageInterface<1>>::storage_fetch_range(&node1, vid, 490, 20)
+            .await
+            .unwrap();
+        let ev = node2.listen_once().await.unwrap().0;
+        assert!(matches!(
+            ev.data,
+            Message::SearchVNodeRange(x) if x.vid == vid && x.start == 490 && x.count == 20
+        ));
+        let ranged = node1.listen_once().await.unwrap().0;
+        assert!(matches!(ranged.data, Message::FoundVNodeRange(ref x) if x.data.vnode.did == vid));
+
+        let range = node1.storage_check_range_cache(vid).await.unwrap();
+        assert_eq!(range.start, 490);
+        assert_eq!(range.total, 500);
+        assert_eq!(range.vnode.data, data[490..].to_vec());
+
+        let full_len = bincode::serialize(&full.data).unwrap().len();
+        let ranged_len = bincode::serialize(&ranged.data).unwrap().len();
+        assert!(ranged_len * 10 < full_len);
+
+        tokio::fs::remove_dir_all("./tmp").await.ok();
+        Ok(())
+    }
 }
@@ -3,9 +3,12 @@ mod encoder;
 pub use encoder::Decoder;
 pub use encoder::Encoded;
 pub use encoder::Encoder;
+pub use encoder::BINARY_ENCODED_TAG;
+pub use encoder::COMPRESSED_ENCODED_TAG;
 
 mod payload;
 pub use payload::decode_gzip_data;
+pub use payload::decode_gzip_data_with_limit;
 pub use payload::encode_data_gzip;
 pub use payload::from_gzipped_data;
 pub use payload::gzip_data;
@@ -29,3 +32,5 @@ pub use handlers::ValidatorFn;
 
 mod protocols;
 pub use protocols::MessageRelay;
+pub use protocols::MessageVerification;
+pub use protocols::ReplayCache;
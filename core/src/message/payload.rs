@@ -1,10 +1,13 @@
+use std::io::Read;
 use std::io::Write;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use bytes::Bytes;
 use derivative::Derivative;
-use flate2::write::GzDecoder;
+use flate2::read::DeflateDecoder;
+use flate2::read::GzDecoder;
+use flate2::write::DeflateEncoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use serde::de::DeserializeOwned;
@@ -14,10 +17,14 @@ use serde::Serialize;
 use super::encoder::Decoder;
 use super::encoder::Encoded;
 use super::encoder::Encoder;
+use super::encoder::BINARY_ENCODED_TAG;
+use super::encoder::COMPRESSED_ENCODED_TAG;
 use super::protocols::MessageRelay;
 use super::protocols::MessageVerification;
+use crate::consts::DEFAULT_HOP_LIMIT;
 use crate::consts::DEFAULT_TTL_MS;
 use crate::consts::MAX_TTL_MS;
+use crate::consts::TRANSPORT_MAX_SIZE;
 use crate::consts::TS_OFFSET_TOLERANCE_MS;
 use crate::dht::Chord;
 use crate::dht::Did;
@@ -26,6 +33,7 @@ use crate::dht::PeerRingAction;
 use crate::error::Error;
 use crate::error::Result;
 use crate::session::SessionManager;
+use crate::transports::lanes::Priority;
 use crate::utils::get_epoch_ms;
 
 /// Compresses the given data byte slice using the gzip algorithm with the specified compression level.
@@ -44,12 +52,23 @@ where T: Serialize {
 }
 
 /// Decompresses the given gzip-compressed byte slice and returns the decompressed byte slice.
+/// Returns [Error::GzipDecode] if the decompressed data is larger than [TRANSPORT_MAX_SIZE].
 pub fn decode_gzip_data(data: &Bytes) -> Result<Bytes> {
+    decode_gzip_data_with_limit(data, TRANSPORT_MAX_SIZE)
+}
+
+/// Decompresses the given gzip-compressed byte slice, which is inflated to at most `max_bytes`.
+/// Returns [Error::GzipDecode] if the decompressed data is larger than `max_bytes`.
+pub fn decode_gzip_data_with_limit(data: &Bytes, max_bytes: usize) -> Result<Bytes> {
     let mut writer = Vec::new();
-    let mut decoder = GzDecoder::new(writer);
-    decoder.write_all(data).map_err(|_| Error::GzipDecode)?;
-    decoder.try_finish().map_err(|_| Error::GzipDecode)?;
-    writer = decoder.finish().map_err(|_| Error::GzipDecode)?;
+    GzDecoder::new(data.as_ref())
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut writer)
+        .map_err(|_| Error::GzipDecode)?;
+    if writer.len() > max_bytes {
+        tracing::warn!("gzip data inflates beyond {} bytes", max_bytes);
+        return Err(Error::GzipDecode);
+    }
     Ok(writer.into())
 }
 
@@ -61,13 +80,34 @@ where T: DeserializeOwned {
     Ok(m)
 }
 
+/// Deflates data with the best compression, prefixed by [COMPRESSED_ENCODED_TAG].
+fn deflate_tagged(data: &[u8]) -> Result<Vec<u8>> {
+    let mut ec = DeflateEncoder::new(vec![COMPRESSED_ENCODED_TAG], Compression::best());
+    ec.write_all(data).map_err(|_| Error::Encode)?;
+    ec.finish().map_err(|_| Error::Encode)
+}
+
+/// Inflates deflated data, which should not be larger than [TRANSPORT_MAX_SIZE].
+fn inflate_data(data: &[u8]) -> Result<Vec<u8>> {
+    let mut writer = Vec::new();
+    DeflateDecoder::new(data)
+        .take(TRANSPORT_MAX_SIZE as u64 + 1)
+        .read_to_end(&mut writer)
+        .map_err(|_| Error::Decode)?;
+    if writer.len() > TRANSPORT_MAX_SIZE {
+        tracing::warn!("deflated data inflates beyond {} bytes", TRANSPORT_MAX_SIZE);
+        return Err(Error::Decode);
+    }
+    Ok(writer)
+}
+
 /// An enumeration of options for generating origin verification or stick verification.
 /// Verification can be Stick Verification or origin verification.
 /// When MessagePayload created, Origin Verification is always generated.
 /// and if OriginVerificationGen is stick, it can including existing stick ov
 pub enum OriginVerificationGen {
     Origin,
-    Stick(MessageVerification),
+    Stick(Box<MessageVerification>),
 }
 
 /// All messages transmitted in RingsNetwork should be wrapped by MessagePayload.
@@ -93,6 +133,9 @@ pub struct MessagePayload<T> {
     /// Same as verification, but the signature was from the original sender.
     #[derivative(Debug = "ignore")]
     pub origin_verification: MessageVerification,
+    /// Priority lane of transport the payload is sent through, which is local to sender.
+    #[serde(skip)]
+    pub priority: Priority,
 }
 
 impl<T> MessagePayload<T>
@@ -104,9 +147,25 @@ where T: Serialize + DeserializeOwned
         session_manager: &SessionManager,
         origin_verification_gen: OriginVerificationGen,
         relay: MessageRelay,
+    ) -> Result<Self> {
+        Self::new_with_ttl(
+            data,
+            session_manager,
+            origin_verification_gen,
+            relay,
+            DEFAULT_TTL_MS,
+        )
+    }
+
+    /// Create new instance with a specific ttl of verification in milliseconds.
+    pub fn new_with_ttl(
+        data: T,
+        session_manager: &SessionManager,
+        origin_verification_gen: OriginVerificationGen,
+        relay: MessageRelay,
+        ttl_ms: usize,
     ) -> Result<Self> {
         let ts_ms = get_epoch_ms();
-        let ttl_ms = DEFAULT_TTL_MS;
         let msg = &MessageVerification::pack_msg(&data, ts_ms, ttl_ms)?;
         let tx_id = uuid::Uuid::new_v4();
         let addr = session_manager.authorizer_did();
@@ -119,7 +178,7 @@ where T: Serialize + DeserializeOwned
         // If origin_verification_gen is set to Origin, simply clone it into.
         let origin_verification = match origin_verification_gen {
             OriginVerificationGen::Origin => verification.clone(),
-            OriginVerificationGen::Stick(ov) => ov,
+            OriginVerificationGen::Stick(ov) => *ov,
         };
 
         Ok(Self {
@@ -129,6 +188,7 @@ where T: Serialize + DeserializeOwned
             verification,
             origin_verification,
             relay,
+            priority: Priority::default(),
         })
     }
 
@@ -138,13 +198,30 @@ where T: Serialize + DeserializeOwned
         session_manager: &SessionManager,
         next_hop: Did,
         destination: Did,
+    ) -> Result<Self> {
+        Self::new_send_with_ttl(data, session_manager, next_hop, destination, DEFAULT_TTL_MS)
+    }
+
+    /// Create new Payload for send, which will be expired after `ttl_ms` milliseconds.
+    pub fn new_send_with_ttl(
+        data: T,
+        session_manager: &SessionManager,
+        next_hop: Did,
+        destination: Did,
+        ttl_ms: usize,
     ) -> Result<Self> {
         let relay = MessageRelay::new(
             vec![session_manager.authorizer_did()],
             next_hop,
             destination,
         );
-        Self::new(data, session_manager, OriginVerificationGen::Origin, relay)
+        Self::new_with_ttl(
+            data,
+            session_manager,
+            OriginVerificationGen::Origin,
+            relay,
+            ttl_ms,
+        )
     }
 
     /// Checks whether the payload is expired.
@@ -237,14 +314,44 @@ where T: Serialize + DeserializeOwned
     fn encode(&self) -> Result<Encoded> {
         self.to_bincode()?.encode()
     }
+
+    /// Encode deflated bincode, it's much shorter for payloads carrying sdp, such as offers.
+    /// Raw bincode of a [Message](crate::message::Message) payload never leads by
+    /// [COMPRESSED_ENCODED_TAG], since it's led by the variant index of message.
+    fn encode_compressed(&self) -> Result<Encoded> {
+        deflate_tagged(&self.to_bincode()?)?.encode()
+    }
+
+    /// Encode to bincode prefixed by [BINARY_ENCODED_TAG], without the textual expansion of [Encoded].
+    fn encode_bytes(&self) -> Result<Bytes> {
+        let bincode = self.to_bincode()?;
+        let mut data = Vec::with_capacity(bincode.len() + 1);
+        data.push(BINARY_ENCODED_TAG);
+        data.extend_from_slice(&bincode);
+        Ok(data.into())
+    }
 }
 
 impl<T> Decoder for MessagePayload<T>
 where T: Serialize + DeserializeOwned
 {
+    /// Decode either deflated bincode leading by [COMPRESSED_ENCODED_TAG], or raw bincode.
     fn from_encoded(encoded: &Encoded) -> Result<Self> {
         let v: Bytes = encoded.decode()?;
-        Self::from_bincode(&v)
+        match v.split_first() {
+            Some((&COMPRESSED_ENCODED_TAG, deflated)) => {
+                Self::from_bincode(&inflate_data(deflated)?)
+            }
+            _ => Self::from_bincode(&v),
+        }
+    }
+
+    /// Decode either binary form leading by [BINARY_ENCODED_TAG], or the bytes of textual [Encoded].
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        match data.split_first() {
+            Some((&BINARY_ENCODED_TAG, bincode)) => Self::from_bincode(bincode),
+            _ => Self::from_encoded(&Encoded::try_from(data.to_vec())?),
+        }
     }
 }
 
@@ -255,9 +362,13 @@ pub trait PayloadSender<T>
 where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
 {
     /// Get the session manager
-    fn session_manager(&self) -> &SessionManager;
+    fn session_manager(&self) -> Arc<SessionManager>;
     /// Get access to DHT.
     fn dht(&self) -> Arc<PeerRing>;
+    /// Count of hops a message sent by this sender can be forwarded.
+    fn hop_limit(&self) -> u8 {
+        DEFAULT_HOP_LIMIT
+    }
     /// Send a message payload to a specified DID.
     async fn do_send_payload(&self, did: Did, payload: MessagePayload<T>) -> Result<()>;
     /// Infer the next hop for a message by calling `dht.find_successor()`.
@@ -279,10 +390,55 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
 
     /// Send a message to a specified destination.
     async fn send_message(&self, msg: T, destination: Did) -> Result<uuid::Uuid> {
+        self.send_message_with_ttl(msg, destination, DEFAULT_TTL_MS)
+            .await
+    }
+
+    /// Send a message to a specified destination, the message will be dropped by receiver
+    /// after `ttl_ms` milliseconds.
+    async fn send_message_with_ttl(
+        &self,
+        msg: T,
+        destination: Did,
+        ttl_ms: usize,
+    ) -> Result<uuid::Uuid> {
+        self.route_message_with_ttl(msg, destination, ttl_ms)
+            .await
+            .map(|(tx_id, _)| tx_id)
+    }
+
+    /// Send a message to a specified destination like `send_message_with_ttl`,
+    /// return tx_id of message and the next hop inferred for it.
+    async fn route_message_with_ttl(
+        &self,
+        msg: T,
+        destination: Did,
+        ttl_ms: usize,
+    ) -> Result<(uuid::Uuid, Did)> {
+        self.route_message_with_priority(msg, destination, ttl_ms, Priority::Normal)
+            .await
+    }
+
+    /// Send a message like `route_message_with_ttl`, through the transport lane of priority.
+    async fn route_message_with_priority(
+        &self,
+        msg: T,
+        destination: Did,
+        ttl_ms: usize,
+        priority: Priority,
+    ) -> Result<(uuid::Uuid, Did)> {
         let next_hop = self.infer_next_hop(None, destination)?;
-        let payload = MessagePayload::new_send(msg, self.session_manager(), next_hop, destination)?;
+        let mut payload = MessagePayload::new_send_with_ttl(
+            msg,
+            &self.session_manager(),
+            next_hop,
+            destination,
+            ttl_ms,
+        )?;
+        payload.relay.hop_limit = self.hop_limit();
+        payload.priority = priority;
         self.send_payload(payload.clone()).await?;
-        Ok(payload.tx_id)
+        Ok((payload.tx_id, next_hop))
     }
 
     /// Send a message to a specified destination by specified next hop.
@@ -292,30 +448,38 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
         destination: Did,
         next_hop: Did,
     ) -> Result<uuid::Uuid> {
-        let payload = MessagePayload::new_send(msg, self.session_manager(), next_hop, destination)?;
+        let mut payload =
+            MessagePayload::new_send(msg, &self.session_manager(), next_hop, destination)?;
+        payload.relay.hop_limit = self.hop_limit();
         self.send_payload(payload.clone()).await?;
         Ok(payload.tx_id)
     }
 
     /// Send a direct message to a specified destination.
     async fn send_direct_message(&self, msg: T, destination: Did) -> Result<uuid::Uuid> {
-        let payload =
-            MessagePayload::new_send(msg, self.session_manager(), destination, destination)?;
+        let mut payload =
+            MessagePayload::new_send(msg, &self.session_manager(), destination, destination)?;
+        payload.relay.hop_limit = self.hop_limit();
         self.send_payload(payload.clone()).await?;
         Ok(payload.tx_id)
     }
 
-    /// Send a report message to a specified destination.
+    /// Send a report message to a specified destination, through the high priority lane
+    /// since reports such as acks are small and awaited by their senders.
     async fn send_report_message(&self, payload: &MessagePayload<T>, msg: T) -> Result<()> {
-        let relay = payload.relay.report(self.dht().did)?;
+        let relay = payload
+            .relay
+            .report(self.dht().did)?
+            .with_hop_limit(self.hop_limit());
 
         let mut pl = MessagePayload::new(
             msg,
-            self.session_manager(),
+            &self.session_manager(),
             OriginVerificationGen::Origin,
             relay,
         )?;
         pl.tx_id = payload.tx_id;
+        pl.priority = Priority::High;
 
         self.send_payload(pl).await
     }
@@ -329,8 +493,8 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
     ) -> Result<()> {
         let mut new_pl = MessagePayload::new(
             payload.data.clone(),
-            self.session_manager(),
-            OriginVerificationGen::Stick(payload.origin_verification.clone()),
+            &self.session_manager(),
+            OriginVerificationGen::Stick(Box::new(payload.origin_verification.clone())),
             relay,
         )?;
         new_pl.tx_id = payload.tx_id;
@@ -344,7 +508,19 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static
         next_hop: Option<Did>,
     ) -> Result<()> {
         let next_hop = self.infer_next_hop(next_hop, payload.relay.destination)?;
-        let relay = payload.relay.forward(self.dht().did, next_hop)?;
+        let relay = payload
+            .relay
+            .forward(self.dht().did, next_hop)
+            .map_err(|e| {
+                if let Error::HopLimitExceeded = e {
+                    tracing::warn!(
+                        "Drop message {} exceeding hop limit, relay path: {:?}",
+                        payload.tx_id,
+                        payload.relay.path
+                    );
+                }
+                e
+            })?;
         self.forward_by_relay(payload, relay).await
     }
 
@@ -363,6 +539,7 @@ pub mod test {
     use rand::Rng;
 
     use super::*;
+    use crate::consts::TRANSPORT_MTU;
     use crate::ecc::SecretKey;
     use crate::message::Message;
 
@@ -401,6 +578,23 @@ pub mod test {
         assert!(payload.verify());
     }
 
+    #[test]
+    fn test_payload_expired_with_ttl() {
+        let key = SecretKey::random();
+        let session = SessionManager::new_with_seckey(&key).unwrap();
+        let did = SecretKey::random().address().into();
+
+        let payload = MessagePayload::new_send_with_ttl(1, &session, did, did, 1).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(payload.origin_verification.is_expired());
+        assert!(payload.verification.is_expired());
+        assert!(!payload.verify());
+
+        let payload = MessagePayload::new_send(1, &session, did, did).unwrap();
+        assert!(!payload.origin_verification.is_expired());
+        assert!(payload.verify());
+    }
+
     #[test]
     fn test_message_payload_from_auto() {
         let next_hop = SecretKey::random().address().into();
@@ -415,6 +609,73 @@ pub mod test {
         assert_eq!(payload, payload2);
     }
 
+    #[test]
+    fn test_decode_gzip_data_bound() {
+        let data = Bytes::from(vec![0u8; TRANSPORT_MAX_SIZE]);
+        let gzipped = encode_data_gzip(&data, 6).unwrap();
+        assert_eq!(decode_gzip_data(&gzipped).unwrap(), data);
+
+        // a small gzip bomb is rejected
+        let data = Bytes::from(vec![0u8; TRANSPORT_MAX_SIZE + 1]);
+        let gzipped = encode_data_gzip(&data, 6).unwrap();
+        assert!(gzipped.len() < TRANSPORT_MTU);
+        assert!(matches!(decode_gzip_data(&gzipped), Err(Error::GzipDecode)));
+
+        // a larger limit is given by the caller
+        let limit = TRANSPORT_MAX_SIZE * 4;
+        assert_eq!(decode_gzip_data_with_limit(&gzipped, limit).unwrap(), data);
+        let data = Bytes::from(vec![0u8; limit + 1]);
+        let gzipped = encode_data_gzip(&data, 6).unwrap();
+        assert!(matches!(
+            decode_gzip_data_with_limit(&gzipped, limit),
+            Err(Error::GzipDecode)
+        ));
+    }
+
+    #[test]
+    fn test_message_payload_binary_encoding() {
+        let next_hop = SecretKey::random().address().into();
+        let payload = new_test_payload(next_hop);
+
+        let binary = payload.encode_bytes().unwrap();
+        assert_eq!(binary[0], BINARY_ENCODED_TAG);
+        let text: Vec<u8> = payload.encode().unwrap().into();
+        assert!(binary.len() < text.len());
+
+        let payload1 = MessagePayload::<TestData>::from_bytes(&binary).unwrap();
+        let payload2 = MessagePayload::<TestData>::from_bytes(&text).unwrap();
+        assert_eq!(payload1, payload);
+        assert_eq!(payload2, payload);
+        assert!(MessagePayload::<TestData>::from_bytes(&binary[..binary.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_message_payload_compressed_encoding() {
+        let next_hop = SecretKey::random().address().into();
+        let msg = Message::custom(&[1u8; 1024]).unwrap();
+        let payload = new_payload(msg, next_hop);
+
+        let raw = payload.encode().unwrap();
+        let compressed = payload.encode_compressed().unwrap();
+        assert!(compressed.len() < raw.len());
+        let data: Vec<u8> = compressed.decode().unwrap();
+        assert_eq!(data[0], COMPRESSED_ENCODED_TAG);
+
+        // Both are decoded, from text or bytes of text.
+        for encoded in [raw, compressed] {
+            assert_eq!(MessagePayload::from_encoded(&encoded).unwrap(), payload);
+            let text: Vec<u8> = encoded.into();
+            assert_eq!(MessagePayload::from_bytes(&text).unwrap(), payload);
+        }
+
+        // A compressed bomb is rejected.
+        let bomb = deflate_tagged(&vec![0u8; TRANSPORT_MAX_SIZE + 1]).unwrap();
+        assert!(matches!(
+            MessagePayload::<Message>::from_encoded(&bomb.encode().unwrap()),
+            Err(Error::Decode)
+        ));
+    }
+
     #[test]
     fn test_message_payload_encode_len() {
         let next_hop = SecretKey::random().address().into();
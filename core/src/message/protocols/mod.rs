@@ -1,5 +1,7 @@
 mod relay;
+mod replay;
 mod verify;
 
 pub use self::relay::MessageRelay;
+pub use self::replay::ReplayCache;
 pub use self::verify::MessageVerification;
@@ -4,6 +4,7 @@ use itertools::izip;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::consts::DEFAULT_HOP_LIMIT;
 use crate::dht::Did;
 use crate::error::Error;
 use crate::error::Result;
@@ -14,6 +15,7 @@ use crate::error::Result;
 /// By calling `relay` method in correct place, `MessageRelay` help to do things:
 /// - Record the whole transport path for inspection.
 /// - Get the sender of a message.
+/// - Limit the count of hops, so a message looping among nodes will be dropped.
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct MessageRelay {
     /// A push only stack. Record routes when handling messages.
@@ -26,20 +28,35 @@ pub struct MessageRelay {
     /// The destination of the message.
     /// It may help the handler to find out `next_hop` in some situations.
     pub destination: Did,
+
+    /// The count of hops the message can still be forwarded, decremented by each `forward`.
+    ///
+    /// Payloads are encoded by bincode, which has no field names to fall back on a default,
+    /// so this field breaks the wire protocol: nodes with and without it can't decode
+    /// messages of each other.
+    pub hop_limit: u8,
 }
 
 impl MessageRelay {
-    /// Create a new `MessageRelay`.
+    /// Create a new `MessageRelay`, which can be forwarded [DEFAULT_HOP_LIMIT] times.
     pub fn new(path: Vec<Did>, next_hop: Did, destination: Did) -> Self {
         Self {
             path,
             next_hop,
             destination,
+            hop_limit: DEFAULT_HOP_LIMIT,
         }
     }
 
+    /// Set the count of hops the message can be forwarded.
+    pub fn with_hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
     /// Validate relay, then create a new `MessageRelay` that have `current` did in the end of path.
     /// The new relay will use `next_hop` as `next_hop` and `self.destination` as `destination`.
+    /// It fails if the hop limit is exhausted, so the message should be dropped.
     pub fn forward(&self, current: Did, next_hop: Did) -> Result<Self> {
         self.validate(current)?;
 
@@ -47,6 +64,10 @@ impl MessageRelay {
             return Err(Error::InvalidNextHop);
         }
 
+        if self.hop_limit == 0 {
+            return Err(Error::HopLimitExceeded);
+        }
+
         let mut path = self.path.clone();
         path.push(current);
 
@@ -54,12 +75,14 @@ impl MessageRelay {
             path,
             next_hop,
             destination: self.destination,
+            hop_limit: self.hop_limit - 1,
         })
     }
 
     /// Validate relay, then create a new `MessageRelay` that used to report the message.
     /// The new relay will use `self.path[self.path.len() - 1]` as `next_hop` and `self.sender()` as `destination`.
-    /// In the new relay, the path will be cleared and only have `current` did.
+    /// In the new relay, the path will be cleared and only have `current` did,
+    /// and the hop limit is reset to [DEFAULT_HOP_LIMIT].
     pub fn report(&self, current: Did) -> Result<Self> {
         self.validate(current)?;
 
@@ -71,6 +94,7 @@ impl MessageRelay {
             path: vec![current],
             next_hop: self.path[self.path.len() - 1],
             destination: self.origin_sender(),
+            hop_limit: DEFAULT_HOP_LIMIT,
         })
     }
 
@@ -166,6 +190,43 @@ where T: PartialEq + std::fmt::Debug {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::ecc::SecretKey;
+
+    #[test]
+    fn test_hop_limit() {
+        let dids = (0..3)
+            .map(|_| Did::from(SecretKey::random().address()))
+            .collect::<Vec<_>>();
+        let destination = Did::from(SecretKey::random().address());
+
+        // A cyclic forwarding among 3 nodes, which never reaches destination.
+        let mut relay = MessageRelay::new(vec![dids[0]], dids[1], destination).with_hop_limit(4);
+        let mut forwarded = 0;
+        let err = loop {
+            let current = relay.next_hop;
+            let next_hop = dids[(dids.iter().position(|d| *d == current).unwrap() + 1) % 3];
+            match relay.forward(current, next_hop) {
+                Ok(r) => {
+                    assert_eq!(r.hop_limit, relay.hop_limit - 1);
+                    relay = r;
+                    forwarded += 1;
+                }
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(err, Error::HopLimitExceeded));
+        assert_eq!(forwarded, 4);
+        // The path is recorded for diagnostics.
+        assert_eq!(relay.path, vec![
+            dids[0], dids[1], dids[2], dids[0], dids[1]
+        ]);
+
+        // Report travels with a fresh budget.
+        assert_eq!(
+            relay.report(relay.next_hop).unwrap().hop_limit,
+            DEFAULT_HOP_LIMIT
+        );
+    }
 
     #[test]
     #[rustfmt::skip]
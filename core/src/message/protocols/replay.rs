@@ -0,0 +1,141 @@
+#![warn(missing_docs)]
+//! A bounded cache of seen message verifications, to reject replayed messages.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use super::MessageVerification;
+use crate::consts::DEFAULT_REPLAY_CACHE_CAPACITY;
+use crate::consts::MAX_TTL_MS;
+use crate::dht::Did;
+use crate::utils::get_epoch_ms;
+
+/// Identify a signed verification by `(session_id, ts_ms, sha256(sig))`.
+type ReplayKey = (Did, u128, [u8; 32]);
+
+/// ReplayCache records recently seen verifications, a verification seen twice is a replay.
+///
+/// Entries are evicted when they are older than `window_ms`, or when the cache is
+/// larger than `capacity`, in which case the oldest entries are evicted first.
+/// The `window_ms` should not be less than the ttl of messages,
+/// otherwise a message can be replayed after it's evicted but before it's expired.
+#[derive(Debug, Clone)]
+pub struct ReplayCache {
+    capacity: usize,
+    window_ms: u128,
+    seen: HashSet<ReplayKey>,
+    order: VecDeque<(u128, ReplayKey)>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_CACHE_CAPACITY, MAX_TTL_MS)
+    }
+}
+
+impl ReplayCache {
+    /// Create a ReplayCache with capacity and window in milliseconds.
+    pub fn new(capacity: usize, window_ms: usize) -> Self {
+        Self {
+            capacity,
+            window_ms: window_ms as u128,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Number of entries in cache.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Check cache is empty or not.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Check and record a verification.
+    /// Return false if the verification is already seen.
+    pub fn check(&mut self, verification: &MessageVerification) -> bool {
+        self.check_at(verification, get_epoch_ms())
+    }
+
+    fn check_at(&mut self, verification: &MessageVerification, now: u128) -> bool {
+        self.evict(now);
+        let key = (
+            verification.session.session_id(),
+            verification.ts_ms,
+            Sha256::digest(&verification.sig).into(),
+        );
+        if !self.seen.insert(key) {
+            return false;
+        }
+        self.order.push_back((now, key));
+        if self.order.len() > self.capacity {
+            if let Some((_, key)) = self.order.pop_front() {
+                self.seen.remove(&key);
+            }
+        }
+        true
+    }
+
+    fn evict(&mut self, now: u128) {
+        while let Some((seen_at, key)) = self.order.front() {
+            if now < seen_at + self.window_ms {
+                break;
+            }
+            self.seen.remove(key);
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecc::SecretKey;
+    use crate::message::MessagePayload;
+    use crate::session::SessionManager;
+
+    fn new_verification(data: u64) -> MessageVerification {
+        let key = SecretKey::random();
+        let session = SessionManager::new_with_seckey(&key).unwrap();
+        let did = SecretKey::random().address().into();
+        MessagePayload::new_send(data, &session, did, did)
+            .unwrap()
+            .verification
+    }
+
+    #[test]
+    fn test_replay_cache_reject_seen() {
+        let mut cache = ReplayCache::default();
+        let v1 = new_verification(1);
+        let v2 = new_verification(2);
+        assert!(cache.check(&v1));
+        assert!(cache.check(&v2));
+        assert!(!cache.check(&v1));
+        assert!(!cache.check(&v2));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_replay_cache_evict() {
+        let mut cache = ReplayCache::new(2, 1000);
+        let vs = (0..3).map(new_verification).collect::<Vec<_>>();
+
+        // evict by size
+        assert!(cache.check_at(&vs[0], 0));
+        assert!(cache.check_at(&vs[1], 0));
+        assert!(cache.check_at(&vs[2], 0));
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.check_at(&vs[2], 0));
+        assert!(cache.check_at(&vs[0], 0));
+
+        // evict by age
+        assert!(cache.check_at(&vs[1], 1000));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -3,6 +3,8 @@
 
 use std::fmt::Write;
 
+#[cfg(feature = "std")]
+use rayon::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -11,14 +13,20 @@ use crate::ecc::PublicKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::session::Session;
+use crate::session::SessionKeyAlgorithm;
+use crate::utils;
 
 /// Message Verification is based on session, and sig.
 /// it also included ttl time and created ts.
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct MessageVerification {
+    /// session of signer, whose session key signs the message
     pub session: Session,
+    /// time to live of message in milliseconds
     pub ttl_ms: usize,
+    /// timestamp in milliseconds when message is created
     pub ts_ms: u128,
+    /// signature of message, signed by session key
     pub sig: Vec<u8>,
 }
 
@@ -39,9 +47,34 @@ impl MessageVerification {
             .is_ok()
     }
 
+    /// Verify a batch of MessageVerification with their data, return results in the order of items.
+    /// Items are verified in parallel on native, and sequentially on wasm.
+    pub fn verify_batch<T>(items: &[(&MessageVerification, &T)]) -> Vec<bool>
+    where T: Serialize + Sync {
+        #[cfg(feature = "std")]
+        let iter = items.par_iter();
+        #[cfg(not(feature = "std"))]
+        let iter = items.iter();
+        iter.map(|(verification, data)| verification.verify(*data))
+            .collect()
+    }
+
+    /// Check message is expired or not.
+    pub fn is_expired(&self) -> bool {
+        let now = utils::get_epoch_ms();
+        now > self.ts_ms + self.ttl_ms as u128
+    }
+
     /// Recover publickey from packed message.
+    /// Only secp256k1 signatures are recoverable, sessions signing with ed25519 will fail.
     pub fn session_pubkey<T>(&self, data: &T) -> Result<PublicKey>
     where T: Serialize {
+        let algorithm = self.session.key_algorithm();
+        if algorithm != SessionKeyAlgorithm::Secp256k1 {
+            return Err(Error::SessionPubkeyUnrecoverable(
+                algorithm.name().to_string(),
+            ));
+        }
         let msg = self.msg(data)?;
         signers::secp256k1::recover(&msg, &self.sig)
     }
@@ -60,3 +93,43 @@ impl MessageVerification {
         Self::pack_msg(data, self.ts_ms, self.ttl_ms)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::consts::DEFAULT_TTL_MS;
+    use crate::ecc::SecretKey;
+    use crate::session::SessionManager;
+
+    #[test]
+    fn test_verify_batch() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let other = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let sign = |sm: &SessionManager, data: &String| {
+            let ts_ms = utils::get_epoch_ms();
+            let msg = MessageVerification::pack_msg(data, ts_ms, DEFAULT_TTL_MS).unwrap();
+            MessageVerification {
+                session: sm.session(),
+                sig: sm.sign(&msg).unwrap(),
+                ttl_ms: DEFAULT_TTL_MS,
+                ts_ms,
+            }
+        };
+
+        let data = (0..8).map(|i| format!("data {}", i)).collect::<Vec<_>>();
+        let mut verifications = data.iter().map(|d| sign(&sm, d)).collect::<Vec<_>>();
+        // Signed other data, by session of other key, and corrupted signature.
+        verifications[1] = sign(&sm, &data[0]);
+        verifications[4].session = other.session();
+        verifications[6].sig[10] ^= 1;
+
+        let items = verifications.iter().zip(data.iter()).collect::<Vec<_>>();
+        let results = MessageVerification::verify_batch(&items);
+        let expected = items.iter().map(|(v, d)| v.verify(*d)).collect::<Vec<_>>();
+        assert_eq!(results, expected);
+        assert_eq!(results, vec![
+            true, false, true, true, false, true, false, true
+        ]);
+        assert!(MessageVerification::verify_batch::<String>(&[]).is_empty());
+    }
+}
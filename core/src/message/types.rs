@@ -8,6 +8,7 @@ use serde::Serialize;
 
 use crate::dht::vnode::VNodeOperation;
 use crate::dht::vnode::VirtualNode;
+use crate::dht::vnode::VirtualNodeRange;
 use crate::dht::Did;
 use crate::dht::TopoInfo;
 use crate::error::Result;
@@ -37,6 +38,24 @@ pub struct ConnectNodeReport {
     pub answer: HandshakeInfo,
 }
 
+/// MessageType use to restart ice of an established transport, send to remote with new offer.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct IceRestartSend {
+    /// uuid of transport
+    pub transport_uuid: String,
+    /// sdp offer of webrtc with new ice credentials
+    pub offer: HandshakeInfo,
+}
+
+/// MessageType report to origin with answer of ice restart.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct IceRestartReport {
+    /// uuid of transport, same as the one in IceRestartSend
+    pub transport_uuid: String,
+    /// sdp answer of webrtc
+    pub answer: HandshakeInfo,
+}
+
 /// MessageType use to find successor in a chord ring.
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct FindSuccessorSend {
@@ -163,6 +182,24 @@ pub struct FoundVNode {
     pub data: Vec<VirtualNode>,
 }
 
+/// MessageType use to search a range of data of virtual node.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SearchVNodeRange {
+    /// The virtual id of searching target
+    pub vid: Did,
+    /// Index of the first entry of data
+    pub start: u64,
+    /// Max count of entries of data
+    pub count: u64,
+}
+
+/// MessageType report to origin found range of virtual node.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FoundVNodeRange {
+    /// Response of [SearchVNodeRange], containing data in range only
+    pub data: VirtualNodeRange,
+}
+
 /// MessageType after `FindSuccessorSend` and syncing data.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct SyncVNodeWithSuccessor {
@@ -230,6 +267,14 @@ pub enum Message {
     QueryForTopoInfoSend(QueryForTopoInfoSend),
     /// Response of QueryForTopoInfoSend
     QueryForTopoInfoReport(QueryForTopoInfoReport),
+    /// Remote message of restarting ice of an established transport.
+    IceRestartSend(IceRestartSend),
+    /// Response of IceRestartSend
+    IceRestartReport(IceRestartReport),
+    /// Remote message of search a range of data of virtual node.
+    SearchVNodeRange(SearchVNodeRange),
+    /// Response when found a range of data of virtual node.
+    FoundVNodeRange(FoundVNodeRange),
 }
 
 impl std::fmt::Display for Message {
@@ -22,12 +22,16 @@ pub use web_sys;
 #[cfg(feature = "wasm")]
 pub use web_sys::RtcIceConnectionState as RTCIceConnectionState;
 #[cfg(feature = "wasm")]
+pub use web_sys::RtcIceGatheringState as RTCIceGatheringState;
+#[cfg(feature = "wasm")]
 pub use web_sys::RtcSdpType as RTCSdpType;
 #[cfg(not(feature = "wasm"))]
 pub use webrtc;
 #[cfg(not(feature = "wasm"))]
 pub use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
 #[cfg(not(feature = "wasm"))]
+pub use webrtc::ice_transport::ice_gathering_state::RTCIceGatheringState;
+#[cfg(not(feature = "wasm"))]
 pub use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 #[cfg(not(feature = "wasm"))]
 pub use webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
@@ -10,23 +10,108 @@
 //!
 //! See [SessionManager] and [SessionManagerBuilder] for details.
 
+use std::collections::HashSet;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
 use rings_derive::wasm_export;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::consts::DEFAULT_SESSION_CLOCK_SKEW_MS;
+use crate::consts::DEFAULT_SESSION_MAX_TTL_MS;
 use crate::consts::DEFAULT_SESSION_TTL_MS;
 use crate::dht::Did;
 use crate::ecc::signers;
+use crate::ecc::signers::eip1271::Eip1271Provider;
 use crate::ecc::PublicKey;
 use crate::ecc::SecretKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::utils;
 
-fn pack_session(session_id: Did, ts_ms: u128, ttl_ms: usize) -> String {
-    format!("{}\n{}\n{}", session_id, ts_ms, ttl_ms)
+/// The first version of [SessionPayload], which is packed as lines of
+/// session_id, ts_ms, ttl_ms and comma joined scopes if they are set.
+pub const SESSION_PAYLOAD_V1: u8 = 1;
+
+/// The version of [SessionPayload] packed by [SessionManagerBuilder].
+pub const SESSION_PAYLOAD_VERSION: u8 = SESSION_PAYLOAD_V1;
+
+fn default_payload_version() -> u8 {
+    SESSION_PAYLOAD_V1
+}
+
+/// SessionPayload is the content of [Session] signed by authorizers.
+///
+/// It's packed into a string for signing, and parsed back by `from_str`.
+/// A version other than v1 is packed with a leading line of `v{version}`,
+/// while v1 is packed without it, so signatures of existing sessions are still valid.
+/// Unknown versions are rejected by both packing and parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionPayload {
+    /// Version of payload format.
+    pub version: u8,
+    /// Did of session key.
+    pub session_id: Did,
+    /// Timestamp when session created.
+    pub ts_ms: u128,
+    /// Session's lifetime.
+    pub ttl_ms: usize,
+    /// Scopes granted to session, all scopes if it's `None`.
+    pub scopes: Option<Vec<String>>,
+}
+
+impl SessionPayload {
+    /// Pack the payload into a string for signing.
+    pub fn pack(&self) -> Result<String> {
+        match self.version {
+            SESSION_PAYLOAD_V1 => Ok(self.pack_v1()),
+            version => Err(Error::UnsupportedSessionPayloadVersion(version)),
+        }
+    }
+
+    /// Scopes are appended only if they are set, so sessions without scopes are packed as before.
+    fn pack_v1(&self) -> String {
+        match &self.scopes {
+            Some(scopes) => format!(
+                "{}\n{}\n{}\n{}",
+                self.session_id,
+                self.ts_ms,
+                self.ttl_ms,
+                scopes.join(",")
+            ),
+            None => format!("{}\n{}\n{}", self.session_id, self.ts_ms, self.ttl_ms),
+        }
+    }
+}
+
+impl FromStr for SessionPayload {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidSessionPayload(s.to_string());
+        if let Some(rest) = s.strip_prefix('v') {
+            let version = rest.split('\n').next().unwrap_or_default();
+            let version = u8::from_str(version).map_err(|_| invalid())?;
+            return Err(Error::UnsupportedSessionPayloadVersion(version));
+        }
+
+        let fields = s.splitn(4, '\n').collect::<Vec<_>>();
+        if fields.len() < 3 {
+            return Err(invalid());
+        }
+        let scopes = fields.get(3).map(|scopes| match *scopes {
+            "" => vec![],
+            scopes => scopes.split(',').map(|s| s.to_string()).collect(),
+        });
+        Ok(Self {
+            version: SESSION_PAYLOAD_V1,
+            session_id: Did::from_str(fields[0]).map_err(|_| invalid())?,
+            ts_ms: u128::from_str(fields[1]).map_err(|_| invalid())?,
+            ttl_ms: usize::from_str(fields[2]).map_err(|_| invalid())?,
+            scopes,
+        })
+    }
 }
 
 /// SessionManagerBuilder is used to build a [SessionManager].
@@ -38,16 +123,26 @@ fn pack_session(session_id: Did, ts_ms: u128, ttl_ms: usize) -> String {
 #[wasm_export]
 pub struct SessionManagerBuilder {
     session_key: SecretKey,
+    /// Algorithm of signatures signed by session key.
+    key_algorithm: SessionKeyAlgorithm,
     /// Authorizer of session.
     authorizer_entity: String,
     /// Authorizer of session.
     authorizer_type: String,
-    /// Session's lifetime
+    /// Session's lifetime requested by `ttl`, which may exceed `max_ttl_ms`.
     ttl_ms: usize,
+    /// Max lifetime of session allowed by policy.
+    max_ttl_ms: usize,
     /// Timestamp when session created
     ts_ms: u128,
     /// Signature
     sig: Vec<u8>,
+    /// Cosigners of multisig session, with their entities, types and signatures.
+    cosigners: Vec<(String, String, Vec<u8>)>,
+    /// Count of authorizers required to sign a multisig session.
+    threshold: Option<usize>,
+    /// Scopes granted to session, all scopes if it's `None`.
+    scopes: Option<Vec<String>>,
 }
 
 /// SessionManager holds the [Session] and its delegated private key.
@@ -79,16 +174,129 @@ pub struct Session {
     session_id: Did,
     /// Authorizer of session
     authorizer: Authorizer,
+    /// Algorithm of signatures signed by session key, sessions dumped without it are secp256k1.
+    #[serde(default)]
+    key_algorithm: SessionKeyAlgorithm,
+    /// Version of [SessionPayload] signed by authorizer, sessions dumped without it are v1.
+    #[serde(default = "default_payload_version")]
+    payload_version: u8,
     /// Session's lifetime
     ttl_ms: usize,
     /// Timestamp when session created
     ts_ms: u128,
     /// Signature to verify that the session was signed by the authorizer.
     sig: Vec<u8>,
+    /// Cosigners of session, `None` for a session of single authorizer.
+    /// It's boxed to keep the size of session for single authorizer.
+    #[serde(default)]
+    multisig: Option<Box<Multisig>>,
+    /// Scopes granted to session, which are signed as part of packed session.
+    /// `None` for a session granted all scopes.
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
+    /// Public key of session key, which messages are encrypted to.
+    /// Sessions dumped without it can't be encrypted to.
+    #[serde(default)]
+    session_pubkey: Option<SessionPubkey>,
+    /// Public key of authorizer recovered from signature, which is memoized since the inputs are immutable.
+    /// It's not serialized, so a deserialized session starts without it.
+    #[serde(skip)]
+    authorizer_pubkey: PubkeyCache,
+}
+
+/// Public key of session key with its signature signed by session key.
+///
+/// The signature is verified against session_id, which is signed by authorizer,
+/// so the public key is bound to the session as well as the payload.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+struct SessionPubkey {
+    pubkey: PublicKey,
+    sig: Vec<u8>,
+}
+
+impl SessionPubkey {
+    fn new(key: SecretKey, algorithm: SessionKeyAlgorithm) -> Self {
+        let pubkey = key.pubkey();
+        Self {
+            sig: algorithm.sign(key, &Self::pack(&pubkey)),
+            pubkey,
+        }
+    }
+
+    /// Pack the public key into the message signed by session key.
+    fn pack(pubkey: &PublicKey) -> String {
+        format!("session pubkey\n{}", hex::encode(pubkey.0))
+    }
+}
+
+/// Memoized public key of authorizer, which is ignored when comparing sessions.
+#[derive(Debug, Clone, Default)]
+struct PubkeyCache(OnceLock<PublicKey>);
+
+impl PartialEq for PubkeyCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PubkeyCache {}
+
+/// Cosigners of a session authorized by N-of-M authorizers, which sign the same packed session.
+///
+/// The authorizer of [Session] is always one of the N signers, since the did of session is derived from it.
+/// A session of single authorizer is the case of no cosigner and threshold 1.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub struct Multisig {
+    /// Other authorizers with their signatures, the signature is empty if it's not signed.
+    pub cosigners: Vec<(Authorizer, Vec<u8>)>,
+    /// Count of authorizers required to sign session, including the authorizer of session.
+    pub threshold: usize,
+}
+
+impl Multisig {
+    /// Check threshold is in range of 1 to the count of all authorizers.
+    fn validate(&self) -> Result<()> {
+        let total = self.cosigners.len() + 1;
+        if self.threshold == 0 || self.threshold > total {
+            return Err(Error::InvalidMultisigThreshold(self.threshold, total));
+        }
+        Ok(())
+    }
+}
+
+/// A set of revoked sessions, keyed by their session_id.
+///
+/// A session can be revoked before it expires, such as when its delegated key is compromised.
+/// Use `verify_self_with_revocations` of [Session] to reject revoked sessions.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct RevocationSet {
+    revoked: HashSet<Did>,
+}
+
+impl RevocationSet {
+    /// Revoke a session, return false if it's already revoked.
+    pub fn revoke(&mut self, session_id: Did) -> bool {
+        self.revoked.insert(session_id)
+    }
+
+    /// Check the session is revoked or not.
+    pub fn is_revoked(&self, session_id: &Did) -> bool {
+        self.revoked.contains(session_id)
+    }
+
+    /// Count of revoked sessions.
+    pub fn len(&self) -> usize {
+        self.revoked.len()
+    }
+
+    /// Check there is no revoked session.
+    pub fn is_empty(&self) -> bool {
+        self.revoked.is_empty()
+    }
 }
 
 /// We will support as many protocols/algorithms as possible.
-/// Currently, it comprises Secp256k1, EIP191, BIP137, and Ed25519.
+/// Currently, it comprises Secp256k1, EIP191, EIP712, BIP137, BIP340, Ed25519 and EIP1271.
 /// We welcome any issues and PRs for additional implementations.
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
 pub enum Authorizer {
@@ -96,12 +304,86 @@ pub enum Authorizer {
     Secp256k1(Did),
     /// ref: <https://eips.ethereum.org/EIPS/eip-191>
     EIP191(Did),
+    /// typed structured data ref: <https://eips.ethereum.org/EIPS/eip-712>
+    /// The packed session is signed as the fixed typed data of [signers::eip712].
+    EIP712(Did),
     /// bitcoin bip137 ref: <https://github.com/bitcoin/bips/blob/master/bip-0137.mediawiki>
     BIP137(Did),
+    /// bitcoin taproot schnorr ref: <https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki>
+    /// The x-only public key is stored as a compressed public key with even y.
+    BIP340(PublicKey),
     /// ed25519
     Ed25519(PublicKey),
+    /// smart contract wallet ref: <https://eips.ethereum.org/EIPS/eip-1271>
+    EIP1271 {
+        /// address of wallet contract
+        contract: Did,
+        /// chain id where the contract deployed
+        chain_id: u64,
+    },
+}
+
+/// Algorithm of the signatures that session key signs on messages,
+/// it's independent of the [Authorizer], which only signs the session.
+///
+/// The session key is always a secp256k1 [SecretKey], which is also used for decryption.
+/// For Ed25519, an ed25519 key is derived from the bytes of session key,
+/// and the session_id is the address of the derived public key.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum SessionKeyAlgorithm {
+    /// ecdsa, the session_id is the address of session key
+    #[default]
+    Secp256k1,
+    /// ed25519 with the derived public key, which is required to verify signatures
+    Ed25519(PublicKey),
+}
+
+impl SessionKeyAlgorithm {
+    /// Parse the lower case name of algorithm for a session key.
+    fn new(name: &str, key: SecretKey) -> Result<Self> {
+        match name {
+            "secp256k1" => Ok(Self::Secp256k1),
+            "ed25519" => Ok(Self::Ed25519(signers::ed25519::pubkey(key))),
+            _ => Err(Error::UnknownSessionKeyAlgorithm(name.to_string())),
+        }
+    }
+
+    /// Lower case name of algorithm.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Secp256k1 => "secp256k1",
+            Self::Ed25519(_) => "ed25519",
+        }
+    }
+
+    /// Get session_id of a session key signing with this algorithm.
+    fn session_id(&self, key: SecretKey) -> Did {
+        match self {
+            Self::Secp256k1 => key.address().into(),
+            Self::Ed25519(pk) => pk.address().into(),
+        }
+    }
+
+    /// Sign message with session key.
+    fn sign(&self, key: SecretKey, msg: &str) -> Vec<u8> {
+        match self {
+            Self::Secp256k1 => signers::secp256k1::sign_raw(key, msg).to_vec(),
+            Self::Ed25519(_) => signers::ed25519::sign(key, msg).to_vec(),
+        }
+    }
+
+    /// Verify message signed by session key against session_id.
+    fn verify(&self, session_id: Did, msg: &str, sig: impl AsRef<[u8]>) -> bool {
+        match self {
+            Self::Secp256k1 => signers::secp256k1::verify(msg, &session_id.into(), sig),
+            Self::Ed25519(pk) => signers::ed25519::verify(msg, &session_id.into(), sig, *pk),
+        }
+    }
 }
 
+/// Chain id of Ethereum mainnet, used when the chain id of EIP1271 authorizer is omitted.
+const EIP1271_DEFAULT_CHAIN_ID: u64 = 1;
+
 impl TryFrom<(String, String)> for Authorizer {
     type Error = Error;
 
@@ -109,15 +391,108 @@ impl TryFrom<(String, String)> for Authorizer {
         match authorizer_type.as_str() {
             "secp256k1" => Ok(Authorizer::Secp256k1(Did::from_str(&authorizer_entity)?)),
             "eip191" => Ok(Authorizer::EIP191(Did::from_str(&authorizer_entity)?)),
+            "eip712" => Ok(Authorizer::EIP712(Did::from_str(&authorizer_entity)?)),
             "bip137" => Ok(Authorizer::BIP137(Did::from_str(&authorizer_entity)?)),
+            // The entity of bip340 is hex encoded x-only public key.
+            "bip340" => {
+                let xonly: [u8; 32] = hex::decode(&authorizer_entity)
+                    .map_err(|_| Error::InvalidPublicKey)?
+                    .try_into()
+                    .map_err(|_| Error::InvalidPublicKey)?;
+                Ok(Authorizer::BIP340(signers::bip340::pubkey_from_xonly(
+                    &xonly,
+                )?))
+            }
             "ed25519" => Ok(Authorizer::Ed25519(PublicKey::try_from_b58t(
                 &authorizer_entity,
             )?)),
+            // The entity of ed25519_solana is base58 of 32 bytes public key, as Solana wallets present.
+            "ed25519_solana" => Ok(Authorizer::Ed25519(PublicKey::try_from_solana(
+                &authorizer_entity,
+            )?)),
+            // The entity of eip1271 is "<contract>" or "<contract>:<chain_id>".
+            "eip1271" => {
+                let (contract, chain_id) = match authorizer_entity.split_once(':') {
+                    Some((contract, chain_id)) => (
+                        contract,
+                        u64::from_str(chain_id).map_err(|_| Error::UnknownAuthorizer)?,
+                    ),
+                    None => (authorizer_entity.as_str(), EIP1271_DEFAULT_CHAIN_ID),
+                };
+                Ok(Authorizer::EIP1271 {
+                    contract: Did::from_str(contract)?,
+                    chain_id,
+                })
+            }
             _ => Err(Error::UnknownAuthorizer),
         }
     }
 }
 
+impl TryFrom<(&str, &str)> for Authorizer {
+    type Error = Error;
+
+    fn try_from((authorizer_entity, authorizer_type): (&str, &str)) -> Result<Self> {
+        Self::try_from((authorizer_entity.to_string(), authorizer_type.to_string()))
+    }
+}
+
+impl From<&Authorizer> for Did {
+    fn from(authorizer: &Authorizer) -> Self {
+        authorizer.did()
+    }
+}
+
+impl From<Authorizer> for Did {
+    fn from(authorizer: Authorizer) -> Self {
+        authorizer.did()
+    }
+}
+
+impl Authorizer {
+    /// Get did of authorizer.
+    /// For the ones carrying a public key, it's the address of the public key.
+    pub fn did(&self) -> Did {
+        match self {
+            Authorizer::Secp256k1(did) => *did,
+            Authorizer::EIP191(did) => *did,
+            Authorizer::EIP712(did) => *did,
+            Authorizer::BIP137(did) => *did,
+            Authorizer::BIP340(pk) => pk.address().into(),
+            Authorizer::Ed25519(pk) => pk.address().into(),
+            Authorizer::EIP1271 { contract, .. } => *contract,
+        }
+    }
+
+    /// Lower case name of algorithm, which is the "authorizer_type" accepted by `try_from`.
+    pub fn algorithm_name(&self) -> &'static str {
+        match self {
+            Authorizer::Secp256k1(_) => "secp256k1",
+            Authorizer::EIP191(_) => "eip191",
+            Authorizer::EIP712(_) => "eip712",
+            Authorizer::BIP137(_) => "bip137",
+            Authorizer::BIP340(_) => "bip340",
+            Authorizer::Ed25519(_) => "ed25519",
+            Authorizer::EIP1271 { .. } => "eip1271",
+        }
+    }
+
+    /// Split the authorizer back into the "authorizer_entity" and "authorizer_type"
+    /// accepted by [SessionManagerBuilder::new].
+    fn to_entity_and_type(&self) -> (String, String) {
+        let entity = match self {
+            Authorizer::Secp256k1(did)
+            | Authorizer::EIP191(did)
+            | Authorizer::EIP712(did)
+            | Authorizer::BIP137(did) => did.to_string(),
+            Authorizer::BIP340(pk) => hex::encode(&pk.0[1..]),
+            Authorizer::Ed25519(pk) => base58::ToBase58::to_base58(&pk.0[..]),
+            Authorizer::EIP1271 { contract, chain_id } => format!("{}:{}", contract, chain_id),
+        };
+        (entity, self.algorithm_name().to_string())
+    }
+}
+
 // A SessionManager can be converted to a string using JSON and then encoded with base58.
 // To load the SessionManager from a string, use `SessionManager::from_str`.
 impl FromStr for SessionManager {
@@ -125,26 +500,86 @@ impl FromStr for SessionManager {
 
     fn from_str(s: &str) -> Result<Self> {
         let s = base58_monero::decode_check(s).map_err(|_| Error::Decode)?;
-        let session_manager: SessionManager =
+        let mut session_manager: SessionManager =
             serde_json::from_slice(&s).map_err(Error::Deserialize)?;
+        // A session dumped without the public key of session key gets it from the session key.
+        if session_manager.session.session_pubkey.is_none() {
+            session_manager.session.session_pubkey = Some(SessionPubkey::new(
+                session_manager.session_key,
+                session_manager.session.key_algorithm,
+            ));
+        }
         Ok(session_manager)
     }
 }
 
+impl SessionManagerBuilder {
+    /// Create a new SessionManagerBuilder with a provided session key, such as one derived from an HD wallet path.
+    /// The session_id is the address of session key, the same as `new`.
+    pub fn new_with_session_key(
+        authorizer_entity: String,
+        authorizer_type: String,
+        session_key: SecretKey,
+    ) -> SessionManagerBuilder {
+        Self {
+            session_key,
+            key_algorithm: SessionKeyAlgorithm::default(),
+            authorizer_entity,
+            authorizer_type,
+            ttl_ms: DEFAULT_SESSION_TTL_MS,
+            max_ttl_ms: DEFAULT_SESSION_MAX_TTL_MS,
+            ts_ms: utils::get_epoch_ms(),
+            sig: vec![],
+            cosigners: vec![],
+            threshold: None,
+            scopes: None,
+        }
+    }
+}
+
 #[wasm_export]
 impl SessionManagerBuilder {
-    /// Create a new SessionManagerBuilder.
-    /// The "authorizer_type" is lower case of [Authorizer] variant.
+    /// Create a new SessionManagerBuilder with a random session key.
+    /// The "authorizer_type" is lower case of [Authorizer] variant,
+    /// or "ed25519_solana" for an Ed25519 authorizer in the base58 form of Solana wallets.
     /// The "authorizer_entity" refers to the entity that is encapsulated by the [Authorizer] variant, in string format.
     pub fn new(authorizer_entity: String, authorizer_type: String) -> SessionManagerBuilder {
-        let session_key = SecretKey::random();
+        Self::new_with_session_key(authorizer_entity, authorizer_type, SecretKey::random())
+    }
+
+    /// Create a SessionManagerBuilder to renew an existing [SessionManager].
+    /// The delegated session key and authorizer are kept, so the session_id stays the same.
+    /// The timestamp is reset to now, and the lifetime can be changed by `ttl` before signing.
+    /// Cosigners of a multisig session are kept without signatures, which should be set by `cosigner`.
+    /// Scopes are kept as well, and the max lifetime is raised to the lifetime of existing session.
+    pub fn renew(existing: &SessionManager) -> SessionManagerBuilder {
+        let (authorizer_entity, authorizer_type) = existing.session.authorizer.to_entity_and_type();
+        let (cosigners, threshold) = match &existing.session.multisig {
+            Some(multisig) => (
+                multisig
+                    .cosigners
+                    .iter()
+                    .map(|(authorizer, _)| {
+                        let (entity, authorizer_type) = authorizer.to_entity_and_type();
+                        (entity, authorizer_type, vec![])
+                    })
+                    .collect(),
+                Some(multisig.threshold),
+            ),
+            None => (vec![], None),
+        };
         Self {
-            session_key,
+            session_key: existing.session_key,
+            key_algorithm: existing.session.key_algorithm,
             authorizer_entity,
             authorizer_type,
-            ttl_ms: DEFAULT_SESSION_TTL_MS,
+            ttl_ms: existing.session.ttl_ms,
+            max_ttl_ms: DEFAULT_SESSION_MAX_TTL_MS.max(existing.session.ttl_ms),
             ts_ms: utils::get_epoch_ms(),
             sig: vec![],
+            cosigners,
+            threshold,
+            scopes: existing.session.scopes.clone(),
         }
     }
 
@@ -158,9 +593,33 @@ impl SessionManagerBuilder {
             .is_ok()
     }
 
-    /// Packs the session into a string for signing.
+    /// Set the algorithm of signatures signed by session key, "secp256k1" by default.
+    /// It should be set before `pack_session`, since the session_id depends on it.
+    pub fn session_key_algorithm(mut self, algorithm: String) -> Result<SessionManagerBuilder> {
+        self.key_algorithm = SessionKeyAlgorithm::new(&algorithm, self.session_key)?;
+        Ok(self)
+    }
+
+    /// Packs the session into a string for signing, in the format of [SESSION_PAYLOAD_VERSION].
     pub fn pack_session(&self) -> String {
-        pack_session(self.session_key.address().into(), self.ts_ms, self.ttl_ms)
+        self.payload().pack_v1()
+    }
+
+    fn payload(&self) -> SessionPayload {
+        SessionPayload {
+            version: SESSION_PAYLOAD_VERSION,
+            session_id: self.key_algorithm.session_id(self.session_key),
+            ts_ms: self.ts_ms,
+            ttl_ms: self.clamped_ttl_ms(),
+            scopes: self.scopes.clone(),
+        }
+    }
+
+    /// Packs the session into EIP712 typed data in JSON for signing by `eth_signTypedData_v4`,
+    /// which is required by "eip712" authorizer.
+    pub fn pack_session_eip712(&self) -> Result<String> {
+        let typed = signers::eip712::TypedSession::from_str(&self.pack_session())?;
+        serde_json::to_string(&typed.typed_data()).map_err(Error::Serialize)
     }
 
     /// Set the signature of session that signed by authorizer.
@@ -169,21 +628,100 @@ impl SessionManagerBuilder {
         self
     }
 
-    /// Set the lifetime of session.
+    /// Set the lifetime of session, it's clamped to the max lifetime when packing session.
+    /// `build` fails if the lifetime exceeds the max one.
     pub fn ttl(mut self, ttl_ms: usize) -> Self {
         self.ttl_ms = ttl_ms;
         self
     }
 
+    /// Set the max lifetime of session allowed, which is 30 days by default.
+    /// Raise it to build a session living longer intentionally.
+    pub fn max_ttl(mut self, max_ttl_ms: usize) -> Self {
+        self.max_ttl_ms = max_ttl_ms;
+        self
+    }
+
+    fn clamped_ttl_ms(&self) -> usize {
+        self.ttl_ms.min(self.max_ttl_ms)
+    }
+
+    /// Add a cosigner of multisig session with its signature of packed session,
+    /// the signature of an existing cosigner is replaced. The entity and type are the same as `new`.
+    pub fn cosigner(
+        mut self,
+        authorizer_entity: String,
+        authorizer_type: String,
+        sig: Vec<u8>,
+    ) -> Self {
+        match self
+            .cosigners
+            .iter_mut()
+            .find(|(e, t, _)| *e == authorizer_entity && *t == authorizer_type)
+        {
+            Some(cosigner) => cosigner.2 = sig,
+            None => self
+                .cosigners
+                .push((authorizer_entity, authorizer_type, sig)),
+        }
+        self
+    }
+
+    /// Set the count of authorizers required to sign a multisig session, including the authorizer.
+    /// All of the authorizer and cosigners are required if it's not set.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Grant a scope to session, such as "connect" or "send".
+    /// A session without any scope granted is allowed for all scopes.
+    /// It should be set before `pack_session`, since scopes are signed as part of session.
+    pub fn scope(mut self, scope: String) -> Result<SessionManagerBuilder> {
+        if scope.is_empty() || scope.contains([',', '\n']) {
+            return Err(Error::InvalidSessionScope(scope));
+        }
+        let scopes = self.scopes.get_or_insert_with(Vec::new);
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+        Ok(self)
+    }
+
     /// Build the [SessionManager].
     pub fn build(self) -> Result<SessionManager> {
+        if self.ttl_ms > self.max_ttl_ms {
+            return Err(Error::SessionTtlExceeded(self.ttl_ms, self.max_ttl_ms));
+        }
         let authorizer = Authorizer::try_from((self.authorizer_entity, self.authorizer_type))?;
+        let multisig = if self.cosigners.is_empty() && self.threshold.unwrap_or(1) == 1 {
+            None
+        } else {
+            let threshold = self.threshold.unwrap_or(self.cosigners.len() + 1);
+            let cosigners = self
+                .cosigners
+                .into_iter()
+                .map(|(entity, authorizer_type, sig)| {
+                    Ok((Authorizer::try_from((entity, authorizer_type))?, sig))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Some(Box::new(Multisig {
+                cosigners,
+                threshold,
+            }))
+        };
         let session = Session {
-            session_id: self.session_key.address().into(),
+            session_id: self.key_algorithm.session_id(self.session_key),
             authorizer,
+            key_algorithm: self.key_algorithm,
+            payload_version: SESSION_PAYLOAD_VERSION,
             ttl_ms: self.ttl_ms,
             ts_ms: self.ts_ms,
             sig: self.sig,
+            multisig,
+            scopes: self.scopes,
+            session_pubkey: Some(SessionPubkey::new(self.session_key, self.key_algorithm)),
+            authorizer_pubkey: PubkeyCache::default(),
         };
 
         session.verify_self()?;
@@ -196,9 +734,26 @@ impl SessionManagerBuilder {
 }
 
 impl Session {
+    /// Get the payload signed by authorizer.
+    pub fn payload(&self) -> SessionPayload {
+        SessionPayload {
+            version: self.payload_version,
+            session_id: self.session_id,
+            ts_ms: self.ts_ms,
+            ttl_ms: self.ttl_ms,
+            scopes: self.scopes.clone(),
+        }
+    }
+
     /// Pack the session into a string for verification or public key recovery.
-    pub fn pack(&self) -> String {
-        pack_session(self.session_id, self.ts_ms, self.ttl_ms)
+    /// It fails if the session is signed in a payload version unknown to this node.
+    pub fn pack(&self) -> Result<String> {
+        self.payload().pack()
+    }
+
+    /// Get the epoch time in ms when the session is created, a renewed session has a later one.
+    pub fn ts_ms(&self) -> u128 {
+        self.ts_ms
     }
 
     /// Check session is expired or not.
@@ -207,58 +762,253 @@ impl Session {
         now > self.ts_ms + self.ttl_ms as u128
     }
 
-    /// Verify session.
-    pub fn verify_self(&self) -> Result<()> {
-        if self.is_expired() {
+    /// Get the remaining lifetime of session in milliseconds.
+    /// The value is negative when the session is already expired.
+    pub fn remaining_ttl_ms(&self) -> i128 {
+        let expired_at = (self.ts_ms + self.ttl_ms as u128) as i128;
+        expired_at - utils::get_epoch_ms() as i128
+    }
+
+    /// Check the session is valid by local clock, tolerating clock skew up to `tolerance_ms`.
+    /// A session created ahead of local clock beyond tolerance is [Error::SessionNotYetValid],
+    /// which is distinguished from [Error::SessionExpired] since it hints a skewed clock.
+    pub fn check_time(&self, tolerance_ms: u128) -> Result<()> {
+        self.check_time_at(utils::get_epoch_ms(), tolerance_ms)
+    }
+
+    fn check_time_at(&self, now: u128, tolerance_ms: u128) -> Result<()> {
+        if self.ts_ms > now.saturating_add(tolerance_ms) {
+            tracing::warn!(
+                "session {} is created {}ms ahead of local clock",
+                self.session_id,
+                self.ts_ms - now
+            );
+            return Err(Error::SessionNotYetValid(self.ts_ms, now));
+        }
+        let expired_at = self.ts_ms + self.ttl_ms as u128;
+        if now > expired_at.saturating_add(tolerance_ms) {
+            tracing::warn!(
+                "session {} is expired {}ms ago by local clock",
+                self.session_id,
+                now - expired_at
+            );
             return Err(Error::SessionExpired);
         }
+        Ok(())
+    }
 
-        let auth_str = self.pack();
+    /// Verify signature of packed session signed by authorizer.
+    /// EIP1271 authorizer requires an on-chain call, which is unsupported.
+    fn verify_authorizer(authorizer: &Authorizer, auth_str: &str, sig: &[u8]) -> Result<bool> {
+        Ok(match *authorizer {
+            Authorizer::Secp256k1(did) => signers::secp256k1::verify(auth_str, &did.into(), sig),
+            Authorizer::EIP191(did) => signers::eip191::verify(auth_str, &did.into(), sig),
+            Authorizer::EIP712(did) => signers::eip712::verify(auth_str, &did.into(), sig),
+            Authorizer::BIP137(did) => signers::bip137::verify(auth_str, &did.into(), sig),
+            Authorizer::BIP340(pk) => signers::bip340::verify(auth_str, pk.0[1..].try_into()?, sig),
+            Authorizer::Ed25519(pk) => signers::ed25519::verify(auth_str, &pk.address(), sig, pk),
+            Authorizer::EIP1271 { .. } => return Err(Error::UnsupportedAuthorizer),
+        })
+    }
 
-        if !(match self.authorizer {
-            Authorizer::Secp256k1(did) => {
-                signers::secp256k1::verify(&auth_str, &did.into(), &self.sig)
+    /// Verify signature of packed session signed by authorizer, with a provider for EIP1271.
+    async fn verify_authorizer_with_provider(
+        authorizer: &Authorizer,
+        auth_str: &str,
+        sig: &[u8],
+        provider: &dyn Eip1271Provider,
+    ) -> Result<bool> {
+        match *authorizer {
+            Authorizer::EIP1271 { contract, chain_id } => {
+                signers::eip1271::verify(provider, chain_id, auth_str, &contract.into(), sig).await
             }
-            Authorizer::EIP191(did) => signers::eip191::verify(&auth_str, &did.into(), &self.sig),
-            Authorizer::BIP137(did) => signers::bip137::verify(&auth_str, &did.into(), &self.sig),
-            Authorizer::Ed25519(pk) => {
-                signers::ed25519::verify(&auth_str, &pk.address(), &self.sig, pk)
+            _ => Self::verify_authorizer(authorizer, auth_str, sig),
+        }
+    }
+
+    /// Check the count of distinct authorizers who signed a multisig session reaches threshold,
+    /// the authorizer of session is already verified. `verified` tells if a cosigner signed.
+    fn check_threshold(&self, verified: impl Fn(&Authorizer, &[u8]) -> bool) -> Result<()> {
+        let Some(multisig) = &self.multisig else {
+            return Ok(());
+        };
+        multisig.validate()?;
+        let mut signed = vec![&self.authorizer];
+        for (authorizer, sig) in multisig.cosigners.iter() {
+            if !sig.is_empty() && !signed.contains(&authorizer) && verified(authorizer, sig) {
+                signed.push(authorizer);
             }
-        }) {
+        }
+        if signed.len() < multisig.threshold {
+            return Err(Error::MultisigThresholdNotMet(
+                signed.len(),
+                multisig.threshold,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Verify session, tolerating [DEFAULT_SESSION_CLOCK_SKEW_MS] of clock skew.
+    /// For a multisig session, at least threshold authorizers should sign the same packed session.
+    /// EIP1271 authorizer requires an on-chain call, use `verify_self_with_provider` instead.
+    pub fn verify_self(&self) -> Result<()> {
+        self.verify_self_with_tolerance(DEFAULT_SESSION_CLOCK_SKEW_MS)
+    }
+
+    /// Verify session, tolerating clock skew up to `tolerance_ms`, see `check_time`.
+    pub fn verify_self_with_tolerance(&self, tolerance_ms: u128) -> Result<()> {
+        self.check_time(tolerance_ms)?;
+
+        let auth_str = self.pack()?;
+
+        // The public key of a recoverable authorizer is recovered only if it's not memoized yet.
+        // It's determined by the immutable payload and signature, so a memoized one is
+        // checked against did just like a freshly recovered one.
+        let verified = match self.authorizer {
+            Authorizer::Secp256k1(did)
+            | Authorizer::BIP137(did)
+            | Authorizer::EIP191(did)
+            | Authorizer::EIP712(did) => match self.authorizer_pubkey() {
+                Ok(pk) => signers::constant_time_eq(pk.address(), did.as_bytes()),
+                Err(_) => false,
+            },
+            _ => Self::verify_authorizer(&self.authorizer, &auth_str, &self.sig)?,
+        };
+        if !verified {
             return Err(Error::VerifySignatureFailed);
         }
 
-        Ok(())
+        self.check_threshold(|authorizer, sig| {
+            Self::verify_authorizer(authorizer, &auth_str, sig).unwrap_or(false)
+        })
+    }
+
+    /// Verify session tolerating clock skew up to `tolerance_ms`,
+    /// and reject it if its session_id is revoked.
+    pub fn verify_self_with_revocations(
+        &self,
+        revoked: &RevocationSet,
+        tolerance_ms: u128,
+    ) -> Result<()> {
+        if revoked.is_revoked(&self.session_id) {
+            return Err(Error::SessionRevoked(self.session_id));
+        }
+        self.verify_self_with_tolerance(tolerance_ms)
+    }
+
+    /// Verify session with a provider, which is able to check EIP1271 signature on chain.
+    /// For other authorizers, it's the same as `verify_self`.
+    pub async fn verify_self_with_provider(&self, provider: &dyn Eip1271Provider) -> Result<()> {
+        self.check_time(DEFAULT_SESSION_CLOCK_SKEW_MS)?;
+
+        let auth_str = self.pack()?;
+        if !Self::verify_authorizer_with_provider(&self.authorizer, &auth_str, &self.sig, provider)
+            .await?
+        {
+            return Err(Error::VerifySignatureFailed);
+        }
+
+        let mut verified = vec![];
+        for (authorizer, sig) in self.multisig.iter().flat_map(|m| m.cosigners.iter()) {
+            let ok = Self::verify_authorizer_with_provider(authorizer, &auth_str, sig, provider)
+                .await
+                .unwrap_or(false);
+            verified.push((authorizer, sig, ok));
+        }
+        self.check_threshold(|authorizer, sig| {
+            verified
+                .iter()
+                .any(|(a, s, ok)| *ok && *a == authorizer && s.as_slice() == sig)
+        })
+    }
+
+    /// Get scopes granted to session, `None` for a session granted all scopes.
+    pub fn scopes(&self) -> Option<&[String]> {
+        self.scopes.as_deref()
+    }
+
+    /// Check the scope is granted to session.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().any(|s| s == scope),
+            None => true,
+        }
     }
 
-    /// Verify message.
+    /// Get cosigners of a multisig session, `None` for a session of single authorizer.
+    pub fn multisig(&self) -> Option<&Multisig> {
+        self.multisig.as_deref()
+    }
+
+    /// Verify message signed by session key, with the algorithm recorded in session.
+    /// Messages are always signed by session key, never by authorizer, so the algorithm is
+    /// independent of the type of [Authorizer]: an Ed25519 authorizer with the default
+    /// [SessionKeyAlgorithm::Secp256k1] still gets its messages verified by secp256k1
+    /// against session_id. Use [SessionManagerBuilder::session_key_algorithm] for ed25519 messages.
+    /// The session is verified first, so it always fails for EIP1271 authorizer,
+    /// which requires `verify_self_with_provider`.
     pub fn verify(&self, msg: &str, sig: impl AsRef<[u8]>) -> Result<()> {
         self.verify_self()?;
-        if !signers::secp256k1::verify(msg, &self.session_id, sig) {
+        if !self.key_algorithm.verify(self.session_id, msg, sig) {
             return Err(Error::VerifySignatureFailed);
         }
         Ok(())
     }
 
-    /// Get public key from session for encryption.
+    /// Get public key of session key, which messages to session are encrypted to by
+    /// [SessionManager::encrypt_to]. Its signature is verified against session_id,
+    /// while the session itself should be verified by `verify_self` before.
+    pub fn session_pubkey(&self) -> Result<PublicKey> {
+        let Some(SessionPubkey { pubkey, sig }) = &self.session_pubkey else {
+            return Err(Error::SessionKeyPubkeyNotFound(self.session_id));
+        };
+        if !self
+            .key_algorithm
+            .verify(self.session_id, &SessionPubkey::pack(pubkey), sig)
+        {
+            return Err(Error::VerifySignatureFailed);
+        }
+        Ok(*pubkey)
+    }
+
+    /// Get algorithm of signatures signed by session key.
+    pub fn key_algorithm(&self) -> SessionKeyAlgorithm {
+        self.key_algorithm
+    }
+
+    /// Get public key of authorizer from session.
+    /// The public key recovered from signature is memoized after the first successful call or `verify_self`.
     pub fn authorizer_pubkey(&self) -> Result<PublicKey> {
-        let auth_str = self.pack();
+        if let Some(pk) = self.authorizer_pubkey.0.get() {
+            return Ok(*pk);
+        }
+        let pk = self.recover_authorizer_pubkey()?;
+        Ok(*self.authorizer_pubkey.0.get_or_init(|| pk))
+    }
+
+    /// Recover pubkey of authorizer from the signature over session.
+    /// Schnorr and ed25519 signatures are not recoverable, their authorizers carry the pubkey.
+    fn recover_authorizer_pubkey(&self) -> Result<PublicKey> {
+        let auth_str = self.pack()?;
         match self.authorizer {
             Authorizer::Secp256k1(_) => signers::secp256k1::recover(&auth_str, &self.sig),
             Authorizer::BIP137(_) => signers::bip137::recover(&auth_str, &self.sig),
             Authorizer::EIP191(_) => signers::eip191::recover(&auth_str, &self.sig),
+            Authorizer::EIP712(_) => signers::eip712::recover(&auth_str, &self.sig),
+            Authorizer::BIP340(pk) => Ok(pk),
             Authorizer::Ed25519(pk) => Ok(pk),
+            Authorizer::EIP1271 { .. } => Err(Error::UnsupportedAuthorizer),
         }
     }
 
+    /// Get did of session key.
+    pub fn session_id(&self) -> Did {
+        self.session_id
+    }
+
     /// Get authorizer did.
     pub fn authorizer_did(&self) -> Did {
-        match self.authorizer {
-            Authorizer::Secp256k1(did) => did,
-            Authorizer::BIP137(did) => did,
-            Authorizer::EIP191(did) => did,
-            Authorizer::Ed25519(pk) => pk.address().into(),
-        }
+        self.authorizer.did()
     }
 }
 
@@ -282,10 +1032,9 @@ impl SessionManager {
         self.session.clone()
     }
 
-    /// Sign message with session.
+    /// Sign message with session key, using the algorithm recorded in session.
     pub fn sign(&self, msg: &str) -> Result<Vec<u8>> {
-        let key = self.session_key;
-        Ok(signers::secp256k1::sign_raw(key, msg).to_vec())
+        Ok(self.session.key_algorithm.sign(self.session_key, msg))
     }
 
     /// Get authorizer did from session.
@@ -293,6 +1042,25 @@ impl SessionManager {
         self.session.authorizer_did()
     }
 
+    /// Encrypt message to a recipient with ECIES.
+    /// The recipient_pubkey should be the public key of recipient's session key,
+    /// since the recipient will decrypt the message by its delegated session key.
+    /// The ciphertext is self-describing: ephemeral pubkey(65) ‖ nonce(16) ‖ tag(16) ‖ ciphertext.
+    pub fn encrypt_to(&self, recipient_pubkey: &PublicKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+        ecies::encrypt(&recipient_pubkey.0, plaintext).map_err(Error::MessageEncryptionFailed)
+    }
+
+    /// Encrypt message to the session key of recipient by its session with `encrypt_to`.
+    /// The public key of session key is verified by the session, see [Session::session_pubkey].
+    pub fn encrypt_to_session(&self, recipient: &Session, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt_to(&recipient.session_pubkey()?, plaintext)
+    }
+
+    /// Decrypt message that encrypted by `encrypt_to` with the session key.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        ecies::decrypt(&self.session_key.ser(), ciphertext).map_err(Error::MessageDecryptionFailed)
+    }
+
     /// Dump session_manager to string, allowing user to save it in a config file.
     /// It can be restored using `SessionManager::from_str`.
     pub fn dump(&self) -> Result<String> {
@@ -303,6 +1071,8 @@ impl SessionManager {
 
 #[cfg(test)]
 mod test {
+    use web3::types::Address;
+
     use super::*;
 
     #[test]
@@ -322,6 +1092,879 @@ mod test {
         assert_eq!(key.pubkey(), pubkey);
     }
 
+    #[test]
+    pub fn test_renew_session() {
+        let key = SecretKey::random();
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        let builder = SessionManagerBuilder::renew(&sm).ttl(1000);
+        assert_ne!(builder.pack_session(), sm.session().pack().unwrap());
+        let sig = key.sign(&builder.pack_session());
+        let renewed = builder.sig(sig.to_vec()).build().unwrap();
+
+        let session = renewed.session();
+        assert_eq!(session.session_id, sm.session().session_id);
+        assert_eq!(session.authorizer, sm.session().authorizer);
+        assert_eq!(session.ttl_ms, 1000);
+        assert!(session.ts_ms > sm.session().ts_ms);
+        assert!(session.verify_self().is_ok());
+
+        // Signature of the old session can not be reused.
+        let stale = SessionManagerBuilder::renew(&sm).sig(sm.session().sig);
+        assert!(stale.build().is_err());
+    }
+
+    #[test]
+    pub fn test_session_max_ttl() {
+        let key = SecretKey::random();
+        let new_builder = || {
+            SessionManagerBuilder::new(
+                Did::from(key.address()).to_string(),
+                "secp256k1".to_string(),
+            )
+        };
+        let build = |builder: SessionManagerBuilder| {
+            let sig = key.sign(&builder.pack_session());
+            builder.sig(sig.to_vec()).build()
+        };
+
+        // The boundary is allowed.
+        let sm = build(new_builder().ttl(DEFAULT_SESSION_MAX_TTL_MS)).unwrap();
+        assert_eq!(sm.session().ttl_ms, DEFAULT_SESSION_MAX_TTL_MS);
+
+        // A lifetime exceeding the max one is clamped when packing, and rejected by build.
+        let builder = new_builder().ttl(DEFAULT_SESSION_MAX_TTL_MS + 1);
+        assert!(builder
+            .pack_session()
+            .ends_with(&format!("\n{}", DEFAULT_SESSION_MAX_TTL_MS)));
+        assert!(matches!(
+            build(builder),
+            Err(Error::SessionTtlExceeded(ttl, max))
+                if ttl == DEFAULT_SESSION_MAX_TTL_MS + 1 && max == DEFAULT_SESSION_MAX_TTL_MS
+        ));
+        // Keep it in range of usize on wasm32.
+        let long_ttl = DEFAULT_SESSION_MAX_TTL_MS / 2 * 3;
+        assert!(build(new_builder().ttl(long_ttl)).is_err());
+
+        // The max lifetime can be raised intentionally, in any order.
+        let sm = build(new_builder().ttl(long_ttl).max_ttl(long_ttl)).unwrap();
+        assert_eq!(sm.session().ttl_ms, long_ttl);
+        assert!(build(new_builder().max_ttl(1000).ttl(1001)).is_err());
+
+        // Renewing a session which lives longer intentionally keeps its lifetime.
+        let renewed = build(SessionManagerBuilder::renew(&sm)).unwrap();
+        assert_eq!(renewed.session().ttl_ms, long_ttl);
+    }
+
+    #[test]
+    pub fn test_remaining_ttl_ms() {
+        let key = SecretKey::random();
+
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+        let remaining = sm.session().remaining_ttl_ms();
+        assert!(remaining > 0);
+        assert!(remaining <= DEFAULT_SESSION_TTL_MS as i128);
+
+        let builder = SessionManagerBuilder::new(
+            Did::from(key.address()).to_string(),
+            "secp256k1".to_string(),
+        )
+        .ttl(1000);
+        let sig = key.sign(&builder.pack_session());
+        let sm = builder.sig(sig.to_vec()).build().unwrap();
+        let remaining = sm.session().remaining_ttl_ms();
+        assert!(remaining > 0);
+        assert!(remaining <= 1000);
+        assert!(!sm.session().is_expired());
+
+        let mut session = sm.session();
+        session.ts_ms -= 2000;
+        assert!(session.remaining_ttl_ms() < 0);
+        assert!(session.remaining_ttl_ms() > -2000);
+        assert!(session.is_expired());
+    }
+
+    #[test]
+    pub fn test_future_dated_session() {
+        let key = SecretKey::random();
+        let new_session = |ahead_ms: u128| {
+            let mut builder = SessionManagerBuilder::new(
+                Did::from(key.address()).to_string(),
+                "secp256k1".to_string(),
+            );
+            builder.ts_ms += ahead_ms;
+            let sig = key.sign(&builder.pack_session());
+            builder.sig(sig.to_vec()).build().map(|sm| sm.session())
+        };
+
+        // Within tolerance, a session slightly ahead of local clock is valid.
+        let session = new_session(DEFAULT_SESSION_CLOCK_SKEW_MS / 2).unwrap();
+        assert!(session.verify_self().is_ok());
+
+        // Beyond tolerance, it's not yet valid rather than expired.
+        assert!(matches!(
+            new_session(DEFAULT_SESSION_CLOCK_SKEW_MS * 10),
+            Err(Error::SessionNotYetValid(_, _))
+        ));
+
+        // A wider tolerance accepts it.
+        let mut session = new_session(0).unwrap();
+        session.ts_ms += DEFAULT_SESSION_CLOCK_SKEW_MS * 10;
+        assert!(matches!(
+            session.check_time(DEFAULT_SESSION_CLOCK_SKEW_MS),
+            Err(Error::SessionNotYetValid(_, _))
+        ));
+        assert!(session
+            .check_time(DEFAULT_SESSION_CLOCK_SKEW_MS * 20)
+            .is_ok());
+    }
+
+    #[test]
+    pub fn test_session_check_time_at() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let mut session = sm.session();
+        session.ts_ms = 10000;
+        session.ttl_ms = 1000;
+
+        assert!(session.check_time_at(11000, 0).is_ok());
+        assert!(matches!(
+            session.check_time_at(11001, 0),
+            Err(Error::SessionExpired)
+        ));
+        assert!(session.check_time_at(11500, 500).is_ok());
+        assert!(matches!(
+            session.check_time_at(11501, 500),
+            Err(Error::SessionExpired)
+        ));
+
+        assert!(session.check_time_at(9500, 500).is_ok());
+        assert!(matches!(
+            session.check_time_at(9499, 500),
+            Err(Error::SessionNotYetValid(10000, 9499))
+        ));
+    }
+
+    struct MockEip1271Provider {
+        key: SecretKey,
+    }
+
+    #[cfg_attr(feature = "wasm", async_trait::async_trait(?Send))]
+    #[cfg_attr(not(feature = "wasm"), async_trait::async_trait)]
+    impl Eip1271Provider for MockEip1271Provider {
+        async fn call(&self, _chain_id: u64, _contract: Address, data: Vec<u8>) -> Result<Vec<u8>> {
+            // Mock a wallet contract owned by a single key.
+            let hash: [u8; 32] = data[4..36].try_into().unwrap();
+            let sig = signers::eip191::sign(self.key, &hash);
+            let mut ret = vec![0u8; 32];
+            if data == signers::eip1271::encode_call(&hash, &sig) {
+                ret[..4].copy_from_slice(&signers::eip1271::MAGIC_VALUE);
+            }
+            Ok(ret)
+        }
+    }
+
+    #[test]
+    pub fn test_eip1271_authorizer_try_from() {
+        let contract = "0x11E807fcc88dD319270493fB2e822e388Fe36ab0";
+        let authorizer =
+            Authorizer::try_from((contract.to_string(), "eip1271".to_string())).unwrap();
+        assert_eq!(authorizer, Authorizer::EIP1271 {
+            contract: Did::from_str(contract).unwrap(),
+            chain_id: 1,
+        });
+
+        let (entity, authorizer_type) = authorizer.to_entity_and_type();
+        assert_eq!(
+            Authorizer::try_from((entity, authorizer_type)).unwrap(),
+            authorizer
+        );
+
+        let authorizer =
+            Authorizer::try_from((format!("{}:137", contract), "eip1271".to_string())).unwrap();
+        assert_eq!(authorizer, Authorizer::EIP1271 {
+            contract: Did::from_str(contract).unwrap(),
+            chain_id: 137,
+        });
+        assert!(Authorizer::try_from((format!("{}:x", contract), "eip1271".to_string())).is_err());
+    }
+
+    #[test]
+    pub fn test_authorizer_name_and_did() {
+        let key = SecretKey::random();
+        let did: Did = key.address().into();
+        let xonly = PublicKey::from_hex_string(
+            "02dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+        )
+        .unwrap();
+        let ed25519_pk = signers::ed25519::pubkey(key);
+        let authorizers = [
+            (Authorizer::Secp256k1(did), "secp256k1", did),
+            (Authorizer::EIP191(did), "eip191", did),
+            (Authorizer::EIP712(did), "eip712", did),
+            (Authorizer::BIP137(did), "bip137", did),
+            (Authorizer::BIP340(xonly), "bip340", xonly.address().into()),
+            (
+                Authorizer::Ed25519(ed25519_pk),
+                "ed25519",
+                ed25519_pk.address().into(),
+            ),
+            (
+                Authorizer::EIP1271 {
+                    contract: did,
+                    chain_id: 10,
+                },
+                "eip1271",
+                did,
+            ),
+        ];
+        for (authorizer, name, expected_did) in authorizers {
+            assert_eq!(authorizer.algorithm_name(), name);
+            assert_eq!(authorizer.did(), expected_did);
+            assert_eq!(Did::from(&authorizer), expected_did);
+
+            let (entity, authorizer_type) = authorizer.to_entity_and_type();
+            assert_eq!(authorizer_type, name);
+            let restored = Authorizer::try_from((entity.as_str(), name)).unwrap();
+            assert_eq!(restored, authorizer);
+            assert_eq!(Did::from(restored), expected_did);
+        }
+    }
+
+    #[test]
+    pub fn test_bip340_authorizer_try_from() {
+        let xonly = "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659";
+        let authorizer = Authorizer::try_from((xonly.to_string(), "bip340".to_string())).unwrap();
+        let pk = PublicKey::from_hex_string(&format!("02{}", xonly)).unwrap();
+        assert_eq!(authorizer, Authorizer::BIP340(pk));
+
+        let (entity, authorizer_type) = authorizer.to_entity_and_type();
+        assert_eq!(entity, xonly);
+        assert_eq!(
+            Authorizer::try_from((entity, authorizer_type)).unwrap(),
+            authorizer
+        );
+        assert!(Authorizer::try_from((xonly[2..].to_string(), "bip340".to_string())).is_err());
+    }
+
+    #[test]
+    pub fn test_eip712_authorizer() {
+        let key = SecretKey::random();
+        let did = Did::from(key.address());
+        let builder = SessionManagerBuilder::new(did.to_string(), "eip712".to_string())
+            .scope("send".to_string())
+            .unwrap();
+
+        let typed_data: serde_json::Value =
+            serde_json::from_str(&builder.pack_session_eip712().unwrap()).unwrap();
+        assert_eq!(typed_data["primaryType"], "Session");
+        assert_eq!(typed_data["message"]["scopes"], "send");
+
+        let sig = signers::eip712::sign_raw(key, &builder.pack_session()).unwrap();
+        let sm = builder.sig(sig.to_vec()).build().unwrap();
+        let session = sm.session();
+        assert_eq!(session.authorizer, Authorizer::EIP712(did));
+        assert_eq!(session.authorizer_did(), did);
+        assert_eq!(
+            session.authorizer_pubkey().unwrap().address(),
+            key.address()
+        );
+
+        // A personal_sign signature of packed session is not accepted.
+        let builder = SessionManagerBuilder::new(did.to_string(), "eip712".to_string());
+        let sig = signers::eip191::sign_raw(key, &builder.pack_session());
+        assert!(builder.sig(sig.to_vec()).build().is_err());
+    }
+
+    #[test]
+    pub fn test_ed25519_solana_authorizer() {
+        // pubkey and signature of "helloworld" from phantom wallet, see signers::ed25519
+        let pubkey = "9z1ZTaGocNSAu3DSqGKR6Dqt214X4dXucVd6C53EgqBK";
+        let sig_b58 = "2V1AR5byk4a4CkVmFRWU1TVs3ns2CGkuq6xgGju1huGQGq5hGkiHUDjEaJJaL2txfqCSGnQW55jUJpcjKFkZEKq";
+        let sig: Vec<u8> = base58::FromBase58::from_base58(sig_b58).unwrap();
+
+        let authorizer =
+            Authorizer::try_from((pubkey.to_string(), "ed25519_solana".to_string())).unwrap();
+        let Authorizer::Ed25519(pk) = authorizer else {
+            panic!("ed25519_solana should be an Ed25519 authorizer");
+        };
+        assert_eq!(pk, PublicKey::try_from_b58t(pubkey).unwrap());
+        assert!(signers::ed25519::verify(
+            "helloworld",
+            &pk.address(),
+            sig,
+            pk
+        ));
+
+        // A session signed by a solana wallet.
+        let key = SecretKey::random();
+        let solana_pubkey = base58::ToBase58::to_base58(&signers::ed25519::pubkey(key).0[1..]);
+        let builder = SessionManagerBuilder::new(solana_pubkey, "ed25519_solana".to_string());
+        let sig = signers::ed25519::sign(key, &builder.pack_session());
+        assert!(builder.sig(sig.to_vec()).build().is_ok());
+
+        // 33 bytes or not on the curve
+        let padded = base58::ToBase58::to_base58(&pk.0[..]);
+        assert!(Authorizer::try_from((padded, "ed25519_solana".to_string())).is_err());
+        assert!(PublicKey::try_from_solana("11111111111111111111111111111112").is_err());
+        assert!(PublicKey::try_from_solana("not base58 0OIl").is_err());
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(feature = "wasm"), tokio::test)]
+    pub async fn test_eip1271_verify_with_provider() {
+        let key = SecretKey::random();
+        let session_key = SecretKey::random();
+        let contract = Did::from(SecretKey::random().address());
+        let ts_ms = utils::get_epoch_ms();
+        let mut session = Session {
+            session_id: session_key.address().into(),
+            authorizer: Authorizer::EIP1271 {
+                contract,
+                chain_id: 1,
+            },
+            key_algorithm: SessionKeyAlgorithm::Secp256k1,
+            payload_version: SESSION_PAYLOAD_V1,
+            ttl_ms: DEFAULT_SESSION_TTL_MS,
+            ts_ms,
+            sig: vec![],
+            multisig: None,
+            scopes: None,
+            session_pubkey: None,
+            authorizer_pubkey: PubkeyCache::default(),
+        };
+        session.sig = signers::eip191::sign_raw(key, &session.pack().unwrap()).to_vec();
+
+        assert!(matches!(
+            session.verify_self(),
+            Err(Error::UnsupportedAuthorizer)
+        ));
+        assert_eq!(session.authorizer_did(), contract);
+
+        let provider = MockEip1271Provider { key };
+        assert!(session.verify_self_with_provider(&provider).await.is_ok());
+
+        let provider = MockEip1271Provider {
+            key: SecretKey::random(),
+        };
+        assert!(session.verify_self_with_provider(&provider).await.is_err());
+    }
+
+    #[test]
+    pub fn test_encrypt_decrypt() {
+        let alice = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let bob = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        // Alice only gets the session of Bob, such as the one attached to his messages.
+        let bob_session: Session =
+            serde_json::from_str(&serde_json::to_string(&bob.session()).unwrap()).unwrap();
+        assert!(bob_session.verify_self().is_ok());
+
+        let plaintext = b"hello rings";
+        let ciphertext = alice.encrypt_to_session(&bob_session, plaintext).unwrap();
+        assert_ne!(&ciphertext[..], &plaintext[..]);
+        assert_eq!(ciphertext.len(), 65 + 16 + 16 + plaintext.len());
+        assert_eq!(bob.decrypt(&ciphertext).unwrap(), plaintext);
+
+        // Only the recipient can decrypt.
+        assert!(alice.decrypt(&ciphertext).is_err());
+
+        let empty = alice.encrypt_to_session(&bob_session, &[]).unwrap();
+        assert_eq!(bob.decrypt(&empty).unwrap(), Vec::<u8>::new());
+
+        // The public key of session key can be encrypted to directly.
+        let ciphertext = alice
+            .encrypt_to(&bob.session_key.pubkey(), plaintext)
+            .unwrap();
+        assert_eq!(bob.decrypt(&ciphertext).unwrap(), plaintext);
+
+        // Sessions of ed25519 session key can be encrypted to as well.
+        for carol in new_session_managers("ed25519") {
+            let ciphertext = alice
+                .encrypt_to_session(&carol.session(), plaintext)
+                .unwrap();
+            assert_eq!(carol.decrypt(&ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    pub fn test_session_pubkey() {
+        let alice = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let bob = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let mallory = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+
+        // A public key replaced by others fails verification, and the session can't be encrypted to.
+        let mut value = serde_json::to_value(bob.session()).unwrap();
+        value["session_pubkey"] =
+            serde_json::to_value(mallory.session()).unwrap()["session_pubkey"].clone();
+        let forged: Session = serde_json::from_value(value).unwrap();
+        assert!(forged.verify_self().is_ok());
+        assert!(matches!(
+            forged.session_pubkey(),
+            Err(Error::VerifySignatureFailed)
+        ));
+        assert!(alice.encrypt_to_session(&forged, b"hello rings").is_err());
+
+        // A session dumped without public key can't be encrypted to,
+        // and the public key is attached when its manager is restored.
+        let mut value = serde_json::to_value(&bob).unwrap();
+        value["session"]
+            .as_object_mut()
+            .unwrap()
+            .remove("session_pubkey");
+        let legacy: Session = serde_json::from_value(value["session"].clone()).unwrap();
+        assert!(matches!(
+            alice.encrypt_to_session(&legacy, b"hello rings"),
+            Err(Error::SessionKeyPubkeyNotFound(_))
+        ));
+        let dump = base58_monero::encode_check(value.to_string().as_bytes()).unwrap();
+        let restored = SessionManager::from_str(&dump).unwrap();
+        let ciphertext = alice
+            .encrypt_to_session(&restored.session(), b"hello rings")
+            .unwrap();
+        assert_eq!(bob.decrypt(&ciphertext).unwrap(), b"hello rings");
+    }
+
+    #[test]
+    pub fn test_decrypt_tampered_message() {
+        let alice = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let bob = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+
+        let mut ciphertext = alice
+            .encrypt_to_session(&bob.session(), b"hello rings")
+            .unwrap();
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0x01;
+        assert!(matches!(
+            bob.decrypt(&ciphertext),
+            Err(Error::MessageDecryptionFailed(_))
+        ));
+        assert!(bob.decrypt(&ciphertext[..10]).is_err());
+    }
+
+    /// Build sessions with every authorizer that can be signed locally.
+    fn new_session_managers(key_algorithm: &str) -> Vec<SessionManager> {
+        let key = SecretKey::random();
+        let secp256k1 = (Did::from(key.address()).to_string(), "secp256k1");
+        let eip191 = (Did::from(key.address()).to_string(), "eip191");
+        let ed25519 = (
+            base58::ToBase58::to_base58(&signers::ed25519::pubkey(key).0[1..]),
+            "ed25519",
+        );
+        [secp256k1, eip191, ed25519]
+            .into_iter()
+            .map(|(entity, authorizer_type)| {
+                let builder = SessionManagerBuilder::new(entity, authorizer_type.to_string())
+                    .session_key_algorithm(key_algorithm.to_string())
+                    .unwrap();
+                let auth_str = builder.pack_session();
+                let sig = match authorizer_type {
+                    "secp256k1" => key.sign(&auth_str).to_vec(),
+                    "eip191" => signers::eip191::sign_raw(key, &auth_str).to_vec(),
+                    _ => signers::ed25519::sign(key, &auth_str).to_vec(),
+                };
+                builder.sig(sig).build().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_sign_verify_with_key_algorithm() {
+        for key_algorithm in ["secp256k1", "ed25519"] {
+            for sm in new_session_managers(key_algorithm) {
+                let session = sm.session();
+                assert_eq!(session.key_algorithm().name(), key_algorithm);
+
+                let sig = sm.sign("hello rings").unwrap();
+                let sig_len = if key_algorithm == "ed25519" { 64 } else { 65 };
+                assert_eq!(sig.len(), sig_len);
+                assert!(session.verify("hello rings", &sig).is_ok());
+                assert!(session.verify("hello", &sig).is_err());
+
+                // The signature is checked by the algorithm recorded in session.
+                let mut forged = session.clone();
+                forged.key_algorithm = match session.key_algorithm {
+                    SessionKeyAlgorithm::Secp256k1 => {
+                        SessionKeyAlgorithm::Ed25519(signers::ed25519::pubkey(sm.session_key))
+                    }
+                    SessionKeyAlgorithm::Ed25519(_) => SessionKeyAlgorithm::Secp256k1,
+                };
+                assert!(forged.verify("hello rings", &sig).is_err());
+
+                // The algorithm is kept when renewing.
+                let builder = SessionManagerBuilder::renew(&sm);
+                assert_eq!(builder.key_algorithm, session.key_algorithm);
+                assert!(builder
+                    .pack_session()
+                    .starts_with(&session.session_id.to_string()));
+            }
+        }
+
+        assert!(matches!(
+            SessionManagerBuilder::new(String::new(), "secp256k1".to_string())
+                .session_key_algorithm("rsa".to_string()),
+            Err(Error::UnknownSessionKeyAlgorithm(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_ed25519_authorizer_sign_verify() {
+        let key = SecretKey::random();
+        let pubkey = signers::ed25519::pubkey(key);
+        let builder = SessionManagerBuilder::new(
+            base58::ToBase58::to_base58(&pubkey.0[1..]),
+            "ed25519".to_string(),
+        );
+        let sig = signers::ed25519::sign(key, &builder.pack_session());
+        let sm = builder.sig(sig.to_vec()).build().unwrap();
+        let session = sm.session();
+        assert_eq!(session.authorizer_pubkey().unwrap(), pubkey);
+        assert_eq!(session.key_algorithm(), SessionKeyAlgorithm::Secp256k1);
+
+        // Messages are signed by session key with secp256k1, regardless of the ed25519 authorizer.
+        let sig = sm.sign("hello rings").unwrap();
+        assert_eq!(sig.len(), 65);
+        assert!(signers::secp256k1::verify(
+            "hello rings",
+            &session.session_id().into(),
+            &sig
+        ));
+        assert!(session.verify("hello rings", &sig).is_ok());
+
+        // Signatures of authorizer key are not accepted as signatures of session.
+        let sig = signers::ed25519::sign(key, "hello rings");
+        assert!(session.verify("hello rings", sig).is_err());
+    }
+
+    #[test]
+    pub fn test_session_without_key_algorithm() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let mut value = serde_json::to_value(sm.session()).unwrap();
+        value.as_object_mut().unwrap().remove("key_algorithm");
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.key_algorithm(), SessionKeyAlgorithm::Secp256k1);
+        let sig = sm.sign("hello rings").unwrap();
+        assert!(session.verify("hello rings", sig).is_ok());
+    }
+
+    #[test]
+    pub fn test_session_payload() {
+        let session_id = Did::from(SecretKey::random().address());
+        let mut payload = SessionPayload {
+            version: SESSION_PAYLOAD_V1,
+            session_id,
+            ts_ms: 1700000000000,
+            ttl_ms: 3600000,
+            scopes: None,
+        };
+        // v1 is packed as before versioning.
+        let packed = payload.pack().unwrap();
+        assert_eq!(packed, format!("{}\n1700000000000\n3600000", session_id));
+        assert_eq!(SessionPayload::from_str(&packed).unwrap(), payload);
+
+        payload.scopes = Some(vec!["connect".to_string(), "send".to_string()]);
+        let packed = payload.pack().unwrap();
+        assert_eq!(
+            packed,
+            format!("{}\n1700000000000\n3600000\nconnect,send", session_id)
+        );
+        assert_eq!(SessionPayload::from_str(&packed).unwrap(), payload);
+
+        payload.version = 2;
+        assert!(matches!(
+            payload.pack(),
+            Err(Error::UnsupportedSessionPayloadVersion(2))
+        ));
+        assert!(matches!(
+            SessionPayload::from_str(&format!("v2\n{}", packed)),
+            Err(Error::UnsupportedSessionPayloadVersion(2))
+        ));
+        assert!(matches!(
+            SessionPayload::from_str("vx\nhello"),
+            Err(Error::InvalidSessionPayload(_))
+        ));
+        assert!(matches!(
+            SessionPayload::from_str(&format!("{}\n1700000000000", session_id)),
+            Err(Error::InvalidSessionPayload(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_session_payload_version() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let session = sm.session();
+        assert_eq!(session.payload().version, SESSION_PAYLOAD_VERSION);
+        assert_eq!(session.pack().unwrap(), session.payload().pack().unwrap());
+
+        // Sessions dumped before versioning are v1.
+        let mut value = serde_json::to_value(&session).unwrap();
+        value.as_object_mut().unwrap().remove("payload_version");
+        let restored: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(restored.payload_version, SESSION_PAYLOAD_V1);
+        assert!(restored.verify_self().is_ok());
+
+        // A session of unknown payload version is rejected.
+        let mut value = serde_json::to_value(&session).unwrap();
+        value["payload_version"] = serde_json::json!(2);
+        let unknown: Session = serde_json::from_value(value).unwrap();
+        assert!(matches!(
+            unknown.verify_self(),
+            Err(Error::UnsupportedSessionPayloadVersion(2))
+        ));
+    }
+
+    /// Build a multisig session of a secp256k1 authorizer, and cosigners of a secp256k1 key
+    /// and an ed25519 key. Only authorizers in `signed` sign the session.
+    fn new_multisig_builder(threshold: usize, signed: [bool; 3]) -> SessionManagerBuilder {
+        let keys = [
+            SecretKey::random(),
+            SecretKey::random(),
+            SecretKey::random(),
+        ];
+        let builder = SessionManagerBuilder::new(
+            Did::from(keys[0].address()).to_string(),
+            "secp256k1".to_string(),
+        )
+        .threshold(threshold);
+        let auth_str = builder.pack_session();
+        let sig_of = |i: usize, sig: Vec<u8>| if signed[i] { sig } else { vec![] };
+        builder
+            .sig(sig_of(0, keys[0].sign(&auth_str).to_vec()))
+            .cosigner(
+                Did::from(keys[1].address()).to_string(),
+                "secp256k1".to_string(),
+                sig_of(1, keys[1].sign(&auth_str).to_vec()),
+            )
+            .cosigner(
+                base58::ToBase58::to_base58(&signers::ed25519::pubkey(keys[2]).0[1..]),
+                "ed25519".to_string(),
+                sig_of(2, signers::ed25519::sign(keys[2], &auth_str).to_vec()),
+            )
+    }
+
+    #[test]
+    pub fn test_multisig_session() {
+        // 2-of-3 with the authorizer and a secp256k1 cosigner.
+        let sm = new_multisig_builder(2, [true, true, false])
+            .build()
+            .unwrap();
+        let session = sm.session();
+        assert_eq!(session.multisig().unwrap().cosigners.len(), 2);
+        assert!(session.verify_self().is_ok());
+        let sig = sm.sign("hello rings").unwrap();
+        assert!(session.verify("hello rings", sig).is_ok());
+
+        // 2-of-3 with the authorizer and an ed25519 cosigner.
+        assert!(new_multisig_builder(2, [true, false, true]).build().is_ok());
+        // 3-of-3 with mixed secp256k1 and ed25519 authorizers.
+        assert!(new_multisig_builder(3, [true, true, true]).build().is_ok());
+
+        // 1-of-3 doesn't reach threshold.
+        assert!(matches!(
+            new_multisig_builder(2, [true, false, false]).build(),
+            Err(Error::MultisigThresholdNotMet(1, 2))
+        ));
+        // The authorizer is always required, since the did of session is derived from it.
+        assert!(matches!(
+            new_multisig_builder(2, [false, true, true]).build(),
+            Err(Error::VerifySignatureFailed)
+        ));
+        assert!(matches!(
+            new_multisig_builder(4, [true, true, true]).build(),
+            Err(Error::InvalidMultisigThreshold(4, 3))
+        ));
+
+        // A signature of cosigner is counted once, and a forged one is not counted.
+        let mut forged = session.clone();
+        let multisig = forged.multisig.as_mut().unwrap();
+        multisig.cosigners[1] = multisig.cosigners[0].clone();
+        assert!(forged.verify_self().is_ok());
+        forged.multisig.as_mut().unwrap().threshold = 3;
+        assert!(forged.verify_self().is_err());
+        let mut forged = session;
+        forged.multisig.as_mut().unwrap().cosigners[0].1[0] ^= 0x01;
+        assert!(forged.verify_self().is_err());
+    }
+
+    #[test]
+    pub fn test_multisig_renew_and_dump() {
+        let sm = new_multisig_builder(2, [true, true, false])
+            .build()
+            .unwrap();
+        let dump = sm.dump().unwrap();
+        assert_eq!(SessionManager::from_str(&dump).unwrap(), sm);
+
+        // Cosigners are kept without signatures when renewing.
+        let builder = SessionManagerBuilder::renew(&sm);
+        assert_eq!(builder.threshold, Some(2));
+        assert_eq!(builder.cosigners.len(), 2);
+        assert!(builder.cosigners.iter().all(|(_, _, sig)| sig.is_empty()));
+    }
+
+    #[test]
+    pub fn test_session_without_multisig() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        assert!(sm.session().multisig().is_none());
+        let mut value = serde_json::to_value(sm.session()).unwrap();
+        value.as_object_mut().unwrap().remove("multisig");
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session, sm.session());
+        assert!(session.verify_self().is_ok());
+    }
+
+    #[test]
+    pub fn test_session_scopes() {
+        let key = SecretKey::random();
+        let builder = SessionManagerBuilder::new(
+            Did::from(key.address()).to_string(),
+            "secp256k1".to_string(),
+        );
+        let unscoped = builder.pack_session();
+        let builder = builder
+            .scope("send".to_string())
+            .unwrap()
+            .scope("send".to_string())
+            .unwrap();
+        assert_eq!(builder.pack_session(), format!("{}\nsend", unscoped));
+        let sig = key.sign(&builder.pack_session());
+        let sm = builder.sig(sig.to_vec()).build().unwrap();
+        let session = sm.session();
+        assert_eq!(session.scopes(), Some(&["send".to_string()][..]));
+        assert!(session.has_scope("send"));
+        assert!(!session.has_scope("connect"));
+
+        // Scopes are signed, so they can't be widened.
+        // Tampered sessions are deserialized, so they don't share the memoized authorizer.
+        let tamper = |scopes: serde_json::Value| {
+            let mut value = serde_json::to_value(&session).unwrap();
+            value["scopes"] = scopes;
+            serde_json::from_value::<Session>(value).unwrap()
+        };
+        assert!(tamper(serde_json::Value::Null).verify_self().is_err());
+        assert!(tamper(serde_json::json!(["send", "connect"]))
+            .verify_self()
+            .is_err());
+
+        let renewed = SessionManagerBuilder::renew(&sm);
+        assert!(renewed.pack_session().ends_with("\nsend"));
+
+        // A session without scopes is granted all scopes.
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+        assert!(sm.session().scopes().is_none());
+        assert!(sm.session().has_scope("connect"));
+
+        for scope in ["", "send,connect", "send\nconnect"] {
+            assert!(matches!(
+                SessionManagerBuilder::new(
+                    Did::from(key.address()).to_string(),
+                    "secp256k1".to_string()
+                )
+                .scope(scope.to_string()),
+                Err(Error::InvalidSessionScope(_))
+            ));
+        }
+    }
+
+    #[test]
+    pub fn test_session_revocation() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let session = sm.session();
+        let mut revoked = RevocationSet::default();
+        assert!(session
+            .verify_self_with_revocations(&revoked, DEFAULT_SESSION_CLOCK_SKEW_MS)
+            .is_ok());
+
+        assert!(revoked.revoke(
+            SessionManager::new_with_seckey(&SecretKey::random())
+                .unwrap()
+                .session()
+                .session_id()
+        ));
+        assert!(session
+            .verify_self_with_revocations(&revoked, DEFAULT_SESSION_CLOCK_SKEW_MS)
+            .is_ok());
+
+        assert!(revoked.revoke(session.session_id()));
+        assert!(!revoked.revoke(session.session_id()));
+        assert_eq!(revoked.len(), 2);
+        assert!(!session.is_expired());
+        assert!(session.verify_self().is_ok());
+        assert!(matches!(
+            session.verify_self_with_revocations(&revoked, DEFAULT_SESSION_CLOCK_SKEW_MS),
+            Err(Error::SessionRevoked(did)) if did == session.session_id()
+        ));
+    }
+
+    #[test]
+    pub fn test_authorizer_pubkey_memoized() {
+        let key = SecretKey::random();
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+
+        // A deserialized session starts without memoized public key.
+        let session: Session =
+            serde_json::from_str(&serde_json::to_string(&sm.session()).unwrap()).unwrap();
+        assert!(session.authorizer_pubkey.0.get().is_none());
+        assert_eq!(session, sm.session());
+        let bincode_session: Session =
+            bincode::deserialize(&bincode::serialize(&sm.session()).unwrap()).unwrap();
+        assert!(bincode_session.authorizer_pubkey.0.get().is_none());
+
+        assert!(session.verify_self().is_ok());
+        assert_eq!(session.authorizer_pubkey.0.get(), Some(&key.pubkey()));
+
+        let fresh = || -> Session {
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap()
+        };
+
+        // Verifying a message memoizes the public key as well.
+        let s = fresh();
+        let sig = sm.sign("hello").unwrap();
+        assert!(s.verify("hello", &sig).is_ok());
+        assert_eq!(s.authorizer_pubkey.0.get(), Some(&key.pubkey()));
+
+        // Verification reads the memoized public key instead of recovering it,
+        // so a session memoizing another public key is rejected.
+        let s = fresh();
+        s.authorizer_pubkey
+            .0
+            .set(SecretKey::random().pubkey())
+            .unwrap();
+        assert!(matches!(s.verify_self(), Err(Error::VerifySignatureFailed)));
+        assert!(matches!(
+            s.verify("hello", &sig),
+            Err(Error::VerifySignatureFailed)
+        ));
+    }
+
+    #[test]
+    pub fn test_new_with_session_key() {
+        let key = SecretKey::random();
+        let session_key = SecretKey::random();
+        let authorizer_entity = Did::from(key.address()).to_string();
+        let new_builder = || {
+            SessionManagerBuilder::new_with_session_key(
+                authorizer_entity.clone(),
+                "secp256k1".to_string(),
+                session_key,
+            )
+        };
+        let builder = new_builder();
+        let session_id = Did::from(session_key.address());
+        assert!(builder
+            .pack_session()
+            .starts_with(&format!("{}\n", session_id)));
+        let sig = key.sign(&builder.pack_session());
+        let sm = builder.sig(sig.to_vec()).build().unwrap();
+        assert_eq!(sm.session().session_id(), session_id);
+        assert_eq!(sm.session_key, session_key);
+
+        // Sessions of the same session key are reproducible.
+        let other = new_builder();
+        let sig = key.sign(&other.pack_session());
+        let other = other.sig(sig.to_vec()).build().unwrap();
+        assert_eq!(other.session().session_id(), sm.session().session_id());
+    }
+
     #[test]
     pub fn test_dump_restore() {
         let key = SecretKey::random();
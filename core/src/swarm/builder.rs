@@ -2,20 +2,29 @@
 //! This module provider [SwarmBuilder] and it's interface for
 //! [Swarm]
 
-use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::RwLock;
+
+use async_lock::Semaphore;
 
 use crate::channels::Channel;
+use crate::consts::DEFAULT_HOP_LIMIT;
+use crate::consts::DEFAULT_MAX_CONCURRENT_OFFERS;
+use crate::consts::DEFAULT_MAX_PENDING_TRANSPORTS;
 use crate::dht::PeerRing;
+use crate::dht::VNodeCache;
 use crate::message::CallbackFn;
 use crate::message::MessageHandler;
+use crate::message::ReplayCache;
 use crate::message::ValidatorFn;
 use crate::session::SessionManager;
 use crate::storage::MemStorage;
 use crate::storage::PersistenceStorage;
 use crate::swarm::MeasureImpl;
 use crate::swarm::Swarm;
+use crate::swarm::SwarmStats;
+use crate::transports::backpressure::Backpressure;
 use crate::types::channel::Channel as ChannelTrait;
 use crate::types::ice_transport::IceServer;
 
@@ -30,24 +39,37 @@ pub struct SwarmBuilder {
     measure: Option<MeasureImpl>,
     message_callback: Option<CallbackFn>,
     message_validator: Option<ValidatorFn>,
+    replay_cache: Option<ReplayCache>,
+    hop_limit: u8,
+    max_pending_transports: usize,
+    max_concurrent_offers: usize,
+    backpressure: Backpressure,
+    vnode_cache: Option<VNodeCache>,
+    #[cfg(feature = "deterministic")]
+    handshake_seed: Option<u64>,
 }
 
 impl SwarmBuilder {
     /// Creates new instance of [SwarmBuilder]
+    /// The `ice_servers` are separated by `;`, it panics if any of them is invalid.
+    /// Use [SwarmBuilder::new_with_ice_servers] to validate them in advance.
     pub fn new(
         ice_servers: &str,
         dht_storage: PersistenceStorage,
         session_manager: SessionManager,
     ) -> Self {
-        let ice_servers = ice_servers
-            .split(';')
-            .collect::<Vec<&str>>()
-            .into_iter()
-            .map(|s| {
-                IceServer::from_str(s)
-                    .unwrap_or_else(|_| panic!("Failed on parse ice server {:?}", s))
-            })
-            .collect::<Vec<IceServer>>();
+        let ice_servers = IceServer::vec_from_str(ice_servers)
+            .unwrap_or_else(|e| panic!("Failed on parse ice servers {:?}: {}", ice_servers, e));
+        Self::new_with_ice_servers(ice_servers, dht_storage, session_manager)
+    }
+
+    /// Creates new instance of [SwarmBuilder] with ice servers,
+    /// use [IceServer::vec_from_str] or [IceServer::new] to build and validate them.
+    pub fn new_with_ice_servers(
+        ice_servers: Vec<IceServer>,
+        dht_storage: PersistenceStorage,
+        session_manager: SessionManager,
+    ) -> Self {
         SwarmBuilder {
             ice_servers,
             external_address: None,
@@ -58,6 +80,14 @@ impl SwarmBuilder {
             measure: None,
             message_callback: None,
             message_validator: None,
+            replay_cache: None,
+            hop_limit: DEFAULT_HOP_LIMIT,
+            max_pending_transports: DEFAULT_MAX_PENDING_TRANSPORTS,
+            max_concurrent_offers: DEFAULT_MAX_CONCURRENT_OFFERS,
+            backpressure: Backpressure::default(),
+            vnode_cache: None,
+            #[cfg(feature = "deterministic")]
+            handshake_seed: None,
         }
     }
 
@@ -98,18 +128,73 @@ impl SwarmBuilder {
         self
     }
 
+    /// Sets up the capacity and window of the cache used to reject replayed messages.
+    /// The window should not be less than the ttl of messages.
+    pub fn replay_cache(mut self, capacity: usize, window_ms: usize) -> Self {
+        self.replay_cache = Some(ReplayCache::new(capacity, window_ms));
+        self
+    }
+
+    /// Sets up the count of hops a message sent by swarm can be forwarded.
+    /// A message looping among nodes is dropped when it's exhausted.
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    /// Sets up the max count of transports in handshake.
+    /// When it's exceeded, the oldest pending transport is closed before a new one is accepted.
+    pub fn max_pending_transports(mut self, max: usize) -> Self {
+        self.max_pending_transports = max;
+        self
+    }
+
+    /// Sets up the max count of offers generated concurrently.
+    /// Excess offers wait for a slot instead of gathering ice candidates all at once,
+    /// which may exhaust local ports. It's at least 1.
+    pub fn max_concurrent_offers(mut self, max: usize) -> Self {
+        self.max_concurrent_offers = max.max(1);
+        self
+    }
+
+    /// Sets up the watermarks of send queue of each transport.
+    /// Sending through a transport with buffered bytes over the high watermark waits or fails,
+    /// see [Backpressure].
+    pub fn backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Sets up the capacity and ttl of the cache of fetched virtual nodes, see [VNodeCache].
+    /// Cached virtual nodes never expire if `ttl_ms` is `None`.
+    pub fn vnode_cache(mut self, capacity: usize, ttl_ms: Option<u64>) -> Self {
+        self.vnode_cache = Some(VNodeCache::new(capacity, ttl_ms));
+        self
+    }
+
+    /// Seed the randomness of handshake, so that offers and answers are reproducible in tests.
+    /// See [HandshakeRng](crate::swarm::HandshakeRng) for what is seeded.
+    #[cfg(feature = "deterministic")]
+    pub fn handshake_seed(mut self, seed: u64) -> Self {
+        self.handshake_seed = Some(seed);
+        self
+    }
+
     /// Try build for `Swarm`.
     pub fn build(self) -> Swarm {
         let dht_did = self.session_manager.authorizer_did();
 
-        let dht = Arc::new(PeerRing::new_with_storage(
-            dht_did,
-            self.dht_succ_max,
-            self.dht_storage,
-        ));
+        let mut dht = PeerRing::new_with_storage(dht_did, self.dht_succ_max, self.dht_storage);
+        if let Some(cache) = self.vnode_cache {
+            dht.cache = Arc::new(cache);
+        }
+        let dht = Arc::new(dht);
 
-        let message_handler =
+        let mut message_handler =
             MessageHandler::new(dht.clone(), self.message_callback, self.message_validator);
+        if let Some(replay_cache) = self.replay_cache {
+            message_handler = message_handler.with_replay_cache(replay_cache);
+        }
 
         Swarm {
             pending_transports: Mutex::new(vec![]),
@@ -119,7 +204,16 @@ impl SwarmBuilder {
             external_address: self.external_address,
             dht,
             measure: self.measure,
-            session_manager: self.session_manager,
+            stats: SwarmStats::default(),
+            hop_limit: self.hop_limit,
+            max_pending_transports: self.max_pending_transports,
+            max_concurrent_offers: self.max_concurrent_offers,
+            offer_slots: Semaphore::new(self.max_concurrent_offers),
+            backpressure: self.backpressure,
+            last_active: MemStorage::new(),
+            #[cfg(feature = "deterministic")]
+            handshake_rng: self.handshake_seed.map(crate::swarm::HandshakeRng::new),
+            session_manager: RwLock::new(Arc::new(self.session_manager)),
             message_handler,
         }
     }
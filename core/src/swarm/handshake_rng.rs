@@ -0,0 +1,72 @@
+#![warn(missing_docs)]
+//! Deterministic randomness of handshake, used to write golden tests of handshake payloads.
+//!
+//! Only the randomness owned by rings is seeded: the id of transports, the tx_id of offer and answer
+//! payloads, and the ice credentials of native transports. DTLS certificates and gathered candidates
+//! of webrtc are still random.
+use std::sync::Mutex;
+
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+/// A seeded random generator used by swarm in handshake.
+#[derive(Debug)]
+pub struct HandshakeRng {
+    rng: Mutex<StdRng>,
+}
+
+impl HandshakeRng {
+    /// Create a generator with seed, generators of the same seed generate the same sequence.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut rng)
+    }
+
+    /// Generate a random uuid of version 4.
+    pub fn uuid(&self) -> uuid::Uuid {
+        let bytes = self.with_rng(|rng| rng.gen::<[u8; 16]>());
+        uuid::Builder::from_bytes(bytes)
+            .set_variant(uuid::Variant::RFC4122)
+            .set_version(uuid::Version::Random)
+            .build()
+    }
+
+    /// Generate ice username fragment and password.
+    pub fn ice_credentials(&self) -> (String, String) {
+        self.with_rng(|rng| {
+            let mut gen = |len| {
+                rng.sample_iter(&Alphanumeric)
+                    .take(len)
+                    .map(char::from)
+                    .collect::<String>()
+            };
+            (gen(16), gen(32))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_rng() {
+        let rng1 = HandshakeRng::new(42);
+        let rng2 = HandshakeRng::new(42);
+        let uuid = rng1.uuid();
+        assert_eq!(uuid, rng2.uuid());
+        assert_eq!(uuid.get_version(), Some(uuid::Version::Random));
+        let (ufrag, pwd) = rng1.ice_credentials();
+        assert_eq!((ufrag.len(), pwd.len()), (16, 32));
+        assert_eq!((ufrag, pwd), rng2.ice_credentials());
+        assert_ne!(rng1.uuid(), HandshakeRng::new(43).uuid());
+    }
+}
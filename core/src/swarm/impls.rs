@@ -19,15 +19,26 @@ use crate::transports::Transport;
 use crate::types::channel::Channel as ChannelTrait;
 use crate::types::ice_transport::IceTransportInterface;
 use crate::types::ice_transport::IceTrickleScheme;
+use crate::types::ice_transport::TransportDirection;
 
-#[cfg_attr(feature = "wasm", async_trait(?Send))]
-#[cfg_attr(not(feature = "wasm"), async_trait)]
-impl TransportManager for Swarm {
-    type Transport = Arc<Transport>;
-
-    async fn new_transport(&self) -> Result<Self::Transport> {
+impl Swarm {
+    /// Create and start a transport, with the side initiated it if it's for a handshake.
+    async fn new_transport_with_direction(
+        &self,
+        direction: Option<TransportDirection>,
+    ) -> Result<Arc<Transport>> {
         let event_sender = self.transport_event_channel.sender();
         let mut ice_transport = Transport::new(event_sender);
+        if let Some(direction) = direction {
+            ice_transport.set_direction(direction);
+        }
+        // Dummy transports are registered to the hub by id, so their ids are kept.
+        #[cfg(all(feature = "deterministic", not(feature = "dummy")))]
+        if let Some(rng) = &self.handshake_rng {
+            ice_transport.id = rng.uuid();
+            #[cfg(not(feature = "wasm"))]
+            ice_transport.set_ice_credentials(rng.ice_credentials());
+        }
         ice_transport
             .start(self.ice_servers.clone(), self.external_address.clone())
             .await?
@@ -36,6 +47,16 @@ impl TransportManager for Swarm {
 
         Ok(Arc::new(ice_transport))
     }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl TransportManager for Swarm {
+    type Transport = Arc<Transport>;
+
+    async fn new_transport(&self) -> Result<Self::Transport> {
+        self.new_transport_with_direction(None).await
+    }
 
     // register to swarm transports
     // should not wait connection statues here
@@ -57,6 +78,8 @@ impl TransportManager for Swarm {
             }
             if t.id != id {
                 self.transports.set(&did, trans);
+                self.stats.incr_opened();
+                self.stats.incr_closed();
                 if let Err(e) = t.close().await {
                     tracing::error!("failed to close previous while registering {:?}", e);
                     return Err(Error::SwarmToClosePrevTransport(format!("{:?}", e)));
@@ -65,7 +88,9 @@ impl TransportManager for Swarm {
             }
         } else {
             self.transports.set(&did, trans);
+            self.stats.incr_opened();
         }
+        self.touch(did);
         Ok(())
     }
 
@@ -94,7 +119,12 @@ impl TransportManager for Swarm {
     }
 
     fn remove_transport(&self, did: Did) -> Option<(Did, Self::Transport)> {
-        self.transports.remove(&did)
+        let removed = self.transports.remove(&did);
+        if removed.is_some() {
+            self.stats.incr_closed();
+            self.last_active.remove(&did);
+        }
+        removed
     }
 
     fn get_dids(&self) -> Vec<Did> {
@@ -113,10 +143,14 @@ impl TransportHandshake for Swarm {
     type Payload = MessagePayload<Message>;
 
     async fn prepare_transport_offer(&self) -> Result<(Self::Transport, ConnectNodeSend)> {
-        let trans = self.new_transport().await?;
+        // The slot is held until ice candidates are gathered, since gathering binds local ports.
+        let _slot = self.offer_slots.acquire().await;
+        let trans = self
+            .new_transport_with_direction(Some(TransportDirection::Outbound))
+            .await?;
         let offer = trans.get_handshake_info(RTCSdpType::Offer).await?;
 
-        self.push_pending_transport(&trans)?;
+        self.push_pending_transport_and_evict(&trans).await?;
 
         let offer_msg = ConnectNodeSend {
             transport_uuid: trans.id.to_string(),
@@ -135,12 +169,14 @@ impl TransportHandshake for Swarm {
             return Err(Error::AlreadyConnected);
         };
 
-        let trans = self.new_transport().await?;
+        let trans = self
+            .new_transport_with_direction(Some(TransportDirection::Inbound))
+            .await?;
 
         trans.register_remote_info(&offer_msg.offer, did).await?;
         let answer = trans.get_handshake_info(RTCSdpType::Answer).await?;
 
-        self.push_pending_transport(&trans)?;
+        self.push_pending_transport_and_evict(&trans).await?;
 
         let answer_msg = ConnectNodeReport {
             transport_uuid: offer_msg.transport_uuid.clone(),
@@ -155,12 +191,17 @@ impl TransportHandshake for Swarm {
 
         // This payload has fake destination and fake next_hop.
         // The invoker should fix it before sending if it is not a direct message.
-        let payload = MessagePayload::new_send(
+        #[allow(unused_mut)]
+        let mut payload = MessagePayload::new_send(
             Message::ConnectNodeSend(offer_msg),
-            self.session_manager(),
+            &self.session_manager(),
             self.did(),
             self.did(),
         )?;
+        #[cfg(feature = "deterministic")]
+        if let Some(rng) = &self.handshake_rng {
+            payload.tx_id = rng.uuid();
+        }
 
         Ok((transport, payload))
     }
@@ -187,12 +228,17 @@ impl TransportHandshake for Swarm {
 
         // This payload has fake next_hop.
         // The invoker should fix it before sending if it is not a direct message.
-        let answer_payload = MessagePayload::new_send(
+        #[allow(unused_mut)]
+        let mut answer_payload = MessagePayload::new_send(
             Message::ConnectNodeReport(answer_msg),
-            self.session_manager(),
+            &self.session_manager(),
             self.did(),
             self.did(),
         )?;
+        #[cfg(feature = "deterministic")]
+        if let Some(rng) = &self.handshake_rng {
+            answer_payload.tx_id = rng.uuid();
+        }
 
         Ok((transport, answer_payload))
     }
@@ -1,19 +1,27 @@
 #![warn(missing_docs)]
 //! Tranposrt management
 mod builder;
+#[cfg(feature = "deterministic")]
+mod handshake_rng;
 mod impls;
+mod stats;
 mod types;
 
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::RwLock;
 
+use async_lock::Semaphore;
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 pub use builder::SwarmBuilder;
+#[cfg(feature = "deterministic")]
+pub use handshake_rng::HandshakeRng;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+pub use stats::SwarmStats;
 pub use types::MeasureImpl;
 pub use types::WrappedDid;
 
@@ -27,6 +35,8 @@ use crate::error::Result;
 use crate::inspect::SwarmInspect;
 use crate::measure::MeasureCounter;
 use crate::message;
+use crate::message::types::IceRestartReport;
+use crate::message::types::IceRestartSend;
 use crate::message::types::NotifyPredecessorSend;
 use crate::message::ChordStorageInterface;
 use crate::message::Message;
@@ -34,8 +44,11 @@ use crate::message::MessageHandler;
 use crate::message::MessageHandlerEvent;
 use crate::message::MessagePayload;
 use crate::message::PayloadSender;
+use crate::prelude::RTCSdpType;
 use crate::session::SessionManager;
 use crate::storage::MemStorage;
+use crate::transports::backpressure::Backpressure;
+use crate::transports::lanes::Priority;
 use crate::transports::manager::TransportHandshake;
 use crate::transports::manager::TransportManager;
 use crate::transports::Transport;
@@ -44,6 +57,7 @@ use crate::types::channel::TransportEvent;
 use crate::types::ice_transport::IceServer;
 use crate::types::ice_transport::IceTransportInterface;
 use crate::types::ice_transport::IceTrickleScheme;
+use crate::utils::get_epoch_ms;
 
 /// The transports and dht management.
 pub struct Swarm {
@@ -61,7 +75,24 @@ pub struct Swarm {
     pub(crate) dht: Arc<PeerRing>,
     /// Implementationof measurement.
     pub(crate) measure: Option<MeasureImpl>,
-    session_manager: SessionManager,
+    /// Counters of traffic and transports.
+    pub(crate) stats: SwarmStats,
+    /// Count of hops a message sent by swarm can be forwarded.
+    pub(crate) hop_limit: u8,
+    /// Max count of pending transports.
+    pub(crate) max_pending_transports: usize,
+    /// Max count of offers generated concurrently.
+    pub(crate) max_concurrent_offers: usize,
+    /// Slots of offer generation, an offer holds one until its ice candidates are gathered.
+    pub(crate) offer_slots: Semaphore,
+    /// Watermarks of send queue of each transport.
+    pub(crate) backpressure: Backpressure,
+    /// Epoch time in ms of the last message sent to or received from each peer.
+    pub(crate) last_active: MemStorage<Did, u128>,
+    /// Seeded randomness of handshake.
+    #[cfg(feature = "deterministic")]
+    pub(crate) handshake_rng: Option<HandshakeRng>,
+    session_manager: RwLock<Arc<SessionManager>>,
     message_handler: MessageHandler,
 }
 
@@ -76,11 +107,47 @@ impl Swarm {
         self.dht.clone()
     }
 
+    /// Get counters of traffic and transports.
+    pub fn stats(&self) -> &SwarmStats {
+        &self.stats
+    }
+
+    /// Epoch time in ms of the last message sent to or received from peer,
+    /// or of the register of its transport if there is no message yet.
+    pub fn last_active_ms(&self, did: Did) -> Option<u128> {
+        self.last_active.get(&did)
+    }
+
+    pub(crate) fn touch(&self, did: Did) {
+        self.last_active.set(&did, get_epoch_ms());
+    }
+
     /// Retrieves the session manager associated with the current instance.
     /// The session manager provides a segregated approach to manage private keys.
     /// It generates delegated secret keys for the bound entries of PKIs (Public Key Infrastructure).
-    pub fn session_manager(&self) -> &SessionManager {
-        &self.session_manager
+    /// The session manager can be replaced by a renewed one, see [Swarm::set_session_manager].
+    pub fn session_manager(&self) -> Arc<SessionManager> {
+        self.session_manager
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the session manager, such as by a renewed one with extended lifetime.
+    /// Messages are signed by the new session afterwards.
+    /// The authorizer of new session should be the same as existing one, since did of swarm is derived from it.
+    pub fn set_session_manager(&self, session_manager: SessionManager) -> Result<()> {
+        let mut guard = self
+            .session_manager
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        let existing = guard.authorizer_did();
+        let authorizer = session_manager.authorizer_did();
+        if authorizer != existing {
+            return Err(Error::AuthorizerMismatch(existing, authorizer));
+        }
+        *guard = Arc::new(session_manager);
+        Ok(())
     }
 
     /// Load message from a TransportEvent.
@@ -88,6 +155,9 @@ impl Swarm {
         match ev {
             TransportEvent::DataChannelMessage(msg) => {
                 let payload = MessagePayload::from_bincode(&msg)?;
+                self.stats
+                    .incr_received(msg.len(), payload.relay.destination != self.dht.did);
+                self.touch(payload.addr);
                 tracing::debug!("load message from channel: {:?}", payload);
                 Ok(Some(payload))
             }
@@ -103,7 +173,7 @@ impl Swarm {
                     Some(_) => {
                         let payload = MessagePayload::new_send(
                             Message::JoinDHT(message::JoinDHT { did }),
-                            &self.session_manager,
+                            &self.session_manager(),
                             self.dht.did,
                             self.dht.did,
                         )?;
@@ -125,7 +195,7 @@ impl Swarm {
                         tracing::info!("[Swarm::ConnectClosed] transport {:?} closed", uuid);
                         let payload = MessagePayload::new_send(
                             Message::LeaveDHT(message::LeaveDHT { did }),
-                            &self.session_manager,
+                            &self.session_manager(),
                             self.dht.did,
                             self.dht.did,
                         )?;
@@ -245,6 +315,34 @@ impl Swarm {
                 Ok(vec![])
             }
 
+            MessageHandlerEvent::AnswerIceRestart(relay, msg) => {
+                let sender = relay.relay.origin_sender();
+                let transport = self
+                    .get_transport(sender)
+                    .ok_or(Error::MessageHandlerMissTransportConnectedNode)?;
+                transport.register_remote_info(&msg.offer, sender).await?;
+                let answer = transport.get_handshake_info(RTCSdpType::Answer).await?;
+
+                Ok(vec![MessageHandlerEvent::SendReportMessage(
+                    relay.clone(),
+                    Message::IceRestartReport(IceRestartReport {
+                        transport_uuid: msg.transport_uuid.clone(),
+                        answer,
+                    }),
+                )])
+            }
+
+            MessageHandlerEvent::AcceptIceRestart(sender, msg) => {
+                let transport = self
+                    .get_transport(*sender)
+                    .filter(|t| t.id.to_string() == msg.transport_uuid)
+                    .ok_or(Error::MessageHandlerMissTransportConnectedNode)?;
+                transport
+                    .register_remote_info(&msg.answer, sender.to_owned())
+                    .await?;
+                Ok(vec![])
+            }
+
             MessageHandlerEvent::ForwardPayload(payload, next_hop) => {
                 if self
                     .get_and_check_transport(payload.relay.destination)
@@ -318,12 +416,39 @@ impl Swarm {
     }
 
     /// Push a pending transport to pending list.
-    pub fn push_pending_transport(&self, transport: &Arc<Transport>) -> Result<()> {
+    /// If the list is full, the oldest pending transport is removed and returned, which should be closed by caller.
+    pub fn push_pending_transport(
+        &self,
+        transport: &Arc<Transport>,
+    ) -> Result<Option<Arc<Transport>>> {
         let mut pending = self
             .pending_transports
             .try_lock()
             .map_err(|_| Error::SwarmPendingTransTryLockFailed)?;
+        let evicted = if pending.len() >= self.max_pending_transports.max(1) {
+            Some(pending.remove(0))
+        } else {
+            None
+        };
         pending.push(transport.to_owned());
+        Ok(evicted)
+    }
+
+    /// Push a pending transport to pending list, and close the evicted one if the list is full.
+    pub(crate) async fn push_pending_transport_and_evict(
+        &self,
+        transport: &Arc<Transport>,
+    ) -> Result<()> {
+        if let Some(evicted) = self.push_pending_transport(transport)? {
+            tracing::warn!(
+                "pending transports exceed {}, evict transport {}",
+                self.max_pending_transports,
+                evicted.id
+            );
+            if let Err(e) = evicted.close().await {
+                tracing::error!("failed to close evicted transport {}: {:?}", evicted.id, e);
+            }
+        }
         Ok(())
     }
 
@@ -341,6 +466,30 @@ impl Swarm {
         Ok(())
     }
 
+    /// Max count of pending transports, see [SwarmBuilder::max_pending_transports].
+    pub fn max_pending_transports(&self) -> usize {
+        self.max_pending_transports
+    }
+
+    /// Max count of offers generated concurrently, see [SwarmBuilder::max_concurrent_offers].
+    pub fn max_concurrent_offers(&self) -> usize {
+        self.max_concurrent_offers
+    }
+
+    /// Watermarks of send queue of each transport, see [SwarmBuilder::backpressure].
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+
+    /// Count of pending transports.
+    pub fn pending_transport_count(&self) -> Result<usize> {
+        let pending = self
+            .pending_transports
+            .try_lock()
+            .map_err(|_| Error::SwarmPendingTransTryLockFailed)?;
+        Ok(pending.len())
+    }
+
     /// List all the pending transports.
     pub async fn pending_transports(&self) -> Result<Vec<Arc<Transport>>> {
         let pending = self
@@ -402,6 +551,39 @@ impl Swarm {
         Ok(transport)
     }
 
+    /// Restart ice of the transport connected to a Did, to recover it from a network change.
+    /// The transport, its data channel and the dht are kept, fresh candidates are gathered
+    /// and sent to the Did as an ice restart offer.
+    ///
+    /// The path of the transport is unusable while restarting, so the offer is relayed by
+    /// another connected peer, return [Error::IceRestartNoRelay] if there is none.
+    /// Return [Error::TransportNotRestartable] if the transport is not established yet or closed.
+    pub async fn restart_ice(&self, did: Did) -> Result<Arc<Transport>> {
+        let transport = self.get_transport(did).ok_or(Error::TransportNotFound)?;
+
+        let mut relay = None;
+        for (peer, t) in self.get_transports() {
+            if peer != did && t.is_connected().await {
+                relay = Some(peer);
+                break;
+            }
+        }
+        let next_hop = relay.ok_or(Error::IceRestartNoRelay)?;
+
+        let offer = transport.get_ice_restart_info().await?;
+        self.send_message_by_hop(
+            Message::IceRestartSend(IceRestartSend {
+                transport_uuid: transport.id.to_string(),
+                offer,
+            }),
+            did,
+            next_hop,
+        )
+        .await?;
+
+        Ok(transport)
+    }
+
     /// Check the status of swarm
     pub async fn inspect(&self) -> SwarmInspect {
         SwarmInspect::inspect(self).await
@@ -413,7 +595,7 @@ impl Swarm {
 impl<T> PayloadSender<T> for Swarm
 where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static + fmt::Debug
 {
-    fn session_manager(&self) -> &SessionManager {
+    fn session_manager(&self) -> Arc<SessionManager> {
         Swarm::session_manager(self)
     }
 
@@ -421,6 +603,10 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static + fmt::Deb
         Swarm::dht(self)
     }
 
+    fn hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
     async fn do_send_payload(&self, did: Did, payload: MessagePayload<T>) -> Result<()> {
         #[cfg(test)]
         {
@@ -446,7 +632,13 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static + fmt::Deb
         let data = payload.to_bincode()?;
 
         transport.wait_for_data_channel_open().await?;
-        let result = transport.send_message(&data).await;
+        // High priority messages jump ahead of queued chunks, so they don't wait for backpressure.
+        if payload.priority != Priority::High {
+            self.backpressure.reserve(&transport).await?;
+        }
+        let result = transport
+            .send_message_with_priority(&data, payload.priority)
+            .await;
 
         tracing::debug!(
             "Sent {:?}, to node {:?} via transport {:?}",
@@ -455,6 +647,11 @@ where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static + fmt::Deb
             transport.id
         );
 
+        if result.is_ok() {
+            self.stats.incr_sent();
+            self.touch(did);
+        }
+
         if let (Some(measure), did) = (&self.measure, payload.relay.next_hop) {
             if result.is_ok() {
                 measure.incr(did, MeasureCounter::Sent).await
@@ -499,6 +696,8 @@ pub mod tests {
 
     use super::*;
     use crate::ecc::SecretKey;
+    use crate::message::Decoder;
+    use crate::message::Encoder;
     use crate::storage::PersistenceStorage;
     #[cfg(not(feature = "dummy"))]
     use crate::transports::default::transport::tests::establish_connection;
@@ -525,6 +724,80 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_max_pending_transports() -> Result<()> {
+        let key = SecretKey::random();
+        let storage =
+            PersistenceStorage::new_with_path(PersistenceStorage::random_path("./tmp")).await?;
+        let session_manager = SessionManager::new_with_seckey(&key)?;
+        let stun = "stun://stun.l.google.com:19302";
+        let swarm = SwarmBuilder::new(stun, storage, session_manager)
+            .max_pending_transports(2)
+            .build();
+        assert_eq!(swarm.max_pending_transports(), 2);
+
+        let mut ids = vec![];
+        for _ in 0..3 {
+            let (transport, _) = swarm.create_offer().await?;
+            ids.push(transport.id);
+        }
+        assert_eq!(swarm.pending_transport_count()?, 2);
+        assert!(swarm.find_pending_transport(ids[0])?.is_none());
+        assert!(swarm.find_pending_transport(ids[1])?.is_some());
+        assert!(swarm.find_pending_transport(ids[2])?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_offers() -> Result<()> {
+        let key = SecretKey::random();
+        let storage =
+            PersistenceStorage::new_with_path(PersistenceStorage::random_path("./tmp")).await?;
+        let session_manager = SessionManager::new_with_seckey(&key)?;
+        let stun = "stun://stun.l.google.com:19302";
+        let swarm = SwarmBuilder::new(stun, storage, session_manager)
+            .max_concurrent_offers(4)
+            .build();
+        assert_eq!(swarm.max_concurrent_offers(), 4);
+
+        // Offers more than slots wait for their turn, and all of them are completed.
+        let offers = futures::future::join_all((0..32).map(|_| swarm.create_offer())).await;
+        let ids = offers
+            .into_iter()
+            .map(|r| r.map(|(transport, _)| transport.id))
+            .collect::<Result<std::collections::HashSet<_>>>()?;
+        assert_eq!(ids.len(), 32);
+        assert_eq!(swarm.pending_transport_count()?, 32);
+
+        // An offer is not generated until a slot is released.
+        let slots = futures::future::join_all((0..4).map(|_| swarm.offer_slots.acquire())).await;
+        assert!(
+            time::timeout(time::Duration::from_millis(500), swarm.create_offer())
+                .await
+                .is_err()
+        );
+        drop(slots);
+        swarm.create_offer().await?;
+        assert_eq!(swarm.pending_transport_count()?, 33);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_swarm_compressed_offer() -> Result<()> {
+        let swarm1 = new_swarm(SecretKey::random()).await?;
+        let swarm2 = new_swarm(SecretKey::random()).await?;
+        let (_, offer) = swarm1.create_offer().await?;
+
+        let raw = offer.encode()?;
+        let compressed = offer.encode_compressed()?;
+        assert!(compressed.len() < raw.len());
+
+        // A compressed offer is answered as the raw one.
+        let offer = MessagePayload::<Message>::from_encoded(&compressed)?;
+        swarm2.answer_offer(offer).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_swarm_register_and_get() -> Result<()> {
         let swarm1 = new_swarm(SecretKey::random()).await?;
@@ -552,6 +825,30 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_swarm_stats_transports() -> Result<()> {
+        let swarm1 = new_swarm(SecretKey::random()).await?;
+        let swarm2 = new_swarm(SecretKey::random()).await?;
+
+        let transport1 = swarm1.new_transport().await.unwrap();
+        let transport2 = swarm2.new_transport().await.unwrap();
+        establish_connection(&transport1, &transport2).await?;
+
+        swarm1.register(swarm2.did(), transport1.clone()).await?;
+        assert_eq!(swarm1.stats().transports_opened(), 1);
+        assert_eq!(swarm1.stats().transports_closed(), 0);
+
+        swarm1.disconnect(swarm2.did()).await?;
+        assert_eq!(swarm1.stats().transports_opened(), 1);
+        assert_eq!(swarm1.stats().transports_closed(), 1);
+
+        // Removing a missing transport is not counted.
+        assert!(swarm1.remove_transport(swarm2.did()).is_none());
+        assert_eq!(swarm1.stats().transports_closed(), 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_swarm_will_close_previous_transport() -> Result<()> {
         let swarm1 = new_swarm(SecretKey::random()).await?;
@@ -0,0 +1,70 @@
+#![warn(missing_docs)]
+//! Counters of swarm traffic and transports.
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Counters of swarm, which are only incremented since swarm is built.
+#[derive(Debug, Default)]
+pub struct SwarmStats {
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_relayed: AtomicU64,
+    transports_opened: AtomicU64,
+    transports_closed: AtomicU64,
+}
+
+impl SwarmStats {
+    pub(crate) fn incr_sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a received message of `bytes`, which is `relayed` if it's destined to other node.
+    pub(crate) fn incr_received(&self, bytes: usize, relayed: bool) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if relayed {
+            self.bytes_relayed
+                .fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn incr_opened(&self) {
+        self.transports_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn incr_closed(&self) {
+        self.transports_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count of messages sent to transports successfully.
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    /// Count of messages received from transports.
+    pub fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of messages received from transports.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of received messages which are destined to other nodes.
+    pub fn bytes_relayed(&self) -> u64 {
+        self.bytes_relayed.load(Ordering::Relaxed)
+    }
+
+    /// Count of transports registered to swarm.
+    pub fn transports_opened(&self) -> u64 {
+        self.transports_opened.load(Ordering::Relaxed)
+    }
+
+    /// Count of transports removed or replaced from swarm.
+    pub fn transports_closed(&self) -> u64 {
+        self.transports_closed.load(Ordering::Relaxed)
+    }
+}
@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+use web3::types::U256;
+
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::ecc::SecretKey;
@@ -49,27 +51,13 @@ pub async fn gen_pure_dht(did: Did) -> Result<PeerRing> {
     Ok(PeerRing::new_with_storage(did, 3, db))
 }
 
+/// Generate pure dhts of `s` dids evenly spaced on the ring, in ascending order.
 pub async fn gen_sorted_dht(s: usize) -> Vec<PeerRing> {
-    let mut keys: Vec<crate::ecc::SecretKey> = vec![];
-    for _i in 0..s {
-        keys.push(crate::ecc::SecretKey::random());
-    }
-    keys.sort_by_key(|a| a.address());
-
-    #[allow(clippy::needless_collect)]
-    let dids: Vec<crate::dht::Did> = keys
-        .iter()
-        .map(|sk| crate::dht::Did::from(sk.address()))
-        .collect();
-
-    let mut iter = dids.into_iter();
-    let mut ret: Vec<crate::dht::PeerRing> = vec![];
-    for _ in 0..s {
-        ret.push(
-            crate::tests::default::gen_pure_dht(iter.next().unwrap())
-                .await
-                .unwrap(),
-        )
+    let step = U256::from(2).pow(U256::from(160)) / U256::from(s);
+    let mut ret: Vec<PeerRing> = vec![];
+    for i in 0..s {
+        let did = Did::at_position(step / 2 + step * U256::from(i));
+        ret.push(gen_pure_dht(did).await.unwrap())
     }
     ret
 }
@@ -7,11 +7,17 @@ use super::prepare_node;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::Chord;
 use crate::dht::Stabilization;
+use crate::dht::StabilizationConfig;
 use crate::dht::TStabilize;
+use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
 use crate::error::Error;
 use crate::error::Result;
 use crate::inspect::DHTInspect;
+use crate::message::LeaveDHT;
+use crate::message::Message;
+use crate::message::MessagePayload;
+use crate::message::PayloadSender;
 use crate::swarm::tests::new_swarm;
 use crate::swarm::Swarm;
 use crate::tests::default::gen_pure_dht;
@@ -47,12 +53,9 @@ async fn run_node(swarm: Arc<Swarm>) {
 
 #[tokio::test]
 async fn test_stabilization_once() -> Result<()> {
-    let mut key1 = SecretKey::random();
-    let mut key2 = SecretKey::random();
-    // key 2 > key 1 here
-    if key1.address() < key2.address() {
-        (key1, key2) = (key2, key1)
-    }
+    // key 1 > key 2 here
+    let keys = gen_ordered_keys(2);
+    let (key1, key2) = (keys[1], keys[0]);
     let swarm1 = Arc::new(new_swarm(key1).await?);
     let swarm2 = Arc::new(new_swarm(key2).await?);
     manually_establish_connection(&swarm1, &swarm2).await?;
@@ -94,12 +97,9 @@ async fn test_stabilization_once() -> Result<()> {
 
 #[tokio::test]
 async fn test_stabilization() -> Result<()> {
-    let mut key1 = SecretKey::random();
-    let mut key2 = SecretKey::random();
-    // key 2 > key 1 here
-    if key1.address() < key2.address() {
-        (key1, key2) = (key2, key1)
-    }
+    // key 1 > key 2 here
+    let keys = gen_ordered_keys(2);
+    let (key1, key2) = (keys[1], keys[0]);
     let swarm1 = Arc::new(new_swarm(key1).await?);
     let swarm2 = Arc::new(new_swarm(key2).await?);
     manually_establish_connection(&swarm1, &swarm2).await?;
@@ -182,3 +182,208 @@ async fn test_online_stabilization() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_stabilization_leave() -> Result<()> {
+    let keys = gen_ordered_keys(3);
+    let (key1, key2, key3) = (keys[0], keys[1], keys[2]);
+    let swarm1 = Arc::new(new_swarm(key1).await?);
+    let swarm2 = Arc::new(new_swarm(key2).await?);
+    let swarm3 = Arc::new(new_swarm(key3).await?);
+    manually_establish_connection(&swarm1, &swarm2).await?;
+    manually_establish_connection(&swarm2, &swarm3).await?;
+    manually_establish_connection(&swarm1, &swarm3).await?;
+
+    tokio::select! {
+        _ = async {
+            futures::join!(
+                async {
+                    loop {
+                        swarm1.clone().listen().await;
+                    }
+                },
+                async {
+                    loop {
+                        swarm2.clone().listen().await;
+                    }
+                },
+                async {
+                    loop {
+                        swarm3.clone().listen().await;
+                    }
+                },
+            );
+        } => { unreachable!(); }
+        _ = async {
+            sleep(Duration::from_millis(1000)).await;
+            for swarm in [&swarm1, &swarm2, &swarm3] {
+                Stabilization::new(Arc::clone(swarm), 5usize)
+                    .notify_predecessor()
+                    .await?;
+            }
+            sleep(Duration::from_millis(1000)).await;
+            // ring: 1 -> 2 -> 3 -> 1
+            assert_eq!(swarm1.dht().successors().min()?, swarm2.did());
+            assert_eq!(*swarm3.dht().lock_predecessor()?, Some(swarm2.did()));
+
+            // A node can't leave for others.
+            let forged = MessagePayload::new_send(
+                Message::LeaveDHT(LeaveDHT { did: swarm2.did() }),
+                &swarm3.session_manager(),
+                swarm1.did(),
+                swarm1.did(),
+            )?;
+            swarm3.send_payload(forged).await?;
+            sleep(Duration::from_millis(1000)).await;
+            assert!(swarm1.get_transport(swarm2.did()).is_some());
+            assert_eq!(swarm1.dht().successors().min()?, swarm2.did());
+
+            Stabilization::new(Arc::clone(&swarm2), 5usize).leave().await?;
+            sleep(Duration::from_millis(1000)).await;
+            for (did, _) in swarm2.get_transports() {
+                swarm2.disconnect(did).await?;
+            }
+            // Shorter than a stabilization interval.
+            sleep(Duration::from_millis(1000)).await;
+            assert_eq!(swarm1.dht().successors().min()?, swarm3.did());
+            assert!(!swarm1.dht().successors().list()?.contains(&swarm2.did()));
+            assert_eq!(*swarm3.dht().lock_predecessor()?, Some(swarm1.did()));
+            assert!(swarm2.get_transports().is_empty());
+            Ok::<(), Error>(())
+        } => {}
+    }
+    tokio::fs::remove_dir_all("./tmp").await.ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stabilization_leave_two_nodes() -> Result<()> {
+    let swarm1 = Arc::new(new_swarm(SecretKey::random()).await?);
+    let swarm2 = Arc::new(new_swarm(SecretKey::random()).await?);
+    manually_establish_connection(&swarm1, &swarm2).await?;
+
+    tokio::select! {
+        _ = async {
+            futures::join!(
+                async {
+                    loop {
+                        swarm1.clone().listen().await;
+                    }
+                },
+                async {
+                    loop {
+                        swarm2.clone().listen().await;
+                    }
+                },
+            );
+        } => { unreachable!(); }
+        _ = async {
+            sleep(Duration::from_millis(1000)).await;
+            for swarm in [&swarm1, &swarm2] {
+                Stabilization::new(Arc::clone(swarm), 5usize)
+                    .notify_predecessor()
+                    .await?;
+            }
+            sleep(Duration::from_millis(1000)).await;
+            // ring: 1 -> 2 -> 1
+            assert_eq!(swarm1.dht().successors().min()?, swarm2.did());
+            assert_eq!(*swarm1.dht().lock_predecessor()?, Some(swarm2.did()));
+
+            Stabilization::new(Arc::clone(&swarm2), 5usize).leave().await?;
+            sleep(Duration::from_millis(1000)).await;
+            assert!(swarm1.dht().successors().is_empty()?);
+            assert_eq!(*swarm1.dht().lock_predecessor()?, None);
+            Ok::<(), Error>(())
+        } => {}
+    }
+    tokio::fs::remove_dir_all("./tmp").await.ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stabilization_with_succ_len() -> Result<()> {
+    let config = StabilizationConfig {
+        succ_len: 8,
+        interval_secs: 5,
+    };
+    let mut swarms = vec![];
+    let mut stabilizations = vec![];
+    for key in gen_ordered_keys(5) {
+        let swarm = Arc::new(new_swarm(key).await?);
+        stabilizations.push(Stabilization::new_with_config(swarm.clone(), config)?);
+        swarms.push(swarm);
+    }
+    for (i, swarm) in swarms.iter().enumerate() {
+        for other in swarms.iter().skip(i + 1) {
+            manually_establish_connection(swarm, other).await?;
+        }
+    }
+
+    tokio::select! {
+        _ = futures::future::join_all(swarms.iter().map(|swarm| async {
+            loop {
+                swarm.clone().listen().await;
+            }
+        })) => { unreachable!(); }
+        _ = async {
+            sleep(Duration::from_millis(1000)).await;
+            for stabilization in stabilizations.iter() {
+                assert_eq!(stabilization.get_timeout(), 5);
+                stabilization.stabilize().await?;
+            }
+            sleep(Duration::from_millis(1000)).await;
+            // A ring of 5 nodes, every node should retain all the other 4 nodes,
+            // which is more than the default length 3.
+            for swarm in swarms.iter() {
+                assert_eq!(swarm.dht().successors().max_len(), 8);
+                assert_eq!(swarm.dht().successors().list()?.len(), 4);
+            }
+            Ok::<(), Error>(())
+        } => {}
+    }
+    tokio::fs::remove_dir_all("./tmp").await.ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stabilization_with_invalid_succ_len() -> Result<()> {
+    let swarm = Arc::new(new_swarm(SecretKey::random()).await?);
+    for succ_len in [0, 256] {
+        let config = StabilizationConfig {
+            succ_len,
+            interval_secs: 5,
+        };
+        assert!(matches!(
+            Stabilization::new_with_config(swarm.clone(), config),
+            Err(Error::InvalidSuccessorLength(len)) if len == succ_len
+        ));
+    }
+    // The successor list is not resized.
+    assert_eq!(swarm.dht().successors().max_len(), 3);
+    tokio::fs::remove_dir_all("./tmp").await.ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stabilization_interval() -> Result<()> {
+    // Keep a short successor list, but stabilize frequently.
+    let config = StabilizationConfig {
+        succ_len: 3,
+        interval_secs: 1,
+    };
+    let swarm = Arc::new(new_swarm(SecretKey::random()).await?);
+    let stabilization = Arc::new(Stabilization::new_with_config(swarm.clone(), config)?);
+    assert_eq!(stabilization.get_timeout(), 1);
+    assert_eq!(swarm.dht().successors().max_len(), 3);
+
+    let waiting = tokio::spawn(stabilization.clone().wait());
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(stabilization.rounds(), 0);
+    sleep(Duration::from_millis(2200)).await;
+    assert_eq!(stabilization.rounds(), 2);
+
+    stabilization.stop();
+    waiting.await.unwrap();
+    tokio::fs::remove_dir_all("./tmp").await.ok();
+    Ok(())
+}
@@ -0,0 +1,100 @@
+#![warn(missing_docs)]
+//! Backpressure of sending through transports.
+//!
+//! The buffer of data channel works as the bounded send queue of each transport.
+//! Before a message is sent, the bytes buffered in data channel are checked against
+//! the high watermark. Over it, sending is either rejected with [Error::SendQueueFull],
+//! or held back until the buffer drains below the low watermark.
+
+use std::time::Duration;
+
+use futures_timer::Delay;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::DEFAULT_SEND_HIGH_WATERMARK;
+use crate::consts::DEFAULT_SEND_LOW_WATERMARK;
+use crate::consts::SEND_QUEUE_POLL_INTERVAL_MS;
+use crate::error::Error;
+use crate::error::Result;
+use crate::transports::Transport;
+use crate::types::ice_transport::IceTransportInterface;
+
+/// What to do when the send queue of a transport is over the high watermark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressureMode {
+    /// Wait until the send queue drains below the low watermark.
+    #[default]
+    Wait,
+    /// Fail with [Error::SendQueueFull] at once.
+    Reject,
+}
+
+/// Watermarks of the send queue of each transport, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Backpressure {
+    /// sending is held back when buffered bytes exceed it
+    pub high_watermark: usize,
+    /// held back sending is resumed when buffered bytes fall to it
+    pub low_watermark: usize,
+    /// wait or reject when the high watermark is exceeded
+    #[serde(default)]
+    pub mode: BackpressureMode,
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Self {
+            high_watermark: DEFAULT_SEND_HIGH_WATERMARK,
+            low_watermark: DEFAULT_SEND_LOW_WATERMARK,
+            mode: BackpressureMode::default(),
+        }
+    }
+}
+
+impl Backpressure {
+    /// Create a backpressure config, the low watermark should not be greater than the high one.
+    pub fn new(
+        high_watermark: usize,
+        low_watermark: usize,
+        mode: BackpressureMode,
+    ) -> Result<Self> {
+        if low_watermark > high_watermark {
+            return Err(Error::InvalidWatermarks(low_watermark, high_watermark));
+        }
+        Ok(Self {
+            high_watermark,
+            low_watermark,
+            mode,
+        })
+    }
+
+    /// Check the send queue of transport before sending a message through it.
+    /// A message is accepted as long as the queue is not over the high watermark,
+    /// so the queue holds at most one more message per concurrent sender beyond it.
+    pub async fn reserve(&self, transport: &Transport) -> Result<()> {
+        let buffered = transport.buffered_amount().await;
+        if buffered <= self.high_watermark {
+            return Ok(());
+        }
+        if self.mode == BackpressureMode::Reject {
+            return Err(Error::SendQueueFull(buffered));
+        }
+
+        tracing::debug!(
+            "send queue of transport {} is full with {} bytes, waiting for draining",
+            transport.id,
+            buffered
+        );
+        loop {
+            Delay::new(Duration::from_millis(SEND_QUEUE_POLL_INTERVAL_MS)).await;
+            if transport.is_disconnected().await {
+                return Err(Error::RTCDataChannelStateNotOpen);
+            }
+            if transport.buffered_amount().await <= self.low_watermark {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -1,39 +1,53 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_lock::RwLock as AsyncRwLock;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::future::BoxFuture;
 use futures::lock::Mutex as FuturesMutex;
+use futures_timer::Delay;
 use serde_json;
 use webrtc::api::setting_engine::SettingEngine;
 use webrtc::api::APIBuilder;
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
 use webrtc::data_channel::data_channel_state::RTCDataChannelState;
 use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice::candidate::CandidatePairState;
+use webrtc::ice::candidate::CandidateType;
 use webrtc::ice::mdns::MulticastDnsMode;
 use webrtc::ice_transport::ice_candidate::RTCIceCandidate;
 use webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
 use webrtc::ice_transport::ice_gathering_state::RTCIceGatheringState;
 use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::ICECandidatePairStats;
+use webrtc::stats::StatsReportType;
 
 use crate::channels::Channel as AcChannel;
 use crate::chunk::Chunk;
 use crate::chunk::ChunkList;
 use crate::chunk::ChunkManager;
+use crate::consts::SEND_LANES_THRESHOLD;
+use crate::consts::SEND_QUEUE_POLL_INTERVAL_MS;
 use crate::consts::TRANSPORT_MAX_SIZE;
 use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
 use crate::error::Error;
 use crate::error::Result;
 use crate::transports::helper::Promise;
+use crate::transports::lanes::ChunkSink;
+use crate::transports::lanes::Priority;
+use crate::transports::lanes::SendLanes;
 use crate::types::channel::Channel;
 use crate::types::channel::TransportEvent;
+use crate::types::ice_transport::ConnectionStats;
 use crate::types::ice_transport::HandshakeInfo;
 use crate::types::ice_transport::IceCandidate;
 use crate::types::ice_transport::IceCandidateGathering;
@@ -42,6 +56,8 @@ use crate::types::ice_transport::IceTransport;
 use crate::types::ice_transport::IceTransportCallback;
 use crate::types::ice_transport::IceTransportInterface;
 use crate::types::ice_transport::IceTrickleScheme;
+use crate::types::ice_transport::SessionDescription;
+use crate::types::ice_transport::TransportDirection;
 
 type EventSender = <AcChannel<TransportEvent> as Channel<TransportEvent>>::Sender;
 
@@ -60,6 +76,13 @@ pub struct DefaultTransport {
     event_sender: EventSender,
     remote_did: Arc<AsyncRwLock<Option<Did>>>,
     chunk_list: Arc<FuturesMutex<ChunkList<TRANSPORT_MTU>>>,
+    /// chunks waiting for data channel, by priority
+    send_lanes: Arc<SendLanes>,
+    /// static ice username fragment and password, random if not set
+    #[cfg(feature = "deterministic")]
+    ice_credentials: Option<(String, String)>,
+    /// which side initiated this transport
+    direction: Option<TransportDirection>,
 }
 
 impl PartialEq for DefaultTransport {
@@ -140,6 +163,7 @@ impl IceTransport for DefaultTransport {
 #[async_trait]
 impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for DefaultTransport {
     type IceConnectionState = RTCIceConnectionState;
+    type IceGatheringState = RTCIceGatheringState;
 
     fn new(event_sender: EventSender) -> Self {
         Self {
@@ -150,6 +174,10 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for Defaul
             event_sender,
             remote_did: Arc::new(AsyncRwLock::new(None)),
             chunk_list: Default::default(),
+            send_lanes: Arc::new(SendLanes::new(SEND_LANES_THRESHOLD)),
+            #[cfg(feature = "deterministic")]
+            ice_credentials: None,
+            direction: None,
         }
     }
 
@@ -172,6 +200,10 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for Defaul
             // mDNS gathering cannot be used with 1:1 NAT IP mapping for host candidate
             setting.set_ice_multicast_dns_mode(MulticastDnsMode::QueryAndGather);
         }
+        #[cfg(feature = "deterministic")]
+        if let Some((ufrag, pwd)) = self.ice_credentials.clone() {
+            setting.set_ice_credentials(ufrag, pwd);
+        }
         let api = APIBuilder::new().with_setting_engine(setting).build();
         match api.new_peer_connection(config).await {
             Ok(c) => {
@@ -223,6 +255,12 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for Defaul
             .map(|pc| pc.ice_connection_state())
     }
 
+    async fn ice_gathering_state(&self) -> Option<Self::IceGatheringState> {
+        self.get_peer_connection()
+            .await
+            .map(|pc| pc.ice_gathering_state())
+    }
+
     async fn get_stats(&self) -> Option<Vec<String>> {
         let pc = self.get_peer_connection().await?;
         let reports = pc.get_stats().await.reports;
@@ -237,6 +275,35 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for Defaul
         )
     }
 
+    async fn selected_candidate_type(&self) -> Option<String> {
+        let pc = self.get_peer_connection().await?;
+        let reports = pc.get_stats().await.reports;
+        let pair = selected_candidate_pair(&reports)?;
+
+        let candidate_type = |id: &str| match reports.get(id) {
+            Some(StatsReportType::LocalCandidate(c))
+            | Some(StatsReportType::RemoteCandidate(c)) => Some(c.candidate_type),
+            _ => None,
+        };
+        let local = candidate_type(&pair.local_candidate_id)?;
+        let remote = candidate_type(&pair.remote_candidate_id);
+        if remote == Some(CandidateType::Relay) {
+            return Some(CandidateType::Relay.to_string());
+        }
+        Some(local.to_string())
+    }
+
+    async fn connection_stats(&self) -> Option<ConnectionStats> {
+        let pc = self.get_peer_connection().await?;
+        let reports = pc.get_stats().await.reports;
+        let pair = selected_candidate_pair(&reports)?;
+        Some(ConnectionStats::new(
+            pair.current_round_trip_time * 1000.0,
+            pair.requests_sent,
+            pair.responses_received,
+        ))
+    }
+
     async fn is_disconnected(&self) -> bool {
         matches!(
             self.ice_connection_state().await,
@@ -254,40 +321,90 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for Defaul
     }
 
     async fn send_message(&self, msg: &Bytes) -> Result<()> {
+        self.send_message_with_priority(msg, Priority::Normal).await
+    }
+
+    async fn send_message_with_priority(&self, msg: &Bytes, priority: Priority) -> Result<()> {
         if msg.len() > TRANSPORT_MAX_SIZE {
             return Err(Error::MessageTooLarge);
         }
 
+        if self.get_data_channel().await.is_none() {
+            return Err(Error::RTCDataChannelNotReady);
+        }
+
+        let chunks = ChunkList::<TRANSPORT_MTU>::from(msg)
+            .into_iter()
+            .map(|c| {
+                tracing::debug!("Transport chunk data len: {}", c.data.len());
+                c.to_bincode()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.send_lanes.send(self, chunks, priority).await
+    }
+
+    async fn buffered_amount(&self) -> usize {
+        self.buffered().await + self.send_lanes.queued_bytes()
+    }
+
+    fn direction(&self) -> Option<TransportDirection> {
+        self.direction
+    }
+
+    fn set_direction(&mut self, direction: TransportDirection) {
+        self.direction = Some(direction);
+    }
+
+    async fn local_description(&self) -> Option<SessionDescription> {
+        let desc = self
+            .get_peer_connection()
+            .await?
+            .local_description()
+            .await?;
+        Some(SessionDescription {
+            sdp_type: desc.sdp_type.to_string(),
+            sdp: desc.sdp,
+        })
+    }
+}
+
+#[async_trait]
+impl ChunkSink for DefaultTransport {
+    async fn buffered(&self) -> usize {
+        match self.get_data_channel().await {
+            Some(dc) => dc.buffered_amount().await,
+            None => 0,
+        }
+    }
+
+    async fn is_open(&self) -> bool {
+        self.is_data_channel_open().await
+    }
+
+    async fn send_chunk(&self, chunk: &Bytes) -> Result<()> {
+        tracing::debug!("Transport chunk len: {}", chunk.len());
         let dc = self
             .get_data_channel()
             .await
             .ok_or(Error::RTCDataChannelNotReady)?;
 
-        let chunks = ChunkList::<TRANSPORT_MTU>::from(msg);
-
-        for c in chunks {
-            tracing::debug!("Transport chunk data len: {}", c.data.len());
-            let bytes = c.to_bincode()?;
-            tracing::debug!("Transport chunk len: {}", bytes.len());
-
-            let size = bytes.len();
-            match dc.send(&bytes).await {
-                Ok(s) => {
-                    if !s == size {
-                        return Err(Error::RTCDataChannelMessageIncomplete(s, size));
-                    }
+        let size = chunk.len();
+        match dc.send(chunk).await {
+            Ok(s) => {
+                if !s == size {
+                    return Err(Error::RTCDataChannelMessageIncomplete(s, size));
                 }
-                Err(e) => {
-                    if dc.ready_state() != RTCDataChannelState::Open {
-                        return Err(Error::RTCDataChannelStateNotOpen);
-                    } else {
-                        return Err(Error::RTCDataChannelSendTextFailed(e));
-                    }
+                Ok(())
+            }
+            Err(e) => {
+                if dc.ready_state() != RTCDataChannelState::Open {
+                    Err(Error::RTCDataChannelStateNotOpen)
+                } else {
+                    Err(Error::RTCDataChannelSendTextFailed(e))
                 }
             }
         }
-
-        Ok(())
     }
 }
 
@@ -461,22 +578,7 @@ impl IceTrickleScheme for DefaultTransport {
                 sdp
             }
         };
-        let local_candidates_json = self
-            .pending_candidates
-            .lock()
-            .await
-            .iter()
-            .map(|c| c.clone().to_json().unwrap().into())
-            .collect::<Vec<_>>();
-        if local_candidates_json.is_empty() {
-            return Err(Error::FailedOnGatherLocalCandidate);
-        }
-        let data = HandshakeInfo {
-            sdp: serde_json::to_string(&sdp).unwrap(),
-            candidates: local_candidates_json,
-        };
-        tracing::trace!("prepared handshake info :{:?}", data);
-        Ok(data)
+        self.handshake_info_with_candidates(sdp).await
     }
 
     async fn register_remote_info(&self, data: &HandshakeInfo, did: Did) -> Result<()> {
@@ -506,13 +608,89 @@ impl IceTrickleScheme for DefaultTransport {
         let promise = self.connect_success_promise().await?;
         promise.await
     }
+
+    async fn get_ice_restart_info(&self) -> Result<HandshakeInfo> {
+        let peer_connection = self
+            .get_peer_connection()
+            .await
+            .ok_or(Error::RTCPeerConnectionNotEstablish)?;
+        let state = peer_connection.ice_connection_state();
+        if !matches!(
+            state,
+            RTCIceConnectionState::Connected
+                | RTCIceConnectionState::Completed
+                | RTCIceConnectionState::Disconnected
+                | RTCIceConnectionState::Failed
+        ) {
+            return Err(Error::TransportNotRestartable(state.to_string()));
+        }
+
+        let offer = peer_connection
+            .create_offer(Some(RTCOfferOptions {
+                ice_restart: true,
+                ..Default::default()
+            }))
+            .await
+            .map_err(Error::RTCPeerConnectionCreateOfferFailed)?;
+        // gathering is restarted after creating offer
+        let mut gather_complete = peer_connection.gathering_complete_promise().await;
+        // candidates of previous ice session are useless for remote
+        self.pending_candidates.lock().await.clear();
+        self.set_local_description(offer.to_owned()).await?;
+        let _ = gather_complete.recv().await;
+
+        self.handshake_info_with_candidates(offer).await
+    }
+}
+
+/// The nominated pair of succeeded candidate pairs, or the first succeeded one.
+fn selected_candidate_pair(
+    reports: &HashMap<String, StatsReportType>,
+) -> Option<&ICECandidatePairStats> {
+    let succeeded = reports
+        .values()
+        .filter_map(|x| match x {
+            StatsReportType::CandidatePair(pair) if pair.state == CandidatePairState::Succeeded => {
+                Some(pair)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    succeeded
+        .iter()
+        .find(|pair| pair.nominated)
+        .or_else(|| succeeded.first())
+        .copied()
 }
 
 impl DefaultTransport {
-    pub async fn ice_gathering_state(&self) -> Option<RTCIceGatheringState> {
-        self.get_peer_connection()
+    /// Use static ice username fragment and password instead of random ones,
+    /// which should be set before the transport starts.
+    #[cfg(feature = "deterministic")]
+    pub fn set_ice_credentials(&mut self, credentials: (String, String)) {
+        self.ice_credentials = Some(credentials);
+    }
+
+    async fn handshake_info_with_candidates(
+        &self,
+        sdp: RTCSessionDescription,
+    ) -> Result<HandshakeInfo> {
+        let local_candidates_json = self
+            .pending_candidates
+            .lock()
             .await
-            .map(|pc| pc.ice_gathering_state())
+            .iter()
+            .map(|c| c.clone().to_json().unwrap().into())
+            .collect::<Vec<_>>();
+        if local_candidates_json.is_empty() {
+            return Err(Error::FailedOnGatherLocalCandidate);
+        }
+        let data = HandshakeInfo {
+            sdp: serde_json::to_string(&sdp).unwrap(),
+            candidates: local_candidates_json,
+        };
+        tracing::trace!("prepared handshake info :{:?}", data);
+        Ok(data)
     }
 
     pub async fn setup_channel(&mut self, name: &str) -> Result<()> {
@@ -532,36 +710,37 @@ impl DefaultTransport {
         }
     }
 
+    /// Check the data channel is open, messages can't be sent through transport before that.
+    pub async fn is_data_channel_open(&self) -> bool {
+        self.get_data_channel()
+            .await
+            .map_or(false, |dc| dc.ready_state() == RTCDataChannelState::Open)
+    }
+
     pub async fn wait_for_data_channel_open(&self) -> Result<()> {
         if self.is_disconnected().await {
             return Err(Error::RTCPeerConnectionNotEstablish);
         }
 
-        match self.get_data_channel().await {
-            Some(dc) => {
-                if dc.ready_state() == RTCDataChannelState::Open {
-                    Ok(())
-                } else {
-                    let promise = Promise::default();
-                    let state = Arc::clone(&promise.state());
-                    dc.on_open(Box::new(move || {
-                        let state = Arc::clone(&state);
-                        Box::pin(async move {
-                            let mut s = state.lock().unwrap();
-                            if let Some(w) = s.waker.take() {
-                                s.completed = true;
-                                s.succeeded = Some(true);
-                                w.wake();
-                            }
-                        })
-                    }));
-                    promise.await
+        let Some(dc) = self.get_data_channel().await else {
+            tracing::error!("{:?}", Error::RTCDataChannelNotReady);
+            return Err(Error::RTCDataChannelNotReady);
+        };
+
+        // The `on_open` handler of data channel is replaced by each waiter,
+        // so concurrent waiters poll the ready state instead of registering one.
+        loop {
+            match dc.ready_state() {
+                RTCDataChannelState::Open => return Ok(()),
+                RTCDataChannelState::Closing | RTCDataChannelState::Closed => {
+                    return Err(Error::RTCDataChannelStateNotOpen)
                 }
+                _ => {}
             }
-            None => {
-                tracing::error!("{:?}", Error::RTCDataChannelNotReady);
-                Err(Error::RTCDataChannelNotReady)
+            if self.is_disconnected().await {
+                return Err(Error::RTCPeerConnectionNotEstablish);
             }
+            Delay::new(Duration::from_millis(SEND_QUEUE_POLL_INTERVAL_MS)).await;
         }
     }
 
@@ -622,6 +801,8 @@ pub mod tests {
     use super::DefaultTransport as Transport;
     use super::*;
     use crate::ecc::SecretKey;
+    use crate::transports::backpressure::Backpressure;
+    use crate::transports::backpressure::BackpressureMode;
     use crate::types::ice_transport::IceServer;
 
     async fn prepare_transport() -> Result<(Transport, Receiver<TransportEvent>)> {
@@ -822,4 +1003,61 @@ pub mod tests {
         assert_eq!(oversize_message.len(), TRANSPORT_MAX_SIZE + 1);
         assert!(transport1.send_message(&oversize_message).await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_send_queue_backpressure() {
+        let (transport1, _receiver1) = prepare_transport().await.unwrap();
+        let (transport2, _receiver2) = prepare_transport().await.unwrap();
+        establish_connection(&transport1, &transport2)
+            .await
+            .unwrap();
+        transport1.wait_for_data_channel_open().await.unwrap();
+        transport2.wait_for_data_channel_open().await.unwrap();
+
+        let message: Bytes = (0..TRANSPORT_MAX_SIZE)
+            .map(|_| rand::random::<u8>())
+            .collect();
+        let high = TRANSPORT_MAX_SIZE;
+        let low = TRANSPORT_MAX_SIZE / 2;
+
+        // Flooding without waiting is rejected once the queue is over the high watermark.
+        // Sending waits until its chunks leave the lanes, so the flood is sent concurrently.
+        let reject = Backpressure::new(high, low, BackpressureMode::Reject).unwrap();
+        let mut rejected = 0;
+        let mut sending = vec![];
+        for _ in 0..32 {
+            match reject.reserve(&transport1).await {
+                Ok(()) => {
+                    let (transport, message) = (transport1.clone(), message.clone());
+                    sending.push(tokio::spawn(async move {
+                        transport.send_message(&message).await
+                    }));
+                    tokio::task::yield_now().await;
+                }
+                Err(Error::SendQueueFull(buffered)) => {
+                    assert!(buffered > high);
+                    rejected += 1;
+                }
+                Err(e) => panic!("unexpected error {:?}", e),
+            }
+            assert!(transport1.buffered_amount().await <= high + TRANSPORT_MAX_SIZE);
+        }
+        assert!(rejected > 0);
+        for handle in sending {
+            handle.await.unwrap().unwrap();
+        }
+
+        // Flooding with waiting is held back until the queue drains.
+        let wait = Backpressure::new(high, low, BackpressureMode::Wait).unwrap();
+        for _ in 0..32 {
+            wait.reserve(&transport1).await.unwrap();
+            transport1.send_message(&message).await.unwrap();
+            assert!(transport1.buffered_amount().await <= high + TRANSPORT_MAX_SIZE);
+        }
+
+        assert!(matches!(
+            Backpressure::new(low, high, BackpressureMode::Wait),
+            Err(Error::InvalidWatermarks(_, _))
+        ));
+    }
 }
@@ -7,6 +7,7 @@ use bytes::Bytes;
 use dashmap::DashMap;
 use lazy_static::lazy_static;
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
+use webrtc::ice_transport::ice_gathering_state::RTCIceGatheringState;
 use webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
 
 use super::consts;
@@ -18,10 +19,13 @@ use crate::transports::helper::Promise;
 use crate::transports::helper::State;
 use crate::types::channel::Channel;
 use crate::types::channel::TransportEvent;
+use crate::types::ice_transport::ConnectionStats;
 use crate::types::ice_transport::HandshakeInfo;
 use crate::types::ice_transport::IceServer;
 use crate::types::ice_transport::IceTransportInterface;
 use crate::types::ice_transport::IceTrickleScheme;
+use crate::types::ice_transport::SessionDescription;
+use crate::types::ice_transport::TransportDirection;
 
 type EventSender = <AcChannel<TransportEvent> as Channel<TransportEvent>>::Sender;
 
@@ -42,6 +46,7 @@ pub struct DummyTransport {
     event_sender: EventSender,
     ice_connection_state: Arc<Mutex<Option<RTCIceConnectionState>>>,
     remote_did: Arc<AsyncRwLock<Option<Did>>>,
+    direction: Option<TransportDirection>,
 }
 
 impl PartialEq for DummyTransport {
@@ -59,6 +64,7 @@ impl DummyTransport {
 #[async_trait]
 impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for DummyTransport {
     type IceConnectionState = RTCIceConnectionState;
+    type IceGatheringState = RTCIceGatheringState;
 
     fn new(event_sender: EventSender) -> Self {
         Self {
@@ -67,6 +73,7 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for DummyT
             event_sender,
             ice_connection_state: Arc::new(Mutex::new(None)),
             remote_did: Arc::new(AsyncRwLock::new(None)),
+            direction: None,
         }
     }
 
@@ -106,10 +113,22 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for DummyT
         *self.ice_connection_state.lock().unwrap()
     }
 
+    async fn ice_gathering_state(&self) -> Option<Self::IceGatheringState> {
+        None
+    }
+
     async fn get_stats(&self) -> Option<Vec<String>> {
         None
     }
 
+    async fn selected_candidate_type(&self) -> Option<String> {
+        None
+    }
+
+    async fn connection_stats(&self) -> Option<ConnectionStats> {
+        None
+    }
+
     async fn is_disconnected(&self) -> bool {
         matches!(
             self.ice_connection_state().await,
@@ -136,6 +155,22 @@ impl IceTransportInterface<TransportEvent, AcChannel<TransportEvent>> for DummyT
             .unwrap();
         Ok(())
     }
+
+    async fn buffered_amount(&self) -> usize {
+        0
+    }
+
+    fn direction(&self) -> Option<TransportDirection> {
+        self.direction
+    }
+
+    fn set_direction(&mut self, direction: TransportDirection) {
+        self.direction = Some(direction);
+    }
+
+    async fn local_description(&self) -> Option<SessionDescription> {
+        None
+    }
 }
 
 #[async_trait]
@@ -184,6 +219,18 @@ impl IceTrickleScheme for DummyTransport {
         let promise = self.connect_success_promise().await?;
         promise.await
     }
+
+    async fn get_ice_restart_info(&self) -> Result<HandshakeInfo> {
+        match self.ice_connection_state().await {
+            Some(RTCIceConnectionState::Connected)
+            | Some(RTCIceConnectionState::Completed)
+            | Some(RTCIceConnectionState::Disconnected)
+            | Some(RTCIceConnectionState::Failed) => {
+                self.get_handshake_info(RTCSdpType::Offer).await
+            }
+            state => Err(Error::TransportNotRestartable(format!("{:?}", state))),
+        }
+    }
 }
 
 impl DummyTransport {
@@ -197,6 +244,11 @@ impl DummyTransport {
         Ok(promise)
     }
 
+    /// Dummy transport has no data channel, it's open once connected.
+    pub async fn is_data_channel_open(&self) -> bool {
+        self.is_connected().await
+    }
+
     pub async fn wait_for_data_channel_open(&self) -> Result<()> {
         if consts::CHANNEL_OPEN_DELAY {
             super::random_delay().await;
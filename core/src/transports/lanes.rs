@@ -0,0 +1,249 @@
+#![warn(missing_docs)]
+//! Priority lanes of sending through a data channel.
+//!
+//! A message is split into chunks, which are queued in the lane of its [Priority].
+//! Queued chunks are handed to the data channel only while the bytes buffered in it are
+//! below a threshold, taking the front chunk of the highest non-empty lane each time.
+//! So a control message sent after a large transfer is only queued behind the chunks
+//! already buffered in data channel, instead of the whole transfer.
+//!
+//! There is no background task, every sender drains the lanes until its own chunks are sent.
+
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::lock::Mutex as FuturesMutex;
+use futures_timer::Delay;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::SEND_QUEUE_POLL_INTERVAL_MS;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Priority of a message sent through transport, chunks of higher priority are sent first.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Control messages such as acks and pings.
+    High = 0,
+    /// Application messages.
+    #[default]
+    Normal = 1,
+    /// Bulk transfers which can yield to others.
+    Low = 2,
+}
+
+/// Where queued chunks are sent to, such as a data channel.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait ChunkSink {
+    /// Bytes handed to sink but not transmitted yet.
+    async fn buffered(&self) -> usize;
+    /// Check the sink is able to send chunks.
+    async fn is_open(&self) -> bool;
+    /// Send a chunk through sink.
+    async fn send_chunk(&self, chunk: &Bytes) -> Result<()>;
+}
+
+/// Outcome of a queued message, shared by its chunks.
+#[derive(Default)]
+struct Outcome {
+    remaining: usize,
+    failed: bool,
+    error: Option<Error>,
+}
+
+impl Outcome {
+    fn fail(&mut self, error: Error) {
+        if !self.failed {
+            self.failed = true;
+            self.error = Some(error);
+        }
+    }
+
+    /// `None` until all chunks are sent or any of them failed.
+    fn result(&mut self) -> Option<Result<()>> {
+        if self.failed {
+            return Some(Err(self
+                .error
+                .take()
+                .unwrap_or(Error::RTCDataChannelStateNotOpen)));
+        }
+        (self.remaining == 0).then_some(Ok(()))
+    }
+}
+
+struct QueuedChunk {
+    chunk: Bytes,
+    outcome: Arc<Mutex<Outcome>>,
+}
+
+/// Chunks waiting to be sent through a sink, one lane per [Priority].
+pub struct SendLanes {
+    lanes: FuturesMutex<[VecDeque<QueuedChunk>; 3]>,
+    queued_bytes: AtomicUsize,
+    threshold: usize,
+}
+
+impl SendLanes {
+    /// Create lanes which hand chunks to sink while it buffers no more than `threshold` bytes.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            lanes: FuturesMutex::new(Default::default()),
+            queued_bytes: AtomicUsize::new(0),
+            threshold,
+        }
+    }
+
+    /// Bytes of chunks queued in lanes, which are not handed to sink yet.
+    pub fn queued_bytes(&self) -> usize {
+        self.queued_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Queue chunks of a message by its priority, and drain lanes until they are all sent.
+    /// The remaining chunks of message are dropped once any of them failed to be sent.
+    pub async fn send<S>(&self, sink: &S, chunks: Vec<Bytes>, priority: Priority) -> Result<()>
+    where S: ChunkSink + ?Sized {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let outcome = Arc::new(Mutex::new(Outcome {
+            remaining: chunks.len(),
+            ..Default::default()
+        }));
+        {
+            let mut lanes = self.lanes.lock().await;
+            for chunk in chunks {
+                self.queued_bytes.fetch_add(chunk.len(), Ordering::SeqCst);
+                lanes[priority as usize].push_back(QueuedChunk {
+                    chunk,
+                    outcome: outcome.clone(),
+                });
+            }
+        }
+
+        loop {
+            if !sink.is_open().await {
+                if let Ok(mut outcome) = outcome.lock() {
+                    outcome.fail(Error::RTCDataChannelStateNotOpen);
+                }
+            }
+            self.drain(sink).await;
+            let result = outcome.lock().ok().and_then(|mut o| o.result());
+            if let Some(result) = result {
+                return result;
+            }
+            Delay::new(Duration::from_millis(SEND_QUEUE_POLL_INTERVAL_MS)).await;
+        }
+    }
+
+    /// Hand queued chunks to sink by priority, until sink buffers more than threshold.
+    async fn drain<S>(&self, sink: &S)
+    where S: ChunkSink + ?Sized {
+        let mut lanes = self.lanes.lock().await;
+        while sink.buffered().await <= self.threshold {
+            let Some(queued) = lanes.iter_mut().find_map(|lane| lane.pop_front()) else {
+                break;
+            };
+            self.queued_bytes
+                .fetch_sub(queued.chunk.len(), Ordering::SeqCst);
+
+            let failed = queued.outcome.lock().map_or(true, |o| o.failed);
+            if failed {
+                continue;
+            }
+            let result = sink.send_chunk(&queued.chunk).await;
+            let Ok(mut outcome) = queued.outcome.lock() else {
+                continue;
+            };
+            match result {
+                Ok(()) => outcome.remaining -= 1,
+                Err(e) => outcome.fail(e),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "wasm")))]
+mod test {
+    use super::*;
+
+    /// A sink transmitting nothing until it's released, the order of sent chunks is recorded.
+    #[derive(Default)]
+    struct ThrottledSink {
+        buffered: AtomicUsize,
+        sent: Mutex<Vec<Bytes>>,
+    }
+
+    impl ThrottledSink {
+        fn release(&self) {
+            self.buffered.store(0, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl ChunkSink for ThrottledSink {
+        async fn buffered(&self) -> usize {
+            self.buffered.load(Ordering::SeqCst)
+        }
+
+        async fn is_open(&self) -> bool {
+            true
+        }
+
+        async fn send_chunk(&self, chunk: &Bytes) -> Result<()> {
+            self.buffered.fetch_add(chunk.len(), Ordering::SeqCst);
+            self.sent.lock().unwrap().push(chunk.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_first() {
+        let sink = Arc::new(ThrottledSink::default());
+        let lanes = Arc::new(SendLanes::new(10));
+
+        // The first chunk of the large message fills the sink, the rest are queued.
+        let low = {
+            let (sink, lanes) = (sink.clone(), lanes.clone());
+            let chunks = (0..4).map(|i| Bytes::from(vec![i; 100])).collect();
+            tokio::spawn(async move { lanes.send(sink.as_ref(), chunks, Priority::Low).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(sink.sent.lock().unwrap().len(), 1);
+        assert_eq!(lanes.queued_bytes(), 300);
+
+        let high = {
+            let (sink, lanes) = (sink.clone(), lanes.clone());
+            let chunks = vec![Bytes::from_static(b"ack")];
+            tokio::spawn(async move { lanes.send(sink.as_ref(), chunks, Priority::High).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(sink.sent.lock().unwrap().len(), 1);
+
+        // Once the sink is drained, the high priority chunk jumps ahead of queued ones.
+        sink.release();
+        high.await.unwrap().unwrap();
+        for _ in 0..3 {
+            sink.release();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        low.await.unwrap().unwrap();
+
+        let sent = sink.sent.lock().unwrap();
+        assert_eq!(sent.len(), 5);
+        assert_eq!(sent[0], Bytes::from(vec![0; 100]));
+        assert_eq!(sent[1], Bytes::from_static(b"ack"));
+        assert_eq!(sent[2..], [1, 2, 3].map(|i| Bytes::from(vec![i; 100])));
+        assert_eq!(lanes.queued_bytes(), 0);
+    }
+}
@@ -16,7 +16,11 @@ pub use dummy::DummyTransport as Transport;
 #[cfg(feature = "wasm")]
 pub use wasm::WasmTransport as Transport;
 
+/// Bounded send queue of transports.
+pub mod backpressure;
 /// Custom Promise act like Js Promise.
 pub mod helper;
+/// Priority lanes of sending through transports.
+pub mod lanes;
 /// TransportManager trait and implement.
 pub mod manager;
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::RwLock;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures_timer::Delay;
 use js_sys::Uint8Array;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -19,6 +21,7 @@ use web_sys::RtcIceCandidate;
 use web_sys::RtcIceCandidateInit;
 use web_sys::RtcIceConnectionState;
 use web_sys::RtcIceGatheringState;
+use web_sys::RtcOfferOptions;
 use web_sys::RtcPeerConnection;
 use web_sys::RtcPeerConnectionIceEvent;
 use web_sys::RtcSdpType;
@@ -31,14 +34,20 @@ use crate::channels::Channel as CbChannel;
 use crate::chunk::Chunk;
 use crate::chunk::ChunkList;
 use crate::chunk::ChunkManager;
+use crate::consts::SEND_LANES_THRESHOLD;
+use crate::consts::SEND_QUEUE_POLL_INTERVAL_MS;
 use crate::consts::TRANSPORT_MAX_SIZE;
 use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
 use crate::error::Error;
 use crate::error::Result;
 use crate::transports::helper::Promise;
+use crate::transports::lanes::ChunkSink;
+use crate::transports::lanes::Priority;
+use crate::transports::lanes::SendLanes;
 use crate::types::channel::Channel;
 use crate::types::channel::TransportEvent;
+use crate::types::ice_transport::ConnectionStats;
 use crate::types::ice_transport::HandshakeInfo;
 use crate::types::ice_transport::IceCandidate;
 use crate::types::ice_transport::IceCandidateGathering;
@@ -47,6 +56,8 @@ use crate::types::ice_transport::IceTransport;
 use crate::types::ice_transport::IceTransportCallback;
 use crate::types::ice_transport::IceTransportInterface;
 use crate::types::ice_transport::IceTrickleScheme;
+use crate::types::ice_transport::SessionDescription;
+use crate::types::ice_transport::TransportDirection;
 use crate::utils::js_value;
 
 type EventSender = <CbChannel<TransportEvent> as Channel<TransportEvent>>::Sender;
@@ -61,6 +72,8 @@ pub struct WasmTransport {
     event_sender: EventSender,
     remote_did: Arc<RwLock<Option<Did>>>,
     chunk_list: Arc<Mutex<ChunkList<TRANSPORT_MTU>>>,
+    send_lanes: Arc<SendLanes>,
+    direction: Option<TransportDirection>,
 }
 
 impl PartialEq for WasmTransport {
@@ -149,6 +162,7 @@ impl IceTransport for WasmTransport {
 #[async_trait(?Send)]
 impl IceTransportInterface<TransportEvent, CbChannel<TransportEvent>> for WasmTransport {
     type IceConnectionState = RtcIceConnectionState;
+    type IceGatheringState = RtcIceGatheringState;
 
     fn new(event_sender: EventSender) -> Self {
         Self {
@@ -159,6 +173,8 @@ impl IceTransportInterface<TransportEvent, CbChannel<TransportEvent>> for WasmTr
             remote_did: Arc::new(RwLock::new(None)),
             event_sender,
             chunk_list: Default::default(),
+            send_lanes: Arc::new(SendLanes::new(SEND_LANES_THRESHOLD)),
+            direction: None,
         }
     }
 
@@ -233,6 +249,12 @@ impl IceTransportInterface<TransportEvent, CbChannel<TransportEvent>> for WasmTr
             .map(|pc| pc.ice_connection_state())
     }
 
+    async fn ice_gathering_state(&self) -> Option<Self::IceGatheringState> {
+        self.get_peer_connection()
+            .await
+            .map(|pc| pc.ice_gathering_state())
+    }
+
     async fn get_stats(&self) -> Option<Vec<String>> {
         let pc = self.get_peer_connection().await?;
 
@@ -252,6 +274,35 @@ impl IceTransportInterface<TransportEvent, CbChannel<TransportEvent>> for WasmTr
         )
     }
 
+    async fn selected_candidate_type(&self) -> Option<String> {
+        let reports = self.stats_reports().await?;
+        let pair = selected_candidate_pair(&reports)?;
+
+        let candidate_type = |id: &serde_json::Value| {
+            reports
+                .iter()
+                .find(|x| x["id"] == *id)
+                .and_then(|x| x["candidateType"].as_str())
+                .map(|x| x.to_string())
+        };
+        let local = candidate_type(&pair["localCandidateId"])?;
+        let remote = candidate_type(&pair["remoteCandidateId"]);
+        if remote.as_deref() == Some("relay") {
+            return remote;
+        }
+        Some(local)
+    }
+
+    async fn connection_stats(&self) -> Option<ConnectionStats> {
+        let reports = self.stats_reports().await?;
+        let pair = selected_candidate_pair(&reports)?;
+        Some(ConnectionStats::new(
+            pair["currentRoundTripTime"].as_f64().unwrap_or_default() * 1000.0,
+            pair["requestsSent"].as_u64().unwrap_or_default(),
+            pair["responsesReceived"].as_u64().unwrap_or_default(),
+        ))
+    }
+
     async fn is_connected(&self) -> bool {
         self.ice_connection_state()
             .await
@@ -269,28 +320,80 @@ impl IceTransportInterface<TransportEvent, CbChannel<TransportEvent>> for WasmTr
     }
 
     async fn send_message(&self, msg: &Bytes) -> Result<()> {
+        self.send_message_with_priority(msg, Priority::Normal).await
+    }
+
+    async fn send_message_with_priority(&self, msg: &Bytes, priority: Priority) -> Result<()> {
         if msg.len() > TRANSPORT_MAX_SIZE {
             return Err(Error::MessageTooLarge);
         }
 
-        let dc = self
-            .get_data_channel()
-            .await
-            .ok_or(Error::RTCDataChannelNotReady)?;
+        if self.get_data_channel().await.is_none() {
+            return Err(Error::RTCDataChannelNotReady);
+        }
 
-        let chunks = ChunkList::<TRANSPORT_MTU>::from(msg);
+        let chunks = ChunkList::<TRANSPORT_MTU>::from(msg)
+            .into_iter()
+            .map(|c| c.to_bincode())
+            .collect::<Result<Vec<_>>>()?;
 
-        for c in chunks {
-            let bytes = c.to_bincode()?;
-            dc.send_with_u8_array(&bytes)
-                .map_err(|e| Error::RTCDataChannelSendTextFailed(format!("{:?}", e)))?
-        }
+        self.send_lanes.send(self, chunks, priority).await
+    }
 
-        Ok(())
+    async fn buffered_amount(&self) -> usize {
+        self.buffered().await + self.send_lanes.queued_bytes()
+    }
+
+    fn direction(&self) -> Option<TransportDirection> {
+        self.direction
+    }
+
+    fn set_direction(&mut self, direction: TransportDirection) {
+        self.direction = Some(direction);
+    }
+
+    async fn local_description(&self) -> Option<SessionDescription> {
+        let desc = self.get_peer_connection().await?.local_description()?;
+        let json = serde_json::to_string(&RtcSessionDescriptionWrapper::from(desc)).ok()?;
+        serde_json::from_str(&json).ok()
     }
 }
 
+/// The nominated pair of succeeded candidate pairs, or the first succeeded one.
+fn selected_candidate_pair(reports: &[serde_json::Value]) -> Option<&serde_json::Value> {
+    let succeeded = reports
+        .iter()
+        .filter(|x| x["type"] == "candidate-pair" && x["state"] == "succeeded")
+        .collect::<Vec<_>>();
+    succeeded
+        .iter()
+        .find(|x| x["nominated"] == true)
+        .or_else(|| succeeded.first())
+        .copied()
+}
+
 impl WasmTransport {
+    /// Stats reports of peer connection as json objects.
+    async fn stats_reports(&self) -> Option<Vec<serde_json::Value>> {
+        let pc = self.get_peer_connection().await?;
+
+        let stats: RtcStatsReport = wasm_bindgen_futures::JsFuture::from(pc.get_stats())
+            .await
+            .ok()?
+            .into();
+
+        // Each entry is an array of id and stats.
+        Some(
+            stats
+                .entries()
+                .into_iter()
+                .filter_map(|x| dump_stats_entry(&x.ok()))
+                .filter_map(|x| serde_json::from_str::<serde_json::Value>(&x).ok())
+                .filter_map(|x| x.get(1).cloned())
+                .collect(),
+        )
+    }
+
     pub async fn setup_channel(&mut self, name: &str) {
         if let Some(conn) = &self.connection {
             let channel = conn.create_data_channel(name);
@@ -299,6 +402,29 @@ impl WasmTransport {
     }
 }
 
+#[async_trait(?Send)]
+impl ChunkSink for WasmTransport {
+    async fn buffered(&self) -> usize {
+        match self.get_data_channel().await {
+            Some(dc) => dc.buffered_amount() as usize,
+            None => 0,
+        }
+    }
+
+    async fn is_open(&self) -> bool {
+        self.is_data_channel_open().await
+    }
+
+    async fn send_chunk(&self, chunk: &Bytes) -> Result<()> {
+        let dc = self
+            .get_data_channel()
+            .await
+            .ok_or(Error::RTCDataChannelNotReady)?;
+        dc.send_with_u8_array(chunk)
+            .map_err(|e| Error::RTCDataChannelSendTextFailed(format!("{:?}", e)))
+    }
+}
+
 #[async_trait(?Send)]
 impl IceTransportCallback for WasmTransport {
     type OnLocalCandidateHdlrFn = Box<dyn FnMut(RtcPeerConnectionIceEvent)>;
@@ -541,24 +667,7 @@ impl IceTrickleScheme for WasmTransport {
             }
         };
 
-        let local_candidates_json: Vec<IceCandidate> = self
-            .get_pending_candidates()
-            .await
-            .iter()
-            .map(|c| js_value::deserialize::<IceCandidate>(&c.clone().to_json()).unwrap())
-            .collect();
-
-        if local_candidates_json.is_empty() {
-            return Err(Error::FailedOnGatherLocalCandidate);
-        }
-
-        let data = HandshakeInfo {
-            sdp: serde_json::to_string(&RtcSessionDescriptionWrapper::from(sdp))
-                .map_err(Error::Deserialize)?,
-            candidates: local_candidates_json,
-        };
-        tracing::debug!("prepared handshake info :{:?}", data);
-        Ok(data)
+        self.handshake_info_with_candidates(sdp).await
     }
 
     async fn register_remote_info(&self, data: &HandshakeInfo, did: Did) -> Result<()> {
@@ -586,53 +695,96 @@ impl IceTrickleScheme for WasmTransport {
         let promise = self.connect_success_promise().await?;
         promise.await
     }
+
+    async fn get_ice_restart_info(&self) -> Result<HandshakeInfo> {
+        let c = self
+            .get_peer_connection()
+            .await
+            .ok_or(Error::RTCPeerConnectionNotEstablish)?;
+        let state = c.ice_connection_state();
+        if !matches!(
+            state,
+            RtcIceConnectionState::Connected
+                | RtcIceConnectionState::Completed
+                | RtcIceConnectionState::Disconnected
+                | RtcIceConnectionState::Failed
+        ) {
+            return Err(Error::TransportNotRestartable(format!("{:?}", state)));
+        }
+
+        let mut options = RtcOfferOptions::new();
+        options.ice_restart(true);
+        let offer = JsFuture::from(c.create_offer_with_rtc_offer_options(&options))
+            .await
+            .map_err(|e| Error::RTCPeerConnectionCreateOfferFailed(format!("{:?}", e)))?;
+        // candidates of previous ice session are useless for remote
+        self.pending_candidates.lock().unwrap().clear();
+        self.set_local_description(RtcSessionDescriptionWrapper::from(offer.to_owned()))
+            .await?;
+        let promise = self.gather_complete_promise().await?;
+        promise.await?;
+
+        self.handshake_info_with_candidates(offer.into()).await
+    }
 }
 
 impl WasmTransport {
+    async fn handshake_info_with_candidates(
+        &self,
+        sdp: RtcSessionDescription,
+    ) -> Result<HandshakeInfo> {
+        let local_candidates_json: Vec<IceCandidate> = self
+            .get_pending_candidates()
+            .await
+            .iter()
+            .map(|c| js_value::deserialize::<IceCandidate>(&c.clone().to_json()).unwrap())
+            .collect();
+
+        if local_candidates_json.is_empty() {
+            return Err(Error::FailedOnGatherLocalCandidate);
+        }
+
+        let data = HandshakeInfo {
+            sdp: serde_json::to_string(&RtcSessionDescriptionWrapper::from(sdp))
+                .map_err(Error::Deserialize)?,
+            candidates: local_candidates_json,
+        };
+        tracing::debug!("prepared handshake info :{:?}", data);
+        Ok(data)
+    }
+
+    /// Check the data channel is open, messages can't be sent through transport before that.
+    pub async fn is_data_channel_open(&self) -> bool {
+        self.get_data_channel()
+            .await
+            .map_or(false, |dc| dc.ready_state() == RtcDataChannelState::Open)
+    }
+
     pub async fn wait_for_data_channel_open(&self) -> Result<()> {
         if self.is_disconnected().await {
             return Err(Error::RTCPeerConnectionNotEstablish);
         }
 
-        let dc = self.get_data_channel().await;
-        match dc {
-            Some(dc) => {
-                if dc.ready_state() == RtcDataChannelState::Open {
-                    return Ok(());
+        let Some(dc) = self.get_data_channel().await else {
+            tracing::error!("{:?}", Error::RTCDataChannelNotReady);
+            return Err(Error::RTCDataChannelNotReady);
+        };
+
+        // The `onopen` handler of data channel is replaced by each waiter,
+        // so concurrent waiters poll the ready state instead of registering one.
+        loop {
+            match dc.ready_state() {
+                RtcDataChannelState::Open => return Ok(()),
+                RtcDataChannelState::Closing | RtcDataChannelState::Closed => {
+                    return Err(Error::RTCDataChannelStateNotOpen)
                 }
-                let promise = Promise::default();
-                let state = Arc::clone(&promise.state());
-                let dc_cloned = Arc::clone(&dc);
-                let callback = Closure::wrap(Box::new(move || {
-                    tracing::debug!(
-                        "wait_for_data_channel_open, state: {:?}",
-                        dc_cloned.ready_state()
-                    );
-                    match dc_cloned.ready_state() {
-                        RtcDataChannelState::Open => {
-                            let state = Arc::clone(&state);
-                            let mut s = state.lock().unwrap();
-                            if let Some(w) = s.waker.take() {
-                                w.wake();
-                                s.completed = true;
-                                s.succeeded = Some(true);
-                            }
-                        }
-                        x => {
-                            tracing::debug!("datachannel status: {:?}", x)
-                        }
-                    }
-                }) as Box<dyn FnMut()>);
-                dc.set_onopen(Some(callback.as_ref().unchecked_ref()));
-                callback.forget();
-                promise.await?;
+                _ => {}
             }
-            None => {
-                tracing::error!("{:?}", Error::RTCDataChannelNotReady);
-                return Err(Error::RTCDataChannelNotReady);
+            if self.is_disconnected().await {
+                return Err(Error::RTCPeerConnectionNotEstablish);
             }
-        };
-        Ok(())
+            Delay::new(Duration::from_millis(SEND_QUEUE_POLL_INTERVAL_MS)).await;
+        }
     }
 
     pub async fn gather_complete_promise(&self) -> Result<Promise> {
@@ -9,7 +9,9 @@ use serde::Serialize;
 
 pub use self::ice_server::IceServer;
 use crate::dht::Did;
+use crate::error::Error;
 use crate::error::Result;
+use crate::transports::lanes::Priority;
 use crate::types::channel::Channel;
 
 /// Struct From [webrtc-rs](https://docs.rs/webrtc/latest/webrtc/ice_transport/ice_candidate/struct.RTCIceCandidateInit.html)
@@ -36,6 +38,97 @@ pub struct HandshakeInfo {
     pub candidates: Vec<IceCandidate>,
 }
 
+impl HandshakeInfo {
+    /// Session description of handshake, `sdp` is its json form.
+    pub fn session_description(&self) -> Result<SessionDescription> {
+        serde_json::from_str(&self.sdp).map_err(Error::Deserialize)
+    }
+}
+
+/// Session description in plain SDP, for interop with WebRTC peers outside of Rings.
+/// The json form is the same as [RTCSessionDescriptionInit](https://w3c.github.io/webrtc-pc/#dom-rtcsessiondescriptioninit).
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct SessionDescription {
+    /// Type of description, such as `offer` or `answer`.
+    #[serde(rename = "type")]
+    pub sdp_type: String,
+    /// Description in SDP, gathered candidates are included.
+    pub sdp: String,
+}
+
+impl SessionDescription {
+    /// ICE username fragment, the value of first `a=ice-ufrag` attribute.
+    pub fn ice_ufrag(&self) -> Option<&str> {
+        self.attribute("ice-ufrag")
+    }
+
+    /// ICE password, the value of first `a=ice-pwd` attribute.
+    pub fn ice_pwd(&self) -> Option<&str> {
+        self.attribute("ice-pwd")
+    }
+
+    /// Candidates in `a=candidate` attributes.
+    pub fn candidates(&self) -> Vec<&str> {
+        self.sdp
+            .lines()
+            .filter_map(|line| line.trim_end().strip_prefix("a="))
+            .filter(|attr| attr.starts_with("candidate:"))
+            .collect()
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.sdp.lines().find_map(|line| {
+            line.trim_end()
+                .strip_prefix("a=")?
+                .strip_prefix(name)?
+                .strip_prefix(':')
+        })
+    }
+}
+
+/// Stats of the selected candidate pair of a connection.
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy, Default)]
+pub struct ConnectionStats {
+    /// latest round trip time of STUN connectivity checks in ms
+    pub rtt_ms: f64,
+    /// fraction of connectivity checks without response, in `0.0..=1.0`
+    pub packet_loss: f64,
+}
+
+impl ConnectionStats {
+    /// Build stats from counters of connectivity checks.
+    pub fn new(rtt_ms: f64, requests_sent: u64, responses_received: u64) -> Self {
+        let packet_loss = if requests_sent == 0 {
+            0.0
+        } else {
+            1.0 - responses_received.min(requests_sent) as f64 / requests_sent as f64
+        };
+        Self {
+            rtt_ms,
+            packet_loss,
+        }
+    }
+}
+
+/// Which side initiated a transport.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportDirection {
+    /// The transport is created to answer an offer of remote peer.
+    Inbound,
+    /// The transport is created to offer to remote peer.
+    Outbound,
+}
+
+impl std::fmt::Display for TransportDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inbound => write!(f, "inbound"),
+            Self::Outbound => write!(f, "outbound"),
+        }
+    }
+}
+
 /// A useful trait implement by IceTransport that we use.
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
@@ -57,16 +150,37 @@ pub trait IceTransport {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 pub trait IceTransportInterface<E: Send, Ch: Channel<E>> {
     type IceConnectionState;
+    type IceGatheringState;
 
     fn new(event_sender: Ch::Sender) -> Self;
     async fn start(&mut self, addr: Vec<IceServer>, external_id: Option<String>) -> Result<&Self>;
     async fn apply_callback(&self) -> Result<&Self>;
     async fn close(&self) -> Result<()>;
     async fn ice_connection_state(&self) -> Option<Self::IceConnectionState>;
+    /// State of candidate gathering, `None` if the transport is not started or doesn't gather candidates.
+    async fn ice_gathering_state(&self) -> Option<Self::IceGatheringState>;
     async fn get_stats(&self) -> Option<Vec<String>>;
+    /// Type of local candidate of the selected candidate pair, such as `host`, `srflx`, `prflx` or `relay`.
+    /// It's `relay` if either candidate of the pair is relayed, `None` if no pair is selected yet.
+    async fn selected_candidate_type(&self) -> Option<String>;
+    /// Stats of the selected candidate pair, `None` if no pair is selected yet.
+    async fn connection_stats(&self) -> Option<ConnectionStats>;
     async fn is_connected(&self) -> bool;
     async fn is_disconnected(&self) -> bool;
     async fn send_message(&self, msg: &Bytes) -> Result<()>;
+    /// Send message through the lane of priority, so chunks of higher priority jump ahead of
+    /// queued ones. Transports without lanes send it as [Self::send_message] does.
+    async fn send_message_with_priority(&self, msg: &Bytes, _priority: Priority) -> Result<()> {
+        self.send_message(msg).await
+    }
+    /// Bytes queued in data channel which are not transmitted yet, `0` if there is no data channel.
+    async fn buffered_amount(&self) -> usize;
+    /// Which side initiated the transport, `None` if it's not created for a handshake.
+    fn direction(&self) -> Option<TransportDirection>;
+    /// Record which side initiated the transport, before it's shared.
+    fn set_direction(&mut self, direction: TransportDirection);
+    /// Local session description, `None` if it's not set yet, or the transport has no SDP.
+    async fn local_description(&self) -> Option<SessionDescription>;
 }
 
 /// A IceTransportCallback use to register a callback..
@@ -106,4 +220,28 @@ pub trait IceTrickleScheme {
     async fn get_handshake_info(&self, kind: Self::SdpType) -> Result<HandshakeInfo>;
     async fn register_remote_info(&self, data: &HandshakeInfo, did: Did) -> Result<()>;
     async fn wait_for_connected(&self) -> Result<()>;
+    /// Create an offer with new ice credentials on an established connection,
+    /// the data channel is kept, and fresh candidates are gathered.
+    /// Answer it by `register_remote_info` and `get_handshake_info` of answer on remote.
+    async fn get_ice_restart_info(&self) -> Result<HandshakeInfo>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session_description_ice_params() {
+        let info = HandshakeInfo {
+            sdp: r#"{"type":"offer","sdp":"v=0\r\na=ice-ufrag:abcd\r\na=ice-pwd:secret\r\na=candidate:1 1 udp 2130706431 127.0.0.1 50000 typ host\r\n"}"#.to_string(),
+            candidates: vec![],
+        };
+        let desc = info.session_description().unwrap();
+        assert_eq!(desc.sdp_type, "offer");
+        assert_eq!(desc.ice_ufrag(), Some("abcd"));
+        assert_eq!(desc.ice_pwd(), Some("secret"));
+        assert_eq!(desc.candidates(), vec![
+            "candidate:1 1 udp 2130706431 127.0.0.1 50000 typ host"
+        ]);
+    }
 }
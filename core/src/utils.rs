@@ -2,6 +2,7 @@
 use chrono::Utc;
 
 use crate::prelude::RTCIceConnectionState;
+use crate::prelude::RTCIceGatheringState;
 
 /// Get local utc timestamp (millisecond)
 pub fn get_epoch_ms() -> u128 {
@@ -23,6 +24,17 @@ pub fn from_rtc_ice_connection_state(state: RTCIceConnectionState) -> String {
     .to_owned()
 }
 
+/// convert RTCIceGatheringState to string
+pub fn from_rtc_ice_gathering_state(state: RTCIceGatheringState) -> String {
+    match state {
+        RTCIceGatheringState::New => "new",
+        RTCIceGatheringState::Gathering => "gathering",
+        RTCIceGatheringState::Complete => "complete",
+        _ => "unknown",
+    }
+    .to_owned()
+}
+
 /// convert string to RTCIceConnectionState
 #[allow(dead_code)]
 pub fn into_rtc_ice_connection_state(value: &str) -> Option<RTCIceConnectionState> {
@@ -1,17 +1,13 @@
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
 
 use clap::ArgAction;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
-use futures::future::FutureExt;
 use futures::pin_mut;
-use futures::select;
 use futures::StreamExt;
-use futures_timer::Delay;
 use rings_node::backend::service::Backend;
 use rings_node::logging::init_logging;
 use rings_node::logging::LogLevel;
@@ -19,8 +15,10 @@ use rings_node::measure::PeriodicMeasure;
 use rings_node::native::cli::Client;
 use rings_node::native::config;
 use rings_node::native::endpoint::run_http_api;
+use rings_node::native::endpoint::run_metrics_api;
 use rings_node::prelude::http;
 use rings_node::prelude::rings_core::ecc::SecretKey;
+use rings_node::prelude::rings_rpc::types::ServiceMetadata;
 use rings_node::prelude::PersistenceStorage;
 use rings_node::prelude::SessionManager;
 use rings_node::processor::Processor;
@@ -103,6 +101,20 @@ struct RunCommand {
     )]
     pub http_addr: Option<String>,
 
+    #[arg(
+        long,
+        help = "Listen address of Prometheus metrics server. If not provided, use metrics_bind in config file or not run it",
+        env
+    )]
+    pub metrics_addr: Option<String>,
+
+    #[arg(
+        long,
+        help = "Pre-shared bearer token of JSON-RPC server. If not provided, use rpc_token in config file or accept signed requests only",
+        env
+    )]
+    pub rpc_token: Option<String>,
+
     #[arg(
         long,
         short = 's',
@@ -197,6 +209,12 @@ struct ConnectUrlCommand {
     client_args: ClientArgs,
 
     node_url: String,
+
+    #[arg(
+        long,
+        help = "abort if connection is not open in time, default is 30000"
+    )]
+    timeout_ms: Option<u64>,
 }
 
 #[derive(Args, Debug)]
@@ -205,6 +223,12 @@ struct ConnectWithDidCommand {
     client_args: ClientArgs,
 
     did: String,
+
+    #[arg(
+        long,
+        help = "abort if connection is not open in time, default is 30000"
+    )]
+    timeout_ms: Option<u64>,
 }
 
 #[derive(Args, Debug)]
@@ -213,6 +237,12 @@ struct ConnectWithSeedCommand {
     client_args: ClientArgs,
 
     source: String,
+
+    #[arg(
+        long,
+        help = "abort if connection is not open in time, default is 30000"
+    )]
+    timeout_ms: Option<u64>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -346,6 +376,21 @@ struct ServiceRegisterCommand {
     client_args: ClientArgs,
 
     name: String,
+
+    #[arg(
+        long,
+        help = "registration expires if not renewed in time, default is 60000"
+    )]
+    ttl_ms: Option<u64>,
+
+    #[arg(long, help = "version of service provided")]
+    service_version: Option<String>,
+
+    #[arg(long = "tag", action = ArgAction::Append, help = "tags of provider, consumers can filter by them")]
+    tags: Vec<String>,
+
+    #[arg(long, help = "human-readable endpoint of service")]
+    endpoint: Option<String>,
 }
 
 #[derive(Args, Debug)]
@@ -354,6 +399,9 @@ struct ServiceLookupCommand {
     client_args: ClientArgs,
 
     name: String,
+
+    #[arg(long, help = "only lookup providers tagged with it")]
+    tag: Option<String>,
 }
 
 #[derive(Args, Debug)]
@@ -378,6 +426,12 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
     if let Some(http_addr) = args.http_addr {
         c.http_addr = http_addr;
     }
+    if let Some(metrics_addr) = args.metrics_addr {
+        c.metrics_bind = Some(metrics_addr);
+    }
+    if let Some(rpc_token) = args.rpc_token {
+        c.rpc_token = Some(rpc_token);
+    }
 
     let pc = ProcessorConfig::from(&c);
 
@@ -408,21 +462,35 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
     let backend_config = (c.backend, c.extension).into();
     let backend = Backend::new(backend_config, sender).await?;
     let backend_service_names = backend.service_names();
+    let swarm_binding = backend.swarm_binding();
+    let message_types = backend.message_types();
 
     let processor = Arc::new(
         ProcessorBuilder::from_config(serde_yaml::to_string(&pc)?)?
             .storage(per_data_storage)
             .measure(measure)
+            .message_type_registry(message_types)
             .message_callback(Box::new(backend))
+            .backpressure(c.send_queue)
+            .max_concurrent_offers(c.max_concurrent_offers)
+            .session_clock_skew_tolerance(c.session_clock_skew_ms)
             .build()?,
     );
+    swarm_binding.bind(processor.swarm.clone());
     println!("Did: {}", processor.swarm.did());
 
     let processor_clone = processor.clone();
+    let metrics_api = async {
+        match c.metrics_bind {
+            Some(addr) => run_metrics_api(addr, processor.clone()).await,
+            None => Ok(()),
+        }
+    };
     let _ = futures::join!(
         processor.listen(),
-        service_loop_register(&processor, backend_service_names),
-        run_http_api(c.http_addr, processor_clone, receiver),
+        register_services(&processor, backend_service_names),
+        run_http_api(c.http_addr, processor_clone, receiver, c.rpc_token),
+        metrics_api,
     );
 
     Ok(())
@@ -463,7 +531,7 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .connect_peer_via_http(args.node_url.as_str())
+                .connect_peer_via_http(args.node_url.as_str(), args.timeout_ms)
                 .await?
                 .display();
             Ok(())
@@ -472,7 +540,7 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .connect_with_did(args.did.as_str())
+                .connect_with_did(args.did.as_str(), args.timeout_ms)
                 .await?
                 .display();
             Ok(())
@@ -481,7 +549,7 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .connect_with_seed(args.source.as_str())
+                .connect_with_seed(args.source.as_str(), args.timeout_ms)
                 .await?
                 .display();
             Ok(())
@@ -580,7 +648,11 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .register_service(args.name.as_str())
+                .register_service(args.name.as_str(), args.ttl_ms, &ServiceMetadata {
+                    version: args.service_version,
+                    tags: args.tags,
+                    endpoint: args.endpoint,
+                })
                 .await?
                 .display();
             Ok(())
@@ -589,7 +661,7 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .lookup_service(args.name.as_str())
+                .lookup_service(args.name.as_str(), args.tag.as_deref())
                 .await?
                 .display();
             Ok(())
@@ -628,13 +700,3 @@ async fn register_services(processor: &Processor, names: Vec<String>) -> anyhow:
 
     Ok(())
 }
-
-async fn service_loop_register(processor: &Processor, names: Vec<String>) {
-    loop {
-        let timeout = Delay::new(Duration::from_secs(30)).fuse();
-        pin_mut!(timeout);
-        select! {
-            _ = timeout => register_services(processor, names.clone()).await.unwrap_or_else(|e| eprintln!("Error: {}", e)),
-        }
-    }
-}
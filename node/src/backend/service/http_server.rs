@@ -4,15 +4,20 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use bytes::Bytes;
+use futures::Stream;
 use rings_core::chunk::ChunkList;
 use serde::Deserialize;
 use serde::Serialize;
 
 use super::backend::types::BackendMessage;
 use super::backend::types::HttpResponse;
+use super::backend::types::HttpResponsePart;
 use super::backend::MessageEndpoint;
 use super::backend::MessageType;
 use crate::consts::BACKEND_MTU;
+use crate::consts::HTTP_STREAM_CHUNK_SIZE;
+use crate::consts::MAX_HTTP_REQUEST_HEADERS;
+use crate::consts::MAX_HTTP_REQUEST_HEADERS_SIZE;
 use crate::error::Error;
 use crate::error::Result;
 use crate::prelude::rings_rpc::types::HttpRequest;
@@ -60,8 +65,25 @@ impl From<Vec<HiddenServerConfig>> for HttpServer {
 }
 
 impl HttpServer {
-    /// execute http request
-    pub async fn execute(&self, request: &HttpRequest) -> Result<HttpResponse> {
+    /// send http request to hidden service, the body of response is not read
+    async fn send(&self, request: &HttpRequest) -> Result<reqwest::Response> {
+        request.validate().map_err(Error::from)?;
+        let headers_size: usize = request
+            .headers
+            .iter()
+            .map(|(name, value)| name.len() + value.len())
+            .sum();
+        if request.headers.len() > MAX_HTTP_REQUEST_HEADERS
+            || headers_size > MAX_HTTP_REQUEST_HEADERS_SIZE
+        {
+            tracing::info!(
+                "too many headers: count {}, size {}",
+                request.headers.len(),
+                headers_size
+            );
+            return Err(Error::InvalidHeaders);
+        }
+
         let service = self
             .services
             .iter()
@@ -74,7 +96,9 @@ impl HttpServer {
             request.path.trim_start_matches('/')
         );
 
-        let request_url = url.parse::<http::Uri>().unwrap();
+        let request_url = url
+            .parse::<http::Uri>()
+            .map_err(|e| Error::HttpRequestError(e.to_string()))?;
 
         let request_method =
             http::Method::from_str(request.method.as_str()).map_err(|_| Error::InvalidMethod)?;
@@ -97,10 +121,15 @@ impl HttpServer {
             request_builder
         };
 
-        let resp = request_builder
+        request_builder
             .send()
             .await
-            .map_err(|e| Error::HttpRequestError(e.to_string()))?;
+            .map_err(|e| Error::HttpRequestError(e.to_string()))
+    }
+
+    /// execute http request
+    pub async fn execute(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let resp = self.send(request).await?;
 
         let status = resp.status().as_u16();
 
@@ -121,6 +150,52 @@ impl HttpServer {
             body: Some(body),
         })
     }
+
+    /// execute http request, and yield parts of response as the body arrives.
+    /// The stream ends with [HttpResponsePart::End], or [HttpResponsePart::Error]
+    /// if the request failed or upstream closed mid-stream.
+    pub fn execute_stream<'a>(
+        &'a self,
+        request: &'a HttpRequest,
+    ) -> impl Stream<Item = HttpResponsePart> + 'a {
+        async_stream::stream! {
+            let mut resp = match self.send(request).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    yield HttpResponsePart::Error(e.to_string());
+                    return;
+                }
+            };
+
+            yield HttpResponsePart::Head {
+                status: resp.status().as_u16(),
+                headers: resp
+                    .headers()
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_str().unwrap_or("").to_owned()))
+                    .collect(),
+            };
+
+            loop {
+                match resp.chunk().await {
+                    Ok(Some(bytes)) => {
+                        for i in (0..bytes.len()).step_by(HTTP_STREAM_CHUNK_SIZE) {
+                            let end = bytes.len().min(i + HTTP_STREAM_CHUNK_SIZE);
+                            yield HttpResponsePart::Body(bytes.slice(i..end));
+                        }
+                    }
+                    Ok(None) => {
+                        yield HttpResponsePart::End;
+                        return;
+                    }
+                    Err(e) => {
+                        yield HttpResponsePart::Error(e.to_string());
+                        return;
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -163,3 +238,134 @@ impl MessageEndpoint for HttpServer {
         Ok(events)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Serve one connection with raw response, and return the hidden server.
+    async fn serve_once(response: &'static [u8]) -> HttpServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream.write_all(response).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+        HttpServer::from(vec![HiddenServerConfig {
+            name: "test".to_string(),
+            register_service: None,
+            prefix: format!("http://{}", addr),
+        }])
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream() {
+        let server = serve_once(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\nConnection: close\r\n\r\nhello world",
+        )
+        .await;
+        let request = HttpRequest::from(("test", http::Method::GET, "/", 3000));
+        let parts = server.execute_stream(&request).collect::<Vec<_>>().await;
+
+        let HttpResponsePart::Head { status, headers } = &parts[0] else {
+            panic!("first part should be head, got {:?}", parts[0]);
+        };
+        assert_eq!(*status, 200);
+        assert_eq!(headers.get("content-length"), Some(&"11".to_string()));
+        assert_eq!(parts.last(), Some(&HttpResponsePart::End));
+
+        let body = parts[1..parts.len() - 1]
+            .iter()
+            .flat_map(|part| match part {
+                HttpResponsePart::Body(bytes) => bytes.to_vec(),
+                _ => panic!("unexpected part {:?}", part),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(body, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_closed_mid_stream() {
+        let server =
+            serve_once(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nonly part of body").await;
+        let request = HttpRequest::from(("test", http::Method::GET, "/", 3000));
+        let parts = server.execute_stream(&request).collect::<Vec<_>>().await;
+
+        assert!(matches!(parts[0], HttpResponsePart::Head {
+            status: 200,
+            ..
+        }));
+        assert!(matches!(parts.last(), Some(HttpResponsePart::Error(_))));
+        assert!(!parts.contains(&HttpResponsePart::End));
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_invalid_headers() {
+        let server = HttpServer::from(vec![HiddenServerConfig {
+            name: "test".to_string(),
+            register_service: None,
+            prefix: "http://127.0.0.1:1".to_string(),
+        }]);
+
+        let request = HttpRequest::get(
+            "test",
+            "/",
+            3000.into(),
+            &[("x-test", "value\r\nx-injected: true")],
+            None,
+        );
+        assert!(matches!(
+            server.execute(&request).await,
+            Err(Error::HttpRequestError(_))
+        ));
+
+        let headers = (0..=MAX_HTTP_REQUEST_HEADERS)
+            .map(|i| (format!("x-test-{}", i), "value".to_string()))
+            .collect::<Vec<_>>();
+        let request = HttpRequest::get(
+            "test".to_string(),
+            "/".to_string(),
+            3000.into(),
+            &headers,
+            None,
+        );
+        assert!(matches!(
+            server.execute(&request).await,
+            Err(Error::InvalidHeaders)
+        ));
+
+        let headers = vec![(
+            "x-test".to_string(),
+            "a".repeat(MAX_HTTP_REQUEST_HEADERS_SIZE),
+        )];
+        let request = HttpRequest::get(
+            "test".to_string(),
+            "/".to_string(),
+            3000.into(),
+            &headers,
+            None,
+        );
+        assert!(matches!(
+            server.execute(&request).await,
+            Err(Error::InvalidHeaders)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_invalid_service() {
+        let server = HttpServer::default();
+        let request = HttpRequest::from(("test", http::Method::GET, "/", 3000));
+        let parts = server.execute_stream(&request).collect::<Vec<_>>().await;
+        assert_eq!(parts, vec![HttpResponsePart::Error(
+            Error::InvalidService.to_string()
+        )]);
+    }
+}
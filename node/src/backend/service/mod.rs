@@ -5,11 +5,14 @@ pub mod http_server;
 pub mod text;
 pub mod utils;
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::OnceLock;
 
 use arrayref::array_refs;
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::StreamExt;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::broadcast::Sender;
@@ -22,24 +25,53 @@ use crate::backend;
 use crate::backend::extension::Extension;
 use crate::backend::extension::ExtensionConfig;
 use crate::backend::types::BackendMessage;
+use crate::backend::types::HttpResponseChunk;
+use crate::backend::types::HttpResponsePart;
+use crate::backend::types::IncomingMessage;
 use crate::backend::types::MessageEndpoint;
 use crate::backend::types::MessageType;
+use crate::backend::types::MessageTypeRegistry;
 use crate::consts::BACKEND_MTU;
+use crate::consts::DEFAULT_MAX_MESSAGE_BYTES;
 use crate::error::Error;
 use crate::error::Result;
 use crate::prelude::rings_core::chunk::Chunk;
 use crate::prelude::rings_core::chunk::ChunkList;
 use crate::prelude::rings_core::chunk::ChunkManager;
 use crate::prelude::rings_core::message::Message;
+use crate::prelude::rings_rpc::types::HttpRequest;
 use crate::prelude::*;
+use crate::processor::decompress_message;
 
 /// A Backend struct contains http_server.
 pub struct Backend {
     http_server: Arc<HttpServer>,
     text_endpoint: TextEndpoint,
     extension_endpoint: Extension,
-    sender: Sender<BackendMessage>,
+    sender: Sender<IncomingMessage>,
     chunk_list: Arc<Mutex<ChunkList<BACKEND_MTU>>>,
+    swarm: SwarmBinding,
+    message_types: Arc<MessageTypeRegistry>,
+    custom_endpoints: HashMap<String, Box<dyn MessageEndpoint + Send + Sync>>,
+    max_message_bytes: usize,
+}
+
+/// A handle to bind the swarm of [Processor](crate::processor::Processor) to [Backend] after it's built.
+/// With the swarm, backend forwards chunks of streaming http response as they arrive.
+#[derive(Clone, Default)]
+pub struct SwarmBinding(Arc<OnceLock<Arc<Swarm>>>);
+
+impl SwarmBinding {
+    /// Bind the swarm, only the first binding takes effect.
+    pub fn bind(&self, swarm: Arc<Swarm>) {
+        if self.0.set(swarm).is_err() {
+            tracing::warn!("swarm of backend is already bound");
+        }
+    }
+
+    fn get(&self) -> Option<Arc<Swarm>> {
+        self.0.get().cloned()
+    }
 }
 
 /// BackendConfig
@@ -64,21 +96,124 @@ impl From<(Vec<HiddenServerConfig>, ExtensionConfig)> for BackendConfig {
 impl Backend {
     /// new backend
     /// - `ipfs_gateway`
-    pub async fn new(config: BackendConfig, sender: Sender<BackendMessage>) -> Result<Self> {
+    pub async fn new(config: BackendConfig, sender: Sender<IncomingMessage>) -> Result<Self> {
         Ok(Self {
             http_server: Arc::new(HttpServer::from(config.hidden_servers)),
             text_endpoint: TextEndpoint,
             sender,
             extension_endpoint: Extension::new(&config.extensions).await?,
             chunk_list: Default::default(),
+            swarm: Default::default(),
+            message_types: Default::default(),
+            custom_endpoints: HashMap::new(),
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
         })
     }
 
-    async fn handle_chunk_data(&self, data: &[u8]) -> Result<Option<Bytes>> {
+    /// Get the registry of custom message types, which can be shared with
+    /// [ProcessorBuilder::message_type_registry](crate::processor::ProcessorBuilder::message_type_registry).
+    pub fn message_types(&self) -> Arc<MessageTypeRegistry> {
+        self.message_types.clone()
+    }
+
+    /// Set the endpoint handling messages of a custom message type by its registered name.
+    pub fn with_endpoint<E>(mut self, name: &str, endpoint: E) -> Self
+    where E: MessageEndpoint + Send + Sync + 'static {
+        self.custom_endpoints
+            .insert(name.to_string(), Box::new(endpoint));
+        self
+    }
+
+    /// Set the max size of messages inflated from compressed ones, which should be the
+    /// [ProcessorBuilder::max_message_bytes](crate::processor::ProcessorBuilder::max_message_bytes)
+    /// of processor. Default is [DEFAULT_MAX_MESSAGE_BYTES].
+    pub fn with_max_message_bytes(mut self, max: usize) -> Self {
+        self.max_message_bytes = max;
+        self
+    }
+
+    /// Dispatch message of custom type to the endpoint of its registered name.
+    async fn handle_custom_message(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &BackendMessage,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        let endpoint = self
+            .message_types
+            .name(msg.message_type)
+            .and_then(|name| self.custom_endpoints.get(&name));
+        match endpoint {
+            Some(endpoint) => endpoint.handle_message(ctx, msg).await,
+            None => {
+                tracing::debug!(
+                    "custom_message handle unsupported, tag: {:?}",
+                    msg.message_type
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Get the handle to bind swarm of processor to backend.
+    pub fn swarm_binding(&self) -> SwarmBinding {
+        self.swarm.clone()
+    }
+
+    /// Handle streaming http request, response is sent back as a sequence of [HttpResponseChunk].
+    /// Chunks are sent in a spawned task as the body arrives, so the swarm must be bound,
+    /// otherwise the request is rejected with a single [HttpResponsePart::Error].
+    async fn handle_http_stream_request(
+        &self,
+        ctx: &MessagePayload<Message>,
+        tx_id: uuid::Uuid,
+        msg: &BackendMessage,
+    ) -> Result<Vec<MessageHandlerEvent>> {
+        let req: HttpRequest = bincode::deserialize(&msg.data).map_err(|_| Error::DecodeError)?;
+
+        let Some(swarm) = self.swarm.get() else {
+            let chunk = HttpResponseChunk {
+                tx_id,
+                seq: 0,
+                part: HttpResponsePart::Error("streaming response is not available".to_string()),
+            };
+            return Ok(vec![MessageHandlerEvent::SendReportMessage(
+                ctx.clone(),
+                utils::http_response_chunk_message(&chunk)?,
+            )]);
+        };
+
+        let http_server = self.http_server.clone();
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let mut parts = Box::pin(http_server.execute_stream(&req).enumerate());
+            while let Some((seq, part)) = parts.next().await {
+                let chunk = HttpResponseChunk {
+                    tx_id,
+                    seq: seq as u64,
+                    part,
+                };
+                let sent = match utils::http_response_chunk_message(&chunk) {
+                    Ok(msg) => swarm
+                        .send_report_message(&ctx, msg)
+                        .await
+                        .map_err(Error::SendMessage),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sent {
+                    tracing::error!("send http response chunk of {} failed: {}", tx_id, e);
+                    return;
+                }
+            }
+        });
+        Ok(vec![])
+    }
+
+    async fn handle_chunk_data(&self, data: &[u8]) -> Result<(uuid::Uuid, Option<Bytes>)> {
         let chunk_item = Chunk::from_bincode(data).map_err(|_| Error::DecodeError)?;
+        let id = chunk_item.meta.id;
         let mut chunk_list = self.chunk_list.lock().await;
         let data = chunk_list.handle(chunk_item);
-        Ok(data)
+        Ok((id, data))
     }
 
     /// Get service names from http_server config for storage register.
@@ -106,27 +241,30 @@ impl MessageCallback for Backend {
         let msg = msg.0.clone();
 
         let (left, msg) = array_refs![&msg, 4; ..;];
-        let (&[flag], _) = array_refs![left, 1, 3];
+        let (&[flag, compressed], _) = array_refs![left, 2, 2];
 
-        let msg = if flag == 1 {
+        // The tx_id of a chunked message is the id of its chunks.
+        let (tx_id, data) = if flag == 1 {
             let data = self.handle_chunk_data(msg).await;
             if let Err(e) = data {
                 tracing::error!("handle_chunk_data failed: {}", e);
                 return vec![];
             }
-            let data = data.unwrap();
+            let (id, data) = data.unwrap();
             if let Some(data) = data {
-                BackendMessage::try_from(data.to_vec().as_ref())
+                (id, data)
             } else {
                 return vec![];
             }
         } else if flag == 0 {
-            BackendMessage::try_from(msg)
+            (ctx.tx_id, Bytes::copy_from_slice(msg))
         } else {
             tracing::warn!("invalid custom_message flag: {}", flag);
             return vec![];
         };
 
+        let msg = decompress_message(compressed, data, self.max_message_bytes)
+            .and_then(|data| BackendMessage::try_from(data.as_ref()));
         if let Err(e) = msg {
             tracing::error!("decode custom_message failed: {}", e);
             return vec![];
@@ -138,28 +276,138 @@ impl MessageCallback for Backend {
             MessageType::SimpleText => self.text_endpoint.handle_message(ctx, &msg).await,
             MessageType::HttpRequest => self.http_server.handle_message(ctx, &msg).await,
             MessageType::Extension => self.extension_endpoint.handle_message(ctx, &msg).await,
-            _ => {
-                tracing::debug!(
-                    "custom_message handle unsupported, tag: {:?}",
-                    msg.message_type
-                );
-                Ok(vec![])
+            MessageType::HttpStreamRequest => {
+                self.handle_http_stream_request(ctx, tx_id, &msg).await
             }
+            MessageType::Ack | MessageType::HttpResponseChunk => Ok(vec![]),
+            _ => self.handle_custom_message(ctx, &msg).await,
         };
-        if let Err(e) = self.sender.send(msg) {
+        let ack = if matches!(
+            msg.message_type.into(),
+            MessageType::Ack | MessageType::HttpResponseChunk
+        ) {
+            None
+        } else {
+            utils::send_ack_report_message(ctx, tx_id)
+                .await
+                .map_err(|e| tracing::error!("build ack message failed: {}", e))
+                .ok()
+        };
+
+        if let Err(e) = self.sender.send((ctx.relay.origin_sender(), msg).into()) {
             tracing::error!("broadcast backend_message failed, {}", e);
         }
 
-        match result {
+        let mut events = match result {
             Ok(v) => v,
             Err(e) => {
                 tracing::error!("handle custom_message failed: {}", e);
                 vec![]
             }
-        }
+        };
+        events.extend(ack);
+        events
     }
 
     async fn builtin_message(&self, _ctx: &MessagePayload<Message>) -> Vec<MessageHandlerEvent> {
         vec![]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::consts::COMPRESS_LEVEL;
+    use crate::prelude::rings_core::consts::TRANSPORT_MAX_SIZE;
+    use crate::prelude::rings_core::consts::TRANSPORT_MTU;
+    use crate::prelude::rings_core::dht::Did;
+    use crate::prelude::rings_core::ecc::SecretKey;
+    use crate::prelude::rings_core::message::encode_data_gzip;
+    use crate::processor::pack_custom_message;
+
+    struct CountEndpoint(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl MessageEndpoint for CountEndpoint {
+        async fn handle_message(
+            &self,
+            _ctx: &MessagePayload<Message>,
+            _data: &BackendMessage,
+        ) -> Result<Vec<MessageHandlerEvent>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_custom_message_by_name() {
+        let (sender, _receiver) = tokio::sync::broadcast::channel(16);
+        let count = Arc::new(AtomicUsize::new(0));
+        let backend = Backend::new(BackendConfig::default(), sender)
+            .await
+            .unwrap()
+            .with_endpoint("chat", CountEndpoint(count.clone()));
+        backend.message_types().register(1000, "chat").unwrap();
+        backend.message_types().register(1001, "game").unwrap();
+
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let did = Did::from(SecretKey::random().address());
+        for (message_type, expected) in [(1000, 1), (1001, 1), (1002, 1), (1000, 2)] {
+            let mut data = vec![0u8; 4];
+            data.extend_from_slice(&Vec::from(BackendMessage::from((
+                message_type,
+                "hello".as_bytes(),
+            ))));
+            let msg = CustomMessage(data);
+            let ctx = MessagePayload::new_send(Message::CustomMessage(msg.clone()), &sm, did, did)
+                .unwrap();
+            backend.custom_message(&ctx, &msg).await;
+            assert_eq!(count.load(Ordering::SeqCst), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_compressed_chunked_message() {
+        let (sender, _receiver) = tokio::sync::broadcast::channel(16);
+        let count = Arc::new(AtomicUsize::new(0));
+        let backend = Backend::new(BackendConfig::default(), sender)
+            .await
+            .unwrap()
+            .with_endpoint("chat", CountEndpoint(count.clone()));
+        backend.message_types().register(1000, "chat").unwrap();
+
+        // A compressible message inflates beyond the max size of a transport message.
+        let text = (0..200000)
+            .map(|i| format!(r#"{{"seq": {}}}"#, i))
+            .collect::<String>();
+        let data = Vec::from(BackendMessage::from((1000, text.as_bytes())));
+        assert!(data.len() > TRANSPORT_MAX_SIZE);
+        let compressed = encode_data_gzip(&Bytes::from(data), COMPRESS_LEVEL).unwrap();
+        let (_, packed) = pack_custom_message(&compressed, TRANSPORT_MTU / 16, true).unwrap();
+        assert!(packed.len() > 1);
+
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let did = Did::from(SecretKey::random().address());
+        for data in packed.iter() {
+            let msg = CustomMessage(data.clone());
+            let ctx = MessagePayload::new_send(Message::CustomMessage(msg.clone()), &sm, did, did)
+                .unwrap();
+            backend.custom_message(&ctx, &msg).await;
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // A message inflating beyond the max message size of backend is dropped.
+        let backend = backend.with_max_message_bytes(TRANSPORT_MAX_SIZE);
+        let (_, packed) = pack_custom_message(&compressed, TRANSPORT_MTU / 16, true).unwrap();
+        for data in packed.iter() {
+            let msg = CustomMessage(data.clone());
+            let ctx = MessagePayload::new_send(Message::CustomMessage(msg.clone()), &sm, did, did)
+                .unwrap();
+            backend.custom_message(&ctx, &msg).await;
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,8 +1,12 @@
 #![warn(missing_docs)]
 //! utils of service
+use crate::backend::types::BackendMessage;
+use crate::backend::types::HttpResponseChunk;
+use crate::backend::types::MessageType;
 use crate::error::Error;
 use crate::error::Result;
 use crate::prelude::*;
+use crate::processor::ack_message;
 
 /// send chunk report message
 pub async fn send_chunk_report_message(
@@ -19,3 +23,26 @@ pub async fn send_chunk_report_message(
         Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)?,
     ))
 }
+
+/// send ack report message of a received message
+pub async fn send_ack_report_message(
+    ctx: &MessagePayload<Message>,
+    tx_id: uuid::Uuid,
+) -> Result<MessageHandlerEvent> {
+    Ok(MessageHandlerEvent::SendReportMessage(
+        ctx.clone(),
+        ack_message(tx_id)?,
+    ))
+}
+
+/// build custom message of a chunk of streaming http response
+pub fn http_response_chunk_message(chunk: &HttpResponseChunk) -> Result<Message> {
+    let msg: Vec<u8> = BackendMessage::try_from((MessageType::HttpResponseChunk, chunk))?.into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(msg.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&msg);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
@@ -1,6 +1,8 @@
 #![warn(missing_docs)]
 //! Backend Message Types.
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::sync::RwLock;
 
 use bytes::Bytes;
 use serde::Deserialize;
@@ -8,6 +10,7 @@ use serde::Serialize;
 
 use crate::error::Error;
 use crate::error::Result;
+use crate::prelude::rings_core::dht::Did;
 use crate::prelude::*;
 
 /// Enum MessageType of BackendMessage.
@@ -25,6 +28,24 @@ pub enum MessageType {
     HttpResponse,
     /// extension
     Extension,
+    /// delivery receipt, data is the tx_id of received message
+    Ack,
+    /// http request, whose response is sent back as a sequence of `HttpResponseChunk`
+    HttpStreamRequest,
+    /// a chunk of streaming http response
+    HttpResponseChunk,
+    /// message types supported by sender, sent once a peer is connected
+    Capabilities,
+    /// liveness probe, data is the id of ping, which is echoed back by `Pong`
+    Ping,
+    /// reply of `Ping` with the same data
+    Pong,
+    /// request of node info, data is the id of request, which is replied by `NodeInfoResponse`
+    NodeInfoRequest,
+    /// reply of `NodeInfoRequest`, data is the id of request and node info of sender
+    NodeInfoResponse,
+    /// renewed session of sender, which replaces its previous session on receivers
+    SessionRefresh,
 }
 
 impl From<&[u8; 2]> for MessageType {
@@ -41,6 +62,15 @@ impl From<u16> for MessageType {
             3 => MessageType::HttpRequest,
             4 => MessageType::HttpResponse,
             5 => MessageType::Extension,
+            6 => MessageType::Ack,
+            7 => MessageType::HttpStreamRequest,
+            8 => MessageType::HttpResponseChunk,
+            9 => MessageType::Capabilities,
+            10 => MessageType::Ping,
+            11 => MessageType::Pong,
+            12 => MessageType::NodeInfoRequest,
+            13 => MessageType::NodeInfoResponse,
+            14 => MessageType::SessionRefresh,
             _ => MessageType::Unknown,
         }
     }
@@ -55,10 +85,97 @@ impl From<MessageType> for u16 {
             MessageType::HttpRequest => 3,
             MessageType::HttpResponse => 4,
             MessageType::Extension => 5,
+            MessageType::Ack => 6,
+            MessageType::HttpStreamRequest => 7,
+            MessageType::HttpResponseChunk => 8,
+            MessageType::Capabilities => 9,
+            MessageType::Ping => 10,
+            MessageType::Pong => 11,
+            MessageType::NodeInfoRequest => 12,
+            MessageType::NodeInfoResponse => 13,
+            MessageType::SessionRefresh => 14,
         }
     }
 }
 
+impl MessageType {
+    /// The largest message type id reserved for built-in [MessageType].
+    /// Ids from 0 to it are kept for current and future built-in types.
+    pub const RESERVED_MAX: u16 = 255;
+
+    /// Check if a message type id is reserved for built-in [MessageType], including `Unknown`.
+    /// Custom message types of applications should not use these ids.
+    pub fn is_reserved(id: u16) -> bool {
+        id <= Self::RESERVED_MAX
+    }
+
+    /// Ids of built-in [MessageType] except `Unknown`.
+    pub fn builtin_ids() -> impl Iterator<Item = u16> {
+        (1..=Self::RESERVED_MAX).filter(|id| !matches!(Self::from(*id), Self::Unknown))
+    }
+}
+
+/// Registry of custom message types, which names message type ids used by applications.
+/// It can be shared with a `MessageCallback` to dispatch [BackendMessage] by name.
+#[derive(Debug, Default)]
+pub struct MessageTypeRegistry {
+    types: RwLock<HashMap<u16, String>>,
+}
+
+impl MessageTypeRegistry {
+    /// Register a custom message type with its id and name.
+    /// Ids reserved by built-in [MessageType] and registered ids or names are rejected.
+    pub fn register(&self, id: u16, name: &str) -> Result<()> {
+        if MessageType::is_reserved(id) {
+            return Err(Error::ReservedMessageType(id));
+        }
+        let mut types = self.types.write().map_err(|_| Error::Lock)?;
+        if types.contains_key(&id) || types.values().any(|n| n == name) {
+            return Err(Error::DuplicatedMessageType(id, name.to_string()));
+        }
+        types.insert(id, name.to_string());
+        Ok(())
+    }
+
+    /// Get the name of a registered message type id.
+    pub fn name(&self, id: u16) -> Option<String> {
+        self.types.read().ok()?.get(&id).cloned()
+    }
+
+    /// Get the id of a registered message type name.
+    pub fn id(&self, name: &str) -> Option<u16> {
+        self.types
+            .read()
+            .ok()?
+            .iter()
+            .find_map(|(id, n)| (n == name).then_some(*id))
+    }
+
+    /// Get the capabilities of node, including built-in types and registered custom types.
+    pub fn capabilities(&self) -> Capabilities {
+        let mut message_types = MessageType::builtin_ids().collect::<BTreeSet<_>>();
+        if let Ok(types) = self.types.read() {
+            message_types.extend(types.keys());
+        }
+        Capabilities { message_types }
+    }
+}
+
+/// Capabilities, data of [MessageType::Capabilities].
+/// - `message_types`: ids of message types the sender understands.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Capabilities {
+    /// supported message type ids
+    pub message_types: BTreeSet<u16>,
+}
+
+impl Capabilities {
+    /// Check if a message type id is supported.
+    pub fn supports(&self, message_type: u16) -> bool {
+        self.message_types.contains(&message_type)
+    }
+}
+
 /// BackendMessage struct for CustomMessage.
 /// A backend message body's length at least is 32bytes;
 /// - `message_type`: `[u8;2]`
@@ -88,6 +205,21 @@ impl BackendMessage {
     }
 }
 
+/// BackendMessage received from remote peer, which is broadcasted to local subscribers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingMessage {
+    /// did of sender
+    pub from: Did,
+    /// received message
+    pub msg: BackendMessage,
+}
+
+impl From<(Did, BackendMessage)> for IncomingMessage {
+    fn from((from, msg): (Did, BackendMessage)) -> Self {
+        Self { from, msg }
+    }
+}
+
 impl From<(u16, &[u8])> for BackendMessage {
     fn from((message_type, data): (u16, &[u8])) -> Self {
         Self::new(message_type, [0u8; 30], data)
@@ -174,3 +306,43 @@ pub struct HttpResponse {
     /// body: optional
     pub body: Option<Bytes>,
 }
+
+/// A part of streaming http response.
+/// The parts of a response are `Head`, any number of `Body`, and a final `End` or `Error`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum HttpResponsePart {
+    /// status and headers of response
+    Head {
+        /// status
+        status: u16,
+        /// headers
+        headers: HashMap<String, String>,
+    },
+    /// a piece of body
+    Body(Bytes),
+    /// final marker of a complete response
+    End,
+    /// final marker of a failed response, such as upstream closed mid-stream
+    Error(String),
+}
+
+impl HttpResponsePart {
+    /// Check if it's the last part of a response.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::End | Self::Error(_))
+    }
+}
+
+/// HttpResponseChunk, data of [MessageType::HttpResponseChunk].
+/// - `tx_id`: tx_id of the streaming http request.
+/// - `seq`: sequence number of the part in response, starting from 0.
+/// - `part`: a part of response.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct HttpResponseChunk {
+    /// tx_id of request
+    pub tx_id: uuid::Uuid,
+    /// sequence number
+    pub seq: u64,
+    /// part of response
+    pub part: HttpResponsePart,
+}
@@ -14,6 +14,7 @@ use crate::backend::types::BackendMessage;
 use crate::backend::types::HttpResponse;
 use crate::backend::types::MessageType;
 use crate::consts::BACKEND_MTU;
+use crate::consts::DEFAULT_MAX_MESSAGE_BYTES;
 use crate::error;
 use crate::jsonrpc::build_handler;
 use crate::jsonrpc::handler::browser::MethodHandler;
@@ -37,7 +38,6 @@ use crate::prelude::rings_core::message::MessageHandlerEvent;
 use crate::prelude::rings_core::message::MessagePayload;
 use crate::prelude::rings_core::prelude::uuid::Uuid;
 use crate::prelude::rings_core::prelude::vnode;
-use crate::prelude::rings_core::prelude::vnode::VirtualNode;
 use crate::prelude::rings_core::prelude::web3::ethabi::Token;
 use crate::prelude::rings_core::storage::PersistenceStorage;
 use crate::prelude::rings_core::transports::manager::TransportHandshake;
@@ -45,7 +45,6 @@ use crate::prelude::rings_core::transports::manager::TransportManager;
 use crate::prelude::rings_core::types::ice_transport::IceTransportInterface;
 use crate::prelude::rings_core::types::ice_transport::IceTrickleScheme;
 use crate::prelude::rings_core::utils::from_rtc_ice_connection_state;
-use crate::prelude::rings_core::utils::js_utils;
 use crate::prelude::rings_core::utils::js_value;
 use crate::prelude::wasm_bindgen;
 use crate::prelude::wasm_bindgen::prelude::*;
@@ -55,6 +54,7 @@ use crate::prelude::wasm_export;
 use crate::prelude::web3::contract::tokens::Tokenizable;
 use crate::prelude::web_sys::RtcIceConnectionState;
 use crate::prelude::CallbackFn;
+use crate::processor;
 use crate::processor::Processor;
 use crate::processor::ProcessorBuilder;
 
@@ -204,7 +204,7 @@ impl Client {
         let p = self.processor.clone();
         future_to_promise(async move {
             let transport = p
-                .connect_peer_via_http(remote_url.as_str())
+                .connect_peer_via_http(remote_url.as_str(), None)
                 .await
                 .map_err(JsError::from)?
                 .transport;
@@ -223,7 +223,7 @@ impl Client {
         future_to_promise(async move {
             let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
             let peer = p
-                .connect_with_did(did, false)
+                .connect_with_did(did, false, None)
                 .await
                 .map_err(JsError::from)?;
             let state = peer.transport.ice_connection_state().await;
@@ -254,7 +254,10 @@ impl Client {
         let p = self.processor.clone();
         future_to_promise(async move {
             let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
-            let peer = p.connect_with_did(did, true).await.map_err(JsError::from)?;
+            let peer = p
+                .connect_with_did(did, true, None)
+                .await
+                .map_err(JsError::from)?;
             let state = peer.transport.ice_connection_state().await;
             Ok(JsValue::try_from(&Peer::from((
                 state,
@@ -268,7 +271,7 @@ impl Client {
     pub fn create_offer(&self) -> js_sys::Promise {
         let p = self.processor.clone();
         future_to_promise(async move {
-            let (_, offer_payload) = p.swarm.create_offer().await.map_err(JsError::from)?;
+            let (_, offer_payload) = p.create_offer().await.map_err(JsError::from)?;
             let s = serde_json::to_string(&offer_payload).map_err(JsError::from)?;
             Ok(s.into())
         })
@@ -279,11 +282,7 @@ impl Client {
         let p = self.processor.clone();
         future_to_promise(async move {
             let offer_payload = serde_json::from_str(&offer_payload).map_err(JsError::from)?;
-            let (_, answer_payload) = p
-                .swarm
-                .answer_offer(offer_payload)
-                .await
-                .map_err(JsError::from)?;
+            let (_, answer_payload) = p.answer_offer(offer_payload).await.map_err(JsError::from)?;
             let s = serde_json::to_string(&answer_payload).map_err(JsError::from)?;
             Ok(s.into())
         })
@@ -335,6 +334,24 @@ impl Client {
         })
     }
 
+    /// health of node, it's ready when listening and has a successor
+    pub fn health(&self) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let v = js_value::serialize(&p.health()).map_err(JsError::from)?;
+            Ok(v)
+        })
+    }
+
+    /// get metrics of node, such as latency of handshakes
+    pub fn metrics(&self) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let v = js_value::serialize(&p.metrics()).map_err(JsError::from)?;
+            Ok(v)
+        })
+    }
+
     /// disconnect a peer with web3 address
     pub fn disconnect(&self, address: String, addr_type: Option<AddressType>) -> js_sys::Promise {
         let p = self.processor.clone();
@@ -346,11 +363,63 @@ impl Client {
         })
     }
 
+    /// restart ice of the connection to a peer, to recover it from a network change
+    pub fn restart_ice(&self, address: String, addr_type: Option<AddressType>) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
+            p.restart_ice(did).await.map_err(JsError::from)?;
+
+            Ok(JsValue::from_str(did.to_string().as_str()))
+        })
+    }
+
+    pub fn add_sticky_peer(
+        &self,
+        address: String,
+        addr_type: Option<AddressType>,
+    ) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
+            Ok(JsValue::from_bool(p.add_sticky_peer(did)))
+        })
+    }
+
+    pub fn remove_sticky_peer(
+        &self,
+        address: String,
+        addr_type: Option<AddressType>,
+    ) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let did = get_did(address.as_str(), addr_type.unwrap_or(AddressType::DEFAULT))?;
+            Ok(JsValue::from_bool(p.remove_sticky_peer(did).await))
+        })
+    }
+
+    pub fn list_sticky_peers(&self) -> js_sys::Promise {
+        let p = self.processor.clone();
+        future_to_promise(async move {
+            let peers = p
+                .sticky_peers()
+                .into_iter()
+                .map(|(did, state)| js_value::serialize(&state.into_response(did)))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(JsError::from)?;
+            let mut js_array = js_sys::Array::new();
+            js_array.extend(peers);
+            Ok(js_array.into())
+        })
+    }
+
+    /// disconnect all peers, resolve a summary of closed peers and errors
     pub fn disconnect_all(&self) -> js_sys::Promise {
         let p = self.processor.clone();
         future_to_promise(async move {
-            p.disconnect_all().await;
-            Ok(JsValue::from_str("ok"))
+            let summary = p.disconnect_all().await;
+            let v = js_value::serialize(&summary).map_err(JsError::from)?;
+            Ok(v)
         })
     }
 
@@ -382,13 +451,32 @@ impl Client {
     pub fn send_message(&self, destination: String, msg: js_sys::Uint8Array) -> js_sys::Promise {
         let p = self.processor.clone();
         future_to_promise(async move {
-            p.send_message(destination.as_str(), &msg.to_vec())
+            p.send_message(destination.as_str(), &msg.to_vec(), None)
                 .await
                 .map_err(JsError::from)?;
             Ok(JsValue::from_bool(true))
         })
     }
 
+    /// cancel a chunked message being sent, return `cancelled`, `completed` or `unknown`
+    pub fn cancel_message(&self, tx_id: String) -> Result<JsValue, JsError> {
+        let tx_id = Uuid::from_str(&tx_id).map_err(|_| JsError::new("invalid tx_id"))?;
+        let status = self.processor.cancel_send(tx_id);
+        Ok(js_value::serialize(&status).map_err(JsError::from)?)
+    }
+
+    /// list tx_id of messages being sent
+    pub fn list_sending_messages(&self) -> js_sys::Array {
+        let mut js_array = js_sys::Array::new();
+        js_array.extend(
+            self.processor
+                .sending_messages()
+                .into_iter()
+                .map(|x| JsValue::from_str(x.to_string().as_str())),
+        );
+        js_array
+    }
+
     /// get peer by address
     pub fn get_peer(&self, address: String, addr_type: Option<AddressType>) -> js_sys::Promise {
         let p = self.processor.clone();
@@ -464,7 +552,7 @@ impl Client {
             if peer.transport.is_connected().await {
                 return Ok(JsValue::null());
             }
-            if let Err(e) = peer.transport.wait_for_data_channel_open().await {
+            if let Err(e) = p.wait_for_data_channel_open(&peer.transport).await {
                 log::warn!("wait_for_data_channel failed: {}", e);
             }
             //.map_err(JsError::from)?;
@@ -620,32 +708,27 @@ impl Client {
         })
     }
 
+    /// check if the delivery receipt of a sent message is received recently
+    /// - tx_id: tx_id returned by sending message
+    pub fn is_delivered(&self, tx_id: String) -> Result<bool, JsError> {
+        let tx_id = Uuid::from_str(&tx_id).map_err(|_| JsError::new("invalid tx_id"))?;
+        Ok(self.processor.is_delivered(tx_id))
+    }
+
     /// lookup service did on DHT by its name
     /// - name: The name of service
     pub fn lookup_service(&self, name: String) -> js_sys::Promise {
         let p = self.processor.clone();
 
         future_to_promise(async move {
-            let rid = VirtualNode::gen_did(&name).map_err(JsError::from)?;
-
-            tracing::debug!("browser lookup_service storage_fetch: {}", rid);
-            p.storage_fetch(rid).await.map_err(JsError::from)?;
-            tracing::debug!("browser lookup_service finish storage_fetch: {}", rid);
-            js_utils::window_sleep(500).await?;
-            let result = p.storage_check_cache(rid).await;
-
-            if let Some(vnode) = result {
-                let dids = vnode
-                    .data
-                    .iter()
-                    .map(|v| v.decode())
-                    .filter_map(|v| v.ok())
-                    .map(|x: String| JsValue::from_str(x.as_str()))
-                    .collect::<js_sys::Array>();
-                Ok(JsValue::from(dids))
-            } else {
-                Ok(JsValue::from(js_sys::Array::new()))
-            }
+            let dids = p
+                .lookup_service(&name, None)
+                .await
+                .map_err(JsError::from)?
+                .into_iter()
+                .map(|r| JsValue::from_str(r.did.as_str()))
+                .collect::<js_sys::Array>();
+            Ok(JsValue::from(dids))
         })
     }
 }
@@ -679,9 +762,8 @@ impl MessageCallbackInstance {
     pub async fn handle_message_data(
         &self,
         relay: &MessagePayload<Message>,
-        data: &Bytes,
+        m: &BackendMessage,
     ) -> anyhow::Result<()> {
-        let m = BackendMessage::try_from(data.to_vec()).map_err(|e| anyhow::anyhow!("{}", e))?;
         match m.message_type.into() {
             MessageType::SimpleText => {
                 self.handle_simple_text_message(relay, m.data.as_slice())
@@ -732,7 +814,8 @@ impl MessageCallbackInstance {
             msg_content.len(),
         );
         let this = JsValue::null();
-        let msg_content = message::decode_gzip_data(&Bytes::from(data.to_vec())).unwrap();
+        let msg_content =
+            message::decode_gzip_data(&Bytes::from(data.to_vec())).map_err(|e| anyhow!("{}", e))?;
         log::info!(
             "message of {:?} received, after gunzip: {:?}",
             relay.tx_id,
@@ -755,7 +838,7 @@ impl MessageCallbackInstance {
         Ok(())
     }
 
-    fn handle_chunk_data(&self, data: &[u8]) -> anyhow::Result<Option<Bytes>> {
+    fn handle_chunk_data(&self, data: &[u8]) -> anyhow::Result<(Uuid, Option<Bytes>)> {
         let c_lock = self.chunk_list.try_lock();
         if c_lock.is_err() {
             return Err(anyhow!("lock chunklist failed"));
@@ -764,6 +847,7 @@ impl MessageCallbackInstance {
 
         let chunk_item =
             Chunk::from_bincode(data).map_err(|_| anyhow!("BincodeDeserialize failed"))?;
+        let id = chunk_item.meta.id;
 
         log::debug!(
             "before handle chunk, chunk list len: {}",
@@ -780,7 +864,7 @@ impl MessageCallbackInstance {
             chunk_list.as_vec().len()
         );
 
-        Ok(data)
+        Ok((id, data))
     }
 }
 
@@ -796,32 +880,61 @@ impl MessageCallback for MessageCallbackInstance {
         }
 
         let (left, right) = array_refs![&msg.0, 4; ..;];
-        let (&[tag], _) = array_refs![left, 1, 3];
+        let (&[tag, compressed], _) = array_refs![left, 2, 2];
 
-        let data = if tag == 1 {
+        // The tx_id of a chunked message is the id of its chunks.
+        let (tx_id, data) = if tag == 1 {
             let data = self.handle_chunk_data(right);
             if let Err(e) = data {
                 log::error!("handle chunk data failed: {}", e);
                 return vec![];
             }
-            let data = data.unwrap();
+            let (id, data) = data.unwrap();
             log::debug!("chunk message of {:?} received", relay.tx_id);
             if let Some(data) = data {
-                data
+                (id, data)
             } else {
                 log::info!("chunk message of {:?} not complete", relay.tx_id);
                 return vec![];
             }
         } else if tag == 0 {
-            Bytes::from(right.to_vec())
+            (relay.tx_id, Bytes::from(right.to_vec()))
         } else {
             log::error!("invalid message tag: {}", tag);
             return vec![];
         };
-        if let Err(e) = self.handle_message_data(relay, &data).await {
+        // Browser clients receive messages up to the default max size of processor.
+        let data = match processor::decompress_message(compressed, data, DEFAULT_MAX_MESSAGE_BYTES)
+        {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("decompress message failed: {}", e);
+                return vec![];
+            }
+        };
+        let m = match BackendMessage::try_from(data.to_vec()) {
+            Ok(m) => m,
+            Err(e) => {
+                log::error!("decode message failed: {}", e);
+                return vec![];
+            }
+        };
+        if let Err(e) = self.handle_message_data(relay, &m).await {
             log::error!("handle http_server_msg failed, {}", e);
         }
-        vec![]
+        if matches!(
+            m.message_type.into(),
+            MessageType::Ack | MessageType::HttpResponseChunk
+        ) {
+            return vec![];
+        }
+        match processor::ack_message(tx_id) {
+            Ok(ack) => vec![MessageHandlerEvent::SendReportMessage(relay.clone(), ack)],
+            Err(e) => {
+                log::error!("build ack message failed: {}", e);
+                vec![]
+            }
+        }
     }
 
     async fn builtin_message(&self, relay: &MessagePayload<Message>) -> Vec<MessageHandlerEvent> {
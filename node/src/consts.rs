@@ -1,5 +1,88 @@
 use crate::prelude::rings_core::consts::*;
 
 pub const BACKEND_MTU: usize = TRANSPORT_MAX_SIZE - TRANSPORT_MTU;
+/// Default max size of chunk when processor splits a large message
+pub const DEFAULT_CHUNK_SIZE: usize = TRANSPORT_MTU;
+/// Max size of body in a chunk of streaming http response
+pub const HTTP_STREAM_CHUNK_SIZE: usize = TRANSPORT_MTU;
+/// Max count of out-of-order chunks buffered for a streaming http response
+pub const HTTP_STREAM_MAX_PENDING_CHUNKS: usize = 256;
+/// Max count of headers in a relayed http request
+pub const MAX_HTTP_REQUEST_HEADERS: usize = 64;
+/// Max total size of header names and values in a relayed http request
+pub const MAX_HTTP_REQUEST_HEADERS_SIZE: usize = 16 * 1024;
+/// Default max size of a custom message, a larger one is rejected when sending and dropped when receiving
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+/// Default threshold of message size, a larger message will be compressed when sending
+pub const DEFAULT_COMPRESS_THRESHOLD: usize = 1024;
+/// Count of recently sent messages to remember, cancelling them reports completed
+pub const COMPLETED_MESSAGES_CAPACITY: usize = 1024;
+/// Count of recently received delivery receipts to remember
+pub const DELIVERY_RECEIPTS_CAPACITY: usize = 1024;
+/// How long a received delivery receipt is remembered
+pub const DELIVERY_RECEIPT_TTL_MS: u128 = 300000;
+/// Gzip compression level of message
+pub const COMPRESS_LEVEL: u8 = 6;
+/// Default count of pings sent by `ping`
+pub const DEFAULT_PING_COUNT: usize = 4;
+/// Max count of pings sent by `ping`
+pub const MAX_PING_COUNT: usize = 100;
+/// Default time of waiting for the pong of each ping
+pub const DEFAULT_PING_TIMEOUT_MS: u64 = 5000;
+/// Default time of waiting for the reply of requesting node info of a remote node
+pub const DEFAULT_REMOTE_NODE_INFO_TIMEOUT_MS: u64 = 5000;
+/// Default max count of remote nodes requested when walking the ring
+pub const DEFAULT_CRAWL_MAX_HOPS: usize = 256;
+/// Interval of fetching a subscribed topic for new messages
+pub const TOPIC_POLL_INTERVAL_MS: u64 = 1000;
+/// Capacity of channel of messages of a subscribed topic
+pub const TOPIC_CHANNEL_SIZE: usize = 1024;
+/// Default timeout of connecting a peer, until the data channel is open
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 30000;
+/// Max count of senders tracked by rate limiter, idle senders are forgotten beyond it
+pub const RATE_LIMIT_MAX_BUCKETS: usize = 10000;
+/// Default time to live of a service registration
+pub const DEFAULT_SERVICE_TTL_MS: u64 = 60000;
+/// Default interval of checking registered services to renew
+pub const DEFAULT_SERVICE_HEARTBEAT_CHECK_INTERVAL_MS: u64 = 1000;
+/// Max count of providers in the index of service whose records are fetched at once,
+/// by a lookup or by pruning the index
+pub const SERVICE_INDEX_MAX_FETCHES: usize = 64;
+/// Redundant setting of the index of service, which is touched by every provider.
+/// Operations from remote nodes are routed to the holder of vid itself instead of its copies,
+/// so an index touched by many providers is only complete on that holder.
+pub const SERVICE_INDEX_REDUNDANT: u16 = 1;
+/// Max time of waiting for messages being sent when shutting down, the rest are cancelled
+pub const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_MS: u64 = 5000;
+/// Interval of checking a peer is routable after its data channel opens
+pub const CONNECT_READY_CHECK_INTERVAL_MS: u64 = 50;
+/// Interval of checking messages being sent when shutting down
+pub const SHUTDOWN_DRAIN_CHECK_INTERVAL_MS: u64 = 50;
+/// Capacity of channel of each subscriber of node events
+pub const NODE_EVENT_CHANNEL_SIZE: usize = 1024;
+/// Count of latest samples which percentiles of latency histogram are calculated from
+pub const LATENCY_HISTOGRAM_WINDOW: usize = 1024;
+/// Max time of a handshake being timed, a handshake not finished in time is forgotten
+pub const HANDSHAKE_PENDING_STALE_MS: u64 = 300000;
 /// Redundant setting of vnode data storage
 pub const DATA_REDUNDANT: u16 = 6;
+/// Default delay before the first retry of reconnecting a sticky peer
+pub const DEFAULT_RECONNECT_BASE_DELAY_MS: u64 = 1000;
+/// Default cap of delay between retries of reconnecting a sticky peer
+pub const DEFAULT_RECONNECT_MAX_DELAY_MS: u64 = 60000;
+/// Default interval of checking transports of sticky peers
+pub const DEFAULT_RECONNECT_CHECK_INTERVAL_MS: u64 = 1000;
+/// Max interval of checking idle transports, a shorter idle timeout is checked at its own interval
+pub const IDLE_CHECK_INTERVAL_MS: u64 = 5000;
+/// Interval of sampling stats of connections to score their quality
+pub const QUALITY_CHECK_INTERVAL_MS: u64 = 5000;
+/// Default timeout of querying state of a transport, a transport not responding in time is `Unknown`
+pub const DEFAULT_STATE_QUERY_TIMEOUT_MS: u64 = 1000;
+/// Max count of transports queried for states at once
+pub const STATE_QUERY_CONCURRENCY: usize = 16;
+/// Default time of waiting for a virtual node fetched from DHT
+pub const DEFAULT_STORAGE_GET_TIMEOUT_MS: u64 = 3000;
+/// Delay before the first check of a virtual node being fetched, it's doubled after each check
+pub const STORAGE_GET_BACKOFF_MIN_MS: u64 = 20;
+/// Cap of delay between checks of a virtual node being fetched
+pub const STORAGE_GET_BACKOFF_MAX_MS: u64 = 500;
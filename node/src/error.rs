@@ -16,6 +16,8 @@ pub enum Error {
     RemoteRpcError(String) = 100,
     #[error("Unknown rpc error.")]
     UnknownRpcError = 101,
+    #[error("Signaling over websocket failed: {0}.")]
+    WsSignalingError(String) = 102,
     #[error("Pending Transport error: {0}.")]
     PendingTransport(rings_core::error::Error) = 202,
     #[error("Transport not found.")]
@@ -58,6 +60,8 @@ pub enum Error {
     InternalError = 502,
     #[error("No Permission")]
     NoPermission = 504,
+    #[error("Session is not granted scope {0}")]
+    MissingScope(String) = 505,
     #[error("Connect error, {0}")]
     ConnectError(rings_core::error::Error) = 600,
     #[error("Send message error: {0}")]
@@ -66,6 +70,34 @@ pub enum Error {
     VNodeError(rings_core::error::Error) = 603,
     #[error("service register action error: {0}")]
     ServiceRegisterError(rings_core::error::Error) = 604,
+    #[error("leave dht error: {0}")]
+    LeaveError(rings_core::error::Error) = 605,
+    #[error("restart ice error: {0}")]
+    RestartIceError(rings_core::error::Error) = 606,
+    #[error("connect timeout after {0}ms")]
+    ConnectTimeout(u64) = 607,
+    #[error("message {0} is cancelled")]
+    SendCancelled(String) = 608,
+    #[error("node is shutting down")]
+    ShuttingDown = 609,
+    #[error("message type {1} is not supported by peer {0}")]
+    UnsupportedMessageType(String, u16) = 610,
+    #[error("send queue is full with {0} bytes buffered")]
+    QueueFull(usize) = 611,
+    #[error("no alive provider of service {0}")]
+    ServiceNotFound(String) = 612,
+    #[error("fetch vnode error: {0}")]
+    VNodeFetchError(String) = 613,
+    #[error("message of {0} bytes exceeds the max size {1}")]
+    MessageTooLarge(usize, usize) = 614,
+    #[error("node info of {0} is not replied in {1}ms")]
+    RemoteNodeInfoTimeout(String, u64) = 615,
+    #[error("no seed is remembered")]
+    NoSeed = 616,
+    #[error("refresh session error: {0}")]
+    RefreshSession(rings_core::error::Error) = 617,
+    #[error("vnode {0} is not resolved in {1}ms")]
+    VNodeNotResolved(String, u64) = 618,
     #[error("JsError: {0}")]
     JsError(String) = 700,
     #[error("Invalid message")]
@@ -86,12 +118,28 @@ pub enum Error {
     Storage(rings_core::error::Error) = 807,
     #[error("Swarm Error: {0}")]
     Swarm(rings_core::error::Error) = 808,
+    #[error("Invalid chunk size")]
+    InvalidChunkSize = 809,
+    #[error("Invalid ice servers: {0}")]
+    InvalidIceServer(rings_core::error::Error) = 810,
+    #[error("Message type {0} is reserved by built-in types")]
+    ReservedMessageType(u16) = 811,
+    #[error("Message type {0} or name {1} is registered")]
+    DuplicatedMessageType(u16, String) = 812,
+    #[error("Invalid seed, {0}")]
+    InvalidSeed(String) = 813,
+    #[error("Invalid stabilization config: {0}")]
+    InvalidStabilization(rings_core::error::Error) = 814,
     #[error("Create File Error: {0}")]
     CreateFileError(String) = 900,
     #[error("Open File Error: {0}")]
     OpenFileError(String) = 901,
     #[error("acquire lock failed")]
     Lock = 902,
+    #[error("Invalid keystore: {0}")]
+    InvalidKeystore(String) = 903,
+    #[error("Decrypt keystore failed, the password may be wrong")]
+    KeystoreDecryptError = 904,
     #[error("serde json error: {0}")]
     SerdeJsonError(#[from] serde_json::Error) = 1000,
     #[error("serde yaml error: {0}")]
@@ -138,6 +186,7 @@ impl From<crate::prelude::rings_rpc::error::Error> for Error {
             rings_rpc::error::Error::RpcError(v) => Error::RemoteRpcError(v.to_string()),
             rings_rpc::error::Error::InvalidSignature => Error::InvalidData,
             rings_rpc::error::Error::InvalidHeaders => Error::InvalidHeaders,
+            rings_rpc::error::Error::InvalidHttpRequest(v) => Error::HttpRequestError(v),
             _ => Error::UnknownRpcError,
         }
     }
@@ -0,0 +1,419 @@
+//! Lifecycle events of node.
+//!
+//! [EventBus] snapshots connected peers, their transport states and the position of node in ring,
+//! and emits a [NodeEvent] for each difference found. A snapshot is taken after the node handles
+//! a builtin message, or disconnects a peer by itself. Neighbors changed by a round of
+//! stabilization are reported to the bus as a [NeighborObserver].
+//! Subscribers get events by [Processor::events](crate::processor::Processor::events).
+#![warn(missing_docs)]
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::Weak;
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::backend::types::MessageTypeRegistry;
+use crate::consts::NODE_EVENT_CHANNEL_SIZE;
+use crate::message_limit::MessageSizeLimit;
+use crate::peer_capabilities::PeerCapabilities;
+use crate::peer_sessions::PeerSessions;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::dht::NeighborObserver;
+use crate::prelude::rings_core::dht::SuccessorReader;
+use crate::prelude::rings_core::message::CustomMessage;
+use crate::prelude::rings_core::message::Message;
+use crate::prelude::rings_core::message::MessageCallback;
+use crate::prelude::rings_core::message::MessageHandlerEvent;
+use crate::prelude::rings_core::message::MessagePayload;
+use crate::prelude::rings_core::transports::manager::TransportManager;
+use crate::prelude::rings_core::types::ice_transport::IceTransportInterface;
+use crate::prelude::rings_core::utils::from_rtc_ice_connection_state;
+use crate::prelude::CallbackFn;
+use crate::prelude::Swarm;
+use crate::processor::ack_of;
+use crate::processor::capabilities_message;
+use crate::processor::capabilities_of;
+use crate::processor::node_info_request_of;
+use crate::processor::node_info_response_message;
+use crate::processor::node_info_response_of;
+use crate::processor::ping_of;
+use crate::processor::pong_message;
+use crate::processor::pong_of;
+use crate::processor::reported_node_info;
+use crate::processor::session_refresh_of;
+use crate::processor::PendingNodeInfos;
+use crate::processor::PendingPings;
+use crate::receipts::DeliveryReceipts;
+
+/// Lifecycle event of node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// A transport of peer is registered.
+    PeerConnected(Did),
+    /// The transport of peer is removed.
+    PeerDisconnected(Did),
+    /// Successor list of node is changed, with the new list.
+    SuccessorChanged(Vec<Did>),
+    /// Predecessor of node is changed, with the new one.
+    PredecessorChanged(Option<Did>),
+    /// Ice connection state of the transport of peer is changed, `None` if it's removed.
+    TransportStateChanged(Did, Option<String>),
+    /// The transport of peer is closed by node since it's idle beyond the idle timeout.
+    PeerIdleClosed(Did),
+}
+
+#[derive(Default, PartialEq, Eq)]
+struct Snapshot {
+    transports: BTreeMap<Did, Option<String>>,
+    successors: Vec<Did>,
+    predecessor: Option<Did>,
+}
+
+impl Snapshot {
+    async fn take(swarm: &Swarm) -> Self {
+        let mut transports = BTreeMap::new();
+        for (did, transport) in swarm.get_transports() {
+            let state = transport
+                .ice_connection_state()
+                .await
+                .map(from_rtc_ice_connection_state);
+            transports.insert(did, state);
+        }
+        let dht = swarm.dht();
+        Self {
+            transports,
+            successors: dht.successors().list().unwrap_or_default(),
+            predecessor: dht.lock_predecessor().map(|p| *p).unwrap_or(None),
+        }
+    }
+
+    fn diff(&self, new: &Self) -> Vec<NodeEvent> {
+        let mut events = vec![];
+        for (did, state) in new.transports.iter() {
+            match self.transports.get(did) {
+                None => {
+                    events.push(NodeEvent::PeerConnected(*did));
+                    events.push(NodeEvent::TransportStateChanged(*did, state.clone()));
+                }
+                Some(old) if old != state => {
+                    events.push(NodeEvent::TransportStateChanged(*did, state.clone()));
+                }
+                _ => {}
+            }
+        }
+        for did in self.transports.keys() {
+            if !new.transports.contains_key(did) {
+                events.push(NodeEvent::PeerDisconnected(*did));
+                events.push(NodeEvent::TransportStateChanged(*did, None));
+            }
+        }
+        if self.successors != new.successors {
+            events.push(NodeEvent::SuccessorChanged(new.successors.clone()));
+        }
+        if self.predecessor != new.predecessor {
+            events.push(NodeEvent::PredecessorChanged(new.predecessor));
+        }
+        events
+    }
+}
+
+/// Emit [NodeEvent] to subscribers when state of swarm changes.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<mpsc::Sender<NodeEvent>>>,
+    snapshot: futures::lock::Mutex<Snapshot>,
+}
+
+impl EventBus {
+    /// Subscribe events, the subscription is dropped with the receiver.
+    /// Events are dropped for a subscriber which doesn't keep up.
+    pub fn subscribe(&self) -> mpsc::Receiver<NodeEvent> {
+        let (sender, receiver) = mpsc::channel(NODE_EVENT_CHANNEL_SIZE);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    pub(crate) fn emit(&self, event: NodeEvent) {
+        let Ok(mut subscribers) = self.subscribers.lock() else {
+            return;
+        };
+        subscribers.retain_mut(|s| match s.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(e) if e.is_full() => {
+                tracing::warn!("drop node event {:?}, subscriber is full", event);
+                true
+            }
+            Err(_) => false,
+        });
+    }
+
+    /// Snapshot state of swarm, and emit events of changes since last snapshot.
+    /// Return the emitted events.
+    pub async fn observe(&self, swarm: &Swarm) -> Vec<NodeEvent> {
+        let mut snapshot = self.snapshot.lock().await;
+        let new = Snapshot::take(swarm).await;
+        if *snapshot == new {
+            return vec![];
+        }
+        let events = snapshot.diff(&new);
+        *snapshot = new;
+        drop(snapshot);
+        for event in events.iter() {
+            self.emit(event.clone());
+        }
+        events
+    }
+
+    /// Emit events of changes of successors and predecessor since last snapshot,
+    /// the snapshot of transports is kept. Return the emitted events.
+    pub async fn observe_neighbors(
+        &self,
+        successors: Vec<Did>,
+        predecessor: Option<Did>,
+    ) -> Vec<NodeEvent> {
+        let mut snapshot = self.snapshot.lock().await;
+        let mut events = vec![];
+        if snapshot.successors != successors {
+            events.push(NodeEvent::SuccessorChanged(successors.clone()));
+            snapshot.successors = successors;
+        }
+        if snapshot.predecessor != predecessor {
+            events.push(NodeEvent::PredecessorChanged(predecessor));
+            snapshot.predecessor = predecessor;
+        }
+        drop(snapshot);
+        for event in events.iter() {
+            self.emit(event.clone());
+        }
+        events
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl NeighborObserver for EventBus {
+    async fn neighbors_changed(&self, successors: Vec<Did>, predecessor: Option<Did>) {
+        self.observe_neighbors(successors, predecessor).await;
+    }
+}
+
+/// [MessageCallback] to observe the swarm after each builtin message, then call the inner callback.
+/// Delivery receipts are recorded before the inner callback handles them.
+/// Capabilities of node are advertised to each newly connected peer, and advertisements
+/// received are recorded instead of passed to the inner callback.
+/// Pings are replied with pongs, and pongs resolve the pings waiting for them.
+/// Requests of node info are replied likewise.
+/// Sessions refreshed by peers are validated and recorded, then custom messages of a peer
+/// signed by its replaced session are dropped.
+/// Custom messages over the size limit are dropped before all of them.
+/// The swarm is bound after it's built, it's held weakly since the swarm owns the callback.
+pub(crate) struct EventCallback {
+    inner: Option<CallbackFn>,
+    bus: Arc<EventBus>,
+    receipts: Arc<DeliveryReceipts>,
+    capabilities: Arc<PeerCapabilities>,
+    sessions: Arc<PeerSessions>,
+    pings: Arc<PendingPings>,
+    node_infos: Arc<PendingNodeInfos>,
+    message_types: Arc<MessageTypeRegistry>,
+    size_limit: MessageSizeLimit,
+    swarm: Arc<OnceLock<Weak<Swarm>>>,
+}
+
+impl EventCallback {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        inner: Option<CallbackFn>,
+        bus: Arc<EventBus>,
+        receipts: Arc<DeliveryReceipts>,
+        capabilities: Arc<PeerCapabilities>,
+        sessions: Arc<PeerSessions>,
+        pings: Arc<PendingPings>,
+        node_infos: Arc<PendingNodeInfos>,
+        message_types: Arc<MessageTypeRegistry>,
+        size_limit: MessageSizeLimit,
+        swarm: Arc<OnceLock<Weak<Swarm>>>,
+    ) -> Self {
+        Self {
+            inner,
+            bus,
+            receipts,
+            capabilities,
+            sessions,
+            pings,
+            node_infos,
+            message_types,
+            size_limit,
+            swarm,
+        }
+    }
+
+    /// Forget the capabilities of a newly connected peer, and advertise ours to it.
+    fn advertise_capabilities(&self, did: Did) -> Option<MessageHandlerEvent> {
+        self.capabilities.remove(did);
+        match capabilities_message(&self.message_types.capabilities()) {
+            Ok(msg) => Some(MessageHandlerEvent::SendDirectMessage(msg, did)),
+            Err(e) => {
+                tracing::warn!("build capabilities message failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl MessageCallback for EventCallback {
+    async fn custom_message(
+        &self,
+        ctx: &MessagePayload<Message>,
+        msg: &CustomMessage,
+    ) -> Vec<MessageHandlerEvent> {
+        if !self.size_limit.admit(msg) {
+            tracing::warn!(
+                "drop oversize custom message {} from {}",
+                ctx.tx_id,
+                ctx.relay.origin_sender()
+            );
+            return vec![];
+        }
+        let origin = ctx.relay.origin_sender();
+        let session = &ctx.origin_verification.session;
+        if !self.sessions.admit(origin, session) {
+            tracing::warn!(
+                "drop custom message {} from {} signed by a refreshed session",
+                ctx.tx_id,
+                origin
+            );
+            return vec![];
+        }
+        if let Some(refreshed) = session_refresh_of(msg) {
+            if let Err(e) = self.sessions.refresh(origin, session, refreshed) {
+                tracing::warn!("reject session refreshed by {}: {}", origin, e);
+            }
+            return vec![];
+        }
+        if let Some(tx_id) = ack_of(msg) {
+            self.receipts.record(tx_id, origin);
+        }
+        if let Some(capabilities) = capabilities_of(msg) {
+            self.capabilities
+                .set(ctx.relay.origin_sender(), capabilities);
+            return vec![];
+        }
+        if let Some(id) = ping_of(msg) {
+            return match pong_message(id) {
+                Ok(pong) => vec![MessageHandlerEvent::SendReportMessage(ctx.clone(), pong)],
+                Err(e) => {
+                    tracing::warn!("build pong message failed: {}", e);
+                    vec![]
+                }
+            };
+        }
+        if let Some(id) = pong_of(msg) {
+            self.pings.resolve(id, ());
+            return vec![];
+        }
+        if let Some(id) = node_info_request_of(msg) {
+            let Some(swarm) = self.swarm.get().and_then(|s| s.upgrade()) else {
+                return vec![];
+            };
+            let info = reported_node_info(&swarm).await;
+            return match node_info_response_message(id, &info) {
+                Ok(reply) => vec![MessageHandlerEvent::SendReportMessage(ctx.clone(), reply)],
+                Err(e) => {
+                    tracing::warn!("build node info message failed: {}", e);
+                    vec![]
+                }
+            };
+        }
+        if let Some((id, info)) = node_info_response_of(msg) {
+            self.node_infos.resolve(id, info);
+            return vec![];
+        }
+        match self.inner {
+            Some(ref cb) => cb.custom_message(ctx, msg).await,
+            None => {
+                tracing::warn!("No callback registered, skip custom message {}", ctx.tx_id);
+                vec![]
+            }
+        }
+    }
+
+    async fn builtin_message(&self, ctx: &MessagePayload<Message>) -> Vec<MessageHandlerEvent> {
+        let mut events = vec![];
+        if let Some(swarm) = self.swarm.get().and_then(|s| s.upgrade()) {
+            for event in self.bus.observe(&swarm).await {
+                if let NodeEvent::PeerConnected(did) = event {
+                    events.extend(self.advertise_capabilities(did));
+                }
+            }
+        }
+        if let Some(ref cb) = self.inner {
+            events.extend(cb.builtin_message(ctx).await);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod tests {
+    use super::*;
+    use crate::prelude::rings_core::ecc::SecretKey;
+
+    #[test]
+    fn test_snapshot_diff() {
+        let did1 = Did::from(SecretKey::random().address());
+        let did2 = Did::from(SecretKey::random().address());
+        let old = Snapshot {
+            transports: BTreeMap::from([(did1, Some("connected".to_string()))]),
+            successors: vec![did1],
+            predecessor: None,
+        };
+        let new = Snapshot {
+            transports: BTreeMap::from([(did2, Some("checking".to_string()))]),
+            successors: vec![did1],
+            predecessor: Some(did1),
+        };
+        assert_eq!(old.diff(&new), vec![
+            NodeEvent::PeerConnected(did2),
+            NodeEvent::TransportStateChanged(did2, Some("checking".to_string())),
+            NodeEvent::PeerDisconnected(did1),
+            NodeEvent::TransportStateChanged(did1, None),
+            NodeEvent::PredecessorChanged(Some(did1)),
+        ]);
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_observe_neighbors() {
+        let did1 = Did::from(SecretKey::random().address());
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+
+        bus.neighbors_changed(vec![did1], None).await;
+        assert_eq!(
+            events.try_next().unwrap(),
+            Some(NodeEvent::SuccessorChanged(vec![did1]))
+        );
+        assert!(events.try_next().is_err());
+
+        // Unchanged neighbors emit nothing.
+        bus.neighbors_changed(vec![did1], None).await;
+        assert!(events.try_next().is_err());
+
+        assert_eq!(bus.observe_neighbors(vec![], Some(did1)).await, vec![
+            NodeEvent::SuccessorChanged(vec![]),
+            NodeEvent::PredecessorChanged(Some(did1)),
+        ]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Fetching of virtual nodes from DHT.
+//!
+//! A vnode held by a remote node is cached when the reply of holder arrives, after
+//! `storage_fetch` returns. [VNodeFetcher] coalesces concurrent fetches of the same vid
+//! into one network fetch, and waits for the fetched vnode, so that readers act on a copy
+//! from its holder instead of a missing or stale cache.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::sync::atomic::AtomicUsize;
+#[cfg(test)]
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use futures::future::FutureExt;
+use futures::future::Shared;
+use futures_timer::Delay;
+
+use crate::consts::DATA_REDUNDANT;
+use crate::consts::STORAGE_GET_BACKOFF_MAX_MS;
+use crate::consts::STORAGE_GET_BACKOFF_MIN_MS;
+use crate::error::Error;
+use crate::error::Result;
+use crate::prelude::rings_core::dht::ChordStorageCache;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::dht::SuccessorReader;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::vnode::VirtualNode;
+use crate::prelude::ChordStorageInterface;
+use crate::prelude::ChordStorageInterfaceCacheChecker;
+use crate::prelude::Swarm;
+
+/// Result of a fetch shared by concurrent callers, the error is stringified since it's not cloneable.
+type SharedFetch = Shared<oneshot::Receiver<std::result::Result<(), String>>>;
+
+/// Fetches of virtual nodes in flight, keyed by vid.
+/// Concurrent fetches of the same vid await one network fetch instead of starting their own.
+#[derive(Default)]
+struct InflightFetches {
+    inner: Mutex<HashMap<Did, SharedFetch>>,
+    /// count of network fetches started, checked by tests of coalescing
+    #[cfg(test)]
+    started: AtomicUsize,
+}
+
+/// Role of a caller in fetching a vid.
+enum InflightFetch<'a> {
+    /// No fetch of the vid is in flight, the caller should fetch it and finish the guard.
+    Leader(FetchGuard<'a>),
+    /// A fetch of the vid is in flight, the caller should await its result.
+    Follower(SharedFetch),
+}
+
+impl InflightFetches {
+    /// Join the fetch of `vid` in flight, or lead a new one if there is none.
+    fn join(&self, vid: Did) -> InflightFetch<'_> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(fetch) = inner.get(&vid) {
+            return InflightFetch::Follower(fetch.clone());
+        }
+        let (tx, rx) = oneshot::channel();
+        inner.insert(vid, rx.shared());
+        #[cfg(test)]
+        self.started.fetch_add(1, Ordering::SeqCst);
+        InflightFetch::Leader(FetchGuard {
+            fetches: self,
+            vid,
+            tx: Some(tx),
+        })
+    }
+
+    #[cfg(test)]
+    fn started(&self) -> usize {
+        self.started.load(Ordering::SeqCst)
+    }
+}
+
+/// Guard of a leading fetch, which is removed from fetches in flight when finished or dropped.
+/// Followers of a dropped guard get a cancelled result.
+struct FetchGuard<'a> {
+    fetches: &'a InflightFetches,
+    vid: Did,
+    tx: Option<oneshot::Sender<std::result::Result<(), String>>>,
+}
+
+impl FetchGuard<'_> {
+    /// Share `result` with followers. The fetch is removed before that,
+    /// so callers coming later start a new fetch.
+    fn finish(mut self, result: &Result<()>) {
+        self.fetches.inner.lock().unwrap().remove(&self.vid);
+        if let Some(tx) = self.tx.take() {
+            let shared = match result {
+                Ok(()) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            };
+            tx.send(shared).ok();
+        }
+    }
+}
+
+impl Drop for FetchGuard<'_> {
+    fn drop(&mut self) {
+        if self.tx.is_some() {
+            self.fetches.inner.lock().unwrap().remove(&self.vid);
+        }
+    }
+}
+
+/// Fetcher of virtual nodes, shared by processor and the loops reading DHT in background.
+pub struct VNodeFetcher {
+    swarm: Arc<Swarm>,
+    fetches: InflightFetches,
+}
+
+impl VNodeFetcher {
+    /// Create a fetcher of virtual nodes on DHT of `swarm`.
+    pub fn new(swarm: Arc<Swarm>) -> Self {
+        Self {
+            swarm,
+            fetches: InflightFetches::default(),
+        }
+    }
+
+    /// Get the swarm whose DHT is read by this fetcher.
+    pub fn swarm(&self) -> &Swarm {
+        &self.swarm
+    }
+
+    /// Fetch virtual node from DHT, the fetched one can be read from local cache.
+    /// Concurrent fetches of the same vid share one network fetch.
+    pub async fn fetch(&self, did: Did) -> Result<()> {
+        self.fetch_redundant::<DATA_REDUNDANT>(did).await
+    }
+
+    /// Fetch virtual node stored with `REDUNDANT` copies, see [VNodeFetcher::fetch].
+    pub async fn fetch_redundant<const REDUNDANT: u16>(&self, did: Did) -> Result<()> {
+        match self.fetches.join(did) {
+            InflightFetch::Leader(guard) => {
+                let result = self.fetch_vnode::<REDUNDANT>(did).await;
+                guard.finish(&result);
+                result
+            }
+            InflightFetch::Follower(fetch) => match fetch.await {
+                Ok(result) => result.map_err(Error::VNodeFetchError),
+                // The leading fetch is cancelled, fetch it alone.
+                Err(_) => self.fetch_vnode::<REDUNDANT>(did).await,
+            },
+        }
+    }
+
+    /// Fetch virtual node from DHT and wait for it, return `None` if it's not fetched within `timeout`.
+    /// The cached copy is dropped first, so the returned one is fetched from its holder by this call.
+    /// The cache is checked with exponential backoff, and it's fetched again on each miss.
+    /// A node without remote successor returns at once, its fetch is only from local storage.
+    pub async fn get(&self, did: Did, timeout: Duration) -> Result<Option<VirtualNode>> {
+        self.get_redundant::<DATA_REDUNDANT>(did, timeout).await
+    }
+
+    /// Get virtual node stored with `REDUNDANT` copies, see [VNodeFetcher::get].
+    pub async fn get_redundant<const REDUNDANT: u16>(
+        &self,
+        did: Did,
+        timeout: Duration,
+    ) -> Result<Option<VirtualNode>> {
+        let deadline = get_epoch_ms() + timeout.as_millis();
+        self.swarm.dht().local_cache_remove(did);
+        let mut backoff = STORAGE_GET_BACKOFF_MIN_MS;
+        loop {
+            self.fetch_redundant::<REDUNDANT>(did).await?;
+            if let Some(vnode) = self.swarm.storage_check_cache(did).await {
+                return Ok(Some(vnode));
+            }
+            let now = get_epoch_ms();
+            if now >= deadline || !self.has_remote_successor() {
+                return Ok(None);
+            }
+            Delay::new(Duration::from_millis(
+                (backoff as u128).min(deadline - now) as u64
+            ))
+            .await;
+            if let Some(vnode) = self.swarm.storage_check_cache(did).await {
+                return Ok(Some(vnode));
+            }
+            backoff = (backoff * 2).min(STORAGE_GET_BACKOFF_MAX_MS);
+        }
+    }
+
+    fn has_remote_successor(&self) -> bool {
+        let dht = self.swarm.dht();
+        dht.successors()
+            .list()
+            .map(|list| list.iter().any(|s| *s != dht.did))
+            .unwrap_or(false)
+    }
+
+    async fn fetch_vnode<const REDUNDANT: u16>(&self, did: Did) -> Result<()> {
+        <Swarm as ChordStorageInterface<REDUNDANT>>::storage_fetch(&self.swarm, did)
+            .await
+            .map_err(Error::VNodeError)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod test {
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_fetch_coalesced() {
+        let (p, path) = prepare_processor(None).await;
+        let fetcher = VNodeFetcher::new(p.swarm.clone());
+        let vid = VirtualNode::gen_did("test_fetch_coalesced").unwrap();
+
+        // A fetch of vid is in flight.
+        let InflightFetch::Leader(guard) = fetcher.fetches.join(vid) else {
+            panic!("expected no fetch in flight");
+        };
+        let fetches = futures::future::join_all((0..10).map(|_| fetcher.fetch(vid)));
+        let finish = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            guard.finish(&Ok(()));
+        };
+        let (results, _) = futures::join!(fetches, finish);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(fetcher.fetches.started(), 1);
+
+        // A fetch after the finished one starts a new network fetch.
+        fetcher.fetch(vid).await.unwrap();
+        assert_eq!(fetcher.fetches.started(), 2);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_drops_stale_cache() {
+        let (p, path) = prepare_processor(None).await;
+        let fetcher = VNodeFetcher::new(p.swarm.clone());
+        let v: VirtualNode = "test_get_drops_stale_cache".to_string().try_into().unwrap();
+        let timeout = Duration::from_secs(1);
+
+        // A cached copy which is not held by any node is not returned.
+        p.swarm.dht().local_cache_set(v.clone());
+        assert_eq!(fetcher.get(v.did, timeout).await.unwrap(), None);
+        assert_eq!(p.storage_check_cache(v.did).await, None);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+}
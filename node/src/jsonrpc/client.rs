@@ -0,0 +1,644 @@
+#![warn(missing_docs)]
+//! A typed client of jsonrpc methods, which builds params and decodes responses.
+//! It calls a remote node over http, or a [Processor] in the same process without serving jsonrpc.
+//!
+//! Sample:
+//! let client = RpcClientBuilder::http("http://localhost:50000").build();
+//! let peers = client.list_peers().await?;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use serde_json::Value;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::Mutex;
+
+use super::handler::methods;
+use super::handler::MethodFnBox;
+use super::server::RpcMeta;
+use crate::backend::types::IncomingMessage;
+use crate::error::Error;
+use crate::error::Result;
+use crate::prelude::http;
+use crate::prelude::jsonrpc_client::SimpleClient;
+use crate::prelude::jsonrpc_core::Params;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::session::SessionManager;
+use crate::prelude::rings_rpc::method::Method;
+use crate::prelude::rings_rpc::response;
+use crate::prelude::rings_rpc::types;
+use crate::prelude::rings_rpc::types::Timeout;
+use crate::processor::Processor;
+
+/// Transport of [RpcClient].
+enum Transport {
+    /// call remote jsonrpc server
+    Http(SimpleClient),
+    /// call handlers of methods with processor in process, the requests are authed
+    InProcess {
+        methods: HashMap<String, MethodFnBox>,
+        meta: RpcMeta,
+    },
+}
+
+/// Builder of [RpcClient].
+pub struct RpcClientBuilder {
+    endpoint: Option<String>,
+    session_manager: Option<SessionManager>,
+    processor: Option<Arc<Processor>>,
+    receiver: Option<Receiver<IncomingMessage>>,
+}
+
+impl RpcClientBuilder {
+    /// Call jsonrpc server of a remote node at `endpoint`.
+    pub fn http(endpoint: &str) -> Self {
+        Self {
+            endpoint: Some(endpoint.to_string()),
+            session_manager: None,
+            processor: None,
+            receiver: None,
+        }
+    }
+
+    /// Call the processor in process.
+    pub fn in_process(processor: Arc<Processor>) -> Self {
+        Self {
+            endpoint: None,
+            session_manager: None,
+            processor: Some(processor),
+            receiver: None,
+        }
+    }
+
+    /// Sign requests to remote node with the session manager, it's ignored in process.
+    pub fn session_manager(mut self, session_manager: SessionManager) -> Self {
+        self.session_manager = Some(session_manager);
+        self
+    }
+
+    /// Set the receiver of backend messages in process,
+    /// which is required by [RpcClient::wait_for_delivery].
+    pub fn receiver(mut self, receiver: Receiver<IncomingMessage>) -> Self {
+        self.receiver = Some(receiver);
+        self
+    }
+
+    /// Build the [RpcClient].
+    pub fn build(self) -> RpcClient {
+        let transport = match (self.processor, self.endpoint) {
+            (Some(processor), _) => {
+                let meta: RpcMeta = match self.receiver {
+                    Some(receiver) => (processor, Arc::new(Mutex::new(receiver)), true).into(),
+                    None => processor.into(),
+                };
+                Transport::InProcess {
+                    methods: methods()
+                        .into_iter()
+                        .map(|(method, handler)| (method.as_str().to_string(), handler))
+                        .collect(),
+                    meta,
+                }
+            }
+            (None, endpoint) => Transport::Http(SimpleClient::new(
+                endpoint.unwrap_or_default().as_str(),
+                self.session_manager,
+            )),
+        };
+        RpcClient { transport }
+    }
+}
+
+/// A typed client mirroring jsonrpc methods of node.
+pub struct RpcClient {
+    transport: Transport,
+}
+
+impl RpcClient {
+    /// Call method with params, and decode its result.
+    async fn call<T: DeserializeOwned>(&self, method: Method, params: Params) -> Result<T> {
+        let resp = match &self.transport {
+            Transport::Http(client) => client
+                .call_method(method.as_str(), params)
+                .await
+                .map_err(|e| Error::RemoteRpcError(e.to_string()))?,
+            Transport::InProcess { methods, meta } => {
+                let handler = methods.get(method.as_str()).ok_or(Error::InvalidMethod)?;
+                handler(params, meta.clone())
+                    .await
+                    .map_err(|e| Error::RemoteRpcError(e.to_string()))?
+            }
+        };
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Call method with params, and ignore its result.
+    async fn call_unit(&self, method: Method, params: Params) -> Result<()> {
+        self.call::<Value>(method, params).await.map(|_| ())
+    }
+
+    /// Connect a peer via its jsonrpc server, returns the transport id.
+    pub async fn connect_peer_via_http(
+        &self,
+        http_url: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<String> {
+        self.call(
+            Method::ConnectPeerViaHttp,
+            Params::Array(vec![json!(http_url), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Connect peers in a seed, each handshake is bounded by `timeout_ms`.
+    pub async fn connect_with_seed(&self, seed: &Value, timeout_ms: Option<u64>) -> Result<()> {
+        self.call_unit(
+            Method::ConnectWithSeed,
+            Params::Array(vec![seed.clone(), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Connect peers of the seed remembered by node which are not connected,
+    /// returns result of each peer in order.
+    pub async fn reconnect_seed(
+        &self,
+        timeout_ms: Option<u64>,
+    ) -> Result<Vec<response::ConnectResult>> {
+        self.call(
+            Method::ReconnectSeed,
+            Params::Array(vec![json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Connect peers via their jsonrpc servers, returns result of each peer in order.
+    pub async fn connect_batch(&self, endpoints: &[&str]) -> Result<Vec<response::ConnectResult>> {
+        self.call(
+            Method::ConnectBatch,
+            Params::Array(endpoints.iter().map(|x| json!(x)).collect()),
+        )
+        .await
+    }
+
+    /// Connect a peer by did through DHT, returns the transport id.
+    pub async fn connect_with_did(&self, did: Did, timeout_ms: Option<u64>) -> Result<String> {
+        self.call(
+            Method::ConnectWithDid,
+            Params::Array(vec![json!(did.to_string()), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Create an offer for manual handshake.
+    pub async fn create_offer(&self) -> Result<String> {
+        self.call(Method::CreateOffer, Params::Array(vec![])).await
+    }
+
+    /// Answer an offer for manual handshake.
+    pub async fn answer_offer(&self, offer: &str) -> Result<String> {
+        self.call(Method::AnswerOffer, Params::Array(vec![json!(offer)]))
+            .await
+    }
+
+    /// Accept an answer for manual handshake.
+    pub async fn accept_answer(&self, answer: &str) -> Result<response::Peer> {
+        self.call(Method::AcceptAnswer, Params::Array(vec![json!(answer)]))
+            .await
+    }
+
+    /// List all connected peers.
+    pub async fn list_peers(&self) -> Result<Vec<response::Peer>> {
+        self.call(Method::ListPeers, Params::Array(vec![])).await
+    }
+
+    /// List a page of connected peers, filtered by their status.
+    pub async fn list_peers_page(
+        &self,
+        options: &types::ListPeersOptions,
+    ) -> Result<response::ListPeersResponse> {
+        let options = serde_json::to_value(options).map_err(|_| Error::EncodeError)?;
+        self.call(Method::ListPeers, Params::Array(vec![options]))
+            .await
+    }
+
+    /// Disconnect the peer.
+    pub async fn disconnect(&self, did: Did) -> Result<()> {
+        self.call_unit(
+            Method::Disconnect,
+            Params::Array(vec![json!(did.to_string())]),
+        )
+        .await
+    }
+
+    /// Disconnect all peers.
+    pub async fn disconnect_all(&self) -> Result<response::DisconnectSummary> {
+        self.call(Method::DisconnectAll, Params::Array(vec![]))
+            .await
+    }
+
+    /// Disconnect peers matching the filter.
+    pub async fn disconnect_where(
+        &self,
+        filter: &types::DisconnectFilter,
+    ) -> Result<response::DisconnectSummary> {
+        let filter = serde_json::to_value(filter).map_err(|_| Error::EncodeError)?;
+        self.call(Method::DisconnectWhere, Params::Array(vec![filter]))
+            .await
+    }
+
+    /// Restart ice of the connection to peer.
+    pub async fn restart_ice(&self, did: Did) -> Result<()> {
+        self.call_unit(
+            Method::RestartIce,
+            Params::Array(vec![json!(did.to_string())]),
+        )
+        .await
+    }
+
+    /// Add a sticky peer, returns false if it's already added.
+    pub async fn add_sticky_peer(&self, did: Did) -> Result<bool> {
+        self.call(
+            Method::AddStickyPeer,
+            Params::Array(vec![json!(did.to_string())]),
+        )
+        .await
+    }
+
+    /// Remove a sticky peer, returns false if it's not a sticky peer.
+    pub async fn remove_sticky_peer(&self, did: Did) -> Result<bool> {
+        self.call(
+            Method::RemoveStickyPeer,
+            Params::Array(vec![json!(did.to_string())]),
+        )
+        .await
+    }
+
+    /// List sticky peers with their reconnection states.
+    pub async fn list_sticky_peers(&self) -> Result<Vec<response::StickyPeer>> {
+        self.call(Method::ListStickyPeers, Params::Array(vec![]))
+            .await
+    }
+
+    /// Revoke a session, returns false if it's already revoked.
+    pub async fn revoke_session(&self, session_id: Did) -> Result<bool> {
+        self.call(
+            Method::RevokeSession,
+            Params::Array(vec![json!(session_id.to_string())]),
+        )
+        .await
+    }
+
+    /// List pending transports.
+    pub async fn list_pendings(&self) -> Result<Vec<response::TransportInfo>> {
+        self.call(Method::ListPendings, Params::Array(vec![])).await
+    }
+
+    /// Close a pending transport.
+    pub async fn close_pending_transport(&self, transport_id: &str) -> Result<()> {
+        self.call_unit(
+            Method::ClosePendingTransport,
+            Params::Array(vec![json!(transport_id)]),
+        )
+        .await
+    }
+
+    /// Send raw text to peer.
+    pub async fn send_message(
+        &self,
+        destination: Did,
+        text: &str,
+    ) -> Result<response::SendMessageResponse> {
+        let mut params = serde_json::Map::new();
+        params.insert("destination".to_owned(), json!(destination.to_string()));
+        params.insert("text".to_owned(), json!(text));
+        self.call(Method::SendTo, Params::Map(params)).await
+    }
+
+    /// Send custom message of `message_type` to peer.
+    pub async fn send_custom_message(
+        &self,
+        destination: Did,
+        message_type: u16,
+        data: &[u8],
+    ) -> Result<response::SendMessageResponse> {
+        self.call(
+            Method::SendCustomMessage,
+            Params::Array(vec![
+                json!(destination.to_string()),
+                json!(message_type),
+                json!(base64::encode(data)),
+            ]),
+        )
+        .await
+    }
+
+    /// Broadcast custom message to all connected peers.
+    pub async fn broadcast_message(
+        &self,
+        message_type: u16,
+        data: &[u8],
+    ) -> Result<response::BroadcastResponse> {
+        self.call(
+            Method::BroadcastMessage,
+            Params::Array(vec![json!(message_type), json!(base64::encode(data))]),
+        )
+        .await
+    }
+
+    /// Send simple text message to peer.
+    pub async fn send_simple_text_message(
+        &self,
+        destination: Did,
+        text: &str,
+    ) -> Result<response::SendMessageResponse> {
+        self.call(
+            Method::SendSimpleText,
+            Params::Array(vec![json!(destination.to_string()), json!(text)]),
+        )
+        .await
+    }
+
+    /// Send http request to hidden service `name` of peer.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_http_request_message(
+        &self,
+        destination: Did,
+        name: &str,
+        method: http::Method,
+        url: &str,
+        timeout: Timeout,
+        headers: &[(&str, &str)],
+        body: Option<Vec<u8>>,
+    ) -> Result<response::SendMessageResponse> {
+        let request = types::HttpRequest::new(name, method, url, timeout, headers, body);
+        let request = serde_json::to_value(request).map_err(|_| Error::EncodeError)?;
+        self.call(
+            Method::SendHttpRequestMessage,
+            Params::Array(vec![json!(destination.to_string()), request]),
+        )
+        .await
+    }
+
+    /// Cancel a message being sent.
+    pub async fn cancel_message(&self, tx_id: &str) -> Result<response::CancelStatus> {
+        self.call(Method::CancelMessage, Params::Array(vec![json!(tx_id)]))
+            .await
+    }
+
+    /// List tx_id of messages being sent.
+    pub async fn list_sending_messages(&self) -> Result<Vec<String>> {
+        self.call(Method::ListSendingMessages, Params::Array(vec![]))
+            .await
+    }
+
+    /// Wait for delivery receipt of a sent message until timeout in milliseconds.
+    pub async fn wait_for_delivery(
+        &self,
+        tx_id: &str,
+        timeout_ms: u64,
+    ) -> Result<response::DeliveryResponse> {
+        self.call(
+            Method::WaitForDelivery,
+            Params::Array(vec![json!(tx_id), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Append data to topic.
+    pub async fn publish_message_to_topic(&self, topic: &str, data: &str) -> Result<()> {
+        self.call_unit(
+            Method::PublishMessageToTopic,
+            Params::Array(vec![json!(topic), json!(data)]),
+        )
+        .await
+    }
+
+    /// Append data to topic, signed by session of the node, so that readers can verify the author.
+    pub async fn publish_signed_message_to_topic(&self, topic: &str, data: &str) -> Result<()> {
+        self.call_unit(
+            Method::PublishMessageToTopic,
+            Params::Array(vec![json!(topic), json!(data), json!(true)]),
+        )
+        .await
+    }
+
+    /// Fetch entries of topic from `index`.
+    pub async fn fetch_messages_of_topic(
+        &self,
+        topic: &str,
+        index: usize,
+    ) -> Result<Vec<response::TopicEntry>> {
+        self.call(
+            Method::FetchMessagesOfTopic,
+            Params::Array(vec![json!(topic), json!(index)]),
+        )
+        .await
+    }
+
+    /// Fetch at most `count` entries of topic from `index`, only entries in range are transferred.
+    pub async fn fetch_messages_of_topic_range(
+        &self,
+        topic: &str,
+        index: usize,
+        count: usize,
+    ) -> Result<Vec<response::TopicEntry>> {
+        self.call(
+            Method::FetchMessagesOfTopic,
+            Params::Array(vec![json!(topic), json!(index), json!(count)]),
+        )
+        .await
+    }
+
+    /// Delete data of topic, keep the most recent `keep_last` ones.
+    pub async fn delete_messages_of_topic(&self, topic: &str, keep_last: usize) -> Result<()> {
+        self.call_unit(
+            Method::DeleteMessagesOfTopic,
+            Params::Array(vec![json!(topic), json!(keep_last)]),
+        )
+        .await
+    }
+
+    /// Register service of node with metadata, which expires after `ttl_ms` unless renewed.
+    pub async fn register_service(
+        &self,
+        name: &str,
+        ttl_ms: Option<u64>,
+        metadata: Option<&types::ServiceMetadata>,
+    ) -> Result<()> {
+        self.call_unit(
+            Method::RegisterService,
+            Params::Array(vec![json!(name), json!(ttl_ms), json!(metadata)]),
+        )
+        .await
+    }
+
+    /// Lookup nodes registered service with their metadata, filtered by `tag` if provided.
+    pub async fn lookup_service(
+        &self,
+        name: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<response::ServiceProvider>> {
+        self.call(
+            Method::LookupService,
+            Params::Array(vec![json!(name), json!(tag)]),
+        )
+        .await
+    }
+
+    /// Send text to a provider of service, which is tagged with `tag` if provided.
+    /// Return did of the provider and tx_id.
+    pub async fn send_to_service(
+        &self,
+        name: &str,
+        text: &str,
+        tag: Option<&str>,
+    ) -> Result<response::SendToServiceResponse> {
+        self.call(
+            Method::SendToService,
+            Params::Array(vec![json!(name), json!(text), json!(tag)]),
+        )
+        .await
+    }
+
+    /// Query info of node.
+    pub async fn node_info(&self) -> Result<response::NodeInfo> {
+        self.call(Method::NodeInfo, Params::None).await
+    }
+
+    /// Query health of node.
+    pub async fn health(&self) -> Result<response::HealthInfo> {
+        self.call(Method::Health, Params::None).await
+    }
+
+    /// Query metrics of node.
+    pub async fn metrics(&self) -> Result<response::MetricsInfo> {
+        self.call(Method::Metrics, Params::None).await
+    }
+
+    /// Query the did of node and the authorizer of its session.
+    pub async fn whoami(&self) -> Result<response::WhoamiResponse> {
+        self.call(Method::Whoami, Params::None).await
+    }
+
+    /// Ping the peer `count` times, and measure round-trip time.
+    pub async fn ping(
+        &self,
+        did: Did,
+        count: Option<usize>,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::PingResponse> {
+        self.call(
+            Method::Ping,
+            Params::Array(vec![
+                json!(did.to_string()),
+                json!(count),
+                json!(timeout_ms),
+            ]),
+        )
+        .await
+    }
+
+    /// Request node info of a remote node, waiting for its reply up to `timeout_ms`.
+    pub async fn remote_node_info(
+        &self,
+        did: Did,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::NodeInfo> {
+        self.call(
+            Method::RemoteNodeInfo,
+            Params::Array(vec![json!(did.to_string()), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Walk the ring from node through successors, requesting at most `max_hops` remote nodes.
+    pub async fn crawl_ring(
+        &self,
+        max_hops: Option<usize>,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::CrawlRingResponse> {
+        self.call(
+            Method::CrawlRing,
+            Params::Array(vec![json!(max_hops), json!(timeout_ms)]),
+        )
+        .await
+    }
+
+    /// Predict the next hop of a message to destination, without sending it.
+    pub async fn route_query(&self, destination: &str) -> Result<response::RouteQueryResponse> {
+        self.call(Method::RouteQuery, Params::Array(vec![json!(destination)]))
+            .await
+    }
+
+    /// Query the chord ring from the view of node.
+    pub async fn inspect_dht(&self) -> Result<response::DHTInfo> {
+        self.call(Method::InspectDht, Params::None).await
+    }
+
+    /// Leave the ring gracefully.
+    pub async fn node_leave(&self) -> Result<()> {
+        self.call_unit(Method::NodeLeave, Params::None).await
+    }
+
+    /// Shutdown the node.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.call_unit(Method::Shutdown, Params::None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_rpc_client_in_process() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let p1 = Arc::new(p1);
+        let p2 = Arc::new(p2);
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        let c1 = RpcClientBuilder::in_process(p1.clone()).build();
+        let c2 = RpcClientBuilder::in_process(p2.clone()).build();
+
+        let offer = c1.create_offer().await.unwrap();
+        assert_eq!(c1.list_pendings().await.unwrap().len(), 1);
+        let answer = c2.answer_offer(&offer).await.unwrap();
+        let peer = c1.accept_answer(&answer).await.unwrap();
+        assert_eq!(peer.did, p2.did().to_string());
+
+        // The peer is listed once its transport is connected.
+        let peers = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let peers = c1.list_peers().await.unwrap();
+                if !peers.is_empty() {
+                    break peers;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].did, p2.did().to_string());
+
+        let info = c1.node_info().await.unwrap();
+        assert_eq!(info.version, crate::util::build_version());
+        assert!(c1.add_sticky_peer(p2.did()).await.unwrap());
+        assert!(!c1.add_sticky_peer(p2.did()).await.unwrap());
+        assert!(c1.remove_sticky_peer(p2.did()).await.unwrap());
+
+        // Errors of handlers are returned instead of malformed values.
+        assert!(matches!(
+            c1.send_custom_message(p2.did(), 1, b"hello").await,
+            Err(Error::RemoteRpcError(_))
+        ));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+}
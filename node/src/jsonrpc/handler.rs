@@ -7,6 +7,8 @@
 use core::future::Future;
 use std::pin::Pin;
 
+use tracing::Instrument;
+
 #[cfg(feature = "browser")]
 pub use self::browser::build_handler;
 #[cfg(feature = "browser")]
@@ -17,10 +19,12 @@ pub use self::default::build_handler;
 pub use self::default::HandlerType;
 use super::server;
 use super::server::RpcMeta;
+use crate::prelude::jsonrpc_core::Error;
 use crate::prelude::jsonrpc_core::Params;
 use crate::prelude::jsonrpc_core::Result;
 use crate::prelude::jsonrpc_core::Value;
 use crate::prelude::rings_rpc::method::Method;
+use crate::prelude::uuid;
 
 /// Type of handler function
 #[cfg(feature = "node")]
@@ -40,10 +44,75 @@ macro_rules! pin {
     };
 }
 
+/// Check the scope required by method before calling handler.
+/// The future of handler is lazy, so it's not run if the scope is missing.
+fn scoped(method: &Method, handler: MethodFnBox) -> MethodFnBox {
+    let method = method.clone();
+    Box::new(move |params, meta| {
+        let fut = handler(params, meta.clone());
+        let method = method.clone();
+        Box::pin(async move {
+            meta.require_scope(&method)?;
+            fut.await
+        })
+    })
+}
+
+/// Run handler in a span named `rpc`, carrying the method, an id of request and the did
+/// authenticated the request if any, so logs of a request can be filtered together.
+/// The id of request is echoed in `data` of error response.
+fn traced(method: &Method, handler: MethodFnBox) -> MethodFnBox {
+    let method = method.clone();
+    Box::new(move |params, meta| {
+        let request_id = uuid::Uuid::new_v4();
+        let span = tracing::info_span!(
+            "rpc",
+            method = method.as_str(),
+            request_id = %request_id,
+            did = meta.authed_did().map(tracing::field::display),
+        );
+        let fut = span.in_scope(|| handler(params, meta));
+        Box::pin(
+            async move {
+                fut.await.map_err(|e| {
+                    tracing::debug!("rpc failed: {}", e.message);
+                    with_request_id(e, request_id)
+                })
+            }
+            .instrument(span),
+        )
+    })
+}
+
+/// Put the id of request in `data` of error, the original data is kept as `detail`.
+fn with_request_id(mut e: Error, request_id: uuid::Uuid) -> Error {
+    let mut data = serde_json::Map::new();
+    data.insert(
+        "request_id".to_string(),
+        Value::String(request_id.to_string()),
+    );
+    if let Some(detail) = e.data.take() {
+        data.insert("detail".to_string(), detail);
+    }
+    e.data = Some(Value::Object(data));
+    e
+}
+
 /// This function will return a list of public functions for all interfaces.
 /// If you need to define interfaces separately for the browser or native,
 /// you should use cfg to control the conditions.
+/// Each handler is wrapped to check the scope required by its method, and traced by [traced].
 pub fn methods() -> Vec<(Method, MethodFnBox)> {
+    unscoped_methods()
+        .into_iter()
+        .map(|(method, handler)| {
+            let handler = traced(&method, scoped(&method, handler));
+            (method, handler)
+        })
+        .collect()
+}
+
+fn unscoped_methods() -> Vec<(Method, MethodFnBox)> {
     vec![
         (
             Method::ConnectPeerViaHttp,
@@ -54,12 +123,19 @@ pub fn methods() -> Vec<(Method, MethodFnBox)> {
             pin!(server::connect_peer_via_http),
         ),
         (Method::ConnectWithSeed, pin!(server::connect_with_seed)),
+        (Method::ReconnectSeed, pin!(server::reconnect_seed)),
         (Method::AnswerOffer, pin!(server::answer_offer)),
         (Method::ConnectWithDid, pin!(server::connect_with_did)),
         (Method::CreateOffer, pin!(server::create_offer)),
         (Method::AcceptAnswer, pin!(server::accept_answer)),
         (Method::ListPeers, pin!(server::list_peers)),
         (Method::Disconnect, pin!(server::close_connection)),
+        (Method::DisconnectAll, pin!(server::disconnect_all)),
+        (Method::DisconnectWhere, pin!(server::disconnect_where)),
+        (Method::RestartIce, pin!(server::restart_ice)),
+        (Method::AddStickyPeer, pin!(server::add_sticky_peer)),
+        (Method::RemoveStickyPeer, pin!(server::remove_sticky_peer)),
+        (Method::ListStickyPeers, pin!(server::list_sticky_peers)),
         (Method::ListPendings, pin!(server::list_pendings)),
         (
             Method::ClosePendingTransport,
@@ -75,6 +151,12 @@ pub fn methods() -> Vec<(Method, MethodFnBox)> {
             pin!(server::send_simple_text_message),
         ),
         (Method::SendCustomMessage, pin!(server::send_custom_message)),
+        (Method::BroadcastMessage, pin!(server::broadcast_message)),
+        (Method::CancelMessage, pin!(server::cancel_message)),
+        (
+            Method::ListSendingMessages,
+            pin!(server::list_sending_messages),
+        ),
         (
             Method::PublishMessageToTopic,
             pin!(server::publish_message_to_topic),
@@ -83,11 +165,30 @@ pub fn methods() -> Vec<(Method, MethodFnBox)> {
             Method::FetchMessagesOfTopic,
             pin!(server::fetch_messages_of_topic),
         ),
+        (
+            Method::DeleteMessagesOfTopic,
+            pin!(server::delete_messages_of_topic),
+        ),
         (Method::RegisterService, pin!(server::register_service)),
         (Method::LookupService, pin!(server::lookup_service)),
+        (Method::SendToService, pin!(server::send_to_service)),
         (Method::NodeInfo, pin!(server::node_info)),
+        (Method::Health, pin!(server::health)),
+        (Method::Metrics, pin!(server::metrics)),
+        (Method::Whoami, pin!(server::whoami)),
+        (Method::NodeLeave, pin!(server::node_leave)),
+        (Method::Shutdown, pin!(server::shutdown)),
+        (Method::ConnectBatch, pin!(server::connect_batch)),
+        (Method::InspectDht, pin!(server::inspect_dht)),
+        (Method::RevokeSession, pin!(server::revoke_session)),
+        (Method::Ping, pin!(server::ping)),
+        (Method::RemoteNodeInfo, pin!(server::remote_node_info)),
+        (Method::CrawlRing, pin!(server::crawl_ring)),
+        (Method::RouteQuery, pin!(server::route_query)),
         #[cfg(feature = "node")]
         (Method::PollMessage, pin!(default::poll_backend_message)),
+        #[cfg(feature = "node")]
+        (Method::WaitForDelivery, pin!(default::wait_for_delivery)),
     ]
 }
 
@@ -194,11 +295,15 @@ pub mod browser {
 /// Implementation for native node
 #[cfg(feature = "node")]
 pub mod default {
+    use std::str::FromStr;
+
     use super::*;
     use crate::error::Error as ServerError;
     use crate::prelude::jsonrpc_core::Error;
     use crate::prelude::jsonrpc_core::MetaIoHandler as MessageHandler;
     use crate::prelude::rings_rpc::response::CustomBackendMessage;
+    use crate::prelude::rings_rpc::response::DeliveryResponse;
+    use crate::prelude::uuid;
 
     /// Type of Messagehandler
     pub type HandlerType = MessageHandler<server::RpcMeta>;
@@ -231,8 +336,8 @@ pub mod default {
             recv.try_recv().ok()
         };
 
-        let message = if let Some(msg) = message {
-            serde_json::to_value(CustomBackendMessage::from(msg))
+        let message = if let Some(incoming) = message {
+            serde_json::to_value(CustomBackendMessage::from(incoming.msg))
                 .map_err(|_| Error::from(ServerError::EncodeError))?
         } else {
             serde_json::Value::Null
@@ -241,4 +346,143 @@ pub mod default {
             "message": message,
         }))
     }
+
+    /// Wait for delivery receipt of a sent message.
+    /// * Params
+    ///   - tx_id: tx_id returned by sending message
+    ///   - timeout: timeout in milliseconds
+    pub async fn wait_for_delivery(params: Params, meta: server::RpcMeta) -> Result<Value> {
+        meta.require_authed()?;
+        let receiver = meta
+            .receiver
+            .as_ref()
+            .ok_or_else(|| Error::from(ServerError::InternalError))?;
+
+        let params: Vec<serde_json::Value> = params.parse()?;
+        let tx_id = server::str_param(&params, 0, "tx_id")?;
+        let tx_id = uuid::Uuid::from_str(tx_id)
+            .map_err(|_| server::invalid_param("expected uuid tx_id at position 0"))?;
+        let timeout = server::u64_param(&params, 1, "timeout")?;
+
+        let mut recv = receiver.lock().await.resubscribe();
+        let delivered = meta
+            .processor
+            .wait_for_delivery(&mut recv, tx_id, std::time::Duration::from_millis(timeout))
+            .await;
+
+        serde_json::to_value(DeliveryResponse {
+            tx_id: tx_id.to_string(),
+            delivered,
+        })
+        .map_err(|_| Error::from(ServerError::EncodeError))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use tracing::field::Field;
+    use tracing::field::Visit;
+    use tracing::span::Attributes;
+    use tracing::span::Id;
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Layer;
+
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    /// Fields of `rpc` spans created.
+    #[derive(Clone, Default)]
+    struct RpcSpans(Arc<Mutex<Vec<HashMap<String, String>>>>);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for RpcSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() != "rpc" {
+                return;
+            }
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    async fn call(
+        spans: &RpcSpans,
+        method: Method,
+        params: Params,
+        meta: RpcMeta,
+    ) -> Result<Value> {
+        let (_, handler) = methods()
+            .into_iter()
+            .find(|(m, _)| m.as_str() == method.as_str())
+            .unwrap();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        let fut = tracing::subscriber::with_default(subscriber, || handler(params, meta));
+        fut.await
+    }
+
+    #[tokio::test]
+    async fn test_rpc_span_fields() {
+        let (processor, path) = prepare_processor(None).await;
+        let processor = Arc::new(processor);
+        let did = processor.did().to_string();
+        let spans = RpcSpans::default();
+
+        let authed = RpcMeta::from(processor.clone());
+        call(&spans, Method::NodeInfo, Params::None, authed)
+            .await
+            .unwrap();
+        let fields = spans.0.lock().unwrap().pop().unwrap();
+        assert_eq!(fields["method"], "nodeInfo");
+        assert_eq!(fields["did"], did);
+        assert!(uuid::Uuid::parse_str(&fields["request_id"]).is_ok());
+
+        // The request id is echoed in error, and the did is absent if it's not authed.
+        let unauthed = RpcMeta::from((processor.clone(), false));
+        let err = call(&spans, Method::ListPeers, Params::None, unauthed)
+            .await
+            .unwrap_err();
+        let fields = spans.0.lock().unwrap().pop().unwrap();
+        assert_eq!(fields["method"], "listPeers");
+        assert!(!fields.contains_key("did"));
+        let data = err.data.unwrap();
+        assert_eq!(data["request_id"], fields["request_id"].as_str());
+
+        // The original data of error is kept as detail.
+        let authed = RpcMeta::from(processor.clone());
+        let err = call(
+            &spans,
+            Method::ConnectWithDid,
+            Params::Array(vec![]),
+            authed,
+        )
+        .await
+        .unwrap_err();
+        let fields = spans.0.lock().unwrap().pop().unwrap();
+        let data = err.data.unwrap();
+        assert_eq!(data["request_id"], fields["request_id"].as_str());
+        assert_eq!(data["detail"], "missing did at position 0");
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
 }
@@ -11,3 +11,11 @@ pub mod handler;
 pub use self::handler::build_handler;
 pub use self::handler::HandlerType;
 pub mod types;
+
+/// Typed client of jsonrpc methods over http or in process
+#[cfg(feature = "node")]
+pub mod client;
+#[cfg(feature = "node")]
+pub use self::client::RpcClient;
+#[cfg(feature = "node")]
+pub use self::client::RpcClientBuilder;
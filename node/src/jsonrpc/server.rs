@@ -1,15 +1,9 @@
 #![warn(missing_docs)]
 //! A jsonrpc-server of rings-node
 /// [JSON-RPC]: https://www.jsonrpc.org/specification
-use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::Arc;
 
-#[cfg(feature = "browser")]
-use futures::channel::mpsc::Receiver;
-use futures::future::join_all;
-#[cfg(feature = "browser")]
-use futures::lock::Mutex;
 use serde_json::Value;
 #[cfg(feature = "node")]
 use tokio::sync::broadcast::Receiver;
@@ -17,62 +11,111 @@ use tokio::sync::broadcast::Receiver;
 use tokio::sync::Mutex;
 
 use crate::backend::types::BackendMessage;
+#[cfg(feature = "node")]
+use crate::backend::types::IncomingMessage;
 use crate::backend::MessageType;
+use crate::consts::DEFAULT_CRAWL_MAX_HOPS;
+use crate::consts::DEFAULT_PING_COUNT;
+use crate::consts::DEFAULT_PING_TIMEOUT_MS;
+use crate::consts::DEFAULT_REMOTE_NODE_INFO_TIMEOUT_MS;
+use crate::consts::DEFAULT_SERVICE_TTL_MS;
+use crate::consts::DEFAULT_STORAGE_GET_TIMEOUT_MS;
+use crate::consts::MAX_PING_COUNT;
 use crate::error::Error as ServerError;
+use crate::error::Result as ServerResult;
 use crate::prelude::jsonrpc_core::Error;
 use crate::prelude::jsonrpc_core::ErrorCode;
 use crate::prelude::jsonrpc_core::Params;
 use crate::prelude::jsonrpc_core::Result;
 use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::inspect::DHTInspect;
 use crate::prelude::rings_core::message::Decoder;
 use crate::prelude::rings_core::message::Encoded;
 use crate::prelude::rings_core::message::Encoder;
 use crate::prelude::rings_core::message::Message;
 use crate::prelude::rings_core::message::MessagePayload;
+use crate::prelude::rings_core::prelude::uuid;
 use crate::prelude::rings_core::prelude::vnode::VirtualNode;
 use crate::prelude::rings_core::transports::manager::TransportHandshake;
-use crate::prelude::rings_core::transports::manager::TransportManager;
-use crate::prelude::rings_core::types::ice_transport::IceTransportInterface;
-use crate::prelude::rings_core::utils::from_rtc_ice_connection_state;
 use crate::prelude::rings_rpc;
+use crate::prelude::rings_rpc::method::Method;
 use crate::prelude::rings_rpc::response;
 use crate::prelude::rings_rpc::response::Peer;
+use crate::prelude::rings_rpc::types::DisconnectFilter;
 use crate::prelude::rings_rpc::types::HttpRequest;
+use crate::prelude::rings_rpc::types::ListPeersOptions;
+use crate::prelude::rings_rpc::types::ServiceMetadata;
 use crate::processor;
 use crate::processor::Processor;
 use crate::seed::Seed;
+use crate::topics;
 
 /// RpcMeta basic info struct
 /// * processor: contain `swarm` instance and `stabilization` instance.
 /// * is_auth: is_auth set true after verify.
+/// * scopes: scopes granted to the session which signed request, all scopes if it's `None`.
+///   It's the scopes of node's session by default.
 #[derive(Clone)]
 pub struct RpcMeta {
-    processor: Arc<Processor>,
-    #[allow(dead_code)]
-    pub(crate) receiver: Option<Arc<Mutex<Receiver<BackendMessage>>>>,
+    pub(crate) processor: Arc<Processor>,
+    /// receiver of backend messages, it's only available on native node
+    #[cfg(feature = "node")]
+    pub(crate) receiver: Option<Arc<Mutex<Receiver<IncomingMessage>>>>,
     /// if is_auth set to true, rpc server of *native node* will check signature from
     /// HEAD['X-SIGNATURE']
     is_auth: bool,
+    scopes: Option<Vec<String>>,
 }
 
 impl RpcMeta {
-    fn require_authed(&self) -> Result<()> {
+    pub(crate) fn require_authed(&self) -> Result<()> {
         if !self.is_auth {
             return Err(Error::from(ServerError::NoPermission));
         }
         Ok(())
     }
+
+    /// Did authenticated the request, which is the authorizer of node, `None` if it's not authed.
+    pub(crate) fn authed_did(&self) -> Option<Did> {
+        self.is_auth.then(|| self.processor.did())
+    }
+
+    /// Limit the meta to scopes, `None` for all scopes.
+    #[cfg(feature = "node")]
+    pub(crate) fn with_scopes(mut self, scopes: Option<Vec<String>>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Check the scope required by method is granted.
+    /// Auth is still checked by handlers, and public methods require no scope.
+    pub(crate) fn require_scope(&self, method: &Method) -> Result<()> {
+        let (Some(scope), Some(scopes)) = (method.scope(), &self.scopes) else {
+            return Ok(());
+        };
+        if !scopes.iter().any(|s| s == scope) {
+            return Err(Error::from(ServerError::MissingScope(scope.to_string())));
+        }
+        Ok(())
+    }
+}
+
+fn session_scopes(processor: &Processor) -> Option<Vec<String>> {
+    let session = processor.swarm.session_manager().session();
+    session.scopes().map(|s| s.to_vec())
 }
 
-impl From<(Arc<Processor>, Arc<Mutex<Receiver<BackendMessage>>>, bool)> for RpcMeta {
+#[cfg(feature = "node")]
+impl From<(Arc<Processor>, Arc<Mutex<Receiver<IncomingMessage>>>, bool)> for RpcMeta {
     fn from(
         (processor, receiver, is_auth): (
             Arc<Processor>,
-            Arc<Mutex<Receiver<BackendMessage>>>,
+            Arc<Mutex<Receiver<IncomingMessage>>>,
             bool,
         ),
     ) -> Self {
         Self {
+            scopes: session_scopes(&processor),
             processor,
             receiver: Some(receiver),
             is_auth,
@@ -83,7 +126,9 @@ impl From<(Arc<Processor>, Arc<Mutex<Receiver<BackendMessage>>>, bool)> for RpcM
 impl From<(Arc<Processor>, bool)> for RpcMeta {
     fn from((processor, is_auth): (Arc<Processor>, bool)) -> Self {
         Self {
+            scopes: session_scopes(&processor),
             processor,
+            #[cfg(feature = "node")]
             receiver: None,
             is_auth,
         }
@@ -93,7 +138,9 @@ impl From<(Arc<Processor>, bool)> for RpcMeta {
 impl From<Arc<Processor>> for RpcMeta {
     fn from(processor: Arc<Processor>) -> Self {
         Self {
+            scopes: session_scopes(&processor),
             processor,
+            #[cfg(feature = "node")]
             receiver: None,
             is_auth: true,
         }
@@ -109,75 +156,235 @@ pub(crate) async fn node_info(_: Params, meta: RpcMeta) -> Result<Value> {
     serde_json::to_value(node_info).map_err(|_| Error::new(ErrorCode::ParseError))
 }
 
+/// Report health of node for readiness probes, it doesn't require auth.
+pub(crate) async fn health(_: Params, meta: RpcMeta) -> Result<Value> {
+    serde_json::to_value(meta.processor.health()).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Handle metrics of node
+pub(crate) async fn metrics(_: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    serde_json::to_value(meta.processor.metrics())
+        .map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Get the did of node and the authorizer of its session.
+pub(crate) async fn whoami(_: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    serde_json::to_value(meta.processor.whoami()).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Make an InvalidParams error, with a human-readable `hint` of the offending param in `data`.
+/// The message is prefixed as the one of [Params::parse] failure.
+pub(crate) fn invalid_param(hint: impl Into<String>) -> Error {
+    let hint = hint.into();
+    Error {
+        data: Some(Value::String(hint.clone())),
+        ..Error::invalid_params(format!("Invalid params: {}", hint))
+    }
+}
+
+/// Get the required param `name` at `index` of params.
+pub(crate) fn param<'a, T>(params: &'a [T], index: usize, name: &str) -> Result<&'a T> {
+    params
+        .get(index)
+        .ok_or_else(|| invalid_param(format!("missing {} at position {}", name, index)))
+}
+
+/// Get the required string param `name` at `index` of params.
+pub(crate) fn str_param<'a>(params: &'a [Value], index: usize, name: &str) -> Result<&'a str> {
+    param(params, index, name)?
+        .as_str()
+        .ok_or_else(|| invalid_param(format!("expected string {} at position {}", name, index)))
+}
+
+/// Get the required unsigned integer param `name` at `index` of params.
+pub(crate) fn u64_param(params: &[Value], index: usize, name: &str) -> Result<u64> {
+    param(params, index, name)?.as_u64().ok_or_else(|| {
+        invalid_param(format!(
+            "expected unsigned integer {} at position {}",
+            name, index
+        ))
+    })
+}
+
+/// Get the optional unsigned integer param `name` at `index` of params.
+/// `null` is treated as not specified.
+pub(crate) fn optional_u64_param(
+    params: &[Value],
+    index: usize,
+    name: &str,
+) -> Result<Option<u64>> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v.as_u64().map(Some).ok_or_else(|| {
+            invalid_param(format!(
+                "expected unsigned integer {} at position {}",
+                name, index
+            ))
+        }),
+    }
+}
+
+/// Get the optional string param `name` at `index` of params.
+/// `null` is treated as not specified.
+pub(crate) fn optional_str_param<'a>(
+    params: &'a [Value],
+    index: usize,
+    name: &str,
+) -> Result<Option<&'a str>> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v.as_str().map(Some).ok_or_else(|| {
+            invalid_param(format!("expected string {} at position {}", name, index))
+        }),
+    }
+}
+
+/// Get the required string field `name` of named params.
+fn str_field<'a>(params: &'a serde_json::Map<String, Value>, name: &str) -> Result<&'a str> {
+    params
+        .get(name)
+        .ok_or_else(|| invalid_param(format!("missing field {}", name)))?
+        .as_str()
+        .ok_or_else(|| invalid_param(format!("expected string field {}", name)))
+}
+
+/// Parse an optional duration in ms, such as `timeout_ms`, at `index` of params.
+/// `null` is treated as not specified.
+fn optional_ms(params: &[Value], index: usize) -> Result<Option<u64>> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v.as_u64().map(Some).ok_or_else(|| {
+            invalid_param(format!(
+                "expected unsigned integer of milliseconds at position {}",
+                index
+            ))
+        }),
+    }
+}
+
 /// Connect Peer VIA http
+/// * Params
+///   - peer_url: url of remote rings-node jsonrpc server
+///   - timeout_ms: optional, timeout of handshake
 pub(crate) async fn connect_peer_via_http(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
-    let p: Vec<String> = params.parse()?;
-    let peer_url = p
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let p: Vec<Value> = params.parse()?;
+    let peer_url = str_param(&p, 0, "peer_url")?;
+    let timeout_ms = optional_ms(&p, 1)?;
     let peer = meta
         .processor
-        .connect_peer_via_http(peer_url)
+        .connect_peer_via_http(peer_url, timeout_ms)
         .await
         .map_err(Error::from)?;
     Ok(Value::String(peer.transport.id.to_string()))
 }
 
 /// Connect Peer with seed
+/// * Params
+///   - seed: peers to connect
+///   - timeout_ms: optional, timeout of handshake with each peer
 pub(crate) async fn connect_with_seed(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
-    let p: Vec<Seed> = params.parse()?;
-    let seed = p
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-
-    let mut connected_addresses: HashSet<Did> = HashSet::from_iter(meta.processor.swarm.get_dids());
-    connected_addresses.insert(meta.processor.swarm.did());
+    let p: Vec<Value> = params.parse()?;
+    let seed: Seed = serde_json::from_value(param(&p, 0, "seed")?.clone())
+        .map_err(|e| invalid_param(format!("expected seed at position 0: {}", e)))?;
+    seed.validate()
+        .map_err(|e| invalid_param(format!("expected seed at position 0: {}", e)))?;
+    let timeout_ms = optional_ms(&p, 1)?;
 
-    let tasks = seed
-        .peers
-        .iter()
-        .filter(|&x| !connected_addresses.contains(&x.did))
-        .map(|x| meta.processor.connect_peer_via_http(&x.endpoint));
-
-    let results = join_all(tasks).await;
+    let results = meta.processor.connect_with_seed(seed, timeout_ms).await;
 
-    let first_err = results.into_iter().find(|x| x.is_err());
-    if let Some(err) = first_err {
+    let first_err = results.into_iter().find(|(_, x)| x.is_err());
+    if let Some((_, err)) = first_err {
         err.map_err(Error::from)?;
     }
 
     Ok(Value::Null)
 }
 
+/// Connect peers of the seed remembered by the last `connectWithSeed`, which are not connected,
+/// report result of each peer in order
+/// * Params
+///   - timeout_ms: optional, timeout of handshake with each peer
+pub(crate) async fn reconnect_seed(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let p: Vec<Value> = params.parse()?;
+    let timeout_ms = optional_ms(&p, 0)?;
+    let results = meta.processor.reconnect_seed(timeout_ms).await?;
+    serde_json::to_value(connect_results(results))
+        .map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Connect peers via http, report result of each peer in order
+pub(crate) async fn connect_batch(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let endpoints: Vec<String> = params.parse()?;
+    let endpoints = endpoints.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+
+    let results = meta
+        .processor
+        .connect_peers_via_http(&endpoints, None)
+        .await;
+
+    let r = connect_results(
+        endpoints
+            .iter()
+            .map(|endpoint| endpoint.to_string())
+            .zip(results),
+    );
+    serde_json::to_value(r).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+fn connect_results(
+    results: impl IntoIterator<Item = (String, ServerResult<processor::Peer>)>,
+) -> Vec<response::ConnectResult> {
+    results
+        .into_iter()
+        .map(|(endpoint, result)| match result {
+            Ok(peer) => response::ConnectResult {
+                endpoint,
+                ok: true,
+                transport_id: Some(peer.transport.id.to_string()),
+                error: None,
+            },
+            Err(e) => response::ConnectResult {
+                endpoint,
+                ok: false,
+                transport_id: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect()
+}
+
 /// Handle Connect with DID
+/// * Params
+///   - did: did of peer
+///   - timeout_ms: optional, timeout of waiting for data channel open and the peer routable,
+///     the transport is closed and an error of code 607 is returned on timeout
 pub(crate) async fn connect_with_did(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
-    let p: Vec<String> = params.parse()?;
-    let address_str = p
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    meta.processor
+    let p: Vec<Value> = params.parse()?;
+    let address_str = str_param(&p, 0, "did")?;
+    let timeout_ms = optional_ms(&p, 1)?;
+    let peer = meta
+        .processor
         .connect_with_did(
-            Did::from_str(address_str).map_err(|_| Error::new(ErrorCode::InvalidParams))?,
+            Did::from_str(address_str).map_err(|_| invalid_param("expected DID at position 0"))?,
             true,
+            timeout_ms,
         )
         .await
         .map_err(Error::from)?;
-    Ok(Value::Null)
+    Ok(Value::String(peer.transport.id.to_string()))
 }
 
 /// Handle create offer
 pub(crate) async fn create_offer(_params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
-    let (_, offer_payload) = meta
-        .processor
-        .swarm
-        .create_offer()
-        .await
-        .map_err(ServerError::CreateOffer)
-        .map_err(Error::from)?;
+    let (_, offer_payload) = meta.processor.create_offer().await?;
 
     let encoded = offer_payload
         .encode()
@@ -190,20 +397,12 @@ pub(crate) async fn create_offer(_params: Params, meta: RpcMeta) -> Result<Value
 /// Handle Answer Offer
 pub(crate) async fn answer_offer(params: Params, meta: RpcMeta) -> Result<Value> {
     let p: Vec<String> = params.parse()?;
-    let offer_payload_str = p
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let offer_payload_str = param(&p, 0, "offer")?;
     let encoded: Encoded = <Encoded as From<&str>>::from(offer_payload_str);
     let offer_payload =
         MessagePayload::<Message>::from_encoded(&encoded).map_err(|_| ServerError::DecodeError)?;
 
-    let (_, answer_payload) = meta
-        .processor
-        .swarm
-        .answer_offer(offer_payload)
-        .await
-        .map_err(ServerError::AnswerOffer)
-        .map_err(Error::from)?;
+    let (_, answer_payload) = meta.processor.answer_offer(offer_payload).await?;
 
     tracing::debug!("connect_peer_via_ice response: {:?}", answer_payload);
     let encoded = answer_payload
@@ -219,9 +418,7 @@ pub(crate) async fn accept_answer(params: Params, meta: RpcMeta) -> Result<Value
     meta.require_authed()?;
 
     let p: Vec<String> = params.parse()?;
-    let answer_payload_str = p
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let answer_payload_str = param(&p, 0, "answer")?;
     let encoded: Encoded = <Encoded as From<&str>>::from(answer_payload_str);
     let answer_payload =
         MessagePayload::<Message>::from_encoded(&encoded).map_err(|_| ServerError::DecodeError)?;
@@ -234,27 +431,42 @@ pub(crate) async fn accept_answer(params: Params, meta: RpcMeta) -> Result<Value
         .map_err(Error::from)?
         .into();
 
-    let state = p.transport.ice_connection_state().await;
-    let r: Peer = p.into_response_peer(state.map(from_rtc_ice_connection_state));
+    let r: Peer = meta
+        .processor
+        .response_peers(&[p])
+        .await
+        .pop()
+        .ok_or(Error::from(ServerError::EncodeError))?;
     r.to_json_obj()
         .map_err(|_| ServerError::EncodeError)
         .map_err(Error::from)
 }
 
 /// Handle list peers
-pub(crate) async fn list_peers(_params: Params, meta: RpcMeta) -> Result<Value> {
+/// * Params
+///   - options: optional [ListPeersOptions], respond with a page and total count if provided.
+pub(crate) async fn list_peers(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
+    let options: Option<ListPeersOptions> = match params {
+        Params::Map(m) => Some(
+            serde_json::from_value(Value::Object(m))
+                .map_err(|e| invalid_param(format!("expected list peers options: {}", e)))?,
+        ),
+        Params::Array(v) if !v.is_empty() => {
+            Some(serde_json::from_value(v[0].clone()).map_err(|e| {
+                invalid_param(format!("expected list peers options at position 0: {}", e))
+            })?)
+        }
+        _ => None,
+    };
+    if let Some(options) = options {
+        let (peers, total) = meta.processor.list_peers_page(&options).await?;
+        return serde_json::to_value(response::ListPeersResponse { peers, total })
+            .map_err(|_| Error::from(ServerError::EncodeError));
+    }
+
     let peers = meta.processor.list_peers().await?;
-    let states_async = peers
-        .iter()
-        .map(|x| x.transport.ice_connection_state())
-        .collect::<Vec<_>>();
-    let states = futures::future::join_all(states_async).await;
-    let r: Vec<Peer> = peers
-        .iter()
-        .zip(states.iter())
-        .map(|(x, y)| x.into_response_peer(y.map(from_rtc_ice_connection_state)))
-        .collect::<Vec<_>>();
+    let r: Vec<Peer> = meta.processor.response_peers(&peers).await;
     serde_json::to_value(r).map_err(|_| Error::from(ServerError::EncodeError))
 }
 
@@ -262,28 +474,113 @@ pub(crate) async fn list_peers(_params: Params, meta: RpcMeta) -> Result<Value>
 pub(crate) async fn close_connection(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<String> = params.parse()?;
-    let did = params
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let did = param(&params, 0, "did")?;
     let did = Did::from_str(did).map_err(|_| Error::from(ServerError::InvalidDid))?;
     meta.processor.disconnect(did).await?;
     Ok(serde_json::json!({}))
 }
 
+/// Handle close all connections
+pub(crate) async fn disconnect_all(_: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let summary = meta.processor.disconnect_all().await;
+    serde_json::to_value(summary).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Handle close connections matching a filter
+pub(crate) async fn disconnect_where(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let filter: DisconnectFilter = match params {
+        Params::Map(m) => serde_json::from_value(Value::Object(m))
+            .map_err(|e| invalid_param(format!("expected disconnect filter: {}", e)))?,
+        Params::Array(v) if !v.is_empty() => serde_json::from_value(v[0].clone()).map_err(|e| {
+            invalid_param(format!("expected disconnect filter at position 0: {}", e))
+        })?,
+        _ => return Err(invalid_param("missing disconnect filter at position 0")),
+    };
+    let summary = meta
+        .processor
+        .disconnect_where(|peer| filter.matches(peer))
+        .await;
+    serde_json::to_value(summary).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Handle restart ice of a connection
+pub(crate) async fn restart_ice(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<String> = params.parse()?;
+    let did = param(&params, 0, "did")?;
+    let did = Did::from_str(did).map_err(|_| Error::from(ServerError::InvalidDid))?;
+    meta.processor.restart_ice(did).await?;
+    Ok(serde_json::json!({}))
+}
+
+/// Add a sticky peer, return false if it's already added
+pub(crate) async fn add_sticky_peer(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<String> = params.parse()?;
+    let did = param(&params, 0, "did")?;
+    let did = Did::from_str(did).map_err(|_| Error::from(ServerError::InvalidDid))?;
+    Ok(meta.processor.add_sticky_peer(did).into())
+}
+
+/// Remove a sticky peer, return false if it's not a sticky peer
+pub(crate) async fn remove_sticky_peer(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<String> = params.parse()?;
+    let did = param(&params, 0, "did")?;
+    let did = Did::from_str(did).map_err(|_| Error::from(ServerError::InvalidDid))?;
+    Ok(meta.processor.remove_sticky_peer(did).await.into())
+}
+
+/// Revoke a session by its session_id
+pub(crate) async fn revoke_session(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<String> = params.parse()?;
+    let session_id = param(&params, 0, "session_id")?;
+    let session_id =
+        Did::from_str(session_id).map_err(|_| invalid_param("expected DID at position 0"))?;
+    Ok(meta.processor.revoke_session(session_id)?.into())
+}
+
+/// List sticky peers with reconnection states
+pub(crate) async fn list_sticky_peers(_params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let peers = meta
+        .processor
+        .sticky_peers()
+        .into_iter()
+        .map(|(did, state)| state.into_response(did))
+        .collect::<Vec<_>>();
+    serde_json::to_value(peers).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Leave the ring gracefully
+pub(crate) async fn node_leave(_params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    meta.processor.leave().await?;
+    Ok(serde_json::json!({}))
+}
+
+/// Shutdown the node cleanly, the jsonrpc server stops serving after responding
+pub(crate) async fn shutdown(_params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    meta.processor.shutdown().await?;
+    Ok(serde_json::json!({}))
+}
+
+/// Inspect the chord ring from the view of node, it requires auth since topology is leaked.
+pub(crate) async fn inspect_dht(_params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let info = response::DHTInfo::from(DHTInspect::inspect(&meta.processor.swarm.dht()));
+    serde_json::to_value(info).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
 /// Handle list pendings
 pub(crate) async fn list_pendings(_params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let transports = meta.processor.list_pendings().await?;
-    let states_async = transports
-        .iter()
-        .map(|x| x.ice_connection_state())
-        .collect::<Vec<_>>();
-    let states = futures::future::join_all(states_async).await;
-    let r: Vec<response::TransportInfo> = transports
-        .iter()
-        .zip(states.iter())
-        .map(|(x, y)| response::TransportInfo::from((x, y.map(from_rtc_ice_connection_state))))
-        .collect::<Vec<_>>();
+    let r = meta.processor.response_transports(&transports).await;
     serde_json::to_value(r).map_err(|_| Error::from(ServerError::EncodeError))
 }
 
@@ -291,9 +588,7 @@ pub(crate) async fn list_pendings(_params: Params, meta: RpcMeta) -> Result<Valu
 pub(crate) async fn close_pending_transport(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<String> = params.parse()?;
-    let transport_id = params
-        .first()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let transport_id = param(&params, 0, "transport_id")?;
     meta.processor
         .close_pending_transport(transport_id.as_str())
         .await?;
@@ -304,25 +599,26 @@ pub(crate) async fn close_pending_transport(params: Params, meta: RpcMeta) -> Re
 pub(crate) async fn send_raw_message(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: serde_json::Map<String, Value> = params.parse()?;
-    let destination = params
-        .get("destination")
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let text = params
-        .get("text")
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let tx_id = meta
+    let destination = str_field(&params, "destination")?;
+    let text = str_field(&params, "text")?;
+    let (tx_id, next_hop) = meta
         .processor
-        .send_message(destination, text.as_bytes())
+        .send_message_with_route(destination, text.as_bytes(), None)
         .await?;
-    Ok(
-        serde_json::to_value(rings_rpc::response::SendMessageResponse::from(
-            tx_id.to_string(),
-        ))
-        .unwrap(),
+    Ok(serde_json::to_value(routed_response(destination, tx_id, next_hop)).unwrap())
+}
+
+/// Response of a sent message with the next hop it's handed to.
+fn routed_response(
+    destination: &str,
+    tx_id: uuid::Uuid,
+    next_hop: Did,
+) -> rings_rpc::response::SendMessageResponse {
+    let relayed = Did::from_str(destination).map_or(true, |did| did != next_hop);
+    rings_rpc::response::SendMessageResponse::with_route(
+        tx_id.to_string(),
+        next_hop.to_string(),
+        relayed,
     )
 }
 
@@ -334,31 +630,27 @@ pub(crate) async fn send_raw_message(params: Params, meta: RpcMeta) -> Result<Va
 pub(crate) async fn send_custom_message(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let destination = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let destination = str_param(&params, 0, "destination")?;
 
-    let message_type: u16 = params
-        .get(1)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_u64()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
+    let message_type: u16 = u64_param(&params, 1, "message_type")?
         .try_into()
-        .map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+        .map_err(|_| invalid_param("expected u16 message_type at position 1"))?;
+    if MessageType::is_reserved(message_type) {
+        return Err(invalid_param(format!(
+            "message_type {} at position 1 is reserved",
+            message_type
+        )));
+    }
 
-    let data = params
-        .get(2)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let data = str_param(&params, 2, "data")?;
 
-    let data = base64::decode(data).map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+    let data =
+        base64::decode(data).map_err(|_| invalid_param("expected base64 data at position 2"))?;
 
-    let msg: BackendMessage = BackendMessage::from((message_type, data.as_ref()));
-    let msg: Vec<u8> = msg.into();
-    let tx_id = meta.processor.send_message(destination, &msg).await?;
+    let tx_id = meta
+        .processor
+        .send_custom_message(destination, message_type, data, [0u8; 30])
+        .await?;
 
     Ok(
         serde_json::to_value(rings_rpc::response::SendMessageResponse::from(
@@ -368,25 +660,66 @@ pub(crate) async fn send_custom_message(params: Params, meta: RpcMeta) -> Result
     )
 }
 
+/// Broadcast a custom message to all connected peers whose data channels are open
+/// * Params
+///   - message_type: custom message type, which should not be reserved
+///   - data: base64 of payload data
+pub(crate) async fn broadcast_message(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let message_type: u16 = u64_param(&params, 0, "message_type")?
+        .try_into()
+        .map_err(|_| invalid_param("expected u16 message_type at position 0"))?;
+    if MessageType::is_reserved(message_type) {
+        return Err(invalid_param(format!(
+            "message_type {} at position 0 is reserved",
+            message_type
+        )));
+    }
+
+    let data = str_param(&params, 1, "data")?;
+    let data =
+        base64::decode(data).map_err(|_| invalid_param("expected base64 data at position 1"))?;
+
+    let resp = meta.processor.broadcast_message(message_type, data).await;
+    serde_json::to_value(resp).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Cancel a chunked message being sent, return `cancelled`, `completed` or `unknown`
+/// * Params
+///   - tx_id: tx_id of the message
+pub(crate) async fn cancel_message(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<String> = params.parse()?;
+    let tx_id = param(&params, 0, "tx_id")?;
+    let tx_id = uuid::Uuid::from_str(tx_id)
+        .map_err(|_| invalid_param("expected uuid tx_id at position 0"))?;
+    let status = meta.processor.cancel_send(tx_id);
+    serde_json::to_value(status).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// List tx_id of messages being sent
+pub(crate) async fn list_sending_messages(_params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let tx_ids = meta
+        .processor
+        .sending_messages()
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    Ok(serde_json::json!(tx_ids))
+}
+
 pub(crate) async fn send_simple_text_message(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let destination = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let text = params
-        .get(1)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let destination = str_param(&params, 0, "destination")?;
+    let text = str_param(&params, 1, "text")?;
 
-    let msg: BackendMessage =
-        BackendMessage::from((MessageType::SimpleText.into(), text.as_bytes()));
-    let msg: Vec<u8> = msg.into();
-    // TODO chunk message flag
-    let tx_id = meta.processor.send_message(destination, &msg).await?;
+    let tx_id = meta
+        .processor
+        .send_simple_text_message(destination, text)
+        .await?;
 
     Ok(
         serde_json::to_value(rings_rpc::response::SendMessageResponse::from(
@@ -400,123 +733,258 @@ pub(crate) async fn send_simple_text_message(params: Params, meta: RpcMeta) -> R
 pub(crate) async fn send_http_request_message(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let destination = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let p2 = params
-        .get(1)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .to_owned();
-    let http_request: HttpRequest =
-        serde_json::from_value(p2).map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+    let destination = str_param(&params, 0, "destination")?;
+    let p2 = param(&params, 1, "http_request")?.to_owned();
+    let http_request: HttpRequest = serde_json::from_value(p2)
+        .map_err(|e| invalid_param(format!("expected http_request at position 1: {}", e)))?;
+    http_request
+        .validate()
+        .map_err(|e| invalid_param(format!("invalid http_request at position 1: {}", e)))?;
 
     let msg: BackendMessage = (MessageType::HttpRequest, &http_request).try_into()?;
     let msg: Vec<u8> = msg.into();
-    // TODO chunk message flag
-    let tx_id = meta.processor.send_message(destination, &msg).await?;
+    let (tx_id, next_hop) = meta
+        .processor
+        .send_message_with_route(destination, &msg, None)
+        .await?;
 
-    Ok(
-        serde_json::to_value(rings_rpc::response::SendMessageResponse::from(
-            tx_id.to_string(),
-        ))
-        .unwrap(),
-    )
+    Ok(serde_json::to_value(routed_response(destination, tx_id, next_hop)).unwrap())
 }
 
+/// Handle publish message to topic
+/// * Params
+///   - topic: topic name
+///   - data: content of message
+///   - sign: optional, sign the message by session of node, so readers can verify its author
 pub(crate) async fn publish_message_to_topic(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let topic = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let data = params
-        .get(1)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .to_string()
-        .encode()
-        .map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+    let topic = str_param(&params, 0, "topic")?;
+    let content = str_param(&params, 1, "data")?;
+    let sign = match params.get(2) {
+        None | Some(Value::Null) => false,
+        Some(v) => v
+            .as_bool()
+            .ok_or_else(|| invalid_param("expected bool sign at position 2"))?,
+    };
+    let session_manager = sign.then(|| meta.processor.swarm.session_manager());
+    let data = topics::encode_entry(content, session_manager.as_deref())
+        .map_err(|_| invalid_param("expected encodable data at position 1"))?;
 
     meta.processor.storage_append_data(topic, data).await?;
 
     Ok(serde_json::json!({}))
 }
 
+/// Fetch messages of topic
+/// * Params
+///   - topic: name of topic
+///   - index: index of the first message
+///   - count: optional, max count of messages. Only messages in range are transferred if it's set,
+///     otherwise the whole topic is fetched.
 pub(crate) async fn fetch_messages_of_topic(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let topic = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    let index = params
-        .get(1)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_i64()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-
-    let vid = VirtualNode::gen_did(topic).map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+    let topic = str_param(&params, 0, "topic")?;
+    let index = u64_param(&params, 1, "index")?;
+    let count = optional_u64_param(&params, 2, "count")?;
 
-    meta.processor.storage_fetch(vid).await?;
-    let result = meta.processor.storage_check_cache(vid).await;
+    let vid = VirtualNode::gen_did(topic)
+        .map_err(|_| invalid_param("expected valid topic at position 0"))?;
 
-    if let Some(vnode) = result {
-        let messages = vnode
-            .data
-            .iter()
-            .skip(index as usize)
-            .map(|v| v.decode())
-            .filter_map(|v| v.ok())
-            .collect::<Vec<String>>();
-        Ok(serde_json::json!(messages))
+    let data = if let Some(count) = count {
+        meta.processor
+            .storage_fetch_range(vid, index, count)
+            .await?;
+        meta.processor
+            .storage_check_range_cache(vid)
+            .await
+            .filter(|range| range.start == index)
+            .map(|range| range.vnode.data)
+            .unwrap_or_default()
     } else {
-        Ok(serde_json::json!(Vec::<String>::new()))
-    }
+        meta.processor
+            .storage_get(
+                vid,
+                std::time::Duration::from_millis(DEFAULT_STORAGE_GET_TIMEOUT_MS),
+            )
+            .await?
+            .map(|vnode| {
+                vnode
+                    .data
+                    .into_iter()
+                    .skip(index as usize)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let entries = data
+        .iter()
+        .filter_map(topics::decode_entry)
+        .collect::<Vec<response::TopicEntry>>();
+    Ok(serde_json::json!(entries))
+}
+
+pub(crate) async fn delete_messages_of_topic(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let topic = str_param(&params, 0, "topic")?;
+    let keep_last = u64_param(&params, 1, "keep_last")?;
+
+    let vid = VirtualNode::gen_did(topic)
+        .map_err(|_| invalid_param("expected valid topic at position 0"))?;
+    meta.processor
+        .storage_truncate(vid, keep_last as usize)
+        .await?;
+
+    Ok(serde_json::json!({}))
 }
 
+/// Register service of node
+/// * Params
+///   - name: name of service
+///   - ttl_ms: optional, [DEFAULT_SERVICE_TTL_MS] if not specified
+///   - metadata: optional [ServiceMetadata] of provider
 pub(crate) async fn register_service(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let name = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
-    meta.processor.register_service(name).await?;
+    let name = str_param(&params, 0, "name")?;
+    let ttl_ms = optional_ms(&params, 1)?.unwrap_or(DEFAULT_SERVICE_TTL_MS);
+    let metadata: ServiceMetadata = match params.get(2) {
+        None | Some(Value::Null) => ServiceMetadata::default(),
+        Some(v) => serde_json::from_value(v.clone())
+            .map_err(|e| invalid_param(format!("expected metadata at position 2: {}", e)))?,
+    };
+    meta.processor
+        .register_service_with_metadata(name, ttl_ms, metadata)
+        .await?;
     Ok(serde_json::json!({}))
 }
 
+/// Lookup providers of service with their metadata
+/// * Params
+///   - name: name of service
+///   - tag: optional, only keep providers tagged with it
 pub(crate) async fn lookup_service(params: Params, meta: RpcMeta) -> Result<Value> {
     meta.require_authed()?;
     let params: Vec<serde_json::Value> = params.parse()?;
-    let name = params
-        .get(0)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?
-        .as_str()
-        .ok_or_else(|| Error::new(ErrorCode::InvalidParams))?;
+    let name = str_param(&params, 0, "name")?;
+    let tag = optional_str_param(&params, 1, "tag")?;
 
-    let rid = VirtualNode::gen_did(name).map_err(|_| Error::new(ErrorCode::InvalidParams))?;
+    let providers = meta
+        .processor
+        .lookup_service(name, tag)
+        .await?
+        .into_iter()
+        .map(|r| response::ServiceProvider {
+            did: r.did,
+            metadata: r.metadata,
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_value(providers).map_err(|_| Error::from(ServerError::EncodeError))
+}
 
-    meta.processor.storage_fetch(rid).await?;
-    let result = meta.processor.storage_check_cache(rid).await;
+/// Send message to a provider of service, without looking it up by client
+/// * Params
+///   - name: name of service
+///   - text: message
+///   - tag: optional, only send to providers tagged with it
+pub(crate) async fn send_to_service(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let name = str_param(&params, 0, "name")?;
+    let text = str_param(&params, 1, "text")?;
+    let tag = optional_str_param(&params, 2, "tag")?;
 
-    if let Some(vnode) = result {
-        let dids = vnode
-            .data
-            .iter()
-            .map(|v| v.decode())
-            .filter_map(|v| v.ok())
-            .collect::<Vec<String>>();
-        Ok(serde_json::json!(dids))
-    } else {
-        Ok(serde_json::json!(Vec::<String>::new()))
-    }
+    let (did, tx_id) = meta
+        .processor
+        .send_to_service(name, tag, text.as_bytes(), None)
+        .await?;
+    Ok(
+        serde_json::to_value(rings_rpc::response::SendToServiceResponse {
+            did: did.to_string(),
+            tx_id: tx_id.to_string(),
+        })
+        .unwrap(),
+    )
+}
+
+/// Ping a peer and measure round-trip time
+/// * Params
+///   - did: did of peer
+///   - count: optional, count of pings, [DEFAULT_PING_COUNT] if not specified
+///   - timeout_ms: optional, time of waiting for the pong of each ping
+pub(crate) async fn ping(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let did = str_param(&params, 0, "did")?;
+    let did = Did::from_str(did).map_err(|_| invalid_param("expected DID at position 0"))?;
+    let count = match params.get(1) {
+        None | Some(Value::Null) => DEFAULT_PING_COUNT,
+        Some(v) => v
+            .as_u64()
+            .map(|v| v as usize)
+            .filter(|v| (1..=MAX_PING_COUNT).contains(v))
+            .ok_or_else(|| {
+                invalid_param(format!(
+                    "expected count in 1..={} at position 1",
+                    MAX_PING_COUNT
+                ))
+            })?,
+    };
+    let timeout_ms = optional_ms(&params, 2)?.unwrap_or(DEFAULT_PING_TIMEOUT_MS);
+    let resp = meta
+        .processor
+        .ping(did, count, std::time::Duration::from_millis(timeout_ms))
+        .await?;
+    serde_json::to_value(resp).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Request node info of a remote node
+/// * Params
+///   - did: did of remote node
+///   - timeout_ms: optional, time of waiting for the reply
+pub(crate) async fn remote_node_info(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let did = str_param(&params, 0, "did")?;
+    let did = Did::from_str(did).map_err(|_| invalid_param("expected DID at position 0"))?;
+    let timeout_ms = optional_ms(&params, 1)?.unwrap_or(DEFAULT_REMOTE_NODE_INFO_TIMEOUT_MS);
+    let info = meta
+        .processor
+        .remote_node_info(did, std::time::Duration::from_millis(timeout_ms))
+        .await?;
+    serde_json::to_value(info).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Walk the ring through successors to discover its topology
+/// * Params
+///   - max_hops: optional, max count of remote nodes requested, [DEFAULT_CRAWL_MAX_HOPS] if not specified
+///   - timeout_ms: optional, time of waiting for the reply of each node
+pub(crate) async fn crawl_ring(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let max_hops = optional_u64_param(&params, 0, "max_hops")?
+        .map(|v| v as usize)
+        .unwrap_or(DEFAULT_CRAWL_MAX_HOPS);
+    let timeout_ms = optional_ms(&params, 1)?.unwrap_or(DEFAULT_REMOTE_NODE_INFO_TIMEOUT_MS);
+    let resp = meta
+        .processor
+        .crawl_ring(max_hops, std::time::Duration::from_millis(timeout_ms))
+        .await?;
+    serde_json::to_value(resp).map_err(|_| Error::from(ServerError::EncodeError))
+}
+
+/// Predict the next hop of a message to destination, without sending it
+/// * Params
+///   - destination: did of destination
+pub(crate) async fn route_query(params: Params, meta: RpcMeta) -> Result<Value> {
+    meta.require_authed()?;
+    let params: Vec<serde_json::Value> = params.parse()?;
+    let destination = str_param(&params, 0, "destination")?;
+    let resp = meta.processor.route_query(destination).await?;
+    serde_json::to_value(resp).map_err(|_| Error::from(ServerError::EncodeError))
 }
 
 #[cfg(feature = "node")]
@@ -525,10 +993,13 @@ mod tests {
     use std::sync::Arc;
 
     use jsonrpc_core::types::params::Params;
+    use serde_json::json;
 
     use super::*;
+    use crate::prelude::rings_core::swarm::HandshakeRng;
     use crate::prelude::*;
     use crate::tests::native::prepare_processor;
+    use crate::tests::native::prepare_processor_builder;
 
     async fn new_rnd_meta() -> RpcMeta {
         let (processor, _) = prepare_processor(None).await;
@@ -547,4 +1018,317 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_deterministic_handshake() {
+        let new_seeded_meta = || async {
+            let (builder, _) = prepare_processor_builder().await;
+            let processor = builder.handshake_seed(42).build().unwrap();
+            RpcMeta::from(Arc::new(processor))
+        };
+        let rng = HandshakeRng::new(42);
+        let transport_id = rng.uuid();
+        let (ufrag, pwd) = rng.ice_credentials();
+        let tx_id = rng.uuid();
+
+        let offer: String = serde_json::from_value(
+            create_offer(Params::None, new_seeded_meta().await)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let encoded = <Encoded as From<&str>>::from(offer.as_str());
+        let payload = MessagePayload::<Message>::from_encoded(&encoded).unwrap();
+        assert_eq!(payload.encode().unwrap(), encoded);
+        assert_eq!(payload.tx_id, tx_id);
+        let Message::ConnectNodeSend(ref offer_msg) = payload.data else {
+            panic!("offer should be ConnectNodeSend");
+        };
+        assert_eq!(offer_msg.transport_uuid, transport_id.to_string());
+        assert!(offer_msg
+            .offer
+            .sdp
+            .contains(&format!("a=ice-ufrag:{}", ufrag)));
+        assert!(offer_msg.offer.sdp.contains(&format!("a=ice-pwd:{}", pwd)));
+
+        // Another node of the same seed reproduces the same ids.
+        let other: String = serde_json::from_value(
+            create_offer(Params::None, new_seeded_meta().await)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let other =
+            MessagePayload::<Message>::from_encoded(&Encoded::from(other.as_str())).unwrap();
+        assert_eq!(other.tx_id, payload.tx_id);
+    }
+
+    #[tokio::test]
+    async fn test_health_without_auth() {
+        let (processor, _) = prepare_processor(None).await;
+        let meta: RpcMeta = (Arc::new(processor), false).into();
+        let health: response::HealthInfo =
+            serde_json::from_value(health(Params::None, meta).await.unwrap()).unwrap();
+        assert!(!health.ready);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_dht() {
+        let (processor, _) = prepare_processor(None).await;
+        let processor = Arc::new(processor);
+
+        let meta: RpcMeta = (processor.clone(), false).into();
+        assert!(inspect_dht(Params::None, meta).await.is_err());
+
+        let meta: RpcMeta = processor.clone().into();
+        let info: response::DHTInfo =
+            serde_json::from_value(inspect_dht(Params::None, meta).await.unwrap()).unwrap();
+        assert_eq!(info.version, response::DHT_INFO_VERSION);
+        assert_eq!(info.did, processor.did().to_string());
+        assert!(info.successors.is_empty());
+        assert!(info.predecessor.is_none());
+        assert!(info.finger_table.iter().all(|f| f.did.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_restart_ice_not_connected() {
+        let (processor, _) = prepare_processor(None).await;
+        let processor = Arc::new(processor);
+        let did = Did::from(SecretKey::random().address()).to_string();
+
+        let meta: RpcMeta = (processor.clone(), false).into();
+        assert!(restart_ice(Params::Array(vec![did.clone().into()]), meta)
+            .await
+            .is_err());
+
+        let meta: RpcMeta = processor.clone().into();
+        let err = restart_ice(Params::Array(vec![did.into()]), meta)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::ServerError(606));
+    }
+
+    #[tokio::test]
+    async fn test_delete_messages_of_topic() {
+        let meta = new_rnd_meta().await;
+        let topic = "test_delete_messages_of_topic";
+        for i in 0..100 {
+            publish_message_to_topic(
+                Params::Array(vec![json!(topic), json!(format!("msg{}", i))]),
+                meta.clone(),
+            )
+            .await
+            .unwrap();
+        }
+
+        delete_messages_of_topic(Params::Array(vec![json!(topic), json!(10)]), meta.clone())
+            .await
+            .unwrap();
+
+        let messages =
+            fetch_messages_of_topic(Params::Array(vec![json!(topic), json!(0)]), meta.clone())
+                .await
+                .unwrap();
+        let messages: Vec<response::TopicEntry> = serde_json::from_value(messages).unwrap();
+        let contents = messages.into_iter().map(|e| e.content).collect::<Vec<_>>();
+        let expected = (90..100).map(|i| format!("msg{}", i)).collect::<Vec<_>>();
+        assert_eq!(contents, expected);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_messages_of_topic_range() {
+        let meta = new_rnd_meta().await;
+        let topic = "test_fetch_messages_of_topic_range";
+        for i in 0..100 {
+            publish_message_to_topic(
+                Params::Array(vec![json!(topic), json!(format!("msg{}", i))]),
+                meta.clone(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let messages = fetch_messages_of_topic(
+            Params::Array(vec![json!(topic), json!(95), json!(3)]),
+            meta.clone(),
+        )
+        .await
+        .unwrap();
+        let messages: Vec<response::TopicEntry> = serde_json::from_value(messages).unwrap();
+        let contents = messages.into_iter().map(|e| e.content).collect::<Vec<_>>();
+        let expected = (95..98).map(|i| format!("msg{}", i)).collect::<Vec<_>>();
+        assert_eq!(contents, expected);
+
+        let messages = fetch_messages_of_topic(
+            Params::Array(vec![json!(topic), json!(98), json!(10)]),
+            meta.clone(),
+        )
+        .await
+        .unwrap();
+        let messages: Vec<response::TopicEntry> = serde_json::from_value(messages).unwrap();
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_signed_messages_of_topic() {
+        let meta = new_rnd_meta().await;
+        let topic = "test_fetch_signed_messages_of_topic";
+        publish_message_to_topic(
+            Params::Array(vec![json!(topic), json!("signed"), json!(true)]),
+            meta.clone(),
+        )
+        .await
+        .unwrap();
+        publish_message_to_topic(
+            Params::Array(vec![json!(topic), json!("plain")]),
+            meta.clone(),
+        )
+        .await
+        .unwrap();
+
+        let messages =
+            fetch_messages_of_topic(Params::Array(vec![json!(topic), json!(0)]), meta.clone())
+                .await
+                .unwrap();
+        let messages: Vec<response::TopicEntry> = serde_json::from_value(messages).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "signed");
+        assert_eq!(
+            messages[0].author_did,
+            Some(meta.processor.did().to_string())
+        );
+        assert!(messages[0].verified);
+        assert_eq!(messages[1].content, "plain");
+        assert_eq!(messages[1].author_did, None);
+        assert!(!messages[1].verified);
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_message_validation() {
+        let meta = new_rnd_meta().await;
+        let did = Did::from(SecretKey::random().address()).to_string();
+        let requests = [
+            json!({"name": "test", "method": "GET", "path": "/", "headers": {"x-test": "a\r\nx-injected: true"}}),
+            json!({"name": "test", "method": "GET", "path": "/", "headers": {"x-test\r\n": "a"}}),
+            json!({"name": "test", "method": "GET", "path": "/", "headers": {"x-test": "caf\u{e9}"}}),
+            json!({"name": "test", "method": "GE T", "path": "/", "headers": {}}),
+            json!({"name": "test", "method": "GET", "path": "/a b", "headers": {}}),
+            json!({"name": "", "method": "GET", "path": "/", "headers": {}}),
+        ];
+        for request in requests {
+            let err = send_http_request_message(
+                Params::Array(vec![json!(did), request.clone()]),
+                meta.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidParams, "{}", request);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_custom_message_reserved_type() {
+        let meta = new_rnd_meta().await;
+        let did = Did::from(SecretKey::random().address()).to_string();
+        let data = base64::encode("hello");
+        for message_type in [
+            0u16,
+            MessageType::SimpleText.into(),
+            MessageType::Ack.into(),
+            255,
+        ] {
+            let err = send_custom_message(
+                Params::Array(vec![json!(did), json!(message_type), json!(data)]),
+                meta.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidParams);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params_hint() {
+        let meta = new_rnd_meta().await;
+        let did = Did::from(SecretKey::random().address()).to_string();
+
+        let hint = |err: Error| err.data.unwrap().as_str().unwrap().to_string();
+        let err = connect_with_did(Params::Array(vec![]), meta.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidParams);
+        assert_eq!(err.message, "Invalid params: missing did at position 0");
+        assert_eq!(hint(err), "missing did at position 0");
+
+        let err = connect_with_did(Params::Array(vec![json!("not a did")]), meta.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(hint(err), "expected DID at position 0");
+
+        let err = send_custom_message(
+            Params::Array(vec![json!(did), json!("1"), json!("")]),
+            meta.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            hint(err),
+            "expected unsigned integer message_type at position 1"
+        );
+
+        let err = send_raw_message(Params::Map(serde_json::Map::new()), meta.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(hint(err), "missing field destination");
+
+        let seed = json!({"peers": [{"did": did, "endpoint": "127.0.0.1:50000"}]});
+        let err = connect_with_seed(Params::Array(vec![seed]), meta.clone())
+            .await
+            .unwrap_err();
+        assert!(
+            hint(err).starts_with("expected seed at position 0: Invalid seed, peers[0].endpoint")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scoped_session_rejected() {
+        let meta = new_rnd_meta()
+            .await
+            .with_scopes(Some(vec!["send".to_string()]));
+        let methods = crate::jsonrpc::handler::methods();
+        let handler = |method: Method| {
+            methods
+                .iter()
+                .find(|(m, _)| m.as_str() == method.as_str())
+                .map(|(_, h)| h)
+                .unwrap()
+        };
+        let did = Did::from(SecretKey::random().address()).to_string();
+
+        // A send-only session is rejected from connecting.
+        let err = handler(Method::ConnectWithDid)(Params::Array(vec![json!(did)]), meta.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.code,
+            ErrorCode::ServerError(ServerError::MissingScope(String::new()).code().into())
+        );
+
+        // It passes the scope check of sending, and fails on params.
+        let err = handler(Method::SendTo)(Params::Map(serde_json::Map::new()), meta.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidParams);
+
+        // Public methods require no scope.
+        assert!(handler(Method::Health)(Params::None, meta.clone())
+            .await
+            .is_ok());
+
+        // A session without scopes is granted all scopes.
+        let err = handler(Method::ConnectWithDid)(Params::Array(vec![]), meta.with_scopes(None))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidParams);
+    }
 }
@@ -1,7 +1,25 @@
 use crate::prelude::rings_rpc::response::CustomBackendMessage;
+use crate::prelude::rings_rpc::response::HttpResponse;
+use crate::prelude::rings_rpc::response::IncomingMessage;
 
 impl From<crate::backend::types::BackendMessage> for CustomBackendMessage {
     fn from(v: crate::backend::types::BackendMessage) -> Self {
         (v.message_type, base64::encode(v.data)).into()
     }
 }
+
+impl From<crate::backend::types::IncomingMessage> for IncomingMessage {
+    fn from(v: crate::backend::types::IncomingMessage) -> Self {
+        Self {
+            from: v.from.to_string(),
+            message_type: v.msg.message_type,
+            data: base64::encode(v.msg.data),
+        }
+    }
+}
+
+impl From<crate::backend::types::HttpResponse> for HttpResponse {
+    fn from(v: crate::backend::types::HttpResponse) -> Self {
+        (v.status, v.headers, v.body.as_deref()).into()
+    }
+}
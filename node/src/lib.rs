@@ -4,14 +4,28 @@ pub mod backend;
 pub mod browser;
 pub mod consts;
 pub mod error;
+pub mod events;
+pub mod fetch;
 pub mod jsonrpc;
 pub mod logging;
 pub mod measure;
+pub mod message_limit;
+pub mod metrics;
 #[cfg(feature = "node")]
 pub mod native;
+pub mod peer_capabilities;
+pub mod peer_sessions;
 pub mod prelude;
 pub mod processor;
+pub mod quality;
+pub mod rate_limit;
+pub mod receipts;
+pub mod reconnect;
 pub mod seed;
+pub mod sending;
+pub mod service_registry;
+pub mod session_cache;
 #[cfg(test)]
 mod tests;
+pub mod topics;
 pub mod util;
@@ -0,0 +1,96 @@
+//! Limit of size of custom messages received by node.
+//!
+//! A message sent in chunks is only reassembled after all of its chunks arrived, so chunks
+//! are summed up by their id as they arrive, and a message exceeding the limit is dropped
+//! before it's reassembled, see [ProcessorBuilder::max_message_bytes](crate::processor::ProcessorBuilder::max_message_bytes).
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::prelude::chunk::Chunk;
+use crate::prelude::rings_core::consts::MAX_TTL_MS;
+use crate::prelude::rings_core::prelude::uuid;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::CustomMessage;
+
+/// Limit of size of custom messages received.
+/// Sizes of chunks are summed up by their id, once the sum exceeds the limit,
+/// the chunk and the rest of its message are dropped, so the message is never reassembled.
+pub(crate) struct MessageSizeLimit {
+    max_bytes: usize,
+    chunked: Mutex<HashMap<uuid::Uuid, ChunkedSize>>,
+}
+
+/// Bytes of chunks of a message received so far.
+struct ChunkedSize {
+    bytes: usize,
+    received: usize,
+    expires_at: u128,
+}
+
+impl MessageSizeLimit {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            chunked: Default::default(),
+        }
+    }
+
+    /// Check whether a received custom message packed by processor is in the limit.
+    /// A message not packed by it is admitted, and left to the callback.
+    pub(crate) fn admit(&self, msg: &CustomMessage) -> bool {
+        if msg.0.len() < 4 {
+            return true;
+        }
+        let (header, data) = msg.0.split_at(4);
+        if header[0] != 1 {
+            return data.len() <= self.max_bytes;
+        }
+        let Ok(chunk) = Chunk::from_bincode(data) else {
+            return true;
+        };
+
+        let now = get_epoch_ms();
+        let mut chunked = self.chunked.lock().unwrap();
+        chunked.retain(|_, size| size.expires_at > now);
+        let size = chunked.entry(chunk.meta.id).or_insert_with(|| ChunkedSize {
+            bytes: 0,
+            received: 0,
+            expires_at: now + chunk.meta.ttl_ms.min(MAX_TTL_MS) as u128,
+        });
+        size.bytes = size.bytes.saturating_add(chunk.data.len());
+        size.received += 1;
+        let admitted = size.bytes <= self.max_bytes;
+        if size.received >= chunk.chunk[1] {
+            chunked.remove(&chunk.meta.id);
+        }
+        admitted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::processor::pack_custom_message;
+
+    #[test]
+    fn test_message_size_limit() {
+        let limit = MessageSizeLimit::new(200);
+        let admit = |data: &[u8]| {
+            let (_, packed) = pack_custom_message(data, 64, false).unwrap();
+            packed
+                .into_iter()
+                .map(|p| limit.admit(&CustomMessage(p)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(admit(&[0u8; 60]), vec![true]);
+        let (_, packed) = pack_custom_message(&[0u8; 201], 1024, false).unwrap();
+        assert!(!limit.admit(&CustomMessage(packed[0].clone())));
+
+        // Chunks are limited by their reassembled total.
+        assert_eq!(admit(&[0u8; 200]), vec![true; 4]);
+        assert_eq!(admit(&[0u8; 300]), vec![true, true, true, false, false]);
+        assert!(limit.chunked.lock().unwrap().is_empty());
+    }
+}
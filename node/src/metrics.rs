@@ -0,0 +1,290 @@
+//! Metrics of node, such as latency of handshakes.
+//!
+//! A handshake is timed from the creation of its offer or answer to the open of data channel,
+//! which is detected by [Processor::wait_for_data_channel_open](crate::processor::Processor::wait_for_data_channel_open).
+//! Latencies are accumulated by [LatencyHistogram].
+//!
+//! On native node, [encode_prometheus] exports counters of swarm, stabilization and handshakes
+//! in Prometheus text format.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+#[cfg(feature = "node")]
+use std::fmt::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::consts::HANDSHAKE_PENDING_STALE_MS;
+use crate::consts::LATENCY_HISTOGRAM_WINDOW;
+use crate::prelude::rings_core::prelude::uuid;
+#[cfg(feature = "node")]
+use crate::prelude::rings_core::transports::manager::TransportManager;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::rings_rpc::response::LatencySummary;
+use crate::prelude::rings_rpc::response::RejectedSessionsSummary;
+
+/// Histogram of latencies in ms.
+/// Count and max are of all samples, percentiles are of the latest [LATENCY_HISTOGRAM_WINDOW] samples.
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    inner: Mutex<LatencyHistogramInner>,
+}
+
+#[derive(Debug, Default)]
+struct LatencyHistogramInner {
+    count: u64,
+    max_ms: u64,
+    samples: VecDeque<u64>,
+}
+
+impl LatencyHistogram {
+    /// Record a latency sample.
+    pub fn record(&self, latency_ms: u64) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        inner.count += 1;
+        inner.max_ms = inner.max_ms.max(latency_ms);
+        if inner.samples.len() >= LATENCY_HISTOGRAM_WINDOW {
+            inner.samples.pop_front();
+        }
+        inner.samples.push_back(latency_ms);
+    }
+
+    /// Summarize recorded samples.
+    pub fn summary(&self) -> LatencySummary {
+        let Ok(inner) = self.inner.lock() else {
+            return LatencySummary::default();
+        };
+        let mut samples = inner.samples.iter().copied().collect::<Vec<_>>();
+        samples.sort_unstable();
+        LatencySummary {
+            count: inner.count,
+            p50_ms: percentile(&samples, 50),
+            p95_ms: percentile(&samples, 95),
+            max_ms: inner.max_ms,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples, 0 if there is no sample.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * p + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+/// Timing of handshakes keyed by transport id.
+#[derive(Debug, Default)]
+pub struct HandshakeMetrics {
+    started: Mutex<HashMap<uuid::Uuid, u128>>,
+    latency: LatencyHistogram,
+}
+
+impl HandshakeMetrics {
+    /// Start timing the handshake of transport.
+    /// Handshakes started more than [HANDSHAKE_PENDING_STALE_MS] ago are forgotten.
+    pub fn start(&self, transport_id: uuid::Uuid) {
+        let now = get_epoch_ms();
+        let Ok(mut started) = self.started.lock() else {
+            return;
+        };
+        started.retain(|_, t| now.saturating_sub(*t) < HANDSHAKE_PENDING_STALE_MS as u128);
+        started.insert(transport_id, now);
+    }
+
+    /// Finish timing the handshake of transport, and record its latency.
+    /// Return the latency, `None` if the handshake is not started or already finished.
+    pub fn finish(&self, transport_id: uuid::Uuid) -> Option<u64> {
+        let started = self.started.lock().ok()?.remove(&transport_id)?;
+        let latency_ms = get_epoch_ms().saturating_sub(started) as u64;
+        self.latency.record(latency_ms);
+        Some(latency_ms)
+    }
+
+    /// Summarize latencies of finished handshakes.
+    pub fn summary(&self) -> LatencySummary {
+        self.latency.summary()
+    }
+}
+
+/// Counters of sessions rejected by local clock when authenticating requests.
+#[derive(Debug, Default)]
+pub struct SessionClockMetrics {
+    expired: AtomicU64,
+    not_yet_valid: AtomicU64,
+}
+
+impl SessionClockMetrics {
+    /// Count a failure of session verification if it's caused by time of session.
+    pub fn record(&self, error: &rings_core::error::Error) {
+        let counter = match error {
+            rings_core::error::Error::SessionExpired => &self.expired,
+            rings_core::error::Error::SessionNotYetValid(_, _) => &self.not_yet_valid,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Summarize counters.
+    pub fn summary(&self) -> RejectedSessionsSummary {
+        RejectedSessionsSummary {
+            expired: self.expired.load(Ordering::Relaxed),
+            not_yet_valid: self.not_yet_valid.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Write a metric of Prometheus text format, with its help and type.
+#[cfg(feature = "node")]
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    writeln!(out, "# HELP {} {}", name, help).ok();
+    writeln!(out, "# TYPE {} {}", name, kind).ok();
+    writeln!(out, "{} {}", name, value).ok();
+}
+
+/// Encode metrics of node in Prometheus text format.
+#[cfg(feature = "node")]
+pub async fn encode_prometheus(processor: &crate::processor::Processor) -> String {
+    let stats = processor.swarm.stats();
+    let pending_transports = processor
+        .swarm
+        .pending_transports()
+        .await
+        .map(|p| p.len())
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    #[rustfmt::skip]
+    let metrics = [
+        ("rings_messages_sent_total", "counter", "Messages sent to transports.", stats.messages_sent()),
+        ("rings_messages_received_total", "counter", "Messages received from transports.", stats.messages_received()),
+        ("rings_bytes_received_total", "counter", "Bytes of messages received from transports.", stats.bytes_received()),
+        ("rings_bytes_relayed_total", "counter", "Bytes of received messages destined to other nodes.", stats.bytes_relayed()),
+        ("rings_transports_opened_total", "counter", "Transports registered to swarm.", stats.transports_opened()),
+        ("rings_transports_closed_total", "counter", "Transports removed from swarm.", stats.transports_closed()),
+        ("rings_active_transports", "gauge", "Connected transports.", processor.swarm.get_transports().len() as u64),
+        ("rings_pending_transports", "gauge", "Transports in handshake.", pending_transports as u64),
+        ("rings_max_pending_transports", "gauge", "Max transports in handshake.", processor.swarm.max_pending_transports() as u64),
+        ("rings_stabilization_runs_total", "counter", "Finished rounds of stabilization.", processor.stabilization.rounds()),
+    ];
+    for (name, kind, help, value) in metrics {
+        write_metric(&mut out, name, kind, help, value);
+    }
+
+    let metrics = processor.metrics();
+    let rejected = metrics.rejected_sessions;
+    write_metric(
+        &mut out,
+        "rings_sessions_expired_total",
+        "counter",
+        "Sessions rejected since they're expired by local clock.",
+        rejected.expired,
+    );
+    write_metric(
+        &mut out,
+        "rings_sessions_not_yet_valid_total",
+        "counter",
+        "Sessions rejected since they're created ahead of local clock.",
+        rejected.not_yet_valid,
+    );
+
+    let handshake = metrics.handshake;
+    write_metric(
+        &mut out,
+        "rings_handshakes_total",
+        "counter",
+        "Finished handshakes.",
+        handshake.count,
+    );
+    let name = "rings_handshake_latency_ms";
+    writeln!(out, "# HELP {} Latency of latest handshakes.", name).ok();
+    writeln!(out, "# TYPE {} gauge", name).ok();
+    for (quantile, value) in [
+        ("0.5", handshake.p50_ms),
+        ("0.95", handshake.p95_ms),
+        ("1", handshake.max_ms),
+    ] {
+        writeln!(out, "{}{{quantile=\"{}\"}} {}", name, quantile, value).ok();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.summary(), LatencySummary::default());
+
+        for ms in (1..=100).rev() {
+            histogram.record(ms);
+        }
+        assert_eq!(histogram.summary(), LatencySummary {
+            count: 100,
+            p50_ms: 50,
+            p95_ms: 95,
+            max_ms: 100,
+        });
+
+        // Percentiles are of the latest samples, max is of all.
+        for _ in 0..LATENCY_HISTOGRAM_WINDOW {
+            histogram.record(10);
+        }
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 100 + LATENCY_HISTOGRAM_WINDOW as u64);
+        assert_eq!((summary.p50_ms, summary.p95_ms), (10, 10));
+        assert_eq!(summary.max_ms, 100);
+    }
+
+    #[test]
+    fn test_handshake_metrics() {
+        let metrics = HandshakeMetrics::default();
+        let id = uuid::Uuid::new_v4();
+        assert!(metrics.finish(id).is_none());
+
+        metrics.start(id);
+        assert!(metrics.finish(id).is_some());
+        assert!(metrics.finish(id).is_none());
+        assert_eq!(metrics.summary().count, 1);
+    }
+
+    #[test]
+    fn test_session_clock_metrics() {
+        let metrics = SessionClockMetrics::default();
+        metrics.record(&rings_core::error::Error::SessionExpired);
+        metrics.record(&rings_core::error::Error::SessionNotYetValid(2, 1));
+        metrics.record(&rings_core::error::Error::SessionNotYetValid(2, 1));
+        metrics.record(&rings_core::error::Error::VerifySignatureFailed);
+        assert_eq!(metrics.summary(), RejectedSessionsSummary {
+            expired: 1,
+            not_yet_valid: 2,
+        });
+    }
+
+    #[cfg(feature = "node")]
+    #[tokio::test]
+    async fn test_encode_prometheus() {
+        let (p, path) = crate::tests::native::prepare_processor(None).await;
+        let text = encode_prometheus(&p).await;
+        for line in [
+            "# TYPE rings_messages_sent_total counter",
+            "rings_messages_sent_total 0",
+            "# TYPE rings_active_transports gauge",
+            "rings_active_transports 0",
+            "rings_pending_transports 0",
+            "rings_max_pending_transports 128",
+            "rings_stabilization_runs_total 0",
+            "rings_handshake_latency_ms{quantile=\"0.95\"} 0",
+            "rings_sessions_not_yet_valid_total 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}", line);
+        }
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+}
@@ -30,6 +30,7 @@ use crate::prelude::http;
 use crate::prelude::rings_core::inspect::SwarmInspect;
 use crate::prelude::rings_core::session::SessionManager;
 use crate::prelude::rings_rpc::client::Client as RpcClient;
+use crate::prelude::rings_rpc::types::ServiceMetadata;
 use crate::prelude::rings_rpc::types::Timeout;
 use crate::seed::Seed;
 use crate::util::loader::ResourceLoader;
@@ -64,10 +65,14 @@ impl Client {
     ///
     /// Takes a URL for an HTTP server that will be used as the signaling channel to exchange ICE candidates and SDP with the remote peer.
     /// Returns a transport ID that can be used to refer to this connection in subsequent WebRTC operations.
-    pub async fn connect_peer_via_http(&mut self, http_url: &str) -> Output<String> {
+    pub async fn connect_peer_via_http(
+        &mut self,
+        http_url: &str,
+        timeout_ms: Option<u64>,
+    ) -> Output<String> {
         let transport_id = self
             .client
-            .connect_peer_via_http(http_url)
+            .connect_peer_via_http(http_url, timeout_ms)
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
 
@@ -75,12 +80,13 @@ impl Client {
     }
 
     /// Attempts to connect to a peer using a seed file located at the specified source path.
-    pub async fn connect_with_seed(&mut self, source: &str) -> Output<()> {
+    pub async fn connect_with_seed(&mut self, source: &str, timeout_ms: Option<u64>) -> Output<()> {
         let seed = Seed::load(source).await?;
+        seed.validate()?;
         let seed_v = serde_json::to_value(seed).map_err(|_| anyhow::anyhow!("serialize failed"))?;
 
         self.client
-            .connect_with_seed(&[seed_v])
+            .connect_with_seed(&seed_v, timeout_ms)
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
 
@@ -88,12 +94,14 @@ impl Client {
     }
 
     /// Attempts to connect to a peer using a DID stored in a Distributed Hash Table (DHT).
-    pub async fn connect_with_did(&mut self, did: &str) -> Output<()> {
-        self.client
-            .connect_with_did(did)
+    pub async fn connect_with_did(&mut self, did: &str, timeout_ms: Option<u64>) -> Output<String> {
+        let transport_id = self
+            .client
+            .connect_with_did(did, timeout_ms)
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
-        ClientOutput::ok("Successful!".to_owned(), ())
+
+        ClientOutput::ok(format!("Your transport_id: {}", transport_id), transport_id)
     }
 
     /// Lists all connected peers and their status.
@@ -208,24 +216,45 @@ impl Client {
         ClientOutput::ok("Done.".into(), ())
     }
 
-    /// Registers a new service with the given name.
-    pub async fn register_service(&self, name: &str) -> Output<()> {
+    /// Registers a new service with the given name and metadata.
+    pub async fn register_service(
+        &self,
+        name: &str,
+        ttl_ms: Option<u64>,
+        metadata: &ServiceMetadata,
+    ) -> Output<()> {
         self.client
-            .register_service(name)
+            .register_service(name, ttl_ms, Some(metadata))
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
         ClientOutput::ok("Done.".into(), ())
     }
 
-    /// Looks up the DIDs of services registered with the given name.
-    pub async fn lookup_service(&self, name: &str) -> Output<()> {
-        let dids = self
+    /// Looks up the providers of services registered with the given name, filtered by `tag`.
+    pub async fn lookup_service(&self, name: &str, tag: Option<&str>) -> Output<()> {
+        let providers = self
             .client
-            .lookup_service(name)
+            .lookup_service(name, tag)
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-        ClientOutput::ok(dids.join("\n"), ())
+        let lines = providers
+            .iter()
+            .map(|p| {
+                let mut line = p.did.clone();
+                if let Some(version) = &p.metadata.version {
+                    line.push_str(&format!(" version={}", version));
+                }
+                if !p.metadata.tags.is_empty() {
+                    line.push_str(&format!(" tags={}", p.metadata.tags.join(",")));
+                }
+                if let Some(endpoint) = &p.metadata.endpoint {
+                    line.push_str(&format!(" endpoint={}", endpoint));
+                }
+                line
+            })
+            .collect::<Vec<_>>();
+        ClientOutput::ok(lines.join("\n"), ())
     }
 
     /// Publishes a message to the specified topic.
@@ -265,7 +294,7 @@ impl Client {
                     }
                     let messages = result.unwrap();
                     for msg in messages.iter().cloned() {
-                        yield msg
+                        yield msg.content
                     }
                     index += messages.len();
                     }
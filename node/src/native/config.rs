@@ -10,7 +10,10 @@ use crate::backend::extension::ExtensionConfig;
 use crate::backend::service::http_server::HiddenServerConfig;
 use crate::error::Error;
 use crate::error::Result;
+use crate::prelude::rings_core::consts::DEFAULT_MAX_CONCURRENT_OFFERS;
+use crate::prelude::rings_core::consts::DEFAULT_SESSION_CLOCK_SKEW_MS;
 use crate::prelude::rings_core::ecc::SecretKey;
+use crate::prelude::rings_core::transports::backpressure::Backpressure;
 use crate::prelude::SessionManager;
 use crate::processor::ProcessorConfig;
 
@@ -41,15 +44,38 @@ where P: AsRef<std::path::Path> {
     expect.to_str().unwrap().to_string()
 }
 
+fn default_max_concurrent_offers() -> usize {
+    DEFAULT_MAX_CONCURRENT_OFFERS
+}
+
+fn default_session_clock_skew_ms() -> u64 {
+    DEFAULT_SESSION_CLOCK_SKEW_MS as u64
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub session_manager: String,
     #[serde(rename = "bind")]
     pub http_addr: String,
     pub endpoint_url: String,
+    /// Listen address of metrics server, which is not run if it's not set.
+    #[serde(default)]
+    pub metrics_bind: Option<String>,
+    /// Pre-shared bearer token of jsonrpc server, requests are authed only by signature if it's not set.
+    #[serde(default)]
+    pub rpc_token: Option<String>,
     pub ice_servers: String,
     pub stabilize_timeout: usize,
     pub external_ip: Option<String>,
+    /// Watermarks of send queue of each transport, in bytes.
+    #[serde(default)]
+    pub send_queue: Backpressure,
+    /// Max count of offers generated concurrently, excess ones wait for a slot.
+    #[serde(default = "default_max_concurrent_offers")]
+    pub max_concurrent_offers: usize,
+    /// Tolerance in ms of clock skew when verifying sessions which sign requests.
+    #[serde(default = "default_session_clock_skew_ms")]
+    pub session_clock_skew_ms: u64,
     /// When there is no configuration in the YAML file,
     /// its deserialization is equivalent to `vec![]` in Rust.
     #[serde(default)]
@@ -84,9 +110,14 @@ impl Config {
             session_manager,
             http_addr: DEFAULT_BIND_ADDRESS.to_string(),
             endpoint_url: DEFAULT_ENDPOINT_URL.to_string(),
+            metrics_bind: None,
+            rpc_token: None,
             ice_servers: DEFAULT_ICE_SERVERS.to_string(),
             stabilize_timeout: DEFAULT_STABILIZE_TIMEOUT,
             external_ip: None,
+            send_queue: Backpressure::default(),
+            max_concurrent_offers: DEFAULT_MAX_CONCURRENT_OFFERS,
+            session_clock_skew_ms: default_session_clock_skew_ms(),
             backend: vec![],
             data_storage: DEFAULT_DATA_STORAGE_CONFIG.clone(),
             measure_storage: DEFAULT_MEASURE_STORAGE_CONFIG.clone(),
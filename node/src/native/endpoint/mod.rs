@@ -18,12 +18,14 @@ use tokio::sync::Mutex;
 use tower_http::cors::CorsLayer;
 
 use self::http_error::HttpError;
-use crate::backend::types::BackendMessage;
+use crate::backend::types::IncomingMessage;
 use crate::jsonrpc::RpcMeta;
 use crate::prelude::http::header;
 use crate::prelude::http::HeaderMap;
 use crate::prelude::http::HeaderValue;
 use crate::prelude::jsonrpc_core::MetaIoHandler;
+use crate::prelude::rings_core::ecc::signers::constant_time_eq;
+use crate::prelude::rings_core::session::Session;
 use crate::prelude::rings_rpc::response::NodeInfo;
 use crate::processor::Processor;
 
@@ -34,15 +36,16 @@ impl crate::prelude::jsonrpc_core::Metadata for RpcMeta {}
 pub struct JsonrpcState {
     processor: Arc<Processor>,
     io_handler: Arc<MetaIoHandler<RpcMeta>>,
-    receiver: Arc<Mutex<Receiver<BackendMessage>>>,
+    receiver: Arc<Mutex<Receiver<IncomingMessage>>>,
+    /// pre-shared bearer token, requests are authed only by signature if it's not set
+    token: Option<String>,
 }
 
 /// websocket state
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct WsState {
     processor: Arc<Processor>,
-    receiver: Arc<Receiver<BackendMessage>>,
+    receiver: Arc<Receiver<IncomingMessage>>,
 }
 
 /// Status state
@@ -51,12 +54,43 @@ pub struct StatusState {
     processor: Arc<Processor>,
 }
 
-/// Run a web server to handle jsonrpc request
+/// Run a web server to export metrics in Prometheus text format at `/metrics`,
+/// it's separated from jsonrpc server for monitoring.
+pub async fn run_metrics_api(addr: String, processor: Arc<Processor>) -> anyhow::Result<()> {
+    let binding_addr: SocketAddr = addr.parse()?;
+    let shutdown_signal = processor.shutdown_signal();
+    let status_state = Arc::new(StatusState { processor });
+
+    let axum_make_service = Router::new()
+        .route("/metrics", get(metrics_handler).with_state(status_state))
+        .into_make_service();
+
+    println!("Metrics endpoint: http://{}/metrics", addr);
+    axum::Server::bind(&binding_addr)
+        .serve(axum_make_service)
+        .with_graceful_shutdown(shutdown_signal)
+        .await?;
+    Ok(())
+}
+
+async fn metrics_handler(State(state): State<Arc<StatusState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::encode_prometheus(&state.processor).await,
+    )
+}
+
+/// Run a web server to handle jsonrpc request.
+/// If `token` is set, requests with HEAD['Authorization'] of `Bearer <token>` are authed as well.
 pub async fn run_http_api(
     addr: String,
     processor: Arc<Processor>,
-    receiver: Receiver<BackendMessage>,
+    receiver: Receiver<IncomingMessage>,
+    token: Option<String>,
 ) -> anyhow::Result<()> {
+    if token.as_deref().map_or(false, |t| t.trim().is_empty()) {
+        return Err(anyhow::anyhow!("rpc token should not be empty"));
+    }
     let binding_addr = addr.parse().unwrap();
 
     let mut jsonrpc_handler: MetaIoHandler<RpcMeta> = MetaIoHandler::default();
@@ -67,6 +101,7 @@ pub async fn run_http_api(
         processor: processor.clone(),
         io_handler: jsonrpc_handler_layer,
         receiver: Arc::new(Mutex::new(receiver.resubscribe())),
+        token,
     });
 
     let ws_state = Arc::new(WsState {
@@ -74,6 +109,7 @@ pub async fn run_http_api(
         receiver: Arc::new(receiver.resubscribe()),
     });
 
+    let shutdown_signal = processor.shutdown_signal();
     let status_state = Arc::new(StatusState { processor });
 
     let axum_make_service = Router::new()
@@ -91,43 +127,93 @@ pub async fn run_http_api(
     println!("WebSocket endpoint: http://{}/ws", addr);
     axum::Server::bind(&binding_addr)
         .serve(axum_make_service)
+        .with_graceful_shutdown(shutdown_signal)
         .await?;
     Ok(())
 }
 
+/// Verify signature of request from HEAD['X-SIGNATURE'].
+/// The request is signed by the session of node, or by the session of HEAD['X-SESSION'],
+/// which should be authorized by the same authorizer of node. Revoked sessions are rejected.
+/// If a non-empty `token` is set and the request carries the same bearer token, it's authed
+/// and granted the scopes of node's session. Otherwise its signature is verified.
+/// Return whether request is authed, and the scopes granted to its session.
+fn verify_request(
+    processor: &Processor,
+    token: Option<&str>,
+    headermap: &HeaderMap,
+    body: &str,
+) -> Result<(bool, Option<Vec<String>>), HttpError> {
+    let node_session = processor.swarm.session_manager().session();
+    let token = token.filter(|t| !t.trim().is_empty());
+    if let (Some(token), Some(bearer)) = (token, bearer_token(headermap)) {
+        if constant_time_eq(bearer.as_bytes(), token.as_bytes()) {
+            return Ok((true, node_session.scopes().map(|s| s.to_vec())));
+        }
+        tracing::error!("bearer token verify failed, fall back to signature");
+    }
+    let Some(signature) = headermap.get("X-SIGNATURE") else {
+        return Ok((false, node_session.scopes().map(|s| s.to_vec())));
+    };
+    let sig = base64::decode(signature).map_err(|e| {
+        tracing::debug!("signature: {:?}", signature);
+        tracing::error!("signature decode failed: {:?}", e);
+        HttpError::BadRequest
+    })?;
+    let session = match headermap.get("X-SESSION") {
+        Some(encoded) => {
+            let session: Session = base64::decode(encoded)
+                .ok()
+                .and_then(|s| serde_json::from_slice(&s).ok())
+                .ok_or_else(|| {
+                    tracing::error!("session decode failed: {:?}", encoded);
+                    HttpError::BadRequest
+                })?;
+            if session.authorizer_did() != node_session.authorizer_did() {
+                tracing::error!("session of request is not authorized by node's authorizer");
+                return Ok((false, session.scopes().map(|s| s.to_vec())));
+            }
+            session
+        }
+        None => node_session,
+    };
+    if let Err(e) = processor.verify_session(&session) {
+        tracing::error!("session verify failed: {:?}", e);
+        return Ok((false, session.scopes().map(|s| s.to_vec())));
+    }
+    let scopes = session.scopes().map(|s| s.to_vec());
+    let is_auth = session
+        .verify(body, sig)
+        .map_err(|e| {
+            tracing::debug!("body: {:?}", body);
+            tracing::debug!("signature: {:?}", signature);
+            tracing::error!("signature verify failed: {:?}", e);
+            e
+        })
+        .is_ok();
+    Ok((is_auth, scopes))
+}
+
+/// Bearer token from HEAD['Authorization'].
+fn bearer_token(headermap: &HeaderMap) -> Option<&str> {
+    headermap
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
 async fn jsonrpc_io_handler(
     State(state): State<Arc<JsonrpcState>>,
     headermap: HeaderMap,
     body: String,
 ) -> Result<JsonResponse, HttpError> {
-    let is_auth = if let Some(signature) = headermap.get("X-SIGNATURE") {
-        let sig = base64::decode(signature).map_err(|e| {
-            tracing::debug!("signature: {:?}", signature);
-            tracing::error!("signature decode failed: {:?}", e);
-            HttpError::BadRequest
-        })?;
-        state
-            .processor
-            .swarm
-            .session_manager()
-            .session()
-            .verify(&body, sig)
-            .map_err(|e| {
-                tracing::debug!("body: {:?}", body);
-                tracing::debug!("signature: {:?}", signature);
-                tracing::error!("signature verify failed: {:?}", e);
-                e
-            })
-            .is_ok()
-    } else {
-        false
-    };
+    let (is_auth, scopes) =
+        verify_request(&state.processor, state.token.as_deref(), &headermap, &body)?;
+    let meta: RpcMeta = (state.processor.clone(), state.receiver.clone(), is_auth).into();
     let r = state
         .io_handler
-        .handle_request(
-            &body,
-            (state.processor.clone(), state.receiver.clone(), is_auth).into(),
-        )
+        .handle_request(&body, meta.with_scopes(scopes))
         .await
         .ok_or(HttpError::BadRequest)?;
     Ok(JsonResponse(r))
@@ -182,3 +268,162 @@ async fn ws_handler(
     tracing::info!("ws connected, remote: {}", addr);
     ws.on_upgrade(move |socket| self::ws::handle_socket(state, socket))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_verify_request_with_revoked_session() {
+        let (processor, path) = prepare_processor(None).await;
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "nodeInfo", "params": []}"#;
+        let sig = processor.swarm.session_manager().sign(body).unwrap();
+        let mut headermap = HeaderMap::new();
+        headermap.insert(
+            "X-SIGNATURE",
+            HeaderValue::from_str(&base64::encode(sig)).unwrap(),
+        );
+        assert!(
+            verify_request(&processor, None, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        // The session is valid and unexpired, but revoked.
+        let session = processor.swarm.session_manager().session();
+        assert!(session.verify_self().is_ok());
+        assert!(processor.revoke_session(session.session_id()).unwrap());
+        assert!(!processor.revoke_session(session.session_id()).unwrap());
+        assert!(processor.verify_session(&session).is_err());
+        assert!(
+            !verify_request(&processor, None, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_request_with_bearer_token() {
+        let (processor, path) = prepare_processor(None).await;
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "nodeInfo", "params": []}"#;
+        let token = Some("secret-token");
+
+        let mut headermap = HeaderMap::new();
+        headermap.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret-token"),
+        );
+        assert!(
+            verify_request(&processor, token, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        headermap.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer wrong-token"),
+        );
+        assert!(
+            !verify_request(&processor, token, &headermap, body)
+                .unwrap()
+                .0
+        );
+        headermap.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("secret-token"),
+        );
+        assert!(
+            !verify_request(&processor, token, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        // Without token configured, bearer token is ignored, and only signature is verified.
+        headermap.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret-token"),
+        );
+        assert!(
+            !verify_request(&processor, None, &headermap, body)
+                .unwrap()
+                .0
+        );
+        let sig = processor.swarm.session_manager().sign(body).unwrap();
+        headermap.insert(
+            "X-SIGNATURE",
+            HeaderValue::from_str(&base64::encode(sig)).unwrap(),
+        );
+        assert!(
+            verify_request(&processor, None, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_request_with_empty_token() {
+        let (processor, path) = prepare_processor(None).await;
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "nodeInfo", "params": []}"#;
+
+        let mut headermap = HeaderMap::new();
+        headermap.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer "));
+        for token in ["", " "] {
+            assert!(
+                !verify_request(&processor, Some(token), &headermap, body)
+                    .unwrap()
+                    .0
+            );
+        }
+
+        // An empty token is rejected before serving.
+        let (_, receiver) = tokio::sync::broadcast::channel(1);
+        let processor = Arc::new(processor);
+        let result = run_http_api(
+            "127.0.0.1:0".to_string(),
+            processor,
+            receiver,
+            Some(" ".to_string()),
+        )
+        .await;
+        assert!(result.is_err());
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_request_with_wrong_bearer_token_and_signature() {
+        let (processor, path) = prepare_processor(None).await;
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "nodeInfo", "params": []}"#;
+        let token = Some("secret-token");
+
+        let mut headermap = HeaderMap::new();
+        headermap.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer wrong-token"),
+        );
+        assert!(
+            !verify_request(&processor, token, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        // A request with mismatched bearer token is still authed by its signature.
+        let sig = processor.swarm.session_manager().sign(body).unwrap();
+        headermap.insert(
+            "X-SIGNATURE",
+            HeaderValue::from_str(&base64::encode(sig)).unwrap(),
+        );
+        assert!(
+            verify_request(&processor, token, &headermap, body)
+                .unwrap()
+                .0
+        );
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+}
@@ -1,46 +1,369 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use axum::extract::ws::Message;
 use axum::extract::ws::WebSocket;
 use futures::SinkExt;
 use futures::StreamExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
 
 use super::WsState;
+use crate::backend::types::IncomingMessage;
+use crate::jsonrpc::server::invalid_param;
+use crate::prelude::jsonrpc_core::Call;
+use crate::prelude::jsonrpc_core::Error;
+use crate::prelude::jsonrpc_core::ErrorCode;
+use crate::prelude::jsonrpc_core::Id;
+use crate::prelude::jsonrpc_core::MethodCall;
+use crate::prelude::jsonrpc_core::Notification;
+use crate::prelude::jsonrpc_core::Output;
+use crate::prelude::jsonrpc_core::Params;
+use crate::prelude::jsonrpc_core::Request;
+use crate::prelude::jsonrpc_core::Result;
+use crate::prelude::jsonrpc_core::Version;
+use crate::prelude::rings_rpc::method::Method;
+use crate::prelude::rings_rpc::response;
 use crate::prelude::rings_rpc::response::BaseResponse;
 use crate::prelude::rings_rpc::response::CustomBackendMessage;
+use crate::prelude::rings_rpc::response::TopicMessage;
+use crate::prelude::uuid;
+use crate::prelude::vnode::VirtualNode;
+use crate::processor::Processor;
+
+/// Size of outgoing buffer of a websocket connection.
+/// Subscriptions of a client who can not keep up with it will be dropped.
+pub const WS_BUFFER_SIZE: usize = 256;
+
+/// Subscriptions of backend messages on one websocket connection.
+/// Each subscription holds its own receiver of the broadcast channel,
+/// and pushes messages as jsonrpc notifications to the outgoing buffer.
+/// Until the client subscribes, messages are pushed as `custom_message` by default.
+/// Messages of topics are pushed the same way when the processor is set.
+pub struct Subscriptions {
+    receiver: Arc<Receiver<IncomingMessage>>,
+    outgoing: mpsc::Sender<String>,
+    dropped: mpsc::UnboundedSender<String>,
+    tasks: HashMap<String, JoinHandle<()>>,
+    default_push: Option<JoinHandle<()>>,
+    processor: Option<Arc<Processor>>,
+}
+
+impl Subscriptions {
+    /// Create subscriptions that push notifications to `outgoing`.
+    /// When a subscriber is dropped for it can not keep up, an error notification is sent
+    /// to `dropped`, which is not limited by the outgoing buffer.
+    pub fn new(
+        receiver: Arc<Receiver<IncomingMessage>>,
+        outgoing: mpsc::Sender<String>,
+        dropped: mpsc::UnboundedSender<String>,
+    ) -> Self {
+        let default_push = tokio::spawn(Self::forward(
+            None,
+            receiver.resubscribe(),
+            outgoing.clone(),
+            dropped.clone(),
+        ));
+        Self {
+            receiver,
+            outgoing,
+            dropped,
+            tasks: HashMap::new(),
+            default_push: Some(default_push),
+            processor: None,
+        }
+    }
+
+    /// Set the processor which topics are subscribed from.
+    pub fn processor(mut self, processor: Arc<Processor>) -> Self {
+        self.processor = Some(processor);
+        self
+    }
+
+    /// Handle a jsonrpc request in text, return the response in text.
+    /// Notifications of client will not be responded.
+    pub fn handle_request(&mut self, text: &str) -> Option<String> {
+        let output = match serde_json::from_str::<Request>(text) {
+            Ok(Request::Single(Call::MethodCall(call))) => self.handle_call(call),
+            Ok(Request::Single(Call::Notification(_))) => return None,
+            Ok(_) => Output::invalid_request(Id::Null, Some(Version::V2)),
+            Err(_) => Output::from(
+                Err(Error::new(ErrorCode::ParseError)),
+                Id::Null,
+                Some(Version::V2),
+            ),
+        };
+        serde_json::to_string(&output).ok()
+    }
+
+    fn handle_call(&mut self, call: MethodCall) -> Output {
+        let result = match Method::try_from(call.method.as_str()) {
+            Ok(Method::SubscribeMessages) => Ok(self.subscribe().into()),
+            Ok(Method::UnsubscribeMessages) => self.handle_unsubscribe(call.params),
+            Ok(Method::SubscribeTopic) => self.handle_subscribe_topic(call.params),
+            Ok(Method::UnsubscribeTopic) => self.handle_unsubscribe(call.params),
+            _ => Err(Error::method_not_found()),
+        };
+        Output::from(result, call.id, call.jsonrpc)
+    }
+
+    fn handle_unsubscribe(&mut self, params: Params) -> Result<serde_json::Value> {
+        let params: Vec<String> = params.parse()?;
+        let id = params
+            .first()
+            .ok_or_else(|| invalid_param("missing subscription id at position 0"))?;
+        Ok(self.unsubscribe(id).into())
+    }
+
+    fn handle_subscribe_topic(&mut self, params: Params) -> Result<serde_json::Value> {
+        let params: Vec<serde_json::Value> = params.parse()?;
+        let topic = params
+            .first()
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| invalid_param("expected string topic at position 0"))?;
+        let index = match params.get(1) {
+            None | Some(serde_json::Value::Null) => 0,
+            Some(v) => v
+                .as_u64()
+                .ok_or_else(|| invalid_param("expected integer index at position 1"))?,
+        };
+        self.subscribe_topic(topic, index as usize).map(Into::into)
+    }
+
+    /// Subscribe messages of topic from `index` on, return the subscription id.
+    pub fn subscribe_topic(&mut self, topic: &str, index: usize) -> Result<String> {
+        let processor = self.processor.clone().ok_or_else(Error::internal_error)?;
+        VirtualNode::gen_did(topic).map_err(|_| invalid_param("expected valid topic"))?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let task = tokio::spawn(Self::forward_topic(
+            id.clone(),
+            processor,
+            topic.to_string(),
+            index,
+            self.outgoing.clone(),
+            self.dropped.clone(),
+        ));
+        self.tasks.retain(|_, t| !t.is_finished());
+        self.tasks.insert(id.clone(), task);
+        Ok(id)
+    }
+
+    /// Subscribe backend messages, return the subscription id.
+    /// The default push of `custom_message` is stopped by the first subscription.
+    pub fn subscribe(&mut self) -> String {
+        if let Some(task) = self.default_push.take() {
+            task.abort();
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let task = tokio::spawn(Self::forward(
+            Some(id.clone()),
+            self.receiver.resubscribe(),
+            self.outgoing.clone(),
+            self.dropped.clone(),
+        ));
+        self.tasks.retain(|_, t| !t.is_finished());
+        self.tasks.insert(id.clone(), task);
+        id
+    }
+
+    /// Unsubscribe backend messages, return false if the subscription is not found.
+    pub fn unsubscribe(&mut self, id: &str) -> bool {
+        if let Some(task) = self.tasks.remove(id) {
+            task.abort();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forward messages to outgoing buffer, for the subscription of `id`,
+    /// or as `custom_message` of default push if `id` is `None`.
+    async fn forward(
+        id: Option<String>,
+        mut receiver: Receiver<IncomingMessage>,
+        outgoing: mpsc::Sender<String>,
+        dropped: mpsc::UnboundedSender<String>,
+    ) {
+        let name = id.as_deref().unwrap_or("default");
+        loop {
+            let msg = match receiver.recv().await {
+                Ok(msg) => msg,
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!("ws subscription {} lagged {} messages, dropped", name, n);
+                    break;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let data = match id {
+                Some(ref id) => notification(id, msg),
+                None => custom_message(msg),
+            };
+            let Some(data) = data else {
+                continue;
+            };
+            match outgoing.try_send(data) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("ws subscription {} can not keep up, dropped", name);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+        if let Some(notification) =
+            id.and_then(|id| dropped_notification(Method::SubscribeMessages, &id))
+        {
+            dropped.send(notification).ok();
+        }
+    }
+
+    /// Forward messages of topic to outgoing buffer for the subscription of `id`.
+    async fn forward_topic(
+        id: String,
+        processor: Arc<Processor>,
+        topic: String,
+        index: usize,
+        outgoing: mpsc::Sender<String>,
+        dropped: mpsc::UnboundedSender<String>,
+    ) {
+        let mut subscription = match processor.subscribe_topic(&topic, index).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                tracing::warn!("ws subscription {} of topic {} failed: {}", id, topic, e);
+                return;
+            }
+        };
+        while let Some(msg) = subscription.recv().await {
+            let Some(data) = topic_notification(&id, msg) else {
+                continue;
+            };
+            match outgoing.try_send(data) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("ws subscription {} can not keep up, dropped", id);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+        if let Some(notification) = dropped_notification(Method::SubscribeTopic, &id) {
+            dropped.send(notification).ok();
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for task in self.tasks.values().chain(self.default_push.iter()) {
+            task.abort();
+        }
+    }
+}
+
+/// Build `custom_message` of backend message for default push.
+fn custom_message(msg: IncomingMessage) -> Option<String> {
+    let data = BaseResponse::new(
+        "custom_message".to_owned(),
+        CustomBackendMessage::from(msg.msg),
+    );
+    serde_json::to_string(&data).ok()
+}
+
+/// Build jsonrpc notification of error for a subscription dropped by node.
+fn dropped_notification(method: Method, id: &str) -> Option<String> {
+    let mut params = serde_json::Map::new();
+    params.insert("subscription".to_owned(), id.into());
+    params.insert(
+        "error".to_owned(),
+        serde_json::to_value(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: "subscription dropped, it can not keep up with messages".to_owned(),
+            data: None,
+        })
+        .ok()?,
+    );
+    serde_json::to_string(&Notification {
+        jsonrpc: Some(Version::V2),
+        method: method.to_string(),
+        params: Params::Map(params),
+    })
+    .ok()
+}
+
+/// Build jsonrpc notification of backend message for a subscription.
+fn notification(id: &str, msg: IncomingMessage) -> Option<String> {
+    let mut params = serde_json::Map::new();
+    params.insert("subscription".to_owned(), id.into());
+    params.insert(
+        "result".to_owned(),
+        serde_json::to_value(response::IncomingMessage::from(msg)).ok()?,
+    );
+    serde_json::to_string(&Notification {
+        jsonrpc: Some(Version::V2),
+        method: Method::SubscribeMessages.to_string(),
+        params: Params::Map(params),
+    })
+    .ok()
+}
+
+/// Build jsonrpc notification of message of topic for a subscription.
+fn topic_notification(id: &str, msg: TopicMessage) -> Option<String> {
+    let mut params = serde_json::Map::new();
+    params.insert("subscription".to_owned(), id.into());
+    params.insert("result".to_owned(), serde_json::to_value(msg).ok()?);
+    serde_json::to_string(&Notification {
+        jsonrpc: Some(Version::V2),
+        method: Method::SubscribeTopic.to_string(),
+        params: Params::Map(params),
+    })
+    .ok()
+}
 
 /// Actual websocket statemachine (one will be spawned per connection)
 pub async fn handle_socket(ws_state: Arc<WsState>, socket: WebSocket) {
     let (mut sender, mut receiver) = socket.split();
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<String>(WS_BUFFER_SIZE);
+    let (dropped, mut dropped_rx) = mpsc::unbounded_channel::<String>();
 
     let mut send_task = tokio::spawn(async move {
+        let mut cnt = 0;
         loop {
-            let mut receiver = ws_state.receiver.resubscribe();
-            if let Ok(data) = receiver.recv().await {
-                let data = BaseResponse::new(
-                    "custom_message".to_owned(),
-                    CustomBackendMessage::from(data),
-                );
-                let data = serde_json::to_value(&data);
-                if data.is_err() {
-                    continue;
-                }
-                let data = data.unwrap();
-                if let Ok(data) = serde_json::to_string(&data) {
-                    if let Err(e) = sender.send(Message::Text(data)).await {
-                        tracing::error!("send_custom_message_to_ws_failed: {}", e);
-                    }
-                }
+            // Errors of dropped subscriptions are sent first, they are not limited by buffer.
+            let data = tokio::select! {
+                biased;
+                Some(data) = dropped_rx.recv() => data,
+                data = outgoing_rx.recv() => match data {
+                    Some(data) => data,
+                    None => break,
+                },
+            };
+            if let Err(e) = sender.send(Message::Text(data)).await {
+                tracing::error!("send_message_to_ws_failed: {}", e);
+                break;
             }
-            drop(receiver);
+            cnt += 1;
         }
+        cnt
     });
     let mut recv_task = tokio::spawn(async move {
+        let mut subscriptions =
+            Subscriptions::new(ws_state.receiver.clone(), outgoing.clone(), dropped)
+                .processor(ws_state.processor.clone());
         let mut cnt = 0;
         while let Some(Ok(msg)) = receiver.next().await {
             cnt += 1;
-            // print message and break if instructed to do so
             tracing::debug!("recv message: {:?}", msg);
+            let Message::Text(text) = msg else {
+                continue;
+            };
+            if let Some(resp) = subscriptions.handle_request(&text) {
+                if outgoing.send(resp).await.is_err() {
+                    break;
+                }
+            }
         }
         cnt
     });
@@ -63,3 +386,176 @@ pub async fn handle_socket(ws_state: Arc<WsState>, socket: WebSocket) {
     }
     tracing::info!("WS over");
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::types::BackendMessage;
+    use crate::prelude::rings_core::dht::Did;
+    use crate::prelude::rings_core::message::Encoder;
+    use crate::prelude::SecretKey;
+
+    fn request(method: Method, params: serde_json::Value) -> String {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method.as_str(),
+            "params": params,
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_messages() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(16);
+        let (outgoing, mut outgoing_rx) = mpsc::channel(16);
+        let (dropped, _dropped_rx) = mpsc::unbounded_channel();
+        let mut subscriptions = Subscriptions::new(Arc::new(receiver), outgoing, dropped);
+        let from: Did = SecretKey::random().address().into();
+
+        let resp = subscriptions
+            .handle_request(&request(Method::SubscribeMessages, serde_json::json!([])))
+            .unwrap();
+        let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+        let id = resp["result"].as_str().unwrap().to_owned();
+        let id2 = subscriptions.subscribe();
+
+        sender
+            .send((from, BackendMessage::from((2, "hello".as_bytes()))).into())
+            .unwrap();
+
+        let mut ids = vec![];
+        for _ in 0..2 {
+            let n: serde_json::Value =
+                serde_json::from_str(&outgoing_rx.recv().await.unwrap()).unwrap();
+            assert_eq!(n["method"], "subscribeMessages");
+            let msg: response::IncomingMessage =
+                serde_json::from_value(n["params"]["result"].clone()).unwrap();
+            assert_eq!(msg.from, from.to_string());
+            assert_eq!(msg.message_type, 2);
+            assert_eq!(base64::decode(msg.data).unwrap(), "hello".as_bytes());
+            ids.push(n["params"]["subscription"].as_str().unwrap().to_owned());
+        }
+        ids.sort();
+        let mut expect = vec![id.clone(), id2.clone()];
+        expect.sort();
+        assert_eq!(ids, expect);
+
+        let resp = subscriptions
+            .handle_request(&request(
+                Method::UnsubscribeMessages,
+                serde_json::json!([id]),
+            ))
+            .unwrap();
+        let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["result"], true);
+        assert!(!subscriptions.unsubscribe(&id));
+        assert!(subscriptions.unsubscribe(&id2));
+
+        let resp = subscriptions
+            .handle_request(&request(Method::NodeInfo, serde_json::json!([])))
+            .unwrap();
+        let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn test_drop_slow_subscriber() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(16);
+        let (outgoing, mut outgoing_rx) = mpsc::channel(2);
+        let (dropped, mut dropped_rx) = mpsc::unbounded_channel();
+        let mut subscriptions = Subscriptions::new(Arc::new(receiver), outgoing, dropped);
+        let from: Did = SecretKey::random().address().into();
+        let id = subscriptions.subscribe();
+
+        for i in 0..4u8 {
+            sender
+                .send((from, BackendMessage::from((2, &[i][..]))).into())
+                .unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // only the buffered messages are received, and the subscription is dropped.
+        assert!(outgoing_rx.recv().await.is_some());
+        assert!(outgoing_rx.recv().await.is_some());
+        assert!(subscriptions.tasks[&id].is_finished());
+        assert!(outgoing_rx.try_recv().is_err());
+
+        // the subscriber is told it's dropped.
+        let n: serde_json::Value = serde_json::from_str(&dropped_rx.recv().await.unwrap()).unwrap();
+        assert_eq!(n["method"], "subscribeMessages");
+        assert_eq!(n["params"]["subscription"], id.as_str());
+        assert_eq!(n["params"]["error"]["code"], -32000);
+    }
+
+    #[tokio::test]
+    async fn test_default_push() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(16);
+        let (outgoing, mut outgoing_rx) = mpsc::channel(16);
+        let (dropped, _dropped_rx) = mpsc::unbounded_channel();
+        let mut subscriptions = Subscriptions::new(Arc::new(receiver), outgoing, dropped);
+        let from: Did = SecretKey::random().address().into();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        sender
+            .send((from, BackendMessage::from((2, "hello".as_bytes()))).into())
+            .unwrap();
+        let msg: serde_json::Value =
+            serde_json::from_str(&outgoing_rx.recv().await.unwrap()).unwrap();
+        assert_eq!(msg["method"], "custom_message");
+        assert_eq!(msg["result"]["message_type"], 2);
+
+        // default push is stopped once subscribed.
+        let id = subscriptions.subscribe();
+        sender
+            .send((from, BackendMessage::from((2, "hello".as_bytes()))).into())
+            .unwrap();
+        let n: serde_json::Value =
+            serde_json::from_str(&outgoing_rx.recv().await.unwrap()).unwrap();
+        assert_eq!(n["params"]["subscription"], id.as_str());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(outgoing_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_topic() {
+        let (processor, path) = crate::tests::native::prepare_processor(None).await;
+        let processor = Arc::new(processor);
+        let (_sender, receiver) = tokio::sync::broadcast::channel(16);
+        let (outgoing, mut outgoing_rx) = mpsc::channel(16);
+        let (dropped, _dropped_rx) = mpsc::unbounded_channel();
+        let mut subscriptions =
+            Subscriptions::new(Arc::new(receiver), outgoing, dropped).processor(processor.clone());
+        let topic = "test_subscribe_topic";
+
+        let resp = subscriptions
+            .handle_request(&request(Method::SubscribeTopic, serde_json::json!([topic])))
+            .unwrap();
+        let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+        let id = resp["result"].as_str().unwrap().to_owned();
+
+        processor
+            .storage_append_data(topic, "hello".to_string().encode().unwrap())
+            .await
+            .unwrap();
+        let n = tokio::time::timeout(std::time::Duration::from_secs(5), outgoing_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let n: serde_json::Value = serde_json::from_str(&n).unwrap();
+        assert_eq!(n["method"], "subscribeTopic");
+        assert_eq!(n["params"]["subscription"], id.as_str());
+        let msg: TopicMessage = serde_json::from_value(n["params"]["result"].clone()).unwrap();
+        assert_eq!(msg.topic, topic);
+        assert_eq!(msg.index, 0);
+        assert_eq!(msg.entry.content, "hello");
+
+        let resp = subscriptions
+            .handle_request(&request(Method::UnsubscribeTopic, serde_json::json!([id])))
+            .unwrap();
+        let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["result"], true);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+}
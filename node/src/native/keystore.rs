@@ -0,0 +1,497 @@
+//! Keystore of rings node.
+//!
+//! A [NodeKeystore] keeps the dumped [SessionManager] together with the rest of [ProcessorConfig]
+//! in a single json file, so that a node can be restored with the same identity.
+//! The session manager, which holds the delegated session key, can be encrypted with a password.
+//! The key is derived from the password by PBKDF2-HMAC-SHA256 with a random salt,
+//! and the session manager is sealed by AES-256-GCM, so a wrong password or a tampered file is
+//! detected on load. Sticky peers and names of registered services are kept as well.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::KeyInit;
+use aes_gcm::Nonce;
+use rand::RngCore;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::storage::PersistenceStorage;
+use crate::prelude::rings_rpc::types::ServiceMetadata;
+use crate::prelude::SessionManager;
+use crate::processor::Processor;
+use crate::processor::ProcessorBuilder;
+use crate::processor::ProcessorConfig;
+
+/// Default rounds of PBKDF2 to stretch a password into the encryption key.
+const KDF_ROUNDS: u32 = 600_000;
+/// Max rounds of PBKDF2 accepted from a keystore file, more rounds would stall loading.
+const MAX_KDF_ROUNDS: u32 = KDF_ROUNDS * 10;
+/// Length of random salt mixed into the password.
+const SALT_LEN: usize = 16;
+/// Length of AES-GCM nonce.
+const NONCE_LEN: usize = 12;
+
+/// The dumped session manager stored in keystore file.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum KeystoreSession {
+    /// Dumped session manager in plain text.
+    Plain { session_manager: String },
+    /// Dumped session manager encrypted with a key derived from password and salt.
+    Encrypted {
+        salt: String,
+        rounds: u32,
+        nonce: String,
+        ciphertext: String,
+    },
+}
+
+/// A service registered by the node, which will be registered again after restoring.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreService {
+    /// Name of service.
+    pub name: String,
+    /// TTL of service registration in milliseconds.
+    pub ttl_ms: u64,
+    /// Metadata attached to service registration.
+    #[serde(default)]
+    pub metadata: ServiceMetadata,
+}
+
+/// Format of keystore file.
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    ice_servers: String,
+    external_address: Option<String>,
+    stabilize_timeout: usize,
+    session: KeystoreSession,
+    #[serde(default)]
+    sticky_peers: Vec<Did>,
+    #[serde(default)]
+    services: Vec<KeystoreService>,
+}
+
+/// NodeKeystore persists and restores the identity and configuration of a [Processor].
+pub struct NodeKeystore {
+    config: ProcessorConfig,
+    sticky_peers: Vec<Did>,
+    services: Vec<KeystoreService>,
+}
+
+impl From<ProcessorConfig> for NodeKeystore {
+    fn from(config: ProcessorConfig) -> Self {
+        Self {
+            config,
+            sticky_peers: vec![],
+            services: vec![],
+        }
+    }
+}
+
+impl NodeKeystore {
+    /// Get the [ProcessorConfig] in keystore.
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// Get the sticky peers in keystore.
+    pub fn sticky_peers(&self) -> &[Did] {
+        &self.sticky_peers
+    }
+
+    /// Get the registered services in keystore.
+    pub fn services(&self) -> &[KeystoreService] {
+        &self.services
+    }
+
+    /// Take sticky peers and registered services of a running [Processor] into keystore.
+    pub fn with_processor_state(mut self, processor: &Processor) -> Self {
+        self.sticky_peers = processor
+            .sticky_peers()
+            .into_iter()
+            .map(|(did, _)| did)
+            .collect();
+        self.sticky_peers.sort();
+        let registry = processor.service_registry();
+        self.services = registry
+            .list()
+            .into_iter()
+            .map(|(name, ttl_ms)| KeystoreService {
+                metadata: registry.metadata(&name).unwrap_or_default(),
+                name,
+                ttl_ms,
+            })
+            .collect();
+        self.services.sort_by(|a, b| a.name.cmp(&b.name));
+        self
+    }
+
+    /// Save keystore to a json file.
+    /// The session manager will be encrypted if `password` is provided.
+    pub fn save<P>(&self, path: P, password: Option<&str>) -> Result<()>
+    where P: AsRef<Path> {
+        let session = match password {
+            None => KeystoreSession::Plain {
+                session_manager: self.config.session_manager.clone(),
+            },
+            Some(password) => {
+                let mut salt = [0u8; SALT_LEN];
+                let mut nonce = [0u8; NONCE_LEN];
+                rand::thread_rng().fill_bytes(&mut salt);
+                rand::thread_rng().fill_bytes(&mut nonce);
+                let cipher = derive_cipher(password, &salt, KDF_ROUNDS)?;
+                let ciphertext = cipher
+                    .encrypt(
+                        Nonce::from_slice(&nonce),
+                        self.config.session_manager.as_bytes(),
+                    )
+                    .map_err(|e| Error::InvalidKeystore(e.to_string()))?;
+                KeystoreSession::Encrypted {
+                    salt: base64::encode(salt),
+                    rounds: KDF_ROUNDS,
+                    nonce: base64::encode(nonce),
+                    ciphertext: base64::encode(ciphertext),
+                }
+            }
+        };
+        let file = KeystoreFile {
+            ice_servers: self.config.ice_servers.clone(),
+            external_address: self.config.external_address.clone(),
+            stabilize_timeout: self.config.stabilize_timeout,
+            session,
+            sticky_peers: self.sticky_peers.clone(),
+            services: self.services.clone(),
+        };
+
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                fs::create_dir_all(parent).map_err(|e| Error::CreateFileError(e.to_string()))?;
+            }
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The keystore may hold a plain session key, keep it readable by owner only.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let f = options
+            .open(path)
+            .map_err(|e| Error::CreateFileError(e.to_string()))?;
+        // The mode only applies to a created file, an existing one is restricted as well.
+        #[cfg(unix)]
+        f.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+            .map_err(|e| Error::CreateFileError(e.to_string()))?;
+        serde_json::to_writer_pretty(io::BufWriter::new(f), &file)?;
+        Ok(())
+    }
+
+    /// Load keystore from a json file saved by `save`.
+    /// The `password` is required if the keystore is encrypted.
+    pub fn load<P>(path: P, password: Option<&str>) -> Result<Self>
+    where P: AsRef<Path> {
+        let f = fs::File::open(path).map_err(|e| Error::OpenFileError(e.to_string()))?;
+        let file: KeystoreFile = serde_json::from_reader(io::BufReader::new(f))
+            .map_err(|e| Error::InvalidKeystore(e.to_string()))?;
+
+        let session_manager = match file.session {
+            KeystoreSession::Plain { session_manager } => session_manager,
+            KeystoreSession::Encrypted {
+                salt,
+                rounds,
+                nonce,
+                ciphertext,
+            } => {
+                let password = password.ok_or_else(|| {
+                    Error::InvalidKeystore("password is required for encrypted keystore".into())
+                })?;
+                let salt = base64::decode(salt)
+                    .map_err(|_| Error::InvalidKeystore("invalid salt".into()))?;
+                let nonce = base64::decode(nonce)
+                    .ok()
+                    .filter(|n| n.len() == NONCE_LEN)
+                    .ok_or_else(|| Error::InvalidKeystore("invalid nonce".into()))?;
+                let ciphertext = base64::decode(ciphertext)
+                    .map_err(|_| Error::InvalidKeystore("invalid ciphertext".into()))?;
+                let cipher = derive_cipher(password, &salt, rounds)?;
+                let plaintext = cipher
+                    .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+                    .map_err(|_| Error::KeystoreDecryptError)?;
+                String::from_utf8(plaintext).map_err(|_| Error::KeystoreDecryptError)?
+            }
+        };
+        SessionManager::from_str(&session_manager)
+            .map_err(|e| Error::InvalidKeystore(e.to_string()))?;
+
+        Ok(Self {
+            config: ProcessorConfig {
+                ice_servers: file.ice_servers,
+                external_address: file.external_address,
+                session_manager,
+                stabilize_timeout: file.stabilize_timeout,
+            },
+            sticky_peers: file.sticky_peers,
+            services: file.services,
+        })
+    }
+
+    /// Make a [ProcessorBuilder] from keystore, which can be configured further.
+    pub fn into_builder(self) -> Result<ProcessorBuilder> {
+        ProcessorBuilder::try_from(self.config)
+    }
+
+    /// Rebuild the [Processor] from keystore, it's ready to `listen`.
+    /// Sticky peers are added back, and services will be registered again by heartbeat
+    /// once the processor is listening.
+    pub fn into_processor(self, storage: PersistenceStorage) -> Result<Processor> {
+        let Self {
+            config,
+            sticky_peers,
+            services,
+        } = self;
+        let processor = ProcessorBuilder::try_from(config)?
+            .storage(storage)
+            .build()?;
+        for did in sticky_peers {
+            processor.add_sticky_peer(did);
+        }
+        let registry = processor.service_registry();
+        for service in services {
+            registry.restore(&service.name, service.ttl_ms, service.metadata.clone());
+        }
+        Ok(processor)
+    }
+}
+
+/// Derive an AES-256-GCM cipher by stretching salted password with PBKDF2-HMAC-SHA256.
+fn derive_cipher(password: &str, salt: &[u8], rounds: u32) -> Result<Aes256Gcm> {
+    if rounds == 0 || rounds > MAX_KDF_ROUNDS {
+        return Err(Error::InvalidKeystore("invalid kdf rounds".into()));
+    }
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, rounds, &mut key);
+    Aes256Gcm::new_from_slice(&key).map_err(|e| Error::InvalidKeystore(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::rings_core::ecc::SecretKey;
+
+    fn new_keystore() -> NodeKeystore {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        NodeKeystore::from(ProcessorConfig {
+            ice_servers: "stun://stun.l.google.com:19302".to_string(),
+            external_address: Some("127.0.0.1".to_string()),
+            session_manager: sm.dump().unwrap(),
+            stabilize_timeout: 200,
+        })
+    }
+
+    fn assert_config_eq(a: &ProcessorConfig, b: &ProcessorConfig) {
+        assert_eq!(a.ice_servers, b.ice_servers);
+        assert_eq!(a.external_address, b.external_address);
+        assert_eq!(a.session_manager, b.session_manager);
+        assert_eq!(a.stabilize_timeout, b.stabilize_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_keystore_round_trip() {
+        let path = PersistenceStorage::random_path("./tmp");
+        let keystore_path = format!("{}/keystore.json", path);
+        let keystore = new_keystore();
+        keystore.save(&keystore_path, None).unwrap();
+
+        let content = fs::read_to_string(&keystore_path).unwrap();
+        assert!(content.contains(&keystore.config().session_manager));
+
+        let loaded = NodeKeystore::load(&keystore_path, None).unwrap();
+        assert_config_eq(loaded.config(), keystore.config());
+        // A password is ignored when keystore is not encrypted.
+        let loaded = NodeKeystore::load(&keystore_path, Some("password")).unwrap();
+        assert_config_eq(loaded.config(), keystore.config());
+
+        let storage = PersistenceStorage::new_with_path(format!("{}/data", path).as_str())
+            .await
+            .unwrap();
+        let did = SessionManager::from_str(&keystore.config().session_manager)
+            .unwrap()
+            .authorizer_did();
+        let processor = loaded.into_processor(storage).unwrap();
+        assert_eq!(processor.did(), did);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keystore_round_trip_with_password() {
+        let path = PersistenceStorage::random_path("./tmp");
+        let keystore_path = format!("{}/keystore.json", path);
+        let keystore = new_keystore();
+        keystore.save(&keystore_path, Some("password")).unwrap();
+
+        let content = fs::read_to_string(&keystore_path).unwrap();
+        assert!(!content.contains(&keystore.config().session_manager));
+
+        let loaded = NodeKeystore::load(&keystore_path, Some("password")).unwrap();
+        assert_config_eq(loaded.config(), keystore.config());
+
+        let storage = PersistenceStorage::new_with_path(format!("{}/data", path).as_str())
+            .await
+            .unwrap();
+        loaded.into_processor(storage).unwrap();
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keystore_round_trip_with_processor_state() {
+        let path = PersistenceStorage::random_path("./tmp");
+        let keystore_path = format!("{}/keystore.json", path);
+        let storage = PersistenceStorage::new_with_path(format!("{}/data", path).as_str())
+            .await
+            .unwrap();
+        let keystore = new_keystore();
+        let config = keystore.config();
+        let processor = ProcessorBuilder::try_from(ProcessorConfig {
+            ice_servers: config.ice_servers.clone(),
+            external_address: config.external_address.clone(),
+            session_manager: config.session_manager.clone(),
+            stabilize_timeout: config.stabilize_timeout,
+        })
+        .unwrap()
+        .storage(storage)
+        .build()
+        .unwrap();
+        let peer = SecretKey::random().address().into();
+        processor.add_sticky_peer(peer);
+        let metadata = ServiceMetadata {
+            version: Some("1.0.0".to_string()),
+            tags: vec!["echo".to_string()],
+            endpoint: None,
+        };
+        processor
+            .service_registry()
+            .restore("echo", 60000, metadata.clone());
+
+        let keystore = keystore.with_processor_state(&processor);
+        keystore.save(&keystore_path, Some("password")).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&keystore_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        drop(processor);
+
+        let loaded = NodeKeystore::load(&keystore_path, Some("password")).unwrap();
+        assert_eq!(loaded.sticky_peers(), &[peer]);
+        assert_eq!(loaded.services(), &[KeystoreService {
+            name: "echo".to_string(),
+            ttl_ms: 60000,
+            metadata: metadata.clone(),
+        }]);
+
+        let storage = PersistenceStorage::new_with_path(format!("{}/data2", path).as_str())
+            .await
+            .unwrap();
+        let processor = loaded.into_processor(storage).unwrap();
+        assert_eq!(
+            processor
+                .sticky_peers()
+                .into_iter()
+                .map(|(did, _)| did)
+                .collect::<Vec<_>>(),
+            vec![peer]
+        );
+        assert_eq!(processor.service_registry().list(), vec![(
+            "echo".to_string(),
+            60000
+        )]);
+        assert_eq!(
+            processor.service_registry().metadata("echo"),
+            Some(metadata)
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_keystore_wrong_password_and_corruption() {
+        let path = PersistenceStorage::random_path("./tmp");
+        let keystore_path = format!("{}/keystore.json", path);
+        new_keystore()
+            .save(&keystore_path, Some("password"))
+            .unwrap();
+
+        assert!(matches!(
+            NodeKeystore::load(&keystore_path, Some("wrong password")),
+            Err(Error::KeystoreDecryptError)
+        ));
+        assert!(matches!(
+            NodeKeystore::load(&keystore_path, None),
+            Err(Error::InvalidKeystore(_))
+        ));
+
+        let mut file: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&keystore_path).unwrap()).unwrap();
+        let ciphertext = file["session"]["ciphertext"].as_str().unwrap();
+        let mut bytes = base64::decode(ciphertext).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        file["session"]["ciphertext"] = base64::encode(bytes).into();
+        fs::write(&keystore_path, file.to_string()).unwrap();
+        assert!(matches!(
+            NodeKeystore::load(&keystore_path, Some("password")),
+            Err(Error::KeystoreDecryptError)
+        ));
+
+        // Unbounded rounds are rejected before deriving the key.
+        for rounds in [0, MAX_KDF_ROUNDS + 1, u32::MAX] {
+            file["session"]["rounds"] = rounds.into();
+            fs::write(&keystore_path, file.to_string()).unwrap();
+            assert!(matches!(
+                NodeKeystore::load(&keystore_path, Some("password")),
+                Err(Error::InvalidKeystore(_))
+            ));
+        }
+
+        fs::write(&keystore_path, "{\"ice_servers\":").unwrap();
+        assert!(matches!(
+            NodeKeystore::load(&keystore_path, Some("password")),
+            Err(Error::InvalidKeystore(_))
+        ));
+
+        assert!(matches!(
+            NodeKeystore::load(format!("{}/missing.json", path), None),
+            Err(Error::OpenFileError(_))
+        ));
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keystore_save_restricts_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = PersistenceStorage::random_path("./tmp");
+        let keystore_path = format!("{}/keystore.json", path);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(&keystore_path, "{}").unwrap();
+        fs::set_permissions(&keystore_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        new_keystore().save(&keystore_path, None).unwrap();
+        let mode = fs::metadata(&keystore_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
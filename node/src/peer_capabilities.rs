@@ -0,0 +1,59 @@
+//! Capabilities advertised by peers.
+//!
+//! A peer advertises the message types it understands when connected, see
+//! [capabilities_message](crate::processor::capabilities_message). The advertisement is kept
+//! until the peer is disconnected, so that messages of unsupported types are not sent to it.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::backend::types::Capabilities;
+use crate::prelude::rings_core::dht::Did;
+
+/// Capabilities advertised by peers, an advertisement replaces the previous one of peer.
+#[derive(Default)]
+pub(crate) struct PeerCapabilities {
+    inner: RwLock<HashMap<Did, Capabilities>>,
+}
+
+impl PeerCapabilities {
+    pub(crate) fn set(&self, did: Did, capabilities: Capabilities) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.insert(did, capabilities);
+        }
+    }
+
+    pub(crate) fn remove(&self, did: Did) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.remove(&did);
+        }
+    }
+
+    pub(crate) fn get(&self, did: Did) -> Option<Capabilities> {
+        self.inner.read().ok()?.get(&did).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::rings_core::ecc::SecretKey;
+
+    #[test]
+    fn test_peer_capabilities() {
+        let capabilities = PeerCapabilities::default();
+        let did = Did::from(SecretKey::random().address());
+        let advertise = |types: &[u16]| Capabilities {
+            message_types: types.iter().copied().collect(),
+        };
+        assert!(capabilities.get(did).is_none());
+
+        // An advertisement replaces the previous one.
+        capabilities.set(did, advertise(&[0, 1]));
+        capabilities.set(did, advertise(&[2]));
+        assert_eq!(capabilities.get(did), Some(advertise(&[2])));
+
+        capabilities.remove(did);
+        assert!(capabilities.get(did).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+//! Sessions refreshed by peers in-band.
+//!
+//! Before its session expires, a node pushes the renewed session to its peers, see
+//! [session_refresh_message](crate::processor::session_refresh_message). A refresh is accepted
+//! only if it's signed by the same authorizer and not older than the session it replaces.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::prelude::rings_core;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::session::Session;
+
+/// Sessions refreshed by peers in-band, a refresh replaces the previous session of peer.
+/// Custom messages of a peer signed by a session older than its refreshed one are dropped.
+#[derive(Default)]
+pub(crate) struct PeerSessions {
+    inner: RwLock<HashMap<Did, Session>>,
+}
+
+impl PeerSessions {
+    /// Validate and store the session refreshed by peer, `current` is the session signing the refresh.
+    /// The refresh is checked against the stored session of peer if any, or `current` otherwise.
+    pub(crate) fn refresh(&self, did: Did, current: &Session, refreshed: Session) -> Result<()> {
+        let mut inner = self.inner.write().map_err(|_| Error::Lock)?;
+        verify_refresh(inner.get(&did).unwrap_or(current), &refreshed)?;
+        inner.insert(did, refreshed);
+        Ok(())
+    }
+
+    /// Check a message of peer is not signed by a session replaced by refresh.
+    pub(crate) fn admit(&self, did: Did, session: &Session) -> bool {
+        self.inner
+            .read()
+            .ok()
+            .and_then(|inner| inner.get(&did).map(|s| session.ts_ms() >= s.ts_ms()))
+            .unwrap_or(true)
+    }
+
+    pub(crate) fn get(&self, did: Did) -> Option<Session> {
+        self.inner.read().ok()?.get(&did).cloned()
+    }
+}
+
+/// Validate a session refreshing the existing one of a peer.
+/// The refreshed session should be signed by the same authorizer, and not older than the existing one.
+pub(crate) fn verify_refresh(existing: &Session, refreshed: &Session) -> Result<()> {
+    refreshed.verify_self().map_err(Error::RefreshSession)?;
+    let authorizer = existing.authorizer_did();
+    if refreshed.authorizer_did() != authorizer {
+        return Err(Error::RefreshSession(
+            rings_core::error::Error::AuthorizerMismatch(authorizer, refreshed.authorizer_did()),
+        ));
+    }
+    if refreshed.ts_ms() < existing.ts_ms() {
+        return Err(Error::RefreshSession(
+            rings_core::error::Error::StaleSession(existing.ts_ms(), refreshed.ts_ms()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::prelude::SecretKey;
+    use crate::prelude::SessionManager;
+    use crate::prelude::SessionManagerBuilder;
+
+    fn renew(sm: &SessionManager, key: &SecretKey) -> SessionManager {
+        let builder = SessionManagerBuilder::renew(sm).ttl(60 * 1000);
+        let sig = key.sign(&builder.pack_session());
+        builder.sig(sig.to_vec()).build().unwrap()
+    }
+
+    #[test]
+    fn test_verify_refresh() {
+        let key = SecretKey::random();
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        let renewed = renew(&sm, &key);
+        assert!(verify_refresh(&sm.session(), &renewed.session()).is_ok());
+
+        // A stale session can not replace the renewed one.
+        let err = verify_refresh(&renewed.session(), &sm.session()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RefreshSession(rings_core::error::Error::StaleSession(_, _))
+        ));
+
+        // A session of another authorizer is rejected.
+        let other_key = SecretKey::random();
+        let other = SessionManager::new_with_seckey(&other_key).unwrap();
+        let other = renew(&other, &other_key);
+        let err = verify_refresh(&sm.session(), &other.session()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RefreshSession(rings_core::error::Error::AuthorizerMismatch(_, _))
+        ));
+        let sessions = PeerSessions::default();
+        let did = sm.authorizer_did();
+        assert!(sessions
+            .refresh(did, &sm.session(), other.session())
+            .is_err());
+        assert!(sessions.get(did).is_none());
+    }
+
+    #[test]
+    fn test_peer_sessions_admit() {
+        let key = SecretKey::random();
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        let renewed = renew(&sm, &key);
+        let sessions = PeerSessions::default();
+        let did = sm.authorizer_did();
+
+        // Messages are admitted before any refresh.
+        assert!(sessions.admit(did, &sm.session()));
+
+        sessions
+            .refresh(did, &sm.session(), renewed.session())
+            .unwrap();
+        assert_eq!(sessions.get(did), Some(renewed.session()));
+        assert!(sessions.admit(did, &renewed.session()));
+        assert!(!sessions.admit(did, &sm.session()));
+    }
+}
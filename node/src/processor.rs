@@ -2,29 +2,100 @@
 
 //! Processor of rings-node jsonrpc-server.
 
+#[cfg(feature = "node")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::Duration;
 
-use futures::future::Join;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::future::join_all;
+use futures::future::AbortHandle;
+use futures::future::Abortable;
+use futures::future::FutureExt;
+use futures::future::Join5;
+use futures::pin_mut;
+use futures::select;
 use futures::Future;
+use futures::Stream;
+use futures::StreamExt;
+use futures_timer::Delay;
 #[cfg(feature = "node")]
 use jsonrpc_core::Metadata;
+use rand::seq::SliceRandom;
 use rings_core::message::MessagePayload;
 use serde::Deserialize;
 use serde::Serialize;
 
+#[cfg(feature = "node")]
+use crate::backend::service::http_server::HiddenServerConfig;
+#[cfg(feature = "node")]
+use crate::backend::service::http_server::HttpServer;
 use crate::backend::types::BackendMessage;
+use crate::backend::types::Capabilities;
+use crate::backend::types::HttpResponseChunk;
+#[cfg(feature = "node")]
+use crate::backend::types::HttpResponsePart;
+#[cfg(feature = "node")]
+use crate::backend::types::IncomingMessage;
 use crate::backend::types::MessageType;
+use crate::backend::types::MessageTypeRegistry;
+use crate::consts::COMPRESS_LEVEL;
+use crate::consts::CONNECT_READY_CHECK_INTERVAL_MS;
 use crate::consts::DATA_REDUNDANT;
+use crate::consts::DEFAULT_CHUNK_SIZE;
+use crate::consts::DEFAULT_COMPRESS_THRESHOLD;
+use crate::consts::DEFAULT_CONNECT_TIMEOUT_MS;
+use crate::consts::DEFAULT_MAX_MESSAGE_BYTES;
+use crate::consts::DEFAULT_SERVICE_TTL_MS;
+use crate::consts::DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_MS;
+use crate::consts::DEFAULT_STATE_QUERY_TIMEOUT_MS;
+use crate::consts::DEFAULT_STORAGE_GET_TIMEOUT_MS;
+#[cfg(feature = "node")]
+use crate::consts::HTTP_STREAM_MAX_PENDING_CHUNKS;
+use crate::consts::IDLE_CHECK_INTERVAL_MS;
+use crate::consts::QUALITY_CHECK_INTERVAL_MS;
+use crate::consts::SERVICE_INDEX_MAX_FETCHES;
+use crate::consts::SERVICE_INDEX_REDUNDANT;
+use crate::consts::SHUTDOWN_DRAIN_CHECK_INTERVAL_MS;
+use crate::consts::STATE_QUERY_CONCURRENCY;
 use crate::error::Error;
 use crate::error::Result;
+use crate::events::EventBus;
+use crate::events::EventCallback;
+use crate::events::NodeEvent;
+use crate::fetch::VNodeFetcher;
 use crate::measure::PeriodicMeasure;
+use crate::message_limit::MessageSizeLimit;
+use crate::metrics::HandshakeMetrics;
+use crate::metrics::SessionClockMetrics;
+use crate::peer_capabilities::PeerCapabilities;
+use crate::peer_sessions::verify_refresh;
+use crate::peer_sessions::PeerSessions;
+use crate::prelude::chunk::Chunk;
 use crate::prelude::http;
 use crate::prelude::jsonrpc_client::SimpleClient;
 use crate::prelude::jsonrpc_core;
+use crate::prelude::rings_core::consts::DEFAULT_HOP_LIMIT;
+use crate::prelude::rings_core::consts::DEFAULT_MAX_CONCURRENT_OFFERS;
+use crate::prelude::rings_core::consts::DEFAULT_MAX_PENDING_TRANSPORTS;
+use crate::prelude::rings_core::consts::DEFAULT_SESSION_CLOCK_SKEW_MS;
+use crate::prelude::rings_core::consts::DEFAULT_TTL_MS;
+use crate::prelude::rings_core::dht::ChordStorageCache;
 use crate::prelude::rings_core::dht::Did;
 use crate::prelude::rings_core::dht::Stabilization;
+use crate::prelude::rings_core::dht::StabilizationConfig;
+use crate::prelude::rings_core::dht::SuccessorReader;
 use crate::prelude::rings_core::dht::TStabilize;
+use crate::prelude::rings_core::message::decode_gzip_data_with_limit;
+use crate::prelude::rings_core::message::encode_data_gzip;
 use crate::prelude::rings_core::message::Decoder;
 use crate::prelude::rings_core::message::Encoded;
 use crate::prelude::rings_core::message::Encoder;
@@ -33,17 +104,28 @@ use crate::prelude::rings_core::message::PayloadSender;
 use crate::prelude::rings_core::prelude::uuid;
 use crate::prelude::rings_core::prelude::web3::contract::tokens::Tokenizable;
 use crate::prelude::rings_core::prelude::web3::ethabi::Token;
+use crate::prelude::rings_core::session::RevocationSet;
+use crate::prelude::rings_core::session::Session;
 use crate::prelude::rings_core::storage::PersistenceStorage;
 use crate::prelude::rings_core::swarm::MeasureImpl;
 use crate::prelude::rings_core::swarm::Swarm;
 use crate::prelude::rings_core::swarm::SwarmBuilder;
+use crate::prelude::rings_core::transports::backpressure::Backpressure;
+use crate::prelude::rings_core::transports::lanes::Priority;
 use crate::prelude::rings_core::transports::manager::TransportHandshake;
 use crate::prelude::rings_core::transports::manager::TransportManager;
 use crate::prelude::rings_core::transports::Transport;
+use crate::prelude::rings_core::types::ice_transport::IceServer;
 use crate::prelude::rings_core::types::ice_transport::IceTransportInterface;
+use crate::prelude::rings_core::types::ice_transport::SessionDescription;
+use crate::prelude::rings_core::utils::from_rtc_ice_connection_state;
+use crate::prelude::rings_core::utils::from_rtc_ice_gathering_state;
+use crate::prelude::rings_core::utils::get_epoch_ms;
 use crate::prelude::rings_rpc::method;
 use crate::prelude::rings_rpc::response;
 use crate::prelude::rings_rpc::types::HttpRequest;
+use crate::prelude::rings_rpc::types::ListPeersOptions;
+use crate::prelude::rings_rpc::types::ServiceMetadata;
 use crate::prelude::rings_rpc::types::Timeout;
 use crate::prelude::vnode;
 use crate::prelude::CallbackFn;
@@ -51,6 +133,26 @@ use crate::prelude::ChordStorageInterface;
 use crate::prelude::ChordStorageInterfaceCacheChecker;
 use crate::prelude::CustomMessage;
 use crate::prelude::SessionManager;
+use crate::quality::ConnectionQuality;
+use crate::rate_limit::RateLimitConfig;
+use crate::rate_limit::RateLimiter;
+use crate::receipts::DeliveryReceipts;
+use crate::reconnect::ReconnectConfig;
+use crate::reconnect::StickyPeerState;
+use crate::reconnect::StickyPeers;
+use crate::seed::Seed;
+use crate::sending::SendingGuard;
+use crate::sending::SendingMessages;
+use crate::service_registry::alive_records;
+use crate::service_registry::ServiceProvider;
+use crate::service_registry::ServiceRecord;
+use crate::service_registry::ServiceRegistry;
+use crate::session_cache::CachedSession;
+use crate::session_cache::SessionCache;
+#[cfg(feature = "node")]
+use crate::topics::TopicFeeds;
+#[cfg(feature = "node")]
+use crate::topics::TopicSubscription;
 
 /// ProcessorConfig is usually serialized as json or yaml.
 /// There is a `from_config` method in [ProcessorBuilder] used to initialize the Builder with a serialized ProcessorConfig.
@@ -75,6 +177,27 @@ pub struct ProcessorBuilder {
     measure: Option<MeasureImpl>,
     message_callback: Option<CallbackFn>,
     stabilize_timeout: usize,
+    succ_len: Option<usize>,
+    max_chunk_size: usize,
+    compress_threshold: Option<usize>,
+    max_message_bytes: usize,
+    reconnect_config: ReconnectConfig,
+    message_types: Arc<MessageTypeRegistry>,
+    rate_limit: Option<RateLimitConfig>,
+    hop_limit: u8,
+    max_pending_transports: usize,
+    max_concurrent_offers: usize,
+    backpressure: Backpressure,
+    idle_timeout_ms: Option<u64>,
+    state_query_timeout_ms: u64,
+    session_clock_skew_ms: u128,
+    storage_cache: Option<(usize, Option<u64>)>,
+    require_capabilities: bool,
+    remember_seed: bool,
+    #[cfg(feature = "node")]
+    hidden_servers: Vec<HiddenServerConfig>,
+    #[cfg(test)]
+    handshake_seed: Option<u64>,
 }
 
 /// Processor for rings-node jsonrpc server
@@ -84,14 +207,71 @@ pub struct Processor {
     pub swarm: Arc<Swarm>,
     /// a stabilization instance,
     pub stabilization: Arc<Stabilization>,
+    /// set when the message listener of swarm starts
+    listening: Arc<AtomicBool>,
+    /// aborts the loops of `listen` when shutting down
+    shutdown: Arc<Shutdown>,
+    /// lifecycle events of node
+    events: Arc<EventBus>,
+    /// latency of handshakes
+    handshake_metrics: Arc<HandshakeMetrics>,
+    /// peers that will be reconnected when their transports dropped
+    sticky_peers: Arc<StickyPeers>,
+    /// sessions revoked locally, which are rejected when authenticating requests
+    revoked_sessions: Arc<RwLock<RevocationSet>>,
+    /// sessions which authenticated requests to node
+    sessions: Arc<SessionCache>,
+    /// sessions rejected by local clock
+    session_clock_metrics: Arc<SessionClockMetrics>,
+    /// tolerance of clock skew when verifying sessions of requests
+    session_clock_skew_ms: u128,
+    /// registry of custom message types
+    message_types: Arc<MessageTypeRegistry>,
+    /// services registered by this node, which are renewed by heartbeat
+    services: Arc<ServiceRegistry>,
+    /// messages being sent, which can be cancelled by tx_id
+    sending: Arc<SendingMessages>,
+    /// fetcher of virtual nodes, which coalesces concurrent fetches of the same vid
+    fetcher: Arc<VNodeFetcher>,
+    /// delivery receipts received recently
+    receipts: Arc<DeliveryReceipts>,
+    /// capabilities advertised by connected peers
+    capabilities: Arc<PeerCapabilities>,
+    /// sessions refreshed by peers
+    peer_sessions: Arc<PeerSessions>,
+    /// pings waiting for their pongs
+    pings: Arc<PendingPings>,
+    /// requests of node info waiting for replies
+    node_infos: Arc<PendingNodeInfos>,
+    /// quality scores of connected peers, sampled periodically
+    quality: Arc<ConnectionQuality>,
+    /// reject message types not advertised by destination
+    require_capabilities: bool,
+    /// the last seed connected with, if remembering it is enabled
+    seed: Arc<RwLock<Option<Seed>>>,
+    remember_seed: bool,
+    /// performs http requests relayed to hidden services of this node
+    #[cfg(feature = "node")]
+    http_server: Arc<HttpServer>,
+    /// fetch loops of subscribed topics
+    #[cfg(feature = "node")]
+    topic_feeds: Arc<TopicFeeds>,
+    /// transports idle beyond it are closed
+    idle_timeout_ms: Option<u64>,
+    /// transports not responding to state queries in it are reported with unknown state
+    state_query_timeout_ms: u64,
+    /// message larger than it will be split into chunks
+    max_chunk_size: usize,
+    /// message larger than it will be compressed, `None` if compression is disabled
+    compress_threshold: Option<usize>,
+    /// message larger than it is rejected when sending
+    max_message_bytes: usize,
 }
 
-impl ProcessorBuilder {
-    /// initialize a [ProcessorBuilder] with a serialized [ProcessorConfig].
-    pub fn from_config(config: String) -> Result<Self> {
-        let config =
-            serde_yaml::from_str::<ProcessorConfig>(&config).map_err(Error::SerdeYamlError)?;
+impl TryFrom<ProcessorConfig> for ProcessorBuilder {
+    type Error = Error;
 
+    fn try_from(config: ProcessorConfig) -> Result<Self> {
         let session_manager =
             SessionManager::from_str(&config.session_manager).map_err(|_| Error::DecodeError)?;
 
@@ -103,8 +283,38 @@ impl ProcessorBuilder {
             measure: None,
             message_callback: None,
             stabilize_timeout: config.stabilize_timeout,
+            succ_len: None,
+            max_chunk_size: DEFAULT_CHUNK_SIZE,
+            compress_threshold: Some(DEFAULT_COMPRESS_THRESHOLD),
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            reconnect_config: ReconnectConfig::default(),
+            message_types: Default::default(),
+            rate_limit: None,
+            hop_limit: DEFAULT_HOP_LIMIT,
+            max_pending_transports: DEFAULT_MAX_PENDING_TRANSPORTS,
+            max_concurrent_offers: DEFAULT_MAX_CONCURRENT_OFFERS,
+            backpressure: Backpressure::default(),
+            idle_timeout_ms: None,
+            state_query_timeout_ms: DEFAULT_STATE_QUERY_TIMEOUT_MS,
+            session_clock_skew_ms: DEFAULT_SESSION_CLOCK_SKEW_MS,
+            storage_cache: None,
+            require_capabilities: false,
+            remember_seed: true,
+            #[cfg(feature = "node")]
+            hidden_servers: vec![],
+            #[cfg(test)]
+            handshake_seed: None,
         })
     }
+}
+
+impl ProcessorBuilder {
+    /// initialize a [ProcessorBuilder] with a serialized [ProcessorConfig].
+    pub fn from_config(config: String) -> Result<Self> {
+        let config =
+            serde_yaml::from_str::<ProcessorConfig>(&config).map_err(Error::SerdeYamlError)?;
+        Self::try_from(config)
+    }
 
     /// Set the storage for the processor.
     pub fn storage(mut self, storage: PersistenceStorage) -> Self {
@@ -124,6 +334,152 @@ impl ProcessorBuilder {
         self
     }
 
+    /// Set the max length of successor list kept by stabilization, it should be in 1..=255.
+    pub fn successor_list_len(mut self, len: usize) -> Self {
+        self.succ_len = Some(len);
+        self
+    }
+
+    /// Set the max chunk size of message for the processor.
+    /// A message larger than it will be split into chunks when sending.
+    pub fn max_chunk_size(mut self, size: usize) -> Self {
+        self.max_chunk_size = size;
+        self
+    }
+
+    /// Enable or disable compression of message when sending, it's enabled by default.
+    pub fn compression(mut self, enable: bool) -> Self {
+        self.compress_threshold = if enable {
+            self.compress_threshold.or(Some(DEFAULT_COMPRESS_THRESHOLD))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Set the threshold of message size for compression, and enable compression.
+    /// A message larger than it will be compressed with gzip when sending.
+    pub fn compress_threshold(mut self, threshold: usize) -> Self {
+        self.compress_threshold = Some(threshold);
+        self
+    }
+
+    /// Set the max size of a custom message for the processor, it's [DEFAULT_MAX_MESSAGE_BYTES] by default.
+    /// Sending a larger message fails, and a larger message received is dropped before the callback.
+    /// A chunked message is limited by its reassembled size.
+    pub fn max_message_bytes(mut self, max: usize) -> Self {
+        self.max_message_bytes = max;
+        self
+    }
+
+    /// Set whether the processor remembers the last seed connected with, it's enabled by default.
+    /// The remembered seed can be connected again by [Processor::reconnect_seed].
+    pub fn remember_seed(mut self, remember: bool) -> Self {
+        self.remember_seed = remember;
+        self
+    }
+
+    /// Set the backoff of reconnecting sticky peers for the processor.
+    pub fn reconnect_config(mut self, config: ReconnectConfig) -> Self {
+        self.reconnect_config = config;
+        self
+    }
+
+    /// Enable rate limiting of custom messages per sender did for the processor.
+    /// Messages exceeding the rate are dropped.
+    pub fn rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limit = Some(config);
+        self
+    }
+
+    /// Set the count of hops a message sent by the processor can be forwarded,
+    /// a message looping among nodes is dropped when it's exhausted.
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    /// Set the max count of transports in handshake,
+    /// the oldest pending transport is closed when a new one exceeds it.
+    pub fn max_pending_transports(mut self, max: usize) -> Self {
+        self.max_pending_transports = max;
+        self
+    }
+
+    /// Set the max count of offers generated concurrently,
+    /// excess offers wait for a slot instead of gathering ice candidates all at once.
+    pub fn max_concurrent_offers(mut self, max: usize) -> Self {
+        self.max_concurrent_offers = max;
+        self
+    }
+
+    /// Set the watermarks of send queue of each transport.
+    /// Sending over the high watermark waits for draining or fails with [Error::QueueFull] by its mode.
+    pub fn backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Close transports which send or receive no message for `timeout_ms`,
+    /// except those of DHT neighbors and sticky peers. It's disabled by default.
+    pub fn idle_timeout(mut self, timeout_ms: u64) -> Self {
+        self.idle_timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    /// Set the timeout of querying state of a transport when listing peers and pending transports,
+    /// a transport not responding in time is listed with `Unknown` state.
+    /// It's [DEFAULT_STATE_QUERY_TIMEOUT_MS] by default.
+    pub fn state_query_timeout(mut self, timeout_ms: u64) -> Self {
+        self.state_query_timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Set the tolerance of clock skew when verifying sessions which sign requests to node.
+    /// A session created ahead of local clock or expired within it is accepted.
+    /// It's [DEFAULT_SESSION_CLOCK_SKEW_MS] by default.
+    pub fn session_clock_skew_tolerance(mut self, tolerance_ms: u64) -> Self {
+        self.session_clock_skew_ms = tolerance_ms as u128;
+        self
+    }
+
+    /// Set the capacity and ttl of the cache read by `storage_check_cache`.
+    /// A cached virtual node is dropped after `ttl_ms` and should be fetched again,
+    /// and the least recently used one is evicted when the cache exceeds `capacity`.
+    /// Cached virtual nodes never expire if `ttl_ms` is `None`.
+    pub fn storage_cache(mut self, capacity: usize, ttl_ms: Option<u64>) -> Self {
+        self.storage_cache = Some((capacity, ttl_ms));
+        self
+    }
+
+    /// Reject sending a message type which the destination doesn't advertise in its capabilities.
+    /// Destinations without advertised capabilities are not checked. It's disabled by default.
+    pub fn require_capabilities(mut self, enable: bool) -> Self {
+        self.require_capabilities = enable;
+        self
+    }
+
+    /// Set the hidden services which http requests relayed to this node are sent to.
+    #[cfg(feature = "node")]
+    pub fn hidden_servers(mut self, services: Vec<HiddenServerConfig>) -> Self {
+        self.hidden_servers = services;
+        self
+    }
+
+    /// Seed the randomness of handshake, so that offers and answers are reproducible.
+    #[cfg(all(test, feature = "node"))]
+    pub(crate) fn handshake_seed(mut self, seed: u64) -> Self {
+        self.handshake_seed = Some(seed);
+        self
+    }
+
+    /// Set the registry of custom message types for the processor.
+    /// The registry can be shared with message callback to dispatch messages by name.
+    pub fn message_type_registry(mut self, registry: Arc<MessageTypeRegistry>) -> Self {
+        self.message_types = registry;
+        self
+    }
+
     /// Build the [Processor].
     pub fn build(self) -> Result<Processor> {
         self.session_manager
@@ -131,11 +487,30 @@ impl ProcessorBuilder {
             .verify_self()
             .map_err(|e| Error::VerifyError(e.to_string()))?;
 
+        if self.max_chunk_size == 0 {
+            return Err(Error::InvalidChunkSize);
+        }
+
+        let ice_servers =
+            IceServer::vec_from_str(&self.ice_servers).map_err(Error::InvalidIceServer)?;
+
+        let backpressure = Backpressure::new(
+            self.backpressure.high_watermark,
+            self.backpressure.low_watermark,
+            self.backpressure.mode,
+        )
+        .map_err(Error::Swarm)?;
+
         let storage = self
             .storage
             .expect("Please set storage by `storage()` method");
 
-        let mut swarm_builder = SwarmBuilder::new(&self.ice_servers, storage, self.session_manager);
+        let mut swarm_builder =
+            SwarmBuilder::new_with_ice_servers(ice_servers, storage, self.session_manager)
+                .hop_limit(self.hop_limit)
+                .max_pending_transports(self.max_pending_transports)
+                .max_concurrent_offers(self.max_concurrent_offers)
+                .backpressure(backpressure);
 
         if let Some(external_address) = self.external_address {
             swarm_builder = swarm_builder.external_address(external_address);
@@ -145,16 +520,89 @@ impl ProcessorBuilder {
             swarm_builder = swarm_builder.measure(measure);
         }
 
-        if let Some(callback) = self.message_callback {
-            swarm_builder = swarm_builder.message_callback(callback);
+        if let Some((capacity, ttl_ms)) = self.storage_cache {
+            swarm_builder = swarm_builder.vnode_cache(capacity, ttl_ms);
+        }
+
+        #[cfg(test)]
+        if let Some(seed) = self.handshake_seed {
+            swarm_builder = swarm_builder.handshake_seed(seed);
+        }
+
+        let events = Arc::new(EventBus::default());
+        let receipts = Arc::new(DeliveryReceipts::default());
+        let capabilities = Arc::new(PeerCapabilities::default());
+        let peer_sessions = Arc::new(PeerSessions::default());
+        let pings = Arc::new(PendingPings::default());
+        let node_infos = Arc::new(PendingNodeInfos::default());
+        let swarm_binding = Arc::new(OnceLock::new());
+        swarm_builder = swarm_builder.message_callback(Box::new(EventCallback::new(
+            self.message_callback,
+            events.clone(),
+            receipts.clone(),
+            capabilities.clone(),
+            peer_sessions.clone(),
+            pings.clone(),
+            node_infos.clone(),
+            self.message_types.clone(),
+            MessageSizeLimit::new(self.max_message_bytes),
+            swarm_binding.clone(),
+        )));
+
+        if let Some(config) = self.rate_limit {
+            swarm_builder = swarm_builder.message_validator(Box::new(RateLimiter::new(config)));
         }
 
         let swarm = Arc::new(swarm_builder.build());
-        let stabilization = Arc::new(Stabilization::new(swarm.clone(), self.stabilize_timeout));
+        swarm_binding.set(Arc::downgrade(&swarm)).ok();
+        let config = StabilizationConfig {
+            succ_len: self
+                .succ_len
+                .unwrap_or_else(|| swarm.dht().successors().max_len().into()),
+            interval_secs: self.stabilize_timeout as u64,
+        };
+        let stabilization = Arc::new(
+            Stabilization::new_with_config(swarm.clone(), config)
+                .map_err(Error::InvalidStabilization)?
+                .with_observer(events.clone()),
+        );
+
+        let fetcher = Arc::new(VNodeFetcher::new(swarm.clone()));
 
         Ok(Processor {
             swarm,
             stabilization,
+            listening: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(Shutdown::default()),
+            events,
+            handshake_metrics: Arc::new(HandshakeMetrics::default()),
+            sticky_peers: Arc::new(StickyPeers::new(self.reconnect_config)),
+            revoked_sessions: Arc::new(RwLock::new(RevocationSet::default())),
+            sessions: Arc::new(SessionCache::default()),
+            session_clock_metrics: Arc::new(SessionClockMetrics::default()),
+            session_clock_skew_ms: self.session_clock_skew_ms,
+            message_types: self.message_types,
+            services: Arc::new(ServiceRegistry::default()),
+            sending: Arc::new(SendingMessages::default()),
+            fetcher,
+            receipts,
+            capabilities,
+            peer_sessions,
+            pings,
+            node_infos,
+            quality: Arc::new(ConnectionQuality::default()),
+            require_capabilities: self.require_capabilities,
+            seed: Arc::new(RwLock::new(None)),
+            remember_seed: self.remember_seed,
+            #[cfg(feature = "node")]
+            http_server: Arc::new(HttpServer::from(self.hidden_servers)),
+            #[cfg(feature = "node")]
+            topic_feeds: Arc::new(TopicFeeds::default()),
+            idle_timeout_ms: self.idle_timeout_ms,
+            state_query_timeout_ms: self.state_query_timeout_ms,
+            max_chunk_size: self.max_chunk_size,
+            compress_threshold: self.compress_threshold,
+            max_message_bytes: self.max_message_bytes,
         })
     }
 }
@@ -162,16 +610,277 @@ impl ProcessorBuilder {
 #[cfg(feature = "node")]
 impl Metadata for Processor {}
 
+/// Requests waiting for their replies by id, a reply without waiting request is ignored.
+pub(crate) struct PendingReplies<T> {
+    inner: std::sync::Mutex<HashMap<uuid::Uuid, oneshot::Sender<T>>>,
+}
+
+/// Pings waiting for their pongs.
+pub(crate) type PendingPings = PendingReplies<()>;
+
+/// Requests of node info waiting for replies of remote nodes.
+pub(crate) type PendingNodeInfos = PendingReplies<response::NodeInfo>;
+
+impl<T> Default for PendingReplies<T> {
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<T> PendingReplies<T> {
+    fn register(&self, id: uuid::Uuid) -> oneshot::Receiver<T> {
+        let (sender, receiver) = oneshot::channel();
+        self.inner.lock().unwrap().insert(id, sender);
+        receiver
+    }
+
+    pub(crate) fn resolve(&self, id: uuid::Uuid, reply: T) {
+        if let Some(sender) = self.remove(id) {
+            sender.send(reply).ok();
+        }
+    }
+
+    fn remove(&self, id: uuid::Uuid) -> Option<oneshot::Sender<T>> {
+        self.inner.lock().unwrap().remove(&id)
+    }
+}
+
+/// State of shutting down, futures made abortable by it are aborted on shutdown.
+#[derive(Default)]
+struct Shutdown {
+    started: AtomicBool,
+    inner: std::sync::Mutex<ShutdownInner>,
+}
+
+#[derive(Default)]
+struct ShutdownInner {
+    aborted: bool,
+    handles: Vec<AbortHandle>,
+}
+
+impl Shutdown {
+    /// Mark shutdown as started, return false if it's already started.
+    fn start(&self) -> bool {
+        !self.started.swap(true, Ordering::SeqCst)
+    }
+
+    fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Make a future abortable by `abort_all`, it's aborted at once if `abort_all` is called.
+    fn abortable<F: Future>(&self, fut: F) -> Abortable<F> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let mut inner = self.inner.lock().unwrap();
+        if inner.aborted {
+            handle.abort();
+        } else {
+            inner.handles.push(handle);
+        }
+        Abortable::new(fut, registration)
+    }
+
+    fn abort_all(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.aborted = true;
+        for handle in inner.handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
 impl Processor {
     /// Listen processor message
-    pub fn listen(&self) -> Join<impl Future, impl Future> {
+    pub fn listen(&self) -> Join5<impl Future, impl Future, impl Future, impl Future, impl Future> {
         let swarm = self.swarm.clone();
-        let message_listener = async { swarm.listen().await };
+        let listening = self.listening.clone();
+        let message_listener = async move {
+            listening.store(true, Ordering::SeqCst);
+            swarm.listen().await
+        };
 
         let stb = self.stabilization.clone();
         let stabilization = async { stb.wait().await };
 
-        futures::future::join(message_listener, stabilization)
+        let sticky_peers = self.sticky_peers.clone().wait(self.swarm.clone());
+
+        let services = self.services.clone().wait(self.fetcher.clone());
+
+        let processor = self.clone();
+        let maintenance = async move {
+            futures::future::join(
+                processor.wait_idle_transports(),
+                processor.wait_connection_quality(),
+            )
+            .await
+        };
+
+        futures::future::join5(
+            self.shutdown.abortable(message_listener),
+            self.shutdown.abortable(stabilization),
+            self.shutdown.abortable(sticky_peers),
+            self.shutdown.abortable(services),
+            self.shutdown.abortable(maintenance),
+        )
+    }
+
+    /// Close idle transports periodically if idle timeout is set, otherwise return at once.
+    async fn wait_idle_transports(&self) {
+        let Some(timeout_ms) = self.idle_timeout_ms else {
+            return;
+        };
+        let interval_ms = timeout_ms.clamp(1, IDLE_CHECK_INTERVAL_MS);
+        loop {
+            Delay::new(Duration::from_millis(interval_ms)).await;
+            self.close_idle_transports(timeout_ms).await;
+        }
+    }
+
+    /// Sample stats of connections to score their quality periodically.
+    async fn wait_connection_quality(&self) {
+        loop {
+            Delay::new(Duration::from_millis(QUALITY_CHECK_INTERVAL_MS)).await;
+            self.update_connection_quality().await;
+        }
+    }
+
+    /// Sample stats of all connected transports into their quality scores,
+    /// and forget scores of closed ones.
+    pub async fn update_connection_quality(&self) {
+        let transports = self.swarm.get_transports();
+        let stats = self
+            .query_transports(
+                transports
+                    .iter()
+                    .map(|(_, transport)| transport.connection_stats()),
+            )
+            .await;
+        for ((_, transport), stats) in transports.iter().zip(stats) {
+            if let Some(stats) = stats {
+                self.quality.update(transport.id, &stats);
+            }
+        }
+        let ids = transports.iter().map(|(_, t)| t.id).collect::<Vec<_>>();
+        self.quality.retain(&ids);
+    }
+
+    /// Quality score of connection to a peer, `0.0` if it's not connected or not sampled yet.
+    /// See [crate::quality] for the scoring function.
+    pub fn peer_quality(&self, did: Did) -> f32 {
+        self.swarm
+            .get_transport(did)
+            .map(|transport| self.quality.score(transport.id))
+            .unwrap_or_default()
+    }
+
+    /// Close transports which send or receive no message for `timeout_ms`,
+    /// except those of DHT neighbors and sticky peers. Return dids of closed ones.
+    /// A [NodeEvent::PeerIdleClosed] is emitted for each of them.
+    pub async fn close_idle_transports(&self, timeout_ms: u64) -> Vec<Did> {
+        let now = get_epoch_ms();
+        let dht = self.swarm.dht();
+        let successors = dht.successors().list().unwrap_or_default();
+        let predecessor = dht.lock_predecessor().map(|p| *p).unwrap_or(None);
+        let mut closed = vec![];
+        for did in self.swarm.get_dids() {
+            let is_neighbor = successors.contains(&did)
+                || predecessor == Some(did)
+                || dht
+                    .lock_finger()
+                    .map(|f| f.contains(Some(did)))
+                    .unwrap_or(false);
+            if is_neighbor || self.sticky_peers.contains(did) {
+                continue;
+            }
+            let last_active_ms = self.swarm.last_active_ms(did).unwrap_or(now);
+            if now.saturating_sub(last_active_ms) < timeout_ms as u128 {
+                continue;
+            }
+            tracing::info!("close transport of {}, idle for {}ms", did, timeout_ms);
+            match self.swarm.disconnect(did).await {
+                Ok(()) => {
+                    self.events.emit(NodeEvent::PeerIdleClosed(did));
+                    closed.push(did);
+                }
+                Err(e) => tracing::warn!("failed to close idle transport of {}: {}", did, e),
+            }
+        }
+        if !closed.is_empty() {
+            self.events.observe(&self.swarm).await;
+        }
+        closed
+    }
+
+    /// Shutdown the node cleanly, it returns once everything is down:
+    /// - leave the ring, so peers around can re-link at once,
+    /// - wait for messages being sent, those not finished in time are cancelled,
+    /// - stop stabilization and other loops of `listen`, the future of `listen` resolves,
+    /// - close all transports, including pending ones.
+    ///
+    /// New messages are rejected once shutdown is started. Calling it again is a no-op.
+    pub async fn shutdown(&self) -> Result<()> {
+        if !self.shutdown.start() {
+            return Ok(());
+        }
+        tracing::info!("shutdown: leave the ring");
+        if let Err(e) = self.leave().await {
+            tracing::warn!("shutdown: failed to leave the ring: {}", e);
+        }
+
+        tracing::info!("shutdown: drain messages being sent");
+        self.drain_sending(DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_MS).await;
+
+        tracing::info!("shutdown: stop stabilization and listeners");
+        self.stabilization.stop();
+        self.shutdown.abort_all();
+        self.listening.store(false, Ordering::SeqCst);
+
+        tracing::info!("shutdown: close transports");
+        for transport in self.list_pendings().await.unwrap_or_default() {
+            if let Err(e) = transport.close().await {
+                tracing::warn!("shutdown: failed to close pending transport: {}", e);
+            }
+            if let Err(e) = self.swarm.pop_pending_transport(transport.id) {
+                tracing::warn!("shutdown: failed to remove pending transport: {}", e);
+            }
+        }
+        for (did, _) in self.swarm.get_transports() {
+            if let Err(e) = self.disconnect(did).await {
+                tracing::warn!("shutdown: failed to disconnect {}: {}", did, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if shutdown is started.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_started()
+    }
+
+    /// A future resolves when the loops of `listen` are stopped by `shutdown`,
+    /// can be used to stop serving.
+    pub fn shutdown_signal(&self) -> impl Future<Output = ()> {
+        self.shutdown
+            .abortable(futures::future::pending::<()>())
+            .map(|_| ())
+    }
+
+    /// Wait for messages being sent, cancel them if not finished in `timeout_ms`.
+    async fn drain_sending(&self, timeout_ms: u64) {
+        let mut waited_ms = 0;
+        while !self.sending.list().is_empty() {
+            if waited_ms >= timeout_ms {
+                for tx_id in self.sending.list() {
+                    tracing::warn!("shutdown: cancel message {}", tx_id);
+                    self.sending.cancel(tx_id);
+                }
+                break;
+            }
+            Delay::new(Duration::from_millis(SHUTDOWN_DRAIN_CHECK_INTERVAL_MS)).await;
+            waited_ms += SHUTDOWN_DRAIN_CHECK_INTERVAL_MS;
+        }
     }
 }
 
@@ -183,16 +892,31 @@ impl Processor {
 
     /// Connect peer with remote rings-node jsonrpc server.
     /// * peer_url: the remote rings-node jsonrpc server url.
-    pub async fn connect_peer_via_http(&self, peer_url: &str) -> Result<Peer> {
+    /// * timeout_ms: abort if data channel is not open in time, [DEFAULT_CONNECT_TIMEOUT_MS] if not specified.
+    pub async fn connect_peer_via_http(
+        &self,
+        peer_url: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<Peer> {
         // request remote offer and sand answer to remote
         tracing::debug!("connect_peer_via_http: {}", peer_url);
 
-        let client = SimpleClient::new(peer_url, None);
-        let (_, offer) = self
-            .swarm
-            .create_offer()
+        let (transport, offer) = self.create_offer().await?;
+        let handshake = async {
+            let peer = self.answer_via_http(peer_url, offer).await?;
+            self.wait_for_data_channel_open(&peer.transport).await?;
+            Ok(peer)
+        };
+        self.with_connect_timeout(&transport, timeout_ms, handshake)
             .await
-            .map_err(Error::CreateOffer)?;
+    }
+
+    async fn answer_via_http(
+        &self,
+        peer_url: &str,
+        offer: MessagePayload<Message>,
+    ) -> Result<Peer> {
+        let client = SimpleClient::new(peer_url, None);
         let encoded_offer = offer.encode().map_err(|_| Error::EncodeError)?;
         tracing::debug!("sending encoded offer {:?} to {}", encoded_offer, peer_url);
         let req: serde_json::Value = serde_json::to_value(encoded_offer)
@@ -210,7 +934,79 @@ impl Processor {
         let answer_payload_str: String =
             serde_json::from_value(resp).map_err(|_| Error::EncodeError)?;
 
-        let encoded_answer: Encoded = <Encoded as From<&str>>::from(&answer_payload_str);
+        self.accept_encoded_answer(&answer_payload_str).await
+    }
+
+    /// Connect peer via a websocket signaling endpoint, the offer and answer are exchanged
+    /// over the websocket instead of http.
+    /// The endpoint should answer the jsonrpc request `answerOffer` sent in a text frame,
+    /// as a rings-node jsonrpc server does, it can be a relay forwarding the offer to the peer.
+    /// * ws_url: the websocket signaling endpoint url.
+    /// * timeout_ms: abort if data channel is not open in time, [DEFAULT_CONNECT_TIMEOUT_MS] if not specified.
+    #[cfg(feature = "node")]
+    pub async fn connect_peer_via_ws(&self, ws_url: &str, timeout_ms: Option<u64>) -> Result<Peer> {
+        tracing::debug!("connect_peer_via_ws: {}", ws_url);
+
+        let (transport, offer) = self.create_offer().await?;
+        let handshake = async {
+            let peer = self.answer_via_ws(ws_url, offer).await?;
+            self.wait_for_data_channel_open(&peer.transport).await?;
+            Ok(peer)
+        };
+        self.with_connect_timeout(&transport, timeout_ms, handshake)
+            .await
+    }
+
+    #[cfg(feature = "node")]
+    async fn answer_via_ws(&self, ws_url: &str, offer: MessagePayload<Message>) -> Result<Peer> {
+        use futures::SinkExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(ws_url)
+            .await
+            .map_err(|e| Error::WsSignalingError(e.to_string()))?;
+        let encoded_offer = offer.encode().map_err(|_| Error::EncodeError)?;
+        let id = jsonrpc_core::Id::Str(offer.tx_id.to_string());
+        let call = jsonrpc_core::MethodCall {
+            jsonrpc: Some(jsonrpc_core::Version::V2),
+            method: method::Method::AnswerOffer.to_string(),
+            params: jsonrpc_core::Params::Array(vec![serde_json::to_value(encoded_offer)?]),
+            id: id.clone(),
+        };
+        ws.send(WsMessage::Text(serde_json::to_string(&call)?))
+            .await
+            .map_err(|e| Error::WsSignalingError(e.to_string()))?;
+
+        // Frames other than the response of the call, such as notifications, are skipped.
+        let resp = loop {
+            let frame = ws
+                .next()
+                .await
+                .ok_or_else(|| Error::WsSignalingError("closed before answer".to_string()))?
+                .map_err(|e| Error::WsSignalingError(e.to_string()))?;
+            let WsMessage::Text(text) = frame else {
+                continue;
+            };
+            match serde_json::from_str::<jsonrpc_core::Output>(&text) {
+                Ok(output) if output.id() == &id => break output,
+                _ => continue,
+            }
+        };
+        let _ = ws.close(None).await;
+
+        let answer_payload_str: String = match resp {
+            jsonrpc_core::Output::Success(s) => {
+                serde_json::from_value(s.result).map_err(|_| Error::EncodeError)?
+            }
+            jsonrpc_core::Output::Failure(f) => {
+                return Err(Error::WsSignalingError(f.error.to_string()))
+            }
+        };
+        self.accept_encoded_answer(&answer_payload_str).await
+    }
+
+    async fn accept_encoded_answer(&self, answer_payload_str: &str) -> Result<Peer> {
+        let encoded_answer: Encoded = <Encoded as From<&str>>::from(answer_payload_str);
 
         let answer_payload = MessagePayload::<Message>::from_encoded(&encoded_answer)
             .map_err(|_| Error::DecodeError)?;
@@ -224,23 +1020,192 @@ impl Processor {
         Ok(Peer::from((did, transport)))
     }
 
+    /// Create an offer of manual handshake, and start timing the handshake.
+    pub async fn create_offer(&self) -> Result<(Arc<Transport>, MessagePayload<Message>)> {
+        let (transport, offer) = self
+            .swarm
+            .create_offer()
+            .await
+            .map_err(Error::CreateOffer)?;
+        self.handshake_metrics.start(transport.id);
+        Ok((transport, offer))
+    }
+
+    /// Answer an offer of manual handshake, and start timing the handshake.
+    pub async fn answer_offer(
+        &self,
+        offer: MessagePayload<Message>,
+    ) -> Result<(Arc<Transport>, MessagePayload<Message>)> {
+        let (transport, answer) = self
+            .swarm
+            .answer_offer(offer)
+            .await
+            .map_err(Error::AnswerOffer)?;
+        self.handshake_metrics.start(transport.id);
+        Ok((transport, answer))
+    }
+
+    /// Wait for the data channel of transport to open.
+    /// The latency of handshake is recorded if it's started by this processor.
+    pub async fn wait_for_data_channel_open(&self, transport: &Transport) -> Result<()> {
+        transport
+            .wait_for_data_channel_open()
+            .await
+            .map_err(Error::ConnectError)?;
+        if let Some(latency_ms) = self.handshake_metrics.finish(transport.id) {
+            tracing::debug!(
+                "handshake of transport {} took {}ms",
+                transport.id,
+                latency_ms
+            );
+        }
+        Ok(())
+    }
+
+    /// Connect peers with remote rings-node jsonrpc servers concurrently.
+    /// The results are in the same order of `peer_urls`.
+    pub async fn connect_peers_via_http(
+        &self,
+        peer_urls: &[&str],
+        timeout_ms: Option<u64>,
+    ) -> Vec<Result<Peer>> {
+        let tasks = peer_urls
+            .iter()
+            .map(|url| self.connect_peer_via_http(url, timeout_ms));
+        futures::future::join_all(tasks).await
+    }
+
+    /// Connect peers of seed which are not connected via http concurrently.
+    /// The seed is remembered for [Processor::reconnect_seed] unless it's disabled.
+    /// Return endpoints of peers connecting, with their results in the same order.
+    pub async fn connect_with_seed(
+        &self,
+        seed: Seed,
+        timeout_ms: Option<u64>,
+    ) -> Vec<(String, Result<Peer>)> {
+        let results = self.connect_seed_peers(&seed, timeout_ms).await;
+        if self.remember_seed {
+            if let Ok(mut remembered) = self.seed.write() {
+                *remembered = Some(seed);
+            }
+        }
+        results
+    }
+
+    /// Connect peers of the remembered seed which are not connected, such as after a partition.
+    /// Return [Error::NoSeed] if no seed is remembered, see [Processor::connect_with_seed].
+    pub async fn reconnect_seed(
+        &self,
+        timeout_ms: Option<u64>,
+    ) -> Result<Vec<(String, Result<Peer>)>> {
+        let seed = self
+            .seed
+            .read()
+            .ok()
+            .and_then(|seed| seed.clone())
+            .ok_or(Error::NoSeed)?;
+        Ok(self.connect_seed_peers(&seed, timeout_ms).await)
+    }
+
+    async fn connect_seed_peers(
+        &self,
+        seed: &Seed,
+        timeout_ms: Option<u64>,
+    ) -> Vec<(String, Result<Peer>)> {
+        let mut connected = HashSet::<Did>::from_iter(self.swarm.get_dids());
+        connected.insert(self.did());
+        let endpoints = seed
+            .peers
+            .iter()
+            .filter(|peer| !connected.contains(&peer.did))
+            .map(|peer| peer.endpoint.as_str())
+            .collect::<Vec<_>>();
+        let results = self.connect_peers_via_http(&endpoints, timeout_ms).await;
+        endpoints
+            .into_iter()
+            .map(String::from)
+            .zip(results)
+            .collect()
+    }
+
     /// Connect peer with web3 did.
     /// There are 3 peers: PeerA, PeerB, PeerC.
     /// 1. PeerA has a connection with PeerB.
     /// 2. PeerC has a connection with PeerB.
     /// 3. PeerC can connect PeerA with PeerA's web3 address.
-    pub async fn connect_with_did(&self, did: Did, wait_for_open: bool) -> Result<Peer> {
+    ///
+    /// When `wait_for_open` is set, it waits within `timeout_ms`, [DEFAULT_CONNECT_TIMEOUT_MS]
+    /// if not specified, until the data channel is open, the transport is registered by swarm
+    /// and the peer joins DHT. So a message sent to the peer right after it returns is delivered.
+    /// On timeout, the transport is closed and [Error::ConnectTimeout] is returned.
+    /// A transport failed before its data channel opens is reported as [Error::ConnectError].
+    pub async fn connect_with_did(
+        &self,
+        did: Did,
+        wait_for_open: bool,
+        timeout_ms: Option<u64>,
+    ) -> Result<Peer> {
         let transport = self.swarm.connect(did).await.map_err(Error::ConnectError)?;
         tracing::debug!("wait for transport connected");
         if wait_for_open {
-            transport
-                .wait_for_data_channel_open()
-                .await
-                .map_err(Error::ConnectError)?;
+            self.handshake_metrics.start(transport.id);
+            let ready = async {
+                self.wait_for_data_channel_open(&transport).await?;
+                self.wait_for_routable(did, &transport).await;
+                Ok(())
+            };
+            self.with_connect_timeout(&transport, timeout_ms, ready)
+                .await?;
         }
         Ok(Peer::from((did, transport)))
     }
 
+    /// Wait until transport of peer is registered by swarm, and the peer joins DHT,
+    /// so messages to the peer are routed to it directly.
+    async fn wait_for_routable(&self, did: Did, transport: &Transport) {
+        loop {
+            let registered = self
+                .swarm
+                .get_transport(did)
+                .map_or(false, |t| t.id == transport.id);
+            let dht = self.swarm.dht();
+            let joined = dht.successors().list().map_or(false, |s| s.contains(&did))
+                || dht
+                    .lock_finger()
+                    .map(|f| f.contains(Some(did)))
+                    .unwrap_or(false);
+            if registered && joined {
+                return;
+            }
+            Delay::new(Duration::from_millis(CONNECT_READY_CHECK_INTERVAL_MS)).await;
+        }
+    }
+
+    /// Run a handshake of transport within `timeout_ms`, [DEFAULT_CONNECT_TIMEOUT_MS] if not specified.
+    /// The transport is closed and removed from pending transports on timeout.
+    async fn with_connect_timeout<T>(
+        &self,
+        transport: &Arc<Transport>,
+        timeout_ms: Option<u64>,
+        handshake: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let timeout_ms = timeout_ms.unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS);
+        let handshake = handshake.fuse();
+        let timeout = Delay::new(Duration::from_millis(timeout_ms)).fuse();
+        pin_mut!(handshake, timeout);
+        select! {
+            result = handshake => result,
+            _ = timeout => {
+                tracing::warn!("transport {} connect timeout after {}ms", transport.id, timeout_ms);
+                if let Err(e) = transport.close().await {
+                    tracing::warn!("failed to close transport {}: {:?}", transport.id, e);
+                }
+                self.swarm.pop_pending_transport(transport.id).ok();
+                Err(Error::ConnectTimeout(timeout_ms))
+            }
+        }
+    }
+
     /// List all peers.
     pub async fn list_peers(&self) -> Result<Vec<Peer>> {
         let transports = self.swarm.get_transports();
@@ -252,37 +1217,252 @@ impl Processor {
         Ok(data)
     }
 
-    /// Get peer by remote did
-    pub async fn get_peer(&self, did: Did) -> Result<Peer> {
-        let transport = self
-            .swarm
-            .get_transport(did)
-            .ok_or(Error::TransportNotFound)?;
-        Ok(Peer::from(&(did, transport)))
-    }
+    /// List a page of peers with their ice connection states.
+    /// Peers are sliced before collecting states, unless they are filtered by states.
+    /// Return the page and the total count of matched peers.
+    pub async fn list_peers_page(
+        &self,
+        options: &ListPeersOptions,
+    ) -> Result<(Vec<response::Peer>, usize)> {
+        let peers = self.list_peers().await?;
+        let offset = options.offset as usize;
+        let limit = options.limit.map(|l| l as usize).unwrap_or(usize::MAX);
 
-    /// Disconnect a peer with web3 did.
-    pub async fn disconnect(&self, did: Did) -> Result<()> {
-        self.swarm
-            .disconnect(did)
+        let Some(states) = &options.states else {
+            let total = peers.len();
+            let page = peers
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .collect::<Vec<_>>();
+            return Ok((self.response_peers(&page).await, total));
+        };
+
+        let matched = self
+            .response_peers(&peers)
             .await
-            .map_err(Error::CloseTransportError)
+            .into_iter()
+            .filter(|p| states.iter().any(|s| s.eq_ignore_ascii_case(&p.state)))
+            .collect::<Vec<_>>();
+        let total = matched.len();
+        let page = matched.into_iter().skip(offset).take(limit).collect();
+        Ok((page, total))
     }
 
-    /// Disconnect all connections.
-    pub async fn disconnect_all(&self) {
-        let transports = self.swarm.get_transports();
-
-        let close_async = transports
+    /// Convert peers to response peers, with their ice connection states, ice gathering states,
+    /// selected candidate types and quality scores.
+    pub async fn response_peers(&self, peers: &[Peer]) -> Vec<response::Peer> {
+        let transports = peers
             .iter()
-            .map(|(_, t)| t.close())
+            .map(|x| x.transport.clone())
             .collect::<Vec<_>>();
-
-        futures::future::join_all(close_async).await;
+        let (states, gathering_states, candidate_types) = futures::future::join3(
+            self.query_transports(transport_queries(&transports, |t| async move {
+                t.ice_connection_state().await
+            })),
+            self.query_transports(transport_queries(&transports, |t| async move {
+                t.ice_gathering_state().await
+            })),
+            self.query_transports(transport_queries(&transports, |t| async move {
+                t.selected_candidate_type().await
+            })),
+        )
+        .await;
+        peers
+            .iter()
+            .zip(states.into_iter().zip(gathering_states.into_iter()))
+            .zip(candidate_types.into_iter())
+            .map(|((x, (y, g)), c)| {
+                x.into_response_peer(
+                    y.map(from_rtc_ice_connection_state),
+                    g.map(from_rtc_ice_gathering_state),
+                    c,
+                    self.quality.score(x.transport.id),
+                )
+            })
+            .collect()
     }
 
-    /// List all pending transport.
-    pub async fn list_pendings(&self) -> Result<Vec<Arc<Transport>>> {
+    /// Convert pending transports to transport infos, with their ice connection states and
+    /// ice gathering states.
+    pub async fn response_transports(
+        &self,
+        transports: &[Arc<Transport>],
+    ) -> Vec<response::TransportInfo> {
+        let (states, gathering_states) = futures::future::join(
+            self.query_transports(transport_queries(transports, |t| async move {
+                t.ice_connection_state().await
+            })),
+            self.query_transports(transport_queries(transports, |t| async move {
+                t.ice_gathering_state().await
+            })),
+        )
+        .await;
+        transports
+            .iter()
+            .zip(states.into_iter().zip(gathering_states.into_iter()))
+            .map(|(x, (y, g))| response::TransportInfo {
+                gathering_state: g.map(from_rtc_ice_gathering_state),
+                ..response::TransportInfo::from((x, y.map(from_rtc_ice_connection_state)))
+            })
+            .collect()
+    }
+
+    /// Run queries of transports concurrently, at most [STATE_QUERY_CONCURRENCY] at once.
+    /// A query not resolved within the state query timeout is resolved as `None`,
+    /// so a hung transport doesn't stall the others. Results are in the order of queries.
+    pub async fn query_transports<F, T>(
+        &self,
+        queries: impl IntoIterator<Item = F>,
+    ) -> Vec<Option<T>>
+    where
+        F: Future<Output = Option<T>>,
+    {
+        let timeout_ms = self.state_query_timeout_ms;
+        futures::stream::iter(queries)
+            .map(|query| async move {
+                let query = query.fuse();
+                let timeout = Delay::new(Duration::from_millis(timeout_ms)).fuse();
+                pin_mut!(query, timeout);
+                select! {
+                    result = query => result,
+                    _ = timeout => None,
+                }
+            })
+            .buffered(STATE_QUERY_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Get peer by remote did
+    pub async fn get_peer(&self, did: Did) -> Result<Peer> {
+        let transport = self
+            .swarm
+            .get_transport(did)
+            .ok_or(Error::TransportNotFound)?;
+        Ok(Peer::from(&(did, transport)))
+    }
+
+    /// Disconnect a peer with web3 did.
+    pub async fn disconnect(&self, did: Did) -> Result<()> {
+        let result = self
+            .swarm
+            .disconnect(did)
+            .await
+            .map_err(Error::CloseTransportError);
+        self.events.observe(&self.swarm).await;
+        result
+    }
+
+    /// Subscribe lifecycle events of node, such as peers connected and changes of successors.
+    /// Events are detected when node handles a message, or disconnects a peer by itself.
+    pub fn events(&self) -> impl Stream<Item = NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Restart ice of the transport connected to a peer, to recover it from a network change.
+    /// The transport and the dht relationship are kept, so queued messages are not lost.
+    pub async fn restart_ice(&self, did: Did) -> Result<()> {
+        self.swarm
+            .restart_ice(did)
+            .await
+            .map_err(Error::RestartIceError)?;
+        Ok(())
+    }
+
+    /// Add a sticky peer, its transport will be reconnected with backoff when dropped.
+    /// Return false if it's already a sticky peer.
+    pub fn add_sticky_peer(&self, did: Did) -> bool {
+        self.sticky_peers.add(did)
+    }
+
+    /// Remove a sticky peer and cancel its in-flight retry, the connected transport is kept.
+    /// Return false if it's not a sticky peer.
+    pub async fn remove_sticky_peer(&self, did: Did) -> bool {
+        self.sticky_peers.remove(&self.swarm, did).await
+    }
+
+    /// List sticky peers with their reconnection states.
+    pub fn sticky_peers(&self) -> Vec<(Did, StickyPeerState)> {
+        self.sticky_peers.states()
+    }
+
+    /// Revoke a session by its session_id, requests signed by it are rejected since then.
+    /// Return false if it's already revoked.
+    pub fn revoke_session(&self, session_id: Did) -> Result<bool> {
+        let mut revoked = self.revoked_sessions.write().map_err(|_| Error::Lock)?;
+        self.sessions.remove(session_id);
+        Ok(revoked.revoke(session_id))
+    }
+
+    /// Verify a session which signs requests to node, it's rejected if revoked.
+    /// Sessions rejected by local clock are counted in `metrics`, since they may hint a skewed clock.
+    /// A verified session is cached, see `list_sessions`.
+    pub fn verify_session(&self, session: &Session) -> Result<()> {
+        let revoked = self.revoked_sessions.read().map_err(|_| Error::Lock)?;
+        session
+            .verify_self_with_revocations(&revoked, self.session_clock_skew_ms)
+            .map_err(|e| {
+                self.session_clock_metrics.record(&e);
+                Error::VerifyError(e.to_string())
+            })?;
+        self.sessions.insert(session);
+        Ok(())
+    }
+
+    /// List sessions which authenticated requests to node, including expired ones.
+    pub fn list_sessions(&self) -> Vec<CachedSession> {
+        self.sessions.list()
+    }
+
+    /// Drop expired sessions from cache, return the number of dropped ones.
+    pub fn prune_expired_sessions(&self) -> usize {
+        self.sessions.prune_expired()
+    }
+
+    /// Disconnect all connections, return a summary of closed peers and errors.
+    pub async fn disconnect_all(&self) -> response::DisconnectSummary {
+        self.disconnect_where(|_| true).await
+    }
+
+    /// Disconnect peers matching `predicate`, such as peers in `failed` ice connection state.
+    /// Peers are closed concurrently, return a summary of closed peers and errors.
+    pub async fn disconnect_where<F>(&self, predicate: F) -> response::DisconnectSummary
+    where F: Fn(&response::Peer) -> bool {
+        let transports = self.swarm.get_transports();
+        let peers = transports.iter().map(Peer::from).collect::<Vec<_>>();
+        let dids = transports
+            .iter()
+            .map(|(did, _)| *did)
+            .zip(self.response_peers(&peers).await)
+            .filter(|(_, peer)| predicate(peer))
+            .map(|(did, _)| did)
+            .collect::<Vec<_>>();
+
+        let results =
+            futures::future::join_all(dids.iter().map(|did| self.swarm.disconnect(*did))).await;
+        self.events.observe(&self.swarm).await;
+
+        let mut summary = response::DisconnectSummary::default();
+        for (did, result) in dids.iter().zip(results) {
+            match result {
+                Ok(()) => summary.closed += 1,
+                Err(e) => summary.errors.push(response::DisconnectError {
+                    did: did.to_string(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        summary
+    }
+
+    /// Leave the ring gracefully, peers around will re-link before transports close.
+    pub async fn leave(&self) -> Result<()> {
+        self.stabilization.leave().await.map_err(Error::LeaveError)
+    }
+
+    /// List all pending transport.
+    pub async fn list_pendings(&self) -> Result<Vec<Arc<Transport>>> {
         let pendings = self
             .swarm
             .pending_transports()
@@ -312,29 +1492,190 @@ impl Processor {
         Ok(())
     }
 
+    /// Get local session description of pending transport in plain SDP, with gathered candidates.
+    /// It's for bridging to WebRTC peers outside of Rings, the handshake is not changed.
+    pub async fn pending_transport_sdp(&self, transport_id: &str) -> Result<SessionDescription> {
+        let transport_id =
+            uuid::Uuid::from_str(transport_id).map_err(|_| Error::InvalidTransportId)?;
+        let transport = self
+            .swarm
+            .find_pending_transport(transport_id)
+            .map_err(|_| Error::TransportNotFound)?
+            .ok_or(Error::TransportNotFound)?;
+        transport
+            .local_description()
+            .await
+            .ok_or(Error::TransportNotFound)
+    }
+
     /// Send custom message to a did.
-    pub async fn send_message(&self, destination: &str, msg: &[u8]) -> Result<uuid::Uuid> {
+    /// The message will be compressed if it's larger than `compress_threshold`.
+    /// The message will be split into chunks if it's larger than `max_chunk_size`,
+    /// in that case the id of chunks is returned as tx_id.
+    /// The message will be dropped by receiver after `ttl_ms` milliseconds,
+    /// the default ttl of message is used if `ttl_ms` is `None`.
+    /// A message larger than `max_message_bytes` is rejected with [Error::MessageTooLarge].
+    /// When capabilities are required, a message with a backend header is rejected with
+    /// [Error::UnsupportedMessageType] if destination doesn't advertise its message type.
+    pub async fn send_message(
+        &self,
+        destination: &str,
+        msg: &[u8],
+        ttl_ms: Option<usize>,
+    ) -> Result<uuid::Uuid> {
+        self.send_message_with_route(destination, msg, ttl_ms)
+            .await
+            .map(|(tx_id, _)| tx_id)
+    }
+
+    /// Send custom message to a did as [Processor::send_message],
+    /// return tx_id and the next hop which the message is handed to.
+    /// The message is sent directly if the next hop is destination, otherwise it's relayed.
+    /// For a chunked message, it's the next hop of the first chunk.
+    pub async fn send_message_with_route(
+        &self,
+        destination: &str,
+        msg: &[u8],
+        ttl_ms: Option<usize>,
+    ) -> Result<(uuid::Uuid, Did)> {
+        self.route_custom_message(destination, msg, ttl_ms, Priority::Normal)
+            .await
+    }
+
+    /// Send custom message to a did as [Processor::send_message], through the transport lane
+    /// of `priority`. Chunks of higher priority are sent ahead of queued chunks of lower ones,
+    /// such as a large transfer sent with [Priority::Low].
+    pub async fn send_message_with_priority(
+        &self,
+        destination: &str,
+        msg: &[u8],
+        ttl_ms: Option<usize>,
+        priority: Priority,
+    ) -> Result<uuid::Uuid> {
+        self.route_custom_message(destination, msg, ttl_ms, priority)
+            .await
+            .map(|(tx_id, _)| tx_id)
+    }
+
+    async fn route_custom_message(
+        &self,
+        destination: &str,
+        msg: &[u8],
+        ttl_ms: Option<usize>,
+        priority: Priority,
+    ) -> Result<(uuid::Uuid, Did)> {
         tracing::info!(
             "send_message, destination: {}, text: {:?}",
             destination,
             msg,
         );
+        if self.is_shutting_down() {
+            return Err(Error::ShuttingDown);
+        }
+        if msg.len() > self.max_message_bytes {
+            return Err(Error::MessageTooLarge(msg.len(), self.max_message_bytes));
+        }
+        if let Ok(backend) = BackendMessage::try_from(msg) {
+            self.check_capability(destination, backend.message_type)?;
+        }
         let destination = Did::from_str(destination).map_err(|_| Error::InvalidDid)?;
 
-        let mut new_msg = Vec::with_capacity(msg.len() + 4);
-        // chunked mark
-        new_msg.push(0);
-        new_msg.extend_from_slice(&[0u8; 3]);
-        new_msg.extend_from_slice(msg);
+        let ttl_ms = ttl_ms.unwrap_or(DEFAULT_TTL_MS);
+        let (msg, compressed) = compress_message(msg, self.compress_threshold)?;
+        let (tx_id, packed) = pack_custom_message(&msg, self.max_chunk_size, compressed)?;
+        let Some(tx_id) = tx_id else {
+            let msg = Message::custom(&packed[0]).map_err(Error::SendMessage)?;
+            let (tx_id, next_hop) = self
+                .swarm
+                .route_message_with_priority(msg, destination, ttl_ms, priority)
+                .await
+                .map_err(send_error)?;
+            self.sending.complete(tx_id);
+            self.receipts.sent(tx_id, destination);
+            return Ok((tx_id, next_hop));
+        };
+
+        self.receipts.sent(tx_id, destination);
+        let sending = SendingGuard::new(&self.sending, tx_id);
+        let mut first_hop = None;
+        for data in packed {
+            if sending.is_cancelled() {
+                tracing::info!("send_message {} is cancelled", tx_id);
+                return Err(Error::SendCancelled(tx_id.to_string()));
+            }
+            let msg = Message::custom(&data).map_err(Error::SendMessage)?;
+            let (_, next_hop) = self
+                .swarm
+                .route_message_with_priority(msg, destination, ttl_ms, priority)
+                .await
+                .map_err(send_error)?;
+            first_hop.get_or_insert(next_hop);
+        }
+        sending.complete();
+        Ok((tx_id, first_hop.unwrap_or(destination)))
+    }
 
-        let msg = Message::custom(&new_msg).map_err(Error::SendMessage)?;
+    /// Cancel a chunked message being sent by [Processor::send_message],
+    /// the remaining chunks will not be sent.
+    pub fn cancel_send(&self, tx_id: uuid::Uuid) -> response::CancelStatus {
+        self.sending.cancel(tx_id)
+    }
 
-        let uuid = self
-            .swarm
-            .send_message(msg, destination)
-            .await
-            .map_err(Error::SendMessage)?;
-        Ok(uuid)
+    /// List tx_id of messages being sent, which can be cancelled by [Processor::cancel_send].
+    pub fn sending_messages(&self) -> Vec<uuid::Uuid> {
+        self.sending.list()
+    }
+
+    /// Check if the delivery receipt of tx_id is received recently from the destination of message.
+    pub fn is_delivered(&self, tx_id: uuid::Uuid) -> bool {
+        self.receipts.contains(tx_id)
+    }
+
+    /// Send custom message to a did, and wait for the delivery receipt.
+    /// The receiver should be subscribed from the channel of backend messages.
+    /// Return the tx_id and whether the message is delivered before timeout.
+    #[cfg(feature = "node")]
+    pub async fn send_message_with_receipt(
+        &self,
+        destination: &str,
+        msg: &[u8],
+        receiver: &mut tokio::sync::broadcast::Receiver<IncomingMessage>,
+        timeout: std::time::Duration,
+    ) -> Result<(uuid::Uuid, bool)> {
+        let tx_id = self.send_message(destination, msg, None).await?;
+        let delivered = self.wait_for_delivery(receiver, tx_id, timeout).await;
+        Ok((tx_id, delivered))
+    }
+
+    /// Wait for the delivery receipt of tx_id from backend messages.
+    /// The receiver should be subscribed before calling, a receipt received earlier is
+    /// remembered by processor for a while. Only a receipt from the destination of message counts.
+    /// Return false if the receipt is not received before timeout.
+    #[cfg(feature = "node")]
+    pub async fn wait_for_delivery(
+        &self,
+        receiver: &mut tokio::sync::broadcast::Receiver<IncomingMessage>,
+        tx_id: uuid::Uuid,
+        timeout: std::time::Duration,
+    ) -> bool {
+        if self.is_delivered(tx_id) {
+            return true;
+        }
+        let wait = async {
+            loop {
+                match receiver.recv().await {
+                    Ok(incoming) => {
+                        // Receipts are recorded with their senders before they are broadcast.
+                        if is_ack_of(&incoming.msg, tx_id) && self.is_delivered(tx_id) {
+                            return true;
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return false,
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.unwrap_or(false)
     }
 
     /// send http request message to node
@@ -363,13 +1704,116 @@ impl Processor {
             url.to_string(),
             timeout,
         );
-        let msg: BackendMessage = BackendMessage::try_from((
-            MessageType::HttpRequest,
-            &HttpRequest::new(name, method, url, timeout, headers, body),
-        ))?;
+        let request = HttpRequest::new(name, method, url, timeout, headers, body);
+        request.validate()?;
+        let msg: BackendMessage = BackendMessage::try_from((MessageType::HttpRequest, &request))?;
+        let msg: Vec<u8> = msg.into();
+
+        self.send_message(destination, &msg, None).await
+    }
+
+    /// Perform a http request relayed to this node with its hidden services,
+    /// and package the status, headers and body returned by upstream into a typed response.
+    /// The status of upstream is kept as is, only failures of sending request are errors.
+    #[cfg(feature = "node")]
+    pub async fn relay_http(&self, request: &HttpRequest) -> Result<response::HttpResponse> {
+        tracing::info!(
+            "relay_http, service: {}, path: {:?}",
+            request.name,
+            request.path
+        );
+        let resp = self.http_server.execute(request).await?;
+        Ok(resp.into())
+    }
+
+    /// Send http request message to node, and receive its response as a stream of [HttpResponsePart].
+    /// The receiver should be subscribed from the channel of backend messages before sending.
+    /// The stream ends after [HttpResponsePart::End] or [HttpResponsePart::Error],
+    /// and the timeout of request bounds the wait for each chunk.
+    #[cfg(feature = "node")]
+    pub async fn send_http_request_stream(
+        &self,
+        destination: &str,
+        request: &HttpRequest,
+        receiver: tokio::sync::broadcast::Receiver<IncomingMessage>,
+    ) -> Result<(uuid::Uuid, impl Stream<Item = HttpResponsePart>)> {
+        tracing::info!(
+            "send_http_request_stream, destination: {}, path: {:?}",
+            destination,
+            request.path,
+        );
+        request.validate()?;
+        let from = Did::from_str(destination).map_err(|_| Error::InvalidDid)?;
+        let msg: BackendMessage = (MessageType::HttpStreamRequest, request).try_into()?;
         let msg: Vec<u8> = msg.into();
+        let tx_id = self.send_message(destination, &msg, None).await?;
+        Ok((
+            tx_id,
+            Self::http_response_stream(receiver, from, tx_id, request.timeout.clone().into()),
+        ))
+    }
 
-        self.send_message(destination, &msg).await
+    /// Collect chunks of streaming http response of tx_id sent by `from` from backend messages,
+    /// and yield their parts in order.
+    /// It yields an error if no chunk arrives within `idle_timeout`, or too many chunks are
+    /// out of order.
+    #[cfg(feature = "node")]
+    pub fn http_response_stream(
+        mut receiver: tokio::sync::broadcast::Receiver<IncomingMessage>,
+        from: Did,
+        tx_id: uuid::Uuid,
+        idle_timeout: Duration,
+    ) -> impl Stream<Item = HttpResponsePart> {
+        async_stream::stream! {
+            let mut pending = BTreeMap::new();
+            let mut next_seq = 0;
+            let mut deadline = tokio::time::Instant::now() + idle_timeout;
+            loop {
+                let Ok(received) = tokio::time::timeout_at(deadline, receiver.recv()).await else {
+                    yield HttpResponsePart::Error("timeout waiting for response chunk".to_string());
+                    return;
+                };
+                match received {
+                    Ok(incoming) => {
+                        if incoming.from != from {
+                            continue;
+                        }
+                        let Some(chunk) = http_response_chunk_of(&incoming.msg, tx_id) else {
+                            continue;
+                        };
+                        deadline = tokio::time::Instant::now() + idle_timeout;
+                        if chunk.seq >= next_seq {
+                            if chunk.seq != next_seq
+                                && !pending.contains_key(&chunk.seq)
+                                && pending.len() >= HTTP_STREAM_MAX_PENDING_CHUNKS
+                            {
+                                yield HttpResponsePart::Error(
+                                    "too many out-of-order response chunks".to_string(),
+                                );
+                                return;
+                            }
+                            pending.insert(chunk.seq, chunk.part);
+                        }
+                        while let Some(part) = pending.remove(&next_seq) {
+                            next_seq += 1;
+                            let is_final = part.is_final();
+                            yield part;
+                            if is_final {
+                                return;
+                            }
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        yield HttpResponsePart::Error(format!("lost {} backend messages", n));
+                        return;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                        yield HttpResponsePart::Error("backend message channel closed".to_string());
+                        return;
+                    }
+                }
+            }
+        }
     }
 
     /// send simple text message
@@ -385,11 +1829,10 @@ impl Processor {
             destination,
             text,
         );
-
         let msg: BackendMessage =
             BackendMessage::from((MessageType::SimpleText.into(), text.as_bytes()));
         let msg: Vec<u8> = msg.into();
-        self.send_message(destination, &msg).await
+        self.send_message(destination, &msg, None).await
     }
 
     /// send custom message
@@ -409,10 +1852,168 @@ impl Processor {
             destination,
             message_type,
         );
-
         let msg: BackendMessage = BackendMessage::new(message_type, extra, data.as_ref());
         let msg: Vec<u8> = msg.into();
-        self.send_message(destination, &msg[..]).await
+        self.send_message(destination, &msg[..], None).await
+    }
+
+    /// Broadcast a custom message to all connected peers concurrently, such as a presence announcement.
+    /// Peers whose data channels are not open are skipped, the result of each other peer is returned.
+    pub async fn broadcast_message(
+        &self,
+        message_type: u16,
+        data: Vec<u8>,
+    ) -> response::BroadcastResponse {
+        let transports = self.swarm.get_transports();
+        tracing::info!(
+            "broadcast_message, peers: {}, message_type: {}",
+            transports.len(),
+            message_type,
+        );
+        let open = futures::future::join_all(
+            transports
+                .iter()
+                .map(|(_, transport)| transport.is_data_channel_open()),
+        )
+        .await;
+
+        let mut resp = response::BroadcastResponse::default();
+        let mut peers = vec![];
+        for ((did, _), open) in transports.iter().zip(open) {
+            if open {
+                peers.push(did.to_string());
+            } else {
+                resp.skipped.push(did.to_string());
+            }
+        }
+
+        let results = futures::future::join_all(
+            peers
+                .iter()
+                .map(|did| self.send_custom_message(did, message_type, data.clone(), [0u8; 30])),
+        )
+        .await;
+        for (did, result) in peers.into_iter().zip(results) {
+            let result = match result {
+                Ok(tx_id) => response::BroadcastResult {
+                    ok: true,
+                    tx_id: Some(tx_id.to_string()),
+                    error: None,
+                },
+                Err(e) => response::BroadcastResult {
+                    ok: false,
+                    tx_id: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            resp.results.insert(did, result);
+        }
+        resp
+    }
+
+    /// Register a custom message type with its id and name.
+    /// Ids reserved by built-in [MessageType] are rejected.
+    pub fn register_custom_message_type(&self, id: u16, name: &str) -> Result<()> {
+        self.message_types.register(id, name)
+    }
+
+    /// Get the registry of custom message types.
+    pub fn message_types(&self) -> Arc<MessageTypeRegistry> {
+        self.message_types.clone()
+    }
+
+    /// Ping a did `count` times one by one, and measure round-trip time by the pong of each ping.
+    /// A ping is lost if its pong is not received within `timeout`.
+    pub async fn ping(
+        &self,
+        destination: Did,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<response::PingResponse> {
+        tracing::info!("ping, destination: {}, count: {}", destination, count);
+        if self.is_shutting_down() {
+            return Err(Error::ShuttingDown);
+        }
+        let mut rtts = vec![];
+        for _ in 0..count {
+            let id = uuid::Uuid::new_v4();
+            let pong = self.pings.register(id);
+            let start = get_epoch_ms();
+            if let Err(e) = self
+                .swarm
+                .route_message_with_priority(
+                    ping_message(id)?,
+                    destination,
+                    DEFAULT_TTL_MS,
+                    Priority::High,
+                )
+                .await
+            {
+                self.pings.remove(id);
+                return Err(Error::SendMessage(e));
+            }
+            let pong = pong.fuse();
+            let delay = Delay::new(timeout).fuse();
+            pin_mut!(pong, delay);
+            select! {
+                result = pong => if result.is_ok() {
+                    rtts.push(get_epoch_ms().saturating_sub(start) as u64);
+                },
+                _ = delay => tracing::debug!("ping {} to {} is lost", id, destination),
+            }
+            self.pings.remove(id);
+        }
+        Ok(response::PingResponse::new(
+            destination.to_string(),
+            count,
+            &rtts,
+        ))
+    }
+
+    /// Get the capabilities advertised by a connected peer after its data channel opened.
+    /// Return `None` if the peer is not connected or hasn't advertised yet.
+    pub fn peer_capabilities(&self, did: Did) -> Option<Capabilities> {
+        self.swarm.get_transport(did)?;
+        self.capabilities.get(did)
+    }
+
+    /// Replace the session of node by a renewed one, such as to extend its lifetime,
+    /// and push it to connected peers, which verify messages of node against it since then.
+    /// The renewed session should be authorized by the same authorizer.
+    pub async fn refresh_session(&self, session_manager: SessionManager) -> Result<()> {
+        let session = session_manager.session();
+        verify_refresh(&self.swarm.session_manager().session(), &session)?;
+        self.swarm
+            .set_session_manager(session_manager)
+            .map_err(Error::RefreshSession)?;
+        let msg = session_refresh_message(&session)?;
+        for did in self.swarm.get_dids() {
+            if let Err(e) = self.swarm.send_direct_message(msg.clone(), did).await {
+                tracing::warn!("push refreshed session to {} failed: {}", did, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the session refreshed by a peer, `None` if it never refreshes.
+    pub fn peer_session(&self, did: Did) -> Option<Session> {
+        self.peer_sessions.get(did)
+    }
+
+    /// Check if destination advertises message type when capabilities are required.
+    /// A destination not connected directly or without advertisement is not checked.
+    /// It's checked by the shared send path, so every message with a backend header is covered.
+    fn check_capability(&self, destination: &str, message_type: u16) -> Result<()> {
+        if !self.require_capabilities {
+            return Ok(());
+        }
+        let did = Did::from_str(destination).map_err(|_| Error::InvalidDid)?;
+        match self.peer_capabilities(did) {
+            Some(capabilities) if !capabilities.supports(message_type) => Err(
+                Error::UnsupportedMessageType(destination.to_string(), message_type),
+            ),
+            _ => Ok(()),
+        }
     }
 
     /// check local cache of dht
@@ -420,11 +2021,46 @@ impl Processor {
         self.swarm.storage_check_cache(did).await
     }
 
-    /// fetch virtual node from DHT
+    /// fetch virtual node from DHT, the fetched one can be read by `storage_check_cache`.
+    /// Concurrent fetches of the same vid share one network fetch.
     pub async fn storage_fetch(&self, did: Did) -> Result<()> {
-        <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_fetch(&self.swarm, did)
-            .await
-            .map_err(Error::VNodeError)
+        self.fetcher.fetch(did).await
+    }
+
+    /// Fetch virtual node from DHT and wait for it, return `None` if it's not fetched within `timeout`.
+    /// The cached copy is dropped first, so the returned one is fetched from its holder by this call.
+    /// A node without remote successor returns at once, its fetch is only from local storage.
+    pub async fn storage_get(
+        &self,
+        did: Did,
+        timeout: Duration,
+    ) -> Result<Option<vnode::VirtualNode>> {
+        self.fetcher.get(did, timeout).await
+    }
+
+    fn has_remote_successor(&self) -> bool {
+        let dht = self.swarm.dht();
+        dht.successors()
+            .list()
+            .map(|list| list.iter().any(|s| *s != dht.did))
+            .unwrap_or(false)
+    }
+
+    /// Get the last fetched range of virtual node from local cache.
+    pub async fn storage_check_range_cache(&self, did: Did) -> Option<vnode::VirtualNodeRange> {
+        self.swarm.storage_check_range_cache(did).await
+    }
+
+    /// fetch at most `count` entries of virtual node from `start` on DHT
+    pub async fn storage_fetch_range(&self, did: Did, start: u64, count: u64) -> Result<()> {
+        <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_fetch_range(
+            &self.swarm,
+            did,
+            start,
+            count,
+        )
+        .await
+        .map_err(Error::VNodeError)
     }
 
     /// store virtual node on DHT
@@ -445,29 +2081,293 @@ impl Processor {
         .map_err(Error::VNodeError)
     }
 
-    /// register service
+    /// Subscribe messages published to topic from `index` on.
+    /// Subscribers of the same topic share one loop which fetches it periodically,
+    /// the loop is stopped when all subscriptions of topic are dropped.
+    #[cfg(feature = "node")]
+    pub async fn subscribe_topic(&self, topic: &str, index: usize) -> Result<TopicSubscription> {
+        let vid = vnode::VirtualNode::gen_did(topic).map_err(Error::VNodeError)?;
+        let cached = self.storage_check_cache(vid).await;
+        Ok(self
+            .topic_feeds
+            .subscribe(self.fetcher.clone(), topic, vid, index, cached))
+    }
+
+    /// drop all but the most recent `keep_last` entries of a virtual node on DHT,
+    /// the compacted virtual node is stored again to make other holders converge.
+    /// The virtual node is fetched from its holder first, and an error is returned
+    /// if it's not resolved in [DEFAULT_STORAGE_GET_TIMEOUT_MS].
+    pub async fn storage_truncate(&self, vid: Did, keep_last: usize) -> Result<()> {
+        let timeout = Duration::from_millis(DEFAULT_STORAGE_GET_TIMEOUT_MS);
+        let vnode = self.storage_get(vid, timeout).await?.ok_or_else(|| {
+            Error::VNodeNotResolved(vid.to_string(), DEFAULT_STORAGE_GET_TIMEOUT_MS)
+        })?;
+        if vnode.data.len() <= keep_last {
+            return Ok(());
+        }
+
+        let skip = vnode.data.len() - keep_last;
+        let compacted = vnode::VirtualNode {
+            did: vnode.did,
+            data: vnode.data.into_iter().skip(skip).collect(),
+            kind: vnode.kind,
+        };
+        self.swarm.dht().local_cache_set(compacted.clone());
+        self.storage_store(compacted).await
+    }
+
+    /// register service with [DEFAULT_SERVICE_TTL_MS]
     pub async fn register_service(&self, name: &str) -> Result<()> {
-        let encoded_did = self
-            .did()
-            .to_string()
-            .encode()
-            .map_err(Error::ServiceRegisterError)?;
-        <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_touch_data(
-            &self.swarm,
-            name,
-            encoded_did,
+        self.register_service_with_ttl(name, DEFAULT_SERVICE_TTL_MS)
+            .await
+    }
+
+    /// Register service with TTL, the registration is renewed by heartbeat while listening.
+    pub async fn register_service_with_ttl(&self, name: &str, ttl_ms: u64) -> Result<()> {
+        self.register_service_with_metadata(name, ttl_ms, ServiceMetadata::default())
+            .await
+    }
+
+    /// Register service with TTL and metadata, such as version and tags of provider.
+    pub async fn register_service_with_metadata(
+        &self,
+        name: &str,
+        ttl_ms: u64,
+        metadata: ServiceMetadata,
+    ) -> Result<()> {
+        self.services
+            .register(&self.swarm, name, ttl_ms, metadata)
+            .await
+    }
+
+    /// Stop renewing a registered service, it will expire after its TTL.
+    /// Return false if the service is not registered by this node.
+    pub fn unregister_service(&self, name: &str) -> bool {
+        self.services.unregister(name)
+    }
+
+    /// Get the services registered by this node.
+    pub fn service_registry(&self) -> Arc<ServiceRegistry> {
+        self.services.clone()
+    }
+
+    /// Lookup registrations of service providers on DHT, expired registrations are excluded.
+    /// The record vnodes of providers in the index of service are fetched concurrently,
+    /// along with legacy records stored in the index itself. Only the latest
+    /// [SERVICE_INDEX_MAX_FETCHES] providers touched into the index are fetched.
+    /// If `tag` is provided, only providers tagged with it are kept.
+    pub async fn lookup_service(
+        &self,
+        name: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<ServiceRecord>> {
+        let timeout = Duration::from_millis(DEFAULT_STORAGE_GET_TIMEOUT_MS);
+        let rid = vnode::VirtualNode::gen_did(name).map_err(Error::VNodeError)?;
+        let Some(mut index) = self
+            .fetcher
+            .get_redundant::<SERVICE_INDEX_REDUNDANT>(rid, timeout)
+            .await?
+        else {
+            return Ok(vec![]);
+        };
+        let mut record_dids = index
+            .data
+            .iter()
+            .rev()
+            .filter_map(ServiceProvider::decode)
+            .take(SERVICE_INDEX_MAX_FETCHES)
+            .map(|provider| provider.record_did(name))
+            .collect::<Result<Vec<_>>>()?;
+        record_dids.reverse();
+        let fetched = join_all(
+            record_dids
+                .into_iter()
+                .map(|did| self.storage_get(did, timeout)),
         )
-        .await
-        .map_err(Error::ServiceRegisterError)
+        .await;
+        for vnode in fetched {
+            if let Some(vnode) = vnode? {
+                index.data.extend(vnode.data);
+            }
+        }
+        Ok(alive_records(&index, get_epoch_ms() as u64)
+            .into_iter()
+            .filter(|r| tag.map_or(true, |tag| r.metadata.has_tag(tag)))
+            .collect())
+    }
+
+    /// Send message to a provider of service, which is tagged with `tag` if provided.
+    /// The connected provider with the highest quality score is preferred,
+    /// otherwise it's picked randomly from alive providers.
+    /// Return did of the picked provider and tx_id of message.
+    pub async fn send_to_service(
+        &self,
+        name: &str,
+        tag: Option<&str>,
+        msg: &[u8],
+        ttl_ms: Option<usize>,
+    ) -> Result<(Did, uuid::Uuid)> {
+        let providers = self
+            .lookup_service(name, tag)
+            .await?
+            .iter()
+            .filter_map(|r| Did::from_str(&r.did).ok())
+            .collect::<Vec<_>>();
+        let best = providers
+            .iter()
+            .map(|did| (*did, self.peer_quality(*did)))
+            .filter(|(_, quality)| *quality > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(did, _)| did);
+        let Some(did) = best.or_else(|| providers.choose(&mut rand::thread_rng()).copied()) else {
+            return Err(Error::ServiceNotFound(name.to_string()));
+        };
+        let tx_id = self.send_message(&did.to_string(), msg, ttl_ms).await?;
+        Ok((did, tx_id))
     }
 
     /// get node info
     pub async fn get_node_info(&self) -> Result<response::NodeInfo> {
-        Ok(response::NodeInfo {
-            version: crate::util::build_version(),
-            swarm: self.swarm.inspect().await,
+        Ok(node_info(&self.swarm).await)
+    }
+
+    /// Get node info of a remote node, by requesting it and waiting for the reply within `timeout`.
+    /// Items of storage are left out of the reply, since they can be too large for a message.
+    pub async fn remote_node_info(
+        &self,
+        destination: Did,
+        timeout: Duration,
+    ) -> Result<response::NodeInfo> {
+        tracing::info!("remote_node_info, destination: {}", destination);
+        if self.is_shutting_down() {
+            return Err(Error::ShuttingDown);
+        }
+        if destination == self.did() {
+            return self.get_node_info().await;
+        }
+        let id = uuid::Uuid::new_v4();
+        let reply = self.node_infos.register(id);
+        if let Err(e) = self
+            .swarm
+            .send_message_with_ttl(node_info_request_message(id)?, destination, DEFAULT_TTL_MS)
+            .await
+        {
+            self.node_infos.remove(id);
+            return Err(Error::SendMessage(e));
+        }
+        let reply = reply.fuse();
+        let delay = Delay::new(timeout).fuse();
+        pin_mut!(reply, delay);
+        let info = select! {
+            result = reply => result.ok(),
+            _ = delay => None,
+        };
+        self.node_infos.remove(id);
+        info.ok_or_else(|| {
+            Error::RemoteNodeInfoTimeout(destination.to_string(), timeout.as_millis() as u64)
+        })
+    }
+
+    /// Walk the ring from this node through the closest successor of each node,
+    /// collecting their neighbors by [Processor::remote_node_info].
+    /// The walk stops when it returns to a visited node, after requesting `max_hops` remote nodes,
+    /// or at a successor not replying within `timeout`, which is reported as unreachable.
+    pub async fn crawl_ring(
+        &self,
+        max_hops: usize,
+        timeout: Duration,
+    ) -> Result<response::CrawlRingResponse> {
+        let info = self.get_node_info().await?;
+        let mut visited = HashSet::from([self.did()]);
+        let mut next = closest_successor(&info);
+        let mut resp = response::CrawlRingResponse {
+            nodes: vec![response::RingNode::from(&info)],
+            complete: false,
+            unreachable: None,
+        };
+        for _ in 0..max_hops {
+            let Some(did) = next.filter(|did| !visited.contains(did)) else {
+                resp.complete = true;
+                return Ok(resp);
+            };
+            match self.remote_node_info(did, timeout).await {
+                Ok(info) => {
+                    visited.insert(did);
+                    next = closest_successor(&info);
+                    resp.nodes.push(response::RingNode::from(&info));
+                }
+                Err(Error::ShuttingDown) => return Err(Error::ShuttingDown),
+                Err(e) => {
+                    tracing::warn!("crawl_ring stopped at {}: {}", did, e);
+                    resp.unreachable = Some(did.to_string());
+                    return Ok(resp);
+                }
+            }
+        }
+        resp.complete = next.map_or(true, |did| visited.contains(&did));
+        Ok(resp)
+    }
+
+    /// Predict the next hop of a message to destination by the same lookup as sending,
+    /// without sending anything. Further hops are decided by remote nodes, so they're not predicted.
+    pub async fn route_query(&self, destination: &str) -> Result<response::RouteQueryResponse> {
+        let destination = Did::from_str(destination).map_err(|_| Error::InvalidDid)?;
+        let next_hop =
+            <Swarm as PayloadSender<Message>>::infer_next_hop(&self.swarm, None, destination)
+                .map_err(send_error)?;
+        Ok(response::RouteQueryResponse {
+            destination: destination.to_string(),
+            next_hop: next_hop.to_string(),
+            relayed: next_hop != destination,
+            connected: self.swarm.get_transport(next_hop).is_some(),
         })
     }
+
+    /// Get metrics of node, such as latency of handshakes.
+    pub fn metrics(&self) -> response::MetricsInfo {
+        response::MetricsInfo {
+            handshake: self.handshake_metrics.summary(),
+            pending_transports: response::PendingTransportsSummary {
+                count: self.swarm.pending_transport_count().unwrap_or_default(),
+                max: self.swarm.max_pending_transports(),
+            },
+            rejected_sessions: self.session_clock_metrics.summary(),
+        }
+    }
+
+    /// Get identity of node, it only reads the current session without any network round-trip.
+    pub fn whoami(&self) -> response::WhoamiResponse {
+        let session = self.swarm.session_manager().session();
+        let remaining_ttl_ms = session.remaining_ttl_ms();
+        response::WhoamiResponse {
+            did: self.did().to_string(),
+            authorizer_did: session.authorizer_did().to_string(),
+            session_expires_in_ms: remaining_ttl_ms.clamp(i64::MIN as i128, i64::MAX as i128)
+                as i64,
+        }
+    }
+
+    /// Get health of node from states of swarm and dht.
+    /// A node is not ready until it has a successor, since lookups can't be completed.
+    pub fn health(&self) -> response::HealthInfo {
+        let dht = self.swarm.dht();
+        let did = dht.did;
+        let listening = self.listening.load(Ordering::SeqCst);
+        let has_successor = self.has_remote_successor();
+        let has_predecessor = dht
+            .lock_predecessor()
+            .map(|pred| pred.map_or(false, |p| p != did))
+            .unwrap_or(false);
+        let stabilized = has_successor && has_predecessor && self.stabilization.rounds() > 0;
+        response::HealthInfo {
+            listening,
+            peers: self.swarm.get_dids().len(),
+            has_successor,
+            has_predecessor,
+            stabilized,
+            ready: listening && has_successor,
+        }
+    }
 }
 
 /// Peer struct
@@ -498,34 +2398,1427 @@ impl From<&(Did, Arc<Transport>)> for Peer {
 }
 
 impl Peer {
-    /// convert peer to response peer
-    pub fn into_response_peer(&self, state: Option<String>) -> rings_rpc::response::Peer {
+    /// convert peer to response peer, with ice connection state, selected candidate type
+    /// and quality score
+    pub fn into_response_peer(
+        &self,
+        state: Option<String>,
+        gathering_state: Option<String>,
+        candidate_type: Option<String>,
+        quality: f32,
+    ) -> rings_rpc::response::Peer {
         rings_rpc::response::Peer {
             did: self.did.clone().into_token().to_string(),
             transport_id: self.transport.id.to_string(),
             state: state.unwrap_or_else(|| "Unknown".to_owned()),
+            gathering_state,
+            candidate_type,
+            quality,
+            direction: self.transport.direction().map(|d| d.to_string()),
         }
     }
-}
+}
+
+/// Build queries of transports on their own handles, collected before the queries are awaited.
+/// A future awaiting queries which borrow transports through an iterator adapter is not general
+/// enough to be `Send`, which the futures of rpc handlers must be.
+pub(crate) fn transport_queries<F, Fut>(transports: &[Arc<Transport>], query: F) -> Vec<Fut>
+where F: FnMut(Arc<Transport>) -> Fut {
+    transports.iter().cloned().map(query).collect()
+}
+
+/// Surface a full send queue as [Error::QueueFull], other errors of sending as [Error::SendMessage].
+fn send_error(e: rings_core::error::Error) -> Error {
+    match e {
+        rings_core::error::Error::SendQueueFull(buffered) => Error::QueueFull(buffered),
+        e => Error::SendMessage(e),
+    }
+}
+
+/// Build the custom message of delivery receipt of tx_id.
+pub fn ack_message(tx_id: uuid::Uuid) -> Result<Message> {
+    let ack: Vec<u8> =
+        BackendMessage::from((MessageType::Ack.into(), &tx_id.as_bytes()[..])).into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(ack.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&ack);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
+
+/// Decode a custom message as a delivery receipt, return the tx_id it acknowledges.
+pub fn ack_of(msg: &CustomMessage) -> Option<uuid::Uuid> {
+    // Receipts are small, they are never chunked or compressed.
+    if msg.0.len() < 4 || msg.0[0] != 0 || msg.0[1] != 0 {
+        return None;
+    }
+    let msg = BackendMessage::try_from(&msg.0[4..]).ok()?;
+    if !matches!(msg.message_type.into(), MessageType::Ack) {
+        return None;
+    }
+    uuid::Uuid::from_slice(&msg.data).ok()
+}
+
+/// Build the custom message advertising capabilities of node.
+pub fn capabilities_message(capabilities: &Capabilities) -> Result<Message> {
+    let msg: Vec<u8> = BackendMessage::try_from((MessageType::Capabilities, capabilities))?.into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(msg.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&msg);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
+
+/// Decode a custom message as an advertisement of capabilities.
+pub fn capabilities_of(msg: &CustomMessage) -> Option<Capabilities> {
+    // Advertisements are small, they are never chunked or compressed.
+    if msg.0.len() < 4 || msg.0[0] != 0 || msg.0[1] != 0 {
+        return None;
+    }
+    let msg = BackendMessage::try_from(&msg.0[4..]).ok()?;
+    if !matches!(msg.message_type.into(), MessageType::Capabilities) {
+        return None;
+    }
+    bincode::deserialize(&msg.data).ok()
+}
+
+/// Build the custom message of ping with its id.
+pub fn ping_message(id: uuid::Uuid) -> Result<Message> {
+    probe_message(MessageType::Ping, id)
+}
+
+/// Build the custom message of pong replying the ping of id.
+pub fn pong_message(id: uuid::Uuid) -> Result<Message> {
+    probe_message(MessageType::Pong, id)
+}
+
+/// Build the custom message requesting node info of remote node, with id of request.
+pub fn node_info_request_message(id: uuid::Uuid) -> Result<Message> {
+    probe_message(MessageType::NodeInfoRequest, id)
+}
+
+/// Decode a custom message as a request of node info, return its id.
+pub fn node_info_request_of(msg: &CustomMessage) -> Option<uuid::Uuid> {
+    probe_of(msg, MessageType::NodeInfoRequest)
+}
+
+/// Build the custom message replying the request of node info of id.
+pub fn node_info_response_message(id: uuid::Uuid, info: &response::NodeInfo) -> Result<Message> {
+    let msg: Vec<u8> =
+        BackendMessage::try_from((MessageType::NodeInfoResponse, &(id, info)))?.into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(msg.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&msg);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
+
+/// Decode a custom message as a reply of node info, return the id of request and the info.
+pub fn node_info_response_of(msg: &CustomMessage) -> Option<(uuid::Uuid, response::NodeInfo)> {
+    // Replies are not chunked or compressed.
+    if msg.0.len() < 4 || msg.0[0] != 0 || msg.0[1] != 0 {
+        return None;
+    }
+    let msg = BackendMessage::try_from(&msg.0[4..]).ok()?;
+    if !matches!(msg.message_type.into(), MessageType::NodeInfoResponse) {
+        return None;
+    }
+    bincode::deserialize(&msg.data).ok()
+}
+
+/// Build the custom message pushing the renewed session of node to its peers.
+pub fn session_refresh_message(session: &Session) -> Result<Message> {
+    let msg: Vec<u8> = BackendMessage::try_from((MessageType::SessionRefresh, session))?.into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(msg.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&msg);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
+
+/// Decode a custom message as a refresh of session, return the renewed session.
+pub fn session_refresh_of(msg: &CustomMessage) -> Option<Session> {
+    // Refreshes are not chunked or compressed.
+    if msg.0.len() < 4 || msg.0[0] != 0 || msg.0[1] != 0 {
+        return None;
+    }
+    let msg = BackendMessage::try_from(&msg.0[4..]).ok()?;
+    if !matches!(msg.message_type.into(), MessageType::SessionRefresh) {
+        return None;
+    }
+    bincode::deserialize(&msg.data).ok()
+}
+
+/// The closest successor in node info, `None` if node has no successor.
+fn closest_successor(info: &response::NodeInfo) -> Option<Did> {
+    info.swarm
+        .dht
+        .successors
+        .iter()
+        .find_map(|s| Did::from_str(s).ok())
+}
+
+/// Node info of swarm, see [Processor::get_node_info].
+pub(crate) async fn node_info(swarm: &Swarm) -> response::NodeInfo {
+    response::NodeInfo {
+        version: crate::util::build_version(),
+        swarm: swarm.inspect().await,
+    }
+}
+
+/// Node info replied to remote nodes, without items of storage.
+pub(crate) async fn reported_node_info(swarm: &Swarm) -> response::NodeInfo {
+    let mut info = node_info(swarm).await;
+    info.swarm.persistence_storage.items.clear();
+    info.swarm.cache_storage.items.clear();
+    info
+}
+
+/// Decode a custom message as a ping, return its id.
+pub fn ping_of(msg: &CustomMessage) -> Option<uuid::Uuid> {
+    probe_of(msg, MessageType::Ping)
+}
+
+/// Decode a custom message as a pong, return the id of ping it replies.
+pub fn pong_of(msg: &CustomMessage) -> Option<uuid::Uuid> {
+    probe_of(msg, MessageType::Pong)
+}
+
+fn probe_message(message_type: MessageType, id: uuid::Uuid) -> Result<Message> {
+    let msg: Vec<u8> = BackendMessage::from((message_type.into(), &id.as_bytes()[..])).into();
+
+    let mut new_bytes: Vec<u8> = Vec::with_capacity(msg.len() + 4);
+    new_bytes.push(0);
+    new_bytes.extend_from_slice(&[0u8; 3]);
+    new_bytes.extend_from_slice(&msg);
+
+    Message::custom(&new_bytes).map_err(|_| Error::InvalidMessage)
+}
+
+fn probe_of(msg: &CustomMessage, message_type: MessageType) -> Option<uuid::Uuid> {
+    // Pings and pongs are small, they are never chunked or compressed.
+    if msg.0.len() < 4 || msg.0[0] != 0 || msg.0[1] != 0 {
+        return None;
+    }
+    let msg = BackendMessage::try_from(&msg.0[4..]).ok()?;
+    if msg.message_type != u16::from(message_type) {
+        return None;
+    }
+    uuid::Uuid::from_slice(&msg.data).ok()
+}
+
+/// Check if a backend message is the delivery receipt of tx_id.
+pub fn is_ack_of(msg: &BackendMessage, tx_id: uuid::Uuid) -> bool {
+    matches!(msg.message_type.into(), MessageType::Ack) && msg.data == tx_id.as_bytes()
+}
+
+/// Decode a backend message as a chunk of streaming http response of tx_id.
+pub fn http_response_chunk_of(
+    msg: &BackendMessage,
+    tx_id: uuid::Uuid,
+) -> Option<HttpResponseChunk> {
+    if !matches!(msg.message_type.into(), MessageType::HttpResponseChunk) {
+        return None;
+    }
+    bincode::deserialize::<HttpResponseChunk>(&msg.data)
+        .ok()
+        .filter(|chunk| chunk.tx_id == tx_id)
+}
+
+/// Compress message with gzip if it's larger than threshold.
+/// The original message is kept if compression doesn't make it smaller.
+/// Return the data and whether it's compressed.
+fn compress_message(msg: &[u8], threshold: Option<usize>) -> Result<(Bytes, bool)> {
+    let msg = Bytes::copy_from_slice(msg);
+    match threshold {
+        Some(threshold) if msg.len() > threshold => {
+            let compressed =
+                encode_data_gzip(&msg, COMPRESS_LEVEL).map_err(|_| Error::EncodeError)?;
+            if compressed.len() < msg.len() {
+                Ok((compressed, true))
+            } else {
+                Ok((msg, false))
+            }
+        }
+        _ => Ok((msg, false)),
+    }
+}
+
+/// Decompress data of custom message by the compressed mark in header.
+/// Compressed data is inflated to at most `max_bytes`, the max message size of receiver.
+pub fn decompress_message(compressed: u8, data: Bytes, max_bytes: usize) -> Result<Bytes> {
+    match compressed {
+        0 => Ok(data),
+        1 => decode_gzip_data_with_limit(&data, max_bytes).map_err(|_| Error::DecodeError),
+        _ => Err(Error::InvalidData),
+    }
+}
+
+/// Pack data into custom messages with chunked mark and compressed mark.
+/// The first byte of the 4 bytes header is the chunked mark,
+/// 0 for a whole message and 1 for a bincode serialized [Chunk].
+/// The second byte is the compressed mark, 1 if the whole message is gzip compressed.
+/// The id of chunks is also returned if the message is chunked.
+pub(crate) fn pack_custom_message(
+    msg: &[u8],
+    max_chunk_size: usize,
+    compressed: bool,
+) -> Result<(Option<uuid::Uuid>, Vec<Vec<u8>>)> {
+    let pack = |flag: u8, data: &[u8]| {
+        let mut new_msg = Vec::with_capacity(data.len() + 4);
+        new_msg.push(flag);
+        new_msg.push(compressed as u8);
+        new_msg.extend_from_slice(&[0u8; 2]);
+        new_msg.extend_from_slice(data);
+        new_msg
+    };
+
+    if msg.len() <= max_chunk_size {
+        return Ok((None, vec![pack(0, msg)]));
+    }
+
+    let chunks = Chunk::split(&Bytes::copy_from_slice(msg), max_chunk_size);
+    let packed = chunks
+        .iter()
+        .map(|c| {
+            c.to_bincode()
+                .map(|bytes| pack(1, &bytes))
+                .map_err(|_| Error::EncodeError)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((chunks.first().map(|c| c.meta.id), packed))
+}
+
+/// unpack custom message to text
+pub fn unpack_text_message(msg: &CustomMessage) -> Result<String> {
+    let (left, right) = msg.0.split_at(4);
+    if left[0] != 0 {
+        return Err(Error::InvalidData);
+    }
+    let data = decompress_message(
+        left[1],
+        Bytes::copy_from_slice(right),
+        DEFAULT_MAX_MESSAGE_BYTES,
+    )?;
+    let text = String::from_utf8(data.to_vec()).unwrap();
+    Ok(text)
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod test {
+    use futures::lock::Mutex;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::prelude::rings_core::dht::SuccessorWriter;
+    use crate::prelude::*;
+    use crate::tests::native::connect;
+    use crate::tests::native::prepare_processor;
+    use crate::tests::native::prepare_processor_builder;
+
+    #[test]
+    fn test_pack_custom_message() {
+        use crate::prelude::chunk::ChunkList;
+        use crate::prelude::chunk::ChunkManager;
+
+        let msg = "hello".repeat(10);
+        let (id, packed) = pack_custom_message(msg.as_bytes(), 50, false).unwrap();
+        assert!(id.is_none());
+        assert_eq!(packed.len(), 1);
+        assert_eq!(packed[0][..4], [0u8; 4]);
+        assert_eq!(&packed[0][4..], msg.as_bytes());
+
+        let msg = "hello".repeat(100);
+        let (id, packed) = pack_custom_message(msg.as_bytes(), 64, false).unwrap();
+        assert_eq!(packed.len(), 8);
+
+        let mut chunk_list = ChunkList::<64>::default();
+        let mut data = None;
+        for p in packed.iter().rev() {
+            assert_eq!(p[..4], [1u8, 0, 0, 0]);
+            assert!(data.is_none());
+            let chunk = Chunk::from_bincode(&p[4..]).unwrap();
+            assert_eq!(Some(chunk.meta.id), id);
+            data = chunk_list.handle(chunk);
+        }
+        assert_eq!(data.unwrap().to_vec(), msg.as_bytes());
+    }
+
+    #[test]
+    fn test_compress_message() {
+        // small message is not compressed
+        let msg = "hello".repeat(10);
+        let (data, compressed) =
+            compress_message(msg.as_bytes(), Some(DEFAULT_COMPRESS_THRESHOLD)).unwrap();
+        assert!(!compressed);
+        assert_eq!(data, msg.as_bytes());
+
+        // compressible message
+        let msg = r#"{"hello": "world"}"#.repeat(100);
+        let (data, compressed) =
+            compress_message(msg.as_bytes(), Some(DEFAULT_COMPRESS_THRESHOLD)).unwrap();
+        assert!(compressed);
+        assert!(data.len() < msg.len());
+        assert_eq!(
+            decompress_message(1, data.clone(), DEFAULT_MAX_MESSAGE_BYTES).unwrap(),
+            msg.as_bytes()
+        );
+
+        let (_, packed) = pack_custom_message(&data, DEFAULT_CHUNK_SIZE, compressed).unwrap();
+        assert_eq!(packed[0][..4], [0u8, 1, 0, 0]);
+        let text = unpack_text_message(&CustomMessage(packed[0].clone())).unwrap();
+        assert_eq!(text, msg);
+
+        // incompressible message is not expanded
+        let msg = (0..256)
+            .flat_map(|_| *uuid::Uuid::new_v4().as_bytes())
+            .collect::<Vec<_>>();
+        let (data, compressed) = compress_message(&msg, Some(DEFAULT_COMPRESS_THRESHOLD)).unwrap();
+        assert!(!compressed);
+        assert_eq!(data, msg);
+
+        // compression is disabled
+        let msg = "hello".repeat(1000);
+        let (data, compressed) = compress_message(msg.as_bytes(), None).unwrap();
+        assert!(!compressed);
+        assert_eq!(data, msg.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_delivery() {
+        let (p, path) = prepare_processor(None).await;
+        let (sender, mut receiver) = tokio::sync::broadcast::channel(16);
+        let destination: Did = SecretKey::random().address().into();
+        let other: Did = SecretKey::random().address().into();
+        let tx_id = uuid::Uuid::new_v4();
+        let timeout = std::time::Duration::from_millis(100);
+        let incoming = |from: Did, message_type: MessageType, data: &[u8]| {
+            IncomingMessage::from((from, BackendMessage::from((message_type.into(), data))))
+        };
+        p.receipts.sent(tx_id, destination);
+
+        sender
+            .send(incoming(
+                destination,
+                MessageType::Ack,
+                &uuid::Uuid::new_v4().as_bytes()[..],
+            ))
+            .unwrap();
+        assert!(!p.wait_for_delivery(&mut receiver, tx_id, timeout).await);
+
+        // A receipt from other node than destination is ignored.
+        p.receipts.record(tx_id, other);
+        sender
+            .send(incoming(other, MessageType::Ack, &tx_id.as_bytes()[..]))
+            .unwrap();
+        assert!(!p.wait_for_delivery(&mut receiver, tx_id, timeout).await);
+
+        p.receipts.record(tx_id, destination);
+        sender
+            .send(incoming(
+                destination,
+                MessageType::SimpleText,
+                &tx_id.as_bytes()[..],
+            ))
+            .unwrap();
+        sender
+            .send(incoming(
+                destination,
+                MessageType::Ack,
+                &tx_id.as_bytes()[..],
+            ))
+            .unwrap();
+        assert!(p.wait_for_delivery(&mut receiver, tx_id, timeout).await);
+
+        // A receipt received before waiting is remembered.
+        let tx_id = uuid::Uuid::new_v4();
+        let Message::CustomMessage(ack) = ack_message(tx_id).unwrap() else {
+            panic!("ack should be a custom message");
+        };
+        assert_eq!(ack_of(&ack), Some(tx_id));
+        p.receipts.sent(tx_id, destination);
+        p.receipts.record(tx_id, destination);
+        assert!(p.wait_for_delivery(&mut receiver, tx_id, timeout).await);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_list_peers_page() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let (p3, path3) = prepare_processor(None).await;
+
+        for p in [&p1, &p2, &p3] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+
+        for p in [&p2, &p3] {
+            connect(&p1, p).await;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+        let default = p1.list_peers_page(&Default::default()).await.unwrap();
+        assert_eq!(default.0.len(), 2);
+        assert_eq!(default.1, 2);
+        assert!(default.0.iter().all(|p| p.candidate_type.is_some()));
+        assert!(default.0.iter().all(|p| p.quality == 0.0));
+
+        p1.update_connection_quality().await;
+        let sampled = p1.list_peers_page(&Default::default()).await.unwrap();
+        assert!(sampled.0.iter().all(|p| p.quality > 0.0));
+        assert!(p1.peer_quality(p2.did()) > 0.0);
+
+        let options = ListPeersOptions {
+            offset: 1,
+            limit: Some(5),
+            states: None,
+        };
+        let (page, total) = p1.list_peers_page(&options).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(total, 2);
+        assert_eq!(page[0].did, default.0[1].did);
+
+        let options = ListPeersOptions {
+            offset: 0,
+            limit: Some(1),
+            states: Some(vec![default.0[0].state.to_uppercase()]),
+        };
+        let (page, total) = p1.list_peers_page(&options).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert!(total >= 1);
+
+        let options = ListPeersOptions {
+            offset: 0,
+            limit: None,
+            states: Some(vec!["no-such-state".to_string()]),
+        };
+        let (page, total) = p1.list_peers_page(&options).await.unwrap();
+        assert!(page.is_empty());
+        assert_eq!(total, 0);
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+        tokio::fs::remove_dir_all(path3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_gathering_state() {
+        let (p, path) = prepare_processor(None).await;
+
+        // Gathering of a fresh transport is not started until its offer is created.
+        let transport = p.swarm.new_transport().await.unwrap();
+        let state = transport.ice_gathering_state().await;
+        assert_eq!(
+            state.map(from_rtc_ice_gathering_state).as_deref(),
+            Some("new")
+        );
+        transport.close().await.unwrap();
+
+        // Candidates are gathered before the offer is returned.
+        p.swarm.create_offer().await.unwrap();
+        let pendings = p.list_pendings().await.unwrap();
+        let infos = p.response_transports(&pendings).await;
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].state, "new");
+        assert_eq!(infos[0].gathering_state.as_deref(), Some("complete"));
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_transport_direction() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+
+        let (transport, offer) = p1.swarm.create_offer().await.unwrap();
+        let (_, answer) = p2.swarm.answer_offer(offer).await.unwrap();
+
+        for (p, direction) in [(&p1, "outbound"), (&p2, "inbound")] {
+            let pendings = p.list_pendings().await.unwrap();
+            assert_eq!(pendings.len(), 1);
+            let info = response::TransportInfo::from((&pendings[0], None));
+            assert_eq!(info.direction.as_deref(), Some(direction));
+        }
+
+        p1.swarm.accept_answer(answer).await.unwrap();
+        transport
+            .connect_success_promise()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+        for (p, direction) in [(&p1, "outbound"), (&p2, "inbound")] {
+            let (peers, _) = p.list_peers_page(&Default::default()).await.unwrap();
+            assert_eq!(peers.len(), 1);
+            assert_eq!(peers[0].direction.as_deref(), Some(direction));
+        }
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_disconnect_where() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let (p3, path3) = prepare_processor(None).await;
+
+        for p in [&p1, &p2, &p3] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+
+        for p in [&p2, &p3] {
+            connect(&p1, p).await;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        assert_eq!(p1.swarm.get_transports().len(), 2);
+
+        let summary = p1
+            .disconnect_where(|peer| peer.state == "no-such-state")
+            .await;
+        assert_eq!(summary.closed, 0);
+        assert!(summary.errors.is_empty());
+        assert_eq!(p1.swarm.get_transports().len(), 2);
+
+        let p2_did = p2.did().to_string();
+        let summary = p1.disconnect_where(|peer| peer.did == p2_did).await;
+        assert_eq!(summary.closed, 1);
+        assert!(summary.errors.is_empty());
+        assert!(p1.swarm.get_transport(p2.did()).is_none());
+
+        let summary = p1.disconnect_all().await;
+        assert_eq!(summary.closed, 1);
+        assert!(summary.errors.is_empty());
+        assert!(p1.swarm.get_transports().is_empty());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+        tokio::fs::remove_dir_all(path3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_handshake_metrics() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        assert_eq!(p1.metrics().handshake.count, 0);
+
+        let (transport1, offer) = p1.create_offer().await.unwrap();
+        let (transport2, answer) = p2.answer_offer(offer).await.unwrap();
+        p1.swarm.accept_answer(answer).await.unwrap();
+        transport1
+            .connect_success_promise()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        p1.wait_for_data_channel_open(&transport1).await.unwrap();
+        p2.wait_for_data_channel_open(&transport2).await.unwrap();
+
+        for p in [&p1, &p2] {
+            let handshake = p.metrics().handshake;
+            assert_eq!(handshake.count, 1);
+            assert_eq!(handshake.p50_ms, handshake.max_ms);
+            assert_eq!(handshake.p95_ms, handshake.max_ms);
+        }
+
+        // Waiting again doesn't record a handshake.
+        p1.wait_for_data_channel_open(&transport1).await.unwrap();
+        assert_eq!(p1.metrics().handshake.count, 1);
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_successor_list_len() {
+        let (builder, path1) = prepare_processor_builder().await;
+        let p = builder.successor_list_len(8).build().unwrap();
+        assert_eq!(p.swarm.dht().successors().max_len(), 8);
+
+        let (builder, path2) = prepare_processor_builder().await;
+        assert!(matches!(
+            builder.successor_list_len(0).build(),
+            Err(Error::InvalidStabilization(_))
+        ));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_close_idle_transports() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let mut events = p1.events();
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        let (transport1, offer) = p1.create_offer().await.unwrap();
+        let (transport2, answer) = p2.answer_offer(offer).await.unwrap();
+        p1.swarm.accept_answer(answer).await.unwrap();
+        transport1
+            .connect_success_promise()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        p1.wait_for_data_channel_open(&transport1).await.unwrap();
+        p2.wait_for_data_channel_open(&transport2).await.unwrap();
+        while !p1
+            .swarm
+            .dht()
+            .successors()
+            .list()
+            .unwrap()
+            .contains(&p2.did())
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // A DHT neighbor is never idle.
+        assert!(p1.close_idle_transports(0).await.is_empty());
+
+        p1.send_simple_text_message(&p2.did().to_string(), "hello")
+            .await
+            .unwrap();
+        p1.swarm.dht().remove(p2.did()).unwrap();
+        assert!(p1.close_idle_transports(1000).await.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(p1.close_idle_transports(1000).await, vec![p2.did()]);
+        assert!(p1.swarm.get_transport(p2.did()).is_none());
+
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = events.next().await {
+                if event == NodeEvent::PeerIdleClosed(p2.did()) {
+                    return true;
+                }
+            }
+            false
+        })
+        .await
+        .unwrap();
+        assert!(closed);
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_connect_peers_via_http() {
+        let (processor, path) = prepare_processor(None).await;
+        let urls = ["http://127.0.0.1:1", "not a url"];
+        let results = processor.connect_peers_via_http(&urls, None).await;
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0], Err(Error::RemoteRpcError(_))));
+        assert!(results[1].is_err());
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_events() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        let events = p1.events();
+        pin_mut!(events);
+
+        connect(&p1, &p2).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let mut received = vec![];
+        while let Ok(Some(ev)) =
+            tokio::time::timeout(Duration::from_millis(100), events.next()).await
+        {
+            received.push(ev);
+        }
+        assert!(received.contains(&NodeEvent::PeerConnected(p2.did())));
+        assert!(received.contains(&NodeEvent::SuccessorChanged(vec![p2.did()])));
+
+        p1.disconnect(p2.did()).await.unwrap();
+        let mut received = vec![];
+        while let Ok(Some(ev)) =
+            tokio::time::timeout(Duration::from_millis(100), events.next()).await
+        {
+            received.push(ev);
+        }
+        assert!(received.contains(&NodeEvent::PeerDisconnected(p2.did())));
+        assert!(received.contains(&NodeEvent::TransportStateChanged(p2.did(), None)));
+        assert!(received.contains(&NodeEvent::SuccessorChanged(vec![])));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_shutdown() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let swarm2 = p2.swarm.clone();
+        tokio::spawn(async move { swarm2.listen().await });
+
+        let listen = p1.listen();
+        let stopped = p1.shutdown_signal();
+        let shutdown = async {
+            connect(&p1, &p2).await;
+            assert_eq!(p1.swarm.get_transports().len(), 1);
+            // A handshake in progress.
+            p1.swarm.create_offer().await.unwrap();
+            assert_eq!(p1.list_pendings().await.unwrap().len(), 1);
+            assert!(p1.health().listening);
+
+            p1.shutdown().await.unwrap();
+            assert!(p1.swarm.get_transports().is_empty());
+            assert!(p1.list_pendings().await.unwrap().is_empty());
+            assert!(!p1.health().listening);
+            assert!(p1.stabilization.is_stopped());
+            assert!(matches!(
+                p1.send_message(&p2.did().to_string(), b"hello", None).await,
+                Err(Error::ShuttingDown)
+            ));
+            // Calling it again is a no-op.
+            p1.shutdown().await.unwrap();
+        };
+        // The future of listen, including stabilization, resolves after shutdown.
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            futures::future::join3(listen, stopped, shutdown),
+        )
+        .await
+        .unwrap();
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_health() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+
+        let health = p1.health();
+        assert!(!health.listening);
+        assert!(!health.has_successor);
+        assert!(!health.ready);
+
+        let checks = async {
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            let health = p1.health();
+            assert!(health.listening);
+            assert_eq!(health.peers, 0);
+            // Listening but not joined the ring.
+            assert!(!health.ready);
+
+            connect(&p1, &p2).await;
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+            let health = p1.health();
+            assert_eq!(health.peers, 1);
+            assert!(health.has_successor);
+            assert!(health.ready);
+        };
+        tokio::select! {
+            _ = p1.listen() => unreachable!(),
+            _ = p2.listen() => unreachable!(),
+            _ = checks => {}
+        }
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_whoami() {
+        let (p1, path1) = prepare_processor(None).await;
+        let sm = p1.swarm.session_manager();
+
+        let whoami = p1.whoami();
+        assert_eq!(whoami.did, p1.did().to_string());
+        assert_eq!(whoami.authorizer_did, sm.authorizer_did().to_string());
+        assert!(whoami.session_expires_in_ms > 0);
+        assert!(whoami.session_expires_in_ms as i128 <= sm.session().remaining_ttl_ms());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_node_info_dht() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+        let info = p1.get_node_info().await.unwrap();
+        let dht = &info.swarm.dht;
+        assert_eq!(dht.did, p1.did().to_string());
+        assert_eq!(dht.successors, vec![p2.did().to_string()]);
+        // Fingers beyond p2 may be left empty, depending on the distance of dids.
+        assert_eq!(
+            dht.finger_table[0],
+            (Some(p2.did().to_string()), 0, dht.finger_table[0].2)
+        );
+        assert!(dht.finger_table[1..].iter().all(|(did, ..)| did.is_none()));
+        assert_eq!(dht.finger_table.last().unwrap().2, 159);
+
+        // the json output of dht is stable.
+        let json = serde_json::to_string(dht).unwrap();
+        let info2 = p1.get_node_info().await.unwrap();
+        assert_eq!(json, serde_json::to_string(&info2.swarm.dht).unwrap());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_response_stream() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(16);
+        let tx_id = uuid::Uuid::new_v4();
+        let from = Did::from(SecretKey::random().address());
+        let timeout = Duration::from_secs(5);
+        let send_chunk =
+            |tx_id: uuid::Uuid, seq: u64, part: HttpResponsePart| {
+                let msg = BackendMessage::try_from((
+                    MessageType::HttpResponseChunk,
+                    &HttpResponseChunk { tx_id, seq, part },
+                ))
+                .unwrap();
+                sender.send((from, msg).into()).unwrap();
+            };
+
+        // chunks arrive out of order, mixed with chunks of other requests or senders
+        send_chunk(tx_id, 2, HttpResponsePart::Body(Bytes::from("world")));
+        send_chunk(uuid::Uuid::new_v4(), 0, HttpResponsePart::End);
+        let forged =
+            BackendMessage::try_from((MessageType::HttpResponseChunk, &HttpResponseChunk {
+                tx_id,
+                seq: 1,
+                part: HttpResponsePart::Body(Bytes::from("forged")),
+            }))
+            .unwrap();
+        sender
+            .send((Did::from(SecretKey::random().address()), forged).into())
+            .unwrap();
+        send_chunk(tx_id, 0, HttpResponsePart::Head {
+            status: 200,
+            headers: HashMap::new(),
+        });
+        send_chunk(tx_id, 3, HttpResponsePart::End);
+        send_chunk(tx_id, 1, HttpResponsePart::Body(Bytes::from("hello ")));
+        send_chunk(tx_id, 4, HttpResponsePart::Body(Bytes::from("ignored")));
+
+        let parts = Processor::http_response_stream(receiver, from, tx_id, timeout)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(parts, vec![
+            HttpResponsePart::Head {
+                status: 200,
+                headers: HashMap::new(),
+            },
+            HttpResponsePart::Body(Bytes::from("hello ")),
+            HttpResponsePart::Body(Bytes::from("world")),
+            HttpResponsePart::End,
+        ]);
+
+        // an error is yielded if the channel is closed before the final part
+        let receiver = sender.subscribe();
+        send_chunk(tx_id, 0, HttpResponsePart::Body(Bytes::from("partial")));
+        drop(sender);
+        let parts = Processor::http_response_stream(receiver, from, tx_id, timeout)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(parts[1], HttpResponsePart::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_http_response_stream_limits() {
+        let (sender, receiver) = tokio::sync::broadcast::channel(1024);
+        let tx_id = uuid::Uuid::new_v4();
+        let from = Did::from(SecretKey::random().address());
+        let send_chunk = |seq: u64| {
+            let msg =
+                BackendMessage::try_from((MessageType::HttpResponseChunk, &HttpResponseChunk {
+                    tx_id,
+                    seq,
+                    part: HttpResponsePart::Body(Bytes::from("body")),
+                }))
+                .unwrap();
+            sender.send((from, msg).into()).unwrap();
+        };
+
+        // an error is yielded when too many chunks are out of order
+        for seq in 1..=HTTP_STREAM_MAX_PENDING_CHUNKS as u64 + 1 {
+            send_chunk(seq);
+        }
+        let parts = Processor::http_response_stream(receiver, from, tx_id, Duration::from_secs(5))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(parts, vec![HttpResponsePart::Error(
+            "too many out-of-order response chunks".to_string()
+        )]);
+
+        // an error is yielded when no chunk arrives in time
+        let receiver = sender.subscribe();
+        send_chunk(0);
+        let parts =
+            Processor::http_response_stream(receiver, from, tx_id, Duration::from_millis(100))
+                .collect::<Vec<_>>()
+                .await;
+        assert_eq!(parts, vec![
+            HttpResponsePart::Body(Bytes::from("body")),
+            HttpResponsePart::Error("timeout waiting for response chunk".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_processor_register_custom_message_type() {
+        let (p, path) = prepare_processor(None).await;
+        let registry = p.message_types();
+
+        for id in [0, 1, 8, 9, 100, 255] {
+            assert!(MessageType::is_reserved(id));
+            assert!(matches!(
+                p.register_custom_message_type(id, "reserved"),
+                Err(Error::ReservedMessageType(_))
+            ));
+        }
+        assert!(!MessageType::is_reserved(256));
+
+        p.register_custom_message_type(1000, "chat").unwrap();
+        assert!(matches!(
+            p.register_custom_message_type(1000, "game"),
+            Err(Error::DuplicatedMessageType(..))
+        ));
+        assert!(matches!(
+            p.register_custom_message_type(1001, "chat"),
+            Err(Error::DuplicatedMessageType(..))
+        ));
+        assert_eq!(registry.name(1000), Some("chat".to_string()));
+        assert_eq!(registry.id("chat"), Some(1000));
+        assert_eq!(registry.name(1001), None);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_peer_capabilities() {
+        let (builder1, path1) = prepare_processor_builder().await;
+        let p1 = builder1.require_capabilities(true).build().unwrap();
+        let (p2, path2) = prepare_processor(None).await;
+        p1.register_custom_message_type(1000, "chat").unwrap();
+        p2.register_custom_message_type(1001, "game").unwrap();
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        assert!(p1.peer_capabilities(p2.did()).is_none());
+
+        connect(&p1, &p2).await;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        let caps2 = p1.peer_capabilities(p2.did()).unwrap();
+        assert!(caps2.supports(1001));
+        assert!(!caps2.supports(1000));
+        assert!(caps2.supports(MessageType::SimpleText.into()));
+        assert!(caps2.supports(MessageType::Capabilities.into()));
+        let caps1 = p2.peer_capabilities(p1.did()).unwrap();
+        assert!(caps1.supports(1000));
+        assert!(!caps1.supports(1001));
+
+        // Sending a type not advertised by peer fails fast if capabilities are required.
+        let did2 = p2.did().to_string();
+        assert!(matches!(
+            p1.send_custom_message(&did2, 1000, b"hello".to_vec(), [0u8; 30])
+                .await,
+            Err(Error::UnsupportedMessageType(_, 1000))
+        ));
+        p1.send_custom_message(&did2, 1001, b"hello".to_vec(), [0u8; 30])
+            .await
+            .unwrap();
+        // The raw send path checks the message type in backend header too.
+        let msg: Vec<u8> = BackendMessage::new(1000, [0u8; 30], b"hello").into();
+        assert!(matches!(
+            p1.send_message(&did2, &msg, None).await,
+            Err(Error::UnsupportedMessageType(_, 1000))
+        ));
+        assert!(matches!(
+            p1.send_message_with_priority(&did2, &msg, None, Priority::Low)
+                .await,
+            Err(Error::UnsupportedMessageType(_, 1000))
+        ));
+        // A payload without backend header has no message type to check.
+        p1.send_message(&did2, b"hello", None).await.unwrap();
+        // Not required by default.
+        p2.send_custom_message(&p1.did().to_string(), 1001, b"hello".to_vec(), [0u8; 30])
+            .await
+            .unwrap();
+
+        p1.disconnect(p2.did()).await.unwrap();
+        assert!(p1.peer_capabilities(p2.did()).is_none());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_relay_http() {
+        use tokio::io::AsyncReadExt;
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\nX-Upstream: mock\r\nConnection: close\r\n\r\nnot found",
+                )
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let (builder, path) = prepare_processor_builder().await;
+        let processor = builder
+            .hidden_servers(vec![HiddenServerConfig {
+                name: "mock".to_string(),
+                register_service: None,
+                prefix: format!("http://{}", addr),
+            }])
+            .build()
+            .unwrap();
+
+        let request = HttpRequest::from(("mock", http::Method::GET, "/missing", 3000));
+        let resp = processor.relay_http(&request).await.unwrap();
+
+        // The response is received by requester as json.
+        let resp: response::HttpResponse =
+            serde_json::from_str(&serde_json::to_string(&resp).unwrap()).unwrap();
+        assert_eq!(resp.status, 404);
+        assert_eq!(resp.status_code(), Some(http::StatusCode::NOT_FOUND));
+        assert!(!resp.is_success());
+        assert_eq!(resp.headers.get("x-upstream"), Some(&"mock".to_string()));
+        assert_eq!(resp.headers.get("content-length"), Some(&"9".to_string()));
+        assert_eq!(resp.body_bytes().unwrap(), Some(b"not found".to_vec()));
+
+        let request = HttpRequest::from(("unknown", http::Method::GET, "/", 3000));
+        assert!(matches!(
+            processor.relay_http(&request).await,
+            Err(Error::InvalidService)
+        ));
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_ping() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+
+        let resp = p1.ping(p2.did(), 3, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(resp.did, p2.did().to_string());
+        assert_eq!(resp.sent, 3);
+        assert_eq!(resp.received, 3);
+        let (min, avg, max) = (
+            resp.min_ms.unwrap(),
+            resp.avg_ms.unwrap(),
+            resp.max_ms.unwrap(),
+        );
+        assert!(min <= avg && avg <= max);
+        assert!(p1.pings.inner.lock().unwrap().is_empty());
+
+        // Nobody replies pings to an absent did.
+        let absent: Did = SecretKey::random().address().into();
+        let resp = p1
+            .ping(absent, 1, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(resp.received, 0);
+        assert!(resp.min_ms.is_none() && resp.avg_ms.is_none() && resp.max_ms.is_none());
+        assert!(p1.pings.inner.lock().unwrap().is_empty());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_remote_node_info() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+
+        let info = p1
+            .remote_node_info(p2.did(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(info.version, crate::util::build_version());
+        assert_eq!(info.swarm.dht.did, p2.did().to_string());
+        assert!(info
+            .swarm
+            .transports
+            .iter()
+            .any(|t| t.did == p1.did().to_string()));
+        assert!(p1.node_infos.inner.lock().unwrap().is_empty());
+
+        // Nobody replies to an absent did.
+        let absent: Did = SecretKey::random().address().into();
+        let err = p1
+            .remote_node_info(absent, Duration::from_millis(500))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RemoteNodeInfoTimeout(did, 500) if did == absent.to_string()));
+        assert!(p1.node_infos.inner.lock().unwrap().is_empty());
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_crawl_ring() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        for (p, other) in [(&p1, &p2), (&p2, &p1)] {
+            while !p
+                .swarm
+                .dht()
+                .successors()
+                .list()
+                .unwrap()
+                .contains(&other.did())
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        let resp = p1.crawl_ring(8, Duration::from_secs(5)).await.unwrap();
+        let dids = resp.nodes.iter().map(|n| n.did.clone()).collect::<Vec<_>>();
+        assert_eq!(dids, vec![p1.did().to_string(), p2.did().to_string()]);
+        assert!(resp.nodes[1].peers.contains(&p1.did().to_string()));
+        assert!(resp.complete);
+        assert_eq!(resp.unreachable, None);
+
+        // No remote node is requested without hops.
+        let resp = p1.crawl_ring(0, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(resp.nodes.len(), 1);
+        assert!(!resp.complete);
+
+        // The walk stops at a successor which never replies.
+        let absent = p1.did() + Did::from(1u32);
+        p1.swarm.dht().successors().update(absent).unwrap();
+        let resp = p1.crawl_ring(8, Duration::from_millis(500)).await.unwrap();
+        assert_eq!(resp.nodes.len(), 1);
+        assert!(!resp.complete);
+        assert_eq!(resp.unreachable, Some(absent.to_string()));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_connect_peer_via_ws() {
+        use futures::SinkExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let p2 = Arc::new(p2);
+        for p in [&p1, p2.as_ref()] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+
+        // A signaling server forwarding offers to p2 and replying its answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        let answerer = p2.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            // Frames not responding the call are skipped by client.
+            ws.send(WsMessage::Text(
+                r#"{"jsonrpc":"2.0","method":"hello"}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+            let Some(Ok(WsMessage::Text(text))) = ws.next().await else {
+                panic!("expected a text frame of offer");
+            };
+            let call: jsonrpc_core::MethodCall = serde_json::from_str(&text).unwrap();
+            assert_eq!(call.method, method::Method::AnswerOffer.as_str());
+            let offer: Vec<String> = call.params.parse().unwrap();
+            let offer =
+                MessagePayload::<Message>::from_encoded(&Encoded::from(offer[0].as_str())).unwrap();
+            let (_, answer) = answerer.answer_offer(offer).await.unwrap();
+            let output = jsonrpc_core::Output::from(
+                Ok(serde_json::to_value(answer.encode().unwrap()).unwrap()),
+                call.id,
+                call.jsonrpc,
+            );
+            ws.send(WsMessage::Text(serde_json::to_string(&output).unwrap()))
+                .await
+                .unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let peer = p1.connect_peer_via_ws(&ws_url, None).await.unwrap();
+        assert_eq!(peer.did, p2.did().into_token());
+        assert!(p1.swarm.get_transport(p2.did()).is_some());
+
+        // Nothing is listening on the url after the server is done.
+        assert!(matches!(
+            p1.connect_peer_via_ws(&ws_url, None).await,
+            Err(Error::WsSignalingError(_))
+        ));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_reconnect_seed() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let p2 = Arc::new(p2);
+        assert!(matches!(p1.reconnect_seed(None).await, Err(Error::NoSeed)));
+
+        for p in [&p1, p2.as_ref()] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (_, receiver) = tokio::sync::broadcast::channel(1);
+        tokio::spawn(crate::native::endpoint::run_http_api(
+            addr.to_string(),
+            p2.clone(),
+            receiver,
+            None,
+        ));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let seed: Seed = serde_json::from_value(serde_json::json!({
+            "peers": [{"did": p2.did().to_string(), "endpoint": format!("http://{}", addr)}]
+        }))
+        .unwrap();
+        let results = p1.connect_with_seed(seed, None).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.is_ok());
+
+        // Connected peers of seed are skipped.
+        assert!(p1.reconnect_seed(None).await.unwrap().is_empty());
+
+        p1.disconnect(p2.did()).await.unwrap();
+        assert!(p1.swarm.get_transport(p2.did()).is_none());
+        while p2.swarm.get_and_check_transport(p1.did()).await.is_some() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let results = p1.reconnect_seed(None).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, format!("http://{}", addr));
+        assert!(results[0].1.is_ok());
+        assert!(p1.swarm.get_transport(p2.did()).is_some());
+
+        p2.shutdown().await.unwrap();
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
 
-/// unpack custom message to text
-pub fn unpack_text_message(msg: &CustomMessage) -> Result<String> {
-    let (left, right) = msg.0.split_at(4);
-    if left[0] != 0 {
-        return Err(Error::InvalidData);
-    }
-    let text = String::from_utf8(right.to_vec()).unwrap();
-    Ok(text)
-}
+    #[tokio::test]
+    async fn test_processor_connect_with_did_timeout() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
 
-#[cfg(test)]
-#[cfg(feature = "node")]
-mod test {
-    use futures::lock::Mutex;
+        // the offer is sent via p2, but nobody will answer it.
+        let absent: Did = SecretKey::random().address().into();
+        let Err(err) = p1.connect_with_did(absent, true, Some(500)).await else {
+            panic!("connect_with_did should timeout");
+        };
+        assert!(matches!(err, Error::ConnectTimeout(500)));
+        assert_eq!(err.code(), 607);
+        assert!(p1.swarm.pending_transports().await.unwrap().is_empty());
 
-    use super::*;
-    use crate::prelude::*;
-    use crate::tests::native::prepare_processor;
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_reject_malformed_ice_servers() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let config = serde_yaml::to_string(&ProcessorConfig {
+            ice_servers: "stun://stun.l.google.com:19302;http://ethereum.org".to_string(),
+            external_address: None,
+            session_manager: sm.dump().unwrap(),
+            stabilize_timeout: 200,
+        })
+        .unwrap();
+        let path = PersistenceStorage::random_path("./tmp");
+        let storage = PersistenceStorage::new_with_path(path.as_str())
+            .await
+            .unwrap();
+        let ret = ProcessorBuilder::from_config(config)
+            .unwrap()
+            .storage(storage)
+            .build();
+        assert!(matches!(ret, Err(Error::InvalidIceServer(_))));
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
 
     #[tokio::test]
     async fn test_processor_create_offer() {
@@ -557,6 +3850,38 @@ mod test {
         tokio::fs::remove_dir_all(path).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_processor_pending_transport_sdp() {
+        use crate::prelude::rings_core::prelude::webrtc::sdp::description::session::SessionDescription as Sdp;
+
+        let (processor, path) = prepare_processor(None).await;
+        let (transport, offer) = processor.create_offer().await.unwrap();
+        let desc = processor
+            .pending_transport_sdp(&transport.id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(desc.sdp_type, "offer");
+        Sdp::unmarshal(&mut std::io::Cursor::new(desc.sdp.as_bytes())).unwrap();
+        assert!(!desc.candidates().is_empty());
+
+        // It's the same session as the offer in Rings encoded form.
+        let Message::ConnectNodeSend(msg) = offer.data else {
+            panic!("expected an offer");
+        };
+        let offered = msg.offer.session_description().unwrap();
+        assert!(desc.ice_ufrag().is_some());
+        assert_eq!(desc.ice_ufrag(), offered.ice_ufrag());
+        assert_eq!(desc.ice_pwd(), offered.ice_pwd());
+
+        let err = processor
+            .pending_transport_sdp(&uuid::Uuid::new_v4().to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::TransportNotFound));
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_processor_close_pending_transport() {
         let (processor, path) = prepare_processor(None).await;
@@ -650,6 +3975,51 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_processor_broadcast_message() {
+        let (hub, path) = prepare_processor(None).await;
+        let mut peers = vec![];
+        for _ in 0..3 {
+            let msgs: Arc<Mutex<Vec<String>>> = Default::default();
+            let callback = Box::new(MsgCallbackStruct { msgs: msgs.clone() });
+            let (p, path) = prepare_processor(Some(callback)).await;
+            peers.push((p, path, msgs));
+        }
+
+        let swarm = hub.swarm.clone();
+        tokio::spawn(async move { swarm.listen().await });
+        for (p, _, _) in peers.iter() {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+
+            let transport = connect(&hub, p).await;
+            hub.wait_for_data_channel_open(&transport).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // The type is ascii in little endian, so that the message is collected as text.
+        let message_type = 0x0150;
+        let resp = hub
+            .broadcast_message(message_type, b"presence".to_vec())
+            .await;
+        assert!(resp.skipped.is_empty());
+        assert_eq!(resp.results.len(), 3);
+        for (p, _, _) in peers.iter() {
+            assert!(resp.results[&p.did().to_string()].ok);
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        for (_, path, msgs) in peers {
+            let msgs = msgs.lock().await;
+            assert_eq!(msgs.len(), 1);
+            let msg = BackendMessage::try_from(msgs[0].as_bytes()).unwrap();
+            assert_eq!(msg.message_type, message_type);
+            assert_eq!(msg.data, b"presence".to_vec());
+            tokio::fs::remove_dir_all(path).await.unwrap();
+        }
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_processor_handshake_msg() {
         let msgs1: Arc<Mutex<Vec<String>>> = Default::default();
@@ -740,7 +4110,7 @@ mod test {
 
         println!("send_message 1");
         let uuid1 = p1
-            .send_message(did2.as_str(), test_text1.as_bytes())
+            .send_message(did2.as_str(), test_text1.as_bytes(), None)
             .await
             .unwrap();
         println!("send_message 1 done, msg id: {}", uuid1);
@@ -749,7 +4119,7 @@ mod test {
 
         println!("send_message 2");
         let uuid2 = p2
-            .send_message(did1.as_str(), test_text2.as_bytes())
+            .send_message(did1.as_str(), test_text2.as_bytes(), None)
             .await
             .unwrap();
         println!("send_message 2 done, msg id: {}", uuid2);
@@ -778,4 +4148,632 @@ mod test {
         tokio::fs::remove_dir_all(path1).await.unwrap();
         tokio::fs::remove_dir_all(path2).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_processor_refresh_session() {
+        let key = SecretKey::random();
+        let sm = SessionManager::new_with_seckey(&key).unwrap();
+        let config = serde_yaml::to_string(&ProcessorConfig {
+            ice_servers: "stun://stun.l.google.com:19302".to_string(),
+            external_address: None,
+            session_manager: sm.dump().unwrap(),
+            stabilize_timeout: 200,
+        })
+        .unwrap();
+        let path1 = PersistenceStorage::random_path("./tmp");
+        let storage = PersistenceStorage::new_with_path(path1.as_str())
+            .await
+            .unwrap();
+        let p1 = ProcessorBuilder::from_config(config)
+            .unwrap()
+            .storage(storage)
+            .build()
+            .unwrap();
+        let msgs2: Arc<Mutex<Vec<String>>> = Default::default();
+        let (p2, path2) = prepare_processor(Some(Box::new(MsgCallbackStruct {
+            msgs: msgs2.clone(),
+        })))
+        .await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(p2.peer_session(p1.did()).is_none());
+
+        // The renewed session, which expires later, is pushed to p2.
+        let old = p1.swarm.session_manager();
+        let builder = SessionManagerBuilder::renew(&old);
+        let sig = key.sign(&builder.pack_session());
+        let renewed = builder.sig(sig.to_vec()).build().unwrap();
+        let session = renewed.session();
+        p1.refresh_session(renewed).await.unwrap();
+        assert_eq!(p1.swarm.session_manager().session(), session);
+        for _ in 0..50 {
+            if p2.peer_session(p1.did()).is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(p2.peer_session(p1.did()), Some(session));
+
+        // A message signed by the old session is dropped, the one signed by renewed session is verified.
+        let did2 = p2.did().to_string();
+        let renewed = p1.swarm.session_manager();
+        p1.swarm
+            .set_session_manager(SessionManager::from_str(&old.dump().unwrap()).unwrap())
+            .unwrap();
+        p1.send_message(&did2, b"old", None).await.unwrap();
+        p1.swarm
+            .set_session_manager(SessionManager::from_str(&renewed.dump().unwrap()).unwrap())
+            .unwrap();
+        p1.send_message(&did2, b"new", None).await.unwrap();
+        for _ in 0..50 {
+            if !msgs2.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(*msgs2.lock().await, vec!["new".to_string()]);
+
+        // A session of another authorizer can not replace the session of node.
+        let other = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        assert!(matches!(
+            p1.refresh_session(other).await,
+            Err(Error::RefreshSession(
+                rings_core::error::Error::AuthorizerMismatch(_, _)
+            ))
+        ));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_send_oversize_message() {
+        let (builder, path) = prepare_processor_builder().await;
+        let p = builder.max_message_bytes(16).build().unwrap();
+        let err = p
+            .send_message(&p.did().to_string(), &[0u8; 17], None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::MessageTooLarge(17, 16)));
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_drop_oversize_message() {
+        let msgs2: Arc<Mutex<Vec<String>>> = Default::default();
+        let callback2 = Box::new(MsgCallbackStruct {
+            msgs: msgs2.clone(),
+        });
+        let (p1, path1) = prepare_processor(None).await;
+        let (builder2, path2) = prepare_processor_builder().await;
+        let p2 = builder2
+            .message_callback(callback2)
+            .max_message_bytes(64)
+            .build()
+            .unwrap();
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+
+        let did2 = p2.did().to_string();
+        let oversize = "x".repeat(100);
+        p1.send_message(&did2, oversize.as_bytes(), None)
+            .await
+            .unwrap();
+        p1.send_message(&did2, b"small", None).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+
+        assert_eq!(*msgs2.try_lock().unwrap(), vec!["small".to_string()]);
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_prune_expired_sessions() {
+        let (processor, path) = prepare_processor(None).await;
+        let key = SecretKey::random();
+        let new_session = |ttl| {
+            let builder = SessionManagerBuilder::new(
+                Did::from(key.address()).to_string(),
+                "secp256k1".into(),
+            )
+            .ttl(ttl);
+            let sig = key.sign(&builder.pack_session());
+            builder.sig(sig.to_vec()).build().unwrap().session()
+        };
+        let expired = new_session(50);
+        let live = new_session(60 * 1000);
+        processor.verify_session(&expired).unwrap();
+        processor.verify_session(&live).unwrap();
+        assert_eq!(processor.list_sessions().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let sessions = processor.list_sessions();
+        let info = |session: &Session| {
+            sessions
+                .iter()
+                .find(|s| s.session_id == session.session_id())
+                .unwrap()
+                .clone()
+        };
+        assert!(info(&expired).expired);
+        assert!(info(&expired).remaining_ttl_ms < 0);
+        assert!(!info(&live).expired);
+        assert!(info(&live).remaining_ttl_ms > 0);
+        assert_eq!(info(&live).authorizer_did, Did::from(key.address()));
+
+        assert_eq!(processor.prune_expired_sessions(), 1);
+        let sessions = processor.list_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, live.session_id());
+        assert_eq!(processor.prune_expired_sessions(), 0);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_session_clock_skew() {
+        let (builder, path) = prepare_processor_builder().await;
+        let processor = builder.session_clock_skew_tolerance(0).build().unwrap();
+        let (builder, path2) = prepare_processor_builder().await;
+        let tolerant = builder
+            .session_clock_skew_tolerance(60 * 1000)
+            .build()
+            .unwrap();
+
+        let key = SecretKey::random();
+        let builder =
+            SessionManagerBuilder::new(Did::from(key.address()).to_string(), "secp256k1".into())
+                .ttl(50);
+        let sig = key.sign(&builder.pack_session());
+        let session = builder.sig(sig.to_vec()).build().unwrap().session();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The expired session is rejected and counted, unless it's within the tolerance.
+        assert!(processor.verify_session(&session).is_err());
+        assert_eq!(
+            processor.metrics().rejected_sessions,
+            response::RejectedSessionsSummary {
+                expired: 1,
+                not_yet_valid: 0,
+            }
+        );
+        tolerant.verify_session(&session).unwrap();
+        assert_eq!(tolerant.metrics().rejected_sessions.expired, 0);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_query_transports_timeout() {
+        let (builder, path) = prepare_processor_builder().await;
+        let p = builder.state_query_timeout(100).build().unwrap();
+
+        // The query of a stalled transport never resolves.
+        let queries = (0..40).map(|i| async move {
+            if i == 3 {
+                futures::future::pending::<()>().await;
+            }
+            Some(i)
+        });
+        let started = std::time::Instant::now();
+        let results = p.query_transports(queries).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(results.len(), 40);
+        assert_eq!(results[3], None);
+        assert!(results
+            .iter()
+            .enumerate()
+            .all(|(i, r)| i == 3 || *r == Some(i)));
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_storage_cache_expire_and_evict() {
+        let (builder, path) = prepare_processor_builder().await;
+        let p = builder.storage_cache(1, Some(500)).build().unwrap();
+        let v1: vnode::VirtualNode = "test_processor_storage_cache_1"
+            .to_string()
+            .try_into()
+            .unwrap();
+        let v2: vnode::VirtualNode = "test_processor_storage_cache_2"
+            .to_string()
+            .try_into()
+            .unwrap();
+        p.storage_store(v1.clone()).await.unwrap();
+        p.storage_store(v2.clone()).await.unwrap();
+
+        p.storage_fetch(v1.did).await.unwrap();
+        assert_eq!(p.storage_check_cache(v1.did).await, Some(v1.clone()));
+
+        // v1 is evicted by size.
+        p.storage_fetch(v2.did).await.unwrap();
+        assert_eq!(p.storage_check_cache(v1.did).await, None);
+        assert_eq!(p.storage_check_cache(v2.did).await, Some(v2.clone()));
+
+        // v2 is expired by ttl, until it's fetched again.
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(p.storage_check_cache(v2.did).await, None);
+        p.storage_fetch(v2.did).await.unwrap();
+        assert_eq!(p.storage_check_cache(v2.did).await, Some(v2));
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_storage_get() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let v: vnode::VirtualNode = "test_processor_storage_get".to_string().try_into().unwrap();
+
+        // A node without remote successor doesn't wait for a missing vnode.
+        let timeout = Duration::from_secs(5);
+        assert_eq!(p1.storage_get(v.did, timeout).await.unwrap(), None);
+
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        for (p, other) in [(&p1, &p2), (&p2, &p1)] {
+            while !p
+                .swarm
+                .dht()
+                .successors()
+                .list()
+                .unwrap()
+                .contains(&other.did())
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        // A freshly stored vnode is got by both, wherever it's held.
+        p1.storage_store(v.clone()).await.unwrap();
+        assert_eq!(
+            p2.storage_get(v.did, timeout).await.unwrap(),
+            Some(v.clone())
+        );
+        assert_eq!(p1.storage_get(v.did, timeout).await.unwrap(), Some(v));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_storage_truncate_remote() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        for (p, other) in [(&p1, &p2), (&p2, &p1)] {
+            while !p
+                .swarm
+                .dht()
+                .successors()
+                .list()
+                .unwrap()
+                .contains(&other.did())
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        // Replicas are held by either node, depending on their random dids.
+        // The vnode is stored instead of appended, since storing is idempotent on each holder.
+        let dht = p1.swarm.dht();
+        let vid = vnode::VirtualNode::gen_did("test_processor_storage_truncate_remote").unwrap();
+        let entries = (0..5)
+            .map(|i| format!("msg{}", i).encode().unwrap())
+            .collect::<Vec<_>>();
+        p2.storage_store(vnode::VirtualNode {
+            did: vid,
+            data: entries.clone(),
+            kind: vnode::VNodeType::Data,
+        })
+        .await
+        .unwrap();
+        let timeout = Duration::from_secs(5);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while p1.storage_get(vid, timeout).await.unwrap().map(|v| v.data)
+                != Some(entries.clone())
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // A stale copy cached by p1 is not used for truncating.
+        dht.local_cache_set(vnode::VirtualNode {
+            did: vid,
+            data: entries[..1].to_vec(),
+            kind: vnode::VNodeType::Data,
+        });
+        p1.storage_truncate(vid, 2).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while p1.storage_get(vid, timeout).await.unwrap().unwrap().data != entries[3..] {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // A vnode which is not resolved fails the truncation.
+        let absent = vnode::VirtualNode::gen_did("test_processor_storage_truncate_absent").unwrap();
+        let err = p1.storage_truncate(absent, 2).await.unwrap_err();
+        assert!(matches!(err, Error::VNodeNotResolved(did, _) if did == absent.to_string()));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_lookup_service_by_tag() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let name = "test_processor_lookup_service_by_tag";
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+        for (p, other) in [(&p1, &p2), (&p2, &p1)] {
+            while !p
+                .swarm
+                .dht()
+                .successors()
+                .list()
+                .unwrap()
+                .contains(&other.did())
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        let cpu = ServiceMetadata {
+            version: Some("1.0.0".to_string()),
+            tags: vec!["cpu".to_string()],
+            endpoint: None,
+        };
+        let gpu = ServiceMetadata {
+            version: Some("2.0.0".to_string()),
+            tags: vec!["gpu".to_string(), "cuda".to_string()],
+            endpoint: Some("https://gpu.example.com".to_string()),
+        };
+        p1.register_service_with_metadata(name, DEFAULT_SERVICE_TTL_MS, cpu.clone())
+            .await
+            .unwrap();
+        p2.register_service_with_metadata(name, DEFAULT_SERVICE_TTL_MS, gpu.clone())
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while p1.lookup_service(name, None).await.unwrap().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let records = p1.lookup_service(name, Some("gpu")).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].did, p2.did().to_string());
+        assert_eq!(records[0].metadata, gpu);
+
+        let records = p1.lookup_service(name, Some("cpu")).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].did, p1.did().to_string());
+        assert_eq!(records[0].metadata, cpu);
+
+        assert!(p1
+            .lookup_service(name, Some("tpu"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let (did, _) = p1
+            .send_to_service(name, Some("cuda"), b"hello", None)
+            .await
+            .unwrap();
+        assert_eq!(did, p2.did());
+        let err = p1
+            .send_to_service(name, Some("tpu"), b"hello", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ServiceNotFound(_)));
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_send_message_next_hop() {
+        async fn connect_successors(a: &Processor, b: &Processor) {
+            connect(a, b).await;
+            for (p, other) in [(a, b), (b, a)] {
+                while !p
+                    .swarm
+                    .dht()
+                    .successors()
+                    .list()
+                    .unwrap()
+                    .contains(&other.did())
+                {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let (p3, path3) = prepare_processor(None).await;
+        for p in [&p1, &p2, &p3] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+
+        // A line topology of p1 - p2 - p3.
+        connect_successors(&p1, &p2).await;
+        connect_successors(&p2, &p3).await;
+        // p1 may learn p3 from p2 while p3 is joining, keep p3 out of its view.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        if p1.swarm.get_transport(p3.did()).is_some() {
+            p1.disconnect(p3.did()).await.unwrap();
+        }
+        p1.swarm.dht().remove(p3.did()).unwrap();
+
+        // The message to the far node is relayed by the middle one.
+        let route = p1.route_query(&p3.did().to_string()).await.unwrap();
+        let (_, next_hop) = p1
+            .send_message_with_route(&p3.did().to_string(), b"hello", None)
+            .await
+            .unwrap();
+        assert_eq!(next_hop, p2.did());
+        assert_eq!(route.next_hop, next_hop.to_string());
+        assert!(route.relayed);
+        assert!(route.connected);
+
+        // The message to a connected peer is sent directly.
+        let route = p1.route_query(&p2.did().to_string()).await.unwrap();
+        let (_, next_hop) = p1
+            .send_message_with_route(&p2.did().to_string(), b"hello", None)
+            .await
+            .unwrap();
+        assert_eq!(next_hop, p2.did());
+        assert_eq!(route.next_hop, next_hop.to_string());
+        assert!(!route.relayed);
+
+        for path in [path1, path2, path3] {
+            tokio::fs::remove_dir_all(path).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processor_connect_with_did_wait_then_send() {
+        let msgs3: Arc<Mutex<Vec<String>>> = Default::default();
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        let (p3, path3) = prepare_processor(Some(Box::new(MsgCallbackStruct {
+            msgs: msgs3.clone(),
+        })))
+        .await;
+        for p in [&p1, &p2, &p3] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        for (a, b) in [(&p1, &p2), (&p3, &p2)] {
+            connect(a, b).await;
+        }
+        // p1 and p3 may connect each other via p2, keep them apart.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        if p1.swarm.get_transport(p3.did()).is_some() {
+            p1.disconnect(p3.did()).await.unwrap();
+        }
+        while p3.swarm.get_and_check_transport(p1.did()).await.is_some() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        p1.swarm.dht().remove(p3.did()).unwrap();
+
+        // A message sent right after connecting is delivered directly, without any sleep.
+        let did3 = p3.did().to_string();
+        p1.connect_with_did(p3.did(), true, None).await.unwrap();
+        let (_, next_hop) = p1
+            .send_message_with_route(&did3, b"hello", None)
+            .await
+            .unwrap();
+        assert_eq!(next_hop, p3.did());
+
+        for _ in 0..50 {
+            if !msgs3.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(*msgs3.lock().await, vec!["hello".to_string()]);
+
+        for path in [path1, path2, path3] {
+            tokio::fs::remove_dir_all(path).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processor_send_to_service_not_found() {
+        let (processor, path) = prepare_processor(None).await;
+        let err = processor
+            .send_to_service("no_such_service", None, b"hello", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ServiceNotFound(name) if name == "no_such_service"));
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_processor_subscribe_topic() {
+        let (p1, path1) = prepare_processor(None).await;
+        let topic = "test_processor_subscribe_topic";
+        let vid = vnode::VirtualNode::gen_did(topic).unwrap();
+        let recv = |mut sub: TopicSubscription| async move {
+            let msg = tokio::time::timeout(Duration::from_secs(5), sub.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            (sub, msg)
+        };
+
+        p1.storage_append_data(topic, "msg0".to_string().encode().unwrap())
+            .await
+            .unwrap();
+        let sub1 = p1.subscribe_topic(topic, 1).await.unwrap();
+        let sub2 = p1.subscribe_topic(topic, 0).await.unwrap();
+        assert_eq!(p1.topic_feeds.subscribers(vid), 2);
+
+        p1.storage_append_data(topic, "msg1".to_string().encode().unwrap())
+            .await
+            .unwrap();
+
+        // Messages before index are skipped.
+        let (sub1, msg) = recv(sub1).await;
+        assert_eq!(msg.topic, topic);
+        assert_eq!(msg.index, 1);
+        assert_eq!(msg.entry.content, "msg1");
+
+        // Both subscribers are pushed by the shared fetch loop.
+        let (sub2, msg) = recv(sub2).await;
+        assert_eq!(msg.index, 0);
+        assert_eq!(msg.entry.content, "msg0");
+        let (sub2, msg) = recv(sub2).await;
+        assert_eq!(msg.index, 1);
+        assert_eq!(msg.entry.content, "msg1");
+
+        // A subscriber joining later catches up from cache.
+        let sub3 = p1.subscribe_topic(topic, 0).await.unwrap();
+        let (sub3, msg) = recv(sub3).await;
+        assert_eq!(msg.entry.content, "msg0");
+        assert_eq!(p1.topic_feeds.subscribers(vid), 3);
+
+        drop(sub1);
+        drop(sub2);
+        assert_eq!(p1.topic_feeds.subscribers(vid), 1);
+        drop(sub3);
+        assert_eq!(p1.topic_feeds.subscribers(vid), 0);
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+    }
 }
@@ -0,0 +1,136 @@
+//! Connection quality scoring of peers.
+//!
+//! [ConnectionQuality] samples [ConnectionStats] of each transport periodically, and keeps
+//! smoothed round trip time, jitter and packet loss of them. Jitter is the smoothed difference
+//! between consecutive round trip times, as the interarrival jitter of RFC 3550.
+//!
+//! The score of a connection is in `0.0..=1.0`, higher is better:
+//!
+//! ```text
+//! quality = (1 - packet_loss) * QUALITY_RTT_REF_MS / (QUALITY_RTT_REF_MS + rtt_ms + QUALITY_JITTER_WEIGHT * jitter_ms)
+//! ```
+//!
+//! So a lossless connection with 100ms round trip time and no jitter scores `0.5`.
+//! A connection without any sample scores `0.0`.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::prelude::rings_core::prelude::uuid;
+use crate::prelude::rings_core::types::ice_transport::ConnectionStats;
+
+/// Round trip time in ms which halves the score of a lossless connection without jitter
+pub const QUALITY_RTT_REF_MS: f64 = 100.0;
+/// Weight of jitter relative to round trip time in score
+pub const QUALITY_JITTER_WEIGHT: f64 = 2.0;
+/// Weight of the latest sample in smoothed values
+pub const QUALITY_SMOOTHING: f64 = 0.2;
+
+/// Smoothed stats of a connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct QualityState {
+    rtt_ms: f64,
+    jitter_ms: f64,
+    packet_loss: f64,
+}
+
+impl QualityState {
+    fn sample(&mut self, stats: &ConnectionStats) {
+        let jitter = (stats.rtt_ms - self.rtt_ms).abs();
+        self.jitter_ms += (jitter - self.jitter_ms) * QUALITY_SMOOTHING;
+        self.rtt_ms += (stats.rtt_ms - self.rtt_ms) * QUALITY_SMOOTHING;
+        self.packet_loss += (stats.packet_loss - self.packet_loss) * QUALITY_SMOOTHING;
+    }
+
+    fn score(&self) -> f32 {
+        let delay = self.rtt_ms.max(0.0) + QUALITY_JITTER_WEIGHT * self.jitter_ms.max(0.0);
+        let delivered = 1.0 - self.packet_loss.clamp(0.0, 1.0);
+        (delivered * QUALITY_RTT_REF_MS / (QUALITY_RTT_REF_MS + delay)) as f32
+    }
+}
+
+impl From<&ConnectionStats> for QualityState {
+    fn from(stats: &ConnectionStats) -> Self {
+        Self {
+            rtt_ms: stats.rtt_ms,
+            jitter_ms: 0.0,
+            packet_loss: stats.packet_loss,
+        }
+    }
+}
+
+/// Quality scores of connections, keyed by transport id.
+#[derive(Default)]
+pub struct ConnectionQuality {
+    states: RwLock<HashMap<uuid::Uuid, QualityState>>,
+}
+
+impl ConnectionQuality {
+    /// Accumulate a sample of stats of a transport.
+    pub fn update(&self, transport_id: uuid::Uuid, stats: &ConnectionStats) {
+        let Ok(mut states) = self.states.write() else {
+            return;
+        };
+        states
+            .entry(transport_id)
+            .and_modify(|s| s.sample(stats))
+            .or_insert_with(|| QualityState::from(stats));
+    }
+
+    /// Score of a transport, `0.0` if it's never sampled.
+    pub fn score(&self, transport_id: uuid::Uuid) -> f32 {
+        self.states
+            .read()
+            .ok()
+            .and_then(|states| states.get(&transport_id).map(|s| s.score()))
+            .unwrap_or_default()
+    }
+
+    /// Forget transports not in `transport_ids`, such as closed ones.
+    pub fn retain(&self, transport_ids: &[uuid::Uuid]) {
+        if let Ok(mut states) = self.states.write() {
+            states.retain(|id, _| transport_ids.contains(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_score() {
+        let quality = ConnectionQuality::default();
+        let id = uuid::Uuid::new_v4();
+        assert_eq!(quality.score(id), 0.0);
+
+        quality.update(id, &ConnectionStats::new(0.0, 10, 10));
+        assert_eq!(quality.score(id), 1.0);
+
+        let id = uuid::Uuid::new_v4();
+        quality.update(id, &ConnectionStats::new(100.0, 10, 10));
+        assert_eq!(quality.score(id), 0.5);
+
+        let id = uuid::Uuid::new_v4();
+        quality.update(id, &ConnectionStats::new(100.0, 10, 5));
+        assert_eq!(quality.score(id), 0.25);
+    }
+
+    #[test]
+    fn test_quality_jitter() {
+        let quality = ConnectionQuality::default();
+        let stable = uuid::Uuid::new_v4();
+        let jittery = uuid::Uuid::new_v4();
+        for rtt in [50.0, 50.0, 50.0, 50.0] {
+            quality.update(stable, &ConnectionStats::new(rtt, 10, 10));
+        }
+        for rtt in [10.0, 90.0, 10.0, 90.0] {
+            quality.update(jittery, &ConnectionStats::new(rtt, 10, 10));
+        }
+        assert!(quality.score(stable) > quality.score(jittery));
+
+        quality.retain(&[stable]);
+        assert_eq!(quality.score(jittery), 0.0);
+        assert!(quality.score(stable) > 0.0);
+    }
+}
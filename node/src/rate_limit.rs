@@ -0,0 +1,261 @@
+//! Rate limiting of custom messages per sender did.
+//!
+//! [RateLimiter] keeps a token bucket for each origin sender of custom messages.
+//! A message is dropped when the bucket of its sender is empty.
+//! At most `max_buckets` senders are tracked. When it's full, senders whose buckets are refilled
+//! are forgotten, at most once per refilling period, and messages of new senders are dropped
+//! until there is room.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::consts::RATE_LIMIT_MAX_BUCKETS;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::message::handlers::MessageValidator;
+use crate::prelude::rings_core::message::Message;
+use crate::prelude::rings_core::message::MessagePayload;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+
+/// Config of rate limiting, each sender can send `burst` messages at once,
+/// and then `messages_per_sec` messages per second.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// rate of refilling tokens of a sender
+    pub messages_per_sec: u32,
+    /// capacity of token bucket of a sender
+    pub burst: u32,
+    /// dids which bypass rate limiting
+    pub allowlist: HashSet<Did>,
+    /// log a warning when a message is dropped
+    pub log_dropped: bool,
+    /// max count of senders tracked
+    pub max_buckets: usize,
+}
+
+impl RateLimitConfig {
+    /// Create a config with rate and burst, without allowlist.
+    pub fn new(messages_per_sec: u32, burst: u32) -> Self {
+        Self {
+            messages_per_sec,
+            burst,
+            allowlist: HashSet::new(),
+            log_dropped: true,
+            max_buckets: RATE_LIMIT_MAX_BUCKETS,
+        }
+    }
+
+    /// Set max count of senders tracked.
+    pub fn max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets;
+        self
+    }
+
+    /// Milliseconds to refill an empty bucket.
+    fn refill_period_ms(&self) -> u128 {
+        let rate = self.messages_per_sec.max(1) as u128;
+        (self.burst as u128 * 1000 + rate - 1) / rate
+    }
+
+    /// Add dids which bypass rate limiting.
+    pub fn allow(mut self, dids: impl IntoIterator<Item = Did>) -> Self {
+        self.allowlist.extend(dids);
+        self
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated_ms: u128,
+}
+
+struct Buckets {
+    buckets: HashMap<Did, TokenBucket>,
+    swept_ms: Option<u128>,
+}
+
+/// Token bucket rate limiter keyed by sender did.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter with config.
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                swept_ms: None,
+            }),
+        }
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket, now_ms: u128) {
+        let elapsed_ms = now_ms.saturating_sub(bucket.updated_ms) as f64;
+        let refilled = elapsed_ms * self.config.messages_per_sec as f64 / 1000.0;
+        bucket.tokens = (bucket.tokens + refilled).min(self.config.burst as f64);
+        bucket.updated_ms = now_ms.max(bucket.updated_ms);
+    }
+
+    /// Take a token of sender at `now_ms`, return false if the message should be dropped.
+    pub fn check(&self, did: Did, now_ms: u128) -> bool {
+        if self.config.allowlist.contains(&did) {
+            return true;
+        }
+        let Ok(mut guard) = self.buckets.lock() else {
+            return true;
+        };
+        let Buckets { buckets, swept_ms } = &mut *guard;
+
+        if buckets.len() >= self.config.max_buckets && !buckets.contains_key(&did) {
+            // Forget senders whose buckets are refilled, no more than once per refilling period,
+            // since no bucket can be refilled after the last sweep before that.
+            let due = swept_ms.map_or(true, |ms| {
+                now_ms.saturating_sub(ms) >= self.config.refill_period_ms()
+            });
+            if due {
+                *swept_ms = Some(now_ms);
+                buckets.retain(|_, bucket| {
+                    self.refill(bucket, now_ms);
+                    bucket.tokens < self.config.burst as f64
+                });
+            }
+            if buckets.len() >= self.config.max_buckets {
+                return false;
+            }
+        }
+
+        let bucket = buckets.entry(did).or_insert(TokenBucket {
+            tokens: self.config.burst as f64,
+            updated_ms: now_ms,
+        });
+        self.refill(bucket, now_ms);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl MessageValidator for RateLimiter {
+    async fn validate(&self, ctx: &MessagePayload<Message>) -> Option<String> {
+        self.validate_at(ctx, get_epoch_ms())
+    }
+}
+
+impl RateLimiter {
+    /// Check a message at `now_ms`, return the reason if it should be dropped.
+    fn validate_at(&self, ctx: &MessagePayload<Message>, now_ms: u128) -> Option<String> {
+        if !matches!(ctx.data, Message::CustomMessage(_)) {
+            return None;
+        }
+        let sender = ctx.relay.origin_sender();
+        if self.check(sender, now_ms) {
+            return None;
+        }
+        if self.config.log_dropped {
+            tracing::warn!("drop message {} from {}, rate limited", ctx.tx_id, sender);
+        }
+        Some(format!("rate limited: {}", sender))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod tests {
+    use super::*;
+    use crate::prelude::rings_core::ecc::SecretKey;
+    use crate::prelude::SessionManager;
+
+    #[test]
+    fn test_rate_limit_per_did() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(10, 10));
+        let did1 = Did::from(SecretKey::random().address());
+        let did2 = Did::from(SecretKey::random().address());
+
+        let passed = (0..100).filter(|_| limiter.check(did1, 1000)).count();
+        assert_eq!(passed, 10);
+        // The other did is not affected.
+        let passed = (0..10).filter(|_| limiter.check(did2, 1000)).count();
+        assert_eq!(passed, 10);
+
+        // Tokens are refilled at 10 messages per second.
+        assert!(!limiter.check(did1, 1099));
+        assert!(limiter.check(did1, 1100));
+        assert!(!limiter.check(did1, 1100));
+        let passed = (0..100).filter(|_| limiter.check(did1, 3000)).count();
+        assert_eq!(passed, 10);
+    }
+
+    #[test]
+    fn test_rate_limit_allowlist() {
+        let did1 = Did::from(SecretKey::random().address());
+        let did2 = Did::from(SecretKey::random().address());
+        let limiter = RateLimiter::new(RateLimitConfig::new(10, 10).allow([did1]));
+
+        assert!((0..100).all(|_| limiter.check(did1, 1000)));
+        assert_eq!((0..100).filter(|_| limiter.check(did2, 1000)).count(), 10);
+    }
+
+    #[test]
+    fn test_rate_limit_max_buckets() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(10, 10).max_buckets(2));
+        let did1 = Did::from(SecretKey::random().address());
+        let did2 = Did::from(SecretKey::random().address());
+        let did3 = Did::from(SecretKey::random().address());
+
+        assert!(limiter.check(did1, 1000));
+        assert!(limiter.check(did2, 1000));
+        // No bucket is refilled, the new sender is dropped.
+        assert!(!limiter.check(did3, 1000));
+        // It's not swept again before a refilling period passed.
+        assert!(!limiter.check(did3, 1500));
+        assert_eq!((0..10).filter(|_| limiter.check(did2, 1500)).count(), 10);
+        // The bucket of did1 is refilled and forgotten, but did2 is still tracked.
+        assert!(limiter.check(did3, 2000));
+        assert_eq!(limiter.buckets.lock().unwrap().buckets.len(), 2);
+        assert!(limiter.check(did2, 2000));
+    }
+
+    #[test]
+    fn test_rate_limit_validate_custom_message() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(10, 10));
+        let sm1 = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let sm2 = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+        let destination = Did::from(SecretKey::random().address());
+
+        let payloads = (0..100)
+            .map(|_| {
+                MessagePayload::new_send(
+                    Message::custom(b"hello").unwrap(),
+                    &sm1,
+                    destination,
+                    destination,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let dropped = payloads
+            .iter()
+            .filter(|payload| limiter.validate_at(payload, 1000).is_some())
+            .count();
+        assert_eq!(dropped, 90);
+
+        let payload = MessagePayload::new_send(
+            Message::custom(b"hello").unwrap(),
+            &sm2,
+            destination,
+            destination,
+        )
+        .unwrap();
+        assert!(limiter.validate_at(&payload, 1000).is_none());
+    }
+}
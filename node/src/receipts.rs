@@ -0,0 +1,149 @@
+//! Delivery receipts of messages sent by node.
+//!
+//! A receipt is recorded as it arrives, and kept for [DELIVERY_RECEIPT_TTL_MS],
+//! so that a sender asking after the receipt arrived still sees its message delivered.
+//! Destinations of sent messages are kept for the same time, and a message is only
+//! delivered by a receipt from its destination, so that other nodes can't forge it.
+#![warn(missing_docs)]
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::consts::DELIVERY_RECEIPTS_CAPACITY;
+use crate::consts::DELIVERY_RECEIPT_TTL_MS;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::prelude::uuid;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+
+/// tx_id of recent messages with their dids, oldest first.
+type Recent = VecDeque<(uuid::Uuid, Did, u128)>;
+
+/// Destinations of recently sent messages, and senders of recently received delivery receipts.
+/// Receipts are recorded as they arrive, so a receipt received before waiting is not missed.
+#[derive(Default)]
+pub(crate) struct DeliveryReceipts {
+    sent: Mutex<Recent>,
+    received: Mutex<Recent>,
+}
+
+impl DeliveryReceipts {
+    /// Remember the destination of a message sent.
+    pub(crate) fn sent(&self, tx_id: uuid::Uuid, destination: Did) {
+        Self::push(&self.sent, tx_id, destination, get_epoch_ms())
+    }
+
+    /// Record a receipt of tx_id sent back by `sender`.
+    pub(crate) fn record(&self, tx_id: uuid::Uuid, sender: Did) {
+        Self::push(&self.received, tx_id, sender, get_epoch_ms())
+    }
+
+    /// Check if a receipt of tx_id is received from the destination of message.
+    pub(crate) fn contains(&self, tx_id: uuid::Uuid) -> bool {
+        self.contains_at(tx_id, get_epoch_ms())
+    }
+
+    fn contains_at(&self, tx_id: uuid::Uuid, now: u128) -> bool {
+        let Some(destination) = Self::find(&self.sent, tx_id, now) else {
+            return false;
+        };
+        let mut received = self.received.lock().unwrap();
+        Self::expire(&mut received, now);
+        received
+            .iter()
+            .any(|(id, sender, _)| *id == tx_id && *sender == destination)
+    }
+
+    fn find(recent: &Mutex<Recent>, tx_id: uuid::Uuid, now: u128) -> Option<Did> {
+        let mut recent = recent.lock().unwrap();
+        Self::expire(&mut recent, now);
+        recent
+            .iter()
+            .find(|(id, _, _)| *id == tx_id)
+            .map(|(_, did, _)| *did)
+    }
+
+    fn push(recent: &Mutex<Recent>, tx_id: uuid::Uuid, did: Did, now: u128) {
+        let mut recent = recent.lock().unwrap();
+        Self::expire(&mut recent, now);
+        recent.push_back((tx_id, did, now));
+        if recent.len() > DELIVERY_RECEIPTS_CAPACITY {
+            recent.pop_front();
+        }
+    }
+
+    fn expire(recent: &mut Recent, now: u128) {
+        while let Some((_, _, ts)) = recent.front() {
+            if now.saturating_sub(*ts) <= DELIVERY_RECEIPT_TTL_MS {
+                break;
+            }
+            recent.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::rings_core::ecc::SecretKey;
+
+    fn random_did() -> Did {
+        SecretKey::random().address().into()
+    }
+
+    #[test]
+    fn test_delivery_receipts_expire() {
+        let receipts = DeliveryReceipts::default();
+        let tx_id = uuid::Uuid::new_v4();
+        let destination = random_did();
+        assert!(!receipts.contains_at(tx_id, 0));
+
+        DeliveryReceipts::push(&receipts.sent, tx_id, destination, 0);
+        DeliveryReceipts::push(&receipts.received, tx_id, destination, 0);
+        assert!(receipts.contains_at(tx_id, DELIVERY_RECEIPT_TTL_MS));
+        assert!(!receipts.contains_at(tx_id, DELIVERY_RECEIPT_TTL_MS + 1));
+    }
+
+    #[test]
+    fn test_delivery_receipts_capacity() {
+        let receipts = DeliveryReceipts::default();
+        let first = uuid::Uuid::new_v4();
+        let destination = random_did();
+        DeliveryReceipts::push(&receipts.sent, first, destination, 0);
+        DeliveryReceipts::push(&receipts.received, first, destination, 0);
+        for _ in 0..DELIVERY_RECEIPTS_CAPACITY {
+            DeliveryReceipts::push(&receipts.received, uuid::Uuid::new_v4(), destination, 0);
+        }
+
+        // The oldest receipt is dropped when it's full.
+        assert!(!receipts.contains_at(first, 0));
+        assert_eq!(
+            receipts.received.lock().unwrap().len(),
+            DELIVERY_RECEIPTS_CAPACITY
+        );
+    }
+
+    #[test]
+    fn test_delivery_receipts_from_destination() {
+        let receipts = DeliveryReceipts::default();
+        let tx_id = uuid::Uuid::new_v4();
+        let destination = random_did();
+
+        // A receipt of unknown message is not a delivery.
+        receipts.record(tx_id, destination);
+        assert!(!receipts.contains(tx_id));
+
+        // A receipt from other node is not a delivery.
+        let tx_id = uuid::Uuid::new_v4();
+        receipts.sent(tx_id, destination);
+        receipts.record(tx_id, random_did());
+        assert!(!receipts.contains(tx_id));
+
+        receipts.record(tx_id, destination);
+        assert!(receipts.contains(tx_id));
+
+        // A receipt arrived before the message is remembered as sent is kept.
+        let tx_id = uuid::Uuid::new_v4();
+        receipts.record(tx_id, destination);
+        receipts.sent(tx_id, destination);
+        assert!(receipts.contains(tx_id));
+    }
+}
@@ -0,0 +1,337 @@
+//! Automatic reconnection of sticky peers.
+//!
+//! A sticky peer is a did that the node cares about. When its transport is dropped,
+//! [StickyPeers] will retry connecting it with exponential backoff and jitter.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::future::FutureExt;
+use futures::pin_mut;
+use futures::select;
+use futures_timer::Delay;
+use rand::Rng;
+
+use crate::consts::DEFAULT_RECONNECT_BASE_DELAY_MS;
+use crate::consts::DEFAULT_RECONNECT_CHECK_INTERVAL_MS;
+use crate::consts::DEFAULT_RECONNECT_MAX_DELAY_MS;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::transports::manager::TransportManager;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::rings_rpc::response;
+use crate::prelude::uuid;
+use crate::prelude::IceTransportInterface;
+use crate::prelude::Swarm;
+
+/// Config of reconnection, the delay of n-th retry is `base_delay_ms * 2^(n-1)`,
+/// capped by `max_delay_ms`, with a random jitter of up to half of the delay.
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// delay before the first retry
+    pub base_delay_ms: u64,
+    /// cap of delay between retries
+    pub max_delay_ms: u64,
+    /// interval of checking transports of sticky peers
+    pub check_interval_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: DEFAULT_RECONNECT_BASE_DELAY_MS,
+            max_delay_ms: DEFAULT_RECONNECT_MAX_DELAY_MS,
+            check_interval_ms: DEFAULT_RECONNECT_CHECK_INTERVAL_MS,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// Delay before the `attempt`-th retry, in range of `[delay / 2, delay]`.
+    pub fn backoff_ms(&self, attempt: u32) -> u64 {
+        let delay = 2u64
+            .saturating_pow(attempt.saturating_sub(1))
+            .saturating_mul(self.base_delay_ms)
+            .min(self.max_delay_ms);
+        let half = delay / 2;
+        half + rand::thread_rng().gen_range(0..=delay - half)
+    }
+}
+
+/// Reconnection state of a sticky peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StickyPeerState {
+    /// Transport of peer is connected.
+    Connected,
+    /// Transport of peer is dropped, `attempt` retries have been made,
+    /// and the next one will be made at `next_retry_ms` (epoch time in ms).
+    Reconnecting {
+        /// count of retries have been made
+        attempt: u32,
+        /// epoch time in ms of next retry
+        next_retry_ms: u128,
+    },
+}
+
+impl StickyPeerState {
+    /// Convert to the jsonrpc response of sticky peer.
+    pub fn into_response(self, did: Did) -> response::StickyPeer {
+        match self {
+            Self::Connected => response::StickyPeer {
+                did: did.to_string(),
+                state: "connected".to_owned(),
+                attempt: 0,
+                next_retry_in_ms: 0,
+            },
+            Self::Reconnecting {
+                attempt,
+                next_retry_ms,
+            } => response::StickyPeer {
+                did: did.to_string(),
+                state: "reconnecting".to_owned(),
+                attempt,
+                next_retry_in_ms: next_retry_ms.saturating_sub(get_epoch_ms()) as u64,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StickyPeer {
+    connected: bool,
+    attempt: u32,
+    next_retry_ms: u128,
+    /// transport created by the last retry, closed when it's replaced or cancelled.
+    pending: Option<uuid::Uuid>,
+}
+
+/// Sticky peers of a node, which will be reconnected when their transports dropped.
+pub struct StickyPeers {
+    config: ReconnectConfig,
+    peers: Mutex<HashMap<Did, StickyPeer>>,
+}
+
+impl StickyPeers {
+    /// Create an empty set of sticky peers.
+    pub fn new(config: ReconnectConfig) -> Self {
+        Self {
+            config,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add a sticky peer, return false if it's already added.
+    /// The peer will be connected at the next check if it's not connected.
+    pub fn add(&self, did: Did) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        if peers.contains_key(&did) {
+            return false;
+        }
+        peers.insert(did, StickyPeer::default());
+        true
+    }
+
+    /// Remove a sticky peer and cancel its in-flight retry.
+    /// Return false if it's not a sticky peer.
+    pub async fn remove(&self, swarm: &Swarm, did: Did) -> bool {
+        let removed = self.peers.lock().unwrap().remove(&did);
+        match removed {
+            Some(peer) => {
+                if let Some(id) = peer.pending {
+                    Self::cancel(swarm, id).await;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check if did is a sticky peer.
+    pub fn contains(&self, did: Did) -> bool {
+        self.peers.lock().unwrap().contains_key(&did)
+    }
+
+    /// List sticky peers with their reconnection states.
+    pub fn states(&self) -> Vec<(Did, StickyPeerState)> {
+        let peers = self.peers.lock().unwrap();
+        peers
+            .iter()
+            .map(|(did, peer)| {
+                let state = if peer.connected {
+                    StickyPeerState::Connected
+                } else {
+                    StickyPeerState::Reconnecting {
+                        attempt: peer.attempt,
+                        next_retry_ms: peer.next_retry_ms,
+                    }
+                };
+                (*did, state)
+            })
+            .collect()
+    }
+
+    /// Check transports of sticky peers once, and retry connecting the dropped ones when due.
+    pub async fn check(&self, swarm: &Swarm) {
+        let dids = self
+            .peers
+            .lock()
+            .unwrap()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        for did in dids {
+            let connected = match swarm.get_transport(did) {
+                Some(t) => t.is_connected().await,
+                None => false,
+            };
+
+            let stale = {
+                let mut peers = self.peers.lock().unwrap();
+                let Some(peer) = peers.get_mut(&did) else {
+                    continue;
+                };
+                if connected {
+                    *peer = StickyPeer {
+                        connected: true,
+                        ..Default::default()
+                    };
+                    continue;
+                }
+                peer.connected = false;
+                if get_epoch_ms() < peer.next_retry_ms {
+                    continue;
+                }
+                peer.pending.take()
+            };
+            if let Some(id) = stale {
+                Self::cancel(swarm, id).await;
+            }
+
+            tracing::info!("reconnecting sticky peer {}", did);
+            let result = swarm.connect(did).await;
+            let pending = match result {
+                Ok(t) => Some(t.id),
+                Err(e) => {
+                    tracing::warn!("failed to reconnect sticky peer {}: {:?}", did, e);
+                    None
+                }
+            };
+
+            let cancelled = {
+                let mut peers = self.peers.lock().unwrap();
+                match peers.get_mut(&did) {
+                    Some(peer) => {
+                        peer.attempt += 1;
+                        peer.next_retry_ms =
+                            get_epoch_ms() + self.config.backoff_ms(peer.attempt) as u128;
+                        peer.pending = pending;
+                        None
+                    }
+                    // removed while connecting
+                    None => pending,
+                }
+            };
+            if let Some(id) = cancelled {
+                Self::cancel(swarm, id).await;
+            }
+        }
+    }
+
+    /// Check sticky peers periodically.
+    pub async fn wait(self: Arc<Self>, swarm: Arc<Swarm>) {
+        loop {
+            let timeout = Delay::new(Duration::from_millis(self.config.check_interval_ms)).fuse();
+            pin_mut!(timeout);
+            select! {
+                _ = timeout => self.check(&swarm).await,
+            }
+        }
+    }
+
+    /// Close a transport created by retry if it's still pending.
+    async fn cancel(swarm: &Swarm, id: uuid::Uuid) {
+        if let Ok(Some(t)) = swarm.find_pending_transport(id) {
+            if let Err(e) = t.close().await {
+                tracing::warn!("failed to close pending transport {}: {:?}", id, e);
+            }
+            swarm.pop_pending_transport(id).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod test {
+    use super::*;
+    use crate::prelude::SecretKey;
+    use crate::tests::native::connect;
+    use crate::tests::native::prepare_processor;
+
+    #[test]
+    fn test_backoff() {
+        let config = ReconnectConfig {
+            base_delay_ms: 1000,
+            max_delay_ms: 10000,
+            check_interval_ms: 1000,
+        };
+        for _ in 0..100 {
+            let d1 = config.backoff_ms(1);
+            assert!((500..=1000).contains(&d1));
+            let d3 = config.backoff_ms(3);
+            assert!((2000..=4000).contains(&d3));
+            let d10 = config.backoff_ms(10);
+            assert!((5000..=10000).contains(&d10));
+            let d100 = config.backoff_ms(100);
+            assert!((5000..=10000).contains(&d100));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sticky_peers_retry_and_cancel() {
+        let (p1, path1) = prepare_processor(None).await;
+        let (p2, path2) = prepare_processor(None).await;
+        for p in [&p1, &p2] {
+            let swarm = p.swarm.clone();
+            tokio::spawn(async move { swarm.listen().await });
+        }
+        connect(&p1, &p2).await;
+
+        let sticky = StickyPeers::new(ReconnectConfig::default());
+        let absent: Did = SecretKey::random().address().into();
+        assert!(sticky.add(p2.did()));
+        assert!(sticky.add(absent));
+        assert!(!sticky.add(absent));
+
+        sticky.check(&p1.swarm).await;
+        let states = sticky.states().into_iter().collect::<HashMap<_, _>>();
+        assert_eq!(states[&p2.did()], StickyPeerState::Connected);
+        let StickyPeerState::Reconnecting {
+            attempt,
+            next_retry_ms,
+        } = states[&absent]
+        else {
+            panic!("absent peer should be reconnecting");
+        };
+        assert_eq!(attempt, 1);
+        assert!(next_retry_ms > get_epoch_ms());
+        assert_eq!(p1.swarm.pending_transports().await.unwrap().len(), 1);
+
+        // not retried before the backoff is due
+        sticky.check(&p1.swarm).await;
+        let states = sticky.states().into_iter().collect::<HashMap<_, _>>();
+        assert!(matches!(states[&absent], StickyPeerState::Reconnecting {
+            attempt: 1,
+            ..
+        }));
+
+        // removing cancels the in-flight retry
+        assert!(sticky.remove(&p1.swarm, absent).await);
+        assert!(!sticky.remove(&p1.swarm, absent).await);
+        assert!(p1.swarm.pending_transports().await.unwrap().is_empty());
+        assert_eq!(sticky.states().len(), 1);
+
+        tokio::fs::remove_dir_all(path1).await.unwrap();
+        tokio::fs::remove_dir_all(path2).await.unwrap();
+    }
+}
@@ -1,20 +1,113 @@
 //! Seed and SeedLoader use for getting peers from endpoint.
+use std::str::FromStr;
+
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::error::Error;
+use crate::error::Result;
 use crate::prelude::rings_core::dht::Did;
 
 /// A list contains SeedPeer.
-#[derive(Deserialize, Serialize, Debug)]
+/// A peer with malformed did is rejected when deserializing, use [Seed::validate] to check endpoints.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "RawSeed")]
 pub struct Seed {
     pub peers: Vec<SeedPeer>,
 }
 
 /// SeedPeer contain `Did` and `endpoint`.
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SeedPeer {
     /// an unique identify.
     pub did: Did,
     /// remote client endpoint
     pub endpoint: String,
 }
+
+#[derive(Deserialize)]
+struct RawSeed {
+    peers: Vec<RawSeedPeer>,
+}
+
+#[derive(Deserialize)]
+struct RawSeedPeer {
+    did: String,
+    endpoint: String,
+}
+
+impl TryFrom<RawSeed> for Seed {
+    type Error = Error;
+
+    fn try_from(raw: RawSeed) -> Result<Self> {
+        let peers = raw
+            .peers
+            .into_iter()
+            .enumerate()
+            .map(|(i, peer)| {
+                let did = Did::from_str(&peer.did).map_err(|_| {
+                    Error::InvalidSeed(format!("peers[{}].did: {} is not a did", i, peer.did))
+                })?;
+                Ok(SeedPeer {
+                    did,
+                    endpoint: peer.endpoint,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { peers })
+    }
+}
+
+impl Seed {
+    /// Check that endpoint of each peer is a well-formed http url,
+    /// the error names the index of the first malformed peer.
+    pub fn validate(&self) -> Result<()> {
+        for (i, peer) in self.peers.iter().enumerate() {
+            let invalid =
+                |reason: String| Error::InvalidSeed(format!("peers[{}].endpoint: {}", i, reason));
+            let url = url::Url::parse(&peer.endpoint)
+                .map_err(|e| invalid(format!("{} is not a url, {}", peer.endpoint, e)))?;
+            if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+                return Err(invalid(format!("{} is not a http url", peer.endpoint)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const DID: &str = "0x11e807fcc88dd319270493fb2e822e388fe36ab0";
+
+    #[test]
+    fn test_seed_with_bad_did() {
+        let err = serde_json::from_value::<Seed>(json!({
+            "peers": [
+                {"did": DID, "endpoint": "http://127.0.0.1:50000"},
+                {"did": "0xnotadid", "endpoint": "http://127.0.0.1:50001"},
+            ]
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("peers[1].did"), "{}", err);
+    }
+
+    #[test]
+    fn test_seed_with_bad_endpoint() {
+        let seed = |endpoint: &str| {
+            serde_json::from_value::<Seed>(json!({
+                "peers": [{"did": DID, "endpoint": endpoint}]
+            }))
+            .unwrap()
+        };
+        assert!(seed("https://seed.example.com:50000").validate().is_ok());
+        for endpoint in ["127.0.0.1:50000", "not a url", "ws://127.0.0.1:50000"] {
+            let err = seed(endpoint).validate().unwrap_err();
+            assert!(matches!(err, Error::InvalidSeed(_)));
+            assert!(err.to_string().contains("peers[0].endpoint"), "{}", err);
+        }
+    }
+}
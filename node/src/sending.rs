@@ -0,0 +1,134 @@
+//! Messages being sent by node.
+//!
+//! A message sent in chunks is tracked by [SendingMessages] until it's completed or dropped,
+//! so that it can be listed and cancelled by its tx_id. tx_id of recently completed messages
+//! are kept to tell a completed message from an unknown one.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::consts::COMPLETED_MESSAGES_CAPACITY;
+use crate::prelude::rings_core::prelude::uuid;
+use crate::prelude::rings_rpc::response;
+
+/// Messages being sent in chunks, and tx_id of recently completed ones.
+#[derive(Default)]
+pub(crate) struct SendingMessages {
+    inner: Mutex<SendingMessagesInner>,
+}
+
+#[derive(Default)]
+struct SendingMessagesInner {
+    /// cancel flags of messages being sent
+    sending: HashMap<uuid::Uuid, bool>,
+    /// recently completed messages, oldest first
+    completed: VecDeque<uuid::Uuid>,
+}
+
+impl SendingMessages {
+    fn start(&self, tx_id: uuid::Uuid) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sending.insert(tx_id, false);
+    }
+
+    fn is_cancelled(&self, tx_id: uuid::Uuid) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.sending.get(&tx_id).copied().unwrap_or(true)
+    }
+
+    fn finish(&self, tx_id: uuid::Uuid) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sending.remove(&tx_id);
+    }
+
+    pub(crate) fn complete(&self, tx_id: uuid::Uuid) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sending.remove(&tx_id);
+        inner.completed.push_back(tx_id);
+        if inner.completed.len() > COMPLETED_MESSAGES_CAPACITY {
+            inner.completed.pop_front();
+        }
+    }
+
+    pub(crate) fn cancel(&self, tx_id: uuid::Uuid) -> response::CancelStatus {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(cancelled) = inner.sending.get_mut(&tx_id) {
+            *cancelled = true;
+            response::CancelStatus::Cancelled
+        } else if inner.completed.contains(&tx_id) {
+            response::CancelStatus::Completed
+        } else {
+            response::CancelStatus::Unknown
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<uuid::Uuid> {
+        let inner = self.inner.lock().unwrap();
+        inner.sending.keys().copied().collect()
+    }
+}
+
+/// Registers a message as being sent, and forgets it when dropped before completed,
+/// so the state is freed even if the sending future is cancelled or fails.
+pub(crate) struct SendingGuard<'a> {
+    sending: &'a SendingMessages,
+    tx_id: uuid::Uuid,
+}
+
+impl<'a> SendingGuard<'a> {
+    pub(crate) fn new(sending: &'a SendingMessages, tx_id: uuid::Uuid) -> Self {
+        sending.start(tx_id);
+        Self { sending, tx_id }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.sending.is_cancelled(self.tx_id)
+    }
+
+    pub(crate) fn complete(self) {
+        self.sending.complete(self.tx_id);
+    }
+}
+
+impl Drop for SendingGuard<'_> {
+    fn drop(&mut self) {
+        self.sending.finish(self.tx_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sending_messages_cancel() {
+        let sending = SendingMessages::default();
+        let tx_id = uuid::Uuid::new_v4();
+        assert_eq!(sending.cancel(tx_id), response::CancelStatus::Unknown);
+
+        // cancel a message in progress
+        let guard = SendingGuard::new(&sending, tx_id);
+        assert_eq!(sending.list(), vec![tx_id]);
+        assert!(!guard.is_cancelled());
+        assert_eq!(sending.cancel(tx_id), response::CancelStatus::Cancelled);
+        assert!(guard.is_cancelled());
+
+        // state is freed when sending is aborted
+        drop(guard);
+        assert!(sending.list().is_empty());
+        assert_eq!(sending.cancel(tx_id), response::CancelStatus::Unknown);
+
+        // completed message can not be cancelled
+        let guard = SendingGuard::new(&sending, tx_id);
+        guard.complete();
+        assert!(sending.list().is_empty());
+        assert_eq!(sending.cancel(tx_id), response::CancelStatus::Completed);
+
+        // only recently completed messages are remembered
+        for _ in 0..COMPLETED_MESSAGES_CAPACITY {
+            sending.complete(uuid::Uuid::new_v4());
+        }
+        assert_eq!(sending.cancel(tx_id), response::CancelStatus::Unknown);
+    }
+}
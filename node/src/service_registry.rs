@@ -0,0 +1,601 @@
+//! Registration of services on DHT with TTL.
+//!
+//! Each provider stores its [ServiceRecord] in a Data vnode of its own, keyed by service name
+//! and provider did (see [ServiceProvider::record_did]), and touches a [ServiceProvider] entry
+//! into the Data vnode of service name as an index. Only the provider writes its record vnode,
+//! and the holder of index dedups identical entries when touched, so registrations of
+//! concurrent providers neither overwrite nor duplicate each other. The index is stored
+//! without redundant copies, since a copy only gets the entries touched by its holder.
+//!
+//! Records are renewed by the heartbeat of [ServiceRegistry], and a record whose
+//! `registered_at_ms + ttl_ms` passed is treated as expired. Since expiry only depends on
+//! the record itself, every reader filters it in the same way. The heartbeat also prunes
+//! index entries of providers whose records are expired, so that the index doesn't grow
+//! with providers gone for good.
+//!
+//! A record may carry [ServiceMetadata] of the provider, such as version and tags,
+//! so that consumers can filter providers before connecting to them.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::future::join_all;
+use futures_timer::Delay;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::DATA_REDUNDANT;
+use crate::consts::DEFAULT_SERVICE_HEARTBEAT_CHECK_INTERVAL_MS;
+use crate::consts::DEFAULT_STORAGE_GET_TIMEOUT_MS;
+use crate::consts::SERVICE_INDEX_MAX_FETCHES;
+use crate::consts::SERVICE_INDEX_REDUNDANT;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fetch::VNodeFetcher;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::message::Encoded;
+use crate::prelude::rings_core::message::Encoder;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::rings_rpc::types::ServiceMetadata;
+use crate::prelude::vnode::VNodeType;
+use crate::prelude::vnode::VirtualNode;
+use crate::prelude::ChordStorageInterface;
+use crate::prelude::Swarm;
+
+/// A registration of service provider stored on DHT.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceRecord {
+    /// did of service provider
+    pub did: String,
+    /// epoch time in ms of registration
+    pub registered_at_ms: u64,
+    /// time to live in ms
+    pub ttl_ms: u64,
+    /// metadata of provider
+    #[serde(default, skip_serializing_if = "ServiceMetadata::is_empty")]
+    pub metadata: ServiceMetadata,
+}
+
+impl ServiceRecord {
+    /// Create a record registered now.
+    pub fn new(did: Did, ttl_ms: u64, metadata: ServiceMetadata) -> Self {
+        Self {
+            did: did.to_string(),
+            registered_at_ms: get_epoch_ms() as u64,
+            ttl_ms,
+            metadata,
+        }
+    }
+
+    /// Check if the record is expired at `now_ms`.
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.registered_at_ms.saturating_add(self.ttl_ms) < now_ms
+    }
+
+    /// Encode the record as vnode data.
+    pub fn encode(&self) -> Result<Encoded> {
+        serde_json::to_string(self)?
+            .encode()
+            .map_err(Error::ServiceRegisterError)
+    }
+
+    /// Decode a record from vnode data.
+    /// Legacy data which is a bare did never expires, since its registration time is unknown.
+    /// An index entry of [ServiceProvider] is not a record.
+    pub fn decode(data: &Encoded) -> Option<Self> {
+        let data: String = data.decode().ok()?;
+        if let Ok(record) = serde_json::from_str::<Self>(&data) {
+            return Some(record);
+        }
+        if serde_json::from_str::<ServiceProvider>(&data).is_ok() {
+            return None;
+        }
+        Some(Self {
+            did: data,
+            registered_at_ms: 0,
+            ttl_ms: u64::MAX,
+            metadata: ServiceMetadata::default(),
+        })
+    }
+}
+
+/// Get records of service providers which are not expired at `now_ms` from the vnode of service.
+/// For each did, only its latest record is considered.
+pub fn alive_records(vnode: &VirtualNode, now_ms: u64) -> Vec<ServiceRecord> {
+    let records = vnode
+        .data
+        .iter()
+        .filter_map(ServiceRecord::decode)
+        .collect::<Vec<_>>();
+
+    let mut latest: HashMap<&str, &ServiceRecord> = HashMap::new();
+    for record in records.iter() {
+        let entry = latest.entry(record.did.as_str()).or_insert(record);
+        if record.registered_at_ms >= entry.registered_at_ms {
+            *entry = record;
+        }
+    }
+
+    let mut alive: Vec<ServiceRecord> = vec![];
+    for record in records.iter() {
+        if latest.get(record.did.as_str()) == Some(&record)
+            && !record.is_expired(now_ms)
+            && !alive.iter().any(|r| r.did == record.did)
+        {
+            alive.push(record.clone());
+        }
+    }
+    alive
+}
+
+/// Get dids of service providers which are not expired at `now_ms` from the vnode of service.
+pub fn alive_services(vnode: &VirtualNode, now_ms: u64) -> Vec<String> {
+    alive_records(vnode, now_ms)
+        .into_iter()
+        .map(|r| r.did)
+        .collect()
+}
+
+/// An index entry of service provider, touched into the vnode of service name.
+/// It never changes between renewals, so the holder keeps one entry for each provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceProvider {
+    /// did of service provider
+    pub provider: String,
+}
+
+impl ServiceProvider {
+    /// Create an index entry of provider.
+    pub fn new(did: Did) -> Self {
+        Self {
+            provider: did.to_string(),
+        }
+    }
+
+    /// Did of the vnode which holds the record of provider for service `name`.
+    pub fn record_did(&self, name: &str) -> Result<Did> {
+        VirtualNode::gen_did(&format!("{}:{}", name, self.provider))
+            .map_err(Error::ServiceRegisterError)
+    }
+
+    /// Encode the entry as vnode data.
+    pub fn encode(&self) -> Result<Encoded> {
+        serde_json::to_string(self)?
+            .encode()
+            .map_err(Error::ServiceRegisterError)
+    }
+
+    /// Decode an entry from vnode data, return `None` for records.
+    pub fn decode(data: &Encoded) -> Option<Self> {
+        let data: String = data.decode().ok()?;
+        serde_json::from_str(&data).ok()
+    }
+}
+
+#[derive(Debug)]
+struct RegisteredService {
+    ttl_ms: u64,
+    registered_at_ms: u128,
+    metadata: ServiceMetadata,
+}
+
+/// Services registered by a node, which will be renewed before their TTL lapse.
+#[derive(Debug, Default)]
+pub struct ServiceRegistry {
+    services: Mutex<HashMap<String, RegisteredService>>,
+}
+
+impl ServiceRegistry {
+    /// Register a service with metadata on DHT, and renew it by heartbeat.
+    pub async fn register(
+        &self,
+        swarm: &Swarm,
+        name: &str,
+        ttl_ms: u64,
+        metadata: ServiceMetadata,
+    ) -> Result<()> {
+        Self::touch(swarm, name, ttl_ms, &metadata).await?;
+        self.services.lock().map_err(|_| Error::Lock)?.insert(
+            name.to_string(),
+            RegisteredService {
+                ttl_ms,
+                registered_at_ms: get_epoch_ms(),
+                metadata,
+            },
+        );
+        Ok(())
+    }
+
+    /// Stop renewing a service, return false if it's not registered.
+    /// The registration on DHT will expire after its TTL.
+    pub fn unregister(&self, name: &str) -> bool {
+        self.services
+            .lock()
+            .map(|mut services| services.remove(name).is_some())
+            .unwrap_or(false)
+    }
+
+    /// Track a service without touching DHT, it will be registered by the next heartbeat.
+    #[cfg(feature = "node")]
+    pub(crate) fn restore(&self, name: &str, ttl_ms: u64, metadata: ServiceMetadata) {
+        if let Ok(mut services) = self.services.lock() {
+            services.insert(name.to_string(), RegisteredService {
+                ttl_ms,
+                registered_at_ms: 0,
+                metadata,
+            });
+        }
+    }
+
+    /// List names of registered services with their TTL.
+    pub fn list(&self) -> Vec<(String, u64)> {
+        self.services
+            .lock()
+            .map(|services| {
+                services
+                    .iter()
+                    .map(|(name, s)| (name.clone(), s.ttl_ms))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get metadata of a registered service.
+    pub fn metadata(&self, name: &str) -> Option<ServiceMetadata> {
+        self.services
+            .lock()
+            .ok()?
+            .get(name)
+            .map(|s| s.metadata.clone())
+    }
+
+    /// Overwrite the record vnode of this node with a new record,
+    /// and touch the entry of this node into the index of service.
+    async fn touch(
+        swarm: &Swarm,
+        name: &str,
+        ttl_ms: u64,
+        metadata: &ServiceMetadata,
+    ) -> Result<()> {
+        let provider = ServiceProvider::new(swarm.did());
+        let record = ServiceRecord::new(swarm.did(), ttl_ms, metadata.clone());
+        let vnode = VirtualNode {
+            did: provider.record_did(name)?,
+            data: vec![record.encode()?],
+            kind: VNodeType::Data,
+        };
+        <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_store(swarm, vnode)
+            .await
+            .map_err(Error::ServiceRegisterError)?;
+        <Swarm as ChordStorageInterface<SERVICE_INDEX_REDUNDANT>>::storage_touch_data(
+            swarm,
+            name,
+            provider.encode()?,
+        )
+        .await
+        .map_err(Error::ServiceRegisterError)
+    }
+
+    /// Drop entries of providers whose records are expired from the index of service.
+    /// Renewed entries are touched to the end of index, so the oldest
+    /// [SERVICE_INDEX_MAX_FETCHES] entries are checked. An entry whose record is not fetched
+    /// is kept. The index is stored again if any entry is dropped, an entry touched by another
+    /// provider meanwhile is lost until its next renewal.
+    async fn prune(fetcher: &VNodeFetcher, name: &str) -> Result<()> {
+        let timeout = Duration::from_millis(DEFAULT_STORAGE_GET_TIMEOUT_MS);
+        let rid = VirtualNode::gen_did(name).map_err(Error::ServiceRegisterError)?;
+        let Some(index) = fetcher
+            .get_redundant::<SERVICE_INDEX_REDUNDANT>(rid, timeout)
+            .await?
+        else {
+            return Ok(());
+        };
+        let providers = index
+            .data
+            .iter()
+            .filter_map(|data| ServiceProvider::decode(data).map(|p| (data, p)))
+            .take(SERVICE_INDEX_MAX_FETCHES)
+            .collect::<Vec<_>>();
+        let records =
+            join_all(providers.iter().map(|(_, provider)| async {
+                fetcher.get(provider.record_did(name)?, timeout).await
+            }))
+            .await;
+
+        let now = get_epoch_ms() as u64;
+        let expired = providers
+            .into_iter()
+            .zip(records)
+            .filter_map(|((data, _), record)| match record {
+                Ok(Some(vnode)) if alive_records(&vnode, now).is_empty() => Some(data.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return Ok(());
+        }
+        tracing::debug!(
+            "prune {} expired providers of service {}",
+            expired.len(),
+            name
+        );
+        let pruned = VirtualNode {
+            did: index.did,
+            data: index
+                .data
+                .into_iter()
+                .filter(|data| !expired.contains(data))
+                .collect(),
+            kind: index.kind,
+        };
+        <Swarm as ChordStorageInterface<SERVICE_INDEX_REDUNDANT>>::storage_store(
+            fetcher.swarm(),
+            pruned,
+        )
+        .await
+        .map_err(Error::ServiceRegisterError)
+    }
+
+    /// Renew services which are registered more than a third of TTL ago,
+    /// and prune expired providers from their indexes.
+    pub async fn heartbeat(&self, fetcher: &VNodeFetcher) {
+        let now = get_epoch_ms();
+        let due = match self.services.lock() {
+            Ok(services) => services
+                .iter()
+                .filter(|(_, s)| now.saturating_sub(s.registered_at_ms) * 3 >= s.ttl_ms as u128)
+                .map(|(name, s)| (name.clone(), s.ttl_ms, s.metadata.clone()))
+                .collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        for (name, ttl_ms, metadata) in due {
+            if let Err(e) = Self::touch(fetcher.swarm(), &name, ttl_ms, &metadata).await {
+                tracing::warn!("renew service {} failed: {}", name, e);
+                continue;
+            }
+            if let Ok(mut services) = self.services.lock() {
+                if let Some(s) = services.get_mut(&name) {
+                    s.registered_at_ms = get_epoch_ms();
+                }
+            }
+            if let Err(e) = Self::prune(fetcher, &name).await {
+                tracing::warn!("prune service {} failed: {}", name, e);
+            }
+        }
+    }
+
+    /// Renew services periodically, it never returns.
+    pub async fn wait(self: std::sync::Arc<Self>, fetcher: std::sync::Arc<VNodeFetcher>) {
+        loop {
+            Delay::new(Duration::from_millis(
+                DEFAULT_SERVICE_HEARTBEAT_CHECK_INTERVAL_MS,
+            ))
+            .await;
+            self.heartbeat(&fetcher).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod test {
+    use super::*;
+    use crate::prelude::SecretKey;
+    use crate::processor::Processor;
+    use crate::tests::native::prepare_processor;
+
+    fn service_vnode(records: &[ServiceRecord]) -> VirtualNode {
+        VirtualNode {
+            did: VirtualNode::gen_did("test").unwrap(),
+            data: records.iter().map(|r| r.encode().unwrap()).collect(),
+            kind: crate::prelude::vnode::VNodeType::Data,
+        }
+    }
+
+    #[test]
+    fn test_alive_services() {
+        let did1 = Did::from(SecretKey::random().address()).to_string();
+        let did2 = Did::from(SecretKey::random().address()).to_string();
+        let record = |did: &str, registered_at_ms, ttl_ms| ServiceRecord {
+            did: did.to_string(),
+            registered_at_ms,
+            ttl_ms,
+            metadata: ServiceMetadata::default(),
+        };
+
+        let vnode = service_vnode(&[
+            record(&did1, 1000, 1000),
+            record(&did2, 1000, 5000),
+            record(&did1, 2500, 1000),
+        ]);
+        assert_eq!(alive_services(&vnode, 1500), vec![
+            did2.clone(),
+            did1.clone()
+        ]);
+        assert_eq!(alive_services(&vnode, 3000), vec![
+            did2.clone(),
+            did1.clone()
+        ]);
+        assert_eq!(alive_services(&vnode, 4000), vec![did2.clone()]);
+        assert!(alive_services(&vnode, 7000).is_empty());
+
+        // legacy registration of bare did never expires
+        let mut vnode = service_vnode(&[record(&did1, 1000, 1000)]);
+        vnode.data.push(did2.encode().unwrap());
+        assert_eq!(alive_services(&vnode, u64::MAX), vec![did2]);
+    }
+
+    #[test]
+    fn test_service_provider_entry() {
+        let did = Did::from(SecretKey::random().address());
+        let provider = ServiceProvider::new(did);
+        let record = ServiceRecord::new(did, 1000, ServiceMetadata::default());
+
+        let entry = provider.encode().unwrap();
+        assert_eq!(ServiceProvider::decode(&entry), Some(provider.clone()));
+        assert_eq!(ServiceRecord::decode(&entry), None);
+        assert_eq!(ServiceProvider::decode(&record.encode().unwrap()), None);
+
+        // Index entries are ignored as records.
+        let mut vnode = service_vnode(&[record.clone()]);
+        vnode.data.push(entry);
+        assert_eq!(alive_records(&vnode, 1500), vec![record]);
+
+        // Records of a provider are kept apart for each service.
+        assert_ne!(
+            provider.record_did("a").unwrap(),
+            provider.record_did("b").unwrap()
+        );
+    }
+
+    async fn dids(p: &Processor, name: &str) -> Vec<String> {
+        p.lookup_service(name, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.did)
+            .collect()
+    }
+
+    #[test]
+    fn test_alive_records_metadata() {
+        let did1 = Did::from(SecretKey::random().address()).to_string();
+        let did2 = Did::from(SecretKey::random().address()).to_string();
+        let metadata = ServiceMetadata {
+            version: Some("1.0.0".to_string()),
+            tags: vec!["gpu".to_string()],
+            endpoint: Some("https://example.com".to_string()),
+        };
+        let tagged = ServiceRecord {
+            did: did1,
+            registered_at_ms: 1000,
+            ttl_ms: 1000,
+            metadata,
+        };
+        let untagged = ServiceRecord {
+            did: did2.clone(),
+            registered_at_ms: 1000,
+            ttl_ms: 1000,
+            metadata: ServiceMetadata::default(),
+        };
+
+        // Records without metadata keep the legacy format.
+        let legacy = format!(
+            r#"{{"did":"{}","registered_at_ms":1000,"ttl_ms":1000}}"#,
+            did2
+        );
+        assert_eq!(serde_json::to_string(&untagged).unwrap(), legacy);
+        assert_eq!(
+            ServiceRecord::decode(&legacy.encode().unwrap()),
+            Some(untagged.clone())
+        );
+
+        let vnode = service_vnode(&[tagged.clone(), untagged.clone()]);
+        assert_eq!(alive_records(&vnode, 1500), vec![tagged, untagged]);
+        assert!(alive_records(&vnode, 1500)[0].metadata.has_tag("gpu"));
+        assert!(!alive_records(&vnode, 1500)[1].metadata.has_tag("gpu"));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_service_ttl_lapse() {
+        let (p, path) = prepare_processor(None).await;
+        p.register_service_with_ttl("test_service", 1000)
+            .await
+            .unwrap();
+        assert_eq!(dids(&p, "test_service").await, vec![p.did().to_string()]);
+
+        // Without heartbeat, the registration expires after TTL.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(dids(&p, "test_service").await.is_empty());
+
+        // Heartbeat renews the registration.
+        let fetcher = VNodeFetcher::new(p.swarm.clone());
+        p.service_registry().heartbeat(&fetcher).await;
+        assert_eq!(dids(&p, "test_service").await, vec![p.did().to_string()]);
+
+        // Renewals replace the record of this node, and keep one index entry of it.
+        for _ in 0..10 {
+            ServiceRegistry::touch(&p.swarm, "test_service", 1000, &ServiceMetadata::default())
+                .await
+                .unwrap();
+        }
+        assert_eq!(dids(&p, "test_service").await, vec![p.did().to_string()]);
+        let timeout = Duration::from_millis(1000);
+        let rid = VirtualNode::gen_did("test_service").unwrap();
+        let index = fetcher
+            .get_redundant::<SERVICE_INDEX_REDUNDANT>(rid, timeout)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(index.data, vec![ServiceProvider::new(p.did())
+            .encode()
+            .unwrap()]);
+        let record_did = ServiceProvider::new(p.did())
+            .record_did("test_service")
+            .unwrap();
+        let records = p.storage_get(record_did, timeout).await.unwrap().unwrap();
+        assert_eq!(records.data.len(), 1);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_prune_expired_providers() {
+        let (p, path) = prepare_processor(None).await;
+        let fetcher = VNodeFetcher::new(p.swarm.clone());
+        let name = "test_heartbeat_prune_expired_providers";
+
+        // Providers gone for good leave their expired records and index entries.
+        let gone = (0..3)
+            .map(|_| Did::from(SecretKey::random().address()))
+            .collect::<Vec<_>>();
+        for did in gone.iter() {
+            let provider = ServiceProvider::new(*did);
+            let record = ServiceRecord {
+                did: did.to_string(),
+                registered_at_ms: 1000,
+                ttl_ms: 1000,
+                metadata: ServiceMetadata::default(),
+            };
+            p.storage_store(VirtualNode {
+                did: provider.record_did(name).unwrap(),
+                data: vec![record.encode().unwrap()],
+                kind: VNodeType::Data,
+            })
+            .await
+            .unwrap();
+            <Swarm as ChordStorageInterface<SERVICE_INDEX_REDUNDANT>>::storage_touch_data(
+                &p.swarm,
+                name,
+                provider.encode().unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+        p.register_service_with_ttl(name, 1500).await.unwrap();
+        let timeout = Duration::from_millis(1000);
+        let rid = VirtualNode::gen_did(name).unwrap();
+        let index = fetcher
+            .get_redundant::<SERVICE_INDEX_REDUNDANT>(rid, timeout)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(index.data.len(), 4);
+
+        // The heartbeat renews this node and drops entries of the gone providers.
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        p.service_registry().heartbeat(&fetcher).await;
+        let index = fetcher
+            .get_redundant::<SERVICE_INDEX_REDUNDANT>(rid, timeout)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(index.data, vec![ServiceProvider::new(p.did())
+            .encode()
+            .unwrap()]);
+        assert_eq!(dids(&p, name).await, vec![p.did().to_string()]);
+
+        tokio::fs::remove_dir_all(path).await.unwrap();
+    }
+}
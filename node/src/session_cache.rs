@@ -0,0 +1,110 @@
+//! Sessions which authenticated requests to node.
+//!
+//! A session is cached when it authenticates a request, so that operators can list the sessions
+//! talking to node and revoke them, see [Processor::list_sessions](crate::processor::Processor::list_sessions).
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::session::Session;
+
+/// Sessions which authenticated requests to node, keyed by session_id.
+/// Expired sessions are kept until `prune_expired`, so that operators can see them.
+#[derive(Default)]
+pub(crate) struct SessionCache {
+    inner: RwLock<HashMap<Did, Session>>,
+}
+
+/// A session cached by node, see [Processor::list_sessions](crate::processor::Processor::list_sessions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedSession {
+    /// did of authorizer which signed the session
+    pub authorizer_did: Did,
+    /// did of session key
+    pub session_id: Did,
+    /// negative if the session is expired
+    pub remaining_ttl_ms: i128,
+    /// whether the session is expired
+    pub expired: bool,
+}
+
+impl SessionCache {
+    pub(crate) fn insert(&self, session: &Session) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.insert(session.session_id(), session.clone());
+        }
+    }
+
+    pub(crate) fn remove(&self, session_id: Did) {
+        if let Ok(mut inner) = self.inner.write() {
+            inner.remove(&session_id);
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<CachedSession> {
+        let Ok(inner) = self.inner.read() else {
+            return vec![];
+        };
+        inner
+            .values()
+            .map(|session| CachedSession {
+                authorizer_did: session.authorizer_did(),
+                session_id: session.session_id(),
+                remaining_ttl_ms: session.remaining_ttl_ms(),
+                expired: session.is_expired(),
+            })
+            .collect()
+    }
+
+    /// Drop expired sessions, return the number of dropped ones.
+    pub(crate) fn prune_expired(&self) -> usize {
+        let Ok(mut inner) = self.inner.write() else {
+            return 0;
+        };
+        let len = inner.len();
+        inner.retain(|_, session| !session.is_expired());
+        len - inner.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::SecretKey;
+    use crate::prelude::SessionManager;
+
+    #[test]
+    fn test_session_cache_prune_expired() {
+        let cache = SessionCache::default();
+        let alive = SessionManager::new_with_seckey(&SecretKey::random())
+            .unwrap()
+            .session();
+        // A session created at epoch is long expired, it's not verified by cache.
+        let mut value = serde_json::to_value(
+            SessionManager::new_with_seckey(&SecretKey::random())
+                .unwrap()
+                .session(),
+        )
+        .unwrap();
+        value["ts_ms"] = serde_json::json!(0);
+        let expired: Session = serde_json::from_value(value).unwrap();
+
+        cache.insert(&alive);
+        cache.insert(&expired);
+        // A session is cached once by its session_id.
+        cache.insert(&alive);
+        let mut list = cache.list();
+        list.sort_by_key(|s| s.expired);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].session_id, alive.session_id());
+        assert!(!list[0].expired && list[0].remaining_ttl_ms > 0);
+        assert_eq!(list[1].session_id, expired.session_id());
+        assert!(list[1].expired && list[1].remaining_ttl_ms < 0);
+
+        assert_eq!(cache.prune_expired(), 1);
+        assert_eq!(cache.list().len(), 1);
+        cache.remove(alive.session_id());
+        assert!(cache.list().is_empty());
+    }
+}
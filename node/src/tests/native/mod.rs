@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use crate::prelude::rings_core::ecc::SecretKey;
 use crate::prelude::rings_core::storage::PersistenceStorage;
+use crate::prelude::rings_core::transports::manager::TransportHandshake;
+use crate::prelude::rings_core::transports::Transport;
 use crate::prelude::CallbackFn;
 use crate::prelude::SessionManager;
 use crate::processor::Processor;
@@ -7,6 +11,16 @@ use crate::processor::ProcessorBuilder;
 use crate::processor::ProcessorConfig;
 
 pub async fn prepare_processor(message_callback: Option<CallbackFn>) -> (Processor, String) {
+    let (mut procssor_builder, storage_path) = prepare_processor_builder().await;
+
+    if let Some(callback) = message_callback {
+        procssor_builder = procssor_builder.message_callback(callback);
+    }
+
+    (procssor_builder.build().unwrap(), storage_path)
+}
+
+pub async fn prepare_processor_builder() -> (ProcessorBuilder, String) {
     let key = SecretKey::random();
     let sm = SessionManager::new_with_seckey(&key).unwrap();
 
@@ -23,13 +37,23 @@ pub async fn prepare_processor(message_callback: Option<CallbackFn>) -> (Process
         .await
         .unwrap();
 
-    let mut procssor_builder = ProcessorBuilder::from_config(config)
+    let procssor_builder = ProcessorBuilder::from_config(config)
         .unwrap()
         .storage(storage);
 
-    if let Some(callback) = message_callback {
-        procssor_builder = procssor_builder.message_callback(callback);
-    }
+    (procssor_builder, storage_path)
+}
 
-    (procssor_builder.build().unwrap(), storage_path)
+/// Connect p1 to p2 by a manual handshake, and wait until the transport of p1 is connected.
+pub async fn connect(p1: &Processor, p2: &Processor) -> Arc<Transport> {
+    let (transport, offer) = p1.swarm.create_offer().await.unwrap();
+    let (_, answer) = p2.swarm.answer_offer(offer).await.unwrap();
+    p1.swarm.accept_answer(answer).await.unwrap();
+    transport
+        .connect_success_promise()
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+    transport
 }
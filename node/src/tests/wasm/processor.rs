@@ -157,27 +157,27 @@ async fn test_processor_handshake_and_msg() {
         .unwrap();
 
     console_log!("processor_send_test_text_messages");
-    p1.send_message(p2_addr.as_str(), test_text1.as_bytes())
+    p1.send_message(p2_addr.as_str(), test_text1.as_bytes(), None)
         .await
         .unwrap();
     console_log!("send test_text1 done");
 
-    p2.send_message(p1_addr.as_str(), test_text2.as_bytes())
+    p2.send_message(p1_addr.as_str(), test_text2.as_bytes(), None)
         .await
         .unwrap();
     console_log!("send test_text2 done");
 
-    p2.send_message(p1_addr.as_str(), test_text3.as_bytes())
+    p2.send_message(p1_addr.as_str(), test_text3.as_bytes(), None)
         .await
         .unwrap();
     console_log!("send test_text3 done");
 
-    p1.send_message(p2_addr.as_str(), test_text4.as_bytes())
+    p1.send_message(p2_addr.as_str(), test_text4.as_bytes(), None)
         .await
         .unwrap();
     console_log!("send test_text4 done");
 
-    p2.send_message(p1_addr.as_str(), test_text5.as_bytes())
+    p2.send_message(p1_addr.as_str(), test_text5.as_bytes(), None)
         .await
         .unwrap();
     console_log!("send test_text5 done");
@@ -245,7 +245,7 @@ async fn test_processor_connect_with_did() {
 
     console_log!("connect p1 and p3");
     // p1 create connect with p3's address
-    let peer3 = p1.connect_with_did(p3.did(), true).await.unwrap();
+    let peer3 = p1.connect_with_did(p3.did(), true, None).await.unwrap();
     console_log!("processor_p1_p3_conntected");
     fluvio_wasm_timer::Delay::new(Duration::from_millis(1000))
         .await
@@ -0,0 +1,192 @@
+//! Subscriptions of topics.
+//!
+//! Instead of polling `fetch_messages_of_topic` by each client, subscribers of a topic share one
+//! loop which fetches the vnode periodically, and messages beyond the last seen index are
+//! broadcast to them. The loop is stopped when the last subscriber of topic is dropped.
+#![warn(missing_docs)]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use super::decode_entry;
+use crate::consts::DEFAULT_STORAGE_GET_TIMEOUT_MS;
+use crate::consts::TOPIC_CHANNEL_SIZE;
+use crate::consts::TOPIC_POLL_INTERVAL_MS;
+use crate::fetch::VNodeFetcher;
+use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_rpc::response::TopicMessage;
+use crate::prelude::vnode::VirtualNode;
+
+/// Fetch loops of subscribed topics, keyed by did of topic vnode.
+#[derive(Default)]
+pub struct TopicFeeds {
+    feeds: Mutex<HashMap<Did, Feed>>,
+}
+
+struct Feed {
+    topic: String,
+    sender: broadcast::Sender<TopicMessage>,
+    /// count of messages which are broadcast
+    seen: usize,
+    subscribers: usize,
+    task: Option<JoinHandle<()>>,
+}
+
+/// A subscription of topic, which receives messages from `index` on.
+/// It unsubscribes the topic when dropped.
+pub struct TopicSubscription {
+    feeds: Arc<TopicFeeds>,
+    vid: Did,
+    next_index: usize,
+    backlog: VecDeque<TopicMessage>,
+    receiver: broadcast::Receiver<TopicMessage>,
+}
+
+impl TopicFeeds {
+    /// Subscribe messages of topic from `index` on, the fetch loop of topic is started by
+    /// the first subscriber. Messages seen by the loop before are replayed from `cached` vnode.
+    pub(crate) fn subscribe(
+        self: &Arc<Self>,
+        fetcher: Arc<VNodeFetcher>,
+        topic: &str,
+        vid: Did,
+        index: usize,
+        cached: Option<VirtualNode>,
+    ) -> TopicSubscription {
+        let mut feeds = self.feeds.lock().unwrap();
+        let feed = feeds.entry(vid).or_insert_with(|| Feed {
+            topic: topic.to_string(),
+            sender: broadcast::channel(TOPIC_CHANNEL_SIZE).0,
+            seen: 0,
+            subscribers: 0,
+            task: None,
+        });
+        feed.subscribers += 1;
+        if feed.task.is_none() {
+            feed.task = Some(tokio::spawn(self.clone().poll(fetcher, vid)));
+        }
+
+        let backlog = cached
+            .map(|vnode| messages_of(topic, &vnode, index, feed.seen))
+            .unwrap_or_default();
+        TopicSubscription {
+            feeds: self.clone(),
+            vid,
+            next_index: index,
+            backlog,
+            receiver: feed.sender.subscribe(),
+        }
+    }
+
+    /// Count of subscribers of topic vnode.
+    pub fn subscribers(&self, vid: Did) -> usize {
+        self.feeds
+            .lock()
+            .unwrap()
+            .get(&vid)
+            .map(|feed| feed.subscribers)
+            .unwrap_or(0)
+    }
+
+    fn unsubscribe(&self, vid: Did) {
+        let mut feeds = self.feeds.lock().unwrap();
+        let Some(feed) = feeds.get_mut(&vid) else {
+            return;
+        };
+        feed.subscribers = feed.subscribers.saturating_sub(1);
+        if feed.subscribers == 0 {
+            if let Some(task) = feed.task.take() {
+                task.abort();
+            }
+            feeds.remove(&vid);
+        }
+    }
+
+    async fn poll(self: Arc<Self>, fetcher: Arc<VNodeFetcher>, vid: Did) {
+        let timeout = Duration::from_millis(DEFAULT_STORAGE_GET_TIMEOUT_MS);
+        loop {
+            match fetcher.get(vid, timeout).await {
+                Ok(Some(vnode)) => self.publish(vid, &vnode),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("fetch topic {} failed: {}", vid, e),
+            }
+            tokio::time::sleep(Duration::from_millis(TOPIC_POLL_INTERVAL_MS)).await;
+        }
+    }
+
+    /// Broadcast messages of vnode beyond the last seen index.
+    fn publish(&self, vid: Did, vnode: &VirtualNode) {
+        let mut feeds = self.feeds.lock().unwrap();
+        let Some(feed) = feeds.get_mut(&vid) else {
+            return;
+        };
+        // The topic is truncated, count from its current length.
+        if vnode.data.len() < feed.seen {
+            feed.seen = vnode.data.len();
+        }
+        for msg in messages_of(&feed.topic, vnode, feed.seen, vnode.data.len()) {
+            feed.sender.send(msg).ok();
+        }
+        feed.seen = vnode.data.len();
+    }
+}
+
+impl TopicSubscription {
+    /// Did of topic vnode.
+    pub fn vid(&self) -> Did {
+        self.vid
+    }
+
+    /// Receive the next message of topic, return `None` if the topic is unsubscribed.
+    /// Messages missed by a subscriber which can not keep up are skipped.
+    pub async fn recv(&mut self) -> Option<TopicMessage> {
+        loop {
+            let msg = match self.backlog.pop_front() {
+                Some(msg) => msg,
+                None => match self.receiver.recv().await {
+                    Ok(msg) => msg,
+                    Err(RecvError::Lagged(n)) => {
+                        tracing::warn!("topic subscription of {} lagged {} messages", self.vid, n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            if msg.index < self.next_index {
+                continue;
+            }
+            self.next_index = msg.index + 1;
+            return Some(msg);
+        }
+    }
+}
+
+impl Drop for TopicSubscription {
+    fn drop(&mut self) {
+        self.feeds.unsubscribe(self.vid);
+    }
+}
+
+/// Decode entries of vnode in `from..to` as messages, undecodable entries are skipped.
+fn messages_of(topic: &str, vnode: &VirtualNode, from: usize, to: usize) -> VecDeque<TopicMessage> {
+    vnode
+        .data
+        .iter()
+        .enumerate()
+        .take(to)
+        .skip(from)
+        .filter_map(|(index, v)| {
+            decode_entry(v).map(|entry| TopicMessage {
+                topic: topic.to_string(),
+                index,
+                entry,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+//! Messages of topics.
+//!
+//! A topic is a Data vnode on DHT which entries are appended to, see `publish_message_to_topic`.
+//! An entry is either plain content, or a [SignedTopicEntry] which carries the session and
+//! signature of its author over content, so that readers can tell who wrote it.
+#![warn(missing_docs)]
+#[cfg(feature = "node")]
+mod feed;
+
+#[cfg(feature = "node")]
+pub use feed::TopicFeeds;
+#[cfg(feature = "node")]
+pub use feed::TopicSubscription;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::prelude::rings_core::consts::DEFAULT_TTL_MS;
+use crate::prelude::rings_core::message::Encoded;
+use crate::prelude::rings_core::message::Encoder;
+use crate::prelude::rings_core::message::MessageVerification;
+use crate::prelude::rings_core::utils::get_epoch_ms;
+use crate::prelude::rings_rpc::response::TopicEntry;
+use crate::prelude::SessionManager;
+
+/// An entry of topic signed by session of its author.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTopicEntry {
+    /// content published to topic
+    pub content: String,
+    /// session of author and its signature over content
+    pub verification: MessageVerification,
+}
+
+impl SignedTopicEntry {
+    /// Sign content by session of `session_manager`.
+    pub fn new(session_manager: &SessionManager, content: &str) -> Result<Self> {
+        let ts_ms = get_epoch_ms();
+        let msg = MessageVerification::pack_msg(&content, ts_ms, DEFAULT_TTL_MS)
+            .map_err(Error::VNodeError)?;
+        let sig = session_manager.sign(&msg).map_err(Error::VNodeError)?;
+        Ok(Self {
+            content: content.to_string(),
+            verification: MessageVerification {
+                session: session_manager.session(),
+                ttl_ms: DEFAULT_TTL_MS,
+                ts_ms,
+                sig,
+            },
+        })
+    }
+
+    /// Verify the signature over content, and the session of author.
+    /// An entry is not verified any more once the session of its author expires.
+    pub fn verify(&self) -> bool {
+        self.verification.verify(&self.content)
+    }
+}
+
+impl From<SignedTopicEntry> for TopicEntry {
+    fn from(entry: SignedTopicEntry) -> Self {
+        Self {
+            author_did: Some(entry.verification.session.authorizer_did().to_string()),
+            verified: entry.verify(),
+            content: entry.content,
+        }
+    }
+}
+
+/// Encode content as an entry of topic, which is signed if `session_manager` is provided.
+pub fn encode_entry(content: &str, session_manager: Option<&SessionManager>) -> Result<Encoded> {
+    let data = match session_manager {
+        Some(sm) => serde_json::to_string(&SignedTopicEntry::new(sm, content)?)?,
+        None => content.to_string(),
+    };
+    data.encode().map_err(|_| Error::EncodeError)
+}
+
+/// Decode an entry of topic, entries which are not signed are read as plain content.
+/// Return `None` if the data is not decodable.
+pub fn decode_entry(data: &Encoded) -> Option<TopicEntry> {
+    let raw: String = data.decode().ok()?;
+    Some(match serde_json::from_str::<SignedTopicEntry>(&raw) {
+        Ok(entry) => entry.into(),
+        Err(_) => TopicEntry {
+            author_did: None,
+            content: raw,
+            verified: false,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::SecretKey;
+
+    #[test]
+    fn test_signed_topic_entry() {
+        let sm = SessionManager::new_with_seckey(&SecretKey::random()).unwrap();
+
+        let data = encode_entry("hello", Some(&sm)).unwrap();
+        let entry = decode_entry(&data).unwrap();
+        assert_eq!(entry.content, "hello");
+        assert_eq!(entry.author_did, Some(sm.authorizer_did().to_string()));
+        assert!(entry.verified);
+
+        // Content tampered after signing is not verified.
+        let mut signed: SignedTopicEntry =
+            serde_json::from_str(&data.decode::<String>().unwrap()).unwrap();
+        signed.content = "hacked".to_string();
+        let data = serde_json::to_string(&signed).unwrap().encode().unwrap();
+        let entry = decode_entry(&data).unwrap();
+        assert_eq!(entry.content, "hacked");
+        assert_eq!(entry.author_did, Some(sm.authorizer_did().to_string()));
+        assert!(!entry.verified);
+
+        // Plain content has no author.
+        let data = encode_entry("plain", None).unwrap();
+        let entry = decode_entry(&data).unwrap();
+        assert_eq!(entry.content, "plain");
+        assert_eq!(entry.author_did, None);
+        assert!(!entry.verified);
+    }
+}
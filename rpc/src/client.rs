@@ -36,12 +36,17 @@ impl Client {
     ///
     /// Takes a URL for an HTTP server that will be used as the signaling channel to exchange ICE candidates and SDP with the remote peer.
     /// Returns a transport ID that can be used to refer to this connection in subsequent WebRTC operations.
-    pub async fn connect_peer_via_http(&mut self, http_url: &str) -> Result<String> {
+    /// The handshake is aborted if the connection is not open within `timeout_ms`, or the default timeout of server.
+    pub async fn connect_peer_via_http(
+        &mut self,
+        http_url: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<String> {
         let resp = self
             .client
             .call_method(
                 Method::ConnectPeerViaHttp.as_str(),
-                Params::Array(vec![Value::String(http_url.to_owned())]),
+                Params::Array(vec![Value::String(http_url.to_owned()), json!(timeout_ms)]),
             )
             .await
             .map_err(Error::RpcError)?;
@@ -50,28 +55,66 @@ impl Client {
         Ok(transport_id.to_string())
     }
 
-    /// Attempts to connect to a peer using a seed file located at the specified source path.
-    pub async fn connect_with_seed(&mut self, seeds: &[serde_json::Value]) -> Result<()> {
+    /// Attempts to connect to peers in a seed, each handshake is bounded by `timeout_ms`.
+    pub async fn connect_with_seed(
+        &mut self,
+        seed: &serde_json::Value,
+        timeout_ms: Option<u64>,
+    ) -> Result<()> {
         self.client
             .call_method(
                 Method::ConnectWithSeed.as_str(),
-                Params::Array(seeds.to_vec()),
+                Params::Array(vec![seed.clone(), json!(timeout_ms)]),
             )
             .await
             .map_err(Error::RpcError)?;
         Ok(())
     }
 
+    /// Reconnects to peers of the seed remembered by node which are not connected,
+    /// returns result of each peer in order. Each handshake is bounded by `timeout_ms`.
+    pub async fn reconnect_seed(
+        &self,
+        timeout_ms: Option<u64>,
+    ) -> Result<Vec<response::ConnectResult>> {
+        let resp = self
+            .client
+            .call_method(
+                Method::ReconnectSeed.as_str(),
+                Params::Array(vec![json!(timeout_ms)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Connects to multiple peers via their endpoints, returns result of each peer in order.
+    pub async fn connect_batch(&self, endpoints: &[&str]) -> Result<Vec<response::ConnectResult>> {
+        let resp = self
+            .client
+            .call_method(
+                Method::ConnectBatch.as_str(),
+                Params::Array(endpoints.iter().map(|x| json!(x)).collect()),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
     /// Attempts to connect to a peer using a DID stored in a Distributed Hash Table (DHT).
-    pub async fn connect_with_did(&mut self, did: &str) -> Result<()> {
-        self.client
+    /// The connection is aborted if it's not open within `timeout_ms`, or the default timeout of server.
+    pub async fn connect_with_did(&mut self, did: &str, timeout_ms: Option<u64>) -> Result<String> {
+        let resp = self
+            .client
             .call_method(
                 Method::ConnectWithDid.as_str(),
-                Params::Array(vec![Value::String(did.to_owned())]),
+                Params::Array(vec![Value::String(did.to_owned()), json!(timeout_ms)]),
             )
             .await
             .map_err(Error::RpcError)?;
-        Ok(())
+
+        let transport_id = resp.as_str().ok_or(Error::DecodeError)?;
+        Ok(transport_id.to_string())
     }
 
     /// Lists all connected peers and their status.
@@ -88,6 +131,20 @@ impl Client {
         Ok(peers)
     }
 
+    /// Lists a page of connected peers, filtered by their status.
+    pub async fn list_peers_page(
+        &mut self,
+        options: &types::ListPeersOptions,
+    ) -> Result<response::ListPeersResponse> {
+        let params = serde_json::to_value(options).map_err(|_| Error::EncodeError)?;
+        let resp = self
+            .client
+            .call_method(Method::ListPeers.as_str(), Params::Array(vec![params]))
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
     /// Disconnects from the peer with the specified DID.
     pub async fn disconnect(&mut self, did: &str) -> Result<()> {
         self.client
@@ -98,6 +155,115 @@ impl Client {
         Ok(())
     }
 
+    /// Disconnects from all peers, returns a summary of closed peers and errors.
+    pub async fn disconnect_all(&mut self) -> Result<response::DisconnectSummary> {
+        let resp = self
+            .client
+            .call_method(Method::DisconnectAll.as_str(), Params::Array(vec![]))
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Disconnects from peers matching the filter, returns a summary of closed peers and errors.
+    pub async fn disconnect_where(
+        &mut self,
+        filter: &types::DisconnectFilter,
+    ) -> Result<response::DisconnectSummary> {
+        let params = serde_json::to_value(filter).map_err(|_| Error::EncodeError)?;
+        let resp = self
+            .client
+            .call_method(
+                Method::DisconnectWhere.as_str(),
+                Params::Array(vec![params]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Restarts ice of the connection to the peer with the specified DID,
+    /// the connection is kept while gathering new candidates.
+    pub async fn restart_ice(&mut self, did: &str) -> Result<()> {
+        self.client
+            .call_method(Method::RestartIce.as_str(), Params::Array(vec![json!(did)]))
+            .await
+            .map_err(Error::RpcError)?;
+
+        Ok(())
+    }
+
+    /// Adds a sticky peer with the specified DID, which will be reconnected with backoff
+    /// when its connection is dropped. Returns false if it's already a sticky peer.
+    pub async fn add_sticky_peer(&mut self, did: &str) -> Result<bool> {
+        let resp = self
+            .client
+            .call_method(
+                Method::AddStickyPeer.as_str(),
+                Params::Array(vec![json!(did)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Removes the sticky peer with the specified DID and cancels its reconnection.
+    /// Returns false if it's not a sticky peer.
+    pub async fn remove_sticky_peer(&mut self, did: &str) -> Result<bool> {
+        let resp = self
+            .client
+            .call_method(
+                Method::RemoveStickyPeer.as_str(),
+                Params::Array(vec![json!(did)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Revoke a session by its session_id, return false if it's already revoked.
+    pub async fn revoke_session(&self, session_id: &str) -> Result<bool> {
+        let resp = self
+            .client
+            .call_method(
+                Method::RevokeSession.as_str(),
+                Params::Array(vec![json!(session_id)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Lists sticky peers with their reconnection states.
+    pub async fn list_sticky_peers(&self) -> Result<Vec<response::StickyPeer>> {
+        let resp = self
+            .client
+            .call_method(Method::ListStickyPeers.as_str(), Params::Array(vec![]))
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Leave the ring gracefully, let predecessor and successor re-link.
+    pub async fn node_leave(&self) -> Result<()> {
+        self.client
+            .call_method(Method::NodeLeave.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+
+        Ok(())
+    }
+
+    /// Shutdown the node cleanly, the node stops serving after responding.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.client
+            .call_method(Method::Shutdown.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+
+        Ok(())
+    }
+
     /// Lists all pending transports and their status.
     pub async fn list_pendings(&self) -> Result<Vec<response::TransportInfo>> {
         let resp = self
@@ -157,6 +323,46 @@ impl Client {
         serde_json::from_value(result).map_err(|_| Error::DecodeError)
     }
 
+    /// Broadcasts a custom message to all connected peers, data should be encoded in base64.
+    pub async fn broadcast_message(
+        &self,
+        message_type: u16,
+        data: &str,
+    ) -> Result<response::BroadcastResponse> {
+        let result = self
+            .client
+            .call_method(
+                Method::BroadcastMessage.as_str(),
+                Params::Array(vec![json!(message_type), json!(data)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(result).map_err(|_| Error::DecodeError)
+    }
+
+    /// Cancels a chunked message being sent, the remaining chunks will not be sent.
+    pub async fn cancel_message(&self, tx_id: &str) -> Result<response::CancelStatus> {
+        let result = self
+            .client
+            .call_method(
+                Method::CancelMessage.as_str(),
+                Params::Array(vec![json!(tx_id)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(result).map_err(|_| Error::DecodeError)
+    }
+
+    /// Lists tx_id of messages being sent.
+    pub async fn list_sending_messages(&self) -> Result<Vec<String>> {
+        let result = self
+            .client
+            .call_method(Method::ListSendingMessages.as_str(), Params::Array(vec![]))
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(result).map_err(|_| Error::DecodeError)
+    }
+
     /// Sends an HTTP request message to the specified peer.
     #[allow(clippy::too_many_arguments)]
     pub async fn send_http_request_message(
@@ -206,25 +412,73 @@ impl Client {
         serde_json::from_value(result).map_err(|_| Error::DecodeError)
     }
 
-    /// Registers a new service with the given name.
-    pub async fn register_service(&self, name: &str) -> Result<()> {
+    /// Waits for the delivery receipt of a sent message until timeout in milliseconds.
+    pub async fn wait_for_delivery(
+        &self,
+        tx_id: &str,
+        timeout_ms: u64,
+    ) -> Result<response::DeliveryResponse> {
+        let result = self
+            .client
+            .call_method(
+                Method::WaitForDelivery.as_str(),
+                Params::Array(vec![json!(tx_id), json!(timeout_ms)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(result).map_err(|_| Error::DecodeError)
+    }
+
+    /// Registers a new service with the given name and optional metadata of provider.
+    /// The registration expires after `ttl_ms` unless renewed by the node, default to 60s.
+    pub async fn register_service(
+        &self,
+        name: &str,
+        ttl_ms: Option<u64>,
+        metadata: Option<&types::ServiceMetadata>,
+    ) -> Result<()> {
         self.client
             .call_method(
                 Method::RegisterService.as_str(),
-                Params::Array(vec![json!(name)]),
+                Params::Array(vec![json!(name), json!(ttl_ms), json!(metadata)]),
             )
             .await
             .map_err(Error::RpcError)?;
         Ok(())
     }
 
-    /// Looks up the DIDs of services registered with the given name.
-    pub async fn lookup_service(&self, name: &str) -> Result<Vec<String>> {
+    /// Looks up the providers of services registered with the given name, with their metadata.
+    /// Only providers tagged with `tag` are returned if it's provided.
+    pub async fn lookup_service(
+        &self,
+        name: &str,
+        tag: Option<&str>,
+    ) -> Result<Vec<response::ServiceProvider>> {
         let resp = self
             .client
             .call_method(
                 Method::LookupService.as_str(),
-                Params::Array(vec![json!(name)]),
+                Params::Array(vec![json!(name), json!(tag)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Send text to a provider of service, which is picked by node from alive providers
+    /// tagged with `tag` if provided.
+    pub async fn send_to_service(
+        &self,
+        name: &str,
+        text: &str,
+        tag: Option<&str>,
+    ) -> Result<response::SendToServiceResponse> {
+        let resp = self
+            .client
+            .call_method(
+                Method::SendToService.as_str(),
+                Params::Array(vec![json!(name), json!(text), json!(tag)]),
             )
             .await
             .map_err(Error::RpcError)?;
@@ -244,7 +498,12 @@ impl Client {
         Ok(())
     }
 
-    pub async fn fetch_topic_messages(&self, topic: &str, index: usize) -> Result<Vec<String>> {
+    /// Fetch entries of topic from `index`, with authors of signed entries.
+    pub async fn fetch_topic_messages(
+        &self,
+        topic: &str,
+        index: usize,
+    ) -> Result<Vec<response::TopicEntry>> {
         let resp = self
             .client
             .call_method(
@@ -257,6 +516,37 @@ impl Client {
         serde_json::from_value(resp).map_err(|_| Error::DecodeError)
     }
 
+    /// Fetch at most `count` entries of topic from `index`, only entries in range are transferred.
+    pub async fn fetch_topic_messages_range(
+        &self,
+        topic: &str,
+        index: usize,
+        count: usize,
+    ) -> Result<Vec<response::TopicEntry>> {
+        let resp = self
+            .client
+            .call_method(
+                Method::FetchMessagesOfTopic.as_str(),
+                Params::Array(vec![json!(topic), json!(index), json!(count)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Delete messages of topic, keep the most recent `keep_last` messages.
+    pub async fn delete_topic_messages(&self, topic: &str, keep_last: usize) -> Result<()> {
+        self.client
+            .call_method(
+                Method::DeleteMessagesOfTopic.as_str(),
+                Params::Array(vec![json!(topic), json!(keep_last)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        Ok(())
+    }
+
     /// Query for swarm inspect info.
     pub async fn inspect(&self) -> Result<response::NodeInfo> {
         let resp = self
@@ -266,4 +556,112 @@ impl Client {
             .map_err(Error::RpcError)?;
         serde_json::from_value(resp).map_err(|_| Error::DecodeError)
     }
+
+    /// Query for health of node, it's available without signature.
+    pub async fn health(&self) -> Result<response::HealthInfo> {
+        let resp = self
+            .client
+            .call_method(Method::Health.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Query for metrics of node, such as latency of handshakes.
+    pub async fn metrics(&self) -> Result<response::MetricsInfo> {
+        let resp = self
+            .client
+            .call_method(Method::Metrics.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Query for the did of node and the authorizer of its session.
+    pub async fn whoami(&self) -> Result<response::WhoamiResponse> {
+        let resp = self
+            .client
+            .call_method(Method::Whoami.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Pings the peer `count` times, and measures round-trip time.
+    /// Each ping waits for its pong up to `timeout_ms`, or the default timeout of server.
+    pub async fn ping(
+        &self,
+        did: &str,
+        count: Option<usize>,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::PingResponse> {
+        let resp = self
+            .client
+            .call_method(
+                Method::Ping.as_str(),
+                Params::Array(vec![json!(did), json!(count), json!(timeout_ms)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Requests node info of a remote node.
+    /// It waits for the reply up to `timeout_ms`, or the default timeout of server.
+    pub async fn remote_node_info(
+        &self,
+        did: &str,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::NodeInfo> {
+        let resp = self
+            .client
+            .call_method(
+                Method::RemoteNodeInfo.as_str(),
+                Params::Array(vec![json!(did), json!(timeout_ms)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Walks the ring from node through successors, requesting at most `max_hops` remote nodes.
+    /// It waits for the reply of each node up to `timeout_ms`, or the default timeout of server.
+    pub async fn crawl_ring(
+        &self,
+        max_hops: Option<usize>,
+        timeout_ms: Option<u64>,
+    ) -> Result<response::CrawlRingResponse> {
+        let resp = self
+            .client
+            .call_method(
+                Method::CrawlRing.as_str(),
+                Params::Array(vec![json!(max_hops), json!(timeout_ms)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Predicts the next hop of a message to destination by the routing of node, without sending it.
+    pub async fn route_query(&self, destination: &str) -> Result<response::RouteQueryResponse> {
+        let resp = self
+            .client
+            .call_method(
+                Method::RouteQuery.as_str(),
+                Params::Array(vec![json!(destination)]),
+            )
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
+
+    /// Query for the chord ring from the view of node.
+    pub async fn inspect_dht(&self) -> Result<response::DHTInfo> {
+        let resp = self
+            .client
+            .call_method(Method::InspectDht.as_str(), Params::None)
+            .await
+            .map_err(Error::RpcError)?;
+        serde_json::from_value(resp).map_err(|_| Error::DecodeError)
+    }
 }
@@ -17,4 +17,6 @@ pub enum Error {
     InvalidSignature,
     #[error("Invalid headers.")]
     InvalidHeaders,
+    #[error("Invalid http request: {0}")]
+    InvalidHttpRequest(String),
 }
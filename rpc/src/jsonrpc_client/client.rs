@@ -84,6 +84,11 @@ impl SimpleClient {
                 .map_err(|e| RpcError::Client(format!("Failed to sign request: {}", e)))?;
             let encoded_sig = base64::encode(sig);
             req = req.header("X-SIGNATURE", encoded_sig);
+            // Attach the session, so the server can verify a session other than its own,
+            // and check the scopes granted to it.
+            let session = serde_json::to_vec(&session_manager.session())
+                .map_err(|e| RpcError::Client(format!("Failed to encode session: {}", e)))?;
+            req = req.header("X-SESSION", base64::encode(session));
         }
 
         let resp = req
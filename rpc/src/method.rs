@@ -24,6 +24,18 @@ pub enum Method {
     SendTo,
     /// Disconnect a peer
     Disconnect,
+    /// Disconnect all peers
+    DisconnectAll,
+    /// Disconnect peers matching a filter
+    DisconnectWhere,
+    /// Restart ice of a connected peer
+    RestartIce,
+    /// Add a sticky peer, which will be reconnected when dropped
+    AddStickyPeer,
+    /// Remove a sticky peer and cancel its reconnection
+    RemoveStickyPeer,
+    /// List sticky peers with reconnection states
+    ListStickyPeers,
     /// List all pending connections
     ListPendings,
     /// Close pending connect
@@ -34,6 +46,10 @@ pub enum Method {
     SendHttpRequestMessage,
     /// SendCustomMessage,
     SendCustomMessage,
+    /// Cancel a message being sent
+    CancelMessage,
+    /// List messages being sent
+    ListSendingMessages,
     /// Append data to topic
     PublishMessageToTopic,
     /// Fetch data of topic
@@ -42,10 +58,52 @@ pub enum Method {
     RegisterService,
     /// Lookup service
     LookupService,
+    /// Send message to a provider of service
+    SendToService,
     /// Poll message
     PollMessage,
     /// Retrieve Node info
     NodeInfo,
+    /// Check if node is ready to route traffic
+    Health,
+    /// Metrics of node, such as latency of handshakes
+    Metrics,
+    /// Wait for delivery receipt of a sent message
+    WaitForDelivery,
+    /// Leave the ring gracefully
+    NodeLeave,
+    /// Shutdown the node cleanly, and stop serving
+    Shutdown,
+    /// Connect peers via http, and report result of each peer
+    ConnectBatch,
+    /// Delete messages of topic, keep the most recent ones
+    DeleteMessagesOfTopic,
+    /// Inspect the chord ring from the view of node
+    InspectDht,
+    /// Revoke a session, requests signed by it are rejected
+    RevokeSession,
+    /// Subscribe backend messages, only available on websocket
+    SubscribeMessages,
+    /// Unsubscribe backend messages, only available on websocket
+    UnsubscribeMessages,
+    /// Ping a peer and measure round-trip time
+    Ping,
+    /// Subscribe new messages of topic, only available on websocket
+    SubscribeTopic,
+    /// Unsubscribe messages of topic, only available on websocket
+    UnsubscribeTopic,
+    /// Request node info of a remote node
+    RemoteNodeInfo,
+    /// Walk the ring through successors to discover its topology
+    CrawlRing,
+    /// Connect peers of the remembered seed which are not connected
+    ReconnectSeed,
+    /// Predict the next hop of a message to destination without sending it
+    RouteQuery,
+    /// Identity of node, the did and the authorizer of its session
+    Whoami,
+    /// Send a custom message to all connected peers
+    BroadcastMessage,
 }
 
 impl Method {
@@ -60,22 +118,107 @@ impl Method {
             Method::AnswerOffer => "answerOffer",
             Method::SendTo => "sendTo",
             Method::Disconnect => "disconnect",
+            Method::DisconnectAll => "disconnectAll",
+            Method::DisconnectWhere => "disconnectWhere",
+            Method::RestartIce => "restartIce",
+            Method::AddStickyPeer => "addStickyPeer",
+            Method::RemoveStickyPeer => "removeStickyPeer",
+            Method::ListStickyPeers => "listStickyPeers",
             Method::AcceptAnswer => "acceptAnswer",
             Method::ListPendings => "listPendings",
             Method::ClosePendingTransport => "closePendingTransport",
             Method::SendSimpleText => "sendSimpleText",
             Method::SendHttpRequestMessage => "sendHttpRequestMessage",
             Method::SendCustomMessage => "sendCustomMessage",
+            Method::CancelMessage => "cancelMessage",
+            Method::ListSendingMessages => "listSendingMessages",
             Method::PublishMessageToTopic => "publishMessageToTopic",
             Method::FetchMessagesOfTopic => "fetchMessagesOfTopic",
             Method::RegisterService => "registerService",
             Method::LookupService => "lookupService",
+            Method::SendToService => "sendToService",
             Method::PollMessage => "pollMessage",
             Method::NodeInfo => "nodeInfo",
+            Method::Health => "health",
+            Method::Metrics => "metrics",
+            Method::WaitForDelivery => "waitForDelivery",
+            Method::NodeLeave => "nodeLeave",
+            Method::Shutdown => "shutdown",
+            Method::ConnectBatch => "connectBatch",
+            Method::DeleteMessagesOfTopic => "deleteMessagesOfTopic",
+            Method::InspectDht => "inspectDht",
+            Method::RevokeSession => "revokeSession",
+            Method::SubscribeMessages => "subscribeMessages",
+            Method::UnsubscribeMessages => "unsubscribeMessages",
+            Method::Ping => "ping",
+            Method::SubscribeTopic => "subscribeTopic",
+            Method::UnsubscribeTopic => "unsubscribeTopic",
+            Method::RemoteNodeInfo => "remoteNodeInfo",
+            Method::CrawlRing => "crawlRing",
+            Method::ReconnectSeed => "reconnectSeed",
+            Method::RouteQuery => "routeQuery",
+            Method::Whoami => "whoami",
+            Method::BroadcastMessage => "broadcastMessage",
         }
     }
 }
 
+impl Method {
+    /// Return the scope of session required to call method, `None` if it's public.
+    /// A session without scopes is granted all of them.
+    pub fn scope(&self) -> Option<&'static str> {
+        Some(match self {
+            Method::NodeInfo | Method::Health | Method::AnswerOffer => return None,
+            Method::ConnectPeerViaHttp
+            | Method::ConnectWithDid
+            | Method::ConnectWithSeed
+            | Method::ConnectBatch
+            | Method::ReconnectSeed
+            | Method::CreateOffer
+            | Method::AcceptAnswer
+            | Method::RestartIce
+            | Method::AddStickyPeer => "connect",
+            Method::Disconnect
+            | Method::DisconnectAll
+            | Method::DisconnectWhere
+            | Method::RemoveStickyPeer
+            | Method::ClosePendingTransport => "disconnect",
+            Method::SendTo
+            | Method::SendSimpleText
+            | Method::SendHttpRequestMessage
+            | Method::SendCustomMessage
+            | Method::BroadcastMessage
+            | Method::CancelMessage
+            | Method::PublishMessageToTopic
+            | Method::SendToService
+            | Method::Ping
+            | Method::RemoteNodeInfo
+            | Method::CrawlRing => "send",
+            Method::ListPeers
+            | Method::ListStickyPeers
+            | Method::ListPendings
+            | Method::ListSendingMessages
+            | Method::FetchMessagesOfTopic
+            | Method::LookupService
+            | Method::PollMessage
+            | Method::WaitForDelivery
+            | Method::Metrics
+            | Method::InspectDht
+            | Method::RouteQuery
+            | Method::Whoami
+            | Method::SubscribeMessages
+            | Method::UnsubscribeMessages
+            | Method::SubscribeTopic
+            | Method::UnsubscribeTopic => "read",
+            Method::RegisterService
+            | Method::DeleteMessagesOfTopic
+            | Method::NodeLeave
+            | Method::Shutdown
+            | Method::RevokeSession => "admin",
+        })
+    }
+}
+
 impl ToString for Method {
     fn to_string(&self) -> String {
         self.as_str().to_owned()
@@ -95,18 +238,47 @@ impl TryFrom<&str> for Method {
             "answerOffer" => Self::AnswerOffer,
             "sendTo" => Self::SendTo,
             "disconnect" => Self::Disconnect,
+            "disconnectAll" => Self::DisconnectAll,
+            "disconnectWhere" => Self::DisconnectWhere,
+            "restartIce" => Self::RestartIce,
+            "addStickyPeer" => Self::AddStickyPeer,
+            "removeStickyPeer" => Self::RemoveStickyPeer,
+            "listStickyPeers" => Self::ListStickyPeers,
             "acceptAnswer" => Self::AcceptAnswer,
             "listPendings" => Self::ListPendings,
             "closePendingTransport" => Self::ClosePendingTransport,
             "sendSimpleText" => Self::SendSimpleText,
             "sendHttpRequestMessage" => Self::SendHttpRequestMessage,
             "sendCustomMessage" => Self::SendCustomMessage,
+            "cancelMessage" => Self::CancelMessage,
+            "listSendingMessages" => Self::ListSendingMessages,
             "publishMessageToTopic" => Method::PublishMessageToTopic,
             "fetchMessagesOfTopic" => Method::FetchMessagesOfTopic,
             "registerService" => Method::RegisterService,
             "lookupService" => Method::LookupService,
+            "sendToService" => Method::SendToService,
             "pollMessage" => Method::PollMessage,
             "nodeInfo" => Method::NodeInfo,
+            "health" => Method::Health,
+            "metrics" => Method::Metrics,
+            "waitForDelivery" => Method::WaitForDelivery,
+            "nodeLeave" => Method::NodeLeave,
+            "shutdown" => Method::Shutdown,
+            "connectBatch" => Method::ConnectBatch,
+            "deleteMessagesOfTopic" => Method::DeleteMessagesOfTopic,
+            "inspectDht" => Method::InspectDht,
+            "revokeSession" => Method::RevokeSession,
+            "subscribeMessages" => Method::SubscribeMessages,
+            "unsubscribeMessages" => Method::UnsubscribeMessages,
+            "ping" => Method::Ping,
+            "subscribeTopic" => Method::SubscribeTopic,
+            "unsubscribeTopic" => Method::UnsubscribeTopic,
+            "remoteNodeInfo" => Method::RemoteNodeInfo,
+            "crawlRing" => Method::CrawlRing,
+            "reconnectSeed" => Method::ReconnectSeed,
+            "routeQuery" => Method::RouteQuery,
+            "whoami" => Method::Whoami,
+            "broadcastMessage" => Method::BroadcastMessage,
             _ => return Err(Error::InvalidMethod),
         })
     }
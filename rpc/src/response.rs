@@ -1,4 +1,5 @@
 //! A JSONRPC response.
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use serde::de::DeserializeOwned;
@@ -9,8 +10,11 @@ use serde_json::Value as JsonValue;
 use crate::error::Error;
 use crate::error::Result;
 use crate::prelude::rings_core::dht::Did;
+use crate::prelude::rings_core::inspect::DHTInspect;
 use crate::prelude::rings_core::inspect::SwarmInspect;
 use crate::prelude::rings_core::transports::Transport;
+use crate::prelude::rings_core::types::ice_transport::IceTransportInterface;
+use crate::types::ServiceMetadata;
 
 /// Peer contains transport address and state information.
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -21,6 +25,18 @@ pub struct Peer {
     pub transport_id: String,
     /// transport ice connection state
     pub state: String,
+    /// ice gathering state of transport, `new`, `gathering` or `complete`
+    #[serde(default)]
+    pub gathering_state: Option<String>,
+    /// type of local candidate in the selected pair, e.g. `host`, `srflx`, `relay`
+    #[serde(default)]
+    pub candidate_type: Option<String>,
+    /// quality score of connection in `0.0..=1.0`, higher is better, `0.0` if not sampled yet
+    #[serde(default)]
+    pub quality: f32,
+    /// `inbound` if remote peer initiated the transport, `outbound` if this node did
+    #[serde(default)]
+    pub direction: Option<String>,
 }
 
 impl Peer {
@@ -39,15 +55,102 @@ impl From<(Did, &Arc<Transport>, Option<String>)> for Peer {
             did: did.to_string(),
             transport_id: transport.id.to_string(),
             state: state.unwrap_or_else(|| "Unknown".to_owned()),
+            gathering_state: None,
+            candidate_type: None,
+            quality: 0.0,
+            direction: transport.direction().map(|d| d.to_string()),
         }
     }
 }
 
+/// A page of peers with the total count of matched peers.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ListPeersResponse {
+    /// peers in this page
+    pub peers: Vec<Peer>,
+    /// total count of matched peers
+    pub total: usize,
+}
+
+/// Summary of disconnecting peers in bulk.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct DisconnectSummary {
+    /// count of closed peers
+    pub closed: usize,
+    /// peers failed to be closed
+    pub errors: Vec<DisconnectError>,
+}
+
+/// A peer failed to be closed in bulk.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DisconnectError {
+    /// did of peer
+    pub did: String,
+    /// error message
+    pub error: String,
+}
+
+/// Result of connecting a peer in a batch.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ConnectResult {
+    /// endpoint of remote rings-node jsonrpc server
+    pub endpoint: String,
+    /// `true` if connected
+    pub ok: bool,
+    /// transport id if connected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport_id: Option<String>,
+    /// error message if failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of broadcasting a message to connected peers.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct BroadcastResponse {
+    /// result of each peer the message is sent to, keyed by did of peer
+    pub results: HashMap<String, BroadcastResult>,
+    /// dids of peers skipped since their data channels are not open
+    pub skipped: Vec<String>,
+}
+
+/// Result of sending a broadcast message to a peer.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BroadcastResult {
+    /// `true` if sent
+    pub ok: bool,
+    /// tx_id of message if sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_id: Option<String>,
+    /// error message if failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Sticky peer and its reconnection state.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct StickyPeer {
+    /// did of peer
+    pub did: String,
+    /// `connected` or `reconnecting`
+    pub state: String,
+    /// count of retries have been made
+    pub attempt: u32,
+    /// milliseconds until the next retry, 0 if connected or due
+    pub next_retry_in_ms: u64,
+}
+
 /// Base Transport Info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransportInfo {
     pub transport_id: String,
     pub state: String,
+    /// ice gathering state of transport, `new`, `gathering` or `complete`
+    #[serde(default)]
+    pub gathering_state: Option<String>,
+    /// `inbound` if remote peer initiated the transport, `outbound` if this node did
+    #[serde(default)]
+    pub direction: Option<String>,
 }
 
 impl TransportInfo {
@@ -55,13 +158,18 @@ impl TransportInfo {
         Self {
             transport_id,
             state: state.unwrap_or_else(|| "Unknown".to_owned()),
+            gathering_state: None,
+            direction: None,
         }
     }
 }
 
 impl From<(&Arc<Transport>, Option<String>)> for TransportInfo {
     fn from((transport, state): (&Arc<Transport>, Option<String>)) -> Self {
-        Self::new(transport.id.to_string(), state)
+        Self {
+            direction: transport.direction().map(|d| d.to_string()),
+            ..Self::new(transport.id.to_string(), state)
+        }
     }
 }
 
@@ -91,22 +199,359 @@ impl From<(u16, String)> for CustomBackendMessage {
     }
 }
 
+/// Backend message pushed to subscribers, with did of sender.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IncomingMessage {
+    /// did of sender
+    pub from: String,
+    /// message type of backend message
+    pub message_type: u16,
+    /// base64 encoded data
+    pub data: String,
+}
+
+/// Entry of topic, with its author if it's signed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TopicEntry {
+    /// did of author, `None` if the entry is not signed
+    pub author_did: Option<String>,
+    /// content published to topic
+    pub content: String,
+    /// signature of author over content is verified
+    pub verified: bool,
+}
+
+/// Message of topic pushed to subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TopicMessage {
+    /// topic of message
+    pub topic: String,
+    /// index of message in topic
+    pub index: usize,
+    /// entry published to topic
+    #[serde(flatten)]
+    pub entry: TopicEntry,
+}
+
+/// Response of http request relayed by a remote node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// status code returned by upstream
+    pub status: u16,
+    /// headers returned by upstream
+    pub headers: HashMap<String, String>,
+    /// base64 encoded body
+    pub body: Option<String>,
+}
+
+impl HttpResponse {
+    /// Status code of response, `None` if upstream returned an invalid one.
+    pub fn status_code(&self) -> Option<http::StatusCode> {
+        http::StatusCode::from_u16(self.status).ok()
+    }
+
+    /// `true` if status is in 200-299.
+    pub fn is_success(&self) -> bool {
+        self.status_code().map_or(false, |s| s.is_success())
+    }
+
+    /// Decode base64 body.
+    pub fn body_bytes(&self) -> Result<Option<Vec<u8>>> {
+        self.body
+            .as_ref()
+            .map(|b| base64::decode(b).map_err(|_| Error::DecodeError))
+            .transpose()
+    }
+}
+
+impl From<(u16, HashMap<String, String>, Option<&[u8]>)> for HttpResponse {
+    fn from((status, headers, body): (u16, HashMap<String, String>, Option<&[u8]>)) -> Self {
+        Self {
+            status,
+            headers,
+            body: body.map(base64::encode),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SendMessageResponse {
     pub tx_id: String,
+    /// did of the next hop which the message is handed to, if it's reported by node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop: Option<String>,
+    /// `true` if the message is relayed by next hop, `false` if it's sent to destination directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayed: Option<bool>,
+}
+
+impl SendMessageResponse {
+    /// Response of a message handed to `next_hop`, which is relayed if it's not destination.
+    pub fn with_route(tx_id: String, next_hop: String, relayed: bool) -> Self {
+        Self {
+            tx_id,
+            next_hop: Some(next_hop),
+            relayed: Some(relayed),
+        }
+    }
 }
 
 impl From<String> for SendMessageResponse {
     fn from(v: String) -> Self {
-        Self { tx_id: v }
+        Self {
+            tx_id: v,
+            next_hop: None,
+            relayed: None,
+        }
     }
 }
 
+/// A provider of service with its registration metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceProvider {
+    /// did of the provider
+    pub did: String,
+    /// metadata attached to registration
+    #[serde(flatten)]
+    pub metadata: ServiceMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendToServiceResponse {
+    /// did of the provider which the message is sent to
+    pub did: String,
+    pub tx_id: String,
+}
+
+/// Delivery status of a sent message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryResponse {
+    /// tx_id of the message
+    pub tx_id: String,
+    /// `true` if delivered, `false` if timed out
+    pub delivered: bool,
+}
+
+/// Round-trip time of pings to a peer, in milliseconds.
+/// `min_ms`, `avg_ms` and `max_ms` are `None` if no pong is received.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PingResponse {
+    /// did of peer
+    pub did: String,
+    /// count of pings sent
+    pub sent: usize,
+    /// count of pongs received in time
+    pub received: usize,
+    /// min round-trip time
+    pub min_ms: Option<u64>,
+    /// average round-trip time
+    pub avg_ms: Option<u64>,
+    /// max round-trip time
+    pub max_ms: Option<u64>,
+}
+
+/// A node visited by walking the ring, with its neighbors.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RingNode {
+    /// did of node
+    pub did: String,
+    /// successors of node, the closest first
+    pub successors: Vec<String>,
+    /// predecessor of node
+    pub predecessor: Option<String>,
+    /// dids of peers connected to node
+    pub peers: Vec<String>,
+}
+
+impl From<&NodeInfo> for RingNode {
+    fn from(info: &NodeInfo) -> Self {
+        Self {
+            did: info.swarm.dht.did.clone(),
+            successors: info.swarm.dht.successors.clone(),
+            predecessor: info.swarm.dht.predecessor.clone(),
+            peers: info
+                .swarm
+                .transports
+                .iter()
+                .map(|t| t.did.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Topology of ring found by walking successors from a node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrawlRingResponse {
+    /// visited nodes in the order of walking, starting from the node crawling
+    pub nodes: Vec<RingNode>,
+    /// `true` if the walk returned to a visited node, so the ring is fully walked
+    pub complete: bool,
+    /// did of the successor which didn't reply, the walk stopped at it
+    pub unreachable: Option<String>,
+}
+
+/// Predicted route of a message to destination, it's not sent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteQueryResponse {
+    /// did of destination
+    pub destination: String,
+    /// did of the next hop which a message to destination would be handed to
+    pub next_hop: String,
+    /// `true` if the message would be relayed by next hop
+    pub relayed: bool,
+    /// `true` if a transport to next hop is registered
+    pub connected: bool,
+}
+
+impl PingResponse {
+    /// Summarize round-trip times of received pongs.
+    pub fn new(did: String, sent: usize, rtts: &[u64]) -> Self {
+        Self {
+            did,
+            sent,
+            received: rtts.len(),
+            min_ms: rtts.iter().min().copied(),
+            avg_ms: (!rtts.is_empty()).then(|| rtts.iter().sum::<u64>() / rtts.len() as u64),
+            max_ms: rtts.iter().max().copied(),
+        }
+    }
+}
+
+/// Result of cancelling a message being sent.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelStatus {
+    /// the message is in progress, further chunks will not be sent
+    Cancelled,
+    /// the message is already fully sent
+    Completed,
+    /// no message is known by the tx_id
+    Unknown,
+}
+
+/// Version of the json shape of [DHTInfo], bumped on breaking changes.
+pub const DHT_INFO_VERSION: u32 = 1;
+
+/// Snapshot of the chord ring from the view of a node.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DHTInfo {
+    /// version of json shape, see [DHT_INFO_VERSION]
+    pub version: u32,
+    /// did of the node
+    pub did: String,
+    /// successor list, ordered by distance from the node
+    pub successors: Vec<String>,
+    /// predecessor of the node
+    pub predecessor: Option<String>,
+    /// finger table, consecutive fingers of same did are merged into a range
+    pub finger_table: Vec<FingerRange>,
+}
+
+/// Range of fingers point to a same did.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FingerRange {
+    /// did of fingers, `None` if fingers are empty
+    pub did: Option<String>,
+    /// index of first finger in range
+    pub start: usize,
+    /// index of last finger in range
+    pub end: usize,
+}
+
+impl From<DHTInspect> for DHTInfo {
+    fn from(v: DHTInspect) -> Self {
+        Self {
+            version: DHT_INFO_VERSION,
+            did: v.did,
+            successors: v.successors,
+            predecessor: v.predecessor,
+            finger_table: v
+                .finger_table
+                .into_iter()
+                .map(|(did, start, end)| FingerRange { did, start, end })
+                .collect(),
+        }
+    }
+}
+
+/// Health of node, it's ready to route traffic when listening and joined the ring.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthInfo {
+    /// the node is listening messages of swarm
+    pub listening: bool,
+    /// count of connected peers
+    pub peers: usize,
+    /// the node has successors other than itself
+    pub has_successor: bool,
+    /// the node has a predecessor other than itself
+    pub has_predecessor: bool,
+    /// a round of stabilization is finished, and both sides of the node are linked
+    pub stabilized: bool,
+    /// the node is listening and has a successor to complete lookups
+    pub ready: bool,
+}
+
+/// Summary of latencies in ms, percentiles are 0 if there is no sample.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    /// count of samples
+    pub count: u64,
+    /// median of latencies
+    pub p50_ms: u64,
+    /// 95th percentile of latencies
+    pub p95_ms: u64,
+    /// max of latencies
+    pub max_ms: u64,
+}
+
+/// Metrics of node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsInfo {
+    /// latency from offer or answer creation to data channel open of handshakes
+    pub handshake: LatencySummary,
+    /// count and limit of transports in handshake
+    #[serde(default)]
+    pub pending_transports: PendingTransportsSummary,
+    /// count of sessions rejected by local clock when authenticating requests
+    #[serde(default)]
+    pub rejected_sessions: RejectedSessionsSummary,
+}
+
+/// Count of sessions rejected by local clock.
+/// Many not yet valid sessions usually mean the clock of node or its clients is skewed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedSessionsSummary {
+    /// count of sessions expired beyond the tolerance of clock skew
+    pub expired: u64,
+    /// count of sessions created ahead of local clock beyond the tolerance of clock skew
+    pub not_yet_valid: u64,
+}
+
+/// Count and limit of pending transports.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTransportsSummary {
+    /// count of transports in handshake
+    pub count: usize,
+    /// max count of transports in handshake, the oldest one is evicted when exceeded
+    pub max: usize,
+}
+
+/// Identity of node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhoamiResponse {
+    /// did of node
+    pub did: String,
+    /// did of authorizer which signed the session of node
+    pub authorizer_did: String,
+    /// remaining ttl of the session, negative if it's expired
+    pub session_expires_in_ms: i64,
+}
+
 /// NodeInfo struct
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeInfo {
     /// node version
     pub version: String,
-    /// swarm inspect info
+    /// swarm inspect info, the position of node in chord ring is included in `swarm.dht`
     pub swarm: SwarmInspect,
 }
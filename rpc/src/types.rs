@@ -4,6 +4,9 @@ use std::time::Duration;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::error::Error;
+use crate::error::Result;
+
 /// Timeout in milliseconds.
 #[derive(Deserialize, Debug, Serialize, Clone)]
 pub struct Timeout(u64);
@@ -117,6 +120,33 @@ impl HttpRequest {
         }
     }
 
+    /// Validate the request before relaying it to a hidden service.
+    /// It rejects invalid method tokens, header names and values with non-ASCII or control
+    /// characters (such as CRLF injection), and paths which are not valid URI path and query.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(Error::InvalidHttpRequest("empty service name".to_string()));
+        }
+        http::Method::from_bytes(self.method.as_bytes())
+            .map_err(|_| Error::InvalidHttpRequest(format!("invalid method: {}", self.method)))?;
+        for (name, value) in self.headers.iter() {
+            http::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| Error::InvalidHttpRequest(format!("invalid header name: {}", name)))?;
+            if !value.is_ascii() || http::header::HeaderValue::from_str(value).is_err() {
+                return Err(Error::InvalidHttpRequest(format!(
+                    "invalid value of header: {}",
+                    name
+                )));
+            }
+        }
+        if !self.path.is_empty() {
+            self.path
+                .parse::<http::uri::PathAndQuery>()
+                .map_err(|_| Error::InvalidHttpRequest(format!("invalid path: {}", self.path)))?;
+        }
+        Ok(())
+    }
+
     /// new `GET` HttpRequest
     /// - `name`
     /// - `method`
@@ -137,3 +167,79 @@ impl HttpRequest {
         Self::new(name, http::Method::GET, url, timeout, headers, body)
     }
 }
+
+/// Filter of peers to disconnect, a peer is matched if it matches all given fields.
+/// An empty filter matches all peers.
+/// - `states`: peers in given ice connection states, like `failed`, `disconnected`
+/// - `dids`: peers with given dids
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DisconnectFilter {
+    /// states
+    #[serde(default)]
+    pub states: Option<Vec<String>>,
+    /// dids
+    #[serde(default)]
+    pub dids: Option<Vec<String>>,
+}
+
+impl DisconnectFilter {
+    /// Check if a peer is matched by the filter.
+    pub fn matches(&self, peer: &crate::response::Peer) -> bool {
+        let state_matched = self.states.as_ref().map_or(true, |states| {
+            states.iter().any(|s| s.eq_ignore_ascii_case(&peer.state))
+        });
+        let did_matched = self.dids.as_ref().map_or(true, |dids| {
+            dids.iter().any(|d| {
+                d.trim_start_matches("0x")
+                    .eq_ignore_ascii_case(peer.did.trim_start_matches("0x"))
+            })
+        });
+        state_matched && did_matched
+    }
+}
+
+/// Options of listing peers with pagination and filtering.
+/// - `offset`: skip first n peers
+/// - `limit`: max count of peers in a page, no limit if not provided
+/// - `states`: only keep peers in given ice connection states, like `connected`, `checking`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ListPeersOptions {
+    /// offset
+    #[serde(default)]
+    pub offset: u32,
+    /// limit
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// states
+    #[serde(default)]
+    pub states: Option<Vec<String>>,
+}
+
+/// Metadata attached to a service registration.
+/// - `version`: version of service provided
+/// - `tags`: capabilities of provider, consumers can filter providers by them
+/// - `endpoint`: human-readable endpoint of service
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServiceMetadata {
+    /// version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// tags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+impl ServiceMetadata {
+    /// Check if no metadata is attached.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Check if the provider is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}